@@ -0,0 +1,733 @@
+//! Optional ownership, permission and extended attribute metadata for ward
+//! entries.
+//!
+//! By default a ward entry records only what identifies content (checksum,
+//! mtime and size, or a symlink target), so a `chmod 4755` or a `chown` goes
+//! unnoticed. With `--track mode,owner` each entry also records its
+//! permission bits (`mode`) and its owner and group (`uid`, `gid`), and with
+//! `--track acl,xattr` a digest of each of its extended attributes. With
+//! `--track links`, files record their hard link count and which other files
+//! of the tree they are hard links of (see `Links`). Which
+//! attributes are tracked is a per-directory setting stored in the ward
+//! file's metadata; see `Track`.
+//!
+//! Only the attributes an entry actually records are compared, so turning
+//! tracking on never reports existing entries as changed, and turning it off
+//! never reports anything either.
+
+use crate::checksum::{Checksum, HashAlgorithm, checksum_bytes};
+use crate::util::dir_handle::{DirHandle, EntryStat};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::OsStr;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Extended attributes holding POSIX ACLs on Linux, recorded by `acl`.
+pub const ACL_XATTRS: [&str; 2] = ["system.posix_acl_access", "system.posix_acl_default"];
+
+/// Which extended attributes ward entries record.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum XattrSelection {
+    #[default]
+    None,
+    All,
+    Named(BTreeSet<String>),
+}
+
+/// Which optional attributes ward entries record.
+///
+/// Stored in a ward file's metadata as a list of names, e.g.
+/// `track = ["mode", "owner", "xattr:security.capability"]`.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(try_from = "Vec<String>", into = "Vec<String>")]
+pub struct Track {
+    /// Permission bits, including setuid, setgid and sticky.
+    pub mode: bool,
+    /// Owner and group ids.
+    pub owner: bool,
+    /// POSIX ACLs, i.e. the `ACL_XATTRS` extended attributes.
+    pub acl: bool,
+    /// Other extended attributes: `xattr` for all of them, `xattr:<name>`
+    /// for a single one.
+    pub xattrs: XattrSelection,
+    /// Hard link count and link group of files.
+    pub links: bool,
+}
+
+impl Track {
+    const MODE: &'static str = "mode";
+    const OWNER: &'static str = "owner";
+    const ACL: &'static str = "acl";
+    const XATTR: &'static str = "xattr";
+    const LINKS: &'static str = "links";
+    const NONE: &'static str = "none";
+
+    pub fn is_empty(&self) -> bool {
+        !self.mode && !self.owner && !self.records_xattrs() && !self.links
+    }
+
+    /// Whether entries record a (possibly empty) set of extended attributes.
+    pub fn records_xattrs(&self) -> bool {
+        self.acl || self.xattrs != XattrSelection::None
+    }
+
+    /// Whether the extended attribute `name` is recorded.
+    pub fn selects_xattr(&self, name: &str) -> bool {
+        (self.acl && ACL_XATTRS.contains(&name))
+            || match &self.xattrs {
+                XattrSelection::None => false,
+                XattrSelection::All => true,
+                XattrSelection::Named(names) => names.contains(name),
+            }
+    }
+
+    /// Names of the tracked attributes, as written in `--track` and in the
+    /// ward file's `track` list.
+    pub fn names(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        if self.mode {
+            names.push(Self::MODE.to_string());
+        }
+        if self.owner {
+            names.push(Self::OWNER.to_string());
+        }
+        if self.acl {
+            names.push(Self::ACL.to_string());
+        }
+        match &self.xattrs {
+            XattrSelection::None => {}
+            XattrSelection::All => names.push(Self::XATTR.to_string()),
+            XattrSelection::Named(xattrs) => {
+                names.extend(xattrs.iter().map(|name| format!("{}:{name}", Self::XATTR)));
+            }
+        }
+        if self.links {
+            names.push(Self::LINKS.to_string());
+        }
+        names
+    }
+
+    fn with_name(mut self, name: &str) -> Result<Self, String> {
+        match name {
+            Self::MODE => self.mode = true,
+            Self::OWNER => self.owner = true,
+            Self::ACL => self.acl = true,
+            Self::XATTR => self.xattrs = XattrSelection::All,
+            Self::LINKS => self.links = true,
+            _ => match name
+                .strip_prefix(Self::XATTR)
+                .and_then(|n| n.strip_prefix(':'))
+            {
+                Some("") => return Err("missing extended attribute name after 'xattr:'".into()),
+                Some(xattr) => match &mut self.xattrs {
+                    XattrSelection::All => {}
+                    XattrSelection::Named(names) => {
+                        names.insert(xattr.to_string());
+                    }
+                    XattrSelection::None => {
+                        self.xattrs = XattrSelection::Named(BTreeSet::from([xattr.to_string()]));
+                    }
+                },
+                None => {
+                    return Err(format!(
+                        "unknown attribute '{name}' (expected {}, {}, {}, {}, {}:<name>, {} or {})",
+                        Self::MODE,
+                        Self::OWNER,
+                        Self::ACL,
+                        Self::XATTR,
+                        Self::XATTR,
+                        Self::LINKS,
+                        Self::NONE
+                    ));
+                }
+            },
+        }
+        Ok(self)
+    }
+}
+
+/// Parses a `--track` value: a comma-separated list of `mode`, `owner`,
+/// `acl`, `xattr`, `xattr:<name>` and `links`, or `none`.
+impl FromStr for Track {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == Self::NONE {
+            return Ok(Track::default());
+        }
+        s.split(',')
+            .try_fold(Track::default(), |track, name| track.with_name(name.trim()))
+    }
+}
+
+impl fmt::Display for Track {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            f.write_str(Self::NONE)
+        } else {
+            f.write_str(&self.names().join(","))
+        }
+    }
+}
+
+impl TryFrom<Vec<String>> for Track {
+    type Error = String;
+
+    fn try_from(names: Vec<String>) -> Result<Self, Self::Error> {
+        names
+            .iter()
+            .try_fold(Track::default(), |track, name| track.with_name(name))
+    }
+}
+
+impl From<Track> for Vec<String> {
+    fn from(track: Track) -> Self {
+        track.names()
+    }
+}
+
+/// Ownership, permission bits, extended attributes and hard links of one
+/// entry. In a `WardEntry` a field is `None` when it is not recorded; in an
+/// `FsEntry` when the platform has no such attribute, or, for `xattrs`, when
+/// they were not read (see `read_xattrs`).
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Attributes {
+    /// Permission bits (`st_mode & 0o7777`).
+    pub mode: Option<u32>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    /// Digest of each extended attribute's value, by name.
+    pub xattrs: Option<BTreeMap<String, Checksum>>,
+    /// Regular files only.
+    pub links: Option<Links>,
+    /// Device and inode number of a regular file, which identify its hard
+    /// links. Never recorded.
+    pub file_id: Option<(u64, u64)>,
+}
+
+/// Hard links of a regular file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Links {
+    /// Number of hard links, including those outside the tree.
+    pub nlink: u64,
+    /// Tree-local id shared by the files of the tree that are hard links of
+    /// one another; `None` for a file with a single link. For an `FsEntry`
+    /// it is assigned once the whole tree has been listed.
+    pub group: Option<String>,
+}
+
+/// Number of hex digits of a link group id.
+pub const LINK_GROUP_LEN: usize = 16;
+
+/// A fresh link group id for the hard links found at `paths`, derived from
+/// the paths so that it is the same for every run that sees the same set.
+pub fn new_link_group<'a>(paths: impl IntoIterator<Item = &'a str>) -> String {
+    let mut joined = Vec::new();
+    for path in paths {
+        joined.extend_from_slice(path.as_bytes());
+        joined.push(0);
+    }
+    let mut id = checksum_bytes(&joined, HashAlgorithm::Sha256).hex;
+    id.truncate(LINK_GROUP_LEN);
+    id
+}
+
+/// Whether `id` has the form of a link group id written by treeward.
+pub fn is_valid_link_group(id: &str) -> bool {
+    id.len() == LINK_GROUP_LEN && id.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+impl Attributes {
+    /// Read the attributes of an entry from its (not followed) metadata.
+    #[cfg(unix)]
+    pub(crate) fn from_stat(stat: &EntryStat) -> Self {
+        Attributes {
+            mode: Some(stat.mode),
+            uid: Some(stat.uid),
+            gid: Some(stat.gid),
+            xattrs: None,
+            links: stat.is_file().then_some(Links {
+                nlink: stat.nlink,
+                group: None,
+            }),
+            file_id: stat.is_file().then_some((stat.dev, stat.ino)),
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub(crate) fn from_stat(_stat: &EntryStat) -> Self {
+        Attributes::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mode.is_none()
+            && self.uid.is_none()
+            && self.gid.is_none()
+            && self.xattrs.is_none()
+            && self.links.is_none()
+    }
+
+    /// Only the attributes `track` selects.
+    pub fn tracked(&self, track: &Track) -> Self {
+        Attributes {
+            mode: self.mode.filter(|_| track.mode),
+            uid: self.uid.filter(|_| track.owner),
+            gid: self.gid.filter(|_| track.owner),
+            xattrs: self
+                .xattrs
+                .as_ref()
+                .filter(|_| track.records_xattrs())
+                .map(|xattrs| {
+                    xattrs
+                        .iter()
+                        .filter(|(name, _)| track.selects_xattr(name))
+                        .map(|(name, digest)| (name.clone(), digest.clone()))
+                        .collect()
+                }),
+            links: self.links.clone().filter(|_| track.links),
+            file_id: None,
+        }
+    }
+
+    /// Only the attributes that `recorded` has, taken from `self`: the
+    /// current values to compare against a ward entry's recorded ones.
+    /// `track` is the setting the entry was recorded with, which tells which
+    /// extended attributes would have been recorded had they existed.
+    pub fn matching(&self, recorded: &Attributes, track: &Track) -> Self {
+        Attributes {
+            mode: self.mode.filter(|_| recorded.mode.is_some()),
+            uid: self.uid.filter(|_| recorded.uid.is_some()),
+            gid: self.gid.filter(|_| recorded.gid.is_some()),
+            xattrs: recorded.xattrs.as_ref().map(|_| {
+                self.xattrs
+                    .iter()
+                    .flatten()
+                    .filter(|(name, _)| track.selects_xattr(name))
+                    .map(|(name, digest)| (name.clone(), digest.clone()))
+                    .collect()
+            }),
+            links: self.links.clone().filter(|_| recorded.links.is_some()),
+            file_id: None,
+        }
+    }
+}
+
+/// Digests of the extended attributes of the entry `name` of the open
+/// directory `dir`, which is at `dir_path` (not following a symlink), that
+/// `select` accepts. Values are hashed with SHA-256, so that large values
+/// such as SELinux labels or ACLs stay out of ward files. Attribute names are
+/// keyed, and selected, by their `util::names` string form, so a name that is
+/// not valid UTF-8 is recorded like any other.
+///
+/// On Linux the entry is opened relative to `dir` and its attributes are read
+/// through that descriptor, so a directory swapped for a symlink cannot
+/// redirect the read; macOS reads them by path. A filesystem without extended
+/// attribute support has none. On other platforms, no extended attributes are
+/// read.
+pub fn read_xattrs(
+    dir: &DirHandle,
+    dir_path: &Path,
+    name: &OsStr,
+    select: impl Fn(&str) -> bool,
+) -> std::io::Result<BTreeMap<String, Checksum>> {
+    let entry = xattr::Entry::open(dir, dir_path, name)?;
+    let mut xattrs = BTreeMap::new();
+    for (name, os_name) in entry.list()? {
+        if !select(&name) {
+            continue;
+        }
+        // A value that vanished since listing is treated as never present.
+        if let Some(value) = entry.get(&os_name)? {
+            xattrs.insert(name, checksum_bytes(&value, HashAlgorithm::Sha256));
+        }
+    }
+    Ok(xattrs)
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+mod xattr {
+    use crate::util::dir_handle::DirHandle;
+    use crate::util::names;
+    use std::ffi::{CStr, CString, OsStr};
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    #[cfg(target_os = "linux")]
+    const NO_ATTRIBUTE: i32 = libc::ENODATA;
+    #[cfg(target_os = "macos")]
+    const NO_ATTRIBUTE: i32 = libc::ENOATTR;
+
+    fn c_path(path: &Path) -> io::Result<CString> {
+        CString::new(path.as_os_str().as_bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+    }
+
+    /// Runs a size-query-then-fill call until the buffer is large enough for
+    /// a value that may be growing concurrently.
+    fn read_sized(mut call: impl FnMut(*mut u8, usize) -> isize) -> io::Result<Vec<u8>> {
+        loop {
+            let size = call(std::ptr::null_mut(), 0);
+            if size < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mut buf = vec![0u8; size as usize];
+            let filled = call(buf.as_mut_ptr(), buf.len());
+            if filled >= 0 {
+                buf.truncate(filled as usize);
+                return Ok(buf);
+            }
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() != Some(libc::ERANGE) {
+                return Err(err);
+            }
+        }
+    }
+
+    /// An entry whose extended attributes are read.
+    #[cfg(target_os = "linux")]
+    pub(super) struct Entry {
+        fd: std::os::fd::OwnedFd,
+    }
+
+    #[cfg(target_os = "linux")]
+    impl Entry {
+        pub(super) fn open(dir: &DirHandle, _dir_path: &Path, name: &OsStr) -> io::Result<Self> {
+            Ok(Entry {
+                fd: dir.open_entry(name)?,
+            })
+        }
+
+        /// Reads with `by_fd` on the `O_PATH` descriptor or, where the kernel
+        /// refuses extended attribute calls on those (`EBADF`), with the
+        /// following `by_path` on its `/proc/self/fd` link, which resolves to
+        /// the entry itself even when it is a symlink.
+        fn read(
+            &self,
+            by_fd: impl Fn(libc::c_int, *mut u8, usize) -> isize,
+            by_path: impl Fn(&CStr, *mut u8, usize) -> isize,
+        ) -> io::Result<Vec<u8>> {
+            use std::os::fd::AsRawFd;
+
+            let fd = self.fd.as_raw_fd();
+            match read_sized(|buf, size| by_fd(fd, buf, size)) {
+                Err(e) if e.raw_os_error() == Some(libc::EBADF) => {
+                    let path = c_path(Path::new(&format!("/proc/self/fd/{fd}")))?;
+                    read_sized(|buf, size| by_path(&path, buf, size))
+                }
+                result => result,
+            }
+        }
+
+        fn read_names(&self) -> io::Result<Vec<u8>> {
+            // SAFETY: `path` is NUL-terminated and `buf` is valid for `size`
+            // bytes (or null with size 0, which only queries the size).
+            self.read(
+                |fd, buf, size| unsafe { libc::flistxattr(fd, buf.cast(), size) },
+                |path, buf, size| unsafe { libc::listxattr(path.as_ptr(), buf.cast(), size) },
+            )
+        }
+
+        fn read_value(&self, name: &CStr) -> io::Result<Vec<u8>> {
+            // SAFETY: as in `read_names`; `name` is NUL-terminated.
+            self.read(
+                |fd, buf, size| unsafe { libc::fgetxattr(fd, name.as_ptr(), buf.cast(), size) },
+                |path, buf, size| unsafe {
+                    libc::getxattr(path.as_ptr(), name.as_ptr(), buf.cast(), size)
+                },
+            )
+        }
+    }
+
+    /// An entry whose extended attributes are read.
+    #[cfg(target_os = "macos")]
+    pub(super) struct Entry {
+        path: CString,
+    }
+
+    #[cfg(target_os = "macos")]
+    impl Entry {
+        pub(super) fn open(_dir: &DirHandle, dir_path: &Path, name: &OsStr) -> io::Result<Self> {
+            Ok(Entry {
+                path: c_path(&dir_path.join(name))?,
+            })
+        }
+
+        fn read_names(&self) -> io::Result<Vec<u8>> {
+            // SAFETY: `path` is NUL-terminated and `buf` is valid for `size`
+            // bytes (or null with size 0, which only queries the size).
+            read_sized(|buf, size| unsafe {
+                libc::listxattr(self.path.as_ptr(), buf.cast(), size, libc::XATTR_NOFOLLOW)
+            })
+        }
+
+        fn read_value(&self, name: &CStr) -> io::Result<Vec<u8>> {
+            // SAFETY: as in `read_names`; `name` is NUL-terminated.
+            read_sized(|buf, size| unsafe {
+                libc::getxattr(
+                    self.path.as_ptr(),
+                    name.as_ptr(),
+                    buf.cast(),
+                    size,
+                    0,
+                    libc::XATTR_NOFOLLOW,
+                )
+            })
+        }
+    }
+
+    impl Entry {
+        /// The names of the entry's extended attributes, each in its string
+        /// form and as the OS spells it.
+        pub(super) fn list(&self) -> io::Result<Vec<(String, CString)>> {
+            match self.read_names() {
+                Ok(names) => Ok(names
+                    .split(|&b| b == 0)
+                    .filter(|name| !name.is_empty())
+                    .map(|name| {
+                        let string = names::from_os(OsStr::from_bytes(name))
+                            .expect("Unix names always have a string form")
+                            .into_owned();
+                        (string, CString::new(name).expect("split at NUL bytes"))
+                    })
+                    .collect()),
+                Err(e) if e.raw_os_error() == Some(libc::ENOTSUP) => Ok(Vec::new()),
+                Err(e) => Err(e),
+            }
+        }
+
+        pub(super) fn get(&self, name: &CStr) -> io::Result<Option<Vec<u8>>> {
+            match self.read_value(name) {
+                Ok(value) => Ok(Some(value)),
+                Err(e) if e.raw_os_error() == Some(NO_ATTRIBUTE) => Ok(None),
+                Err(e) => Err(e),
+            }
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+mod xattr {
+    use crate::util::dir_handle::DirHandle;
+    use std::ffi::{CStr, CString, OsStr};
+    use std::io;
+    use std::path::Path;
+
+    pub(super) struct Entry;
+
+    impl Entry {
+        pub(super) fn open(_dir: &DirHandle, _dir_path: &Path, _name: &OsStr) -> io::Result<Self> {
+            Ok(Entry)
+        }
+
+        pub(super) fn list(&self) -> io::Result<Vec<(String, CString)>> {
+            Ok(Vec::new())
+        }
+
+        pub(super) fn get(&self, _name: &CStr) -> io::Result<Option<Vec<u8>>> {
+            Ok(None)
+        }
+    }
+}
+
+/// Format permission bits the way `chmod` takes them, e.g. `0644`, `4755`.
+pub fn format_mode(mode: u32) -> String {
+    format!("{mode:04o}")
+}
+
+/// Parse permission bits as written by `format_mode`: exactly four octal
+/// digits.
+pub fn parse_mode(s: &str) -> Option<u32> {
+    if s.len() != 4 || !s.bytes().all(|b| matches!(b, b'0'..=b'7')) {
+        return None;
+    }
+    u32::from_str_radix(s, 8).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_track() {
+        assert_eq!(
+            "mode".parse::<Track>().unwrap(),
+            Track {
+                mode: true,
+                ..Track::default()
+            }
+        );
+        assert_eq!(
+            "owner,mode".parse::<Track>().unwrap(),
+            Track {
+                mode: true,
+                owner: true,
+                ..Track::default()
+            }
+        );
+        assert_eq!("none".parse::<Track>().unwrap(), Track::default());
+        assert!("mode,inode".parse::<Track>().is_err());
+        assert!("".parse::<Track>().is_err());
+        assert!("xattr:".parse::<Track>().is_err());
+    }
+
+    #[test]
+    fn test_parse_xattr_selection() {
+        let named: Track = "xattr:security.capability,acl,xattr:user.a"
+            .parse()
+            .unwrap();
+        assert!(named.acl);
+        assert_eq!(
+            named.xattrs,
+            XattrSelection::Named(BTreeSet::from([
+                "security.capability".to_string(),
+                "user.a".to_string()
+            ]))
+        );
+        assert!(named.selects_xattr("system.posix_acl_access"));
+        assert!(named.selects_xattr("user.a"));
+        assert!(!named.selects_xattr("user.b"));
+
+        let all: Track = "xattr:user.a,xattr".parse().unwrap();
+        assert_eq!(all.xattrs, XattrSelection::All);
+        assert!(all.selects_xattr("user.b"));
+        assert!(!all.acl);
+    }
+
+    #[test]
+    fn test_track_display_round_trips() {
+        for track in [
+            "none",
+            "mode",
+            "owner",
+            "mode,owner",
+            "acl,xattr",
+            "owner,xattr:security.capability,xattr:security.selinux",
+        ] {
+            assert_eq!(track.parse::<Track>().unwrap().to_string(), track);
+        }
+    }
+
+    #[test]
+    fn test_mode_format_round_trips() {
+        assert_eq!(format_mode(0o644), "0644");
+        assert_eq!(format_mode(0o4755), "4755");
+        assert_eq!(parse_mode("4755"), Some(0o4755));
+        assert_eq!(parse_mode("0644"), Some(0o644));
+        for bad in ["644", "04755", "0648", "+644", ""] {
+            assert_eq!(parse_mode(bad), None, "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_tracked_and_matching_select_fields() {
+        let all = Attributes {
+            mode: Some(0o755),
+            uid: Some(1),
+            gid: Some(2),
+            ..Attributes::default()
+        };
+        let mode_only = all.tracked(&Track {
+            mode: true,
+            ..Track::default()
+        });
+
+        assert_eq!(
+            mode_only,
+            Attributes {
+                mode: Some(0o755),
+                ..Attributes::default()
+            }
+        );
+        assert_eq!(all.matching(&mode_only, &Track::default()), mode_only);
+        assert!(all.tracked(&Track::default()).is_empty());
+    }
+
+    #[test]
+    fn test_xattrs_are_selected_by_the_recorded_track() {
+        let digest = |value: &str| checksum_bytes(value.as_bytes(), HashAlgorithm::Sha256);
+        let current = Attributes {
+            xattrs: Some(BTreeMap::from([
+                ("user.a".to_string(), digest("a")),
+                ("user.b".to_string(), digest("b")),
+            ])),
+            ..Attributes::default()
+        };
+        let track: Track = "xattr:user.a".parse().unwrap();
+
+        let recorded = current.tracked(&track);
+        assert_eq!(
+            recorded.xattrs,
+            Some(BTreeMap::from([("user.a".to_string(), digest("a"))]))
+        );
+        assert_eq!(current.matching(&recorded, &track), recorded);
+
+        // Tracked but absent: recorded as an empty set, unlike untracked.
+        let none_present = Attributes::default().tracked(&track);
+        assert_eq!(none_present.xattrs, None);
+        let empty = Attributes {
+            xattrs: Some(BTreeMap::new()),
+            ..Attributes::default()
+        };
+        assert_eq!(empty.tracked(&track).xattrs, Some(BTreeMap::new()));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_read_xattrs() {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+
+        let temp = tempfile::TempDir::new().unwrap();
+        let file = temp.path().join("file");
+        std::fs::write(&file, "content").unwrap();
+        let c_file = CString::new(file.as_os_str().as_bytes()).unwrap();
+        for (name, value) in [
+            (c"user.keep", "kept"),
+            (c"user.skip", "skipped"),
+            (c"user.caf\xe9", "raw"),
+        ] {
+            // SAFETY: all pointers are valid for the given lengths.
+            let rc = unsafe {
+                libc::setxattr(
+                    c_file.as_ptr(),
+                    name.as_ptr(),
+                    value.as_ptr().cast(),
+                    value.len(),
+                    0,
+                )
+            };
+            if rc != 0 {
+                // The filesystem backing the temp dir has no user xattrs.
+                return;
+            }
+        }
+
+        // A name that is not valid UTF-8 is recorded in its string form.
+        let dir = DirHandle::open(temp.path()).unwrap();
+        let xattrs = read_xattrs(&dir, temp.path(), OsStr::new("file"), |name| {
+            name != "user.skip"
+        })
+        .unwrap();
+        assert_eq!(
+            xattrs,
+            BTreeMap::from([
+                (
+                    "user.caf\0e9".to_string(),
+                    checksum_bytes(b"raw", HashAlgorithm::Sha256)
+                ),
+                (
+                    "user.keep".to_string(),
+                    checksum_bytes(b"kept", HashAlgorithm::Sha256)
+                ),
+            ])
+        );
+
+        // A symlink's own attributes are read, not its target's.
+        std::os::unix::fs::symlink("file", temp.path().join("link")).unwrap();
+        let xattrs = read_xattrs(&dir, temp.path(), OsStr::new("link"), |_| true).unwrap();
+        assert_eq!(xattrs, BTreeMap::new());
+    }
+}
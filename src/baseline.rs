@@ -0,0 +1,364 @@
+//! Named baselines: frozen copies of a tree's ward files.
+//!
+//! `baseline create` copies every ward file reachable from the root ward,
+//! byte for byte, into `.treeward-baselines/<name>/tree/` at the same
+//! relative path, next to an `info.toml` saying when and what it pinned.
+//! `status --against <name>` then hands that copy to `compute_status` in
+//! place of the live ward files (see `StatusOptions::baseline`), so later
+//! updates do not move what the tree is compared to. Being verbatim copies,
+//! the stored ward files keep their digest chain.
+
+use crate::checksum::HashAlgorithm;
+use crate::dir_list::{BASELINES_DIRNAME, TREEWARD_FILENAME};
+use crate::util::dir_handle::DirHandle;
+use crate::util::{is_real_dir, names};
+use crate::ward_file::{
+    WardEntry, WardFile, WardFileError, content_digest, sync_dir, write_atomic,
+};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const INFO_FILENAME: &str = "info.toml";
+const TREE_DIRNAME: &str = "tree";
+
+#[derive(Debug, thiserror::Error)]
+pub enum BaselineError {
+    #[error("Ward file error: {0}")]
+    WardFile(#[from] WardFileError),
+    #[error("IO error on {}: {source}", path.display())]
+    Io { path: PathBuf, source: io::Error },
+    #[error("Not initialized (use treeward init to initialize)")]
+    NotInitialized,
+    #[error("Ward file {0} does not match the digest recorded in its parent ward")]
+    DigestMismatch(PathBuf),
+    #[error(
+        "Invalid baseline name {0:?} (use letters, digits, '.', '_' and '-', not starting with '.')"
+    )]
+    InvalidName(String),
+    #[error("Baseline {0} already exists")]
+    Exists(String),
+    #[error("No baseline named {0} (see treeward baseline list)")]
+    NotFound(String),
+    #[error("Invalid baseline info {}: {source}", path.display())]
+    Info {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[error("Cannot serialize baseline info: {0}")]
+    Serialize(#[from] toml::ser::Error),
+}
+
+/// What `info.toml` records about a baseline.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BaselineInfo {
+    /// The baseline's directory name.
+    #[serde(skip)]
+    pub name: String,
+    /// When the baseline was created, RFC 3339 in UTC.
+    pub created: String,
+    /// Digest of the root `.treeward` at the time (see `digest`).
+    pub digest: String,
+    /// Number of ward files copied.
+    pub ward_files: usize,
+}
+
+/// Freeze the ward files of the tree rooted at `root` as baseline `name`.
+///
+/// Traversal follows the wards like `migrate_tree`, checking each child's
+/// ward file against the digest recorded in its parent: a baseline of ward
+/// files that were already tampered with would vouch for the tampering. The
+/// copy is assembled in a hidden directory and renamed into place, so a
+/// baseline is either complete or absent.
+pub fn create_baseline(root: &Path, name: &str) -> Result<BaselineInfo, BaselineError> {
+    check_name(name)?;
+    let root = root.canonicalize().map_err(|e| io_error(root, e))?;
+    let root_ward_path = root.join(TREEWARD_FILENAME);
+    let Some((root_ward, root_content)) = WardFile::load_with_content_if_exists(&root_ward_path)?
+    else {
+        return Err(BaselineError::NotInitialized);
+    };
+
+    let mut ward_files: Vec<(PathBuf, String)> = Vec::new();
+    let mut pending = vec![(PathBuf::new(), root_ward, root_content.clone())];
+    while let Some((relative, ward_file, content)) = pending.pop() {
+        for (entry_name, entry) in &ward_file.entries {
+            let WardEntry::Dir { digest, .. } = entry else {
+                continue;
+            };
+            let child = relative.join(names::to_os(entry_name));
+            if !is_real_dir(&root.join(&child)).map_err(|e| io_error(&root.join(&child), e))? {
+                continue;
+            }
+            let child_ward_path = root.join(&child).join(TREEWARD_FILENAME);
+            let loaded = WardFile::load_with_content_if_exists(&child_ward_path)?;
+            if let Some(recorded) = digest {
+                let matches = loaded.as_ref().is_some_and(|(_, child_content)| {
+                    content_digest(child_content, recorded.algorithm) == *recorded
+                });
+                if !matches {
+                    return Err(BaselineError::DigestMismatch(child.join(TREEWARD_FILENAME)));
+                }
+            }
+            if let Some((child_ward, child_content)) = loaded {
+                pending.push((child, child_ward, child_content));
+            }
+        }
+        ward_files.push((relative, content));
+    }
+
+    let baselines = root.join(BASELINES_DIRNAME);
+    let target = baselines.join(name);
+    if target.exists() {
+        return Err(BaselineError::Exists(name.to_string()));
+    }
+    fs::create_dir_all(&baselines).map_err(|e| io_error(&baselines, e))?;
+    let staging = baselines.join(format!(".{name}.{}.tmp", std::process::id()));
+    if staging.exists() {
+        fs::remove_dir_all(&staging).map_err(|e| io_error(&staging, e))?;
+    }
+
+    let info = BaselineInfo {
+        name: name.to_string(),
+        created: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        digest: content_digest(&root_content, HashAlgorithm::default()).to_string(),
+        ward_files: ward_files.len(),
+    };
+    for (relative, content) in &ward_files {
+        let dir = staging.join(TREE_DIRNAME).join(relative);
+        fs::create_dir_all(&dir).map_err(|e| io_error(&dir, e))?;
+        write_atomic(&dir.join(TREEWARD_FILENAME), content.as_bytes())?;
+    }
+    write_atomic(
+        &staging.join(INFO_FILENAME),
+        toml::to_string(&info)?.as_bytes(),
+    )?;
+
+    if target.exists() {
+        fs::remove_dir_all(&staging).map_err(|e| io_error(&staging, e))?;
+        return Err(BaselineError::Exists(name.to_string()));
+    }
+    fs::rename(&staging, &target).map_err(|e| io_error(&target, e))?;
+    sync_baselines(&baselines)?;
+    Ok(info)
+}
+
+/// The baselines of the tree rooted at `root`, oldest first.
+pub fn list_baselines(root: &Path) -> Result<Vec<BaselineInfo>, BaselineError> {
+    let baselines = root.join(BASELINES_DIRNAME);
+    let entries = match fs::read_dir(&baselines) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(io_error(&baselines, e)),
+    };
+    let mut infos = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| io_error(&baselines, e))?;
+        // Staging and deleted baselines are hidden.
+        let file_name = entry.file_name();
+        let Some(name) = names::from_os(&file_name) else {
+            continue;
+        };
+        if name.starts_with('.') {
+            continue;
+        }
+        infos.push(read_info(&baselines, &name)?);
+    }
+    infos.sort_by(|a, b| a.created.cmp(&b.created).then_with(|| a.name.cmp(&b.name)));
+    Ok(infos)
+}
+
+/// Remove baseline `name` of the tree rooted at `root`.
+///
+/// The baseline is first renamed out of sight, so an interrupted removal
+/// never leaves a partial baseline to compare against.
+pub fn delete_baseline(root: &Path, name: &str) -> Result<(), BaselineError> {
+    let target = baseline_dir(root, name)?;
+    let baselines = root.join(BASELINES_DIRNAME);
+    let doomed = baselines.join(format!(".{name}.{}.deleted", std::process::id()));
+    fs::rename(&target, &doomed).map_err(|e| io_error(&target, e))?;
+    sync_baselines(&baselines)?;
+    fs::remove_dir_all(&doomed).map_err(|e| io_error(&doomed, e))
+}
+
+/// The stored ward files of baseline `name`, laid out like the tree rooted at
+/// `root`, for `StatusOptions::baseline`.
+pub fn baseline_tree(root: &Path, name: &str) -> Result<PathBuf, BaselineError> {
+    Ok(baseline_dir(root, name)?.join(TREE_DIRNAME))
+}
+
+fn baseline_dir(root: &Path, name: &str) -> Result<PathBuf, BaselineError> {
+    check_name(name)?;
+    let dir = root.join(BASELINES_DIRNAME).join(name);
+    if !is_real_dir(&dir).map_err(|e| io_error(&dir, e))? {
+        return Err(BaselineError::NotFound(name.to_string()));
+    }
+    Ok(dir)
+}
+
+fn read_info(baselines: &Path, name: &str) -> Result<BaselineInfo, BaselineError> {
+    let path = baselines.join(name).join(INFO_FILENAME);
+    let content = fs::read_to_string(&path).map_err(|e| io_error(&path, e))?;
+    let mut info: BaselineInfo =
+        toml::from_str(&content).map_err(|source| BaselineError::Info { path, source })?;
+    info.name = name.to_string();
+    Ok(info)
+}
+
+/// Names end up as directory names, and a leading dot is reserved for
+/// staging.
+fn check_name(name: &str) -> Result<(), BaselineError> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+    if valid {
+        Ok(())
+    } else {
+        Err(BaselineError::InvalidName(name.to_string()))
+    }
+}
+
+fn sync_baselines(baselines: &Path) -> Result<(), BaselineError> {
+    let dir = DirHandle::open(baselines).map_err(|e| io_error(baselines, e))?;
+    Ok(sync_dir(&dir)?)
+}
+
+fn io_error(path: &Path, source: io::Error) -> BaselineError {
+    BaselineError::Io {
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::Checksum;
+    use crate::digest::tree_digest;
+    use crate::status::ChecksumPolicy;
+    use crate::update::{WardOptions, ward_directory};
+    use std::num::NonZeroUsize;
+    use tempfile::TempDir;
+
+    fn stored_root_digest(root: &Path, name: &str) -> Checksum {
+        let tree = baseline_tree(root, name).unwrap();
+        let content = fs::read_to_string(tree.join(TREEWARD_FILENAME)).unwrap();
+        content_digest(&content, HashAlgorithm::default())
+    }
+
+    fn warded_tree() -> TempDir {
+        let temp = TempDir::new().unwrap();
+        fs::create_dir_all(temp.path().join("a/b")).unwrap();
+        fs::write(temp.path().join("file.txt"), "content").unwrap();
+        fs::write(temp.path().join("a/b/deep.txt"), "deep").unwrap();
+        ward_directory(
+            temp.path(),
+            WardOptions {
+                init: true,
+                allow_init: false,
+                fingerprint: None,
+                accept_digest_mismatch: false,
+                dry_run: false,
+                checksum_policy: ChecksumPolicy::Never,
+                jobs: NonZeroUsize::MIN,
+                hash: HashAlgorithm::Sha256,
+                signing_key: None,
+                track: None,
+                protection: None,
+                keep_going: false,
+                pathspecs: Vec::new(),
+            },
+        )
+        .unwrap();
+        temp
+    }
+
+    #[test]
+    fn test_create_copies_ward_files_verbatim() {
+        let temp = warded_tree();
+        let info = create_baseline(temp.path(), "v1").unwrap();
+
+        assert_eq!(info.ward_files, 3);
+        let digest = tree_digest(temp.path(), HashAlgorithm::default()).unwrap();
+        assert_eq!(info.digest, digest.to_string());
+        assert_eq!(stored_root_digest(temp.path(), "v1"), digest);
+        let tree = baseline_tree(temp.path(), "v1").unwrap();
+        assert_eq!(
+            fs::read(tree.join("a/b/.treeward")).unwrap(),
+            fs::read(temp.path().join("a/b/.treeward")).unwrap()
+        );
+        assert_eq!(list_baselines(temp.path()).unwrap(), vec![info]);
+    }
+
+    #[test]
+    fn test_create_rejects_existing_and_invalid_names() {
+        let temp = warded_tree();
+        create_baseline(temp.path(), "v1").unwrap();
+
+        assert!(matches!(
+            create_baseline(temp.path(), "v1"),
+            Err(BaselineError::Exists(_))
+        ));
+        for name in ["", ".hidden", "a/b", "..", "with space"] {
+            assert!(
+                matches!(
+                    create_baseline(temp.path(), name),
+                    Err(BaselineError::InvalidName(_))
+                ),
+                "{name:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_create_rejects_tampered_ward_file() {
+        let temp = warded_tree();
+        let ward_path = temp.path().join("a/b/.treeward");
+        let content = fs::read_to_string(&ward_path).unwrap();
+        fs::write(&ward_path, content.replace("deep.txt", "Deep.txt")).unwrap();
+
+        assert!(matches!(
+            create_baseline(temp.path(), "v1"),
+            Err(BaselineError::DigestMismatch(path)) if path == Path::new("a/b/.treeward")
+        ));
+        assert!(list_baselines(temp.path()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_create_requires_initialized_tree() {
+        let temp = TempDir::new().unwrap();
+        assert!(matches!(
+            create_baseline(temp.path(), "v1"),
+            Err(BaselineError::NotInitialized)
+        ));
+    }
+
+    #[test]
+    fn test_delete() {
+        let temp = warded_tree();
+        create_baseline(temp.path(), "v1").unwrap();
+        create_baseline(temp.path(), "v2").unwrap();
+
+        delete_baseline(temp.path(), "v1").unwrap();
+
+        let names: Vec<_> = list_baselines(temp.path())
+            .unwrap()
+            .into_iter()
+            .map(|info| info.name)
+            .collect();
+        assert_eq!(names, vec!["v2"]);
+        assert_eq!(
+            fs::read_dir(temp.path().join(BASELINES_DIRNAME))
+                .unwrap()
+                .count(),
+            1
+        );
+        assert!(matches!(
+            delete_baseline(temp.path(), "v1"),
+            Err(BaselineError::NotFound(_))
+        ));
+    }
+}
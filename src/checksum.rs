@@ -1,18 +1,35 @@
 //! File checksumming primitive used by status and update workflows.
 //!
-//! Computes SHA-256 for regular files and returns checksum, mtime, and size
-//! values used to build `WardEntry::File`.
+//! Computes content digests (SHA-256, SHA-512 or BLAKE3) for regular files and
+//! returns checksum, mtime, and size values used to build `WardEntry::File`.
+//! Several algorithms can be computed in a single read, which lets an update
+//! compare against a recorded digest and record a digest in a different
+//! algorithm without reading the file twice. `checksum_files` runs the same
+//! primitive over a batch of paths on a bounded pool of worker threads.
 //!
 //! Concurrent modification is detected two ways: mtimes are compared before and
-//! after the read, and (on Unix) the path is re-checked after reading to confirm
+//! after the read, and (on Unix) the name is re-checked after reading to confirm
 //! it still names the opened file (dev/ino), catching rename/replace races. Either
 //! failure returns `ChecksumError::ConcurrentModification`.
-
-use sha2::{Digest, Sha256};
+//!
+//! Files are opened relative to a handle on their directory (see
+//! `util::dir_handle`). A walk passes the identity the directory had when it
+//! was listed, and a directory that is no longer that one (because it, or a
+//! directory above it, was swapped or moved) is never read from.
+
+use sha2::{Digest, Sha256, Sha512};
+use std::ffi::OsStr;
+use std::fmt;
 use std::fs::File;
+use std::io::Write;
+use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use tracing::{debug, info};
 
+use crate::util::dir_handle::{DirHandle, DirId};
+
 #[derive(Debug, thiserror::Error)]
 pub enum ChecksumError {
     #[error("IO error: {0}")]
@@ -23,26 +40,213 @@ pub enum ChecksumError {
     NotRegularFile(PathBuf),
     #[error("File modified during checksumming: {0}")]
     ConcurrentModification(PathBuf),
+    /// The file's directory is no longer the one the walk listed it in.
+    #[error("Directory replaced during checksumming (concurrent modification): {0}")]
+    DirectoryReplaced(PathBuf),
+}
+
+/// Content hash algorithm a checksum was computed with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    Sha512,
+    Blake3,
+}
+
+impl HashAlgorithm {
+    /// Stable lowercase name, used on disk and in output.
+    pub fn name(self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Sha512 => "sha512",
+            HashAlgorithm::Blake3 => "blake3",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "sha256" => Some(HashAlgorithm::Sha256),
+            "sha512" => Some(HashAlgorithm::Sha512),
+            "blake3" => Some(HashAlgorithm::Blake3),
+            _ => None,
+        }
+    }
+
+    /// Number of hex characters in a digest produced by this algorithm.
+    pub fn hex_len(self) -> usize {
+        match self {
+            HashAlgorithm::Sha256 | HashAlgorithm::Blake3 => 64,
+            HashAlgorithm::Sha512 => 128,
+        }
+    }
+
+    fn hasher(self) -> Hasher {
+        match self {
+            HashAlgorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+            HashAlgorithm::Sha512 => Hasher::Sha512(Sha512::new()),
+            HashAlgorithm::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+        }
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A hex-encoded content digest tagged with the algorithm that produced it.
+///
+/// Displays as `<algorithm>:<hex>`, which is also the persisted form.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checksum {
+    pub algorithm: HashAlgorithm,
+    /// Lowercase hex.
+    pub hex: String,
+}
+
+impl Checksum {
+    pub fn new(algorithm: HashAlgorithm, hex: impl Into<String>) -> Self {
+        Checksum {
+            algorithm,
+            hex: hex.into(),
+        }
+    }
+}
+
+impl fmt::Display for Checksum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.algorithm, self.hex)
+    }
+}
+
+enum Hasher {
+    Sha256(Sha256),
+    Sha512(Sha512),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl Hasher {
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha256(h) => h.update(data),
+            Hasher::Sha512(h) => h.update(data),
+            Hasher::Blake3(h) => {
+                h.update(data);
+            }
+        }
+    }
+
+    fn finalize(self) -> Checksum {
+        match self {
+            Hasher::Sha256(h) => {
+                Checksum::new(HashAlgorithm::Sha256, format!("{:x}", h.finalize()))
+            }
+            Hasher::Sha512(h) => {
+                Checksum::new(HashAlgorithm::Sha512, format!("{:x}", h.finalize()))
+            }
+            Hasher::Blake3(h) => {
+                Checksum::new(HashAlgorithm::Blake3, h.finalize().to_hex().as_str())
+            }
+        }
+    }
+}
+
+/// A digest computed incrementally from everything written to it, for data
+/// read by callers that also do something else with it.
+pub(crate) struct StreamingChecksum(Hasher);
+
+impl StreamingChecksum {
+    pub fn new(algorithm: HashAlgorithm) -> Self {
+        StreamingChecksum(algorithm.hasher())
+    }
+
+    pub fn finish(self) -> Checksum {
+        self.0.finalize()
+    }
+}
+
+impl Write for StreamingChecksum {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Feeds one read of the file to every requested hasher.
+struct Hashers(Vec<Hasher>);
+
+impl Write for Hashers {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        for hasher in &mut self.0 {
+            hasher.update(buf);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
 }
 
+#[derive(Debug, Clone)]
 pub struct FileChecksum {
-    /// Hex encoded.
-    pub sha256: String,
+    /// One checksum per distinct requested algorithm; see `checksum`.
+    pub checksums: Vec<Checksum>,
     /// Modification time captured after checksumming.
     pub mtime: std::time::SystemTime,
     /// File size in bytes.
     pub size: u64,
 }
 
-/// Computes the SHA-256 checksum of a file with concurrent modification detection.
+impl FileChecksum {
+    pub fn checksum(&self, algorithm: HashAlgorithm) -> Option<&Checksum> {
+        self.checksums.iter().find(|c| c.algorithm == algorithm)
+    }
+}
+
+/// Digest of an in-memory byte string, for data that is not a tree file.
+pub fn checksum_bytes(data: &[u8], algorithm: HashAlgorithm) -> Checksum {
+    let mut hasher = algorithm.hasher();
+    hasher.update(data);
+    hasher.finalize()
+}
+
+/// A file to checksum and the algorithms to compute for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChecksumRequest {
+    pub path: PathBuf,
+    pub algorithms: Vec<HashAlgorithm>,
+    /// Identity of the file's directory when it was listed, if known (see
+    /// `checksum_file_in`).
+    pub walked_dir: Option<DirId>,
+}
+
+/// Computes checksums of a file with concurrent modification detection.
+///
+/// One checksum is returned per distinct entry of `algorithms`; all of them
+/// come from the same read of the file.
+///
+/// When `walked_dir` is given, the file is only read if its directory still
+/// is the one so identified (by a walk that listed the file there). The
+/// directory is opened by path, but once its identity is confirmed, the file
+/// is opened relative to that handle, so no swap anywhere along the path can
+/// redirect the read.
 ///
 /// # Behavior
-/// - Opens the file without following symlinks (`O_NOFOLLOW` or platform equivalent)
+/// - Opens the file's directory, then the file relative to it without following
+///   symlinks (`openat` with `O_NOFOLLOW`, or platform equivalent)
 /// - Records the file's modification time before reading
-/// - Reads the file in chunks and computes SHA-256
+/// - Reads the file in chunks and feeds every requested hasher
 /// - Verifies the modification time hasn't changed after reading
-/// - On Unix, also verifies the path still names the opened file (dev/ino),
-///   so rename/replace during the read is detected, not just in-place writes
+/// - On Unix, also verifies the name still names the opened file in the opened
+///   directory (dev/ino), so rename/replace during the read is detected, not
+///   just in-place writes
 /// - Returns an error if the file was modified during checksumming
 ///
 /// # Errors (may be changed in the future)
@@ -52,15 +256,30 @@ pub struct FileChecksum {
 /// - `ChecksumError::ConcurrentModification`: File was detected as being modified while
 ///   checksumming. Note that the absence of this error is *not* a guarantee that the
 ///   file was *not* modified.
-pub fn checksum_file(path: &Path) -> Result<FileChecksum, ChecksumError> {
+/// - `ChecksumError::DirectoryReplaced`: The directory is not `walked_dir`
+pub fn checksum_file_in(
+    path: &Path,
+    walked_dir: Option<DirId>,
+    algorithms: &[HashAlgorithm],
+) -> Result<FileChecksum, ChecksumError> {
     info!("Checksumming {}", path.display());
 
-    let mut file = open_regular_file_no_follow(path)?;
+    let mut unique_algorithms = algorithms.to_vec();
+    unique_algorithms.sort();
+    unique_algorithms.dedup();
+
+    let (dir, name) = open_parent(path, walked_dir)?;
+    let mut file = open_regular_file_no_follow(&dir, name, path)?;
     let metadata_before = file.metadata().map_err(ChecksumError::Io)?;
     let mtime_before = metadata_before.modified().map_err(ChecksumError::Io)?;
 
-    let mut hasher = Sha256::new();
-    std::io::copy(&mut file, &mut hasher).map_err(ChecksumError::Io)?;
+    let mut hashers = Hashers(
+        unique_algorithms
+            .iter()
+            .map(|algorithm| algorithm.hasher())
+            .collect(),
+    );
+    std::io::copy(&mut file, &mut hashers).map_err(ChecksumError::Io)?;
 
     let metadata_after = file.metadata().map_err(ChecksumError::Io)?;
     let mtime_after = metadata_after.modified().map_err(ChecksumError::Io)?;
@@ -68,29 +287,152 @@ pub fn checksum_file(path: &Path) -> Result<FileChecksum, ChecksumError> {
     if mtime_before != mtime_after {
         return Err(ChecksumError::ConcurrentModification(path.to_path_buf()));
     }
-    ensure_path_still_names_open_file(path, &metadata_after)?;
-
-    let hash_bytes = hasher.finalize();
-    let sha256 = format!("{:x}", hash_bytes);
+    ensure_name_still_names_open_file(&dir, name, path, &metadata_after)?;
 
-    debug!("Checksum of {} is {}", path.display(), sha256);
+    let checksums: Vec<Checksum> = hashers.0.into_iter().map(Hasher::finalize).collect();
+    for checksum in &checksums {
+        debug!("Checksum of {} is {}", path.display(), checksum);
+    }
 
     Ok(FileChecksum {
-        sha256,
+        checksums,
         mtime: mtime_after,
         size: metadata_after.len(),
     })
 }
 
+/// `checksum_file_in` without a walked directory. Test convenience.
+#[cfg(test)]
+pub fn checksum_file(
+    path: &Path,
+    algorithms: &[HashAlgorithm],
+) -> Result<FileChecksum, ChecksumError> {
+    checksum_file_in(path, None, algorithms)
+}
+
+/// Checksums every request using up to `jobs` worker threads.
+///
+/// Results are returned in the order of `requests`, so callers observe the same
+/// data regardless of how many workers ran or how they were scheduled.
+///
+/// Failure semantics match checksumming the paths one by one in order: the
+/// first failure stops workers from claiming further paths. Paths are claimed
+/// in order, so every path before the earliest failing one was checksummed
+/// (successfully) and has its result; paths after it may or may not have been,
+/// and their slots are to be ignored. In particular a `ConcurrentModification`
+/// stays fatal, exactly as with a sequential run, for a caller that stops at
+/// the first error.
+pub fn checksum_files(
+    requests: &[ChecksumRequest],
+    jobs: NonZeroUsize,
+) -> Vec<Option<Result<FileChecksum, ChecksumError>>> {
+    run_requests(requests, jobs, true)
+}
+
+/// Like `checksum_files`, but checksums every request whatever fails, and
+/// returns each request's own result.
+pub fn checksum_each_file(
+    requests: &[ChecksumRequest],
+    jobs: NonZeroUsize,
+) -> Vec<Result<FileChecksum, ChecksumError>> {
+    run_requests(requests, jobs, false)
+        .into_iter()
+        .map(|slot| slot.expect("every path is checksummed"))
+        .collect()
+}
+
+/// Runs `requests` on up to `jobs` threads, in claim order. With
+/// `stop_on_failure`, no path is claimed after one fails.
+fn run_requests(
+    requests: &[ChecksumRequest],
+    jobs: NonZeroUsize,
+    stop_on_failure: bool,
+) -> Vec<Option<Result<FileChecksum, ChecksumError>>> {
+    let workers = jobs.get().min(requests.len());
+    if workers <= 1 {
+        let mut results = Vec::with_capacity(requests.len());
+        for request in requests {
+            let result = checksum_file_in(&request.path, request.walked_dir, &request.algorithms);
+            let failed = result.is_err();
+            results.push(Some(result));
+            if failed && stop_on_failure {
+                break;
+            }
+        }
+        results.resize_with(requests.len(), || None);
+        return results;
+    }
+
+    let next_index = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let results: Mutex<Vec<Option<Result<FileChecksum, ChecksumError>>>> = Mutex::new(
+        std::iter::repeat_with(|| None)
+            .take(requests.len())
+            .collect(),
+    );
+
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                while !failed.load(Ordering::Relaxed) {
+                    let index = next_index.fetch_add(1, Ordering::Relaxed);
+                    let Some(request) = requests.get(index) else {
+                        break;
+                    };
+                    let result =
+                        checksum_file_in(&request.path, request.walked_dir, &request.algorithms);
+                    if result.is_err() && stop_on_failure {
+                        failed.store(true, Ordering::Relaxed);
+                    }
+                    results.lock().expect("checksum worker panicked")[index] = Some(result);
+                }
+            });
+        }
+    });
+
+    results.into_inner().expect("checksum worker panicked")
+}
+
+/// Opens the directory of `path`, checking it against `walked_dir`, and
+/// returns it with the file's name in it.
+fn open_parent(
+    path: &Path,
+    walked_dir: Option<DirId>,
+) -> Result<(DirHandle, &OsStr), ChecksumError> {
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+        return Err(ChecksumError::NotRegularFile(path.to_path_buf()));
+    };
+    let parent = if parent.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        parent
+    };
+    let dir = DirHandle::open(parent).map_err(|e| {
+        if e.kind() == std::io::ErrorKind::PermissionDenied {
+            ChecksumError::PermissionDenied(path.to_path_buf())
+        } else {
+            ChecksumError::Io(e)
+        }
+    })?;
+    if let Some(walked_dir) = walked_dir
+        && dir.id().map_err(ChecksumError::Io)? != walked_dir
+    {
+        return Err(ChecksumError::DirectoryReplaced(parent.to_path_buf()));
+    }
+    Ok((dir, name))
+}
+
 #[cfg(unix)]
-fn ensure_path_still_names_open_file(
+pub(crate) fn ensure_name_still_names_open_file(
+    dir: &DirHandle,
+    name: &OsStr,
     path: &Path,
     open_metadata: &std::fs::Metadata,
 ) -> Result<(), ChecksumError> {
     use std::os::unix::fs::MetadataExt;
 
-    let path_metadata = std::fs::symlink_metadata(path).map_err(ChecksumError::Io)?;
-    if open_metadata.dev() != path_metadata.dev() || open_metadata.ino() != path_metadata.ino() {
+    let name_stat = dir.stat(name).map_err(ChecksumError::Io)?;
+    if open_metadata.dev() != name_stat.dev || open_metadata.ino() != name_stat.ino {
         return Err(ChecksumError::ConcurrentModification(path.to_path_buf()));
     }
 
@@ -98,7 +440,9 @@ fn ensure_path_still_names_open_file(
 }
 
 #[cfg(not(unix))]
-fn ensure_path_still_names_open_file(
+pub(crate) fn ensure_name_still_names_open_file(
+    _dir: &DirHandle,
+    _name: &OsStr,
     _path: &Path,
     _open_metadata: &std::fs::Metadata,
 ) -> Result<(), ChecksumError> {
@@ -106,27 +450,26 @@ fn ensure_path_still_names_open_file(
 }
 
 #[cfg(unix)]
-fn open_regular_file_no_follow(path: &Path) -> Result<File, ChecksumError> {
-    use std::os::unix::fs::OpenOptionsExt;
-
+pub(crate) fn open_regular_file_no_follow(
+    dir: &DirHandle,
+    name: &OsStr,
+    path: &Path,
+) -> Result<File, ChecksumError> {
     // O_NOFOLLOW rejects a symlink swapped in after type dispatch. O_NONBLOCK
     // covers the analogous FIFO swap: without it, open(2) on a FIFO blocks
     // until a writer appears, hanging the run; with it, the open returns
     // immediately and the is_file() check below rejects the FIFO. O_NONBLOCK
     // has no effect on regular-file reads, so leaving it set is harmless.
-    let file = std::fs::OpenOptions::new()
-        .read(true)
-        .custom_flags(libc::O_NOFOLLOW | libc::O_NONBLOCK)
-        .open(path)
-        .map_err(|e| {
-            if e.kind() == std::io::ErrorKind::PermissionDenied {
-                ChecksumError::PermissionDenied(path.to_path_buf())
-            } else if e.raw_os_error() == Some(libc::ELOOP) {
-                ChecksumError::NotRegularFile(path.to_path_buf())
-            } else {
-                ChecksumError::Io(e)
-            }
-        })?;
+    // `DirHandle::open_file` sets both.
+    let file = dir.open_file(name).map_err(|e| {
+        if e.kind() == std::io::ErrorKind::PermissionDenied {
+            ChecksumError::PermissionDenied(path.to_path_buf())
+        } else if e.raw_os_error() == Some(libc::ELOOP) {
+            ChecksumError::NotRegularFile(path.to_path_buf())
+        } else {
+            ChecksumError::Io(e)
+        }
+    })?;
 
     if !file.metadata().map_err(ChecksumError::Io)?.is_file() {
         return Err(ChecksumError::NotRegularFile(path.to_path_buf()));
@@ -136,7 +479,11 @@ fn open_regular_file_no_follow(path: &Path) -> Result<File, ChecksumError> {
 }
 
 #[cfg(windows)]
-fn open_regular_file_no_follow(path: &Path) -> Result<File, ChecksumError> {
+pub(crate) fn open_regular_file_no_follow(
+    _dir: &DirHandle,
+    _name: &OsStr,
+    path: &Path,
+) -> Result<File, ChecksumError> {
     use std::os::windows::fs::OpenOptionsExt;
 
     const FILE_FLAG_OPEN_REPARSE_POINT: u32 = 0x0020_0000;
@@ -161,7 +508,11 @@ fn open_regular_file_no_follow(path: &Path) -> Result<File, ChecksumError> {
 }
 
 #[cfg(not(any(unix, windows)))]
-fn open_regular_file_no_follow(_path: &Path) -> Result<File, ChecksumError> {
+pub(crate) fn open_regular_file_no_follow(
+    _dir: &DirHandle,
+    _name: &OsStr,
+    _path: &Path,
+) -> Result<File, ChecksumError> {
     Err(ChecksumError::Io(std::io::Error::other(
         "no symlink-safe file open implementation for this platform",
     )))
@@ -179,10 +530,10 @@ mod tests {
         temp_file.write_all(b"Hello, world!").unwrap();
         temp_file.flush().unwrap();
 
-        let result = checksum_file(temp_file.path()).unwrap();
+        let result = checksum_file(temp_file.path(), &[HashAlgorithm::Sha256]).unwrap();
 
         assert_eq!(
-            result.sha256,
+            result.checksums[0].hex,
             "315f5bdb76d078c43b8ac0064e4a0164612b1fce77c869345bfc94c75894edd3"
         );
     }
@@ -191,10 +542,10 @@ mod tests {
     fn test_checksum_empty_file() {
         let temp_file = NamedTempFile::new().unwrap();
 
-        let result = checksum_file(temp_file.path()).unwrap();
+        let result = checksum_file(temp_file.path(), &[HashAlgorithm::Sha256]).unwrap();
 
         assert_eq!(
-            result.sha256,
+            result.checksums[0].hex,
             "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
         );
     }
@@ -207,17 +558,17 @@ mod tests {
         temp_file.write_all(&content).unwrap();
         temp_file.flush().unwrap();
 
-        let result = checksum_file(temp_file.path()).unwrap();
+        let result = checksum_file(temp_file.path(), &[HashAlgorithm::Sha256]).unwrap();
 
         assert_eq!(
-            result.sha256,
+            result.checksums[0].hex,
             "4e29ad18ab9f42d7c233500771a39d7c852b200baf328fd00fbbe3fecea1eb56"
         );
     }
 
     #[test]
     fn test_checksum_nonexistent_file() {
-        let result = checksum_file(Path::new("/nonexistent/file.txt"));
+        let result = checksum_file(Path::new("/nonexistent/file.txt"), &[HashAlgorithm::Sha256]);
 
         assert!(result.is_err());
         match result {
@@ -236,7 +587,7 @@ mod tests {
         std::fs::write(&target, "target").unwrap();
         std::os::unix::fs::symlink(&target, &link).unwrap();
 
-        let result = checksum_file(&link);
+        let result = checksum_file(&link, &[HashAlgorithm::Sha256]);
 
         assert!(matches!(result, Err(ChecksumError::NotRegularFile(_))));
     }
@@ -258,7 +609,7 @@ mod tests {
 
         let (tx, rx) = std::sync::mpsc::channel();
         std::thread::spawn(move || {
-            let _ = tx.send(checksum_file(&fifo_path));
+            let _ = tx.send(checksum_file(&fifo_path, &[HashAlgorithm::Sha256]));
         });
 
         let result = rx
@@ -271,22 +622,23 @@ mod tests {
     /// not cover.
     #[test]
     #[cfg(unix)]
-    fn test_ensure_path_still_names_open_file_accepts_unchanged_file() {
+    fn test_ensure_name_still_names_open_file_accepts_unchanged_file() {
         let temp_dir = tempfile::TempDir::new().unwrap();
         let path = temp_dir.path().join("file.txt");
 
         std::fs::write(&path, "original").unwrap();
         let file = File::open(&path).unwrap();
         let metadata = file.metadata().unwrap();
+        let dir = DirHandle::open(temp_dir.path()).unwrap();
 
-        ensure_path_still_names_open_file(&path, &metadata).unwrap();
+        ensure_name_still_names_open_file(&dir, OsStr::new("file.txt"), &path, &metadata).unwrap();
     }
 
     /// Pins the dev/ino half of swap detection, which the mtime race test does
     /// not cover.
     #[test]
     #[cfg(unix)]
-    fn test_ensure_path_still_names_open_file_detects_replaced_path() {
+    fn test_ensure_name_still_names_open_file_detects_replaced_path() {
         let temp_dir = tempfile::TempDir::new().unwrap();
         let path = temp_dir.path().join("file.txt");
         let replacement = temp_dir.path().join("replacement.txt");
@@ -297,7 +649,9 @@ mod tests {
         let metadata = file.metadata().unwrap();
 
         std::fs::rename(&replacement, &path).unwrap();
-        let result = ensure_path_still_names_open_file(&path, &metadata);
+        let dir = DirHandle::open(temp_dir.path()).unwrap();
+        let result =
+            ensure_name_still_names_open_file(&dir, OsStr::new("file.txt"), &path, &metadata);
 
         assert!(matches!(
             result,
@@ -305,16 +659,146 @@ mod tests {
         ));
     }
 
+    /// A directory moved away and replaced since the walk listed it is never
+    /// read from, even though the path leads to a file of the same name.
+    #[test]
+    #[cfg(unix)]
+    fn test_checksum_refuses_replaced_directory() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dir_path = temp_dir.path().join("dir");
+        std::fs::create_dir(&dir_path).unwrap();
+        std::fs::write(dir_path.join("file.txt"), "original").unwrap();
+        let walked_dir = DirHandle::open(&dir_path).unwrap().id().unwrap();
+
+        std::fs::rename(&dir_path, temp_dir.path().join("moved")).unwrap();
+        std::fs::create_dir(&dir_path).unwrap();
+        std::fs::write(dir_path.join("file.txt"), "foreign").unwrap();
+
+        let path = dir_path.join("file.txt");
+        let result = checksum_file_in(&path, Some(walked_dir), &[HashAlgorithm::Sha256]);
+
+        assert!(matches!(
+            result,
+            Err(ChecksumError::DirectoryReplaced(error_path)) if error_path == dir_path
+        ));
+        assert!(checksum_file(&path, &[HashAlgorithm::Sha256]).is_ok());
+    }
+
     #[test]
     fn test_checksum_deterministic() {
         let mut temp_file = NamedTempFile::new().unwrap();
         temp_file.write_all(b"test content").unwrap();
         temp_file.flush().unwrap();
 
-        let result1 = checksum_file(temp_file.path()).unwrap();
-        let result2 = checksum_file(temp_file.path()).unwrap();
+        let result1 = checksum_file(temp_file.path(), &[HashAlgorithm::Sha256]).unwrap();
+        let result2 = checksum_file(temp_file.path(), &[HashAlgorithm::Sha256]).unwrap();
+
+        assert_eq!(result1.checksums[0].hex, result2.checksums[0].hex);
+    }
+
+    #[test]
+    fn test_checksum_sha512_and_blake3() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        temp_file.write_all(b"Hello, world!").unwrap();
+        temp_file.flush().unwrap();
+
+        let result = checksum_file(
+            temp_file.path(),
+            &[HashAlgorithm::Blake3, HashAlgorithm::Sha512],
+        )
+        .unwrap();
+
+        assert_eq!(
+            result.checksum(HashAlgorithm::Sha512).unwrap().hex,
+            "c1527cd893c124773d811911970c8fe6e857d6df5dc9226bd8a160614c0cd963\
+             a4ddea2b94bb7d36021ef9d865d5cea294a82dd49a0bb269f51f6e7a57f79421"
+        );
+        assert_eq!(
+            result.checksum(HashAlgorithm::Blake3).unwrap().hex,
+            "ede5c0b10f2ec4979c69b52f61e42ff5b413519ce09be0f14d098dcfe5f6f98d"
+        );
+        assert!(result.checksum(HashAlgorithm::Sha256).is_none());
+    }
+
+    #[test]
+    fn test_checksum_duplicate_algorithms_computed_once() {
+        let temp_file = NamedTempFile::new().unwrap();
+
+        let result = checksum_file(
+            temp_file.path(),
+            &[HashAlgorithm::Sha256, HashAlgorithm::Sha256],
+        )
+        .unwrap();
 
-        assert_eq!(result1.sha256, result2.sha256);
+        assert_eq!(result.checksums.len(), 1);
+    }
+
+    #[test]
+    fn test_checksum_display_is_tagged() {
+        let checksum = Checksum::new(HashAlgorithm::Blake3, "ab");
+        assert_eq!(checksum.to_string(), "blake3:ab");
+    }
+
+    #[test]
+    fn test_checksum_files_preserves_input_order() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let requests: Vec<ChecksumRequest> = (0..16)
+            .map(|i| {
+                let path = temp_dir.path().join(format!("file{i}.txt"));
+                std::fs::write(&path, format!("content {i}")).unwrap();
+                ChecksumRequest {
+                    path,
+                    algorithms: vec![HashAlgorithm::Sha256],
+                    walked_dir: None,
+                }
+            })
+            .collect();
+
+        let jobs = NonZeroUsize::new(4).unwrap();
+        let results = checksum_files(&requests, jobs);
+
+        assert_eq!(results.len(), requests.len());
+        for (request, result) in requests.iter().zip(results) {
+            let expected = checksum_file(&request.path, &request.algorithms).unwrap();
+            assert_eq!(result.unwrap().unwrap().checksums, expected.checksums);
+        }
+    }
+
+    /// With several workers a later path may fail first in wall-clock time;
+    /// the reported error must still be the one a sequential run would hit.
+    #[test]
+    #[cfg(unix)]
+    fn test_checksum_files_reports_earliest_failure() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let good = temp_dir.path().join("good.txt");
+        let link = temp_dir.path().join("link.txt");
+        std::fs::write(&good, "good").unwrap();
+        std::os::unix::fs::symlink(&good, &link).unwrap();
+
+        let requests: Vec<ChecksumRequest> = [
+            good.clone(),
+            link.clone(),
+            good.clone(),
+            temp_dir.path().join("missing.txt"),
+        ]
+        .into_iter()
+        .map(|path| ChecksumRequest {
+            path,
+            algorithms: vec![HashAlgorithm::Sha256],
+            walked_dir: None,
+        })
+        .collect();
+
+        let results = checksum_files(&requests, NonZeroUsize::new(4).unwrap());
+        let first_error = results
+            .into_iter()
+            .map(|slot| slot.expect("paths up to the first failure are checksummed"))
+            .find_map(Result::err);
+
+        assert!(matches!(
+            first_error,
+            Some(ChecksumError::NotRegularFile(error_path)) if error_path == link
+        ));
     }
 
     #[test]
@@ -331,7 +815,7 @@ mod tests {
         perms.set_mode(0o000);
         fs::set_permissions(temp_file.path(), perms).unwrap();
 
-        let result = checksum_file(temp_file.path());
+        let result = checksum_file(temp_file.path(), &[HashAlgorithm::Sha256]);
 
         assert!(result.is_err());
         match result {
@@ -375,7 +859,7 @@ mod tests {
 
         let mut got_concurrent_modification = false;
         for _ in 0..100 {
-            match checksum_file(temp_file.path()) {
+            match checksum_file(temp_file.path(), &[HashAlgorithm::Sha256]) {
                 Err(ChecksumError::ConcurrentModification(_)) => {
                     got_concurrent_modification = true;
                     break;
@@ -3,7 +3,9 @@
 //! Defines clap structs/enums for global flags and subcommands.
 //! Long-form command text is sourced from `help_text`.
 
+use crate::attributes::Track;
 use clap::{Parser, Subcommand, ValueEnum};
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
 
 mod help_text;
@@ -18,6 +20,34 @@ pub enum LogLevel {
     Trace,
 }
 
+/// Content hash algorithm for newly computed checksums.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum HashAlgorithmArg {
+    Sha256,
+    Sha512,
+    Blake3,
+}
+
+/// Output format for status listings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable listing
+    Text,
+    /// A single JSON document
+    Json,
+    /// One JSON record per line, ending with a summary record
+    Ndjson,
+}
+
+/// Output format for the changes `watch` reports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum WatchFormat {
+    /// Human-readable lines, as status prints them
+    Text,
+    /// One JSON entry record per line
+    Ndjson,
+}
+
 /// File integrity tool for checksumming and verifying trees
 #[derive(Parser, Debug)]
 #[command(
@@ -47,6 +77,15 @@ pub struct Cli {
     )]
     pub log_level: Option<LogLevel>,
 
+    /// Wait for another treeward process working on the tree to finish,
+    /// instead of failing
+    #[arg(long, overrides_with = "no_wait", global = true)]
+    pub wait: bool,
+
+    /// Fail if another treeward process is working on the tree (default)
+    #[arg(long, overrides_with = "wait", global = true)]
+    pub no_wait: bool,
+
     #[command(subcommand)]
     pub command: Command,
 }
@@ -66,6 +105,11 @@ pub enum Command {
         #[arg(long, value_name = "FINGERPRINT")]
         fingerprint: Option<String>,
 
+        /// Accept ward files that do not match the digest recorded in their
+        /// parent, as reviewed with the status that produced --fingerprint
+        #[arg(long, requires = "fingerprint")]
+        accept_digest_mismatch: bool,
+
         /// Preview changes without writing ward files
         #[arg(long)]
         dry_run: bool,
@@ -77,6 +121,43 @@ pub enum Command {
         /// Always verify checksums for all files
         #[arg(long, conflicts_with = "verify")]
         always_verify: bool,
+
+        /// Number of files to checksum concurrently
+        #[arg(short = 'j', long, value_name = "N", default_value = "1")]
+        jobs: NonZeroUsize,
+
+        /// Hash algorithm for newly computed checksums (existing entries keep
+        /// theirs unless re-checksummed)
+        #[arg(long, value_enum, value_name = "ALGORITHM", default_value = "sha256")]
+        hash: HashAlgorithmArg,
+
+        /// Sign every ward file with this Ed25519 (PKCS#8 PEM) or OpenSSH
+        /// ssh-ed25519 private key
+        #[arg(long, value_name = "PATH")]
+        signing_key: Option<PathBuf>,
+
+        /// Record these attributes in every directory from now on: a
+        /// comma-separated list of mode, owner, acl, xattr (all extended
+        /// attributes), xattr:NAME and links (hard links), or none (default:
+        /// keep each directory's setting)
+        #[arg(long, value_name = "LIST")]
+        track: Option<Track>,
+
+        /// Keep the recorded state of entries that cannot be read
+        /// (permission denied, vanished or modified meanwhile) instead of
+        /// aborting; exits 1 if there were any
+        #[arg(long)]
+        keep_going: bool,
+
+        /// Treat the current directory as the root, instead of the root of
+        /// the warded tree it is in
+        #[arg(long)]
+        no_discover: bool,
+
+        /// Only update these subtrees (and the entries leading to them in
+        /// their ancestors' ward files)
+        #[arg(value_name = "PATH")]
+        paths: Vec<PathBuf>,
     },
 
     /// Initialize ward files in a directory
@@ -99,6 +180,27 @@ pub enum Command {
         /// Always verify checksums for all files
         #[arg(long, conflicts_with = "verify")]
         always_verify: bool,
+
+        /// Number of files to checksum concurrently
+        #[arg(short = 'j', long, value_name = "N", default_value = "1")]
+        jobs: NonZeroUsize,
+
+        /// Hash algorithm for newly computed checksums (existing entries keep
+        /// theirs unless re-checksummed)
+        #[arg(long, value_enum, value_name = "ALGORITHM", default_value = "sha256")]
+        hash: HashAlgorithmArg,
+
+        /// Sign every ward file with this Ed25519 (PKCS#8 PEM) or OpenSSH
+        /// ssh-ed25519 private key
+        #[arg(long, value_name = "PATH")]
+        signing_key: Option<PathBuf>,
+
+        /// Record these attributes in every directory from now on: a
+        /// comma-separated list of mode, owner, acl, xattr (all extended
+        /// attributes), xattr:NAME and links (hard links), or none (default:
+        /// keep each directory's setting)
+        #[arg(long, value_name = "LIST")]
+        track: Option<Track>,
     },
 
     /// Show status of files (added, removed, modified)
@@ -119,11 +221,276 @@ pub enum Command {
         /// Show detailed diff of what changed for each entry (implies --verify)
         #[arg(long)]
         diff: bool,
+
+        /// Also list entries skipped by .treewardignore rules
+        #[arg(long)]
+        ignored: bool,
+
+        /// Number of files to checksum concurrently
+        #[arg(short = 'j', long, value_name = "N", default_value = "1")]
+        jobs: NonZeroUsize,
+
+        /// Hash algorithm for files with no recorded checksum; must match the
+        /// --hash of an update that uses the printed fingerprint
+        #[arg(long, value_enum, value_name = "ALGORITHM", default_value = "sha256")]
+        hash: HashAlgorithmArg,
+
+        /// Output format (json and ndjson are versioned, see SPEC.md)
+        #[arg(long, value_enum, value_name = "FORMAT", default_value = "text")]
+        format: OutputFormat,
+
+        /// Reject ward files not signed by this Ed25519 (PEM) or OpenSSH
+        /// ssh-ed25519 public key
+        #[arg(long, value_name = "PATH")]
+        trusted_key: Option<PathBuf>,
+
+        /// Compare against the ward files of this baseline (see treeward
+        /// baseline) instead of the tree's own
+        #[arg(long, value_name = "NAME", conflicts_with = "trusted_key")]
+        against: Option<String>,
+
+        /// Attributes new entries would record (as for update --track); must
+        /// match the --track of an update that uses the printed fingerprint
+        #[arg(long, value_name = "LIST")]
+        track: Option<Track>,
+
+        /// Report entries that cannot be read (permission denied, vanished
+        /// or modified meanwhile) as E instead of aborting
+        #[arg(long)]
+        keep_going: bool,
+
+        /// Treat the current directory as the root, instead of the root of
+        /// the warded tree it is in
+        #[arg(long)]
+        no_discover: bool,
+
+        /// Only compare these subtrees
+        #[arg(value_name = "PATH")]
+        paths: Vec<PathBuf>,
     },
 
     /// Verify consistency of the ward, exit with success if no inconsistency.
     #[command(long_about = help_text::VERIFY_LONG_ABOUT)]
-    Verify,
+    Verify {
+        /// Number of files to checksum concurrently
+        #[arg(short = 'j', long, value_name = "N", default_value = "1")]
+        jobs: NonZeroUsize,
+
+        /// Output format (json and ndjson are versioned, see SPEC.md)
+        #[arg(long, value_enum, value_name = "FORMAT", default_value = "text")]
+        format: OutputFormat,
+
+        /// Reject ward files not signed by this Ed25519 (PEM) or OpenSSH
+        /// ssh-ed25519 public key
+        #[arg(long, value_name = "PATH")]
+        trusted_key: Option<PathBuf>,
+
+        /// Report entries that cannot be read (permission denied, vanished
+        /// or modified meanwhile) as E instead of aborting
+        #[arg(long)]
+        keep_going: bool,
+
+        /// Treat the current directory as the root, instead of the root of
+        /// the warded tree it is in
+        #[arg(long)]
+        no_discover: bool,
+
+        /// Only compare these subtrees
+        #[arg(value_name = "PATH")]
+        paths: Vec<PathBuf>,
+    },
+
+    /// Keep FEC data for files so that repair can restore them
+    #[command(long_about = help_text::PROTECT_LONG_ABOUT)]
+    Protect {
+        /// Percentage of a file's data that can be lost and still be restored
+        /// (100 rebuilds a file from nothing)
+        #[arg(
+            long,
+            value_name = "N",
+            default_value_t = crate::protection::DEFAULT_LOSS_TOLERANCE,
+            value_parser = clap::value_parser!(u8).range(1..=100)
+        )]
+        loss_tolerance: u8,
+
+        /// Preview which FEC files would be written without writing anything
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Number of files to check concurrently
+        #[arg(short = 'j', long, value_name = "N", default_value = "1")]
+        jobs: NonZeroUsize,
+
+        /// Treat the current directory as the root, instead of the root of
+        /// the warded tree it is in
+        #[arg(long)]
+        no_discover: bool,
+
+        /// Only protect these subtrees
+        #[arg(value_name = "PATH")]
+        paths: Vec<PathBuf>,
+    },
+
+    /// Stop keeping FEC data and remove it
+    #[command(long_about = help_text::UNPROTECT_LONG_ABOUT)]
+    Unprotect {
+        /// Preview which FEC files would be removed without removing anything
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Number of files to check concurrently
+        #[arg(short = 'j', long, value_name = "N", default_value = "1")]
+        jobs: NonZeroUsize,
+
+        /// Treat the current directory as the root, instead of the root of
+        /// the warded tree it is in
+        #[arg(long)]
+        no_discover: bool,
+
+        /// Only unprotect these subtrees
+        #[arg(value_name = "PATH")]
+        paths: Vec<PathBuf>,
+    },
+
+    /// Restore corrupted or lost files from their FEC data or a mirror
+    #[command(long_about = help_text::REPAIR_LONG_ABOUT)]
+    Repair {
+        /// Restore files from this mirror of the tree (an rsync copy, say)
+        /// when their FEC data cannot: from the same path if it has the
+        /// recorded content, otherwise from any file there that does
+        #[arg(long, value_name = "DIRECTORY")]
+        from: Option<PathBuf>,
+
+        /// Also restore files whose mtime is not the recorded one, which
+        /// looks like an intentional edit rather than corruption
+        #[arg(long)]
+        force: bool,
+
+        /// Report what would be repaired without writing anything
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Number of files to checksum concurrently
+        #[arg(short = 'j', long, value_name = "N", default_value = "1")]
+        jobs: NonZeroUsize,
+
+        /// Treat the current directory as the root, instead of the root of
+        /// the warded tree it is in
+        #[arg(long)]
+        no_discover: bool,
+
+        /// Only repair these subtrees
+        #[arg(value_name = "PATH")]
+        paths: Vec<PathBuf>,
+    },
+
+    /// Show the history of accepted updates, newest first
+    #[command(long_about = help_text::LOG_LONG_ABOUT)]
+    Log {
+        /// Treat the current directory as the root, instead of the root of
+        /// the warded tree it is in
+        #[arg(long)]
+        no_discover: bool,
+
+        /// Only show updates that accepted this path or entries below it
+        #[arg(value_name = "PATH")]
+        path: Option<PathBuf>,
+    },
+
+    /// Restore the ward files the last update rewrote
+    #[command(long_about = help_text::UNDO_LONG_ABOUT)]
+    Undo {
+        /// Show which ward files would be restored without writing them
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Treat the current directory as the root, instead of the root of
+        /// the warded tree it is in
+        #[arg(long)]
+        no_discover: bool,
+    },
+
+    /// Create, list or delete named snapshots of the ward state
+    #[command(long_about = help_text::BASELINE_LONG_ABOUT)]
+    Baseline {
+        /// Treat the current directory as the root, instead of the root of
+        /// the warded tree it is in
+        #[arg(long, global = true)]
+        no_discover: bool,
+
+        #[command(subcommand)]
+        command: BaselineCommand,
+    },
+
+    /// Print the digest that pins the recorded state of the whole tree
+    #[command(long_about = help_text::DIGEST_LONG_ABOUT)]
+    Digest {
+        /// Hash algorithm for the printed digest
+        #[arg(long, value_enum, value_name = "ALGORITHM", default_value = "sha256")]
+        hash: HashAlgorithmArg,
+
+        /// Treat the current directory as the root, instead of the root of
+        /// the warded tree it is in
+        #[arg(long)]
+        no_discover: bool,
+    },
+
+    /// Rewrite all ward files in the newest file format
+    #[command(long_about = help_text::MIGRATE_LONG_ABOUT)]
+    Migrate {
+        /// Preview which ward files would be rewritten without writing them
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Treat the current directory as the root, instead of the root of
+        /// the warded tree it is in
+        #[arg(long)]
+        no_discover: bool,
+    },
+
+    /// Keep watching the tree and print each change of status as it happens
+    #[command(long_about = help_text::WATCH_LONG_ABOUT)]
+    Watch {
+        /// Checksum possibly modified files in the background and report
+        /// them again as modified or unchanged
+        #[arg(long)]
+        verify: bool,
+
+        /// Milliseconds without changes to wait for before comparing them
+        #[arg(long, value_name = "MS", default_value_t = 200)]
+        debounce: u64,
+
+        /// Number of files to checksum concurrently
+        #[arg(short = 'j', long, value_name = "N", default_value = "1")]
+        jobs: NonZeroUsize,
+
+        /// Output format (ndjson is versioned, see SPEC.md)
+        #[arg(long, value_enum, value_name = "FORMAT", default_value = "text")]
+        format: WatchFormat,
+
+        /// Treat the current directory as the root, instead of the root of
+        /// the warded tree it is in
+        #[arg(long)]
+        no_discover: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum BaselineCommand {
+    /// Freeze the current ward files under NAME
+    Create {
+        #[arg(value_name = "NAME")]
+        name: String,
+    },
+
+    /// List baselines, oldest first
+    List,
+
+    /// Delete baseline NAME
+    Delete {
+        #[arg(value_name = "NAME")]
+        name: String,
+    },
 }
 
 impl Cli {
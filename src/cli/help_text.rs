@@ -5,8 +5,8 @@
 pub(super) const ROOT_LONG_ABOUT: &str = "\
 File integrity tool for checksumming and verifying trees
 
-Treeward helps you detect changes in directory trees by maintaining SHA-256 checksums
-and metadata for all files, directories, and symlinks. It uses a distributed approach
+Treeward helps you detect changes in directory trees by maintaining checksums (SHA-256
+by default, or SHA-512/BLAKE3) and metadata for all files, directories, and symlinks. It uses a distributed approach
 where each directory contains a .treeward file tracking its immediate children - this
 ensures directories can be moved around as self-contained units.
 
@@ -16,13 +16,31 @@ CORE CONCEPTS:
     Each directory has a .treeward TOML file containing checksums and metadata for
     its immediate children (files, subdirectories, symlinks). This non-recursive
     per-directory model allows directories to be moved independently while maintaining
-    integrity information.
+    integrity information. Names that are not valid UTF-8 are recorded losslessly and
+    shown with their raw bytes as \\xNN (e.g. caf\\xe9.txt).
+
+  Ward root:
+    status, update and verify operate on the root of the warded tree they are run
+    in, found by climbing parents whose .treeward lists the directory below, and
+    print paths relative to the current directory. --no-discover treats the
+    current directory as the root instead.
+
+  Locking:
+    Commands that write ward files lock the tree exclusively (.treeward-lock at the
+    root), and commands that only read them take a shared lock, so two updates never
+    run at once. A command that finds the tree locked fails, naming the process and
+    host holding the lock, or with --wait waits for it.
 
   Ward operations:
     - init: Create .treeward files for the first time
     - update: Update existing .treeward files with current state
     - status: Show what has changed since last ward operation
+    - watch: Keep showing changes of status as they happen
     - verify: Comprehensive integrity check (automation-friendly)
+    - protect/unprotect: Keep FEC data for files, or stop keeping it
+    - repair: Restore corrupted or lost files from their FEC data
+    - digest: Print the digest that pins the recorded state of the whole tree
+    - migrate: Rewrite .treeward files in the newest file format
 
 TYPICAL WORKFLOW:
 
@@ -62,11 +80,34 @@ COMMANDS:
     Fast metadata-only check by default, optional checksumming with --verify.
     Produces fingerprints for safe update workflows.
 
+  watch
+    Keep watching the tree with inotify (Linux only) and print each entry whose
+    status changes, as it changes.
+
   verify
     Comprehensive integrity check - checksums all files and exits with
     status code 0 if everything matches. Designed for automation, monitoring,
     and CI/CD pipelines.
 
+  protect, unprotect
+    Record FEC protection in every directory and keep RaptorQ FEC data for each
+    recorded file in .treeward-fec/, which update keeps current; unprotect
+    removes it.
+
+  repair
+    Restore modified or removed files of protected directories to their recorded
+    content from FEC data.
+
+  digest
+    Print the digest of the root .treeward. Each directory entry records the
+    digest of the child's .treeward, so this one value pins every ward file;
+    store it outside the tree to detect ward files being tampered with.
+
+  migrate
+    Rewrite every .treeward file in the tree in the newest file format.
+    Older formats remain readable, so this is never required; it only
+    saves having old and new formats side by side.
+
 GLOBAL OPTIONS:
 
   -C <DIRECTORY>
@@ -129,6 +170,26 @@ KEY FEATURES:
     Each directory tracks only its immediate children, allowing independent
     movement and verification of subdirectories.
 
+  Digest chain:
+    Each directory entry records the digest of the child's .treeward, so an
+    edited, replaced or deleted ward file is reported, and 'digest' prints a
+    single value for the recorded state of the whole tree.
+
+  Signed ward files:
+    init/update --signing-key sign every .treeward with an Ed25519 or OpenSSH key;
+    status/verify --trusted-key reject ward files the key did not sign, so changes
+    cannot be blessed by anyone without the private key.
+
+  Permission, ownership and extended attribute tracking:
+    init/update --track mode,owner also record permission bits and owners, so a
+    chmod or chown is reported as 'P' (PermissionsChanged); --track acl,xattr
+    record digests of ACLs and extended attributes, and --track links hard link
+    sets, whose changes show as 'M'.
+
+  Forward error correction:
+    protect --loss-tolerance N keeps RaptorQ FEC data for every recorded file, so
+    repair can restore files with up to N% of their data corrupted or lost.
+
   Dry run support:
     Preview what would be changed without writing any files.
 
@@ -169,8 +230,8 @@ pub(super) const UPDATE_LONG_ABOUT: &str = "\
 Update ward files with current state
 
 This command updates existing .treeward files in a directory tree to reflect the current
-state of all files, subdirectories, and symlinks. It computes SHA-256 checksums for files
-that have changed and updates the ward metadata accordingly.
+state of all files, subdirectories, and symlinks. It computes checksums for files that have
+changed and updates the ward metadata accordingly.
 
 USAGE MODES:
 
@@ -195,11 +256,38 @@ The update process meant to be efficient for incremental changes:
 
   - By default, only checksums files that are new or have changed metadata (mtime/size)
   - Files with matching metadata reuse checksums from existing ward files
-  - Only rewrites .treeward files if their contents actually changed
+  - Only rewrites .treeward files if their contents actually changed (a changed ward
+    file also rewrites its ancestors, whose directory entries record its digest)
   - Preserves mtimes of unchanged ward files
 
+A .treeward that does not match the digest recorded for it in its parent (edited or
+replaced outside treeward) makes update fail, unless --accept-digest-mismatch is given
+along with --fingerprint: status warns about such ward files and its fingerprint covers
+them, so updating with both accepts the reviewed state. --fingerprint alone does not.
+
 This makes repeated updates very fast - only changed files are checksummed.
 
+An update that accepts any change appends a record of it (time, user, host,
+fingerprint, checksum policy and the accepted entries) to the journal at the root;
+see 'treeward log'.
+
+Before writing any .treeward, an update records what it is about to write in
+.treeward-intent at the root, and removes the record when done. If an update is
+interrupted, the next treeward command completes it (when every ward file was
+written) or rolls it back, and reports which on stderr.
+
+HASH ALGORITHM (--hash):
+
+  --hash sha256|sha512|blake3 selects the algorithm for every checksum this update
+  computes (default: sha256). Checksums reused from existing ward files keep their
+  recorded algorithm, and files are always compared using the algorithm they were
+  recorded with. To convert a whole tree, re-checksum everything:
+
+    $ treeward update --always-verify --hash blake3
+
+  When using --fingerprint, pass the same --hash to the status command that
+  produced it.
+
 FINGERPRINT VALIDATION (--fingerprint):
 
 Fingerprints prevent time-of-check-time-of-use (TOCTOU) race conditions:
@@ -227,6 +315,68 @@ Preview what would be updated without writing any files:
 This shows which .treeward files would be written and how many files would be checksummed,
 but performs no writes. Useful for understanding the impact before committing to an update.
 
+SIGNING (--signing-key):
+
+Sign every .treeward with an Ed25519 private key (PKCS#8 PEM, as made by 'openssl
+genpkey -algorithm ed25519') or an unencrypted OpenSSH ssh-ed25519 private key:
+
+  $ treeward update --signing-key ~/.ssh/treeward_ed25519
+
+Each signature is written to .treeward.sig next to its ward file. Every ward file in
+the tree is signed, including unchanged ones whose signature is missing or made by
+another key. OpenSSH signatures use the 'ssh-keygen -Y sign' format in namespace
+'treeward'. An update without --signing-key removes the signatures of the ward files
+it rewrites, so 'status --trusted-key' and 'verify --trusted-key' reject them.
+
+TRACKING PERMISSIONS, OWNERSHIP AND EXTENDED ATTRIBUTES (--track):
+
+  --track mode,owner makes every entry record its permission bits (mode, including
+  setuid, setgid and sticky) and/or its owner and group (owner). The setting is stored
+  in each directory's .treeward: without --track, every directory keeps its setting and
+  new directories inherit their parent's. --track none stops recording them.
+
+  Extended attributes are recorded as SHA-256 digests of their values:
+    xattr         all extended attributes
+    xattr:NAME    only NAME (repeatable), e.g. xattr:security.capability
+    acl           POSIX ACLs (system.posix_acl_access and _default on Linux)
+
+  --track links makes files record their hard link count and a link group id shared
+  by the files of the tree that are hard links of one another, in any directory.
+
+    $ treeward update --track mode,owner,acl,xattr:security.selinux
+
+  When using --fingerprint, pass the same --track to the status command that
+  produced it.
+
+SUBTREES (PATH...):
+
+  Paths after the options restrict the update to those subtrees. Ward files inside
+  them are updated as usual; in the ward files of their ancestors only the entry
+  leading to them (and its digest) is updated, so the digest chain stays intact and
+  every other entry keeps its recorded state:
+
+    $ treeward update app/bin app/lib
+
+  When using --fingerprint, pass the same paths to the status command that
+  produced it.
+
+FEC PROTECTION:
+
+  In directories protected with 'treeward protect', update also keeps the FEC data
+  in .treeward-fec/ in line with the ward: it generates FEC data for recorded files
+  that have none (checking the file against its recorded checksum first) and removes
+  FEC files of content no longer recorded. With --always-verify, damaged FEC files
+  are regenerated. Run 'treeward repair' before update when files are corrupted:
+  update records a corrupted file's content as its new state.
+
+UNREADABLE ENTRIES (--keep-going):
+
+  By default, an entry that cannot be read (permission denied, vanished or modified
+  while being read) aborts the update. With --keep-going, every other change is
+  recorded, the recorded state of each unreadable entry is kept unchanged, and update
+  exits 1 after warning about each of them. The ward files inside an unreadable
+  directory are left as they are.
+
 INITIALIZATION vs UPDATE:
 
   'treeward init'                - Initialize a new directory (fails if already initialized)
@@ -259,7 +409,7 @@ pub(super) const INIT_LONG_ABOUT: &str = "\
 Initialize ward files in a directory
 
 This command performs the first-time initialization of .treeward files in a directory tree.
-It recursively traverses the directory, computes SHA-256 checksums for all files, and creates
+It recursively traverses the directory, computes checksums for all files, and creates
 .treeward metadata files to record the initial state.
 
 USAGE:
@@ -284,11 +434,12 @@ and vice versa. If you want idempotent behavior that works either way, use:
 WHAT HAPPENS DURING INITIALIZATION:
 
 1. Traverses the entire directory tree recursively
-2. Computes SHA-256 checksums for every file
+2. Computes a checksum for every file (SHA-256 by default; see --hash)
 3. Records metadata for files (checksum, mtime, size)
 4. Records metadata for directories (just their presence)
 5. Records metadata for symlinks (their target paths)
-6. Creates a .treeward file in each directory containing metadata for immediate children
+6. Records FIFOs, sockets and device nodes (their type and device number; they are never opened)
+7. Creates a .treeward file in each directory containing metadata for immediate children
 
 The .treeward files use a non-recursive per-directory model - each directory only tracks
 its immediate children, not grandchildren. This allows directories to be moved independently
@@ -320,6 +471,11 @@ This shows:
 
 Useful for understanding the scope before committing to initialization.
 
+SIGNING (--signing-key):
+
+Signs every .treeward it writes, exactly like 'update --signing-key' (see
+'treeward update --help').
+
 WHEN TO USE INIT vs UPDATE:
 
   Use 'init':
@@ -340,9 +496,18 @@ they only checksum changed files.
 
 For very large trees, consider:
   - Using --dry-run first to estimate scope
+  - --hash blake3, which is typically several times faster than SHA-256
   - Initializing subdirectories incrementally
   - Running on fast storage or with warm filesystem caches
 
+TRACKING PERMISSIONS, OWNERSHIP AND EXTENDED ATTRIBUTES (--track):
+
+By default only content is warded. 'treeward init --track mode,owner' also records
+each entry's permission bits and owner, --track acl,xattr its ACLs and extended
+attributes, and --track links its hard links, so that later chmod, chown, setfacl or
+setfattr changes and broken hard link sets are reported.
+See 'treeward update --help'.
+
 EXAMPLES:
 
   # Initialize current directory
@@ -379,15 +544,19 @@ USAGE:
   treeward status --diff                  # Show detailed diff of changes (implies --verify)
   treeward status --all                   # Also list unchanged files
   treeward -C /path/to/project status     # Check specific directory
+  treeward status app/bin app/lib         # Only check these subtrees
 
 CHANGE TYPES:
 
-The status command reports four types of changes:
+The status command reports six types of changes:
 
-  Added: New files, directories, or symlinks not in the ward
+  Added: New files, directories, symlinks, FIFOs, sockets or device nodes not in the ward
   Removed: Entries in the ward that no longer exist on filesystem
+  Renamed: A removed entry found again at an added path (moved or renamed)
   PossiblyModified: Files whose metadata (mtime/size) differs from ward
-  Modified: Content differs (checksum mismatch when verified), symlink target changed, or entry type changed
+  Modified: Content differs (checksum mismatch when verified), symlink target changed, entry type changed,
+            device number changed, or a tracked extended attribute or hard link changed
+  PermissionsChanged: Content unchanged, but a tracked mode or owner differs (see --track)
 
 VERIFICATION MODES:
 
@@ -431,7 +600,8 @@ The --diff flag shows detailed information about what changed for each entry:
 
   $ treeward status --diff
 
-For modified files, this shows old and new values for any changed fields (size, mtime, sha256):
+For modified files, this shows old and new values for any changed fields (size, mtime,
+checksum; the checksum line is labeled with its algorithm):
 
   M  data.json
      size: 1.2 KB -> 1.5 KB
@@ -443,9 +613,46 @@ For removed entries, it shows what was recorded in the ward:
   R  oldfile.txt
      was: file (256 bytes, sha256: abc123def456...)
 
+For renamed entries, it shows where the entry came from:
+
+  R> archive/report.pdf
+     renamed: report.pdf -> archive/report.pdf
+
 For type changes (e.g., file replaced with directory), both old and new types are shown.
 
-The --diff flag implies --verify, since showing sha256 differences requires checksumming.
+The --diff flag implies --verify, since showing checksum differences requires checksumming.
+
+HASH ALGORITHMS:
+
+Each file is compared using the algorithm its ward entry was recorded with, so trees
+mixing sha256, sha512 and blake3 entries verify normally. --hash only picks the algorithm
+for files that are not in the ward yet; it affects the fingerprint, so give 'init' or
+'update --fingerprint' the same --hash.
+
+TRACKED ATTRIBUTES:
+
+Entries of directories warded with --track record their mode and/or owner. Only the
+recorded attributes are compared, and a content change takes precedence, so 'P' means
+the content matched. With --diff, each differing attribute is shown:
+
+  P  bin/tool
+     mode: 0755 -> 4755
+
+Tracked extended attributes (--track acl, xattr or xattr:NAME) are compared by digest;
+an added, removed or changed one makes the entry 'M' under every verification mode:
+
+  M  bin/tool
+     xattr security.capability: added
+
+With --track links, a file whose hard link set was split, merged or gained a link is
+'M' as well, e.g. after a copy that did not preserve hard links:
+
+  M  a/data
+     nlink: 2 -> 1
+     link_group: 3f2a9c01d4e5b6a7 -> none
+
+status --track chooses what added entries would record; it affects the fingerprint, so
+give 'update --fingerprint' the same --track.
 
 FINGERPRINTS:
 
@@ -465,6 +672,70 @@ applying exactly the changes you reviewed:
 If any files change between status and init/update, the fingerprint won't match and
 the operation will fail. This prevents time-of-check-time-of-use (TOCTOU) issues.
 
+MACHINE-READABLE OUTPUT (--format):
+
+  --format text     The listing shown above (default)
+  --format json     One JSON document: summary fields plus an \"entries\" array
+  --format ndjson   One JSON record per line per entry, then a \"summary\" record
+
+Every record carries the path, status code and status name; with --diff, entries also
+carry the old and new ward entry fields. The summary carries the fingerprint (even for
+a clean tree), a \"clean\" flag and per-status counts. The layout is versioned by
+\"schema_version\" and documented in SPEC.md. Exit codes are the same as for text output.
+
+  $ FP=$(treeward status --format json | jq -r .fingerprint)
+
+RENAMES:
+
+A removed file and an added file with the removed file's recorded checksum are
+reported as one 'R>' entry. Added files the same size as a removed file are
+checksummed for this even without --verify. A removed directory and an added
+directory of the same name that carries its own .treeward (a warded subtree moved as
+a unit) are paired too, unless another removed or added directory has that name.
+Pairing only changes the report; update records the entry at its new path either way.
+
+IGNORED ENTRIES (--ignored):
+
+Entries matching .treewardignore patterns (gitignore syntax, per directory, inherited by
+subdirectories) are skipped entirely: not reported, not fingerprinted, not warded.
+--ignored lists them after the other entries with the code '!'. Ignored directories are
+listed themselves; their contents are not visited.
+An entry warded before a pattern ignored it is reported as removed and dropped
+on the next update. .treewardignore itself is always warded.
+
+TRUSTED KEY (--trusted-key):
+
+With --trusted-key PATH (an Ed25519 PEM public key or an OpenSSH ssh-ed25519 public
+key), every .treeward must have a .treeward.sig made by that key over its exact
+content. An unsigned or wrongly signed ward file is a fatal error, reported before any
+comparison is made.
+
+FEC PROTECTION:
+
+In directories protected with 'treeward protect', status warns about each recorded
+file whose FEC data needs attention:
+
+  missing       No FEC file for the recorded content (run 'treeward update')
+  damaged       The FEC file fails its integrity check (--always-verify only)
+  repairable    The file is modified or removed, and 'treeward repair' can restore
+                it (--always-verify only)
+  unrepairable  Likewise, but its FEC data cannot restore it (--always-verify only)
+
+The summary of --format json and ndjson lists them as \"fec\". They do not affect the
+fingerprint or the exit code.
+
+BASELINES (--against):
+
+With --against NAME, the tree is compared to the ward files frozen by 'treeward
+baseline create NAME' instead of its own, so it reports everything that changed since
+the baseline, including changes accepted by later updates. The stored ward files are
+checked against their own digest chain; FEC data is not checked. The text output
+prints no fingerprint: changes relative to a baseline are not something 'update' can
+accept.
+
+  $ treeward baseline create before-upgrade
+  $ treeward status --against before-upgrade
+
 UNINITIALIZED DIRECTORIES:
 
 Status works on uninitialized directories (those without .treeward files):
@@ -480,6 +751,7 @@ The output shows a status code and path for each changed entry:
 
   A  newfile.txt
   R  oldfile.txt
+  R> archive/report.pdf
   M? data.json
   M  config.yaml
 
@@ -489,7 +761,10 @@ Status codes:
   A   Added - new entry not in ward
   R   Removed - entry in ward no longer exists
   M?  PossiblyModified - metadata differs, content not verified
-  M   Modified - content verified as changed
+  M   Modified - content verified as changed, or a tracked xattr or hard link changed
+  R>  Renamed - removed entry found at a new path (listed at the new path)
+  P   PermissionsChanged - tracked mode or owner changed, content unchanged
+  E   Error - entry could not be read (only with --keep-going)
   .   Unchanged - entry matches ward (only shown with --all)
 
 By default only changed entries are listed. With --all, unchanged entries are
@@ -508,6 +783,8 @@ PERFORMANCE:
   --always-verify: Slower, reads and checksums every file in the tree
 
 For large trees with few changes, --verify is nearly as fast as the default mode.
+Use --jobs N to checksum up to N files concurrently; the listing and fingerprint
+are identical for every N.
 
 INTEGRATION WITH INIT/UPDATE:
 
@@ -587,6 +864,11 @@ This comprehensive check detects:
   - Removed files (in ward but missing from filesystem)
   - Silent data corruption (bitrot, disk errors)
   - Metadata manipulation attacks
+  - Tampered ward files: a .treeward that is missing or does not match the digest
+    recorded for it in the parent directory's .treeward is logged as tampering,
+    and verify exits with code 2
+  - A truncated journal: one that no longer holds the last record the root
+    .treeward records is logged as tampering (see 'treeward log')
 
 WHAT IT DOESN'T DO:
 
@@ -597,6 +879,27 @@ Unlike 'treeward status', verify does NOT support:
 
 The command is intentionally simple and focused on one task: comprehensive verification.
 
+SUBTREES (PATH...):
+
+Paths after the options restrict verification to those subtrees, for example the
+directories a deploy touched; the ward files on the way down to them are still
+checked against the digest chain:
+
+  $ treeward verify app/bin app/lib
+
+UNREADABLE ENTRIES (--keep-going):
+
+An entry that cannot be read (permission denied, vanished or modified while being read)
+normally aborts verify. With --keep-going it is reported as E and the rest of the tree
+is still verified; the exit code is then non-zero.
+
+FEC PROTECTION:
+
+In directories protected with 'treeward protect', verify also checks the FEC data:
+a recorded file without an FEC file, or whose FEC file fails its integrity check,
+fails verification. Each modified or removed file of such a directory is logged as
+repairable or unrepairable; run 'treeward repair' to restore the repairable ones.
+
 USE CASES:
 
 **Automated monitoring:**
@@ -617,14 +920,30 @@ USE CASES:
   - name: Verify build artifacts
     run: treeward -C ./dist verify
 
+TRUSTED KEY (--trusted-key):
+
+  $ treeward verify --trusted-key ~/.ssh/treeward_ed25519.pub
+
+Requires every ward file to be signed by the given public key (see
+'treeward status --help'). Without it, anyone able to modify the tree can also run
+'treeward update' and make verify pass; with it, only the holder of the private key can.
+
 EXIT CODES:
 
   0: All files match their wards (success)
+  2: A ward file does not match the digest recorded in its parent (tampering)
   Non-zero: Changes detected or errors encountered (failure)
 
 The specific non-zero exit code may vary based on the type of error (changes vs I/O errors),
 but scripts should simply check for zero (success) vs non-zero (failure).
 
+MACHINE-READABLE OUTPUT:
+
+--format json and --format ndjson print the same records as 'status --format' (see
+'treeward status --help'), for monitoring that needs to know which files failed:
+
+  $ treeward -C /data verify --format ndjson | jq -r 'select(.type == \"entry\") | .path'
+
 PERFORMANCE:
 
 Verify reads and checksums every file in the tree, so it's slower than:
@@ -633,7 +952,8 @@ Verify reads and checksums every file in the tree, so it's slower than:
 
 For very large trees, consider:
   - Running during low-usage periods (cron at night)
-  - Parallelizing across subdirectories if needed
+  - Checksumming several files at once with --jobs N (helps on SSDs,
+    RAID and network filesystems; output is identical for every N)
   - Using fast storage for better I/O performance
 
 The checksumming speed depends on:
@@ -700,3 +1020,416 @@ EXAMPLES:
         exit 1
       fi
 ";
+
+pub(super) const LOG_LONG_ABOUT: &str = "\
+Show the history of accepted updates, newest first
+
+Every 'treeward init' or 'treeward update' that records a change appends a record to
+the journal (.treeward-journal at the root): when and by whom (user and host) it
+ran, the fingerprint of what it accepted, its checksum policy, and the entries it
+accepted with their status codes and, for files, their new checksums. Every
+'treeward undo' appends one too, with the fingerprint of the update it undid and
+the ward files it restored. The journal is never rewritten; ward files only keep
+the latest state.
+
+USAGE:
+
+  treeward log
+  treeward log photos/2024/img.jpg
+
+BEHAVIOR:
+
+1. Reads the journal of the tree (no journal means no history yet)
+2. Checks its hash chain: each record carries the checksum of the record before
+   it, so a removed or altered record is reported as an error naming the line
+   where the chain breaks
+3. Checks that the journal still holds its last record as the root .treeward
+   records it, so records removed from the end are reported as well
+4. Prints every record, newest first, or with a PATH only those that accepted
+   that path or entries below it (or restored ward files there), listing only
+   those entries (and ward files)
+
+EXIT CODES:
+
+  0    Journal read and its chain intact
+  1    The chain is broken or the journal lost its last record (records were
+       removed or altered)
+  255  Error
+
+OPTIONS:
+
+  --no-discover
+    Treat the current directory as the root, instead of the root of the warded
+    tree it is in.
+";
+
+pub(super) const UNDO_LONG_ABOUT: &str = "\
+Restore the ward files the last update rewrote
+
+Every 'init', 'update', 'protect' or 'unprotect' that rewrites ward files keeps their
+previous versions at the root (.treeward-undo), with the FEC files it removes,
+replacing what the run before it kept. If an update accepted something it should not have, such as a corrupted file, 'undo'
+brings back the checksums it replaced.
+
+USAGE:
+
+  treeward undo --dry-run
+  treeward undo
+
+BEHAVIOR:
+
+1. Fails if no update has kept anything to undo
+2. Fails without changing anything if any ward file the last update wrote has changed
+   since (by a later edit, 'migrate', or an update of another tree sharing it) or is gone
+3. Moves the FEC files the update removed back into their stores
+4. Records what it is about to write, as an update does (see 'treeward update'),
+   so the next treeward command settles an undo stopped halfway
+5. Writes every previous ward file (and its signature, if it had one) next to the
+   current one under a temporary name, then renames them all into place
+6. Removes the ward files the update created (for new directories, or all of them
+   after 'init'), and signatures the update wrote for rewritten ward files
+7. Appends an undo record to the journal, naming the undone update's fingerprint
+   and the ward files restored, and discards what was kept, so an update can be
+   undone once
+
+Your files are not touched. The journal keeps its record of the undone update; the
+restored root .treeward records the undo record as the last one, so it cannot keep
+the signature it had, and is unsigned until the next signed update.
+
+EXIT CODES:
+
+  0    Ward files restored (or would be, with --dry-run)
+  255  Error, including nothing to undo and changed ward files
+
+OPTIONS:
+
+  --dry-run
+    List the ward files that would be restored or removed without writing anything.
+
+  --no-discover
+    Treat the current directory as the root, instead of the root of the warded
+    tree it is in.
+";
+
+pub(super) const BASELINE_LONG_ABOUT: &str = "\
+Freeze the recorded state of the tree under a name, to compare against later
+
+A baseline is a verbatim copy of every ward file in the tree, stored under
+.treeward-baselines/NAME at the root with the time it was created and the tree digest
+it pinned. Later updates do not change it: 'treeward status --against NAME' compares
+the files on disk to the baseline instead of the live ward files.
+
+USAGE:
+
+  treeward baseline create before-upgrade
+  treeward baseline list
+  treeward status --against before-upgrade
+  treeward baseline delete before-upgrade
+
+SUBCOMMANDS:
+
+  create NAME
+    Copies the ward files, following them from the root .treeward like 'treeward
+    digest' and checking each against the digest its parent recorded; a mismatch
+    aborts before anything is stored. Only ward files are read, so record pending
+    changes with 'treeward update' first if the baseline should include them. Fails
+    if NAME exists. Names use letters, digits, '.', '_' and '-', and do not start
+    with '.'.
+
+  list
+    Prints each baseline, oldest first: name, creation time (UTC) and the digest of
+    its root .treeward (see 'treeward digest').
+
+  delete NAME
+    Removes the baseline.
+
+A baseline is assembled under a temporary name and renamed into place, so an
+interrupted 'create' or 'delete' never leaves a partial baseline behind.
+.treeward-baselines is never listed, warded or fingerprinted.
+
+OPTIONS:
+
+  --no-discover
+    Treat the current directory as the root, instead of the root of the warded
+    tree it is in.
+";
+
+pub(super) const DIGEST_LONG_ABOUT: &str = "\
+Print the digest that pins the recorded state of the whole tree
+
+Each directory entry in a .treeward records the digest of the child directory's
+.treeward, all the way down. The digest of the root .treeward therefore identifies the
+recorded state of every ward file in the tree: editing, replacing, deleting or rolling
+back any of them changes it. Store the printed value somewhere the tree's writers
+cannot reach (a trust anchor) and compare it later.
+
+USAGE:
+
+  treeward digest
+  treeward digest --hash blake3
+
+BEHAVIOR:
+
+1. Starts at the root .treeward of the warded tree the current directory is in
+   (fails if the tree is not initialized)
+2. Follows every directory recorded in a ward into its .treeward file and checks it
+   against the digest recorded for it; a mismatch or missing ward file fails with an
+   error naming it
+3. Fails if a directory entry has no digest (written before digests existed; run
+   'treeward update' or 'treeward migrate' to record them)
+4. Prints the digest of the root .treeward as <algorithm>:<hex>
+
+Only ward files are read. The digest vouches for what was recorded; 'treeward verify'
+checks the files against it. Every 'update' that changes any ward file changes the
+digest.
+
+OPTIONS:
+
+  --hash <ALGORITHM>
+    Algorithm for the printed digest: sha256 (default), sha512 or blake3. Digests
+    recorded inside ward files keep the algorithm they were recorded with.
+  --no-discover
+    Treat the current directory as the root, instead of the root of the warded
+    tree it is in.
+";
+
+pub(super) const MIGRATE_LONG_ABOUT: &str = "\
+Rewrite ward files in the newest file format
+
+Every .treeward file format version treeward has ever written can still be read,
+and 'update' writes the newest format whenever it changes a ward file. A tree warded
+by an older treeward therefore keeps working unmodified; 'migrate' converts all of its
+ward files at once, for example before handing the tree to tools that only understand
+the newest format.
+
+USAGE:
+
+  treeward migrate
+  treeward migrate --dry-run
+
+BEHAVIOR:
+
+1. Starts at the root .treeward of the warded tree the current directory is in
+   (fails if the tree is not initialized)
+2. Follows every directory recorded in a ward into its .treeward file
+3. Loads all ward files first; a corrupt or unsupported ward file anywhere aborts
+   the migration before anything is written
+4. Checks each followed ward file against the digest its parent recorded for it;
+   a mismatch aborts the migration before anything is written
+5. Rewrites each ward file not already in the newest format, keeping its entries
+   as recorded, and records digests in directory entries that lack one (a parent
+   whose child ward file is rewritten is rewritten too, to record the new digest)
+
+Migration neither reads nor checksums your files, and does not record changes made
+since the last update: run 'treeward status' to see those. Checksums keep the
+algorithm they were recorded with. A rewritten ward file loses its signature; sign
+the tree again with 'treeward update --signing-key'.
+
+Each ward file is written the same way 'update' writes it: to a temporary file that
+is fsynced and atomically renamed into place, after recording what is about to be
+rewritten, so the next treeward command settles a migration stopped halfway.
+Directories present on disk but not
+in any ward, and directories replaced by symlinks, are not visited.
+
+OPTIONS:
+
+  --dry-run
+    List the ward files that would be rewritten without writing anything.
+    Use -v to see the list.
+  --no-discover
+    Treat the current directory as the root, instead of the root of the warded
+    tree it is in.
+";
+
+pub(super) const PROTECT_LONG_ABOUT: &str = "\
+Protect files with forward error correction (FEC) data
+
+Records FEC protection in the .treeward of every directory in the tree (or in the
+given subtrees) and generates RaptorQ FEC data for each recorded file, so that
+'treeward repair' can restore the file's recorded content after corruption or
+partial loss.
+
+USAGE:
+
+  treeward protect
+  treeward protect --loss-tolerance 25 photos
+
+BEHAVIOR:
+
+1. Checks that the tree matches its ward (like 'treeward status'): protection covers
+   the recorded state only, so pending changes must be recorded with 'treeward
+   update' first, and protect fails without writing anything otherwise
+2. Records the setting in the [metadata.protection] table of each .treeward; new
+   directories inherit their parent's setting
+3. Writes .treeward-fec/<sha256>.fec next to each recorded file, generated from
+   the file's content after checking it still matches its recorded checksum.
+   Files with identical content share one FEC file
+
+From then on 'update' keeps the FEC data in line with the ward: files whose content
+changed get new FEC data and FEC files no longer needed are removed. Running protect
+again with another --loss-tolerance regenerates the FEC data of every file. A
+rewritten .treeward loses its signature, as with update without --signing-key.
+
+LOSS TOLERANCE (--loss-tolerance):
+
+  The percentage (1-100, default 10) of a file's data that can be lost or corrupted
+  and still be restored. FEC data takes about that share of the file's size. At
+  100, a file can be rebuilt even when it is gone entirely. Damage is located in
+  blocks of about 1/1024 of each 64 MiB segment, so scattered small corruptions
+  cost a whole block each.
+
+OPTIONS:
+
+  --dry-run
+    List the FEC files that would be written without writing anything (use -v).
+
+  -j, --jobs <N>
+    Number of files to check concurrently.
+
+  --no-discover
+    Treat the current directory as the root, instead of the root of the warded
+    tree it is in.
+";
+
+pub(super) const UNPROTECT_LONG_ABOUT: &str = "\
+Remove forward error correction (FEC) protection
+
+Records that the directories of the tree (or of the given subtrees) are no longer
+protected and removes their .treeward-fec directories. Like protect, it requires
+the tree to match its ward.
+
+USAGE:
+
+  treeward unprotect
+  treeward unprotect scratch
+
+OPTIONS:
+
+  --dry-run
+    List the FEC files that would be removed without removing anything (use -v).
+
+  -j, --jobs <N>
+    Number of files to check concurrently.
+
+  --no-discover
+    Treat the current directory as the root, instead of the root of the warded
+    tree it is in.
+";
+
+pub(super) const REPAIR_LONG_ABOUT: &str = "\
+Restore corrupted or lost files from their FEC data or a mirror
+
+Checksums every file of the tree (or of the given subtrees) like 'treeward verify',
+and restores each modified or removed file to its recorded content, permission bits
+kept and recorded mtime restored: from its FEC data if its directory is protected
+(see 'treeward protect'), otherwise from the mirror given with --from.
+
+USAGE:
+
+  treeward repair
+  treeward repair --dry-run
+  treeward repair photos/2024
+  treeward repair --from /mnt/backup/photos
+
+BEHAVIOR:
+
+1. Whatever is left of a damaged file, possibly nothing, is combined with its FEC
+   data to rebuild the recorded content
+2. Failing that, with --from, the file at the same path in the mirror is used if
+   it has the recorded checksum, otherwise any file in the mirror that does (only
+   files of the recorded size are checksummed)
+3. The result must match the checksum recorded in the .treeward; only then is it
+   written to a temporary file, fsynced and atomically renamed over the damaged file
+4. Files that cannot be restored (too much lost, no FEC data, no copy in the
+   mirror, or no longer a regular file) are reported and left alone
+
+Repair only ever restores the recorded content, so a modified file whose mtime is
+not the recorded one is left alone: silent corruption keeps the mtime, while an
+edit not yet recorded with 'treeward update' does not. Record such edits first, or
+give --force to undo them. Added entries are only reported, as are files of
+unprotected directories without --from. A damaged FEC file is reported too;
+'treeward update --always-verify' regenerates it.
+
+EXIT CODES:
+
+  0    Nothing left to repair (or, with --dry-run, everything can be repaired)
+  1    Some modified or removed files could not be restored, or were left alone
+       without --force
+  255  Error
+
+OPTIONS:
+
+  --from <DIRECTORY>
+    Restore files that their FEC data cannot from this mirror of the tree, such
+    as an rsync copy. The mirror is only read and need not be warded.
+
+  --force
+    Also restore files whose mtime changed, undoing edits not yet recorded.
+
+  --dry-run
+    Report what would be repaired without writing anything.
+
+  -j, --jobs <N>
+    Number of files to checksum concurrently.
+
+  --no-discover
+    Treat the current directory as the root, instead of the root of the warded
+    tree it is in.
+";
+
+pub(super) const WATCH_LONG_ABOUT: &str = "\
+Keep watching the tree and print each change of status as it happens
+
+Watch compares the tree once, the way 'treeward status' does, and then watches every
+directory with inotify (Linux only). Whenever files change it compares just the
+changed paths again and prints each entry whose status is now different: a new file
+shows up as A, a removed one as R, an edited one as M?. An entry that returns to its
+recorded state (or was added and is gone again) is printed as unchanged (.).
+
+USAGE:
+
+  treeward watch
+  treeward watch --verify --format ndjson
+
+BEHAVIOR:
+
+1. Compares the whole tree and prints every entry that is not unchanged
+2. Watches every directory, except ignored ones and treeward's own
+3. Collects changes until none has arrived for --debounce milliseconds, then
+   compares the changed paths again (a changed .treeward, as written by an update,
+   has its whole directory compared again) and prints what changed
+4. With --verify, checksums possibly modified files in the background and prints
+   them again as M or . once their checksum is known, unless they changed again
+   meanwhile
+5. Runs until interrupted
+
+Each comparison takes a shared lock of the tree, waiting for a running update to
+finish. Watch never writes ward files; accept changes with 'treeward update' as usual.
+If the kernel drops events (its queue overflowed), the whole tree is compared again.
+Each directory takes one inotify watch; a tree with more directories than
+fs.inotify.max_user_watches allows cannot be watched.
+
+EXIT CODES:
+
+  255  Error (such as the root being removed); watch otherwise runs until interrupted
+
+OPTIONS:
+
+  --verify
+    Checksum possibly modified files in the background to tell M from unchanged.
+
+  --debounce <MS>
+    Milliseconds without changes to wait for before comparing them (default 200).
+
+  -j, --jobs <N>
+    Number of files to checksum concurrently.
+
+  --format <FORMAT>
+    text (default): one line per change, as status prints them.
+    ndjson: one entry record per change, as in status --format ndjson, without
+    a summary record (see SPEC.md).
+
+  --no-discover
+    Treat the current directory as the root, instead of the root of the warded
+    tree it is in.
+";
@@ -1,14 +1,19 @@
 //! Presentation layer for status output.
 //!
 //! Formats `status::StatusEntry` values for terminal output and optional
-//! field-level diffs.
+//! field-level diffs. Machine-readable output lives in `json`.
 
+use std::collections::{BTreeMap, BTreeSet};
 use std::path::Path;
 
+use crate::attributes::{Attributes, Links, format_mode};
+use crate::checksum::Checksum;
 use crate::status;
 use crate::util::escape_control;
 use crate::ward_file::WardEntry;
 
+pub mod json;
+
 /// Format a symlink target for display, escaping control characters.
 /// Non-UTF-8 bytes are replaced lossily; exact-byte fidelity does not
 /// matter here because this output is presentation-only.
@@ -30,6 +35,18 @@ pub fn print_statuses(statuses: &[status::StatusEntry], show_diff: bool) {
     }
 }
 
+/// Print entries skipped by ignore rules, with the `!` code `status --ignored`
+/// uses for them.
+pub fn print_ignored(ignored: &[String]) {
+    for path in ignored {
+        println!("{:<2} {}", IGNORED_CODE, escape_control(path));
+    }
+}
+
+/// Listing code for ignored entries; not a `StatusType`, since ignored entries
+/// are not compared at all.
+pub const IGNORED_CODE: &str = "!";
+
 fn format_diff_lines(entry: &status::StatusEntry) -> Vec<String> {
     match entry {
         status::StatusEntry::Added { .. } | status::StatusEntry::Unchanged { .. } => Vec::new(),
@@ -46,11 +63,33 @@ fn format_diff_lines(entry: &status::StatusEntry) -> Vec<String> {
             ward_entry,
             old_ward_entry,
             ..
+        }
+        | status::StatusEntry::PermissionsChanged {
+            ward_entry,
+            old_ward_entry,
+            ..
         } => match (old_ward_entry, ward_entry) {
             (Some(old), Some(new)) => format_entry_diff(old, new),
             (Some(old), None) => vec![format_was_entry_verbose(old)],
             _ => Vec::new(),
         },
+        status::StatusEntry::Renamed {
+            path,
+            from,
+            ward_entry,
+            old_ward_entry,
+        } => {
+            let mut lines = vec![format!(
+                "   renamed: {} -> {}",
+                escape_control(from),
+                escape_control(path)
+            )];
+            if let (Some(old), Some(new)) = (old_ward_entry, ward_entry) {
+                lines.extend(format_entry_diff(old, new));
+            }
+            lines
+        }
+        status::StatusEntry::Error { kind, .. } => vec![format!("   error: {kind}")],
     }
 }
 
@@ -61,21 +100,23 @@ fn format_was_entry(entry: &WardEntry) -> String {
 fn format_was_entry_verbose(entry: &WardEntry) -> String {
     match entry {
         WardEntry::File {
-            sha256,
+            checksum,
             size,
             mtime_nanos,
+            ..
         } => {
             format!(
-                "   was: file ({}, mtime: {}, sha256: {})",
+                "   was: file ({}, mtime: {}, {})",
                 format_size(*size),
                 format_mtime(*mtime_nanos),
-                truncate_sha256(sha256)
+                format_checksum(checksum)
             )
         }
-        WardEntry::Dir {} => "   was: directory".to_string(),
-        WardEntry::Symlink { symlink_target } => {
+        WardEntry::Dir { .. } => "   was: directory".to_string(),
+        WardEntry::Symlink { symlink_target, .. } => {
             format!("   was: symlink -> {}", format_target(symlink_target))
         }
+        WardEntry::Special { kind, .. } => format!("   was: {kind}"),
     }
 }
 
@@ -95,14 +136,16 @@ fn format_entry_diff(old: &WardEntry, new: &WardEntry) -> Vec<String> {
     match (old, new) {
         (
             WardEntry::File {
-                sha256: old_sha,
+                checksum: old_checksum,
                 mtime_nanos: old_mtime,
                 size: old_size,
+                ..
             },
             WardEntry::File {
-                sha256: new_sha,
+                checksum: new_checksum,
                 mtime_nanos: new_mtime,
                 size: new_size,
+                ..
             },
         ) => {
             if old_size != new_size {
@@ -119,20 +162,31 @@ fn format_entry_diff(old: &WardEntry, new: &WardEntry) -> Vec<String> {
                     format_mtime(*new_mtime)
                 ));
             }
-            if old_sha != new_sha {
+            if old_checksum.algorithm != new_checksum.algorithm {
+                lines.push(format!(
+                    "   checksum: {}:{} -> {}:{}",
+                    old_checksum.algorithm,
+                    truncate_digest(&old_checksum.hex),
+                    new_checksum.algorithm,
+                    truncate_digest(&new_checksum.hex)
+                ));
+            } else if old_checksum.hex != new_checksum.hex {
                 lines.push(format!(
-                    "   sha256: {} -> {}",
-                    truncate_sha256(old_sha),
-                    truncate_sha256(new_sha)
+                    "   {}: {} -> {}",
+                    old_checksum.algorithm,
+                    truncate_digest(&old_checksum.hex),
+                    truncate_digest(&new_checksum.hex)
                 ));
             }
         }
         (
             WardEntry::Symlink {
                 symlink_target: old_target,
+                ..
             },
             WardEntry::Symlink {
                 symlink_target: new_target,
+                ..
             },
         ) => {
             if old_target != new_target {
@@ -143,28 +197,131 @@ fn format_entry_diff(old: &WardEntry, new: &WardEntry) -> Vec<String> {
                 ));
             }
         }
+        (WardEntry::Dir { .. }, WardEntry::Dir { .. }) => {}
+        (WardEntry::Special { kind: old_kind, .. }, WardEntry::Special { kind: new_kind, .. })
+            if old_kind.type_name() == new_kind.type_name() =>
+        {
+            if let (Some((old_major, old_minor)), Some((new_major, new_minor))) =
+                (old_kind.device(), new_kind.device())
+                && (old_major, old_minor) != (new_major, new_minor)
+            {
+                lines.push(format!(
+                    "   device: {old_major}:{old_minor} -> {new_major}:{new_minor}"
+                ));
+            }
+        }
         _ => {
             lines.push(format!("   was: {}", format_entry_type(old)));
             lines.push(format!("   now: {}", format_entry_type(new)));
+            return lines;
         }
     }
 
+    lines.extend(format_attribute_diff(old.attributes(), new.attributes()));
     lines
 }
 
+/// One line per attribute that differs, e.g. `mode: 0644 -> 4755` or
+/// `link_group: 3f2a9c01d4e5b6a7 -> none`. An
+/// attribute recorded on one side only is shown as `untracked` on the other.
+fn format_attribute_diff(old: &Attributes, new: &Attributes) -> Vec<String> {
+    let show = |value: Option<String>| value.unwrap_or_else(|| "untracked".to_string());
+    let fields = [
+        ("mode", old.mode.map(format_mode), new.mode.map(format_mode)),
+        (
+            "uid",
+            old.uid.map(|id| id.to_string()),
+            new.uid.map(|id| id.to_string()),
+        ),
+        (
+            "gid",
+            old.gid.map(|id| id.to_string()),
+            new.gid.map(|id| id.to_string()),
+        ),
+        (
+            "nlink",
+            old.links.as_ref().map(|links| links.nlink.to_string()),
+            new.links.as_ref().map(|links| links.nlink.to_string()),
+        ),
+        (
+            "link_group",
+            old.links.as_ref().map(format_link_group),
+            new.links.as_ref().map(format_link_group),
+        ),
+    ];
+    let mut lines: Vec<String> = fields
+        .into_iter()
+        .filter(|(_, old, new)| old != new)
+        .map(|(name, old, new)| format!("   {name}: {} -> {}", show(old), show(new)))
+        .collect();
+    lines.extend(format_xattr_diff(old.xattrs.as_ref(), new.xattrs.as_ref()));
+    lines
+}
+
+/// A recorded link group is untrusted input like a digest; `none` stands for
+/// a file with a single link.
+fn format_link_group(links: &Links) -> String {
+    match &links.group {
+        Some(group) => escape_control(group).into_owned(),
+        None => "none".to_string(),
+    }
+}
+
+/// One line per extended attribute that was added, removed or changed, e.g.
+/// `xattr security.capability: sha256: 3a5b... -> sha256: 9f01...`. When the
+/// set is recorded on one side only, a single `xattrs:` line lists the names.
+fn format_xattr_diff(
+    old: Option<&BTreeMap<String, Checksum>>,
+    new: Option<&BTreeMap<String, Checksum>>,
+) -> Vec<String> {
+    let names = |xattrs: Option<&BTreeMap<String, Checksum>>| match xattrs {
+        None => "untracked".to_string(),
+        Some(xattrs) if xattrs.is_empty() => "none".to_string(),
+        Some(xattrs) => xattrs
+            .keys()
+            .map(|name| escape_control(name).into_owned())
+            .collect::<Vec<_>>()
+            .join(", "),
+    };
+    let (Some(old_xattrs), Some(new_xattrs)) = (old, new) else {
+        return if old == new {
+            Vec::new()
+        } else {
+            vec![format!("   xattrs: {} -> {}", names(old), names(new))]
+        };
+    };
+
+    let all_names: BTreeSet<&String> = old_xattrs.keys().chain(new_xattrs.keys()).collect();
+    all_names
+        .into_iter()
+        .filter_map(|name| {
+            let change = match (old_xattrs.get(name), new_xattrs.get(name)) {
+                (Some(old), Some(new)) if old != new => {
+                    format!("{} -> {}", format_checksum(old), format_checksum(new))
+                }
+                (Some(_), None) => "removed".to_string(),
+                (None, Some(_)) => "added".to_string(),
+                _ => return None,
+            };
+            Some(format!("   xattr {}: {change}", escape_control(name)))
+        })
+        .collect()
+}
+
 fn format_entry_type(entry: &WardEntry) -> String {
     match entry {
-        WardEntry::File { sha256, size, .. } => {
+        WardEntry::File { checksum, size, .. } => {
             format!(
-                "file ({}, sha256: {})",
+                "file ({}, {})",
                 format_size(*size),
-                truncate_sha256(sha256)
+                format_checksum(checksum)
             )
         }
-        WardEntry::Dir {} => "directory".to_string(),
-        WardEntry::Symlink { symlink_target } => {
+        WardEntry::Dir { .. } => "directory".to_string(),
+        WardEntry::Symlink { symlink_target, .. } => {
             format!("symlink -> {}", format_target(symlink_target))
         }
+        WardEntry::Special { kind, .. } => kind.to_string(),
     }
 }
 
@@ -194,14 +351,18 @@ fn format_mtime(nanos: u64) -> String {
     datetime.format("%Y-%m-%d %H:%M:%S%.3f").to_string()
 }
 
-/// Abbreviate a recorded checksum for display.
+fn format_checksum(checksum: &Checksum) -> String {
+    format!("{}: {}", checksum.algorithm, truncate_digest(&checksum.hex))
+}
+
+/// Abbreviate a recorded digest for display.
 ///
-/// The sha256 string comes from a `.treeward` file, which is untrusted input:
+/// The digest string comes from a `.treeward` file, which is untrusted input:
 /// it is truncated on char boundaries (a hostile multi-byte string must not
 /// panic the formatter) and escaped like file names (a crafted "checksum" must
 /// not inject terminal escape sequences).
-fn truncate_sha256(sha256: &str) -> String {
-    let mut chars = sha256.chars();
+fn truncate_digest(digest: &str) -> String {
+    let mut chars = digest.chars();
     let prefix: String = chars.by_ref().take(12).collect();
     if chars.next().is_some() {
         format!("{}...", escape_control(&prefix))
@@ -213,21 +374,24 @@ fn truncate_sha256(sha256: &str) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::checksum::HashAlgorithm;
     use std::path::PathBuf;
 
     fn make_file(size: u64, sha256: &str) -> WardEntry {
         WardEntry::File {
-            sha256: sha256.to_string(),
+            checksum: Checksum::new(HashAlgorithm::Sha256, sha256),
             mtime_nanos: 1_704_067_200_000_000_000,
             size,
+            attributes: Attributes::default(),
         }
     }
 
     fn make_file_with_mtime(size: u64, sha256: &str, mtime_nanos: u64) -> WardEntry {
         WardEntry::File {
-            sha256: sha256.to_string(),
+            checksum: Checksum::new(HashAlgorithm::Sha256, sha256),
             mtime_nanos,
             size,
+            attributes: Attributes::default(),
         }
     }
 
@@ -251,7 +415,10 @@ mod tests {
     fn diff_removed_directory() {
         let entry = status::StatusEntry::Removed {
             path: "old_dir".into(),
-            old_ward_entry: Some(WardEntry::Dir {}),
+            old_ward_entry: Some(WardEntry::Dir {
+                digest: None,
+                attributes: Attributes::default(),
+            }),
         };
 
         assert_eq!(format_diff(&entry), "   was: directory\n");
@@ -263,12 +430,31 @@ mod tests {
             path: "old_link".into(),
             old_ward_entry: Some(WardEntry::Symlink {
                 symlink_target: PathBuf::from("/usr/bin/python3"),
+                attributes: Attributes::default(),
             }),
         };
 
         assert_eq!(format_diff(&entry), "   was: symlink -> /usr/bin/python3\n");
     }
 
+    #[test]
+    fn diff_renamed_shows_old_and_new_path() {
+        let entry = status::StatusEntry::Renamed {
+            path: "new/place.txt".into(),
+            from: "old/place.txt".into(),
+            ward_entry: None,
+            old_ward_entry: Some(WardEntry::Dir {
+                digest: None,
+                attributes: Attributes::default(),
+            }),
+        };
+
+        assert_eq!(
+            format_diff(&entry),
+            "   renamed: old/place.txt -> new/place.txt\n"
+        );
+    }
+
     #[test]
     fn diff_added_produces_no_output() {
         let entry = status::StatusEntry::Added {
@@ -355,6 +541,31 @@ mod tests {
         );
     }
 
+    #[test]
+    fn diff_modified_file_algorithm_change() {
+        let old = make_file(
+            100,
+            "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+        );
+        let new = WardEntry::File {
+            checksum: Checksum::new(HashAlgorithm::Blake3, "b".repeat(64)),
+            mtime_nanos: 1_704_067_200_000_000_000,
+            size: 100,
+            attributes: Attributes::default(),
+        };
+
+        let entry = status::StatusEntry::Modified {
+            path: "rehashed.txt".into(),
+            ward_entry: Some(new),
+            old_ward_entry: Some(old),
+        };
+
+        assert_eq!(
+            format_diff(&entry),
+            "   checksum: sha256:aaaaaaaaaaaa... -> blake3:bbbbbbbbbbbb...\n"
+        );
+    }
+
     #[test]
     fn diff_modified_file_mtime_change() {
         let old_mtime: u64 = 1_000_000_000_000_000_000;
@@ -392,7 +603,10 @@ mod tests {
             512,
             "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
         );
-        let new = WardEntry::Dir {};
+        let new = WardEntry::Dir {
+            digest: None,
+            attributes: Attributes::default(),
+        };
 
         let entry = status::StatusEntry::Modified {
             path: "was_file".into(),
@@ -408,9 +622,13 @@ mod tests {
 
     #[test]
     fn diff_type_change_directory_to_symlink() {
-        let old = WardEntry::Dir {};
+        let old = WardEntry::Dir {
+            digest: None,
+            attributes: Attributes::default(),
+        };
         let new = WardEntry::Symlink {
             symlink_target: PathBuf::from("../other"),
+            attributes: Attributes::default(),
         };
 
         let entry = status::StatusEntry::Modified {
@@ -429,9 +647,11 @@ mod tests {
     fn diff_symlink_target_change() {
         let old = WardEntry::Symlink {
             symlink_target: PathBuf::from("/old/target"),
+            attributes: Attributes::default(),
         };
         let new = WardEntry::Symlink {
             symlink_target: PathBuf::from("/new/target"),
+            attributes: Attributes::default(),
         };
 
         let entry = status::StatusEntry::Modified {
@@ -447,23 +667,25 @@ mod tests {
     }
 
     #[test]
-    fn truncate_sha256_handles_hostile_strings() {
+    fn truncate_digest_handles_hostile_strings() {
         // Multi-byte char straddling the truncation point must not panic.
         assert_eq!(
-            truncate_sha256("aaaaaaaaaaa\u{e9}zzz"),
+            truncate_digest("aaaaaaaaaaa\u{e9}zzz"),
             "aaaaaaaaaaa\u{e9}..."
         );
         // Control characters from a crafted ward file are escaped.
-        assert_eq!(truncate_sha256("\u{1b}]0;x\u{7}"), "\\u{1b}]0;x\\u{7}");
+        assert_eq!(truncate_digest("\u{1b}]0;x\u{7}"), "\\u{1b}]0;x\\u{7}");
     }
 
     #[test]
     fn diff_symlink_target_with_control_characters_is_escaped() {
         let old = WardEntry::Symlink {
             symlink_target: PathBuf::from("/old/target"),
+            attributes: Attributes::default(),
         };
         let new = WardEntry::Symlink {
             symlink_target: PathBuf::from("/new/\x1b[2Jtarget"),
+            attributes: Attributes::default(),
         };
 
         let entry = status::StatusEntry::Modified {
@@ -497,4 +719,85 @@ mod tests {
 
         assert_eq!(format_diff(&entry), "   size: 1.0 KB -> 2.0 KB\n");
     }
+
+    #[test]
+    fn diff_permissions_changed_shows_attributes() {
+        let old = WardEntry::Dir {
+            digest: None,
+            attributes: Attributes {
+                mode: Some(0o644),
+                uid: Some(0),
+                gid: Some(0),
+                xattrs: None,
+                ..Attributes::default()
+            },
+        };
+        let new = WardEntry::Dir {
+            digest: None,
+            attributes: Attributes {
+                mode: Some(0o4755),
+                uid: Some(0),
+                gid: None,
+                xattrs: None,
+                ..Attributes::default()
+            },
+        };
+
+        let entry = status::StatusEntry::PermissionsChanged {
+            path: "bin".into(),
+            ward_entry: Some(new),
+            old_ward_entry: Some(old),
+        };
+
+        assert_eq!(
+            format_diff(&entry),
+            "   mode: 0644 -> 4755\n   gid: 0 -> untracked\n"
+        );
+    }
+
+    #[test]
+    fn diff_modified_shows_xattr_changes() {
+        let digest = |hex: char| Checksum::new(HashAlgorithm::Sha256, hex.to_string().repeat(64));
+        let old = WardEntry::Symlink {
+            symlink_target: "target".into(),
+            attributes: Attributes {
+                xattrs: Some(BTreeMap::from([
+                    ("security.capability".to_string(), digest('a')),
+                    ("user.gone".to_string(), digest('b')),
+                ])),
+                ..Attributes::default()
+            },
+        };
+        let new = WardEntry::Symlink {
+            symlink_target: "target".into(),
+            attributes: Attributes {
+                xattrs: Some(BTreeMap::from([
+                    ("security.capability".to_string(), digest('c')),
+                    ("user.new".to_string(), digest('d')),
+                ])),
+                ..Attributes::default()
+            },
+        };
+
+        let entry = status::StatusEntry::Modified {
+            path: "link".into(),
+            ward_entry: Some(new),
+            old_ward_entry: Some(old),
+        };
+
+        assert_eq!(
+            format_diff(&entry),
+            "   xattr security.capability: sha256: aaaaaaaaaaaa... -> sha256: cccccccccccc...\n   \
+             xattr user.gone: removed\n   \
+             xattr user.new: added\n"
+        );
+    }
+
+    #[test]
+    fn diff_shows_newly_tracked_xattrs() {
+        assert_eq!(
+            format_xattr_diff(None, Some(&BTreeMap::new())),
+            vec!["   xattrs: untracked -> none".to_string()]
+        );
+    }
 }
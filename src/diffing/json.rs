@@ -0,0 +1,608 @@
+//! Machine-readable status output (`--format json` / `--format ndjson`).
+//!
+//! The record layout is a stable interface for scripts, versioned by
+//! `SCHEMA_VERSION` and documented in SPEC.md. Any change to it other than
+//! adding a field must bump the version.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::attributes::format_mode;
+use crate::checksum::Checksum;
+use crate::diffing::IGNORED_CODE;
+use crate::dir_list::SpecialKind;
+use crate::status::{self, StatusEntry, StatusResult, StatusType};
+use crate::util::names;
+use crate::ward_file::WardEntry;
+
+/// Version of the JSON record layout; carried by every document and record.
+const SCHEMA_VERSION: u32 = 1;
+
+/// One status entry, shared by both formats.
+#[derive(Debug, Serialize)]
+struct EntryRecord<'a> {
+    path: &'a str,
+    /// Same code as the text listing (`A`, `R`, `M?`, `M`, `R>`, `P`, `E`, `.`).
+    code: &'static str,
+    status: &'static str,
+    /// Previous path of a renamed entry.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    from: Option<&'a str>,
+    /// Why an errored entry could not be read.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    old: Option<JsonWardEntry>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    new: Option<JsonWardEntry>,
+}
+
+/// JSON rendering of a `WardEntry`, mirroring the `.treeward` entry schema.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum JsonWardEntry {
+    File {
+        checksum: String,
+        mtime_nanos: u64,
+        size: u64,
+        #[serde(flatten)]
+        attributes: JsonAttributes,
+    },
+    Dir {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        digest: Option<String>,
+        #[serde(flatten)]
+        attributes: JsonAttributes,
+    },
+    Symlink {
+        /// Lossy for non-UTF-8 targets, which JSON strings cannot carry.
+        symlink_target: String,
+        #[serde(flatten)]
+        attributes: JsonAttributes,
+    },
+    Fifo {
+        #[serde(flatten)]
+        attributes: JsonAttributes,
+    },
+    Socket {
+        #[serde(flatten)]
+        attributes: JsonAttributes,
+    },
+    #[serde(rename = "char_device")]
+    CharDevice {
+        major: u32,
+        minor: u32,
+        #[serde(flatten)]
+        attributes: JsonAttributes,
+    },
+    #[serde(rename = "block_device")]
+    BlockDevice {
+        major: u32,
+        minor: u32,
+        #[serde(flatten)]
+        attributes: JsonAttributes,
+    },
+}
+
+/// Recorded attributes of a `JsonWardEntry`; absent when not tracked.
+#[derive(Debug, Serialize)]
+struct JsonAttributes {
+    /// Four octal digits, as in the ward file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uid: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gid: Option<u32>,
+    /// Name to `<algorithm>:<hex>` digest of the value.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    xattrs: Option<BTreeMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nlink: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    link_group: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct Counts {
+    added: usize,
+    removed: usize,
+    possibly_modified: usize,
+    modified: usize,
+    renamed: usize,
+    permissions_changed: usize,
+    errors: usize,
+    unchanged: usize,
+    ignored: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct Summary<'a> {
+    schema_version: u32,
+    fingerprint: &'a str,
+    /// True when no entry is anything other than unchanged (errored entries
+    /// included) and no ward file fails its recorded digest.
+    clean: bool,
+    counts: Counts,
+    digest_mismatches: &'a [String],
+    /// FEC findings of protected directories; omitted when there are none.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    fec: Vec<FecRecord<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct FecRecord<'a> {
+    path: &'a str,
+    state: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+struct Document<'a> {
+    #[serde(flatten)]
+    summary: Summary<'a>,
+    entries: Vec<EntryRecord<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum NdjsonRecord<'a> {
+    Entry {
+        schema_version: u32,
+        #[serde(flatten)]
+        entry: Box<EntryRecord<'a>>,
+    },
+    Summary(Summary<'a>),
+}
+
+/// Print the whole result as a single JSON document. With `show_ignored`,
+/// entries skipped by ignore rules follow the status entries.
+pub fn print_json(result: &StatusResult, show_ignored: bool) {
+    let document = Document {
+        summary: summary(result),
+        entries: entry_records(result, show_ignored).collect(),
+    };
+    println!("{}", to_json(&document));
+}
+
+/// Print one JSON record per status entry (and, with `show_ignored`, per
+/// ignored entry), then a summary record.
+pub fn print_ndjson(result: &StatusResult, show_ignored: bool) {
+    for line in ndjson_lines(result, show_ignored) {
+        println!("{}", line);
+    }
+}
+
+/// Print the NDJSON record of a single status entry, as `watch` reports
+/// changes.
+pub fn print_ndjson_entry(entry: &StatusEntry) {
+    println!("{}", ndjson_entry_line(entry));
+}
+
+fn ndjson_entry_line(entry: &StatusEntry) -> String {
+    to_json(&NdjsonRecord::Entry {
+        schema_version: SCHEMA_VERSION,
+        entry: Box::new(entry_record(entry)),
+    })
+}
+
+fn ndjson_lines(result: &StatusResult, show_ignored: bool) -> Vec<String> {
+    entry_records(result, show_ignored)
+        .map(|entry| NdjsonRecord::Entry {
+            schema_version: SCHEMA_VERSION,
+            entry: Box::new(entry),
+        })
+        .chain(std::iter::once(NdjsonRecord::Summary(summary(result))))
+        .map(|record| to_json(&record))
+        .collect()
+}
+
+fn entry_records(
+    result: &StatusResult,
+    show_ignored: bool,
+) -> impl Iterator<Item = EntryRecord<'_>> {
+    let ignored = if show_ignored {
+        result.ignored.as_slice()
+    } else {
+        &[]
+    };
+    result
+        .statuses
+        .iter()
+        .map(entry_record)
+        .chain(ignored.iter().map(|path| EntryRecord {
+            path,
+            code: IGNORED_CODE,
+            status: "ignored",
+            from: None,
+            error: None,
+            old: None,
+            new: None,
+        }))
+}
+
+fn to_json<T: Serialize>(value: &T) -> String {
+    // Only strings, integers and booleans: serialization cannot fail.
+    let json = serde_json::to_string(value).expect("status records serialize to JSON");
+    escape_remaining_controls(&json)
+}
+
+/// Escape the control characters serde_json leaves raw (DEL and C1 controls).
+///
+/// JSON only requires escaping U+0000..U+001F, but the stdout listing must not
+/// carry any raw control character a crafted name could use against a terminal
+/// (the single-byte CSI U+009B included). Outside strings, JSON text is ASCII
+/// without controls, so every match is inside a string, where `\uXXXX` is a
+/// valid escape for the same character.
+fn escape_remaining_controls(json: &str) -> String {
+    let mut escaped = String::with_capacity(json.len());
+    for c in json.chars() {
+        if c == '\u{7f}' || ('\u{80}'..='\u{9f}').contains(&c) {
+            escaped.push_str(&format!("\\u{:04x}", c as u32));
+        } else {
+            escaped.push(c);
+        }
+    }
+    escaped
+}
+
+fn summary(result: &StatusResult) -> Summary<'_> {
+    let mut counts = Counts::default();
+    for entry in &result.statuses {
+        let count = match entry.status_type() {
+            StatusType::Added => &mut counts.added,
+            StatusType::Removed => &mut counts.removed,
+            StatusType::PossiblyModified => &mut counts.possibly_modified,
+            StatusType::Modified => &mut counts.modified,
+            StatusType::Renamed => &mut counts.renamed,
+            StatusType::PermissionsChanged => &mut counts.permissions_changed,
+            StatusType::Error => &mut counts.errors,
+            StatusType::Unchanged => &mut counts.unchanged,
+        };
+        *count += 1;
+    }
+
+    counts.ignored = result.ignored.len();
+
+    Summary {
+        schema_version: SCHEMA_VERSION,
+        fingerprint: &result.fingerprint,
+        clean: counts.added
+            + counts.removed
+            + counts.possibly_modified
+            + counts.modified
+            + counts.renamed
+            + counts.permissions_changed
+            + counts.errors
+            == 0
+            && result.digest_mismatches.is_empty(),
+        counts,
+        digest_mismatches: &result.digest_mismatches,
+        fec: result
+            .fec
+            .iter()
+            .map(|(path, state)| FecRecord {
+                path,
+                state: state.as_str(),
+            })
+            .collect(),
+    }
+}
+
+fn entry_record(entry: &StatusEntry) -> EntryRecord<'_> {
+    let status_type = entry.status_type();
+    let (old, new) = match entry {
+        StatusEntry::Added { ward_entry, .. } | StatusEntry::Unchanged { ward_entry, .. } => {
+            (None, ward_entry.as_ref())
+        }
+        StatusEntry::Removed { old_ward_entry, .. } => (old_ward_entry.as_ref(), None),
+        StatusEntry::Error { .. } => (None, None),
+        StatusEntry::Modified {
+            ward_entry,
+            old_ward_entry,
+            ..
+        }
+        | StatusEntry::PossiblyModified {
+            ward_entry,
+            old_ward_entry,
+            ..
+        }
+        | StatusEntry::Renamed {
+            ward_entry,
+            old_ward_entry,
+            ..
+        }
+        | StatusEntry::PermissionsChanged {
+            ward_entry,
+            old_ward_entry,
+            ..
+        } => (old_ward_entry.as_ref(), ward_entry.as_ref()),
+    };
+    let from = match entry {
+        StatusEntry::Renamed { from, .. } => Some(from.as_str()),
+        _ => None,
+    };
+    let error = match entry {
+        StatusEntry::Error { kind, .. } => Some(kind.as_str()),
+        _ => None,
+    };
+
+    EntryRecord {
+        path: entry.path(),
+        code: status::status_type_code(status_type),
+        status: status_name(status_type),
+        from,
+        error,
+        old: old.map(json_ward_entry),
+        new: new.map(json_ward_entry),
+    }
+}
+
+fn status_name(status_type: StatusType) -> &'static str {
+    match status_type {
+        StatusType::Added => "added",
+        StatusType::Removed => "removed",
+        StatusType::PossiblyModified => "possibly_modified",
+        StatusType::Modified => "modified",
+        StatusType::Renamed => "renamed",
+        StatusType::PermissionsChanged => "permissions_changed",
+        StatusType::Error => "error",
+        StatusType::Unchanged => "unchanged",
+    }
+}
+
+fn json_ward_entry(entry: &WardEntry) -> JsonWardEntry {
+    let attributes = JsonAttributes {
+        mode: entry.attributes().mode.map(format_mode),
+        uid: entry.attributes().uid,
+        gid: entry.attributes().gid,
+        xattrs: entry.attributes().xattrs.as_ref().map(|xattrs| {
+            xattrs
+                .iter()
+                .map(|(name, digest)| (name.clone(), digest.to_string()))
+                .collect()
+        }),
+        nlink: entry.attributes().links.as_ref().map(|links| links.nlink),
+        link_group: entry
+            .attributes()
+            .links
+            .as_ref()
+            .and_then(|links| links.group.clone()),
+    };
+    match entry {
+        WardEntry::File {
+            checksum,
+            mtime_nanos,
+            size,
+            ..
+        } => JsonWardEntry::File {
+            checksum: checksum.to_string(),
+            mtime_nanos: *mtime_nanos,
+            size: *size,
+            attributes,
+        },
+        WardEntry::Dir { digest, .. } => JsonWardEntry::Dir {
+            digest: digest.as_ref().map(Checksum::to_string),
+            attributes,
+        },
+        WardEntry::Symlink { symlink_target, .. } => JsonWardEntry::Symlink {
+            symlink_target: names::from_os(symlink_target.as_os_str())
+                .unwrap_or_else(|| symlink_target.to_string_lossy())
+                .into_owned(),
+            attributes,
+        },
+        WardEntry::Special { kind, .. } => match *kind {
+            SpecialKind::Fifo => JsonWardEntry::Fifo { attributes },
+            SpecialKind::Socket => JsonWardEntry::Socket { attributes },
+            SpecialKind::CharDevice { major, minor } => JsonWardEntry::CharDevice {
+                major,
+                minor,
+                attributes,
+            },
+            SpecialKind::BlockDevice { major, minor } => JsonWardEntry::BlockDevice {
+                major,
+                minor,
+                attributes,
+            },
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attributes::Attributes;
+    use crate::checksum::{Checksum, HashAlgorithm};
+    use std::path::PathBuf;
+
+    fn file(size: u64, hex: &str) -> WardEntry {
+        WardEntry::File {
+            checksum: Checksum::new(HashAlgorithm::Sha256, hex.repeat(64)),
+            mtime_nanos: 7,
+            size,
+            attributes: Attributes::default(),
+        }
+    }
+
+    fn result(statuses: Vec<StatusEntry>) -> StatusResult {
+        StatusResult {
+            statuses,
+            ignored: Vec::new(),
+            digest_mismatches: Vec::new(),
+            tracks: Default::default(),
+            protections: Default::default(),
+            fec: Vec::new(),
+            out_of_scope: Default::default(),
+            scope: crate::pathspec::Scope::All,
+            dir_ids: Default::default(),
+            fingerprint: "fp".to_string(),
+        }
+    }
+
+    #[test]
+    fn ndjson_emits_entries_then_summary() {
+        let result = result(vec![
+            StatusEntry::Added {
+                path: "new.txt".into(),
+                ward_entry: None,
+            },
+            StatusEntry::Modified {
+                path: "dir/changed.txt".into(),
+                ward_entry: Some(file(2, "b")),
+                old_ward_entry: Some(file(1, "a")),
+            },
+        ]);
+
+        let lines = ndjson_lines(&result, false);
+
+        assert_eq!(
+            lines,
+            vec![
+                r#"{"type":"entry","schema_version":1,"path":"new.txt","code":"A","status":"added"}"#
+                    .to_string(),
+                format!(
+                    concat!(
+                        r#"{{"type":"entry","schema_version":1,"path":"dir/changed.txt","code":"M","status":"modified","#,
+                        r#""old":{{"type":"file","checksum":"sha256:{a}","mtime_nanos":7,"size":1}},"#,
+                        r#""new":{{"type":"file","checksum":"sha256:{b}","mtime_nanos":7,"size":2}}}}"#
+                    ),
+                    a = "a".repeat(64),
+                    b = "b".repeat(64)
+                ),
+                r#"{"type":"summary","schema_version":1,"fingerprint":"fp","clean":false,"counts":{"added":1,"removed":0,"possibly_modified":0,"modified":1,"renamed":0,"permissions_changed":0,"errors":0,"unchanged":0,"ignored":0},"digest_mismatches":[]}"#
+                    .to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn ndjson_entry_line_matches_the_listing() {
+        let entry = StatusEntry::Unchanged {
+            path: "gone.txt".into(),
+            ward_entry: None,
+        };
+
+        assert_eq!(
+            ndjson_entry_line(&entry),
+            r#"{"type":"entry","schema_version":1,"path":"gone.txt","code":".","status":"unchanged"}"#
+        );
+        assert_eq!(
+            ndjson_entry_line(&entry),
+            ndjson_lines(&result(vec![entry]), false)[0]
+        );
+    }
+
+    #[test]
+    fn json_document_of_clean_tree() {
+        let result = result(vec![StatusEntry::Unchanged {
+            path: "link".into(),
+            ward_entry: Some(WardEntry::Symlink {
+                symlink_target: PathBuf::from("target"),
+                attributes: Attributes::default(),
+            }),
+        }]);
+
+        let document = Document {
+            summary: summary(&result),
+            entries: result.statuses.iter().map(entry_record).collect(),
+        };
+
+        assert_eq!(
+            to_json(&document),
+            concat!(
+                r#"{"schema_version":1,"fingerprint":"fp","clean":true,"#,
+                r#""counts":{"added":0,"removed":0,"possibly_modified":0,"modified":0,"renamed":0,"permissions_changed":0,"errors":0,"unchanged":1,"ignored":0},"digest_mismatches":[],"#,
+                r#""entries":[{"path":"link","code":".","status":"unchanged","#,
+                r#""new":{"type":"symlink","symlink_target":"target"}}]}"#
+            )
+        );
+    }
+
+    #[test]
+    fn ignored_entries_listed_only_on_request() {
+        let mut result = result(Vec::new());
+        result.ignored = vec!["cache".to_string()];
+
+        let summary = r#"{"type":"summary","schema_version":1,"fingerprint":"fp","clean":true,"counts":{"added":0,"removed":0,"possibly_modified":0,"modified":0,"renamed":0,"permissions_changed":0,"errors":0,"unchanged":0,"ignored":1},"digest_mismatches":[]}"#;
+        assert_eq!(ndjson_lines(&result, false), vec![summary.to_string()]);
+        assert_eq!(
+            ndjson_lines(&result, true),
+            vec![
+                r#"{"type":"entry","schema_version":1,"path":"cache","code":"!","status":"ignored"}"#
+                    .to_string(),
+                summary.to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn control_characters_are_escaped() {
+        let entry = StatusEntry::Added {
+            path: "a\u{1b}]0;x\u{7}\u{7f}\u{9b}b".into(),
+            ward_entry: None,
+        };
+
+        let json = to_json(&entry_record(&entry));
+
+        assert!(!json.chars().any(char::is_control), "{json:?}");
+        assert_eq!(
+            json,
+            r#"{"path":"a\u001b]0;x\u0007\u007f\u009bb","code":"A","status":"added"}"#
+        );
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed["path"], entry.path());
+    }
+
+    #[test]
+    fn removed_entry_carries_old_directory() {
+        let entry = StatusEntry::Removed {
+            path: "gone".into(),
+            old_ward_entry: Some(WardEntry::Dir {
+                digest: None,
+                attributes: Attributes::default(),
+            }),
+        };
+
+        assert_eq!(
+            to_json(&entry_record(&entry)),
+            r#"{"path":"gone","code":"R","status":"removed","old":{"type":"dir"}}"#
+        );
+    }
+
+    #[test]
+    fn renamed_entry_carries_source_path() {
+        let result = result(vec![StatusEntry::Renamed {
+            path: "b/moved".into(),
+            from: "a/moved".into(),
+            ward_entry: None,
+            old_ward_entry: None,
+        }]);
+
+        let lines = ndjson_lines(&result, false);
+
+        assert_eq!(
+            lines[0],
+            r#"{"type":"entry","schema_version":1,"path":"b/moved","code":"R>","status":"renamed","from":"a/moved"}"#
+        );
+        let summary: serde_json::Value = serde_json::from_str(&lines[1]).unwrap();
+        assert_eq!(summary["counts"]["renamed"], 1);
+        assert_eq!(summary["clean"], false);
+    }
+
+    #[test]
+    fn summary_lists_fec_findings() {
+        let mut result = result(vec![StatusEntry::Modified {
+            path: "data.bin".into(),
+            ward_entry: None,
+            old_ward_entry: None,
+        }]);
+        result.fec = vec![("data.bin".to_string(), status::FecState::Repairable)];
+
+        let lines = ndjson_lines(&result, false);
+
+        let summary: serde_json::Value = serde_json::from_str(&lines[1]).unwrap();
+        assert_eq!(
+            summary["fec"],
+            serde_json::json!([{"path": "data.bin", "state": "repairable"}])
+        );
+    }
+}
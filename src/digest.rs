@@ -0,0 +1,182 @@
+//! Whole-tree digest: the top of the chain of ward file digests.
+//!
+//! Every directory entry in a ward records the digest of the child directory's
+//! `.treeward`, so the digest of the root `.treeward` pins the recorded state
+//! of the entire tree. Stored outside the tree, it lets a later check detect
+//! any ward file being edited, replaced or rolled back, including the root's.
+
+use crate::checksum::{Checksum, HashAlgorithm};
+use crate::dir_list::TREEWARD_FILENAME;
+use crate::util::{is_real_dir, names};
+use crate::ward_file::{WardEntry, WardFile, WardFileError, content_digest};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, thiserror::Error)]
+pub enum DigestError {
+    #[error("Ward file error: {0}")]
+    WardFile(#[from] WardFileError),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Not initialized (use treeward init to initialize)")]
+    NotInitialized,
+    #[error("Ward file {0} does not match the digest recorded in its parent ward")]
+    Mismatch(PathBuf),
+    #[error("No digest recorded for {0} (run treeward update or treeward migrate)")]
+    Unpinned(PathBuf),
+}
+
+/// Check the digest chain of the tree rooted at `root` and return the digest
+/// of its root `.treeward`, computed with `algorithm`.
+///
+/// Traversal follows the wards like `migrate_tree`: from the root
+/// `.treeward` into each entry recorded as a directory that is a real
+/// directory on disk. Each such directory's `.treeward` must exist and match
+/// the recorded digest (`Mismatch`), and every followed entry must have one
+/// (`Unpinned`, for entries written before digests existed). Only ward files
+/// are read: the digest vouches for the recorded state, and `verify` checks
+/// the tree against it.
+pub fn tree_digest(root: &Path, algorithm: HashAlgorithm) -> Result<Checksum, DigestError> {
+    let root = root.canonicalize()?;
+
+    let root_ward_path = root.join(TREEWARD_FILENAME);
+    let Some((root_ward, root_content)) = WardFile::load_with_content_if_exists(&root_ward_path)?
+    else {
+        return Err(DigestError::NotInitialized);
+    };
+
+    let mut pending = vec![(root.clone(), root_ward)];
+    while let Some((dir, ward_file)) = pending.pop() {
+        for (name, entry) in &ward_file.entries {
+            let WardEntry::Dir { digest, .. } = entry else {
+                continue;
+            };
+            let child = dir.join(names::to_os(name));
+            if !is_real_dir(&child)? {
+                continue;
+            }
+
+            let child_ward_path = child.join(TREEWARD_FILENAME);
+            let relative = child_ward_path
+                .strip_prefix(&root)
+                .expect("ward paths are collected under the root")
+                .to_path_buf();
+            let Some(recorded) = digest else {
+                return Err(DigestError::Unpinned(relative));
+            };
+            match WardFile::load_with_content_if_exists(&child_ward_path)? {
+                Some((child_ward, child_content))
+                    if content_digest(&child_content, recorded.algorithm) == *recorded =>
+                {
+                    pending.push((child, child_ward));
+                }
+                _ => return Err(DigestError::Mismatch(relative)),
+            }
+        }
+    }
+
+    Ok(content_digest(&root_content, algorithm))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// Ward a tree of `sub/inner` with one file per directory, the way
+    /// `update` writes it (child digests recorded in parents).
+    fn warded_tree() -> TempDir {
+        let temp = TempDir::new().unwrap();
+        fs::create_dir_all(temp.path().join("sub/inner")).unwrap();
+        fs::write(temp.path().join("top.txt"), "top").unwrap();
+        fs::write(temp.path().join("sub/inner/deep.txt"), "deep").unwrap();
+        crate::update::ward_directory(
+            temp.path(),
+            crate::update::WardOptions {
+                init: true,
+                allow_init: false,
+                fingerprint: None,
+                accept_digest_mismatch: false,
+                dry_run: false,
+                checksum_policy: crate::status::ChecksumPolicy::Never,
+                jobs: std::num::NonZeroUsize::MIN,
+                hash: HashAlgorithm::Sha256,
+                signing_key: None,
+                track: None,
+                protection: None,
+                keep_going: false,
+                pathspecs: Vec::new(),
+            },
+        )
+        .unwrap();
+        temp
+    }
+
+    #[test]
+    fn test_digest_is_root_ward_file_digest() {
+        let temp = warded_tree();
+        let root_content = fs::read_to_string(temp.path().join(TREEWARD_FILENAME)).unwrap();
+
+        let digest = tree_digest(temp.path(), HashAlgorithm::Blake3).unwrap();
+
+        assert_eq!(digest, content_digest(&root_content, HashAlgorithm::Blake3));
+    }
+
+    #[test]
+    fn test_edited_nested_ward_file_breaks_chain() {
+        let temp = warded_tree();
+        let inner_ward = temp.path().join("sub/inner").join(TREEWARD_FILENAME);
+        let content = fs::read_to_string(&inner_ward).unwrap();
+        fs::write(&inner_ward, format!("# edited\n{content}")).unwrap();
+
+        let result = tree_digest(temp.path(), HashAlgorithm::Sha256);
+
+        assert!(
+            matches!(result, Err(DigestError::Mismatch(ref path)) if path == Path::new("sub/inner/.treeward")),
+            "{result:?}"
+        );
+    }
+
+    #[test]
+    fn test_deleted_nested_ward_file_breaks_chain() {
+        let temp = warded_tree();
+        fs::remove_file(temp.path().join("sub").join(TREEWARD_FILENAME)).unwrap();
+
+        let result = tree_digest(temp.path(), HashAlgorithm::Sha256);
+
+        assert!(
+            matches!(result, Err(DigestError::Mismatch(_))),
+            "{result:?}"
+        );
+    }
+
+    #[test]
+    fn test_directory_entry_without_digest_is_unpinned() {
+        let temp = TempDir::new().unwrap();
+        fs::create_dir(temp.path().join("sub")).unwrap();
+        fs::write(
+            temp.path().join(TREEWARD_FILENAME),
+            "[metadata]\nversion = 2\n\n[entries.sub]\ntype = \"dir\"\n",
+        )
+        .unwrap();
+        WardFile::new(Default::default())
+            .save(&temp.path().join("sub").join(TREEWARD_FILENAME))
+            .unwrap();
+
+        let result = tree_digest(temp.path(), HashAlgorithm::Sha256);
+
+        assert!(
+            matches!(result, Err(DigestError::Unpinned(_))),
+            "{result:?}"
+        );
+    }
+
+    #[test]
+    fn test_not_initialized() {
+        let temp = TempDir::new().unwrap();
+
+        let result = tree_digest(temp.path(), HashAlgorithm::Sha256);
+
+        assert!(matches!(result, Err(DigestError::NotInitialized)));
+    }
+}
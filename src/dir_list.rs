@@ -1,16 +1,41 @@
 //! Non-recursive directory listing for the treeward checksumming tool.
 //!
 //! This module provides functionality to list the immediate children of a directory,
-//! collecting filesystem metadata (mtime, size, symlink targets) for each entry.
+//! collecting filesystem metadata (mtime, size, symlink targets, device numbers,
+//! ownership and permission bits, and on request extended attributes) for each
+//! entry. FIFOs, sockets and device nodes are listed from their metadata alone;
+//! nothing here ever opens them.
+//! Names that are not valid UTF-8 are listed in their lossless string form
+//! (see `util::names`).
 //! The listing is non-recursive - each directory has its own `.treeward` file
 //! containing only its immediate children, allowing directories to be moved
 //! independently while maintaining their integrity information.
 
+use crate::attributes::{self, Attributes};
+use crate::util::dir_handle::{DirHandle, DirId, EntryStat, EntryType, is_swap};
+use crate::util::names;
 use std::collections::BTreeMap;
+use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 
 pub(crate) const TREEWARD_FILENAME: &str = ".treeward";
+/// Detached signature of the sibling `.treeward`, written when signing.
+pub(crate) const SIGNATURE_FILENAME: &str = ".treeward.sig";
+/// Directory holding the FEC files of a protected directory (see
+/// `protection`).
+pub(crate) const FEC_DIRNAME: &str = ".treeward-fec";
+/// Journal of accepted updates, at the root (see `journal`).
+pub(crate) const JOURNAL_FILENAME: &str = ".treeward-journal";
+/// Directory holding the named baselines, at the root (see `baseline`).
+pub(crate) const BASELINES_DIRNAME: &str = ".treeward-baselines";
+/// Previous versions of the ward files the last update rewrote, at the root
+/// (see `undo`).
+pub(crate) const UNDO_DIRNAME: &str = ".treeward-undo";
+/// Write-ahead record of an update in progress, at the root (see `intent`).
+pub(crate) const INTENT_FILENAME: &str = ".treeward-intent";
+/// Advisory lock of the tree, at the root (see `lock`).
+pub(crate) const LOCK_FILENAME: &str = ".treeward-lock";
 
 #[derive(Debug, thiserror::Error)]
 pub enum DirListError {
@@ -24,7 +49,14 @@ pub enum DirListError {
     /// intact files look empty when a single child vanishes mid-listing.
     #[error("directory not found: {0}")]
     DirectoryNotFound(PathBuf),
-    /// A child entry vanished between `read_dir` and inspecting it.
+    /// A directory seen moments ago was replaced, by a symlink or anything
+    /// else, or moved away: opening it by name now would lead elsewhere.
+    ///
+    /// Fatal even with `keep_going`: following the replacement would record
+    /// (or write ward files into) a tree that is not the one being walked.
+    #[error("directory replaced during the operation (concurrent modification): {0}")]
+    DirectoryReplaced(PathBuf),
+    /// A child entry vanished between listing and inspecting it.
     ///
     /// Fatal, mirroring the checksumming concurrent-modification policy: a
     /// race must surface as an error, never be misreported as a removal.
@@ -34,61 +66,268 @@ pub enum DirListError {
     PermissionDenied(PathBuf),
     #[error("non-UTF-8 path not supported: {0:?}")]
     NonUtf8Path(PathBuf),
-    #[error("unsupported file type (not a regular file, directory, or symlink): {0}")]
+    #[error(
+        "unsupported file type (not a regular file, directory, symlink, FIFO, socket, or device): {0}"
+    )]
     UnsupportedFileType(PathBuf),
+    #[error("Failed to read extended attributes of {}: {source}", path.display())]
+    Xattrs {
+        path: PathBuf,
+        source: std::io::Error,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum FsEntry {
-    File { mtime: SystemTime, size: u64 },
-    Dir { mtime: SystemTime },
-    Symlink { symlink_target: PathBuf },
+    File {
+        mtime: SystemTime,
+        size: u64,
+        attributes: Attributes,
+    },
+    Dir {
+        mtime: SystemTime,
+        attributes: Attributes,
+    },
+    Symlink {
+        symlink_target: PathBuf,
+        attributes: Attributes,
+    },
+    /// A FIFO, socket or device node.
+    Special {
+        kind: SpecialKind,
+        attributes: Attributes,
+    },
+}
+
+/// The type of a special file, with the device number for device nodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SpecialKind {
+    Fifo,
+    Socket,
+    CharDevice { major: u32, minor: u32 },
+    BlockDevice { major: u32, minor: u32 },
 }
 
-pub fn list_directory(root: &Path) -> Result<BTreeMap<String, FsEntry>, DirListError> {
-    let read_dir = std::fs::read_dir(root).map_err(|e| {
-        if e.kind() == std::io::ErrorKind::PermissionDenied {
-            DirListError::PermissionDenied(root.to_path_buf())
+impl SpecialKind {
+    /// Classify a file type that is not a regular file, directory or symlink.
+    #[cfg(unix)]
+    fn from_stat(stat: &EntryStat) -> Option<Self> {
+        let rdev = stat.rdev as libc::dev_t;
+        #[allow(clippy::unnecessary_cast)]
+        let (major, minor) = (libc::major(rdev) as u32, libc::minor(rdev) as u32);
+        match stat.entry_type {
+            EntryType::Fifo => Some(SpecialKind::Fifo),
+            EntryType::Socket => Some(SpecialKind::Socket),
+            EntryType::CharDevice => Some(SpecialKind::CharDevice { major, minor }),
+            EntryType::BlockDevice => Some(SpecialKind::BlockDevice { major, minor }),
+            _ => None,
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn from_stat(_stat: &EntryStat) -> Option<Self> {
+        None
+    }
+
+    /// The device number as `(major, minor)`, for device nodes.
+    pub fn device(&self) -> Option<(u32, u32)> {
+        match *self {
+            SpecialKind::Fifo | SpecialKind::Socket => None,
+            SpecialKind::CharDevice { major, minor }
+            | SpecialKind::BlockDevice { major, minor } => Some((major, minor)),
+        }
+    }
+
+    /// The type alone, without the device number.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            SpecialKind::Fifo => "fifo",
+            SpecialKind::Socket => "socket",
+            SpecialKind::CharDevice { .. } => "char device",
+            SpecialKind::BlockDevice { .. } => "block device",
+        }
+    }
+}
+
+impl std::fmt::Display for SpecialKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.device() {
+            Some((major, minor)) => write!(f, "{} {major}:{minor}", self.type_name()),
+            None => f.write_str(self.type_name()),
+        }
+    }
+}
+
+impl FsEntry {
+    pub fn attributes(&self) -> &Attributes {
+        match self {
+            FsEntry::File { attributes, .. }
+            | FsEntry::Dir { attributes, .. }
+            | FsEntry::Symlink { attributes, .. }
+            | FsEntry::Special { attributes, .. } => attributes,
+        }
+    }
+
+    pub fn attributes_mut(&mut self) -> &mut Attributes {
+        match self {
+            FsEntry::File { attributes, .. }
+            | FsEntry::Dir { attributes, .. }
+            | FsEntry::Symlink { attributes, .. }
+            | FsEntry::Special { attributes, .. } => attributes,
+        }
+    }
+}
+
+/// Opens the root of a walk (following symlinks, like the canonicalization
+/// that found it).
+pub fn open_root(path: &Path) -> Result<DirHandle, DirListError> {
+    DirHandle::open(path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::PermissionDenied => DirListError::PermissionDenied(path.to_path_buf()),
+        std::io::ErrorKind::NotFound => DirListError::DirectoryNotFound(path.to_path_buf()),
+        _ => DirListError::Io(e),
+    })
+}
+
+/// Opens the subdirectory `name` of `parent`, which is at `path`'s parent,
+/// without following a symlink.
+///
+/// The caller saw `name` listed as a directory, so a symlink or anything else
+/// in its place now is `DirectoryReplaced`.
+pub fn open_directory(
+    parent: &DirHandle,
+    name: &str,
+    path: &Path,
+) -> Result<DirHandle, DirListError> {
+    parent.open_dir(&names::to_os(name)).map_err(|e| {
+        if is_swap(&e) {
+            DirListError::DirectoryReplaced(path.to_path_buf())
+        } else if e.kind() == std::io::ErrorKind::PermissionDenied {
+            DirListError::PermissionDenied(path.to_path_buf())
         } else if e.kind() == std::io::ErrorKind::NotFound {
-            DirListError::DirectoryNotFound(root.to_path_buf())
+            DirListError::DirectoryNotFound(path.to_path_buf())
+        } else {
+            DirListError::Io(e)
+        }
+    })
+}
+
+/// Opens the directory at `path` again after a walk, walking down from the
+/// walk's `root` (at `root_path`) one component at a time, and checks that it
+/// still is the directory the walk saw as `walked`.
+///
+/// Anything but that directory at `path` now, including nothing at all, is
+/// `DirectoryReplaced`.
+pub fn reopen_directory(
+    root: &DirHandle,
+    root_path: &Path,
+    path: &Path,
+    walked: Option<DirId>,
+) -> Result<DirHandle, DirListError> {
+    let relative = path
+        .strip_prefix(root_path)
+        .map_err(|e| DirListError::Io(std::io::Error::other(e)))?;
+    let dir = root.open_path(relative).map_err(|e| {
+        if is_swap(&e) || e.kind() == std::io::ErrorKind::NotFound {
+            DirListError::DirectoryReplaced(path.to_path_buf())
+        } else if e.kind() == std::io::ErrorKind::PermissionDenied {
+            DirListError::PermissionDenied(path.to_path_buf())
         } else {
             DirListError::Io(e)
         }
     })?;
+    if let Some(walked) = walked
+        && dir.id().map_err(DirListError::Io)? != walked
+    {
+        return Err(DirListError::DirectoryReplaced(path.to_path_buf()));
+    }
+    Ok(dir)
+}
 
-    let mut entries = BTreeMap::new();
+/// Whether `name` is one of the treeward files any directory may hold, which
+/// listings leave out.
+fn is_treeward_name(name: &OsStr) -> bool {
+    [TREEWARD_FILENAME, SIGNATURE_FILENAME, FEC_DIRNAME]
+        .iter()
+        .any(|own| name == *own)
+}
+
+/// Whether `name` is one of the treeward files kept only at the root, which
+/// listings of the root leave out. Elsewhere they are ordinary entries.
+fn is_root_treeward_name(name: &OsStr) -> bool {
+    [
+        JOURNAL_FILENAME,
+        BASELINES_DIRNAME,
+        UNDO_DIRNAME,
+        INTENT_FILENAME,
+        LOCK_FILENAME,
+    ]
+    .iter()
+    .any(|own| name == *own)
+}
+
+/// Whether listings leave `name` out of a directory, which is the root of the
+/// walk if `at_root`.
+pub(crate) fn is_left_out(name: &OsStr, at_root: bool) -> bool {
+    is_treeward_name(name) || (at_root && is_root_treeward_name(name))
+}
+
+/// Lists the directory `path` as the root of a walk. Test convenience: walks
+/// open each directory once and list it with `list_directory_at`.
+#[cfg(test)]
+pub fn list_directory(path: &Path) -> Result<BTreeMap<String, FsEntry>, DirListError> {
+    list_directory_at(&open_root(path)?, path, true)
+}
 
-    for entry in read_dir {
-        let entry = entry.map_err(DirListError::Io)?;
-        let path = entry.path();
+/// Lists the open directory `dir`, which is at `path`. Every entry is
+/// inspected relative to `dir`, never by path. `at_root` tells whether `dir`
+/// is the root of the walk, the only place the root-only treeward files are
+/// left out.
+pub fn list_directory_at(
+    dir: &DirHandle,
+    path: &Path,
+    at_root: bool,
+) -> Result<BTreeMap<String, FsEntry>, DirListError> {
+    let names = dir.entry_names().map_err(DirListError::Io)?;
 
-        if path.file_name() == Some(std::ffi::OsStr::new(TREEWARD_FILENAME)) {
+    let mut entries = BTreeMap::new();
+
+    for name in names {
+        if is_left_out(&name, at_root) {
             continue;
         }
+        let entry_path = path.join(&name);
 
-        let metadata = std::fs::symlink_metadata(&path).map_err(|e| child_error(&path, e))?;
-
-        let filename = path
-            .file_name()
-            .ok_or_else(|| DirListError::Io(std::io::Error::other("Failed to get filename")))?
-            .to_str()
-            .ok_or_else(|| DirListError::NonUtf8Path(path.clone()))?
-            .to_string();
-
-        let file_type = metadata.file_type();
-
-        let fs_entry = if file_type.is_symlink() {
-            let symlink_target = std::fs::read_link(&path).map_err(|e| child_error(&path, e))?;
-            FsEntry::Symlink { symlink_target }
-        } else if file_type.is_dir() {
-            let mtime = metadata.modified().map_err(DirListError::Io)?;
-            FsEntry::Dir { mtime }
-        } else if file_type.is_file() {
-            let mtime = metadata.modified().map_err(DirListError::Io)?;
-            let size = metadata.len();
-            FsEntry::File { mtime, size }
-        } else {
-            return Err(DirListError::UnsupportedFileType(path));
+        let stat = dir.stat(&name).map_err(|e| child_error(&entry_path, e))?;
+
+        let filename = names::from_os(&name)
+            .ok_or_else(|| DirListError::NonUtf8Path(entry_path.clone()))?
+            .into_owned();
+
+        let attributes = Attributes::from_stat(&stat);
+
+        let fs_entry = match stat.entry_type {
+            EntryType::Symlink => {
+                let symlink_target = dir
+                    .read_link(&name)
+                    .map_err(|e| child_error(&entry_path, e))?;
+                FsEntry::Symlink {
+                    symlink_target,
+                    attributes,
+                }
+            }
+            EntryType::Dir => FsEntry::Dir {
+                mtime: stat.mtime,
+                attributes,
+            },
+            EntryType::File => FsEntry::File {
+                mtime: stat.mtime,
+                size: stat.size,
+                attributes,
+            },
+            _ => match SpecialKind::from_stat(&stat) {
+                Some(kind) => FsEntry::Special { kind, attributes },
+                None => return Err(DirListError::UnsupportedFileType(entry_path)),
+            },
         };
 
         entries.insert(filename, fs_entry);
@@ -97,11 +336,39 @@ pub fn list_directory(root: &Path) -> Result<BTreeMap<String, FsEntry>, DirListE
     Ok(entries)
 }
 
+/// Reads the extended attributes that `select` accepts into `entries`, as
+/// listed from the open directory `dir`, which is at `path`, by
+/// `list_directory_at`. Every entry is opened relative to `dir`.
+///
+/// Kept separate from the listing because it costs a few system calls per
+/// entry, which only directories tracking extended attributes pay.
+pub fn read_extended_attributes(
+    dir: &DirHandle,
+    path: &Path,
+    entries: &mut BTreeMap<String, FsEntry>,
+    select: impl Fn(&str) -> bool,
+) -> Result<(), DirListError> {
+    for (name, entry) in entries.iter_mut() {
+        let name = names::to_os(name);
+        let entry_path = path.join(&name);
+        let xattrs =
+            attributes::read_xattrs(dir, path, &name, &select).map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => DirListError::EntryVanished(entry_path.clone()),
+                _ => DirListError::Xattrs {
+                    path: entry_path.clone(),
+                    source: e,
+                },
+            })?;
+        entry.attributes_mut().xattrs = Some(xattrs);
+    }
+    Ok(())
+}
+
 /// Maps a per-child inspection failure during listing.
 ///
 /// NotFound here means the child existed in the directory listing moments
 /// ago: a concurrent modification, kept deliberately distinct from the
-/// directory-level NotFound handled at the `read_dir` open.
+/// directory-level NotFound reported when the directory is opened.
 fn child_error(path: &Path, e: std::io::Error) -> DirListError {
     match e.kind() {
         std::io::ErrorKind::PermissionDenied => DirListError::PermissionDenied(path.to_path_buf()),
@@ -170,7 +437,7 @@ mod tests {
         assert!(entries.contains_key("link.txt"));
         let link_entry = entries.get("link.txt").unwrap();
         assert!(matches!(link_entry, FsEntry::Symlink { .. }));
-        if let FsEntry::Symlink { symlink_target } = link_entry {
+        if let FsEntry::Symlink { symlink_target, .. } = link_entry {
             assert!(symlink_target.ends_with("target.txt"));
         }
     }
@@ -298,6 +565,115 @@ mod tests {
         assert!(!subdir_entries.contains_key(TREEWARD_FILENAME));
     }
 
+    #[test]
+    fn test_traverse_excludes_signature_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        fs::write(root.join("file1.txt"), "content1").unwrap();
+        fs::write(root.join(SIGNATURE_FILENAME), "signature").unwrap();
+
+        let entries = list_directory(root).unwrap();
+
+        assert_eq!(entries.keys().collect::<Vec<_>>(), vec!["file1.txt"]);
+    }
+
+    #[test]
+    fn test_traverse_excludes_fec_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        fs::write(root.join("file1.txt"), "content1").unwrap();
+        fs::create_dir(root.join(FEC_DIRNAME)).unwrap();
+
+        let entries = list_directory(root).unwrap();
+
+        assert_eq!(entries.keys().collect::<Vec<_>>(), vec!["file1.txt"]);
+    }
+
+    #[test]
+    fn test_traverse_excludes_journal() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        fs::write(root.join("file1.txt"), "content1").unwrap();
+        fs::write(root.join(JOURNAL_FILENAME), "{}\n").unwrap();
+
+        let entries = list_directory(root).unwrap();
+
+        assert_eq!(entries.keys().collect::<Vec<_>>(), vec!["file1.txt"]);
+    }
+
+    #[test]
+    fn test_traverse_excludes_baselines() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        fs::write(root.join("file1.txt"), "content1").unwrap();
+        fs::create_dir(root.join(BASELINES_DIRNAME)).unwrap();
+
+        let entries = list_directory(root).unwrap();
+
+        assert_eq!(entries.keys().collect::<Vec<_>>(), vec!["file1.txt"]);
+    }
+
+    #[test]
+    fn test_traverse_excludes_undo_generation() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        fs::write(root.join("file1.txt"), "content1").unwrap();
+        fs::create_dir(root.join(UNDO_DIRNAME)).unwrap();
+
+        let entries = list_directory(root).unwrap();
+
+        assert_eq!(entries.keys().collect::<Vec<_>>(), vec!["file1.txt"]);
+    }
+
+    #[test]
+    fn test_traverse_excludes_intent_record() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        fs::write(root.join("file1.txt"), "content1").unwrap();
+        fs::write(root.join(INTENT_FILENAME), "{}").unwrap();
+
+        let entries = list_directory(root).unwrap();
+
+        assert_eq!(entries.keys().collect::<Vec<_>>(), vec!["file1.txt"]);
+    }
+
+    #[test]
+    fn test_traverse_excludes_lock_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        fs::write(root.join("file1.txt"), "content1").unwrap();
+        fs::write(root.join(LOCK_FILENAME), "").unwrap();
+
+        let entries = list_directory(root).unwrap();
+
+        assert_eq!(entries.keys().collect::<Vec<_>>(), vec!["file1.txt"]);
+    }
+
+    #[test]
+    fn test_traverse_lists_root_only_names_below_the_root() {
+        let temp_dir = TempDir::new().unwrap();
+        let sub = temp_dir.path().join("sub");
+
+        fs::create_dir(&sub).unwrap();
+        fs::write(sub.join(JOURNAL_FILENAME), "user data").unwrap();
+        fs::write(sub.join(TREEWARD_FILENAME), "").unwrap();
+        fs::create_dir(sub.join(UNDO_DIRNAME)).unwrap();
+
+        let entries = list_directory_at(&open_root(&sub).unwrap(), &sub, false).unwrap();
+
+        assert_eq!(
+            entries.keys().collect::<Vec<_>>(),
+            vec![JOURNAL_FILENAME, UNDO_DIRNAME]
+        );
+    }
+
     #[test]
     #[cfg(unix)]
     fn test_traverse_permission_denied() {
@@ -339,7 +715,7 @@ mod tests {
         assert!(entries.contains_key("broken_link"));
         let entry = entries.get("broken_link").unwrap();
         assert!(matches!(entry, FsEntry::Symlink { .. }));
-        if let FsEntry::Symlink { symlink_target } = entry {
+        if let FsEntry::Symlink { symlink_target, .. } = entry {
             assert_eq!(symlink_target, &PathBuf::from("/nonexistent/target"));
         }
     }
@@ -374,7 +750,7 @@ mod tests {
         assert!(entries.contains_key("link"));
         let link_entry = entries.get("link").unwrap();
         assert!(matches!(link_entry, FsEntry::Symlink { .. }));
-        if let FsEntry::Symlink { symlink_target } = link_entry {
+        if let FsEntry::Symlink { symlink_target, .. } = link_entry {
             assert!(symlink_target.ends_with("target.txt"));
         }
 
@@ -454,7 +830,7 @@ mod tests {
         assert!(entries.contains_key("link"));
         let entry = entries.get("link").unwrap();
         assert!(matches!(entry, FsEntry::Symlink { .. }));
-        if let FsEntry::Symlink { symlink_target } = entry {
+        if let FsEntry::Symlink { symlink_target, .. } = entry {
             assert_eq!(symlink_target, &PathBuf::from("/some/target"));
         }
     }
@@ -475,7 +851,7 @@ mod tests {
         assert!(entries.contains_key("self"));
         let entry = entries.get("self").unwrap();
         match entry {
-            FsEntry::Symlink { symlink_target } => {
+            FsEntry::Symlink { symlink_target, .. } => {
                 assert_eq!(symlink_target, &PathBuf::from("self"));
             }
             _ => panic!("Expected Symlink entry"),
@@ -499,7 +875,7 @@ mod tests {
 
         assert!(entries.contains_key("a"));
         match entries.get("a").unwrap() {
-            FsEntry::Symlink { symlink_target } => {
+            FsEntry::Symlink { symlink_target, .. } => {
                 assert_eq!(symlink_target, &PathBuf::from("b"));
             }
             _ => panic!("Expected Symlink entry for 'a'"),
@@ -507,7 +883,7 @@ mod tests {
 
         assert!(entries.contains_key("b"));
         match entries.get("b").unwrap() {
-            FsEntry::Symlink { symlink_target } => {
+            FsEntry::Symlink { symlink_target, .. } => {
                 assert_eq!(symlink_target, &PathBuf::from("a"));
             }
             _ => panic!("Expected Symlink entry for 'b'"),
@@ -529,7 +905,7 @@ mod tests {
         assert_eq!(entries.len(), 1);
         assert!(entries.contains_key("parent"));
         match entries.get("parent").unwrap() {
-            FsEntry::Symlink { symlink_target } => {
+            FsEntry::Symlink { symlink_target, .. } => {
                 assert_eq!(symlink_target, &PathBuf::from(".."));
             }
             _ => panic!("Expected Symlink entry"),
@@ -538,46 +914,62 @@ mod tests {
 
     #[test]
     #[cfg(unix)]
-    fn test_unsupported_file_type_fifo() {
+    fn test_fifo_is_listed_as_special() {
         use nix::sys::stat;
         use nix::unistd;
 
         let temp_dir = TempDir::new().unwrap();
         let root = temp_dir.path();
 
-        let fifo_path = root.join("test_fifo");
-        unistd::mkfifo(&fifo_path, stat::Mode::S_IRWXU).unwrap();
+        unistd::mkfifo(&root.join("test_fifo"), stat::Mode::S_IRWXU).unwrap();
 
-        let result = list_directory(root);
+        let entries = list_directory(root).unwrap();
 
-        assert!(result.is_err());
-        match result {
-            Err(DirListError::UnsupportedFileType(path)) => {
-                assert_eq!(path, fifo_path);
-            }
-            _ => panic!("Expected UnsupportedFileType error, got {:?}", result),
+        match entries.get("test_fifo") {
+            Some(FsEntry::Special { kind, .. }) => assert_eq!(*kind, SpecialKind::Fifo),
+            other => panic!("Expected Special entry, got {:?}", other),
         }
     }
 
     #[test]
     #[cfg(unix)]
-    fn test_unsupported_file_type_socket() {
+    fn test_socket_is_listed_as_special() {
         use std::os::unix::net::UnixListener;
 
         let temp_dir = TempDir::new().unwrap();
         let root = temp_dir.path();
 
-        let socket_path = root.join("test_socket");
-        let _listener = UnixListener::bind(&socket_path).unwrap();
+        let _listener = UnixListener::bind(root.join("test_socket")).unwrap();
 
-        let result = list_directory(root);
+        let entries = list_directory(root).unwrap();
 
-        assert!(result.is_err());
-        match result {
-            Err(DirListError::UnsupportedFileType(path)) => {
-                assert_eq!(path, socket_path);
+        match entries.get("test_socket") {
+            Some(FsEntry::Special { kind, .. }) => assert_eq!(*kind, SpecialKind::Socket),
+            other => panic!("Expected Special entry, got {:?}", other),
+        }
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_device_node_records_device_number() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        // Creating device nodes needs CAP_MKNOD.
+        let path = std::ffi::CString::new(root.join("null").to_str().unwrap()).unwrap();
+        let dev = libc::makedev(1, 3);
+        if unsafe { libc::mknod(path.as_ptr(), libc::S_IFCHR | 0o666, dev) } != 0 {
+            return;
+        }
+
+        let entries = list_directory(root).unwrap();
+
+        match entries.get("null") {
+            Some(FsEntry::Special { kind, .. }) => {
+                assert_eq!(*kind, SpecialKind::CharDevice { major: 1, minor: 3 });
+                assert_eq!(kind.to_string(), "char device 1:3");
             }
-            _ => panic!("Expected UnsupportedFileType error, got {:?}", result),
+            other => panic!("Expected Special entry, got {:?}", other),
         }
     }
 
@@ -605,10 +997,12 @@ mod tests {
                 FsEntry::File {
                     mtime: mtime1,
                     size: size1,
+                    ..
                 },
                 FsEntry::File {
                     mtime: mtime2,
                     size: size2,
+                    ..
                 },
             ) => {
                 assert_eq!(size1, size2);
@@ -617,4 +1011,87 @@ mod tests {
             _ => panic!("Expected both entries to be files"),
         }
     }
+
+    /// Hard links are only told apart by their shared inode and link count.
+    #[test]
+    #[cfg(unix)]
+    fn test_hard_links_share_file_id() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        fs::write(root.join("original.txt"), "content").unwrap();
+        fs::hard_link(root.join("original.txt"), root.join("hardlink.txt")).unwrap();
+        fs::write(root.join("other.txt"), "content").unwrap();
+
+        let entries = list_directory(root).unwrap();
+        let attributes = |name: &str| entries[name].attributes().clone();
+
+        assert_eq!(
+            attributes("original.txt").file_id,
+            attributes("hardlink.txt").file_id
+        );
+        assert_ne!(
+            attributes("original.txt").file_id,
+            attributes("other.txt").file_id
+        );
+        assert_eq!(attributes("original.txt").links.unwrap().nlink, 2);
+        assert_eq!(attributes("other.txt").links.unwrap().nlink, 1);
+    }
+
+    /// A directory swapped for a symlink after it was listed is refused, not
+    /// followed.
+    #[test]
+    #[cfg(unix)]
+    fn test_open_directory_refuses_directory_swapped_for_symlink() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let outside = TempDir::new().unwrap();
+        fs::create_dir(root.join("sub")).unwrap();
+
+        let dir = open_root(root).unwrap();
+        let entries = list_directory_at(&dir, root, true).unwrap();
+        assert!(matches!(entries["sub"], FsEntry::Dir { .. }));
+        fs::remove_dir(root.join("sub")).unwrap();
+        std::os::unix::fs::symlink(outside.path(), root.join("sub")).unwrap();
+
+        let result = open_directory(&dir, "sub", &root.join("sub"));
+
+        assert!(matches!(result, Err(DirListError::DirectoryReplaced(_))));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_reopen_directory_checks_walked_identity() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let outside = TempDir::new().unwrap();
+        fs::create_dir_all(root.join("a/b")).unwrap();
+
+        let dir = open_root(root).unwrap();
+        let a = open_directory(&dir, "a", &root.join("a")).unwrap();
+        let walked = open_directory(&a, "b", &root.join("a/b"))
+            .unwrap()
+            .id()
+            .unwrap();
+        let b = root.join("a/b");
+        assert!(reopen_directory(&dir, root, &b, Some(walked)).is_ok());
+
+        // A fresh directory in its place is not the one walked.
+        fs::rename(&b, root.join("moved")).unwrap();
+        fs::create_dir(&b).unwrap();
+        assert!(matches!(
+            reopen_directory(&dir, root, &b, Some(walked)),
+            Err(DirListError::DirectoryReplaced(_))
+        ));
+
+        // Nor is the walked directory reached through a symlinked parent.
+        fs::remove_dir(&b).unwrap();
+        fs::rename(root.join("moved"), &b).unwrap();
+        fs::rename(root.join("a"), outside.path().join("a")).unwrap();
+        std::os::unix::fs::symlink(outside.path().join("a"), root.join("a")).unwrap();
+        assert!(matches!(
+            reopen_directory(&dir, root, &b, Some(walked)),
+            Err(DirListError::DirectoryReplaced(_))
+        ));
+    }
 }
@@ -0,0 +1,159 @@
+//! Discovery of the ward root enclosing the working directory.
+//!
+//! A warded directory is listed as a `dir` entry in its parent's `.treeward`,
+//! so the tree it belongs to can be found by climbing parents for as long as
+//! each one records the directory below it, much like git finds `.git`.
+
+use crate::util::names;
+use crate::ward_file::{WardEntry, WardFile, WardFileError};
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+/// The root of the warded tree a command operates on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WardRoot {
+    /// The root directory, canonicalized when discovered.
+    pub root: PathBuf,
+    /// String form of the working directory relative to `root`; empty when
+    /// the working directory is the root. Output paths are shown relative to
+    /// the working directory (see `names::relative_to`).
+    pub prefix: String,
+}
+
+impl WardRoot {
+    /// `dir` itself as the root, without discovery.
+    pub fn at(dir: &Path) -> Self {
+        WardRoot {
+            root: dir.to_path_buf(),
+            prefix: String::new(),
+        }
+    }
+
+    /// Finds the root of the warded tree containing `dir`: the topmost
+    /// ancestor reached by climbing parents whose `.treeward` lists the
+    /// directory below as a `dir` entry. `dir` itself when its parent does not
+    /// (including when `dir` is not warded at all).
+    ///
+    /// A parent `.treeward` that cannot be read for lack of permission ends
+    /// the climb; any other failure to load one is an error.
+    pub fn discover(dir: &Path) -> Result<Self, WardFileError> {
+        let mut root = dir.canonicalize().map_err(WardFileError::Io)?;
+        let mut prefix: Vec<String> = Vec::new();
+        while let (Some(parent), Some(name)) = (root.parent(), root.file_name()) {
+            let Some(name) = names::from_os(name) else {
+                break;
+            };
+            let ward_file = match WardFile::load_with_content_if_exists(&parent.join(".treeward")) {
+                Ok(Some((ward_file, _))) => ward_file,
+                Ok(None) | Err(WardFileError::PermissionDenied(_)) => break,
+                Err(e) => return Err(e),
+            };
+            if !matches!(
+                ward_file.entries.get(name.as_ref()),
+                Some(WardEntry::Dir { .. })
+            ) {
+                break;
+            }
+            prefix.push(name.into_owned());
+            root = parent.to_path_buf();
+        }
+
+        if !prefix.is_empty() {
+            info!("Using ward root {}", root.display());
+        }
+        prefix.reverse();
+        Ok(WardRoot {
+            root,
+            prefix: prefix.join("/"),
+        })
+    }
+
+    /// `paths`, given relative to the working directory (or absolute), as
+    /// pathspecs for `root`.
+    pub fn pathspecs(&self, paths: &[PathBuf]) -> Vec<PathBuf> {
+        if self.prefix.is_empty() {
+            return paths.to_vec();
+        }
+        let cwd = self.root.join(names::to_os(&self.prefix));
+        paths.iter().map(|path| cwd.join(path)).collect()
+    }
+
+    /// `path`, relative to `root`, as shown to the user.
+    pub fn display_path(&self, path: &str) -> String {
+        names::relative_to(&self.prefix, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::HashAlgorithm;
+    use crate::status::ChecksumPolicy;
+    use crate::update::{WardOptions, ward_directory};
+    use std::fs;
+    use std::num::NonZeroUsize;
+    use tempfile::TempDir;
+
+    fn ward_tree(root: &Path) {
+        ward_directory(
+            root,
+            WardOptions {
+                init: true,
+                allow_init: false,
+                fingerprint: None,
+                accept_digest_mismatch: false,
+                dry_run: false,
+                checksum_policy: ChecksumPolicy::Never,
+                jobs: NonZeroUsize::MIN,
+                hash: HashAlgorithm::Sha256,
+                signing_key: None,
+                track: None,
+                protection: None,
+                keep_going: false,
+                pathspecs: Vec::new(),
+            },
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_discovers_root_from_nested_directory() {
+        let temp = TempDir::new().unwrap();
+        let tree = temp.path().join("tree");
+        fs::create_dir_all(tree.join("a/b")).unwrap();
+        ward_tree(&tree);
+
+        let ward_root = WardRoot::discover(&tree.join("a/b")).unwrap();
+
+        assert_eq!(ward_root.root, tree.canonicalize().unwrap());
+        assert_eq!(ward_root.prefix, "a/b");
+        assert_eq!(ward_root.display_path("a/x.txt"), "../x.txt");
+    }
+
+    #[test]
+    fn test_stops_at_parent_that_does_not_list_the_directory() {
+        let temp = TempDir::new().unwrap();
+        let tree = temp.path().join("tree");
+        fs::create_dir_all(&tree).unwrap();
+        ward_tree(&tree);
+        // Created after warding, so the root's ward does not list it.
+        fs::create_dir(tree.join("new")).unwrap();
+        ward_tree(&tree.join("new"));
+
+        let ward_root = WardRoot::discover(&tree.join("new")).unwrap();
+
+        assert_eq!(ward_root.root, tree.join("new").canonicalize().unwrap());
+        assert_eq!(ward_root.prefix, "");
+    }
+
+    #[test]
+    fn test_root_of_tree_is_its_own_root() {
+        let temp = TempDir::new().unwrap();
+        ward_tree(temp.path());
+
+        let ward_root = WardRoot::discover(temp.path()).unwrap();
+
+        assert_eq!(ward_root.root, temp.path().canonicalize().unwrap());
+        assert_eq!(ward_root.prefix, "");
+    }
+}
@@ -0,0 +1,638 @@
+//! Forward error correction for protected files (see `protection`).
+//!
+//! A file is cut into segments of at most `FecConfig::segment_size` bytes, and
+//! each segment is encoded on its own as a single RaptorQ source block, so
+//! neither generation nor repair holds more than one segment of the file in
+//! memory. For every segment, the FEC data keeps a short digest of each source
+//! symbol, which is how repair tells the intact symbols of a damaged file from
+//! the corrupt ones, plus enough repair symbols to stand in for
+//! `loss_tolerance_percent` of the segment's source symbols.
+//!
+//! FEC file layout (integers little-endian):
+//!
+//! - Header: magic `TWFC`, u32 format version (1), u64 original file size,
+//!   u32 segment size, u32 number of segments, u32 loss tolerance percent.
+//! - Per segment: u64 offset in the file, u32 length, the 12-byte RaptorQ
+//!   object transmission information, an 8-byte digest (truncated SHA-256)
+//!   of each source symbol (the last one zero-padded), u32 number of repair
+//!   packets, each packet with a u32 length prefix, and the SHA-256 of the
+//!   segment's bytes so far.
+//! - Footer: SHA-256 of everything before it.
+
+use raptorq::{
+    EncodingPacket, ObjectTransmissionInformation, SourceBlockDecoder, SourceBlockEncoder,
+};
+use sha2::{Digest, Sha256};
+use std::io::{self, Read, Write};
+
+/// Default segment size: 64 MiB.
+pub const DEFAULT_SEGMENT_SIZE: u32 = 64 * 1024 * 1024;
+
+const MAGIC: &[u8; 4] = b"TWFC";
+const FORMAT_VERSION: u32 = 1;
+
+/// Symbols a segment is cut into, as far as the symbol size limits allow.
+const TARGET_SYMBOLS: u32 = 1024;
+const MIN_SYMBOL_SIZE: u32 = 64;
+/// Largest multiple of `SYMBOL_ALIGNMENT` that fits RaptorQ's u16 symbol size.
+const MAX_SYMBOL_SIZE: u32 = 65528;
+const SYMBOL_ALIGNMENT: u8 = 8;
+const SYMBOL_DIGEST_LEN: usize = 8;
+/// Repair symbols beyond the loss tolerance. RaptorQ decodes from the source
+/// symbol count plus a couple more with overwhelming probability.
+const DECODE_OVERHEAD: u32 = 2;
+
+#[derive(Debug, thiserror::Error)]
+pub enum FecError {
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Not a treeward FEC file")]
+    NotFec,
+    #[error("Unsupported FEC file version: {0}")]
+    UnsupportedVersion(u32),
+    #[error("Corrupt FEC file: {0}")]
+    Corrupt(String),
+    #[error("File too large for FEC")]
+    TooLarge,
+    #[error("Too much data lost to repair segment {0}")]
+    Unrecoverable(u32),
+}
+
+/// How FEC data is generated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FecConfig {
+    /// Share (1-100) of each segment's data that can be lost or corrupted and
+    /// still be repaired. 100 allows repair with no data left at all.
+    pub loss_tolerance_percent: u8,
+    /// Segment size in bytes; `DEFAULT_SEGMENT_SIZE` except in tests.
+    pub segment_size: u32,
+}
+
+impl FecConfig {
+    pub fn new(loss_tolerance_percent: u8) -> Self {
+        FecConfig {
+            loss_tolerance_percent,
+            segment_size: DEFAULT_SEGMENT_SIZE,
+        }
+    }
+}
+
+/// The header of a FEC file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FecHeader {
+    pub original_size: u64,
+    pub segment_size: u32,
+    pub segments: u32,
+    pub loss_tolerance_percent: u8,
+}
+
+/// What a `repair` did.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RepairStats {
+    pub segments: u32,
+    /// Segments that had damaged symbols and were reconstructed.
+    pub segments_repaired: u32,
+}
+
+/// Generates FEC data for the `size` bytes read from `source` and writes it
+/// to `out`, one segment at a time.
+pub fn write_fec<R: Read, W: Write>(
+    source: &mut R,
+    size: u64,
+    config: &FecConfig,
+    out: &mut W,
+) -> Result<(), FecError> {
+    assert!((1..=100).contains(&config.loss_tolerance_percent));
+    assert!(config.segment_size > 0 && config.segment_size <= DEFAULT_SEGMENT_SIZE);
+
+    let segment_size = u64::from(config.segment_size);
+    let segments = u32::try_from(size.div_ceil(segment_size)).map_err(|_| FecError::TooLarge)?;
+    let mut out = HashingWriter {
+        inner: out,
+        hasher: Sha256::new(),
+    };
+    out.write_all(MAGIC)?;
+    out.write_all(&FORMAT_VERSION.to_le_bytes())?;
+    out.write_all(&size.to_le_bytes())?;
+    out.write_all(&config.segment_size.to_le_bytes())?;
+    out.write_all(&segments.to_le_bytes())?;
+    out.write_all(&u32::from(config.loss_tolerance_percent).to_le_bytes())?;
+
+    let mut data = Vec::new();
+    for index in 0..segments {
+        let offset = u64::from(index) * segment_size;
+        let length = segment_size.min(size - offset) as usize;
+        data.resize(length, 0);
+        source.read_exact(&mut data)?;
+        let record = encode_segment(offset, &data, config.loss_tolerance_percent);
+        out.write_all(&record)?;
+        out.write_all(&Sha256::digest(&record))?;
+    }
+
+    let footer = out.hasher.finalize();
+    out.inner.write_all(&footer)?;
+    Ok(())
+}
+
+/// Reads the header of a FEC file, without checking anything else.
+pub fn read_header<R: Read>(fec: &mut R) -> Result<FecHeader, FecError> {
+    read_header_from(&mut FecReader::new(fec))
+}
+
+/// Reads a whole FEC file and checks its integrity.
+pub fn verify<R: Read>(fec: &mut R) -> Result<FecHeader, FecError> {
+    let mut reader = FecReader::new(fec);
+    let header = read_header_from(&mut reader)?;
+    for index in 0..header.segments {
+        read_segment(&mut reader, &header, index)?;
+    }
+    reader.check_footer()?;
+    Ok(header)
+}
+
+/// Reconstructs the original file from what is left of it in `damaged` and
+/// the FEC data in `fec`, and writes it to `out`.
+///
+/// `damaged` may be short (or empty): whatever is missing counts as lost.
+/// Bytes beyond the original size are ignored. Segments whose symbols all
+/// match their digests are copied as they are; only damaged segments are
+/// decoded. Each segment's FEC data is checked before it is used, so damaged
+/// FEC data fails with `FecError::Corrupt` rather than producing wrong
+/// output. `out` may have received part of the file when this fails.
+pub fn repair<D: Read, F: Read, W: Write>(
+    damaged: &mut D,
+    fec: &mut F,
+    out: &mut W,
+) -> Result<RepairStats, FecError> {
+    let mut reader = FecReader::new(fec);
+    let header = read_header_from(&mut reader)?;
+    let mut stats = RepairStats {
+        segments: header.segments,
+        segments_repaired: 0,
+    };
+
+    let mut data = Vec::new();
+    for index in 0..header.segments {
+        let mut segment = read_segment(&mut reader, &header, index)?;
+        data.clear();
+        damaged
+            .by_ref()
+            .take(u64::from(segment.length))
+            .read_to_end(&mut data)?;
+
+        let intact = segment.intact_symbols(&data);
+        if intact.len() == segment.symbol_digests.len() {
+            out.write_all(&data)?;
+            continue;
+        }
+
+        let symbol_size = segment.symbol_size();
+        let mut decoder = SourceBlockDecoder::new2(0, &segment.oti, u64::from(segment.length));
+        let source_packets = intact.into_iter().map(|esi| {
+            let start = esi as usize * symbol_size;
+            let mut symbol = data[start..data.len().min(start + symbol_size)].to_vec();
+            symbol.resize(symbol_size, 0);
+            EncodingPacket::new(raptorq::PayloadId::new(0, esi), symbol)
+        });
+        let decoded = decoder
+            .decode(source_packets.chain(std::mem::take(&mut segment.repair_packets)))
+            .ok_or(FecError::Unrecoverable(index))?;
+        let decoded = &decoded[..segment.length as usize];
+        if segment.intact_symbols(decoded).len() != segment.symbol_digests.len() {
+            return Err(FecError::Unrecoverable(index));
+        }
+        out.write_all(decoded)?;
+        stats.segments_repaired += 1;
+    }
+
+    reader.check_footer()?;
+    Ok(stats)
+}
+
+/// Symbol size for a segment of `length` bytes: enough to stay within
+/// `TARGET_SYMBOLS` symbols where possible.
+fn symbol_size_for(length: u32) -> u16 {
+    let size = length
+        .div_ceil(TARGET_SYMBOLS)
+        .next_multiple_of(u32::from(SYMBOL_ALIGNMENT))
+        .clamp(MIN_SYMBOL_SIZE, MAX_SYMBOL_SIZE);
+    size as u16
+}
+
+/// Repair symbols stored for a segment of `source_symbols` symbols.
+fn repair_symbol_count(source_symbols: u32, loss_tolerance_percent: u8) -> u32 {
+    (source_symbols * u32::from(loss_tolerance_percent)).div_ceil(100) + DECODE_OVERHEAD
+}
+
+fn symbol_digest(symbol: &[u8]) -> [u8; SYMBOL_DIGEST_LEN] {
+    let digest = Sha256::digest(symbol);
+    digest[..SYMBOL_DIGEST_LEN]
+        .try_into()
+        .expect("digest is longer than a symbol digest")
+}
+
+/// Encodes one segment into its record, without the trailing checksum.
+fn encode_segment(offset: u64, data: &[u8], loss_tolerance_percent: u8) -> Vec<u8> {
+    let length = data.len() as u32;
+    let symbol_size = symbol_size_for(length);
+    let oti =
+        ObjectTransmissionInformation::new(u64::from(length), symbol_size, 1, 1, SYMBOL_ALIGNMENT);
+    let source_symbols = length.div_ceil(u32::from(symbol_size));
+    let mut padded = data.to_vec();
+    padded.resize(source_symbols as usize * usize::from(symbol_size), 0);
+    let encoder = SourceBlockEncoder::new2(0, &oti, &padded);
+    let repair_packets = encoder.repair_packets(
+        0,
+        repair_symbol_count(source_symbols, loss_tolerance_percent),
+    );
+
+    let mut record = Vec::new();
+    record.extend_from_slice(&offset.to_le_bytes());
+    record.extend_from_slice(&length.to_le_bytes());
+    record.extend_from_slice(&oti.serialize());
+    for symbol in padded.chunks(usize::from(symbol_size)) {
+        record.extend_from_slice(&symbol_digest(symbol));
+    }
+    record.extend_from_slice(&(repair_packets.len() as u32).to_le_bytes());
+    for packet in repair_packets {
+        let packet = packet.serialize();
+        record.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+        record.extend_from_slice(&packet);
+    }
+    record
+}
+
+/// One segment's FEC data, as read back.
+struct Segment {
+    length: u32,
+    oti: ObjectTransmissionInformation,
+    symbol_digests: Vec<[u8; SYMBOL_DIGEST_LEN]>,
+    repair_packets: Vec<EncodingPacket>,
+}
+
+impl Segment {
+    fn symbol_size(&self) -> usize {
+        usize::from(self.oti.symbol_size())
+    }
+
+    /// Indexes of the source symbols that are complete in `data` (this
+    /// segment's bytes as far as they are available) and match their digest.
+    fn intact_symbols(&self, data: &[u8]) -> Vec<u32> {
+        let symbol_size = self.symbol_size();
+        let mut symbol = Vec::with_capacity(symbol_size);
+        (0..self.symbol_digests.len())
+            .filter(|&index| {
+                let start = index * symbol_size;
+                let end = (self.length as usize).min(start + symbol_size);
+                if data.len() < end {
+                    return false;
+                }
+                symbol.clear();
+                symbol.extend_from_slice(&data[start..end]);
+                symbol.resize(symbol_size, 0);
+                symbol_digest(&symbol) == self.symbol_digests[index]
+            })
+            .map(|index| index as u32)
+            .collect()
+    }
+}
+
+fn read_header_from<R: Read>(reader: &mut FecReader<R>) -> Result<FecHeader, FecError> {
+    let magic: [u8; 4] = reader.read_array().map_err(|_| FecError::NotFec)?;
+    if &magic != MAGIC {
+        return Err(FecError::NotFec);
+    }
+    let version = reader.read_u32()?;
+    if version != FORMAT_VERSION {
+        return Err(FecError::UnsupportedVersion(version));
+    }
+    let original_size = reader.read_u64()?;
+    let segment_size = reader.read_u32()?;
+    let segments = reader.read_u32()?;
+    let loss_tolerance = reader.read_u32()?;
+
+    if segment_size == 0 || segment_size > DEFAULT_SEGMENT_SIZE {
+        return Err(FecError::Corrupt(format!(
+            "invalid segment size {segment_size}"
+        )));
+    }
+    if u64::from(segments) != original_size.div_ceil(u64::from(segment_size)) {
+        return Err(FecError::Corrupt(format!(
+            "{segments} segments do not cover {original_size} bytes"
+        )));
+    }
+    let loss_tolerance_percent = u8::try_from(loss_tolerance)
+        .ok()
+        .filter(|percent| (1..=100).contains(percent))
+        .ok_or_else(|| FecError::Corrupt(format!("invalid loss tolerance {loss_tolerance}")))?;
+    Ok(FecHeader {
+        original_size,
+        segment_size,
+        segments,
+        loss_tolerance_percent,
+    })
+}
+
+/// Reads segment `index`, checking that it is consistent with the header and
+/// intact.
+fn read_segment<R: Read>(
+    reader: &mut FecReader<R>,
+    header: &FecHeader,
+    index: u32,
+) -> Result<Segment, FecError> {
+    let corrupt = |what: &str| FecError::Corrupt(format!("segment {index}: {what}"));
+    reader.segment = Some(Sha256::new());
+
+    let offset = reader.read_u64()?;
+    let length = reader.read_u32()?;
+    let expected_offset = u64::from(index) * u64::from(header.segment_size);
+    let expected_length =
+        u64::from(header.segment_size).min(header.original_size - expected_offset) as u32;
+    if offset != expected_offset || length != expected_length {
+        return Err(corrupt("unexpected offset or length"));
+    }
+    let oti = ObjectTransmissionInformation::deserialize(&reader.read_array()?);
+    let symbol_size = symbol_size_for(length);
+    if oti.transfer_length() != u64::from(length)
+        || oti.symbol_size() != symbol_size
+        || oti.source_blocks() != 1
+        || oti.sub_blocks() != 1
+        || oti.symbol_alignment() != SYMBOL_ALIGNMENT
+    {
+        return Err(corrupt("unexpected encoding parameters"));
+    }
+    let source_symbols = length.div_ceil(u32::from(symbol_size));
+    let symbol_digests = (0..source_symbols)
+        .map(|_| reader.read_array())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let repair_count = reader.read_u32()?;
+    if repair_count > repair_symbol_count(source_symbols, 100) {
+        return Err(corrupt("too many repair packets"));
+    }
+    let packet_len = 4 + usize::from(symbol_size);
+    let mut repair_packets = Vec::with_capacity(repair_count as usize);
+    for _ in 0..repair_count {
+        if reader.read_u32()? as usize != packet_len {
+            return Err(corrupt("unexpected packet length"));
+        }
+        repair_packets.push(EncodingPacket::deserialize(&reader.read_vec(packet_len)?));
+    }
+
+    let computed = reader
+        .segment
+        .take()
+        .expect("segment hashing was started above")
+        .finalize();
+    let recorded: [u8; 32] = reader.read_array()?;
+    if computed.as_slice() != recorded {
+        return Err(corrupt("checksum mismatch"));
+    }
+    Ok(Segment {
+        length,
+        oti,
+        symbol_digests,
+        repair_packets,
+    })
+}
+
+/// Reads a FEC file, hashing everything for the footer check and, while a
+/// segment is being read, for that segment's check.
+struct FecReader<R> {
+    inner: R,
+    file: Sha256,
+    segment: Option<Sha256>,
+}
+
+impl<R: Read> FecReader<R> {
+    fn new(inner: R) -> Self {
+        FecReader {
+            inner,
+            file: Sha256::new(),
+            segment: None,
+        }
+    }
+
+    fn read_into(&mut self, buf: &mut [u8]) -> Result<(), FecError> {
+        self.inner.read_exact(buf).map_err(|e| {
+            if e.kind() == io::ErrorKind::UnexpectedEof {
+                FecError::Corrupt("truncated".to_string())
+            } else {
+                FecError::Io(e)
+            }
+        })?;
+        self.file.update(&*buf);
+        if let Some(segment) = &mut self.segment {
+            segment.update(&*buf);
+        }
+        Ok(())
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], FecError> {
+        let mut buf = [0u8; N];
+        self.read_into(&mut buf)?;
+        Ok(buf)
+    }
+
+    fn read_vec(&mut self, len: usize) -> Result<Vec<u8>, FecError> {
+        let mut buf = vec![0u8; len];
+        self.read_into(&mut buf)?;
+        Ok(buf)
+    }
+
+    fn read_u32(&mut self) -> Result<u32, FecError> {
+        Ok(u32::from_le_bytes(self.read_array()?))
+    }
+
+    fn read_u64(&mut self) -> Result<u64, FecError> {
+        Ok(u64::from_le_bytes(self.read_array()?))
+    }
+
+    /// Checks the footer against everything read, and that nothing follows.
+    fn check_footer(mut self) -> Result<(), FecError> {
+        let computed = std::mem::take(&mut self.file).finalize();
+        let recorded: [u8; 32] = self.read_array()?;
+        if computed.as_slice() != recorded {
+            return Err(FecError::Corrupt("checksum mismatch".to_string()));
+        }
+        if self.inner.read(&mut [0u8; 1])? != 0 {
+            return Err(FecError::Corrupt("trailing data".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// Writes through to `inner`, hashing everything written.
+struct HashingWriter<W> {
+    inner: W,
+    hasher: Sha256,
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_config(loss_tolerance_percent: u8) -> FecConfig {
+        FecConfig {
+            loss_tolerance_percent,
+            segment_size: 1024,
+        }
+    }
+
+    fn sample(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 31 % 251) as u8).collect()
+    }
+
+    fn generate(data: &[u8], config: &FecConfig) -> Vec<u8> {
+        let mut fec = Vec::new();
+        write_fec(&mut &data[..], data.len() as u64, config, &mut fec).unwrap();
+        fec
+    }
+
+    fn repaired(damaged: &[u8], fec: &[u8]) -> Result<(Vec<u8>, RepairStats), FecError> {
+        let mut out = Vec::new();
+        let stats = repair(&mut &damaged[..], &mut &fec[..], &mut out)?;
+        Ok((out, stats))
+    }
+
+    #[test]
+    fn test_intact_file_round_trips_without_decoding() {
+        let data = sample(5000);
+        let fec = generate(&data, &test_config(10));
+
+        let header = verify(&mut &fec[..]).unwrap();
+        let (out, stats) = repaired(&data, &fec).unwrap();
+
+        assert_eq!(header.original_size, 5000);
+        assert_eq!(header.segments, 5);
+        assert_eq!(header.loss_tolerance_percent, 10);
+        assert_eq!(out, data);
+        assert_eq!(stats.segments_repaired, 0);
+    }
+
+    #[test]
+    fn test_repairs_corruption_in_several_segments() {
+        let data = sample(4096 + 100);
+        let fec = generate(&data, &test_config(10));
+        let mut damaged = data.clone();
+        damaged[10] ^= 0xff;
+        damaged[2048 + 500] ^= 0x01;
+        damaged[4096 + 99] = 0;
+
+        let (out, stats) = repaired(&damaged, &fec).unwrap();
+
+        assert_eq!(out, data);
+        assert_eq!(stats.segments, 5);
+        assert_eq!(stats.segments_repaired, 3);
+    }
+
+    #[test]
+    fn test_repairs_truncated_file_and_ignores_extra_bytes() {
+        let data = sample(3000);
+        let fec = generate(&data, &test_config(50));
+
+        let (out, _) = repaired(&data[..2900], &fec).unwrap();
+        assert_eq!(out, data);
+
+        let mut extended = data.clone();
+        extended.extend_from_slice(b"appended");
+        let (out, stats) = repaired(&extended, &fec).unwrap();
+        assert_eq!(out, data);
+        assert_eq!(stats.segments_repaired, 0);
+    }
+
+    #[test]
+    fn test_full_loss_tolerance_rebuilds_from_nothing() {
+        let data = sample(2500);
+        let fec = generate(&data, &test_config(100));
+
+        let (out, stats) = repaired(&[], &fec).unwrap();
+
+        assert_eq!(out, data);
+        assert_eq!(stats.segments_repaired, 3);
+    }
+
+    #[test]
+    fn test_loss_beyond_tolerance_is_unrecoverable() {
+        let data = sample(1024);
+        let fec = generate(&data, &test_config(10));
+        let mut damaged = data.clone();
+        damaged[..512].fill(0);
+
+        assert!(matches!(
+            repaired(&damaged, &fec),
+            Err(FecError::Unrecoverable(0))
+        ));
+    }
+
+    #[test]
+    fn test_edge_sizes() {
+        for len in [0, 1, 63, 64, 1023, 1024, 1025, 2048] {
+            let data = sample(len);
+            let fec = generate(&data, &test_config(100));
+
+            let (out, _) = repaired(&[], &fec).unwrap();
+
+            assert_eq!(out, data, "length {len}");
+            assert_eq!(
+                verify(&mut &fec[..]).unwrap().segments as usize,
+                len.div_ceil(1024)
+            );
+        }
+    }
+
+    #[test]
+    fn test_damaged_fec_data_is_detected() {
+        let data = sample(2000);
+        let fec = generate(&data, &test_config(20));
+
+        let mut flipped = fec.clone();
+        flipped[100] ^= 0x01;
+        assert!(matches!(
+            verify(&mut &flipped[..]),
+            Err(FecError::Corrupt(_))
+        ));
+        let mut damaged = data.clone();
+        damaged[0] ^= 0x01;
+        assert!(matches!(
+            repaired(&damaged, &flipped),
+            Err(FecError::Corrupt(_))
+        ));
+
+        assert!(matches!(
+            verify(&mut &fec[..fec.len() - 1]),
+            Err(FecError::Corrupt(_))
+        ));
+        let mut trailing = fec.clone();
+        trailing.push(0);
+        assert!(matches!(
+            verify(&mut &trailing[..]),
+            Err(FecError::Corrupt(_))
+        ));
+        assert!(matches!(
+            verify(&mut &b"not fec"[..]),
+            Err(FecError::NotFec)
+        ));
+    }
+
+    #[test]
+    fn test_symbol_size_stays_within_raptorq_limits() {
+        assert_eq!(symbol_size_for(1), 64);
+        assert_eq!(symbol_size_for(1024 * 1024), 1024);
+        let size = symbol_size_for(DEFAULT_SEGMENT_SIZE);
+        assert_eq!(size % 8, 0);
+        assert!(DEFAULT_SEGMENT_SIZE.div_ceil(u32::from(size)) <= 1100);
+    }
+}
@@ -0,0 +1,202 @@
+//! Per-directory `.treewardignore` rules with gitignore semantics.
+//!
+//! Each directory may contain a `.treewardignore` file whose patterns apply to
+//! that directory and everything below it. Rules from deeper directories take
+//! precedence over inherited ones, and within one file the last matching
+//! pattern wins, exactly as with `.gitignore`. Matching itself is delegated to
+//! the `ignore` crate's gitignore implementation.
+
+use ignore::Match;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::util::dir_handle::DirHandle;
+
+pub(crate) const TREEWARDIGNORE_FILENAME: &str = ".treewardignore";
+
+#[derive(Debug, thiserror::Error)]
+pub enum IgnoreError {
+    #[error("IO error reading {0}: {1}")]
+    Io(PathBuf, std::io::Error),
+    #[error("Permission denied: {0}")]
+    PermissionDenied(PathBuf),
+    #[error("Invalid pattern in {0}: {1}")]
+    InvalidPattern(PathBuf, ignore::Error),
+}
+
+/// The ignore rules in effect for one directory: its own `.treewardignore`
+/// layered over those of its ancestors within the tree.
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreRules {
+    /// Outermost directory first. Shared between sibling directories, hence `Arc`.
+    layers: Vec<Arc<Gitignore>>,
+}
+
+impl IgnoreRules {
+    /// Rules for the child directory `dir`, adding its `.treewardignore` if
+    /// `ignore_file_in` is given. The caller passes the directory's handle
+    /// only when its listing shows the file exists and is a regular file, and
+    /// the file is read through the handle without following a symlink, so
+    /// rules cannot be pulled in from outside the tree.
+    pub fn descend(
+        &self,
+        dir: &Path,
+        ignore_file_in: Option<&DirHandle>,
+    ) -> Result<Self, IgnoreError> {
+        let Some(handle) = ignore_file_in else {
+            return Ok(self.clone());
+        };
+
+        let path = dir.join(TREEWARDIGNORE_FILENAME);
+        let content = handle
+            .read_to_string(OsStr::new(TREEWARDIGNORE_FILENAME))
+            .map_err(|e| {
+                if e.kind() == std::io::ErrorKind::PermissionDenied {
+                    IgnoreError::PermissionDenied(path.clone())
+                } else {
+                    IgnoreError::Io(path.clone(), e)
+                }
+            })?;
+
+        // Unlike git, an invalid pattern is an error rather than silently
+        // skipped: a typo must not quietly put files back under (or out of)
+        // integrity checking.
+        let mut builder = GitignoreBuilder::new(dir);
+        for line in content.lines() {
+            builder
+                .add_line(Some(path.clone()), line)
+                .map_err(|e| IgnoreError::InvalidPattern(path.clone(), e))?;
+        }
+        let gitignore = builder
+            .build()
+            .map_err(|e| IgnoreError::InvalidPattern(path.clone(), e))?;
+
+        let mut layers = self.layers.clone();
+        layers.push(Arc::new(gitignore));
+        Ok(IgnoreRules { layers })
+    }
+
+    /// Whether `path` (a child of the directory these rules were built for)
+    /// is ignored. `is_dir` selects whether directory-only patterns (`foo/`)
+    /// apply.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        for layer in self.layers.iter().rev() {
+            match layer.matched(path, is_dir) {
+                Match::None => continue,
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn rules_for(dir: &Path, patterns: &str) -> IgnoreRules {
+        fs::write(dir.join(TREEWARDIGNORE_FILENAME), patterns).unwrap();
+        IgnoreRules::default()
+            .descend(dir, Some(&DirHandle::open(dir).unwrap()))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_unanchored_pattern_matches_at_any_depth() {
+        let temp = TempDir::new().unwrap();
+        let rules = rules_for(temp.path(), "*.swp\n");
+
+        assert!(rules.is_ignored(&temp.path().join("a.swp"), false));
+        assert!(rules.is_ignored(&temp.path().join("sub/deeper/b.swp"), false));
+        assert!(!rules.is_ignored(&temp.path().join("a.txt"), false));
+    }
+
+    #[test]
+    fn test_anchored_pattern_matches_only_relative_to_its_directory() {
+        let temp = TempDir::new().unwrap();
+        let rules = rules_for(temp.path(), "/build\n");
+
+        assert!(rules.is_ignored(&temp.path().join("build"), true));
+        assert!(!rules.is_ignored(&temp.path().join("sub/build"), true));
+    }
+
+    #[test]
+    fn test_directory_only_pattern() {
+        let temp = TempDir::new().unwrap();
+        let rules = rules_for(temp.path(), "cache/\n");
+
+        assert!(rules.is_ignored(&temp.path().join("cache"), true));
+        assert!(!rules.is_ignored(&temp.path().join("cache"), false));
+    }
+
+    #[test]
+    fn test_negation_within_one_file() {
+        let temp = TempDir::new().unwrap();
+        let rules = rules_for(temp.path(), "*.log\n!keep.log\n");
+
+        assert!(rules.is_ignored(&temp.path().join("a.log"), false));
+        assert!(!rules.is_ignored(&temp.path().join("keep.log"), false));
+    }
+
+    #[test]
+    fn test_deeper_rules_override_inherited_ones() {
+        let temp = TempDir::new().unwrap();
+        let sub = temp.path().join("sub");
+        fs::create_dir(&sub).unwrap();
+        let parent = rules_for(temp.path(), "*.log\n");
+        fs::write(sub.join(TREEWARDIGNORE_FILENAME), "!important.log\n").unwrap();
+        let child = parent
+            .descend(&sub, Some(&DirHandle::open(&sub).unwrap()))
+            .unwrap();
+
+        assert!(child.is_ignored(&sub.join("other.log"), false));
+        assert!(!child.is_ignored(&sub.join("important.log"), false));
+        // The child's rules do not leak back up to the parent.
+        assert!(parent.is_ignored(&temp.path().join("important.log"), false));
+    }
+
+    #[test]
+    fn test_comments_and_blank_lines() {
+        let temp = TempDir::new().unwrap();
+        let rules = rules_for(temp.path(), "# a comment\n\n\\#literal\n");
+
+        assert!(!rules.is_ignored(&temp.path().join("# a comment"), false));
+        assert!(rules.is_ignored(&temp.path().join("#literal"), false));
+    }
+
+    #[test]
+    fn test_invalid_pattern_is_an_error() {
+        let temp = TempDir::new().unwrap();
+        fs::write(temp.path().join(TREEWARDIGNORE_FILENAME), "a[z-a]\n").unwrap();
+
+        let result = IgnoreRules::default()
+            .descend(temp.path(), Some(&DirHandle::open(temp.path()).unwrap()));
+
+        assert!(matches!(result, Err(IgnoreError::InvalidPattern(..))));
+    }
+
+    /// An ignore file swapped for a symlink after the listing saw a regular
+    /// file is not followed.
+    #[test]
+    #[cfg(unix)]
+    fn test_symlinked_ignore_file_is_not_read() {
+        let temp = TempDir::new().unwrap();
+        let outside = TempDir::new().unwrap();
+        fs::write(outside.path().join("rules"), "*\n").unwrap();
+        std::os::unix::fs::symlink(
+            outside.path().join("rules"),
+            temp.path().join(TREEWARDIGNORE_FILENAME),
+        )
+        .unwrap();
+
+        let result = IgnoreRules::default()
+            .descend(temp.path(), Some(&DirHandle::open(temp.path()).unwrap()));
+
+        assert!(matches!(result, Err(IgnoreError::Io(..))), "{result:?}");
+    }
+}
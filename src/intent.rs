@@ -0,0 +1,582 @@
+//! Write-ahead intent record for the ward files an update rewrites.
+//!
+//! Each `.treeward` is replaced atomically, but an update rewrites many of
+//! them one after another, so a crash or a full disk halfway leaves a mix of
+//! old and new ward files that no fingerprint describes. Before writing any,
+//! `ward_directory` therefore records in `.treeward-intent` at the root the
+//! previous and the new content (and signature) of every ward file it is
+//! about to rewrite, with its journal record (which the new root ward file
+//! records as the journal head), and removes the intent record once
+//! everything is written. `undo_last_update` does the same for the ward files
+//! it puts back, and `migrate_tree` for the ward files it converts, which it
+//! does not journal.
+//!
+//! An intent record found later belongs to an interrupted update, and
+//! `recover` settles it: if every ward file was written, the update is rolled
+//! forward (signatures and the journal record completed); otherwise it is
+//! rolled back to the previous ward files. Either way the tree ends up in a
+//! state that one fingerprint described.
+
+use crate::dir_list::{
+    INTENT_FILENAME, JOURNAL_FILENAME, SIGNATURE_FILENAME, TREEWARD_FILENAME, UNDO_DIRNAME,
+};
+use crate::journal::{JournalError, JournalRecord, RecordKind, append_record, host_name};
+use crate::undo;
+use crate::util::dir_handle::DirHandle;
+use crate::util::names;
+use crate::ward_file::{WardFileError, sync_dir, write_atomic_in};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, thiserror::Error)]
+pub enum IntentError {
+    #[error("Ward file error: {0}")]
+    WardFile(#[from] WardFileError),
+    #[error("Journal error: {0}")]
+    Journal(#[from] JournalError),
+    #[error("IO error on {}: {source}", path.display())]
+    Io { path: PathBuf, source: io::Error },
+    #[error("Invalid intent record {}: {source}", path.display())]
+    Invalid {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    #[error("Cannot serialize intent record: {0}")]
+    Serialize(#[from] serde_json::Error),
+    #[error(
+        "Another update is in progress or was interrupted ({} exists at the root)",
+        INTENT_FILENAME
+    )]
+    Pending,
+    #[error(
+        "Cannot recover an interrupted update: ward file {} matches neither its previous nor its new content (remove {} at the root to leave the ward files as they are)",
+        .0.display(),
+        INTENT_FILENAME
+    )]
+    Diverged(PathBuf),
+    #[error("Non-UTF-8 path not supported: {0:?}")]
+    NonUtf8(PathBuf),
+}
+
+/// What an update is about to do.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Intent {
+    pid: u32,
+    host: String,
+    /// When the update started writing, RFC 3339 in UTC.
+    started: String,
+    /// Size of the journal before the update, to tell whether its record
+    /// was appended.
+    journal_len: u64,
+    /// The update's journal record, exactly as it is to be appended; `None`
+    /// for a migration.
+    record: Option<JournalRecord>,
+    ward_files: Vec<IntendedWardFile>,
+}
+
+/// One ward file an update is about to rewrite.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct IntendedWardFile {
+    /// String form of the directory, relative to the root; empty for the root.
+    path: String,
+    /// Current content, `None` if the directory has no ward file yet.
+    previous: Option<String>,
+    /// Current signature, if any.
+    previous_signature: Option<String>,
+    /// New content, `None` if the ward file is removed (by `undo`, of a
+    /// directory the undone update created it in).
+    next: Option<String>,
+    /// Signature of `next`, when the update signs; otherwise the signature
+    /// is removed.
+    next_signature: Option<String>,
+}
+
+impl IntendedWardFile {
+    /// The ward file of the directory `relative` to the root.
+    pub(crate) fn new(
+        relative: &Path,
+        previous: Option<String>,
+        previous_signature: Option<String>,
+        next: Option<String>,
+        next_signature: Option<String>,
+    ) -> Result<Self, IntentError> {
+        let path = names::from_os(relative.as_os_str())
+            .ok_or_else(|| IntentError::NonUtf8(relative.to_path_buf()))?
+            .into_owned();
+        Ok(IntendedWardFile {
+            path,
+            previous,
+            previous_signature,
+            next,
+            next_signature,
+        })
+    }
+}
+
+/// How `recover` settled an interrupted update (or undo, as the journal
+/// record tells; a migration has none).
+#[derive(Debug, PartialEq, Eq)]
+pub enum Recovery {
+    /// Every ward file had been written; the rest of the update was
+    /// completed.
+    RolledForward {
+        record: Option<JournalRecord>,
+        ward_files: usize,
+    },
+    /// The ward files written so far were restored to their previous
+    /// content.
+    RolledBack {
+        record: Option<JournalRecord>,
+        ward_files: usize,
+    },
+}
+
+impl Intent {
+    pub(crate) fn new(record: Option<JournalRecord>, ward_files: Vec<IntendedWardFile>) -> Self {
+        Intent {
+            pid: std::process::id(),
+            host: host_name(),
+            started: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            journal_len: 0,
+            record,
+            ward_files,
+        }
+    }
+
+    /// Durably record the intent in the open root directory `root_dir`,
+    /// which is at `root`. Fails with `Pending` if an intent record is
+    /// already there: it must be recovered first.
+    pub(crate) fn begin(
+        mut self,
+        root_dir: &DirHandle,
+        root: &Path,
+    ) -> Result<ActiveIntent, IntentError> {
+        match root_dir.stat(INTENT_FILENAME.as_ref()) {
+            Ok(_) => return Err(IntentError::Pending),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(io_error(&root.join(INTENT_FILENAME), e)),
+        }
+        self.journal_len = journal_len(root_dir, root)?;
+        write_atomic_in(root_dir, root, INTENT_FILENAME, &serde_json::to_vec(&self)?)?;
+        Ok(ActiveIntent)
+    }
+}
+
+/// An intent record on disk, to be finished once the update is written.
+#[must_use]
+pub(crate) struct ActiveIntent;
+
+impl ActiveIntent {
+    /// Remove the intent record: the update is complete.
+    pub(crate) fn finish(self, root_dir: &DirHandle, root: &Path) -> Result<(), IntentError> {
+        remove_file(root_dir, root, INTENT_FILENAME)?;
+        Ok(sync_dir(root_dir)?)
+    }
+}
+
+/// Whether an intent record is left in the tree rooted at `root`: an update
+/// is running or was interrupted there.
+pub fn is_pending(root: &Path) -> bool {
+    fs::symlink_metadata(root.join(INTENT_FILENAME)).is_ok()
+}
+
+/// Settle the update interrupted in the tree rooted at `root`, if any.
+///
+/// Each ward file the update intended to rewrite is compared with its
+/// previous and new content. If all have their new content, the signatures
+/// are brought in line with it and the journal record (the one the new root
+/// ward file records as the head) is appended unless it already was; for an
+/// undo, the generation it undid is removed. Otherwise every one with its new
+/// content gets its previous content and signature back, and ones the update
+/// created are removed. Either way the intent record is then removed. A ward file with other
+/// content (changed since, by hand or by another tool) makes recovery fail
+/// with `Diverged`, leaving everything as it is.
+///
+/// The caller holds the tree's exclusive lock, which a running update holds
+/// until it is done, so any intent record found is of an update that stopped.
+pub fn recover(root: &Path) -> Result<Option<Recovery>, IntentError> {
+    let root_dir = match DirHandle::open(root) {
+        Ok(root_dir) => root_dir,
+        // Nothing to recover; whatever runs next reports the problem.
+        Err(e)
+            if matches!(
+                e.kind(),
+                io::ErrorKind::NotFound | io::ErrorKind::NotADirectory
+            ) =>
+        {
+            return Ok(None);
+        }
+        Err(e) => return Err(io_error(root, e)),
+    };
+    let path = root.join(INTENT_FILENAME);
+    let content = match root_dir.read_to_string(INTENT_FILENAME.as_ref()) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(io_error(&path, e)),
+    };
+    let intent: Intent =
+        serde_json::from_str(&content).map_err(|source| IntentError::Invalid { path, source })?;
+    let mut dirs = Vec::new();
+    let mut all_written = true;
+    for ward_file in &intent.ward_files {
+        let relative = PathBuf::from(names::to_os(&ward_file.path).into_owned());
+        let ward_path = relative.join(TREEWARD_FILENAME);
+        let dir = root_dir
+            .open_path(&relative)
+            .map_err(|_| IntentError::Diverged(ward_path.clone()))?;
+        let dir_path = root.join(&relative);
+        let current = read_optional(&dir, &dir_path, TREEWARD_FILENAME)?;
+        let written = current == ward_file.next;
+        if !written && current != ward_file.previous {
+            return Err(IntentError::Diverged(ward_path));
+        }
+        all_written &= written;
+        dirs.push((dir, dir_path, written));
+    }
+
+    let recovery = if all_written {
+        for (ward_file, (dir, dir_path, _)) in intent.ward_files.iter().zip(&dirs) {
+            set_file(dir, dir_path, SIGNATURE_FILENAME, &ward_file.next_signature)?;
+            sync_dir(dir)?;
+        }
+        if let Some(record) = &intent.record {
+            if journal_len(&root_dir, root)? == intent.journal_len {
+                append_record(&root_dir, root, record)?;
+            }
+            if record.kind == RecordKind::Undo {
+                undo::remove_generation(root).map_err(|e| io_error(&root.join(UNDO_DIRNAME), e))?;
+            }
+        }
+        Recovery::RolledForward {
+            record: intent.record,
+            ward_files: dirs.len(),
+        }
+    } else {
+        let mut restored = 0;
+        for (ward_file, (dir, dir_path, written)) in intent.ward_files.iter().zip(&dirs) {
+            if !written {
+                continue;
+            }
+            set_file(dir, dir_path, TREEWARD_FILENAME, &ward_file.previous)?;
+            set_file(
+                dir,
+                dir_path,
+                SIGNATURE_FILENAME,
+                &ward_file.previous_signature,
+            )?;
+            sync_dir(dir)?;
+            restored += 1;
+        }
+        Recovery::RolledBack {
+            record: intent.record,
+            ward_files: restored,
+        }
+    };
+
+    remove_file(&root_dir, root, INTENT_FILENAME)?;
+    sync_dir(&root_dir)?;
+    Ok(Some(recovery))
+}
+
+fn journal_len(root_dir: &DirHandle, root: &Path) -> Result<u64, IntentError> {
+    match root_dir.stat(JOURNAL_FILENAME.as_ref()) {
+        Ok(stat) => Ok(stat.size),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(0),
+        Err(e) => Err(io_error(&root.join(JOURNAL_FILENAME), e)),
+    }
+}
+
+fn read_optional(
+    dir: &DirHandle,
+    dir_path: &Path,
+    name: &str,
+) -> Result<Option<String>, IntentError> {
+    match dir.read_to_string(name.as_ref()) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        // Not valid UTF-8, so not anything treeward wrote.
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => Ok(Some(String::new())),
+        Err(e) => Err(io_error(&dir_path.join(name), e)),
+    }
+}
+
+/// Give `name` in `dir` the content `content`, or remove it for `None`.
+fn set_file(
+    dir: &DirHandle,
+    dir_path: &Path,
+    name: &str,
+    content: &Option<String>,
+) -> Result<(), IntentError> {
+    match content {
+        Some(content) => {
+            if read_optional(dir, dir_path, name)?.as_ref() != Some(content) {
+                write_atomic_in(dir, dir_path, name, content.as_bytes())?;
+            }
+            Ok(())
+        }
+        None => remove_file(dir, dir_path, name),
+    }
+}
+
+fn remove_file(dir: &DirHandle, dir_path: &Path, name: &str) -> Result<(), IntentError> {
+    match dir.remove_file(name.as_ref()) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(io_error(&dir_path.join(name), e)),
+        _ => Ok(()),
+    }
+}
+
+fn io_error(path: &Path, source: io::Error) -> IntentError {
+    IntentError::Io {
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::HashAlgorithm;
+    use crate::journal::read_journal;
+    use crate::status::ChecksumPolicy;
+    use crate::update::{WardOptions, ward_directory};
+    use std::num::NonZeroUsize;
+    use tempfile::TempDir;
+
+    fn update(root: &Path, init: bool) -> JournalRecord {
+        ward_directory(
+            root,
+            WardOptions {
+                init,
+                allow_init: false,
+                fingerprint: None,
+                accept_digest_mismatch: false,
+                dry_run: false,
+                checksum_policy: ChecksumPolicy::Never,
+                jobs: NonZeroUsize::MIN,
+                hash: HashAlgorithm::Sha256,
+                signing_key: None,
+                track: None,
+                protection: None,
+                keep_going: false,
+                pathspecs: Vec::new(),
+            },
+        )
+        .unwrap();
+        let journal = read_journal(root).unwrap();
+        journal.records.last().unwrap().clone()
+    }
+
+    fn ward_content(root: &Path, dir: &str) -> String {
+        fs::read_to_string(root.join(dir).join(TREEWARD_FILENAME)).unwrap()
+    }
+
+    /// A tree updated once after init, with the ward contents before and
+    /// after that update, by directory, and the update's journal record.
+    fn updated_tree() -> (TempDir, Vec<(&'static str, String, String)>, JournalRecord) {
+        let temp = TempDir::new().unwrap();
+        fs::create_dir(temp.path().join("sub")).unwrap();
+        fs::write(temp.path().join("sub/file.txt"), "before").unwrap();
+        update(temp.path(), true);
+        let before: Vec<_> = ["sub", ""]
+            .iter()
+            .map(|dir| ward_content(temp.path(), dir))
+            .collect();
+        fs::write(temp.path().join("sub/file.txt"), "after!").unwrap();
+        let record = update(temp.path(), false);
+        let ward_files = ["sub", ""]
+            .into_iter()
+            .zip(before)
+            .map(|(dir, before)| (dir, before, ward_content(temp.path(), dir)))
+            .collect();
+        (temp, ward_files, record)
+    }
+
+    /// Leaves an intent record for the update of `ward_files`, as an
+    /// interrupted process would.
+    fn leave_intent(
+        root: &Path,
+        ward_files: &[(&str, String, String)],
+        record: Option<&JournalRecord>,
+    ) {
+        let intent = Intent::new(
+            record.cloned(),
+            ward_files
+                .iter()
+                .map(|(dir, before, after)| {
+                    IntendedWardFile::new(
+                        Path::new(dir),
+                        Some(before.clone()),
+                        None,
+                        Some(after.clone()),
+                        None,
+                    )
+                    .unwrap()
+                })
+                .collect(),
+        );
+        let root_dir = DirHandle::open(root).unwrap();
+        let _ = intent.begin(&root_dir, root).unwrap();
+    }
+
+    #[test]
+    fn test_rolls_back_partly_written_update() {
+        let (temp, ward_files, record) = updated_tree();
+        // Interrupted after writing sub/.treeward but not the root's.
+        fs::write(temp.path().join(TREEWARD_FILENAME), &ward_files[1].1).unwrap();
+        leave_intent(temp.path(), &ward_files, Some(&record));
+
+        assert_eq!(
+            recover(temp.path()).unwrap(),
+            Some(Recovery::RolledBack {
+                record: Some(record),
+                ward_files: 1
+            })
+        );
+        for (dir, before, _) in &ward_files {
+            assert_eq!(&ward_content(temp.path(), dir), before);
+        }
+        assert!(!temp.path().join(INTENT_FILENAME).exists());
+        assert_eq!(recover(temp.path()).unwrap(), None);
+    }
+
+    #[test]
+    fn test_rolls_forward_fully_written_update() {
+        let (temp, ward_files, record) = updated_tree();
+        // Interrupted before the journal record was appended.
+        let journal_path = temp.path().join(JOURNAL_FILENAME);
+        let journal = fs::read_to_string(&journal_path).unwrap();
+        let first_line = journal.lines().next().unwrap();
+        fs::write(&journal_path, format!("{first_line}\n")).unwrap();
+        leave_intent(temp.path(), &ward_files, Some(&record));
+
+        assert_eq!(
+            recover(temp.path()).unwrap(),
+            Some(Recovery::RolledForward {
+                record: Some(record.clone()),
+                ward_files: 2
+            })
+        );
+        for (dir, _, after) in &ward_files {
+            assert_eq!(&ward_content(temp.path(), dir), after);
+        }
+        let journal = read_journal(temp.path()).unwrap();
+        assert!(journal.breaks.is_empty());
+        assert_eq!(journal.lost_head, None);
+        assert_eq!(journal.records, vec![journal.records[0].clone(), record]);
+    }
+
+    #[test]
+    fn test_rolls_forward_fully_written_undo() {
+        let (temp, ward_files, _) = updated_tree();
+        let journal_path = temp.path().join(JOURNAL_FILENAME);
+        let journal = fs::read_to_string(&journal_path).unwrap();
+        crate::undo::undo_last_update(temp.path(), false).unwrap();
+        let record = read_journal(temp.path()).unwrap().records[2].clone();
+        let ward_files: Vec<_> = ward_files
+            .into_iter()
+            .map(|(dir, _, after)| (dir, after, ward_content(temp.path(), dir)))
+            .collect();
+        // Interrupted before the journal record was appended and the undone
+        // generation removed.
+        fs::write(&journal_path, journal).unwrap();
+        let generation = temp.path().join(UNDO_DIRNAME).join("last");
+        fs::create_dir_all(&generation).unwrap();
+        leave_intent(temp.path(), &ward_files, Some(&record));
+
+        assert_eq!(
+            recover(temp.path()).unwrap(),
+            Some(Recovery::RolledForward {
+                record: Some(record.clone()),
+                ward_files: 2
+            })
+        );
+        let journal = read_journal(temp.path()).unwrap();
+        assert_eq!(journal.lost_head, None);
+        assert_eq!(journal.records.last(), Some(&record));
+        assert!(!generation.exists());
+    }
+
+    #[test]
+    fn test_rolls_forward_migration_without_journaling() {
+        let (temp, ward_files, _) = updated_tree();
+        let journal_path = temp.path().join(JOURNAL_FILENAME);
+        let journal = fs::read(&journal_path).unwrap();
+        leave_intent(temp.path(), &ward_files, None);
+
+        assert_eq!(
+            recover(temp.path()).unwrap(),
+            Some(Recovery::RolledForward {
+                record: None,
+                ward_files: 2
+            })
+        );
+        assert_eq!(fs::read(&journal_path).unwrap(), journal);
+        assert!(!temp.path().join(INTENT_FILENAME).exists());
+    }
+
+    #[test]
+    fn test_journal_record_is_not_appended_twice() {
+        let (temp, ward_files, record) = updated_tree();
+        let journal_path = temp.path().join(JOURNAL_FILENAME);
+        let journal = fs::read_to_string(&journal_path).unwrap();
+        let first_line = journal.lines().next().unwrap();
+        fs::write(&journal_path, format!("{first_line}\n")).unwrap();
+        leave_intent(temp.path(), &ward_files, Some(&record));
+        // Interrupted after the journal record was appended.
+        fs::write(&journal_path, &journal).unwrap();
+
+        recover(temp.path()).unwrap();
+
+        assert_eq!(read_journal(temp.path()).unwrap().records.len(), 2);
+    }
+
+    #[test]
+    fn test_diverged_ward_file_is_left_alone() {
+        let (temp, ward_files, record) = updated_tree();
+        leave_intent(temp.path(), &ward_files, Some(&record));
+        let edited = ward_files[0].2.clone() + "\n";
+        fs::write(temp.path().join("sub").join(TREEWARD_FILENAME), &edited).unwrap();
+
+        assert!(matches!(
+            recover(temp.path()),
+            Err(IntentError::Diverged(path)) if path == Path::new("sub/.treeward")
+        ));
+        assert_eq!(ward_content(temp.path(), "sub"), edited);
+        assert!(temp.path().join(INTENT_FILENAME).exists());
+    }
+
+    #[test]
+    fn test_update_refuses_to_start_over_an_intent() {
+        let (temp, ward_files, record) = updated_tree();
+        leave_intent(temp.path(), &ward_files, Some(&record));
+
+        let root_dir = DirHandle::open(temp.path()).unwrap();
+        let intent = Intent::new(Some(record), Vec::new());
+        assert!(matches!(
+            intent.begin(&root_dir, temp.path()),
+            Err(IntentError::Pending)
+        ));
+    }
+
+    /// Recovery runs under the exclusive lock, so the process named in the
+    /// record is not running the update even if its PID is in use again.
+    #[cfg(unix)]
+    #[test]
+    fn test_intent_of_a_reused_pid_is_recovered() {
+        let (temp, ward_files, record) = updated_tree();
+        leave_intent(temp.path(), &ward_files, Some(&record));
+        let intent_path = temp.path().join(INTENT_FILENAME);
+        let mut intent: Intent =
+            serde_json::from_str(&fs::read_to_string(&intent_path).unwrap()).unwrap();
+        intent.pid = std::os::unix::process::parent_id();
+        fs::write(&intent_path, serde_json::to_vec(&intent).unwrap()).unwrap();
+
+        assert!(matches!(
+            recover(temp.path()),
+            Ok(Some(Recovery::RolledForward { .. }))
+        ));
+        assert!(!intent_path.exists());
+    }
+}
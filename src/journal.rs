@@ -0,0 +1,612 @@
+//! Append-only journal of the updates accepted into a tree.
+//!
+//! Ward files only keep the latest recorded state. Every `init` or `update`
+//! that records something also appends one JSON line to `.treeward-journal`
+//! at the root: when, by whom, under which fingerprint and checksum policy,
+//! and which entries it accepted. So does every `undo`, naming the update it
+//! undid and the ward files it put back. The journal is never rewritten.
+//!
+//! Records are hash-chained: each carries its sequence number and the
+//! checksum of the previous record's line, so removing or altering any record
+//! but the last breaks the chain at the record after it, which `read_journal`
+//! reports. Removing records from the end leaves the chain intact, so the
+//! root `.treeward` records the sequence number and line checksum of the
+//! last record as its `journal` head (covered by its digest and signature),
+//! and `read_journal` also reports a journal that no longer holds that
+//! record.
+
+use crate::checksum::{HashAlgorithm, checksum_bytes};
+use crate::dir_list::{JOURNAL_FILENAME, TREEWARD_FILENAME};
+use crate::status::{ChecksumPolicy, StatusEntry, StatusType, status_type_code};
+use crate::util::dir_handle::DirHandle;
+use crate::ward_file::{WardEntry, WardFile, WardFileError};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, thiserror::Error)]
+pub enum JournalError {
+    #[error("IO error on {}: {source}", path.display())]
+    Io { path: PathBuf, source: io::Error },
+    #[error("Cannot serialize journal record: {0}")]
+    Serialize(#[from] serde_json::Error),
+    #[error("Ward file error: {0}")]
+    WardFile(#[from] WardFileError),
+}
+
+/// One accepted update, or the undoing of one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalRecord {
+    /// Position in the journal, counting from 0.
+    pub seq: u64,
+    /// Checksum of the previous record's line; `None` for the first record.
+    pub prev: Option<String>,
+    /// Left out for an update.
+    #[serde(default, skip_serializing_if = "RecordKind::is_update")]
+    pub kind: RecordKind,
+    /// When the record was written, RFC 3339 in UTC.
+    pub time: String,
+    pub user: String,
+    pub host: String,
+    /// Fingerprint of the accepted changes (see `StatusResult::fingerprint`);
+    /// for an undo, those of the update it undid.
+    pub fingerprint: String,
+    /// Checksum policy of an update; `None` for an undo.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy: Option<ChecksumPolicy>,
+    /// Entries an update accepted; empty for an undo.
+    pub entries: Vec<JournalEntry>,
+    /// Ward files an undo restored, or removed if the undone update had
+    /// created them, as paths relative to the root in string form.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub restored: Vec<String>,
+}
+
+/// What a journal record is of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordKind {
+    #[default]
+    Update,
+    Undo,
+}
+
+impl RecordKind {
+    fn is_update(&self) -> bool {
+        *self == RecordKind::Update
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RecordKind::Update => "update",
+            RecordKind::Undo => "undo",
+        }
+    }
+}
+
+/// The last record of the journal, as the root ward file records it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JournalHead {
+    pub seq: u64,
+    /// Checksum of the record's line, as `prev` of a record after it has.
+    pub hash: String,
+}
+
+impl JournalRecord {
+    /// The head of a journal that ends with this record.
+    pub fn head(&self) -> Result<JournalHead, JournalError> {
+        Ok(JournalHead {
+            seq: self.seq,
+            hash: line_checksum(&serde_json::to_vec(self)?),
+        })
+    }
+}
+
+/// One entry an update accepted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// Same code as the status listing (`A`, `R`, `M?`, `M`, `R>`, `P`).
+    pub code: String,
+    pub path: String,
+    /// Previous path of a renamed entry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    /// Recorded checksum of an added or changed file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+}
+
+impl JournalEntry {
+    /// The entry for an accepted status entry; `None` for unchanged and
+    /// unreadable entries, which an update does not change.
+    pub fn from_status(entry: &StatusEntry) -> Option<Self> {
+        let status_type = entry.status_type();
+        if matches!(status_type, StatusType::Unchanged | StatusType::Error) {
+            return None;
+        }
+        let (from, ward_entry) = match entry {
+            StatusEntry::Renamed {
+                from, ward_entry, ..
+            } => (Some(from.clone()), ward_entry.as_ref()),
+            StatusEntry::Added { ward_entry, .. }
+            | StatusEntry::Modified { ward_entry, .. }
+            | StatusEntry::PossiblyModified { ward_entry, .. }
+            | StatusEntry::PermissionsChanged { ward_entry, .. } => (None, ward_entry.as_ref()),
+            _ => (None, None),
+        };
+        let checksum = match ward_entry {
+            Some(WardEntry::File { checksum, .. }) => Some(checksum.to_string()),
+            _ => None,
+        };
+        Some(JournalEntry {
+            code: status_type_code(status_type).to_string(),
+            path: entry.path().to_string(),
+            from,
+            checksum,
+        })
+    }
+
+    /// Whether the entry (or, for a rename, its previous path) is `path` or
+    /// below it. Paths are in string form; the empty path is the root.
+    pub fn touches(&self, path: &str) -> bool {
+        let below = |entry_path: &str| is_at_or_below(entry_path, path);
+        below(&self.path) || self.from.as_deref().is_some_and(below)
+    }
+}
+
+/// Whether `entry_path` is `path` or below it, both in string form; every
+/// path is below the root, the empty path.
+pub fn is_at_or_below(entry_path: &str, path: &str) -> bool {
+    path.is_empty()
+        || entry_path == path
+        || entry_path
+            .strip_prefix(path)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// The records of a journal, with the breaks found in its chain.
+#[derive(Debug, Default)]
+pub struct Journal {
+    pub records: Vec<JournalRecord>,
+    /// Line number (from 1) and description of each break in the chain.
+    pub breaks: Vec<(usize, String)>,
+    /// Description of how the journal lost the record the root ward file
+    /// records as its head, if it did.
+    pub lost_head: Option<String>,
+}
+
+/// Reads the journal of the tree rooted at `root`, checking its chain and
+/// that it still holds the head recorded in the root ward file.
+///
+/// A missing journal has no records. A line that is not a record is a break
+/// and otherwise skipped; the record after it must chain to it all the same.
+/// Records after the head are not a problem.
+pub fn read_journal(root: &Path) -> Result<Journal, JournalError> {
+    let head = WardFile::load_with_content_if_exists(&root.join(TREEWARD_FILENAME))?
+        .and_then(|(ward_file, _)| ward_file.journal_head().cloned());
+    let path = root.join(JOURNAL_FILENAME);
+    let content = match fs::read(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(source) => return Err(JournalError::Io { path, source }),
+    };
+
+    let mut journal = Journal::default();
+    let mut head_found = false;
+    let mut previous: Option<(&[u8], Option<u64>)> = None;
+    for (index, line) in lines(&content).enumerate() {
+        let line_number = index + 1;
+        let Ok(record) = serde_json::from_slice::<JournalRecord>(line) else {
+            journal
+                .breaks
+                .push((line_number, "is not a journal record".to_string()));
+            previous = Some((line, None));
+            continue;
+        };
+        let (expected_prev, expected_seq) = match previous {
+            None => (None, Some(0)),
+            Some((line, seq)) => (Some(line_checksum(line)), seq.map(|seq| seq + 1)),
+        };
+        if record.prev != expected_prev {
+            journal.breaks.push((
+                line_number,
+                "does not chain to the line before it (records removed or altered)".to_string(),
+            ));
+        } else if expected_seq.is_some_and(|seq| seq != record.seq) {
+            journal.breaks.push((
+                line_number,
+                format!("has sequence number {} out of order", record.seq),
+            ));
+        }
+        head_found |= head
+            .as_ref()
+            .is_some_and(|head| head.seq == record.seq && head.hash == line_checksum(line));
+        previous = Some((line, Some(record.seq)));
+        journal.records.push(record);
+    }
+    if let Some(head) = head
+        && !head_found
+    {
+        journal.lost_head = Some(
+            if journal.records.iter().any(|record| record.seq == head.seq) {
+                format!(
+                    "no longer holds record {} as the root ward file records it (records altered, or removed and others appended)",
+                    head.seq
+                )
+            } else {
+                format!(
+                    "ends before record {}, which the root ward file records as its last (records removed from the end)",
+                    head.seq
+                )
+            },
+        );
+    }
+    Ok(journal)
+}
+
+/// The record that appending an update with `fingerprint` and `policy` that
+/// accepted `entries` to the journal in the open root directory `root_dir`,
+/// which is at `root`, would add: it chains to the journal's last line,
+/// whatever it holds.
+///
+/// The record is prepared before the update writes anything, so that the
+/// root ward file can record it as the journal head (see `JournalHead`).
+pub(crate) fn next_record(
+    root_dir: &DirHandle,
+    root: &Path,
+    fingerprint: &str,
+    policy: ChecksumPolicy,
+    entries: Vec<JournalEntry>,
+) -> Result<JournalRecord, JournalError> {
+    Ok(JournalRecord {
+        policy: Some(policy),
+        entries,
+        ..chained_record(root_dir, root, fingerprint)?
+    })
+}
+
+/// The record that undoing the update with `fingerprint` by putting back the
+/// ward files `restored` would add, like `next_record`.
+pub(crate) fn next_undo_record(
+    root_dir: &DirHandle,
+    root: &Path,
+    fingerprint: &str,
+    restored: Vec<String>,
+) -> Result<JournalRecord, JournalError> {
+    Ok(JournalRecord {
+        kind: RecordKind::Undo,
+        restored,
+        ..chained_record(root_dir, root, fingerprint)?
+    })
+}
+
+/// A record with `fingerprint` chained to the journal's last line, with
+/// nothing else filled in.
+fn chained_record(
+    root_dir: &DirHandle,
+    root: &Path,
+    fingerprint: &str,
+) -> Result<JournalRecord, JournalError> {
+    let content = read_content(root_dir, root)?;
+    let last = lines(&content).last();
+    Ok(JournalRecord {
+        seq: last
+            .and_then(|line| serde_json::from_slice::<JournalRecord>(line).ok())
+            .map_or(lines(&content).count() as u64, |last| last.seq + 1),
+        prev: last.map(line_checksum),
+        kind: RecordKind::Update,
+        time: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        user: user_name(),
+        host: host_name(),
+        fingerprint: fingerprint.to_string(),
+        policy: None,
+        entries: Vec::new(),
+        restored: Vec::new(),
+    })
+}
+
+/// Appends `record` (see `next_record`) to the journal in the open root
+/// directory `root_dir`, which is at `root`, and syncs it.
+///
+/// A last line cut short (by a crash during an earlier append) is ended
+/// first, so the record starts on a line of its own.
+pub(crate) fn append_record(
+    root_dir: &DirHandle,
+    root: &Path,
+    record: &JournalRecord,
+) -> Result<(), JournalError> {
+    let io_error = |source| JournalError::Io {
+        path: root.join(JOURNAL_FILENAME),
+        source,
+    };
+    let mut file = root_dir
+        .open_append(JOURNAL_FILENAME.as_ref())
+        .map_err(io_error)?;
+    let content = read_content(root_dir, root)?;
+    let mut line = Vec::new();
+    if !content.is_empty() && !content.ends_with(b"\n") {
+        line.push(b'\n');
+    }
+    serde_json::to_writer(&mut line, record)?;
+    line.push(b'\n');
+    // One write, so that a concurrent reader never sees half a record in
+    // the normal case.
+    file.write_all(&line).map_err(io_error)?;
+    file.sync_all().map_err(io_error)?;
+    Ok(())
+}
+
+/// The journal's content, empty if there is none yet.
+fn read_content(root_dir: &DirHandle, root: &Path) -> Result<Vec<u8>, JournalError> {
+    let mut content = Vec::new();
+    match root_dir
+        .open_file(JOURNAL_FILENAME.as_ref())
+        .and_then(|mut reader| reader.read_to_end(&mut content))
+    {
+        Ok(_) => Ok(content),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(source) => Err(JournalError::Io {
+            path: root.join(JOURNAL_FILENAME),
+            source,
+        }),
+    }
+}
+
+/// The non-empty lines of `content`, without their newlines.
+fn lines(content: &[u8]) -> impl Iterator<Item = &[u8]> {
+    content
+        .split(|byte| *byte == b'\n')
+        .filter(|line| !line.is_empty())
+}
+
+fn line_checksum(line: &[u8]) -> String {
+    checksum_bytes(line, HashAlgorithm::Sha256).to_string()
+}
+
+/// Name of the effective user, or its ID if it has none.
+#[cfg(unix)]
+fn user_name() -> String {
+    // SAFETY: geteuid cannot fail.
+    let uid = unsafe { libc::geteuid() };
+    let mut buf = vec![0 as libc::c_char; 4096];
+    let mut passwd = std::mem::MaybeUninit::<libc::passwd>::uninit();
+    let mut result = std::ptr::null_mut();
+    // SAFETY: `passwd` is valid for writing a `struct passwd` and `buf` for
+    // `buf.len()` bytes; on success `result` points to `passwd`, whose
+    // strings point into `buf`.
+    let status = unsafe {
+        libc::getpwuid_r(
+            uid,
+            passwd.as_mut_ptr(),
+            buf.as_mut_ptr(),
+            buf.len(),
+            &mut result,
+        )
+    };
+    if status != 0 || result.is_null() {
+        return uid.to_string();
+    }
+    // SAFETY: getpwuid_r succeeded, so `pw_name` is a NUL-terminated string
+    // in `buf`, which is still alive.
+    unsafe { std::ffi::CStr::from_ptr((*result).pw_name) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[cfg(not(unix))]
+fn user_name() -> String {
+    std::env::var("USERNAME").unwrap_or_default()
+}
+
+#[cfg(unix)]
+pub(crate) fn host_name() -> String {
+    let mut buf = vec![0 as libc::c_char; 256];
+    // SAFETY: `buf` is valid for writing `buf.len()` bytes.
+    if unsafe { libc::gethostname(buf.as_mut_ptr(), buf.len()) } != 0 {
+        return String::new();
+    }
+    // The name may be cut short without a NUL.
+    *buf.last_mut().expect("buffer is not empty") = 0;
+    // SAFETY: `buf` is NUL-terminated.
+    unsafe { std::ffi::CStr::from_ptr(buf.as_ptr()) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[cfg(not(unix))]
+pub(crate) fn host_name() -> String {
+    std::env::var("COMPUTERNAME").unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dir_list::open_root;
+    use tempfile::TempDir;
+
+    fn entry(code: &str, path: &str) -> JournalEntry {
+        JournalEntry {
+            code: code.to_string(),
+            path: path.to_string(),
+            from: None,
+            checksum: None,
+        }
+    }
+
+    fn append(root: &Path, entries: Vec<JournalEntry>) -> JournalRecord {
+        let root_dir = open_root(root).unwrap();
+        let record = next_record(&root_dir, root, "fp", ChecksumPolicy::Never, entries).unwrap();
+        append_record(&root_dir, root, &record).unwrap();
+        record
+    }
+
+    fn journal_lines(root: &Path) -> Vec<String> {
+        fs::read_to_string(root.join(JOURNAL_FILENAME))
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_records_chain() {
+        let temp = TempDir::new().unwrap();
+        let first = append(temp.path(), vec![entry("A", "a.txt")]);
+        let second = append(temp.path(), vec![entry("M", "a.txt")]);
+
+        assert_eq!((first.seq, first.prev.as_deref()), (0, None));
+        assert_eq!(second.seq, 1);
+        assert_eq!(
+            second.prev,
+            Some(line_checksum(journal_lines(temp.path())[0].as_bytes()))
+        );
+        assert!(!first.user.is_empty());
+
+        let journal = read_journal(temp.path()).unwrap();
+        assert_eq!(journal.records, vec![first, second]);
+        assert!(journal.breaks.is_empty());
+    }
+
+    #[test]
+    fn test_undo_records_chain_and_name_their_kind() {
+        let temp = TempDir::new().unwrap();
+        append(temp.path(), vec![entry("A", "a.txt")]);
+        let root_dir = open_root(temp.path()).unwrap();
+        let undo =
+            next_undo_record(&root_dir, temp.path(), "fp", vec![".treeward".to_string()]).unwrap();
+        append_record(&root_dir, temp.path(), &undo).unwrap();
+
+        let lines = journal_lines(temp.path());
+        assert!(!lines[0].contains("\"kind\""));
+        assert!(lines[1].contains("\"kind\":\"undo\""));
+        assert!(!lines[1].contains("\"policy\""));
+        let journal = read_journal(temp.path()).unwrap();
+        assert!(journal.breaks.is_empty());
+        assert_eq!(journal.records[0].kind, RecordKind::Update);
+        assert_eq!(journal.records[1], undo);
+    }
+
+    #[test]
+    fn test_removed_or_altered_records_break_the_chain() {
+        let temp = TempDir::new().unwrap();
+        for path in ["a", "b", "c"] {
+            append(temp.path(), vec![entry("A", path)]);
+        }
+        let journal_path = temp.path().join(JOURNAL_FILENAME);
+        let lines = journal_lines(temp.path());
+
+        fs::write(&journal_path, format!("{}\n{}\n", lines[0], lines[2])).unwrap();
+        let journal = read_journal(temp.path()).unwrap();
+        assert_eq!(journal.records.len(), 2);
+        assert_eq!(
+            journal
+                .breaks
+                .iter()
+                .map(|(line, _)| *line)
+                .collect::<Vec<_>>(),
+            vec![2]
+        );
+
+        let altered = lines[1].replace("\"b\"", "\"x\"");
+        fs::write(
+            &journal_path,
+            format!("{}\n{altered}\n{}\n", lines[0], lines[2]),
+        )
+        .unwrap();
+        let journal = read_journal(temp.path()).unwrap();
+        assert_eq!(
+            journal
+                .breaks
+                .iter()
+                .map(|(line, _)| *line)
+                .collect::<Vec<_>>(),
+            vec![3]
+        );
+
+        fs::write(&journal_path, format!("{}\n{}\n", lines[1], lines[2])).unwrap();
+        let journal = read_journal(temp.path()).unwrap();
+        assert_eq!(
+            journal
+                .breaks
+                .iter()
+                .map(|(line, _)| *line)
+                .collect::<Vec<_>>(),
+            vec![1]
+        );
+    }
+
+    #[test]
+    fn test_journal_without_its_head_is_reported() {
+        let temp = TempDir::new().unwrap();
+        for path in ["a", "b"] {
+            append(temp.path(), vec![entry("A", path)]);
+        }
+        let journal_path = temp.path().join(JOURNAL_FILENAME);
+        let lines = journal_lines(temp.path());
+        let last = append(temp.path(), vec![entry("A", "c")]);
+        WardFile::new(Default::default())
+            .with_journal_head(Some(last.head().unwrap()))
+            .save(&temp.path().join(TREEWARD_FILENAME))
+            .unwrap();
+        assert_eq!(read_journal(temp.path()).unwrap().lost_head, None);
+
+        fs::write(&journal_path, format!("{}\n{}\n", lines[0], lines[1])).unwrap();
+        let journal = read_journal(temp.path()).unwrap();
+        assert!(journal.breaks.is_empty());
+        assert!(
+            journal
+                .lost_head
+                .unwrap()
+                .starts_with("ends before record 2")
+        );
+
+        append(temp.path(), vec![entry("A", "other")]);
+        let journal = read_journal(temp.path()).unwrap();
+        assert!(journal.breaks.is_empty());
+        assert!(
+            journal
+                .lost_head
+                .unwrap()
+                .starts_with("no longer holds record 2")
+        );
+    }
+
+    #[test]
+    fn test_append_after_torn_line_starts_a_new_line() {
+        let temp = TempDir::new().unwrap();
+        append(temp.path(), vec![entry("A", "a")]);
+        let journal_path = temp.path().join(JOURNAL_FILENAME);
+        let mut content = fs::read(&journal_path).unwrap();
+        content.extend_from_slice(b"{\"seq\":1,");
+        fs::write(&journal_path, &content).unwrap();
+
+        let record = append(temp.path(), vec![entry("A", "b")]);
+
+        assert_eq!(record.seq, 2);
+        let journal = read_journal(temp.path()).unwrap();
+        assert_eq!(journal.records.len(), 2);
+        assert_eq!(
+            journal
+                .breaks
+                .iter()
+                .map(|(line, _)| *line)
+                .collect::<Vec<_>>(),
+            vec![2]
+        );
+    }
+
+    #[test]
+    fn test_entry_touches_path_and_below() {
+        let renamed = JournalEntry {
+            from: Some("old/name".to_string()),
+            ..entry("R>", "new/name")
+        };
+        assert!(renamed.touches(""));
+        assert!(renamed.touches("new"));
+        assert!(renamed.touches("old/name"));
+        assert!(!renamed.touches("ne"));
+        assert!(!renamed.touches("new/name/below"));
+    }
+}
@@ -0,0 +1,299 @@
+//! Advisory lock that keeps treeward processes working on the same tree from
+//! getting in each other's way.
+//!
+//! Commands that write ward files (`init`, `update`, `undo`, ...) hold an
+//! exclusive lock on `.treeward-lock` at the root while they run, and commands
+//! that only read them (`status`, `verify`, ...) a shared one. Two updates
+//! therefore never race their writes, and a reader never sees an update half
+//! written. The lock is a `flock` lock, which the kernel releases when the
+//! process exits, however it exits.
+//!
+//! The lock file also names its holders, one JSON line each, so that a command
+//! finding the tree locked can say by whom: an exclusive holder replaces the
+//! content with its own line, a shared holder appends one, and whoever
+//! releases the lock last empties the file again. The file itself is never
+//! removed, as removing a lock file races with processes about to lock it.
+
+use crate::dir_list::LOCK_FILENAME;
+use crate::journal::host_name;
+use crate::util::escape_control;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+#[derive(Debug, thiserror::Error)]
+pub enum LockError {
+    #[error("IO error on {}: {source}", path.display())]
+    Io { path: PathBuf, source: io::Error },
+    #[error("{} is locked by {holders} (use --wait to wait for it)", root.display())]
+    Locked { root: PathBuf, holders: String },
+}
+
+/// How a command uses the tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LockMode {
+    /// Only reads ward files, alongside any number of other readers.
+    Shared,
+    /// Writes ward files, with no one else reading or writing them.
+    Exclusive,
+}
+
+/// A process holding the lock, as recorded in the lock file.
+#[derive(Debug, Serialize, Deserialize)]
+struct Holder {
+    pid: u32,
+    host: String,
+    mode: LockMode,
+}
+
+/// The lock on a tree, released when dropped.
+#[derive(Debug)]
+pub struct TreeLock {
+    /// `None` for a reader of a tree without a lock file.
+    file: Option<File>,
+    mode: LockMode,
+}
+
+impl TreeLock {
+    /// Lock the tree rooted at `root` for `mode`. If another process holds a
+    /// conflicting lock, fails with `Locked`, naming it, or with `wait` blocks
+    /// until it is released.
+    ///
+    /// Only an exclusive lock creates the lock file. A reader of a tree
+    /// without one (written by a treeward that did not lock) goes without a
+    /// lock, and one that may not write it (a read-only tree) locks it
+    /// without recording itself.
+    pub fn acquire(root: &Path, mode: LockMode, wait: bool) -> Result<TreeLock, LockError> {
+        let path = root.join(LOCK_FILENAME);
+        let io_error = |source| LockError::Io {
+            path: path.clone(),
+            source,
+        };
+
+        let mut options = OpenOptions::new();
+        options
+            .read(true)
+            .append(true)
+            .create(mode == LockMode::Exclusive);
+        let (mut file, writable) = match options.open(&path) {
+            Ok(file) => (file, true),
+            Err(e) if mode == LockMode::Shared && e.kind() == io::ErrorKind::NotFound => {
+                return Ok(TreeLock { file: None, mode });
+            }
+            Err(e)
+                if mode == LockMode::Shared
+                    && matches!(
+                        e.kind(),
+                        io::ErrorKind::PermissionDenied | io::ErrorKind::ReadOnlyFilesystem
+                    ) =>
+            {
+                (File::open(&path).map_err(io_error)?, false)
+            }
+            Err(e) => return Err(io_error(e)),
+        };
+
+        let locked = match mode {
+            LockMode::Shared => file.try_lock_shared(),
+            LockMode::Exclusive => file.try_lock(),
+        };
+        match locked {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let holders = describe_holders(&path);
+                if !wait {
+                    return Err(LockError::Locked {
+                        root: root.to_path_buf(),
+                        holders,
+                    });
+                }
+                info!("Waiting for {holders} to release {}", root.display());
+                match mode {
+                    LockMode::Shared => file.lock_shared(),
+                    LockMode::Exclusive => file.lock(),
+                }
+                .map_err(io_error)?;
+            }
+            Err(TryLockError::Error(e)) => return Err(io_error(e)),
+        }
+
+        if writable {
+            if mode == LockMode::Exclusive {
+                file.set_len(0).map_err(io_error)?;
+            }
+            let holder = Holder {
+                pid: std::process::id(),
+                host: host_name(),
+                mode,
+            };
+            let mut line = serde_json::to_vec(&holder).expect("holder serializes");
+            line.push(b'\n');
+            file.write_all(&line).map_err(io_error)?;
+        }
+        Ok(TreeLock {
+            file: Some(file),
+            mode,
+        })
+    }
+}
+
+impl Drop for TreeLock {
+    fn drop(&mut self) {
+        let Some(file) = &self.file else {
+            return;
+        };
+        // A reader is the last holder if it can then lock exclusively. Failing
+        // to empty the file only leaves lines of holders that are gone.
+        if self.mode == LockMode::Shared {
+            let _ = file.unlock();
+            if file.try_lock().is_err() {
+                return;
+            }
+        }
+        let _ = file.set_len(0);
+    }
+}
+
+/// The holders recorded in the lock file at `path` that may still hold the
+/// lock, for an error message: recorded processes of this host that are gone
+/// are left out.
+fn describe_holders(path: &Path) -> String {
+    let content = fs::read_to_string(path).unwrap_or_default();
+    let host = host_name();
+    let holders: Vec<String> = content
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Holder>(line).ok())
+        .filter(|holder| holder.host != host || is_running(holder.pid))
+        .map(|holder| {
+            let what = match holder.mode {
+                LockMode::Shared => "reading",
+                LockMode::Exclusive => "writing",
+            };
+            format!(
+                "process {} on {} ({what})",
+                holder.pid,
+                escape_control(&holder.host)
+            )
+        })
+        .collect();
+    if holders.is_empty() {
+        "another process".to_string()
+    } else {
+        holders.join(", ")
+    }
+}
+
+/// Whether process `pid` exists.
+#[cfg(unix)]
+fn is_running(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // SAFETY: signal 0 only checks that the process exists.
+    let result = unsafe { libc::kill(pid, 0) };
+    result == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+fn is_running(_pid: u32) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn holders(root: &Path) -> Vec<Holder> {
+        fs::read_to_string(root.join(LOCK_FILENAME))
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_exclusive_lock_excludes_everyone() {
+        let temp = TempDir::new().unwrap();
+        let lock = TreeLock::acquire(temp.path(), LockMode::Exclusive, false).unwrap();
+
+        for mode in [LockMode::Shared, LockMode::Exclusive] {
+            match TreeLock::acquire(temp.path(), mode, false) {
+                Err(LockError::Locked { holders, .. }) => {
+                    let expected = format!("process {} on", std::process::id());
+                    assert!(holders.starts_with(&expected), "{holders}");
+                    assert!(holders.ends_with("(writing)"), "{holders}");
+                }
+                other => panic!("expected Locked, got {other:?}"),
+            }
+        }
+
+        drop(lock);
+        TreeLock::acquire(temp.path(), LockMode::Exclusive, false).unwrap();
+    }
+
+    #[test]
+    fn test_readers_share_the_lock_and_exclude_writers() {
+        let temp = TempDir::new().unwrap();
+        drop(TreeLock::acquire(temp.path(), LockMode::Exclusive, false).unwrap());
+
+        let first = TreeLock::acquire(temp.path(), LockMode::Shared, false).unwrap();
+        let second = TreeLock::acquire(temp.path(), LockMode::Shared, false).unwrap();
+        assert_eq!(holders(temp.path()).len(), 2);
+        assert!(matches!(
+            TreeLock::acquire(temp.path(), LockMode::Exclusive, false),
+            Err(LockError::Locked { holders, .. }) if holders.ends_with("(reading)")
+        ));
+
+        drop(first);
+        assert_eq!(holders(temp.path()).len(), 2);
+        drop(second);
+        assert!(holders(temp.path()).is_empty());
+    }
+
+    #[test]
+    fn test_waits_for_the_holder() {
+        let temp = TempDir::new().unwrap();
+        let lock = TreeLock::acquire(temp.path(), LockMode::Exclusive, false).unwrap();
+
+        let root = temp.path().to_path_buf();
+        let waiter =
+            std::thread::spawn(move || TreeLock::acquire(&root, LockMode::Shared, true).is_ok());
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        drop(lock);
+        assert!(waiter.join().unwrap());
+    }
+
+    #[test]
+    fn test_reader_goes_without_a_lock_file() {
+        let temp = TempDir::new().unwrap();
+        TreeLock::acquire(temp.path(), LockMode::Shared, false).unwrap();
+        assert!(!temp.path().join(LOCK_FILENAME).exists());
+    }
+
+    #[test]
+    fn test_holders_that_are_gone_are_not_named() {
+        let temp = TempDir::new().unwrap();
+        drop(TreeLock::acquire(temp.path(), LockMode::Exclusive, false).unwrap());
+        let lock = TreeLock::acquire(temp.path(), LockMode::Shared, false).unwrap();
+        let stale = Holder {
+            pid: u32::MAX,
+            host: host_name(),
+            mode: LockMode::Exclusive,
+        };
+        let mut content = fs::read_to_string(temp.path().join(LOCK_FILENAME)).unwrap();
+        content.push_str(&serde_json::to_string(&stale).unwrap());
+        fs::write(temp.path().join(LOCK_FILENAME), content).unwrap();
+
+        match TreeLock::acquire(temp.path(), LockMode::Exclusive, false) {
+            Err(LockError::Locked { holders, .. }) => {
+                assert_eq!(holders.matches("process").count(), 1, "{holders}");
+                assert!(!holders.contains(&u32::MAX.to_string()), "{holders}");
+            }
+            other => panic!("expected Locked, got {other:?}"),
+        }
+        drop(lock);
+    }
+}
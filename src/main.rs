@@ -5,30 +5,58 @@
 //!
 //! Tracing setup for CLI output is initialized here.
 
+mod attributes;
+mod baseline;
 mod checksum;
 mod cli;
 mod diffing;
+mod digest;
 mod dir_list;
+mod discover;
+mod fec;
+mod ignore_rules;
+mod intent;
+mod journal;
+mod lock;
+mod migrate;
+mod mirror;
+mod pathspec;
+mod protection;
+mod repair;
+mod signing;
 mod status;
+mod undo;
 mod update;
 mod util;
 mod ward_file;
+mod watch;
 
-use cli::{Cli, Command, LogLevel};
-use status::ChecksumPolicy;
+use attributes::Track;
+use checksum::HashAlgorithm;
+use cli::{BaselineCommand, Cli, Command, HashAlgorithmArg, LogLevel, OutputFormat, WatchFormat};
+use discover::WardRoot;
+use intent::Recovery;
+use lock::{LockMode, TreeLock};
+use migrate::{MigrateOptions, migrate_tree};
+use protection::Protection;
+use repair::{RepairOptions, RepairSource, repair_tree};
+use signing::{SigningKey, TrustedKey};
+use status::{ChecksumPolicy, StatusOptions};
 use std::fmt as stdfmt;
 use std::io::{IsTerminal, stderr};
-use std::path::Path;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
 use std::process::ExitCode;
-use tracing::{Event, Level, Subscriber, error, info};
+use tracing::{Event, Level, Subscriber, error, info, warn};
 use tracing_subscriber::filter::EnvFilter;
 use tracing_subscriber::fmt as tracing_fmt;
 use tracing_subscriber::fmt::FmtContext;
 use tracing_subscriber::fmt::format::{FormatEvent, FormatFields, Writer};
 use tracing_subscriber::prelude::*;
 use tracing_subscriber::registry::LookupSpan;
-use update::{WardOptions, ward_directory};
+use update::{WardOptions, WardResult, set_protection, ward_directory};
 use util::escape_control;
+use watch::{WatchEvent, WatchOptions};
 
 fn checksum_policy_from_flags(always_verify: bool, verify: bool) -> ChecksumPolicy {
     match (always_verify, verify) {
@@ -51,11 +79,19 @@ fn follow_up_verify_flag(always_verify: bool, verify: bool, diff: bool) -> &'sta
 struct WardExitCode;
 
 impl WardExitCode {
-    /// Exit code used when the ward status is unclean (differences found).
+    /// Exit code used when the ward status is unclean (differences found, or
+    /// with `--keep-going`, entries that could not be read).
     fn status_unclean() -> ExitCode {
         ExitCode::from(1)
     }
 
+    /// Exit code used by `verify` when a ward file does not match the digest
+    /// recorded in its parent: the ward itself was tampered with, so the
+    /// other results cannot be trusted.
+    fn digest_mismatch() -> ExitCode {
+        ExitCode::from(2)
+    }
+
     /// Exit code used for other errors (I/O errors, invalid arguments, etc.).
     fn any_error() -> ExitCode {
         ExitCode::from(255)
@@ -80,44 +116,199 @@ fn main() -> ExitCode {
     }
 
     let current_dir = Path::new(".");
+    let wait = cli.wait;
 
     let result: anyhow::Result<ExitCode> = match cli.command {
         Command::Update {
             allow_init,
             fingerprint,
+            accept_digest_mismatch,
             dry_run,
             verify,
             always_verify,
+            jobs,
+            hash,
+            signing_key,
+            track,
+            keep_going,
+            no_discover,
+            paths,
         } => handle_init_or_update(
             current_dir,
+            !no_discover,
+            wait,
             false,
             allow_init,
             fingerprint,
+            accept_digest_mismatch,
             dry_run,
             verify,
             always_verify,
+            jobs,
+            hash_algorithm(hash),
+            signing_key.as_deref(),
+            track,
+            keep_going,
+            paths,
         ),
         Command::Init {
             fingerprint,
             dry_run,
             verify,
             always_verify,
+            jobs,
+            hash,
+            signing_key,
+            track,
         } => handle_init_or_update(
             current_dir,
+            false,
+            wait,
             true,
             false,
             fingerprint,
+            false,
             dry_run,
             verify,
             always_verify,
+            jobs,
+            hash_algorithm(hash),
+            signing_key.as_deref(),
+            track,
+            false,
+            Vec::new(),
         ),
         Command::Status {
             verify,
             always_verify,
             all,
             diff,
-        } => handle_status(current_dir, verify, always_verify, all, diff),
-        Command::Verify => handle_verify(current_dir),
+            ignored,
+            jobs,
+            hash,
+            format,
+            trusted_key,
+            against,
+            track,
+            keep_going,
+            no_discover,
+            paths,
+        } => handle_status(
+            current_dir,
+            !no_discover,
+            wait,
+            verify,
+            always_verify,
+            all,
+            diff,
+            ignored,
+            jobs,
+            hash_algorithm(hash),
+            format,
+            trusted_key.as_deref(),
+            against.as_deref(),
+            track,
+            keep_going,
+            &paths,
+        ),
+        Command::Verify {
+            jobs,
+            format,
+            trusted_key,
+            keep_going,
+            no_discover,
+            paths,
+        } => handle_verify(
+            current_dir,
+            !no_discover,
+            wait,
+            jobs,
+            format,
+            trusted_key.as_deref(),
+            keep_going,
+            &paths,
+        ),
+        Command::Protect {
+            loss_tolerance,
+            dry_run,
+            jobs,
+            no_discover,
+            paths,
+        } => handle_set_protection(
+            current_dir,
+            !no_discover,
+            wait,
+            Protection::Enabled { loss_tolerance },
+            dry_run,
+            jobs,
+            paths,
+        ),
+        Command::Unprotect {
+            dry_run,
+            jobs,
+            no_discover,
+            paths,
+        } => handle_set_protection(
+            current_dir,
+            !no_discover,
+            wait,
+            Protection::Disabled,
+            dry_run,
+            jobs,
+            paths,
+        ),
+        Command::Repair {
+            from,
+            force,
+            dry_run,
+            jobs,
+            no_discover,
+            paths,
+        } => handle_repair(
+            current_dir,
+            !no_discover,
+            wait,
+            RepairOptions {
+                dry_run,
+                jobs,
+                mirror: from,
+                force,
+                pathspecs: paths,
+            },
+        ),
+        Command::Log { no_discover, path } => handle_log(current_dir, !no_discover, wait, path),
+        Command::Undo {
+            dry_run,
+            no_discover,
+        } => handle_undo(current_dir, !no_discover, wait, dry_run),
+        Command::Baseline {
+            no_discover,
+            command,
+        } => handle_baseline(current_dir, !no_discover, wait, command),
+        Command::Digest { hash, no_discover } => {
+            handle_digest(current_dir, !no_discover, wait, hash_algorithm(hash))
+        }
+        Command::Migrate {
+            dry_run,
+            no_discover,
+        } => handle_migrate(current_dir, !no_discover, wait, dry_run),
+        Command::Watch {
+            verify,
+            debounce,
+            jobs,
+            format,
+            no_discover,
+        } => handle_watch(
+            current_dir,
+            !no_discover,
+            wait,
+            WatchOptions {
+                verify,
+                debounce: std::time::Duration::from_millis(debounce),
+                jobs,
+            },
+            format,
+        ),
     };
 
     match result {
@@ -129,48 +320,91 @@ fn main() -> ExitCode {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn handle_init_or_update(
     path: &Path,
+    discover: bool,
+    wait: bool,
     init: bool,
     allow_init: bool,
     fingerprint: Option<String>,
+    accept_digest_mismatch: bool,
     dry_run: bool,
     verify: bool,
     always_verify: bool,
+    jobs: NonZeroUsize,
+    hash: HashAlgorithm,
+    signing_key: Option<&Path>,
+    track: Option<Track>,
+    keep_going: bool,
+    paths: Vec<PathBuf>,
 ) -> anyhow::Result<ExitCode> {
+    let (ward_root, _lock) = find_ward_root(path, discover, writer_mode(dry_run), wait)?;
     let options = WardOptions {
         init,
         allow_init,
         fingerprint,
+        accept_digest_mismatch,
         dry_run,
         checksum_policy: checksum_policy_from_flags(always_verify, verify),
+        jobs,
+        hash,
+        signing_key: signing_key.map(SigningKey::load).transpose()?,
+        track,
+        protection: None,
+        keep_going,
+        pathspecs: ward_root.pathspecs(&paths),
     };
 
-    let result = ward_directory(path, options)?;
+    let result = ward_directory(&ward_root.root, options)?;
 
     if dry_run {
         info!("DRY RUN - no files were modified");
     }
 
-    info!("Warded {} files", result.files_warded);
+    log_ward_result(&ward_root, &result);
 
-    if !result.ward_files_updated.is_empty() {
-        info!("Updated {} ward files:", result.ward_files_updated.len());
-        for ward_path in result.ward_files_updated {
-            info!("  {}", ward_path.display());
+    if !result.errors.is_empty() {
+        for (path, kind) in &result.errors {
+            warn!(
+                "Could not read {}: {kind}",
+                escape_control(&ward_root.display_path(path))
+            );
         }
+        warn!(
+            "Kept the recorded state of {} entries that could not be read",
+            result.errors.len()
+        );
+        return Ok(WardExitCode::status_unclean());
     }
 
     Ok(ExitCode::SUCCESS)
 }
 
+#[allow(clippy::too_many_arguments)]
 fn handle_status(
     path: &Path,
+    discover: bool,
+    wait: bool,
     verify: bool,
     always_verify: bool,
     all: bool,
     diff: bool,
+    show_ignored: bool,
+    jobs: NonZeroUsize,
+    hash: HashAlgorithm,
+    format: OutputFormat,
+    trusted_key: Option<&Path>,
+    against: Option<&str>,
+    track: Option<Track>,
+    keep_going: bool,
+    paths: &[PathBuf],
 ) -> anyhow::Result<ExitCode> {
+    let (ward_root, _lock) = find_ward_root(path, discover, LockMode::Shared, wait)?;
+    let trusted_key = trusted_key.map(TrustedKey::load).transpose()?;
+    let baseline = against
+        .map(|name| baseline::baseline_tree(&ward_root.root, name))
+        .transpose()?;
     // --diff implies --verify (checksum files to show old vs new sha256)
     let policy = checksum_policy_from_flags(always_verify, verify || diff);
 
@@ -186,61 +420,599 @@ fn handle_status(
         status::DiffMode::None
     };
 
-    let result = status::compute_status(
-        path,
-        policy,
-        mode,
-        status::StatusPurpose::Display,
-        diff_mode,
+    let mut result = status::compute_status(
+        &ward_root.root,
+        &StatusOptions {
+            policy,
+            mode,
+            purpose: status::StatusPurpose::Display,
+            diff_mode,
+            jobs,
+            hash,
+            trusted_key: trusted_key.as_ref(),
+            track: track.as_ref(),
+            protection: None,
+            keep_going,
+            pathspecs: &ward_root.pathspecs(paths),
+            baseline: baseline.as_deref(),
+        },
     )?;
+    result.map_paths(|path| ward_root.display_path(path));
 
-    let has_interesting_changes = result
-        .statuses
-        .iter()
-        .any(|c| c.status_type() != status::StatusType::Unchanged);
+    warn_entry_errors(&result.statuses);
+    for ward_path in &result.digest_mismatches {
+        warn!(
+            "Ward file {} does not match the digest recorded in its parent ward (changed outside treeward)",
+            escape_control(ward_path)
+        );
+    }
+    warn_fec_states(&result.fec);
 
-    diffing::print_statuses(&result.statuses, diff);
+    let has_interesting_changes = !result.digest_mismatches.is_empty()
+        || result
+            .statuses
+            .iter()
+            .any(|c| c.status_type() != status::StatusType::Unchanged);
+
+    match format {
+        OutputFormat::Text => {
+            diffing::print_statuses(&result.statuses, diff);
+            if show_ignored {
+                diffing::print_ignored(&result.ignored);
+            }
+        }
+        OutputFormat::Json => diffing::json::print_json(&result, show_ignored),
+        OutputFormat::Ndjson => diffing::json::print_ndjson(&result, show_ignored),
+    }
 
     if !has_interesting_changes {
         return Ok(ExitCode::SUCCESS);
     }
+    // Changes relative to a baseline are not something update can accept.
+    if against.is_some() {
+        return Ok(WardExitCode::status_unclean());
+    }
 
-    println!();
-    println!("Fingerprint: {}", result.fingerprint);
+    if format == OutputFormat::Text {
+        println!();
+        println!("Fingerprint: {}", result.fingerprint);
+    }
 
     let verify_flag = follow_up_verify_flag(always_verify, verify, diff);
+    let keep_going_flag = if keep_going { " --keep-going" } else { "" };
+    let paths: String = paths
+        .iter()
+        .map(|path| format!(" {}", escape_control(&path.to_string_lossy())))
+        .collect();
 
     info!(
-        "Run 'treeward init|update{} --fingerprint {}' to accept these changes and update the ward.",
-        verify_flag, result.fingerprint
+        "Run 'treeward init|update{}{} --fingerprint {}{}' to accept these changes and update the ward.",
+        verify_flag, keep_going_flag, result.fingerprint, paths
     );
 
     Ok(WardExitCode::status_unclean())
 }
 
-fn handle_verify(path: &Path) -> anyhow::Result<ExitCode> {
-    let result = status::compute_status(
-        path,
-        ChecksumPolicy::Always,
-        status::StatusMode::Interesting,
-        status::StatusPurpose::Display,
-        status::DiffMode::None,
+#[allow(clippy::too_many_arguments)]
+fn handle_verify(
+    path: &Path,
+    discover: bool,
+    wait: bool,
+    jobs: NonZeroUsize,
+    format: OutputFormat,
+    trusted_key: Option<&Path>,
+    keep_going: bool,
+    paths: &[PathBuf],
+) -> anyhow::Result<ExitCode> {
+    let (ward_root, _lock) = find_ward_root(path, discover, LockMode::Shared, wait)?;
+    let trusted_key = trusted_key.map(TrustedKey::load).transpose()?;
+    let mut result = status::compute_status(
+        &ward_root.root,
+        &StatusOptions {
+            policy: ChecksumPolicy::Always,
+            mode: status::StatusMode::Interesting,
+            purpose: status::StatusPurpose::Display,
+            diff_mode: status::DiffMode::None,
+            jobs,
+            hash: HashAlgorithm::default(),
+            trusted_key: trusted_key.as_ref(),
+            track: None,
+            protection: None,
+            keep_going,
+            pathspecs: &ward_root.pathspecs(paths),
+            baseline: None,
+        },
     )?;
+    result.map_paths(|path| ward_root.display_path(path));
+
+    warn_entry_errors(&result.statuses);
+    match format {
+        OutputFormat::Text => diffing::print_statuses(&result.statuses, false),
+        OutputFormat::Json => diffing::json::print_json(&result, false),
+        OutputFormat::Ndjson => diffing::json::print_ndjson(&result, false),
+    }
+
+    for ward_path in &result.digest_mismatches {
+        error!(
+            "Tampering detected: ward file {} does not match the digest recorded in its parent ward",
+            escape_control(ward_path)
+        );
+    }
+    let lost_head = journal::read_journal(&ward_root.root)?.lost_head;
+    if let Some(problem) = &lost_head {
+        error!("Tampering detected: journal {problem}");
+    }
+
+    warn_fec_states(&result.fec);
+    let fec_failures = result
+        .fec
+        .iter()
+        .filter(|(_, state)| matches!(state, status::FecState::Missing | status::FecState::Damaged))
+        .count();
 
-    if result.statuses.is_empty() {
+    if result.statuses.is_empty()
+        && result.digest_mismatches.is_empty()
+        && lost_head.is_none()
+        && fec_failures == 0
+    {
         info!("Verification successful: No changes or corruption detected");
         return Ok(ExitCode::SUCCESS);
     }
 
-    diffing::print_statuses(&result.statuses, false);
+    if !result.digest_mismatches.is_empty() {
+        error!(
+            "Verification failed: ward digest chain broken at {} ward file(s)",
+            result.digest_mismatches.len()
+        );
+        return Ok(WardExitCode::digest_mismatch());
+    }
 
-    error!(
-        "Verification failed: {} change(s) detected",
-        result.statuses.len()
-    );
+    if result.statuses.is_empty() && lost_head.is_none() {
+        error!("Verification failed: {fec_failures} file(s) with missing or damaged FEC data");
+    } else if result.statuses.is_empty() {
+        error!("Verification failed: journal truncated");
+    } else {
+        error!(
+            "Verification failed: {} change(s) detected",
+            result.statuses.len()
+        );
+    }
     Ok(WardExitCode::status_unclean())
 }
 
+/// Log what an update wrote (or, in a dry run, would write).
+fn log_ward_result(ward_root: &WardRoot, result: &WardResult) {
+    info!("Warded {} files", result.files_warded);
+
+    let lists = [
+        ("Updated", "ward files", &result.ward_files_updated),
+        ("Signed", "ward files", &result.signatures_updated),
+        ("Wrote", "FEC files", &result.fec_files_written),
+        ("Removed", "FEC files", &result.fec_files_removed),
+    ];
+    for (verb, what, paths) in lists {
+        if !paths.is_empty() {
+            info!("{verb} {} {what}:", paths.len());
+            for path in paths {
+                info!("  {}", ward_root.display_path(&path.to_string_lossy()));
+            }
+        }
+    }
+}
+
+fn handle_set_protection(
+    path: &Path,
+    discover: bool,
+    wait: bool,
+    protection: Protection,
+    dry_run: bool,
+    jobs: NonZeroUsize,
+    paths: Vec<PathBuf>,
+) -> anyhow::Result<ExitCode> {
+    let (ward_root, _lock) = find_ward_root(path, discover, writer_mode(dry_run), wait)?;
+    let (result, previous) = set_protection(
+        &ward_root.root,
+        protection,
+        dry_run,
+        jobs,
+        ward_root.pathspecs(&paths),
+    )?;
+
+    if dry_run {
+        info!("DRY RUN - no files were modified");
+    }
+
+    if let Some(loss_tolerance) = protection.loss_tolerance() {
+        for (dir, recorded) in &previous {
+            if let Some(recorded) = recorded.loss_tolerance()
+                && recorded != loss_tolerance
+            {
+                let dir = ward_root.display_path(&dir.to_string_lossy());
+                warn!(
+                    "Changing the loss tolerance of {} from {recorded}% to {loss_tolerance}%",
+                    escape_control(if dir.is_empty() { "." } else { &dir })
+                );
+            }
+        }
+    }
+
+    log_ward_result(&ward_root, &result);
+    Ok(ExitCode::SUCCESS)
+}
+
+fn handle_repair(
+    path: &Path,
+    discover: bool,
+    wait: bool,
+    options: RepairOptions,
+) -> anyhow::Result<ExitCode> {
+    let dry_run = options.dry_run;
+    let (ward_root, _lock) = find_ward_root(path, discover, writer_mode(dry_run), wait)?;
+    let result = repair_tree(
+        &ward_root.root,
+        RepairOptions {
+            pathspecs: ward_root.pathspecs(&options.pathspecs),
+            ..options
+        },
+    )?;
+    let display = |path: &str| escape_control(&ward_root.display_path(path)).into_owned();
+
+    if dry_run {
+        info!("DRY RUN - no files were modified");
+    }
+
+    for (path, source) in &result.repaired {
+        match source {
+            RepairSource::Fec => {
+                let verb = if dry_run { "Would repair" } else { "Repaired" };
+                println!("{verb} {}", display(path));
+            }
+            RepairSource::Mirror(source) => {
+                let verb = if dry_run { "Would restore" } else { "Restored" };
+                println!(
+                    "{verb} {} from {}",
+                    display(path),
+                    escape_control(&source.to_string_lossy())
+                );
+            }
+        }
+    }
+    for (path, reason) in &result.unrepairable {
+        error!("Cannot repair {}: {reason}", display(path));
+    }
+    for path in &result.no_fec {
+        error!(
+            "Cannot repair {}: no FEC data for its recorded content",
+            display(path)
+        );
+    }
+    for path in &result.intentional {
+        error!(
+            "Not repairing {}: its mtime changed, so the change looks intentional (use --force to restore it anyway)",
+            display(path)
+        );
+    }
+    for path in &result.unprotected {
+        warn!(
+            "Not repairable (not a recorded file, or nothing to restore it from): {}",
+            display(path)
+        );
+    }
+    for path in &result.damaged_fec {
+        warn!(
+            "FEC data of {} is damaged; run 'treeward update --always-verify' to regenerate it",
+            display(path)
+        );
+    }
+
+    if !result.unrepairable.is_empty()
+        || !result.no_fec.is_empty()
+        || !result.intentional.is_empty()
+    {
+        return Ok(WardExitCode::status_unclean());
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Warn about each protected file whose FEC data needs attention.
+fn warn_fec_states(fec: &[(String, status::FecState)]) {
+    for (path, state) in fec {
+        let what = match state {
+            status::FecState::Missing => "has no FEC data",
+            status::FecState::Damaged => "has damaged FEC data",
+            status::FecState::Repairable => "can be restored with treeward repair",
+            status::FecState::Unrepairable => "cannot be restored from its FEC data",
+        };
+        warn!("{} {what}", escape_control(path));
+    }
+}
+
+/// The root to operate on for the directory `path`: with `discover`, the root
+/// of the warded tree it is in (see `WardRoot::discover`), otherwise `path`.
+/// Returned with the tree locked for `mode` (see `lock_tree`).
+fn find_ward_root(
+    path: &Path,
+    discover: bool,
+    mode: LockMode,
+    wait: bool,
+) -> anyhow::Result<(WardRoot, TreeLock)> {
+    let ward_root = if discover {
+        WardRoot::discover(path)?
+    } else {
+        WardRoot::at(path)
+    };
+    let lock = lock_tree(&ward_root.root, mode, wait)?;
+    Ok((ward_root, lock))
+}
+
+/// Lock the tree at `root` for `mode` (see `lock`), once any update
+/// interrupted there has been settled. Settling writes ward files, so a
+/// reader that finds an intent record first takes the lock exclusively.
+fn lock_tree(root: &Path, mode: LockMode, wait: bool) -> anyhow::Result<TreeLock> {
+    if mode == LockMode::Shared && intent::is_pending(root) {
+        let _lock = TreeLock::acquire(root, LockMode::Exclusive, wait)?;
+        recover_interrupted_update(root)?;
+    }
+    let lock = TreeLock::acquire(root, mode, wait)?;
+    if mode == LockMode::Exclusive {
+        recover_interrupted_update(root)?;
+    }
+    Ok(lock)
+}
+
+/// How a command that writes the tree unless `dry_run` locks it.
+fn writer_mode(dry_run: bool) -> LockMode {
+    if dry_run {
+        LockMode::Shared
+    } else {
+        LockMode::Exclusive
+    }
+}
+
+/// Roll an update (or undo or migration) interrupted in the tree at `root`
+/// forward or back (see `intent::recover`) and say which.
+fn recover_interrupted_update(root: &Path) -> anyhow::Result<()> {
+    let run = |record: &Option<journal::JournalRecord>| match record {
+        Some(record) => format!(
+            "{} (fingerprint {})",
+            record.kind.name(),
+            escape_control(&record.fingerprint)
+        ),
+        None => "migration".to_string(),
+    };
+    match intent::recover(root)? {
+        None => {}
+        Some(Recovery::RolledForward { record, ward_files }) => warn!(
+            "Completed an interrupted {}: all {ward_files} of its ward files had been written",
+            run(&record)
+        ),
+        Some(Recovery::RolledBack { record, ward_files }) => warn!(
+            "Rolled back an interrupted {}: restored {ward_files} ward files it had written",
+            run(&record)
+        ),
+    }
+    Ok(())
+}
+
+/// Name each entry `--keep-going` could not read, with the reason, which the
+/// listing only shows with `--diff`.
+fn warn_entry_errors(statuses: &[status::StatusEntry]) {
+    for entry in statuses {
+        if let status::StatusEntry::Error { path, kind, .. } = entry {
+            warn!("Could not read {}: {kind}", escape_control(path));
+        }
+    }
+}
+
+fn handle_log(
+    path: &Path,
+    discover: bool,
+    wait: bool,
+    filter: Option<PathBuf>,
+) -> anyhow::Result<ExitCode> {
+    let (ward_root, _lock) = find_ward_root(path, discover, LockMode::Shared, wait)?;
+    let root = ward_root.root.canonicalize()?;
+    let filter = match ward_root.pathspecs(filter.as_slice()).first() {
+        Some(pathspec) => pathspec::relative_path(&root, pathspec)?,
+        None => String::new(),
+    };
+    let journal = journal::read_journal(&root)?;
+    let display = |path: &str| escape_control(&ward_root.display_path(path)).into_owned();
+
+    let mut first = true;
+    for record in journal.records.iter().rev() {
+        let entries: Vec<_> = record
+            .entries
+            .iter()
+            .filter(|entry| entry.touches(&filter))
+            .collect();
+        let restored: Vec<_> = record
+            .restored
+            .iter()
+            .filter(|path| journal::is_at_or_below(path, &filter))
+            .collect();
+        if entries.is_empty() && restored.is_empty() && !filter.is_empty() {
+            continue;
+        }
+        if !first {
+            println!();
+        }
+        first = false;
+        println!("{} {}", record.kind.name(), record.seq);
+        println!("Date:        {}", escape_control(&record.time));
+        println!(
+            "User:        {}@{}",
+            escape_control(&record.user),
+            escape_control(&record.host)
+        );
+        println!("Fingerprint: {}", escape_control(&record.fingerprint));
+        if let Some(policy) = record.policy {
+            println!("Policy:      {}", checksum_policy_name(policy));
+        }
+        if !entries.is_empty() || !restored.is_empty() {
+            println!();
+        }
+        for entry in entries {
+            let path = match &entry.from {
+                Some(from) => format!("{} <- {}", display(&entry.path), display(from)),
+                None => display(&entry.path),
+            };
+            println!("    {:<3}{path}", escape_control(&entry.code));
+        }
+        for path in restored {
+            println!("    {}", display(path));
+        }
+    }
+
+    for (line, problem) in &journal.breaks {
+        error!("Journal line {line} {problem}");
+    }
+    if let Some(problem) = &journal.lost_head {
+        error!("Journal {problem}");
+    }
+    if !journal.breaks.is_empty() || journal.lost_head.is_some() {
+        return Ok(WardExitCode::status_unclean());
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// The flag that selects `policy`, as shown by `log`.
+fn checksum_policy_name(policy: ChecksumPolicy) -> &'static str {
+    match policy {
+        ChecksumPolicy::Never => "metadata only",
+        ChecksumPolicy::WhenPossiblyModified => "--verify",
+        ChecksumPolicy::Always => "--always-verify",
+    }
+}
+
+fn handle_undo(path: &Path, discover: bool, wait: bool, dry_run: bool) -> anyhow::Result<ExitCode> {
+    let (ward_root, _lock) = find_ward_root(path, discover, writer_mode(dry_run), wait)?;
+    let result = undo::undo_last_update(&ward_root.root, dry_run)?;
+
+    if dry_run {
+        info!("DRY RUN - no files were modified");
+    }
+    let (restore, remove) = if dry_run {
+        ("Would restore", "Would remove")
+    } else {
+        ("Restored", "Removed")
+    };
+    let display =
+        |path: &Path| escape_control(&ward_root.display_path(&path.to_string_lossy())).into_owned();
+    for path in &result.restored {
+        println!("{restore} {}", display(path));
+    }
+    for path in &result.removed {
+        println!("{remove} {}", display(path));
+    }
+    info!(
+        "Undid the update of {} (fingerprint {})",
+        escape_control(&result.time),
+        escape_control(&result.fingerprint)
+    );
+    Ok(ExitCode::SUCCESS)
+}
+
+fn handle_baseline(
+    path: &Path,
+    discover: bool,
+    wait: bool,
+    command: BaselineCommand,
+) -> anyhow::Result<ExitCode> {
+    let mode = match command {
+        BaselineCommand::List => LockMode::Shared,
+        BaselineCommand::Create { .. } | BaselineCommand::Delete { .. } => LockMode::Exclusive,
+    };
+    let (ward_root, _lock) = find_ward_root(path, discover, mode, wait)?;
+    match command {
+        BaselineCommand::Create { name } => {
+            let info = baseline::create_baseline(&ward_root.root, &name)?;
+            println!(
+                "Created baseline {} ({} ward files, {})",
+                info.name, info.ward_files, info.digest
+            );
+        }
+        BaselineCommand::List => {
+            for info in baseline::list_baselines(&ward_root.root)? {
+                println!(
+                    "{}  {}  {}",
+                    info.name,
+                    escape_control(&info.created),
+                    escape_control(&info.digest)
+                );
+            }
+        }
+        BaselineCommand::Delete { name } => {
+            baseline::delete_baseline(&ward_root.root, &name)?;
+            println!("Deleted baseline {name}");
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn handle_digest(
+    path: &Path,
+    discover: bool,
+    wait: bool,
+    hash: HashAlgorithm,
+) -> anyhow::Result<ExitCode> {
+    let (ward_root, _lock) = find_ward_root(path, discover, LockMode::Shared, wait)?;
+    let digest = digest::tree_digest(&ward_root.root, hash)?;
+    println!("{digest}");
+    Ok(ExitCode::SUCCESS)
+}
+
+fn handle_migrate(
+    path: &Path,
+    discover: bool,
+    wait: bool,
+    dry_run: bool,
+) -> anyhow::Result<ExitCode> {
+    let (ward_root, _lock) = find_ward_root(path, discover, writer_mode(dry_run), wait)?;
+    let result = migrate_tree(&ward_root.root, MigrateOptions { dry_run })?;
+
+    if dry_run {
+        info!("DRY RUN - no files were modified");
+    }
+
+    info!(
+        "Migrated {} of {} ward files",
+        result.ward_files_migrated.len(),
+        result.ward_files_checked
+    );
+    for ward_path in result.ward_files_migrated {
+        info!("  {}", ward_path.display());
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn handle_watch(
+    path: &Path,
+    discover: bool,
+    wait: bool,
+    options: WatchOptions,
+    format: WatchFormat,
+) -> anyhow::Result<ExitCode> {
+    let (ward_root, lock) = find_ward_root(path, discover, LockMode::Shared, wait)?;
+    watch::watch(&ward_root.root, lock, &options, &mut |event| match event {
+        WatchEvent::Entry(entry) => {
+            let mut entry = entry.clone();
+            entry.map_paths(|path| ward_root.display_path(path));
+            warn_entry_errors(std::slice::from_ref(&entry));
+            match format {
+                WatchFormat::Text => diffing::print_statuses(std::slice::from_ref(&entry), false),
+                WatchFormat::Ndjson => diffing::json::print_ndjson_entry(&entry),
+            }
+        }
+        WatchEvent::DigestMismatch(ward_path) => warn!(
+            "Ward file {} does not match the digest recorded in its parent ward (changed outside treeward)",
+            escape_control(&ward_root.display_path(ward_path))
+        ),
+    })?;
+    Ok(ExitCode::SUCCESS)
+}
+
 fn log_level_from_verbose(verbose: u8) -> &'static str {
     match verbose {
         0 => "warn",
@@ -249,6 +1021,14 @@ fn log_level_from_verbose(verbose: u8) -> &'static str {
     }
 }
 
+fn hash_algorithm(arg: HashAlgorithmArg) -> HashAlgorithm {
+    match arg {
+        HashAlgorithmArg::Sha256 => HashAlgorithm::Sha256,
+        HashAlgorithmArg::Sha512 => HashAlgorithm::Sha512,
+        HashAlgorithmArg::Blake3 => HashAlgorithm::Blake3,
+    }
+}
+
 fn explicit_log_level(log_level: LogLevel) -> &'static str {
     match log_level {
         LogLevel::Error => "error",
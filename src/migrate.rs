@@ -0,0 +1,388 @@
+//! Whole-tree ward file format migration.
+//!
+//! Loads every `.treeward` file reachable from the root ward (any historical
+//! format version is readable) and rewrites those not already in the current
+//! version. Entries are carried over unchanged apart from directory digests,
+//! which are recorded where missing and refreshed where a child ward file is
+//! rewritten; no tree files are checksummed.
+
+use crate::checksum::{Checksum, HashAlgorithm};
+use crate::dir_list::{
+    DirListError, TREEWARD_FILENAME, open_directory, open_root, reopen_directory,
+};
+use crate::intent::{IntendedWardFile, Intent, IntentError};
+use crate::signing::{SignatureError, read_signature, remove_signature};
+use crate::util::dir_handle::{DirHandle, DirId, EntryType};
+use crate::util::names;
+use crate::ward_file::{WardEntry, WardFile, WardFileError, content_digest};
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, thiserror::Error)]
+pub enum MigrateError {
+    #[error("Ward file error: {0}")]
+    WardFile(#[from] WardFileError),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("{0}")]
+    DirList(#[from] DirListError),
+    #[error("Not initialized (use treeward init to initialize)")]
+    NotInitialized,
+    #[error("Ward file {0} does not match the digest recorded in its parent ward")]
+    DigestMismatch(PathBuf),
+    #[error("Signature error: {0}")]
+    Signature(#[from] SignatureError),
+    #[error("Intent record error: {0}")]
+    Intent(#[from] IntentError),
+}
+
+pub struct MigrateOptions {
+    pub dry_run: bool,
+}
+
+#[derive(Debug)]
+pub struct MigrateResult {
+    /// Number of ward files loaded, whether or not they needed migration.
+    pub ward_files_checked: usize,
+    /// Relative paths of `.treeward` files that were rewritten (or would be
+    /// rewritten in dry-run mode).
+    pub ward_files_migrated: Vec<PathBuf>,
+}
+
+/// Rewrite every ward file in the tree rooted at `root` in the current format
+/// version.
+///
+/// Traversal follows the wards themselves: starting at the root `.treeward`,
+/// it descends into each entry recorded as a directory that is still a real
+/// directory (not a symlink) on disk and has a `.treeward`. Directories the
+/// ward does not know about are left alone, as are missing subdirectory ward
+/// files; both are status concerns, not format concerns. Like an update, the
+/// walk and the rewrite go through directory handles (see
+/// `util::dir_handle`), and each directory is reopened for writing only if
+/// it is still the one the walk read.
+///
+/// All ward files are loaded before any is written, so a corrupt or
+/// unsupported ward file anywhere in the tree, or one that does not match the
+/// digest recorded for it, aborts the migration without leaving it
+/// half-converted. Ward files are then settled deepest first: each directory
+/// entry gets the digest of its child's ward file as it is after migration
+/// (recorded digests keep their algorithm, new ones use the default), and a
+/// ward file is rewritten when it is not in the current version or its
+/// digests changed. The rewrites are recorded in an intent record first (see
+/// `intent`), so that a migration stopped halfway is settled like an update.
+/// Each rewrite replaces the ward file atomically and removes its now stale
+/// signature.
+pub fn migrate_tree(root: &Path, options: MigrateOptions) -> Result<MigrateResult, MigrateError> {
+    let root = root.canonicalize()?;
+    let root_dir = open_root(&root)?;
+
+    // Keyed by directory. A child is visited only through its parent's
+    // entry, and its recorded digest is checked on the way in.
+    let mut ward_files: BTreeMap<PathBuf, (DirId, WardFile, String)> = BTreeMap::new();
+    let mut pending: Vec<(PathBuf, DirHandle, Option<Checksum>)> =
+        vec![(root.clone(), root_dir.open_path(Path::new(""))?, None)];
+    while let Some((dir_path, dir, recorded_digest)) = pending.pop() {
+        let ward_path = dir_path.join(TREEWARD_FILENAME);
+        let loaded = WardFile::load_with_content_if_exists_in(&dir, &dir_path)?;
+        if let Some(recorded) = recorded_digest {
+            let matches = loaded.as_ref().is_some_and(|(_, content)| {
+                content_digest(content, recorded.algorithm) == recorded
+            });
+            if !matches {
+                return Err(MigrateError::DigestMismatch(relative(&root, &ward_path)));
+            }
+        }
+        let Some((ward_file, content)) = loaded else {
+            if dir_path == root {
+                return Err(MigrateError::NotInitialized);
+            }
+            continue;
+        };
+
+        for (name, entry) in &ward_file.entries {
+            if let WardEntry::Dir { digest, .. } = entry {
+                let child = dir_path.join(names::to_os(name));
+                let is_dir = match dir.stat(&names::to_os(name)) {
+                    Ok(stat) => stat.entry_type == EntryType::Dir,
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => false,
+                    Err(e) => return Err(e.into()),
+                };
+                if is_dir {
+                    let child_dir = open_directory(&dir, name, &child)?;
+                    pending.push((child, child_dir, digest.clone()));
+                }
+            }
+        }
+
+        ward_files.insert(dir_path, (dir.id()?, ward_file, content));
+    }
+    let ward_files_checked = ward_files.len();
+
+    let mut dirs: Vec<PathBuf> = ward_files.keys().cloned().collect();
+    dirs.sort_by_key(|dir| std::cmp::Reverse(dir.components().count()));
+    let mut contents: HashMap<PathBuf, String> = HashMap::new();
+    let mut settled = Vec::new();
+    let mut intended = Vec::new();
+    for dir_path in dirs {
+        let (walked, ward_file, content) = ward_files
+            .remove(&dir_path)
+            .expect("directory list comes from the map");
+        let mut migrated = ward_file.clone();
+        for (name, entry) in &mut migrated.entries {
+            if let WardEntry::Dir { digest, .. } = entry
+                && let Some(child_content) = contents.get(&dir_path.join(names::to_os(name)))
+            {
+                let algorithm = digest
+                    .as_ref()
+                    .map_or(HashAlgorithm::default(), |recorded| recorded.algorithm);
+                *digest = Some(content_digest(child_content, algorithm));
+            }
+        }
+
+        let content = if migrated.version() == WardFile::CURRENT_VERSION
+            && migrated.entries == ward_file.entries
+        {
+            content
+        } else {
+            let next = migrated.to_toml()?;
+            if !options.dry_run {
+                let dir = reopen_directory(&root_dir, &root, &dir_path, Some(walked))?;
+                // The signature covered the old bytes; `update --signing-key`
+                // signs the migrated file.
+                intended.push(IntendedWardFile::new(
+                    relative(&root, &dir_path).as_path(),
+                    Some(content),
+                    read_signature(&dir, &dir_path)?,
+                    Some(next.clone()),
+                    None,
+                )?);
+            }
+            settled.push((dir_path.clone(), walked, migrated));
+            next
+        };
+        contents.insert(dir_path, content);
+    }
+
+    let mut ward_files_migrated: Vec<PathBuf> = settled
+        .iter()
+        .map(|(dir_path, _, _)| relative(&root, &dir_path.join(TREEWARD_FILENAME)))
+        .collect();
+    ward_files_migrated.sort();
+    if !options.dry_run && !settled.is_empty() {
+        let intent = Intent::new(None, intended).begin(&root_dir, &root)?;
+        for (dir_path, walked, migrated) in settled {
+            let dir = reopen_directory(&root_dir, &root, &dir_path, Some(walked))?;
+            migrated.save_in(&dir, &dir_path)?;
+            remove_signature(&dir, &dir_path)?;
+        }
+        intent.finish(&root_dir, &root)?;
+    }
+
+    Ok(MigrateResult {
+        ward_files_checked,
+        ward_files_migrated,
+    })
+}
+
+fn relative(root: &Path, path: &Path) -> PathBuf {
+    path.strip_prefix(root)
+        .expect("ward paths are collected under the root")
+        .to_path_buf()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attributes::Attributes;
+    use crate::dir_list::INTENT_FILENAME;
+    use std::fs;
+    use tempfile::TempDir;
+
+    const V1_FILE_ENTRY: &str = r#"[entries."a.txt"]
+type = "file"
+sha256 = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+mtime_nanos = 1
+size = 5
+"#;
+
+    fn write_v1(dir: &Path, extra: &str) {
+        fs::write(
+            dir.join(TREEWARD_FILENAME),
+            format!("[metadata]\nversion = 1\n\n{V1_FILE_ENTRY}\n{extra}"),
+        )
+        .unwrap();
+    }
+
+    const SUB_DIR_ENTRY: &str = "[entries.sub]\ntype = \"dir\"\n";
+
+    #[test]
+    fn test_migrates_nested_ward_files() {
+        let temp = TempDir::new().unwrap();
+        fs::create_dir(temp.path().join("sub")).unwrap();
+        write_v1(temp.path(), SUB_DIR_ENTRY);
+        write_v1(&temp.path().join("sub"), "");
+
+        let before = WardFile::load(&temp.path().join("sub/.treeward")).unwrap();
+
+        let result = migrate_tree(temp.path(), MigrateOptions { dry_run: false }).unwrap();
+
+        assert_eq!(result.ward_files_checked, 2);
+        assert_eq!(
+            result.ward_files_migrated,
+            vec![PathBuf::from(".treeward"), PathBuf::from("sub/.treeward")]
+        );
+        for ward_path in [".treeward", "sub/.treeward"] {
+            let ward_file = WardFile::load(&temp.path().join(ward_path)).unwrap();
+            assert_eq!(ward_file.version(), WardFile::CURRENT_VERSION);
+        }
+        let after = WardFile::load(&temp.path().join("sub/.treeward")).unwrap();
+        assert_eq!(after.entries, before.entries);
+        assert!(!crate::intent::is_pending(temp.path()));
+    }
+
+    #[test]
+    fn test_refuses_to_start_over_an_intent() {
+        let temp = TempDir::new().unwrap();
+        write_v1(temp.path(), "");
+        let ward_path = temp.path().join(TREEWARD_FILENAME);
+        let before = fs::read_to_string(&ward_path).unwrap();
+        fs::write(temp.path().join(INTENT_FILENAME), "{}").unwrap();
+
+        let result = migrate_tree(temp.path(), MigrateOptions { dry_run: false });
+
+        assert!(matches!(
+            result,
+            Err(MigrateError::Intent(IntentError::Pending))
+        ));
+        assert_eq!(fs::read_to_string(&ward_path).unwrap(), before);
+    }
+
+    #[test]
+    fn test_dry_run_writes_nothing() {
+        let temp = TempDir::new().unwrap();
+        write_v1(temp.path(), "");
+        let ward_path = temp.path().join(TREEWARD_FILENAME);
+        let before = fs::read_to_string(&ward_path).unwrap();
+
+        let result = migrate_tree(temp.path(), MigrateOptions { dry_run: true }).unwrap();
+
+        assert_eq!(result.ward_files_migrated, vec![PathBuf::from(".treeward")]);
+        assert_eq!(fs::read_to_string(&ward_path).unwrap(), before);
+    }
+
+    #[test]
+    fn test_current_version_is_left_alone() {
+        let temp = TempDir::new().unwrap();
+        WardFile::new(Default::default())
+            .save(&temp.path().join(TREEWARD_FILENAME))
+            .unwrap();
+
+        let result = migrate_tree(temp.path(), MigrateOptions { dry_run: false }).unwrap();
+
+        assert_eq!(result.ward_files_checked, 1);
+        assert!(result.ward_files_migrated.is_empty());
+    }
+
+    #[test]
+    fn test_not_initialized() {
+        let temp = TempDir::new().unwrap();
+
+        let result = migrate_tree(temp.path(), MigrateOptions { dry_run: false });
+
+        assert!(matches!(result, Err(MigrateError::NotInitialized)));
+    }
+
+    #[test]
+    fn test_corrupt_ward_file_aborts_before_writing() {
+        let temp = TempDir::new().unwrap();
+        fs::create_dir(temp.path().join("sub")).unwrap();
+        write_v1(temp.path(), SUB_DIR_ENTRY);
+        fs::write(
+            temp.path().join("sub/.treeward"),
+            "[metadata]\nversion = 99\n",
+        )
+        .unwrap();
+        let root_before = fs::read_to_string(temp.path().join(".treeward")).unwrap();
+
+        let result = migrate_tree(temp.path(), MigrateOptions { dry_run: false });
+
+        assert!(matches!(
+            result,
+            Err(MigrateError::WardFile(WardFileError::UnsupportedVersion(
+                99
+            )))
+        ));
+        assert_eq!(
+            fs::read_to_string(temp.path().join(".treeward")).unwrap(),
+            root_before
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_does_not_follow_directory_replaced_by_symlink() {
+        let temp = TempDir::new().unwrap();
+        let outside = TempDir::new().unwrap();
+        write_v1(outside.path(), "");
+        let outside_before = fs::read_to_string(outside.path().join(".treeward")).unwrap();
+
+        std::os::unix::fs::symlink(outside.path(), temp.path().join("sub")).unwrap();
+        write_v1(temp.path(), SUB_DIR_ENTRY);
+
+        let result = migrate_tree(temp.path(), MigrateOptions { dry_run: false }).unwrap();
+
+        assert_eq!(result.ward_files_checked, 1);
+        assert_eq!(
+            fs::read_to_string(outside.path().join(".treeward")).unwrap(),
+            outside_before
+        );
+    }
+
+    #[test]
+    fn test_records_digests_bottom_up() {
+        let temp = TempDir::new().unwrap();
+        fs::create_dir(temp.path().join("sub")).unwrap();
+        write_v1(temp.path(), SUB_DIR_ENTRY);
+        write_v1(&temp.path().join("sub"), "");
+
+        migrate_tree(temp.path(), MigrateOptions { dry_run: false }).unwrap();
+
+        let sub_content = fs::read_to_string(temp.path().join("sub/.treeward")).unwrap();
+        let root_ward = WardFile::load(&temp.path().join(TREEWARD_FILENAME)).unwrap();
+        assert_eq!(
+            root_ward.entries["sub"],
+            WardEntry::Dir {
+                digest: Some(content_digest(&sub_content, HashAlgorithm::Sha256)),
+                attributes: Attributes::default(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_digest_mismatch_aborts_before_writing() {
+        let temp = TempDir::new().unwrap();
+        fs::create_dir(temp.path().join("sub")).unwrap();
+        write_v1(&temp.path().join("sub"), "");
+        let wrong_digest = format!("sha256:{}", "0".repeat(64));
+        fs::write(
+            temp.path().join(TREEWARD_FILENAME),
+            format!(
+                "[metadata]\nversion = 3\n\n[entries.sub]\ntype = \"dir\"\ndigest = \"{wrong_digest}\"\n"
+            ),
+        )
+        .unwrap();
+        let sub_before = fs::read_to_string(temp.path().join("sub/.treeward")).unwrap();
+
+        let result = migrate_tree(temp.path(), MigrateOptions { dry_run: false });
+
+        assert!(
+            matches!(result, Err(MigrateError::DigestMismatch(ref path)) if path == Path::new("sub/.treeward")),
+            "{result:?}"
+        );
+        assert_eq!(
+            fs::read_to_string(temp.path().join("sub/.treeward")).unwrap(),
+            sub_before
+        );
+    }
+}
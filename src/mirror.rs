@@ -0,0 +1,219 @@
+//! Looking up recorded content in a mirror of the tree.
+//!
+//! A mirror is any directory holding copies of the tree's files, such as an
+//! rsync copy. It need not be warded and is only ever read. A file is looked
+//! for at its own relative path first; failing that, anywhere in the mirror
+//! through an index of the mirror's files by size, so only files of the right
+//! size are ever checksummed. The index is built on first use, and each
+//! candidate is checksummed at most once per algorithm.
+
+use crate::checksum::{Checksum, ChecksumError, HashAlgorithm, checksum_file_in};
+use crate::dir_list::FEC_DIRNAME;
+use crate::util::names;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+#[derive(Debug, thiserror::Error)]
+pub enum MirrorError {
+    #[error("Mirror {} is not a directory", .0.display())]
+    NotADirectory(PathBuf),
+    #[error("IO error on mirror {}: {source}", path.display())]
+    Io { path: PathBuf, source: io::Error },
+}
+
+pub struct Mirror {
+    root: PathBuf,
+    /// Paths relative to `root` of the mirror's regular files, by size.
+    by_size: Option<HashMap<u64, Vec<PathBuf>>>,
+    checksums: HashMap<(PathBuf, HashAlgorithm), Option<Checksum>>,
+}
+
+impl Mirror {
+    pub fn new(root: &Path) -> Result<Self, MirrorError> {
+        let io_error = |source| MirrorError::Io {
+            path: root.to_path_buf(),
+            source,
+        };
+        let root = root.canonicalize().map_err(io_error)?;
+        if !fs::metadata(&root).map_err(io_error)?.is_dir() {
+            return Err(MirrorError::NotADirectory(root));
+        }
+        Ok(Mirror {
+            root,
+            by_size: None,
+            checksums: HashMap::new(),
+        })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// A mirror file with `size` bytes and content `checksum`, relative to
+    /// the mirror's root: the one at `relative` (in string form) if it
+    /// matches, otherwise the first match in the mirror, by path.
+    ///
+    /// Candidates that cannot be read are skipped with a warning.
+    pub fn find(
+        &mut self,
+        relative: &str,
+        size: u64,
+        checksum: &Checksum,
+    ) -> Result<Option<PathBuf>, MirrorError> {
+        let same_path = PathBuf::from(names::to_os(relative).into_owned());
+        if self.matches(&same_path, checksum) {
+            return Ok(Some(same_path));
+        }
+
+        if self.by_size.is_none() {
+            let mut by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+            index_dir(&self.root, Path::new(""), &mut by_size)?;
+            for paths in by_size.values_mut() {
+                paths.sort();
+            }
+            self.by_size = Some(by_size);
+        }
+        let candidates = self
+            .by_size
+            .as_ref()
+            .and_then(|by_size| by_size.get(&size))
+            .cloned()
+            .unwrap_or_default();
+        Ok(candidates
+            .into_iter()
+            .find(|candidate| *candidate != same_path && self.matches(candidate, checksum)))
+    }
+
+    fn matches(&mut self, relative: &Path, checksum: &Checksum) -> bool {
+        let key = (relative.to_path_buf(), checksum.algorithm);
+        if let Some(cached) = self.checksums.get(&key) {
+            return cached.as_ref() == Some(checksum);
+        }
+        let path = self.root.join(relative);
+        let computed = match checksum_file_in(&path, None, &[checksum.algorithm]) {
+            Ok(file_checksum) => file_checksum.checksum(checksum.algorithm).cloned(),
+            Err(ChecksumError::Io(e)) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(ChecksumError::NotRegularFile(_)) => None,
+            Err(e) => {
+                warn!("Skipping mirror file {}: {e}", path.display());
+                None
+            }
+        };
+        let matched = computed.as_ref() == Some(checksum);
+        self.checksums.insert(key, computed);
+        matched
+    }
+}
+
+/// Adds the regular files below `root.join(relative)` to `by_size`, without
+/// following symlinks. Directories that cannot be read are skipped with a
+/// warning.
+fn index_dir(
+    root: &Path,
+    relative: &Path,
+    by_size: &mut HashMap<u64, Vec<PathBuf>>,
+) -> Result<(), MirrorError> {
+    let dir = root.join(relative);
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if relative.as_os_str().is_empty() => {
+            return Err(MirrorError::Io {
+                path: dir,
+                source: e,
+            });
+        }
+        Err(e) => {
+            warn!("Skipping mirror directory {}: {e}", dir.display());
+            return Ok(());
+        }
+    };
+    for entry in entries {
+        let entry = entry.map_err(|source| MirrorError::Io {
+            path: dir.clone(),
+            source,
+        })?;
+        let child = relative.join(entry.file_name());
+        let file_type = entry.file_type().map_err(|source| MirrorError::Io {
+            path: root.join(&child),
+            source,
+        })?;
+        if file_type.is_dir() {
+            if entry.file_name() != FEC_DIRNAME {
+                index_dir(root, &child, by_size)?;
+            }
+        } else if file_type.is_file() {
+            let size = entry
+                .metadata()
+                .map_err(|source| MirrorError::Io {
+                    path: root.join(&child),
+                    source,
+                })?
+                .len();
+            by_size.entry(size).or_default().push(child);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::checksum_bytes;
+    use tempfile::TempDir;
+
+    fn sha256(data: &[u8]) -> Checksum {
+        checksum_bytes(data, HashAlgorithm::Sha256)
+    }
+
+    #[test]
+    fn test_finds_same_path_then_any_path_by_content() {
+        let temp = TempDir::new().unwrap();
+        fs::create_dir_all(temp.path().join("a/b")).unwrap();
+        fs::write(temp.path().join("a/b/file.txt"), "same").unwrap();
+        fs::write(temp.path().join("a/moved.txt"), "moved").unwrap();
+        fs::write(temp.path().join("other.txt"), "other").unwrap();
+        let mut mirror = Mirror::new(temp.path()).unwrap();
+
+        assert_eq!(
+            mirror.find("a/b/file.txt", 4, &sha256(b"same")).unwrap(),
+            Some(PathBuf::from("a/b/file.txt"))
+        );
+        assert_eq!(
+            mirror.find("a/b/file.txt", 5, &sha256(b"moved")).unwrap(),
+            Some(PathBuf::from("a/moved.txt"))
+        );
+        assert_eq!(mirror.find("gone.txt", 5, &sha256(b"lost!")).unwrap(), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_skips_symlinks() {
+        let temp = TempDir::new().unwrap();
+        let outside = TempDir::new().unwrap();
+        fs::write(outside.path().join("target.txt"), "linked").unwrap();
+        std::os::unix::fs::symlink(
+            outside.path().join("target.txt"),
+            temp.path().join("link.txt"),
+        )
+        .unwrap();
+        let mut mirror = Mirror::new(temp.path()).unwrap();
+
+        assert_eq!(
+            mirror.find("link.txt", 6, &sha256(b"linked")).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_not_a_directory() {
+        let temp = TempDir::new().unwrap();
+        fs::write(temp.path().join("file"), "").unwrap();
+        assert!(matches!(
+            Mirror::new(&temp.path().join("file")),
+            Err(MirrorError::NotADirectory(_))
+        ));
+    }
+}
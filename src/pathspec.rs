@@ -0,0 +1,178 @@
+//! Pathspecs that restrict a walk to some subtrees of the root.
+//!
+//! `status`, `update` and `verify` accept paths naming the subtrees to look
+//! at. The walk still enters every directory on the way down to them, since
+//! their ward files chain the subtrees' digests up to the root, but in those
+//! directories it only looks at the entry leading on; everything else there is
+//! out of scope and kept as recorded.
+
+use crate::util::names;
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Debug, thiserror::Error)]
+pub enum PathspecError {
+    #[error("Pathspec {0} is outside the warded tree")]
+    OutsideRoot(PathBuf),
+    #[error("Pathspec {0} cannot be represented on this platform")]
+    Unrepresentable(PathBuf),
+    #[error("Pathspec matches nothing: {0} does not exist")]
+    NoMatch(String),
+    #[error("Pathspec matches nothing: {0} is not a directory")]
+    NotADirectory(String),
+}
+
+/// The part of a directory that a walk looks at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Scope {
+    /// The directory and everything below it.
+    All,
+    /// Only the named entries, each with its own scope. The names are on the
+    /// way to (or are) a pathspec.
+    Only(BTreeMap<String, Scope>),
+}
+
+impl Scope {
+    /// The scope of the canonical `root` for `pathspecs`, which are absolute
+    /// or relative to `root`. No pathspecs means the whole tree; a pathspec
+    /// naming the root itself has the same effect.
+    pub fn new(root: &Path, pathspecs: &[PathBuf]) -> Result<Self, PathspecError> {
+        if pathspecs.is_empty() {
+            return Ok(Scope::All);
+        }
+        let mut scope = Scope::Only(BTreeMap::new());
+        for pathspec in pathspecs {
+            scope.insert(&relative_names(root, pathspec)?);
+        }
+        Ok(scope)
+    }
+
+    /// The scope of the child `name`, or `None` if it is out of scope.
+    pub fn child(&self, name: &str) -> Option<&Scope> {
+        match self {
+            Scope::All => Some(self),
+            Scope::Only(children) => children.get(name),
+        }
+    }
+
+    /// Whether the directory at `relative` (to the root) is entirely in
+    /// scope, as opposed to on the way down to a pathspec or out of scope.
+    pub fn covers(&self, relative: &Path) -> bool {
+        let mut scope = self;
+        for name in relative {
+            let Some(child) = names::from_os(name).and_then(|name| scope.child(&name)) else {
+                return false;
+            };
+            scope = child;
+        }
+        matches!(scope, Scope::All)
+    }
+
+    fn insert(&mut self, names: &[String]) {
+        match names.split_first() {
+            None => *self = Scope::All,
+            Some((first, rest)) => {
+                // Under `All`, the pathspec is already covered.
+                if let Scope::Only(children) = self {
+                    children
+                        .entry(first.clone())
+                        .or_insert_with(|| Scope::Only(BTreeMap::new()))
+                        .insert(rest);
+                }
+            }
+        }
+    }
+}
+
+/// String form of `pathspec` relative to the canonical `root` (see
+/// `util::names`); empty for the root itself.
+pub fn relative_path(root: &Path, pathspec: &Path) -> Result<String, PathspecError> {
+    Ok(relative_names(root, pathspec)?.join("/"))
+}
+
+/// The entry names leading from `root` to `pathspec`, resolving `.` and `..`
+/// lexically.
+fn relative_names(root: &Path, pathspec: &Path) -> Result<Vec<String>, PathspecError> {
+    let mut path = PathBuf::new();
+    for component in root.join(pathspec).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                path.pop();
+            }
+            other => path.push(other),
+        }
+    }
+    // An absolute pathspec may reach the root through a symlink.
+    let relative = match path.strip_prefix(root) {
+        Ok(relative) => relative.to_path_buf(),
+        Err(_) => path
+            .canonicalize()
+            .ok()
+            .and_then(|canonical| Some(canonical.strip_prefix(root).ok()?.to_path_buf()))
+            .ok_or_else(|| PathspecError::OutsideRoot(pathspec.to_path_buf()))?,
+    };
+    relative
+        .iter()
+        .map(|name| {
+            names::from_os(name)
+                .map(|name| name.into_owned())
+                .ok_or_else(|| PathspecError::Unrepresentable(pathspec.to_path_buf()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn only<const N: usize>(children: [(&str, Scope); N]) -> Scope {
+        Scope::Only(
+            children
+                .into_iter()
+                .map(|(name, scope)| (name.to_string(), scope))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_no_pathspecs_is_the_whole_tree() {
+        assert_eq!(Scope::new(Path::new("/r"), &[]).unwrap(), Scope::All);
+    }
+
+    #[test]
+    fn test_pathspecs_merge_into_one_tree() {
+        let scope = Scope::new(
+            Path::new("/r"),
+            &[
+                PathBuf::from("a/b"),
+                PathBuf::from("./a/c/../d"),
+                PathBuf::from("/r/e"),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(
+            scope,
+            only([
+                ("a", only([("b", Scope::All), ("d", Scope::All)])),
+                ("e", Scope::All),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_enclosing_pathspec_covers_nested_one() {
+        let scope = Scope::new(Path::new("/r"), &[PathBuf::from("a"), PathBuf::from("a/b")]);
+        assert_eq!(scope.unwrap(), only([("a", Scope::All)]));
+
+        let scope = Scope::new(Path::new("/r"), &[PathBuf::from("a"), PathBuf::from(".")]);
+        assert_eq!(scope.unwrap(), Scope::All);
+    }
+
+    #[test]
+    fn test_pathspec_outside_root_is_an_error() {
+        let result = Scope::new(Path::new("/r"), &[PathBuf::from("../elsewhere")]);
+        assert!(matches!(result, Err(PathspecError::OutsideRoot(_))));
+    }
+}
@@ -0,0 +1,870 @@
+//! FEC protection of a directory's files.
+//!
+//! A protected directory records its setting in its ward file's metadata
+//! (`[metadata.protection]`, see `Protection`) and keeps FEC data (see `fec`)
+//! for each of its recorded files in `.treeward-fec/`. FEC files are named
+//! after the file's recorded checksum, `<hex>.fec` for SHA-256 and
+//! `<algorithm>-<hex>.fec` otherwise, so identical files share one, a rename
+//! needs no new FEC data, and a file whose content changes gets a new FEC file
+//! while the old one becomes an orphan to remove.
+//!
+//! The store is only ever changed to match a ward file that was just written:
+//! FEC data is generated from the file's current content, which must still
+//! hash to the recorded checksum, so it can only ever restore recorded
+//! content. Like ward files, FEC files and repaired files are written through
+//! a handle on their directory (see `util::dir_handle`) via a temp file and a
+//! rename.
+
+use crate::checksum::{
+    Checksum, ChecksumError, HashAlgorithm, StreamingChecksum, ensure_name_still_names_open_file,
+    open_regular_file_no_follow,
+};
+use crate::dir_list::FEC_DIRNAME;
+use crate::fec::{self, FecConfig, FecError};
+use crate::undo::{UndoError, UndoRecorder};
+use crate::util::dir_handle::DirHandle;
+use crate::util::names;
+use crate::ward_file::{WardFileError, create_temp_file, sync_dir};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Loss tolerance of `protect` without `--loss-tolerance`.
+pub const DEFAULT_LOSS_TOLERANCE: u8 = 10;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProtectionError {
+    #[error("IO error on {}: {source}", path.display())]
+    Io { path: PathBuf, source: io::Error },
+    #[error("Permission denied: {0}")]
+    PermissionDenied(PathBuf),
+    #[error("Checksum error: {0}")]
+    Checksum(#[from] ChecksumError),
+    #[error("Ward file error: {0}")]
+    WardFile(#[from] WardFileError),
+    #[error("FEC file {}: {source}", path.display())]
+    Fec { path: PathBuf, source: FecError },
+    #[error("File does not match its recorded checksum: {0}")]
+    ContentMismatch(PathBuf),
+    #[error("File modified during FEC generation (concurrent modification): {0}")]
+    ConcurrentModification(PathBuf),
+    #[error("Undo error: {0}")]
+    Undo(#[from] UndoError),
+}
+
+fn io_error(path: &Path, source: io::Error) -> ProtectionError {
+    if source.kind() == io::ErrorKind::PermissionDenied {
+        ProtectionError::PermissionDenied(path.to_path_buf())
+    } else {
+        ProtectionError::Io {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+}
+
+/// Whether a directory's files are protected, and how strongly.
+///
+/// Stored in a ward file's metadata as
+/// `[metadata.protection]` with `enabled = true` and `loss_tolerance = N`;
+/// an unprotected directory leaves the section out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(try_from = "ProtectionToml", into = "ProtectionToml")]
+pub enum Protection {
+    #[default]
+    Disabled,
+    /// FEC data can restore a file of which up to `loss_tolerance` percent
+    /// (1-100) is lost or corrupt.
+    Enabled { loss_tolerance: u8 },
+}
+
+impl Protection {
+    pub fn is_disabled(&self) -> bool {
+        *self == Protection::Disabled
+    }
+
+    pub fn loss_tolerance(&self) -> Option<u8> {
+        match self {
+            Protection::Disabled => None,
+            Protection::Enabled { loss_tolerance } => Some(*loss_tolerance),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProtectionToml {
+    enabled: bool,
+    loss_tolerance: u8,
+}
+
+impl TryFrom<ProtectionToml> for Protection {
+    type Error = String;
+
+    fn try_from(toml: ProtectionToml) -> Result<Self, Self::Error> {
+        if !(1..=100).contains(&toml.loss_tolerance) {
+            return Err(format!(
+                "invalid loss_tolerance {}: must be 1 to 100",
+                toml.loss_tolerance
+            ));
+        }
+        Ok(if toml.enabled {
+            Protection::Enabled {
+                loss_tolerance: toml.loss_tolerance,
+            }
+        } else {
+            Protection::Disabled
+        })
+    }
+}
+
+impl From<Protection> for ProtectionToml {
+    fn from(protection: Protection) -> Self {
+        ProtectionToml {
+            enabled: !protection.is_disabled(),
+            loss_tolerance: protection
+                .loss_tolerance()
+                .unwrap_or(DEFAULT_LOSS_TOLERANCE),
+        }
+    }
+}
+
+/// Name of the FEC file for content with the recorded `checksum`.
+pub(crate) fn fec_file_name(checksum: &Checksum) -> String {
+    match checksum.algorithm {
+        HashAlgorithm::Sha256 => format!("{}.fec", checksum.hex),
+        algorithm => format!("{}-{}.fec", algorithm.name(), checksum.hex),
+    }
+}
+
+/// Opens the FEC store of the open directory `dir`, if it has one.
+fn open_store(dir: &DirHandle, dir_path: &Path) -> Result<Option<DirHandle>, ProtectionError> {
+    match dir.open_dir(FEC_DIRNAME.as_ref()) {
+        Ok(store) => Ok(Some(store)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(io_error(&dir_path.join(FEC_DIRNAME), e)),
+    }
+}
+
+/// Names of the FEC files in the store of the open directory `dir`, which is
+/// at `dir_path`; empty if it has no store.
+pub(crate) fn stored_names(
+    dir: &DirHandle,
+    dir_path: &Path,
+) -> Result<BTreeSet<String>, ProtectionError> {
+    let Some(store) = open_store(dir, dir_path)? else {
+        return Ok(BTreeSet::new());
+    };
+    let names = store
+        .entry_names()
+        .map_err(|e| io_error(&dir_path.join(FEC_DIRNAME), e))?;
+    Ok(names
+        .into_iter()
+        .filter_map(|name| name.into_string().ok())
+        .filter(|name| name.ends_with(".fec") && !name.starts_with('.'))
+        .collect())
+}
+
+/// Checks the integrity of the FEC file `fec_name` in the store of `dir`.
+/// Returns what is wrong with it, if anything.
+pub(crate) fn check_fec_file(
+    dir: &DirHandle,
+    dir_path: &Path,
+    fec_name: &str,
+) -> Result<Option<FecError>, ProtectionError> {
+    let path = dir_path.join(FEC_DIRNAME).join(fec_name);
+    let Some(store) = open_store(dir, dir_path)? else {
+        return Err(io_error(&path, io::ErrorKind::NotFound.into()));
+    };
+    let file = store
+        .open_file(fec_name.as_ref())
+        .map_err(|e| io_error(&path, e))?;
+    match fec::verify(&mut BufReader::new(file)) {
+        Ok(_) => Ok(None),
+        Err(FecError::Io(e)) => Err(io_error(&path, e)),
+        Err(e) => Ok(Some(e)),
+    }
+}
+
+/// A recorded file of a protected directory.
+pub(crate) struct ProtectedFile<'a> {
+    pub name: &'a str,
+    pub checksum: &'a Checksum,
+}
+
+/// FEC files a store sync wrote and removed, by name.
+#[derive(Debug, Default)]
+pub(crate) struct StoreChanges {
+    pub written: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// Brings the FEC store of the open directory `dir`, which is at `dir_path`,
+/// in line with its recorded `files`.
+///
+/// Generates every FEC file that is missing, was made with another loss
+/// tolerance, or is named in `regenerate`, from the file's current content,
+/// which must match the recorded checksum. Files named in `unreadable` are
+/// not read; their FEC files are kept if there are any. Every other FEC file
+/// is an orphan and removed (kept by `undo` if given), and so is the store
+/// once it is empty. With `dry_run`, reports what it would do without reading
+/// or writing anything but the store's FEC file headers.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn sync_store(
+    dir: &DirHandle,
+    dir_path: &Path,
+    files: &[ProtectedFile],
+    loss_tolerance: u8,
+    unreadable: &BTreeSet<&str>,
+    regenerate: &BTreeSet<String>,
+    mut undo: Option<&mut UndoRecorder>,
+    dry_run: bool,
+) -> Result<StoreChanges, ProtectionError> {
+    let store_path = dir_path.join(FEC_DIRNAME);
+    let stored = stored_names(dir, dir_path)?;
+    let mut store = open_store(dir, dir_path)?;
+    let mut changes = StoreChanges::default();
+    let mut expected = BTreeSet::new();
+
+    for file in files {
+        let fec_name = fec_file_name(file.checksum);
+        if !expected.insert(fec_name.clone()) || unreadable.contains(file.name) {
+            continue;
+        }
+        if stored.contains(&fec_name) && !regenerate.contains(&fec_name) {
+            let store = store.as_ref().expect("store has FEC files");
+            if stored_loss_tolerance(store, &store_path, &fec_name)? == Some(loss_tolerance) {
+                continue;
+            }
+        }
+        if !dry_run {
+            let store = match &mut store {
+                Some(store) => store,
+                None => {
+                    dir.create_dir(FEC_DIRNAME.as_ref())
+                        .map_err(|e| io_error(&store_path, e))?;
+                    store.insert(
+                        open_store(dir, dir_path)?
+                            .ok_or_else(|| io_error(&store_path, io::ErrorKind::NotFound.into()))?,
+                    )
+                }
+            };
+            write_fec_file(dir, dir_path, store, file, &fec_name, loss_tolerance)?;
+        }
+        changes.written.push(fec_name);
+    }
+
+    for fec_name in stored.difference(&expected) {
+        if !dry_run {
+            let store = store.as_ref().expect("store has FEC files");
+            remove_fec_file(store, dir_path, fec_name, undo.as_deref_mut())?;
+        }
+        changes.removed.push(fec_name.clone());
+    }
+
+    if !dry_run && let Some(store) = &store {
+        sync_dir(store)?;
+        if expected.is_empty() {
+            remove_store_dir(dir, dir_path)?;
+        }
+    }
+    Ok(changes)
+}
+
+/// Removes the FEC store of the open directory `dir`, which is at
+/// `dir_path`, if it has one, and returns the names of the FEC files it held.
+/// They are kept by `undo` if given.
+pub(crate) fn remove_store(
+    dir: &DirHandle,
+    dir_path: &Path,
+    mut undo: Option<&mut UndoRecorder>,
+    dry_run: bool,
+) -> Result<Vec<String>, ProtectionError> {
+    let Some(store) = open_store(dir, dir_path)? else {
+        return Ok(Vec::new());
+    };
+    let removed: Vec<String> = stored_names(dir, dir_path)?.into_iter().collect();
+    if !dry_run {
+        for fec_name in &removed {
+            remove_fec_file(&store, dir_path, fec_name, undo.as_deref_mut())?;
+        }
+        remove_store_dir(dir, dir_path)?;
+    }
+    Ok(removed)
+}
+
+/// Removes the FEC file `fec_name` from `store`, the store of the directory
+/// at `dir_path`, or moves it into the generation `undo` collects.
+fn remove_fec_file(
+    store: &DirHandle,
+    dir_path: &Path,
+    fec_name: &str,
+    undo: Option<&mut UndoRecorder>,
+) -> Result<(), ProtectionError> {
+    match undo {
+        Some(undo) => Ok(undo.keep_fec(store, dir_path, fec_name)?),
+        None => store
+            .remove_file(fec_name.as_ref())
+            .map_err(|e| io_error(&dir_path.join(FEC_DIRNAME).join(fec_name), e)),
+    }
+}
+
+/// Removes the store directory unless something other than FEC files (such
+/// as a temp file of a concurrent run) is left in it.
+fn remove_store_dir(dir: &DirHandle, dir_path: &Path) -> Result<(), ProtectionError> {
+    match dir.remove_dir(FEC_DIRNAME.as_ref()) {
+        Ok(()) => sync_dir(dir).map_err(Into::into),
+        Err(e) if e.kind() == io::ErrorKind::DirectoryNotEmpty => Ok(()),
+        Err(e) => Err(io_error(&dir_path.join(FEC_DIRNAME), e)),
+    }
+}
+
+/// Loss tolerance of the stored FEC file `fec_name`, or `None` if its header
+/// cannot be read.
+fn stored_loss_tolerance(
+    store: &DirHandle,
+    store_path: &Path,
+    fec_name: &str,
+) -> Result<Option<u8>, ProtectionError> {
+    let file = store
+        .open_file(fec_name.as_ref())
+        .map_err(|e| io_error(&store_path.join(fec_name), e))?;
+    match fec::read_header(&mut BufReader::new(file)) {
+        Ok(header) => Ok(Some(header.loss_tolerance_percent)),
+        Err(FecError::Io(e)) => Err(io_error(&store_path.join(fec_name), e)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Generates the FEC file `fec_name` in `store` from the file `file.name` in
+/// `dir`, checking that what was read matches the recorded checksum.
+fn write_fec_file(
+    dir: &DirHandle,
+    dir_path: &Path,
+    store: &DirHandle,
+    file: &ProtectedFile,
+    fec_name: &str,
+    loss_tolerance: u8,
+) -> Result<(), ProtectionError> {
+    let name = names::to_os(file.name);
+    let path = dir_path.join(&name);
+    let fec_path = dir_path.join(FEC_DIRNAME).join(fec_name);
+
+    let source = open_regular_file_no_follow(dir, &name, &path)?;
+    let metadata_before = source.metadata().map_err(|e| io_error(&path, e))?;
+    let mut reader = HashingReader {
+        inner: BufReader::new(source),
+        hasher: StreamingChecksum::new(file.checksum.algorithm),
+    };
+
+    let (temp_name, temp_file) =
+        create_temp_file(store, fec_name).map_err(|e| io_error(&fec_path, e))?;
+    let written = (|| {
+        let mut out = BufWriter::new(temp_file);
+        fec::write_fec(
+            &mut reader,
+            metadata_before.len(),
+            &FecConfig::new(loss_tolerance),
+            &mut out,
+        )
+        .map_err(|e| match e {
+            // The file ended early: it was truncated meanwhile.
+            FecError::Io(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                ProtectionError::ConcurrentModification(path.clone())
+            }
+            FecError::Io(e) => io_error(&fec_path, e),
+            source => ProtectionError::Fec {
+                path: fec_path.clone(),
+                source,
+            },
+        })?;
+        let temp_file = out
+            .into_inner()
+            .map_err(|e| io_error(&fec_path, e.into_error()))?;
+
+        let source = reader.inner.into_inner();
+        let metadata_after = source.metadata().map_err(|e| io_error(&path, e))?;
+        if metadata_after.modified().ok() != metadata_before.modified().ok()
+            || metadata_after.len() != metadata_before.len()
+        {
+            return Err(ProtectionError::ConcurrentModification(path.clone()));
+        }
+        ensure_name_still_names_open_file(dir, &name, &path, &metadata_after)?;
+        if reader.hasher.finish() != *file.checksum {
+            return Err(ProtectionError::ContentMismatch(path.clone()));
+        }
+
+        temp_file.sync_all().map_err(|e| io_error(&fec_path, e))?;
+        store
+            .rename(temp_name.as_ref(), fec_name.as_ref())
+            .map_err(|e| io_error(&fec_path, e))
+    })();
+    if written.is_err() {
+        let _ = store.remove_file(temp_name.as_ref());
+    }
+    written
+}
+
+/// Recorded state of a file to repair.
+pub(crate) struct RecordedFile<'a> {
+    pub checksum: &'a Checksum,
+    pub mtime_nanos: u64,
+}
+
+/// What `repair_file` or `restore_file` did (or, with `dry_run`, would do).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum RepairOutcome {
+    /// The file was restored to its recorded content and mtime.
+    Repaired,
+    /// There is no FEC file for the recorded content.
+    NoFec,
+    /// The FEC data cannot restore the recorded content, for the reason
+    /// given.
+    Unrepairable(String),
+    /// Something other than a regular file has the name; repair never
+    /// replaces it.
+    NotAFile,
+}
+
+/// Restores the file `name` in the open directory `dir`, which is at
+/// `dir_path`, to its `recorded` content from the directory's FEC data.
+///
+/// Whatever is left of the file, which may be nothing, is fed to the
+/// decoder, and the result replaces the file as `restore_file` describes.
+/// With `dry_run`, the result is only hashed.
+pub(crate) fn repair_file(
+    dir: &DirHandle,
+    dir_path: &Path,
+    name: &str,
+    recorded: &RecordedFile,
+    dry_run: bool,
+) -> Result<RepairOutcome, ProtectionError> {
+    let fec_name = fec_file_name(recorded.checksum);
+    let fec_path = dir_path.join(FEC_DIRNAME).join(&fec_name);
+
+    let Some(store) = open_store(dir, dir_path)? else {
+        return Ok(RepairOutcome::NoFec);
+    };
+    let fec_file = match store.open_file(fec_name.as_ref()) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(RepairOutcome::NoFec),
+        Err(e) => return Err(io_error(&fec_path, e)),
+    };
+    let mut fec_reader = BufReader::new(fec_file);
+
+    restore_file(dir, dir_path, name, recorded, dry_run, |damaged, out| {
+        let mut damaged_reader: Box<dyn Read> = match damaged {
+            Some(file) => Box::new(BufReader::new(file)),
+            None => Box::new(io::empty()),
+        };
+        match fec::repair(&mut damaged_reader, &mut fec_reader, &mut { out }) {
+            Ok(_) => Ok(None),
+            Err(FecError::Io(e)) => Err(io_error(&fec_path, e)),
+            Err(e) => Ok(Some(e.to_string())),
+        }
+    })
+}
+
+/// Replaces the file `name` in the open directory `dir`, which is at
+/// `dir_path`, with the content `fill` writes, which must be its `recorded`
+/// content.
+///
+/// `fill` is given the file as it is now, if there is one, and returns the
+/// reason it could not produce the content, if so. What it writes must hash
+/// to the recorded checksum; it goes to a temp file, which is given the
+/// replaced file's permission bits and the recorded mtime and renamed over
+/// the file, provided the name still names the file there was (or, if there
+/// was none, still nothing). With `dry_run`, what `fill` writes is only
+/// hashed.
+pub(crate) fn restore_file(
+    dir: &DirHandle,
+    dir_path: &Path,
+    name: &str,
+    recorded: &RecordedFile,
+    dry_run: bool,
+    fill: impl FnOnce(Option<&File>, &mut dyn Write) -> Result<Option<String>, ProtectionError>,
+) -> Result<RepairOutcome, ProtectionError> {
+    let os_name = names::to_os(name);
+    let path = dir_path.join(&os_name);
+
+    let damaged = match dir.stat(&os_name) {
+        Ok(stat) if !stat.is_file() => return Ok(RepairOutcome::NotAFile),
+        Ok(_) => Some(open_regular_file_no_follow(dir, &os_name, &path)?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(io_error(&path, e)),
+    };
+    let damaged_metadata = damaged
+        .as_ref()
+        .map(File::metadata)
+        .transpose()
+        .map_err(|e| io_error(&path, e))?;
+    let mismatch = || {
+        RepairOutcome::Unrepairable(
+            "restored content does not match the recorded checksum".to_string(),
+        )
+    };
+
+    if dry_run {
+        let mut hasher = StreamingChecksum::new(recorded.checksum.algorithm);
+        if let Some(reason) = fill(damaged.as_ref(), &mut hasher)? {
+            return Ok(RepairOutcome::Unrepairable(reason));
+        }
+        return Ok(if hasher.finish() == *recorded.checksum {
+            RepairOutcome::Repaired
+        } else {
+            mismatch()
+        });
+    }
+
+    let (temp_name, temp_file) = create_temp_file(dir, name).map_err(|e| io_error(&path, e))?;
+    let mut out = HashingWriter {
+        inner: BufWriter::new(temp_file),
+        hasher: StreamingChecksum::new(recorded.checksum.algorithm),
+    };
+    let outcome = (|| {
+        if let Some(reason) = fill(damaged.as_ref(), &mut out)? {
+            return Ok(RepairOutcome::Unrepairable(reason));
+        }
+        if out.hasher.finish() != *recorded.checksum {
+            return Ok(mismatch());
+        }
+        let temp_file = out
+            .inner
+            .into_inner()
+            .map_err(|e| io_error(&path, e.into_error()))?;
+        if let Some(metadata) = &damaged_metadata {
+            temp_file
+                .set_permissions(metadata.permissions())
+                .map_err(|e| io_error(&path, e))?;
+        }
+        temp_file
+            .set_modified(SystemTime::UNIX_EPOCH + Duration::from_nanos(recorded.mtime_nanos))
+            .map_err(|e| io_error(&path, e))?;
+        temp_file.sync_all().map_err(|e| io_error(&path, e))?;
+
+        // Only replace the file that was read (or, if there was none, still
+        // nothing).
+        match &damaged_metadata {
+            Some(metadata) => ensure_name_still_names_open_file(dir, &os_name, &path, metadata)?,
+            None => match dir.stat(&os_name) {
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                _ => return Err(ProtectionError::ConcurrentModification(path.clone())),
+            },
+        }
+        dir.rename(temp_name.as_ref(), &os_name)
+            .map_err(|e| io_error(&path, e))?;
+        sync_dir(dir)?;
+        Ok(RepairOutcome::Repaired)
+    })();
+    if !matches!(outcome, Ok(RepairOutcome::Repaired)) {
+        let _ = dir.remove_file(temp_name.as_ref());
+    }
+    outcome
+}
+
+/// Passes reads through, hashing everything read.
+struct HashingReader<R> {
+    inner: R,
+    hasher: StreamingChecksum,
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.write_all(&buf[..n])?;
+        Ok(n)
+    }
+}
+
+/// Passes writes through, hashing everything written.
+struct HashingWriter<W> {
+    inner: W,
+    hasher: StreamingChecksum,
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.write_all(&buf[..n])?;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::checksum_bytes;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn protect(temp: &TempDir, files: &[(&str, &[u8])], loss_tolerance: u8) -> StoreChanges {
+        let checksums: Vec<(String, Checksum)> = files
+            .iter()
+            .map(|(name, content)| {
+                fs::write(temp.path().join(name), content).unwrap();
+                (
+                    name.to_string(),
+                    checksum_bytes(content, HashAlgorithm::Sha256),
+                )
+            })
+            .collect();
+        let protected: Vec<ProtectedFile> = checksums
+            .iter()
+            .map(|(name, checksum)| ProtectedFile { name, checksum })
+            .collect();
+        let dir = DirHandle::open(temp.path()).unwrap();
+        sync_store(
+            &dir,
+            temp.path(),
+            &protected,
+            loss_tolerance,
+            &BTreeSet::new(),
+            &BTreeSet::new(),
+            None,
+            false,
+        )
+        .unwrap()
+    }
+
+    fn recorded_mtime(path: &Path) -> u64 {
+        fs::metadata(path)
+            .unwrap()
+            .modified()
+            .unwrap()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos() as u64
+    }
+
+    #[test]
+    fn test_protection_toml_round_trip_and_validation() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Wrapper {
+            protection: Protection,
+        }
+
+        let enabled = Wrapper {
+            protection: Protection::Enabled { loss_tolerance: 25 },
+        };
+        let toml_string = toml::to_string(&enabled).unwrap();
+        assert!(toml_string.contains("enabled = true"), "{toml_string}");
+        assert!(toml_string.contains("loss_tolerance = 25"), "{toml_string}");
+        assert_eq!(toml::from_str::<Wrapper>(&toml_string).unwrap(), enabled);
+
+        for bad in [
+            "[protection]\nenabled = true\nloss_tolerance = 0\n",
+            "[protection]\nenabled = true\nloss_tolerance = 101\n",
+            "[protection]\nenabled = true\n",
+            "[protection]\nenabled = true\nloss_tolerance = 10\nextra = 1\n",
+        ] {
+            assert!(toml::from_str::<Wrapper>(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_fec_file_names_are_content_addressed() {
+        let sha256 = checksum_bytes(b"data", HashAlgorithm::Sha256);
+        let blake3 = checksum_bytes(b"data", HashAlgorithm::Blake3);
+
+        assert_eq!(fec_file_name(&sha256), format!("{}.fec", sha256.hex));
+        assert_eq!(fec_file_name(&blake3), format!("blake3-{}.fec", blake3.hex));
+    }
+
+    #[test]
+    fn test_sync_writes_shares_and_removes_orphans() {
+        let temp = TempDir::new().unwrap();
+
+        let changes = protect(
+            &temp,
+            &[("a", b"same"), ("b", b"same"), ("c", b"other")],
+            10,
+        );
+        assert_eq!(changes.written.len(), 2);
+        assert!(changes.removed.is_empty());
+
+        let changes = protect(
+            &temp,
+            &[("a", b"same"), ("b", b"same"), ("c", b"other")],
+            10,
+        );
+        assert!(changes.written.is_empty());
+        assert!(changes.removed.is_empty());
+
+        let changes = protect(&temp, &[("a", b"same"), ("c", b"changed")], 10);
+        assert_eq!(
+            changes.written,
+            vec![fec_file_name(&checksum_bytes(
+                b"changed",
+                HashAlgorithm::Sha256
+            ))]
+        );
+        assert_eq!(
+            changes.removed,
+            vec![fec_file_name(&checksum_bytes(
+                b"other",
+                HashAlgorithm::Sha256
+            ))]
+        );
+
+        let changes = protect(&temp, &[("a", b"same"), ("c", b"changed")], 50);
+        assert_eq!(changes.written.len(), 2);
+
+        let changes = protect(&temp, &[], 50);
+        assert_eq!(changes.removed.len(), 2);
+        assert!(!temp.path().join(FEC_DIRNAME).exists());
+    }
+
+    #[test]
+    fn test_sync_rejects_content_not_matching_checksum() {
+        let temp = TempDir::new().unwrap();
+        fs::write(temp.path().join("a"), b"actual").unwrap();
+        let recorded = checksum_bytes(b"recorded", HashAlgorithm::Sha256);
+        let dir = DirHandle::open(temp.path()).unwrap();
+
+        let result = sync_store(
+            &dir,
+            temp.path(),
+            &[ProtectedFile {
+                name: "a",
+                checksum: &recorded,
+            }],
+            10,
+            &BTreeSet::new(),
+            &BTreeSet::new(),
+            None,
+            false,
+        );
+
+        assert!(matches!(result, Err(ProtectionError::ContentMismatch(_))));
+        assert_eq!(stored_names(&dir, temp.path()).unwrap(), BTreeSet::new());
+    }
+
+    #[test]
+    fn test_repair_restores_content_and_mtime() {
+        let temp = TempDir::new().unwrap();
+        let content: Vec<u8> = (0..10_000).map(|i| (i % 251) as u8).collect();
+        protect(&temp, &[("data.bin", &content)], 10);
+        let path = temp.path().join("data.bin");
+        let mtime_nanos = recorded_mtime(&path);
+        let checksum = checksum_bytes(&content, HashAlgorithm::Sha256);
+        let recorded = RecordedFile {
+            checksum: &checksum,
+            mtime_nanos,
+        };
+
+        let mut damaged = content.clone();
+        damaged[5000] ^= 0xff;
+        fs::write(&path, &damaged).unwrap();
+        let dir = DirHandle::open(temp.path()).unwrap();
+
+        assert_eq!(
+            repair_file(&dir, temp.path(), "data.bin", &recorded, true).unwrap(),
+            RepairOutcome::Repaired
+        );
+        assert_eq!(fs::read(&path).unwrap(), damaged);
+
+        assert_eq!(
+            repair_file(&dir, temp.path(), "data.bin", &recorded, false).unwrap(),
+            RepairOutcome::Repaired
+        );
+        assert_eq!(fs::read(&path).unwrap(), content);
+        assert_eq!(recorded_mtime(&path), mtime_nanos);
+    }
+
+    #[test]
+    fn test_repair_outcomes_without_usable_fec_data() {
+        let temp = TempDir::new().unwrap();
+        let content: Vec<u8> = (0..10_000).map(|i| (i % 251) as u8).collect();
+        protect(&temp, &[("data.bin", &content)], 10);
+        let checksum = checksum_bytes(&content, HashAlgorithm::Sha256);
+        let recorded = RecordedFile {
+            checksum: &checksum,
+            mtime_nanos: 0,
+        };
+        let dir = DirHandle::open(temp.path()).unwrap();
+
+        fs::remove_file(temp.path().join("data.bin")).unwrap();
+        assert!(matches!(
+            repair_file(&dir, temp.path(), "data.bin", &recorded, false).unwrap(),
+            RepairOutcome::Unrepairable(_)
+        ));
+        assert!(!temp.path().join("data.bin").exists());
+
+        fs::create_dir(temp.path().join("data.bin")).unwrap();
+        assert_eq!(
+            repair_file(&dir, temp.path(), "data.bin", &recorded, false).unwrap(),
+            RepairOutcome::NotAFile
+        );
+
+        let other = checksum_bytes(b"other", HashAlgorithm::Sha256);
+        let unprotected = RecordedFile {
+            checksum: &other,
+            mtime_nanos: 0,
+        };
+        assert_eq!(
+            repair_file(&dir, temp.path(), "other", &unprotected, false).unwrap(),
+            RepairOutcome::NoFec
+        );
+    }
+
+    #[test]
+    fn test_full_tolerance_recreates_removed_file() {
+        let temp = TempDir::new().unwrap();
+        protect(&temp, &[("gone.txt", b"precious")], 100);
+        let checksum = checksum_bytes(b"precious", HashAlgorithm::Sha256);
+        let dir = DirHandle::open(temp.path()).unwrap();
+        fs::remove_file(temp.path().join("gone.txt")).unwrap();
+
+        let outcome = repair_file(
+            &dir,
+            temp.path(),
+            "gone.txt",
+            &RecordedFile {
+                checksum: &checksum,
+                mtime_nanos: 1_000_000_000,
+            },
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(outcome, RepairOutcome::Repaired);
+        assert_eq!(fs::read(temp.path().join("gone.txt")).unwrap(), b"precious");
+    }
+
+    #[test]
+    fn test_damaged_fec_file_is_detected_and_remove_store_clears_it() {
+        let temp = TempDir::new().unwrap();
+        protect(&temp, &[("a", b"content")], 10);
+        let dir = DirHandle::open(temp.path()).unwrap();
+        let fec_name = fec_file_name(&checksum_bytes(b"content", HashAlgorithm::Sha256));
+        assert!(
+            check_fec_file(&dir, temp.path(), &fec_name)
+                .unwrap()
+                .is_none()
+        );
+
+        let fec_path = temp.path().join(FEC_DIRNAME).join(&fec_name);
+        let mut bytes = fs::read(&fec_path).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0x01;
+        fs::write(&fec_path, bytes).unwrap();
+        assert!(
+            check_fec_file(&dir, temp.path(), &fec_name)
+                .unwrap()
+                .is_some()
+        );
+
+        assert_eq!(
+            remove_store(&dir, temp.path(), None, false).unwrap(),
+            vec![fec_name]
+        );
+        assert!(!temp.path().join(FEC_DIRNAME).exists());
+    }
+}
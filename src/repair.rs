@@ -0,0 +1,511 @@
+//! Restoring recorded content from FEC data or a mirror.
+//!
+//! `repair_tree` checksums every file of the tree (as `verify` does) and
+//! restores each modified or removed file to its recorded content, from the
+//! FEC data of its directory if it is protected and that can rebuild it (see
+//! `protection::repair_file`), otherwise from a mirror if one is given (see
+//! `mirror`). Repair only ever brings back what the ward records, so a file
+//! whose change looks intentional, with an mtime other than the recorded one,
+//! is left alone unless forced: silent corruption keeps the mtime, while an
+//! edit that was not yet recorded with `update` does not.
+
+use crate::checksum::HashAlgorithm;
+use crate::dir_list::{DirListError, open_root, reopen_directory};
+use crate::mirror::{Mirror, MirrorError};
+use crate::protection::{ProtectionError, RecordedFile, RepairOutcome, repair_file, restore_file};
+use crate::status::{
+    ChecksumPolicy, DiffMode, FecState, StatusEntry, StatusError, StatusMode, StatusOptions,
+    StatusPurpose, compute_status,
+};
+use crate::util::names;
+use crate::ward_file::WardEntry;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, thiserror::Error)]
+pub enum RepairError {
+    #[error("Status error: {0}")]
+    Status(#[from] StatusError),
+    #[error("Directory listing error: {0}")]
+    DirList(#[from] DirListError),
+    #[error("Protection error: {0}")]
+    Protection(#[from] ProtectionError),
+    #[error("Mirror error: {0}")]
+    Mirror(#[from] MirrorError),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Not initialized (use treeward init to initialize)")]
+    NotInitialized,
+}
+
+pub struct RepairOptions {
+    pub dry_run: bool,
+    pub jobs: NonZeroUsize,
+    /// Mirror of the tree to restore files from when their FEC data cannot.
+    pub mirror: Option<PathBuf>,
+    /// Also restore files whose change looks intentional.
+    pub force: bool,
+    /// Subtrees to repair, absolute or relative to the root; empty repairs
+    /// the whole tree (see `StatusOptions::pathspecs`).
+    pub pathspecs: Vec<PathBuf>,
+}
+
+/// Where a repaired file's content came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RepairSource {
+    /// Its directory's FEC data.
+    Fec,
+    /// The mirror file at this absolute path.
+    Mirror(PathBuf),
+}
+
+#[derive(Debug, Default)]
+pub struct RepairResult {
+    /// Relative paths of files restored (or that would be), with where their
+    /// content came from.
+    pub repaired: Vec<(String, RepairSource)>,
+    /// Modified or removed files that could not be restored, with the
+    /// reason.
+    pub unrepairable: Vec<(String, String)>,
+    /// Modified or removed files of protected directories without FEC data
+    /// for their recorded content (without a mirror).
+    pub no_fec: Vec<String>,
+    /// Modified files whose mtime is not the recorded one, left alone
+    /// without `force`.
+    pub intentional: Vec<String>,
+    /// Files whose FEC file fails its integrity check; `update` with
+    /// `ChecksumPolicy::Always` regenerates it.
+    pub damaged_fec: Vec<String>,
+    /// Entries that differ from the ward but that repair cannot restore:
+    /// not a recorded file, or (without a mirror) not in a protected
+    /// directory.
+    pub unprotected: Vec<String>,
+}
+
+/// Restore the modified and removed files in the tree rooted at `root` from
+/// their FEC data or `options.mirror`.
+///
+/// Every file is checksummed against the ward (`ChecksumPolicy::Always`), so
+/// silent corruption is found too. A file the status run reports as
+/// repairable from its FEC data is restored from that; any other is looked
+/// up in the mirror. Files are restored through a handle on their directory,
+/// which must still be the directory the run listed, and the restored content
+/// is checked against the recorded checksum again before it replaces the
+/// file (see `protection::restore_file`). With `dry_run`, nothing is written.
+///
+/// Added, renamed and other changed entries are listed in `unprotected`:
+/// there is no recorded content to restore them to.
+pub fn repair_tree(root: &Path, options: RepairOptions) -> Result<RepairResult, RepairError> {
+    let root = root.canonicalize()?;
+    if !root.join(".treeward").exists() {
+        return Err(RepairError::NotInitialized);
+    }
+    let mut mirror = options.mirror.as_deref().map(Mirror::new).transpose()?;
+
+    let status = compute_status(
+        &root,
+        &StatusOptions {
+            policy: ChecksumPolicy::Always,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::Capture,
+            jobs: options.jobs,
+            hash: HashAlgorithm::default(),
+            trusted_key: None,
+            track: None,
+            protection: None,
+            keep_going: false,
+            pathspecs: &options.pathspecs,
+            baseline: None,
+        },
+    )?;
+
+    let fec: HashMap<&str, FecState> = status
+        .fec
+        .iter()
+        .map(|(path, state)| (path.as_str(), *state))
+        .collect();
+    let mut result = RepairResult::default();
+    for (path, state) in &status.fec {
+        if *state == FecState::Damaged {
+            result.damaged_fec.push(path.clone());
+        }
+    }
+
+    let root_dir = open_root(&root)?;
+    for entry in &status.statuses {
+        let path = entry.path();
+        let (recorded, current) = match entry {
+            StatusEntry::Modified {
+                old_ward_entry,
+                ward_entry,
+                ..
+            } => (old_ward_entry.as_ref(), ward_entry.as_ref()),
+            StatusEntry::Removed { old_ward_entry, .. } => (old_ward_entry.as_ref(), None),
+            _ => (None, None),
+        };
+        let state = fec.get(path).copied();
+        let Some(WardEntry::File {
+            checksum,
+            mtime_nanos,
+            size,
+            ..
+        }) = recorded
+        else {
+            result.unprotected.push(path.to_string());
+            continue;
+        };
+        if state.is_none() && mirror.is_none() {
+            result.unprotected.push(path.to_string());
+            continue;
+        }
+        if !options.force
+            && let Some(WardEntry::File {
+                mtime_nanos: current_mtime,
+                ..
+            }) = current
+            && current_mtime != mtime_nanos
+        {
+            result.intentional.push(path.to_string());
+            continue;
+        }
+
+        // Split in string form; only the joined paths are OS paths.
+        let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+        let dir_path = root.join(names::to_os(parent));
+        let walked = status
+            .dir_ids
+            .get(Path::new(&names::to_os(parent)))
+            .copied();
+        let dir = reopen_directory(&root_dir, &root, &dir_path, walked)?;
+        let recorded = RecordedFile {
+            checksum,
+            mtime_nanos: *mtime_nanos,
+        };
+
+        let fec_outcome = match state {
+            Some(FecState::Repairable) => Some(repair_file(
+                &dir,
+                &dir_path,
+                name,
+                &recorded,
+                options.dry_run,
+            )?),
+            _ => None,
+        };
+        if fec_outcome == Some(RepairOutcome::Repaired) {
+            result.repaired.push((path.to_string(), RepairSource::Fec));
+            continue;
+        }
+
+        let Some(mirror) = mirror.as_mut() else {
+            // Unrepairable ones are decoded again (without writing) for the
+            // reason.
+            let outcome = match (state, fec_outcome) {
+                (_, Some(outcome)) => outcome,
+                (Some(FecState::Unrepairable), None) => {
+                    repair_file(&dir, &dir_path, name, &recorded, true)?
+                }
+                _ => RepairOutcome::NoFec,
+            };
+            match outcome {
+                RepairOutcome::Repaired => result.unrepairable.push((
+                    path.to_string(),
+                    "FEC data changed since it was checked".to_string(),
+                )),
+                RepairOutcome::NoFec => result.no_fec.push(path.to_string()),
+                outcome => result
+                    .unrepairable
+                    .push((path.to_string(), reason(outcome))),
+            }
+            continue;
+        };
+
+        let Some(found) = mirror.find(path, *size, checksum)? else {
+            result.unrepairable.push((
+                path.to_string(),
+                format!(
+                    "no file with the recorded content in {}",
+                    mirror.root().display()
+                ),
+            ));
+            continue;
+        };
+        let source = mirror.root().join(found);
+        let outcome = restore_file(
+            &dir,
+            &dir_path,
+            name,
+            &recorded,
+            options.dry_run,
+            |_, out| {
+                let mut file = File::open(&source).map_err(|e| ProtectionError::Io {
+                    path: source.clone(),
+                    source: e,
+                })?;
+                io::copy(&mut file, out).map_err(|e| ProtectionError::Io {
+                    path: source.clone(),
+                    source: e,
+                })?;
+                Ok(None)
+            },
+        )?;
+        match outcome {
+            RepairOutcome::Repaired => result
+                .repaired
+                .push((path.to_string(), RepairSource::Mirror(source))),
+            outcome => result
+                .unrepairable
+                .push((path.to_string(), reason(outcome))),
+        }
+    }
+    Ok(result)
+}
+
+/// Why a file was not restored, for an outcome other than `Repaired`.
+fn reason(outcome: RepairOutcome) -> String {
+    match outcome {
+        RepairOutcome::Unrepairable(reason) => reason,
+        RepairOutcome::NotAFile => "no longer a file".to_string(),
+        RepairOutcome::NoFec => "no FEC data for its recorded content".to_string(),
+        RepairOutcome::Repaired => unreachable!("repaired files have no reason"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protection::Protection;
+    use crate::update::set_protection;
+    use crate::update::{WardOptions, ward_directory};
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn update_options() -> WardOptions {
+        WardOptions {
+            init: false,
+            allow_init: false,
+            fingerprint: None,
+            accept_digest_mismatch: false,
+            dry_run: false,
+            checksum_policy: ChecksumPolicy::Never,
+            jobs: NonZeroUsize::MIN,
+            hash: HashAlgorithm::Sha256,
+            signing_key: None,
+            track: None,
+            protection: None,
+            keep_going: false,
+            pathspecs: Vec::new(),
+        }
+    }
+
+    fn protected_tree(loss_tolerance: u8) -> TempDir {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path();
+        fs::create_dir(root.join("sub")).unwrap();
+        fs::write(root.join("sub/kept.txt"), "kept content").unwrap();
+        fs::write(root.join("plain.txt"), "plain").unwrap();
+        ward_directory(
+            root,
+            WardOptions {
+                init: true,
+                ..update_options()
+            },
+        )
+        .unwrap();
+        set_protection(
+            root,
+            Protection::Enabled { loss_tolerance },
+            false,
+            NonZeroUsize::MIN,
+            vec![root.join("sub")],
+        )
+        .unwrap();
+        temp
+    }
+
+    fn options(dry_run: bool) -> RepairOptions {
+        RepairOptions {
+            dry_run,
+            jobs: NonZeroUsize::MIN,
+            mirror: None,
+            force: false,
+            pathspecs: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_repairs_protected_and_reports_unprotected() {
+        let temp = protected_tree(100);
+        let root = temp.path();
+        fs::remove_file(root.join("sub/kept.txt")).unwrap();
+        fs::write(root.join("plain.txt"), "edited").unwrap();
+        fs::write(root.join("added.txt"), "added").unwrap();
+
+        let dry = repair_tree(root, options(true)).unwrap();
+        assert_eq!(
+            dry.repaired,
+            vec![("sub/kept.txt".to_string(), RepairSource::Fec)]
+        );
+        assert!(!root.join("sub/kept.txt").exists());
+
+        let result = repair_tree(root, options(false)).unwrap();
+        assert_eq!(
+            result.repaired,
+            vec![("sub/kept.txt".to_string(), RepairSource::Fec)]
+        );
+        assert_eq!(
+            result.unprotected,
+            vec!["added.txt".to_string(), "plain.txt".to_string()]
+        );
+        assert!(result.unrepairable.is_empty() && result.no_fec.is_empty());
+        assert_eq!(
+            fs::read_to_string(root.join("sub/kept.txt")).unwrap(),
+            "kept content"
+        );
+    }
+
+    #[test]
+    fn test_reports_unrepairable_with_reason() {
+        let temp = protected_tree(1);
+        let root = temp.path();
+        fs::write(root.join("sub/large.bin"), vec![b'a'; 5000]).unwrap();
+        ward_directory(root, update_options()).unwrap();
+        // Every symbol is damaged, far beyond 1%.
+        fs::write(root.join("sub/large.bin"), vec![b'b'; 5000]).unwrap();
+
+        let result = repair_tree(
+            root,
+            RepairOptions {
+                force: true,
+                ..options(false)
+            },
+        )
+        .unwrap();
+
+        assert!(result.repaired.is_empty());
+        assert_eq!(result.unrepairable.len(), 1);
+        assert_eq!(result.unrepairable[0].0, "sub/large.bin");
+        assert_eq!(
+            fs::read(root.join("sub/large.bin")).unwrap(),
+            vec![b'b'; 5000]
+        );
+    }
+
+    #[test]
+    fn test_leaves_intentional_changes_alone_unless_forced() {
+        let temp = protected_tree(100);
+        let root = temp.path();
+        let path = root.join("sub/kept.txt");
+        fs::write(&path, "edited content").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(std::time::SystemTime::UNIX_EPOCH)
+            .unwrap();
+
+        let result = repair_tree(root, options(false)).unwrap();
+        assert_eq!(result.intentional, vec!["sub/kept.txt".to_string()]);
+        assert!(result.repaired.is_empty());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "edited content");
+
+        let forced = RepairOptions {
+            force: true,
+            ..options(false)
+        };
+        let result = repair_tree(root, forced).unwrap();
+        assert_eq!(result.repaired.len(), 1);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "kept content");
+    }
+
+    #[test]
+    fn test_restores_from_mirror_by_path_or_content() {
+        let temp = protected_tree(1);
+        let root = temp.path();
+        // Too large for its FEC data to rebuild from nothing at 1%.
+        fs::write(root.join("sub/large.bin"), vec![b'a'; 5000]).unwrap();
+        ward_directory(root, update_options()).unwrap();
+        let mirror = TempDir::new().unwrap();
+        fs::create_dir(mirror.path().join("moved")).unwrap();
+        fs::write(mirror.path().join("plain.txt"), "plain").unwrap();
+        fs::write(mirror.path().join("moved/large.bin"), vec![b'a'; 5000]).unwrap();
+        let plain_mtime = fs::metadata(root.join("plain.txt"))
+            .unwrap()
+            .modified()
+            .unwrap();
+        fs::remove_file(root.join("plain.txt")).unwrap();
+        fs::remove_file(root.join("sub/large.bin")).unwrap();
+        let mirror_root = mirror.path().canonicalize().unwrap();
+
+        let result = repair_tree(
+            root,
+            RepairOptions {
+                mirror: Some(mirror.path().to_path_buf()),
+                ..options(false)
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            result.repaired,
+            vec![
+                (
+                    "plain.txt".to_string(),
+                    RepairSource::Mirror(mirror_root.join("plain.txt"))
+                ),
+                (
+                    "sub/large.bin".to_string(),
+                    RepairSource::Mirror(mirror_root.join("moved/large.bin"))
+                ),
+            ]
+        );
+        assert!(result.unrepairable.is_empty() && result.unprotected.is_empty());
+        assert_eq!(
+            fs::read(root.join("sub/large.bin")).unwrap(),
+            vec![b'a'; 5000]
+        );
+        assert_eq!(
+            fs::metadata(root.join("plain.txt"))
+                .unwrap()
+                .modified()
+                .unwrap(),
+            plain_mtime
+        );
+    }
+
+    #[test]
+    fn test_mirror_without_the_recorded_content() {
+        let temp = protected_tree(1);
+        let root = temp.path();
+        let mirror = TempDir::new().unwrap();
+        fs::write(mirror.path().join("plain.txt"), "stale").unwrap();
+        fs::remove_file(root.join("plain.txt")).unwrap();
+
+        let result = repair_tree(
+            root,
+            RepairOptions {
+                mirror: Some(mirror.path().to_path_buf()),
+                ..options(false)
+            },
+        )
+        .unwrap();
+
+        assert!(result.repaired.is_empty());
+        assert_eq!(result.unrepairable.len(), 1);
+        assert_eq!(result.unrepairable[0].0, "plain.txt");
+        assert!(!root.join("plain.txt").exists());
+    }
+
+    #[test]
+    fn test_not_initialized() {
+        let temp = TempDir::new().unwrap();
+        assert!(matches!(
+            repair_tree(temp.path(), options(false)),
+            Err(RepairError::NotInitialized)
+        ));
+    }
+}
@@ -6,14 +6,31 @@
 //! The traversal also emits fingerprint records and can materialize complete
 //! `WardEntry` values for the update path via `StatusPurpose::WardUpdate`.
 
-use crate::checksum::{ChecksumError, checksum_file};
-use crate::dir_list::{DirListError, FsEntry, list_directory};
-use crate::util::hashing;
-use crate::ward_file::{WardEntry, WardFile, WardFileError};
+use crate::attributes::{self, Attributes, Track};
+use crate::checksum::{
+    Checksum, ChecksumError, ChecksumRequest, FileChecksum, HashAlgorithm, checksum_each_file,
+    checksum_files,
+};
+use crate::dir_list::{
+    DirListError, FsEntry, SpecialKind, TREEWARD_FILENAME, list_directory_at, open_directory,
+    open_root, read_extended_attributes, reopen_directory,
+};
+use crate::ignore_rules::{IgnoreError, IgnoreRules, TREEWARDIGNORE_FILENAME};
+use crate::pathspec::{PathspecError, Scope};
+use crate::protection::{
+    Protection, ProtectionError, RecordedFile, RepairOutcome, check_fec_file, fec_file_name,
+    repair_file, stored_names,
+};
+use crate::signing::{SignatureError, TrustedKey, check_ward_signature};
+use crate::util::dir_handle::{DirHandle, DirId};
+use crate::util::{hashing, names};
+use crate::ward_file::{WardEntry, WardFile, WardFileError, content_digest};
 use base64::Engine;
 use sha2::{Digest, Sha256};
-use std::collections::BTreeMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::io::ErrorKind;
+use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf, StripPrefixError};
 use std::time::UNIX_EPOCH;
 use tracing::info;
@@ -24,8 +41,16 @@ pub enum StatusError {
     WardFile(#[from] WardFileError),
     #[error("Directory listing error: {0}")]
     DirList(#[from] DirListError),
+    #[error("Ignore rules error: {0}")]
+    Ignore(#[from] IgnoreError),
+    #[error("Signature error: {0}")]
+    Signature(#[from] SignatureError),
+    #[error("{0}")]
+    Pathspec(#[from] PathspecError),
     #[error("Checksum error: {0}")]
     Checksum(#[from] ChecksumError),
+    #[error("Protection error: {0}")]
+    Protection(#[from] ProtectionError),
     #[error("Path error: {0}")]
     StripPrefix(#[from] StripPrefixError),
     #[error("{0}")]
@@ -40,9 +65,77 @@ pub enum StatusType {
     /// for reporting purposes (even if checksummed for ward updates).
     PossiblyModified,
     Modified,
+    /// A removed entry and an added entry identified as the same object moved
+    /// to a new path.
+    Renamed,
+    /// Content is unchanged but a recorded attribute (mode, uid or gid)
+    /// differs.
+    PermissionsChanged,
+    /// The entry could not be read (only with `StatusOptions::keep_going`).
+    Error,
     Unchanged,
 }
 
+/// Why an entry could not be compared, under `StatusOptions::keep_going`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EntryErrorKind {
+    /// Reading the entry (or listing the directory) was not permitted.
+    PermissionDenied,
+    /// The entry disappeared while it was being read (for a directory: it, or
+    /// an entry in it, disappeared while it was being listed).
+    Vanished,
+    /// The entry changed while it was being read.
+    ConcurrentModification,
+}
+
+impl EntryErrorKind {
+    /// Stable name, used in JSON output and in the fingerprint.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EntryErrorKind::PermissionDenied => "permission_denied",
+            EntryErrorKind::Vanished => "vanished",
+            EntryErrorKind::ConcurrentModification => "concurrent_modification",
+        }
+    }
+
+    /// The kind of a failure that only concerns the entry being read, or
+    /// `None` for a failure that must abort the run.
+    fn of(error: &StatusError) -> Option<Self> {
+        match error {
+            StatusError::Checksum(ChecksumError::PermissionDenied(_))
+            | StatusError::DirList(DirListError::PermissionDenied(_))
+            | StatusError::WardFile(WardFileError::PermissionDenied(_)) => {
+                Some(EntryErrorKind::PermissionDenied)
+            }
+            StatusError::DirList(DirListError::Xattrs { source, .. })
+                if source.kind() == ErrorKind::PermissionDenied =>
+            {
+                Some(EntryErrorKind::PermissionDenied)
+            }
+            StatusError::Checksum(
+                ChecksumError::ConcurrentModification(_) | ChecksumError::NotRegularFile(_),
+            ) => Some(EntryErrorKind::ConcurrentModification),
+            StatusError::Checksum(ChecksumError::Io(e)) if e.kind() == ErrorKind::NotFound => {
+                Some(EntryErrorKind::Vanished)
+            }
+            StatusError::DirList(
+                DirListError::EntryVanished(_) | DirListError::DirectoryNotFound(_),
+            ) => Some(EntryErrorKind::Vanished),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for EntryErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            EntryErrorKind::PermissionDenied => "permission denied",
+            EntryErrorKind::Vanished => "vanished while being read",
+            EntryErrorKind::ConcurrentModification => "modified while being read",
+        })
+    }
+}
+
 /// Represents the status of a single filesystem entry compared to its ward state.
 ///
 /// `StatusEntry` is the result of comparing a filesystem entry against the corresponding
@@ -54,11 +147,10 @@ pub enum StatusType {
 /// Some variants carry `ward_entry` and/or `old_ward_entry` fields:
 ///
 /// - `ward_entry: Option<WardEntry>` - The current/new ward data for this entry.
-///   Populated when `StatusPurpose::WardUpdate` is used. Under `DiffMode::Capture`
-///   it is also populated for every Modified/PossiblyModified entry (including
-///   type changes) and for Unchanged file and symlink entries (reachable via
-///   `status --diff --all`) - but not for Added entries or unchanged directories.
-///   When present, it is always complete (never contains placeholder data).
+///   Populated when `StatusPurpose::WardUpdate` is used, and for every entry
+///   that exists on the filesystem under `DiffMode::Capture` (Unchanged ones
+///   are reachable via `status --diff --all`). When present, it is always
+///   complete (never contains placeholder data).
 ///
 /// - `old_ward_entry: Option<WardEntry>` - The original ward data before the change.
 ///   Only populated when `DiffMode::Capture` is used, to enable displaying what
@@ -67,8 +159,9 @@ pub enum StatusType {
 /// # Variants
 ///
 /// - `Added`: Entry exists in filesystem but not in ward. The `ward_entry` contains
-///   the new entry data to be written (if `WardUpdate` purpose). No `old_ward_entry`
-///   since there was no previous ward data.
+///   the new entry data to be written (if `WardUpdate` purpose or
+///   `DiffMode::Capture`). No `old_ward_entry` since there was no previous ward
+///   data.
 ///
 /// - `Removed`: Entry exists in ward but not in filesystem. The `old_ward_entry`
 ///   contains the original ward data (if `DiffMode::Capture`). No `ward_entry`
@@ -85,10 +178,24 @@ pub enum StatusType {
 ///   `ward_entry`; with `DiffMode::Capture`, `old_ward_entry` contains the original
 ///   ward data.
 ///
+/// - `Renamed`: A `Removed` and an `Added` entry paired up as one object moved
+///   from `from` to `path` (see `compute_status`). Carries `ward_entry` and
+///   `old_ward_entry` exactly as the `Added` and `Removed` entries it replaces
+///   would have.
+///
+/// - `PermissionsChanged`: Entry exists in both with the same type and content,
+///   but a recorded attribute differs. Carries `ward_entry` and `old_ward_entry`
+///   like `Modified`; under `DiffMode::Capture` this includes directories.
+///
 /// - `Unchanged`: Entry exists in both and matches. The `ward_entry` contains the
-///   current entry data (with `WardUpdate` purpose, or with `DiffMode::Capture` for
-///   file and symlink entries), which may have updated metadata even if content is
-///   unchanged. No `old_ward_entry` since nothing changed.
+///   current entry data (with `WardUpdate` purpose or `DiffMode::Capture`), which
+///   may have updated metadata even if content is unchanged. No `old_ward_entry`
+///   since nothing changed.
+///
+/// - `Error`: The entry could not be read, so its state is unknown (only with
+///   `StatusOptions::keep_going`). With `WardUpdate` purpose, `ward_entry` is
+///   the entry as recorded in the ward, which an update keeps unchanged; it is
+///   `None` when the ward has no entry for the path.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum StatusEntry {
     Added {
@@ -112,10 +219,31 @@ pub enum StatusEntry {
         /// The original ward entry (for diff display)
         old_ward_entry: Option<WardEntry>,
     },
+    Renamed {
+        /// The new path.
+        path: String,
+        /// The old path.
+        from: String,
+        ward_entry: Option<WardEntry>,
+        /// The original ward entry at `from` (for diff display)
+        old_ward_entry: Option<WardEntry>,
+    },
+    PermissionsChanged {
+        path: String,
+        ward_entry: Option<WardEntry>,
+        /// The original ward entry (for diff display)
+        old_ward_entry: Option<WardEntry>,
+    },
     Unchanged {
         path: String,
         ward_entry: Option<WardEntry>,
     },
+    Error {
+        path: String,
+        kind: EntryErrorKind,
+        /// The recorded ward entry, carried forward as is
+        ward_entry: Option<WardEntry>,
+    },
 }
 
 impl StatusEntry {
@@ -125,7 +253,33 @@ impl StatusEntry {
             StatusEntry::Removed { path, .. } => path,
             StatusEntry::Modified { path, .. } => path,
             StatusEntry::PossiblyModified { path, .. } => path,
+            StatusEntry::Renamed { path, .. } => path,
+            StatusEntry::PermissionsChanged { path, .. } => path,
             StatusEntry::Unchanged { path, .. } => path,
+            StatusEntry::Error { path, .. } => path,
+        }
+    }
+
+    /// Rewrites the entry's paths (its path, and a renamed entry's old path)
+    /// with `rewrite` (see `StatusResult::map_paths`).
+    pub fn map_paths(&mut self, rewrite: impl Fn(&str) -> String) {
+        for path in self.paths_mut() {
+            *path = rewrite(path);
+        }
+    }
+
+    /// The entry's paths (its path, and a renamed entry's old path) for
+    /// rewriting with `map_paths`.
+    fn paths_mut(&mut self) -> Vec<&mut String> {
+        match self {
+            StatusEntry::Renamed { path, from, .. } => vec![path, from],
+            StatusEntry::Added { path, .. }
+            | StatusEntry::Removed { path, .. }
+            | StatusEntry::Modified { path, .. }
+            | StatusEntry::PossiblyModified { path, .. }
+            | StatusEntry::PermissionsChanged { path, .. }
+            | StatusEntry::Unchanged { path, .. }
+            | StatusEntry::Error { path, .. } => vec![path],
         }
     }
 
@@ -134,7 +288,10 @@ impl StatusEntry {
             StatusEntry::Added { ward_entry, .. }
             | StatusEntry::Modified { ward_entry, .. }
             | StatusEntry::Unchanged { ward_entry, .. }
-            | StatusEntry::PossiblyModified { ward_entry, .. } => ward_entry.as_ref(),
+            | StatusEntry::PossiblyModified { ward_entry, .. }
+            | StatusEntry::Renamed { ward_entry, .. }
+            | StatusEntry::PermissionsChanged { ward_entry, .. }
+            | StatusEntry::Error { ward_entry, .. } => ward_entry.as_ref(),
             StatusEntry::Removed { .. } => None,
         }
     }
@@ -145,12 +302,16 @@ impl StatusEntry {
             StatusEntry::Removed { .. } => StatusType::Removed,
             StatusEntry::Modified { .. } => StatusType::Modified,
             StatusEntry::PossiblyModified { .. } => StatusType::PossiblyModified,
+            StatusEntry::Renamed { .. } => StatusType::Renamed,
+            StatusEntry::PermissionsChanged { .. } => StatusType::PermissionsChanged,
             StatusEntry::Unchanged { .. } => StatusType::Unchanged,
+            StatusEntry::Error { .. } => StatusType::Error,
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ChecksumPolicy {
     /// Never compute checksums. Files with differing metadata will be
     /// reported as PossiblyModified.
@@ -178,7 +339,7 @@ pub enum StatusPurpose {
     /// `DiffMode::Capture` is enabled, entries include `ward_entry` to show
     /// new values in diffs. Status classification (Modified vs PossiblyModified)
     /// is driven by `ChecksumPolicy`, but `DiffMode::Capture` independently
-    /// forces checksumming of metadata-differing files - even under
+    /// forces checksumming of metadata-differing and added files - even under
     /// `ChecksumPolicy::Never` - so diffs can show old vs new checksums.
     Display,
 
@@ -225,9 +386,70 @@ pub enum DiffMode {
     Capture,
 }
 
+/// The state of a protected file's FEC data (see `protection`), where it
+/// needs attention.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FecState {
+    /// The directory is protected but has no FEC file for the file's
+    /// recorded content.
+    Missing,
+    /// The FEC file fails its integrity check. Only checked under
+    /// `ChecksumPolicy::Always`.
+    Damaged,
+    /// The file is modified or removed, and its FEC data restores its
+    /// recorded content. Only checked under `ChecksumPolicy::Always` with
+    /// `StatusPurpose::Display`.
+    Repairable,
+    /// Likewise, but the FEC data cannot restore it (too much is lost, or the
+    /// name is no longer a file).
+    Unrepairable,
+}
+
+impl FecState {
+    /// Stable lowercase name, used in output.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FecState::Missing => "missing",
+            FecState::Damaged => "damaged",
+            FecState::Repairable => "repairable",
+            FecState::Unrepairable => "unrepairable",
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct StatusResult {
     pub statuses: Vec<StatusEntry>,
+    /// Paths excluded by `.treewardignore` rules, sorted. An ignored directory
+    /// is listed itself; its contents are never visited. Ignored entries take
+    /// no part in statuses, ward building, or the fingerprint.
+    pub ignored: Vec<String>,
+    /// Paths of `.treeward` files, sorted, that are missing or whose content
+    /// does not match the digest recorded for them in the parent directory's
+    /// ward: the ward itself was changed outside treeward. Covered by the
+    /// fingerprint.
+    pub digest_mismatches: Vec<String>,
+    /// Attribute tracking in effect for each directory that tracks anything,
+    /// keyed by path relative to the root. `build_ward_files` records it in
+    /// the directory's ward file.
+    pub tracks: BTreeMap<PathBuf, Track>,
+    /// FEC protection in effect for each protected directory, keyed like
+    /// `tracks`. `build_ward_files` records it in the directory's ward file.
+    pub protections: BTreeMap<PathBuf, Protection>,
+    /// Protected files whose FEC data needs attention, by path, sorted. Not
+    /// covered by the fingerprint: FEC data is derived from the recorded
+    /// state, not part of it.
+    pub fec: Vec<(String, FecState)>,
+    /// Recorded entries that the pathspecs leave out, in the directories on
+    /// the way down to them, keyed by directory path relative to the root.
+    /// `build_ward_files` keeps them as recorded.
+    pub out_of_scope: BTreeMap<PathBuf, BTreeMap<String, WardEntry>>,
+    /// The part of the tree the pathspecs select.
+    pub(crate) scope: Scope,
+    /// Identity of every directory the walk listed, keyed by path relative to
+    /// the root. `ward_directory` only writes a ward file into a directory
+    /// that still is the one listed.
+    pub(crate) dir_ids: BTreeMap<PathBuf, DirId>,
     /// A unique fingerprint representing the entire changeset.
     ///
     /// This is currently a Base64-encoded SHA-256 but it could change
@@ -237,6 +459,26 @@ pub struct StatusResult {
     pub fingerprint: String,
 }
 
+impl StatusResult {
+    /// Rewrites every reported path (of statuses, ignored entries, digest
+    /// mismatches and FEC states) with `rewrite`, to show them relative to another directory
+    /// than the root. Leaves the fingerprint alone, and makes the result
+    /// unfit for `build_ward_files`.
+    pub fn map_paths(&mut self, rewrite: impl Fn(&str) -> String) {
+        for entry in &mut self.statuses {
+            entry.map_paths(&rewrite);
+        }
+        for path in self
+            .ignored
+            .iter_mut()
+            .chain(&mut self.digest_mismatches)
+            .chain(self.fec.iter_mut().map(|(path, _)| path))
+        {
+            *path = rewrite(path);
+        }
+    }
+}
+
 /// Canonicalized fingerprint input for one status entry.
 ///
 /// We decouple fingerprint construction from `StatusEntry` so hashing can use
@@ -259,31 +501,113 @@ struct FingerprintRecord {
 /// enough state to bind a fingerprint to the exact reviewed snapshot.
 #[derive(Debug, Clone, PartialEq, Eq)]
 enum FingerprintPayload {
-    /// Present for Added/Modified/PossiblyModified files.
+    /// Present for Added/Modified/PossiblyModified/PermissionsChanged files.
     File {
         /// Current filesystem mtime at status computation time.
         mtime_nanos: u64,
         /// Current filesystem size at status computation time.
         size: u64,
         // Present only when status determination was checksum-based.
-        sha256: Option<String>,
+        checksum: Option<Checksum>,
+        /// Current values of the tracked attributes (see `fingerprint_attributes`).
+        attributes: Attributes,
+    },
+    /// Present for Added directories, type changes to directories and
+    /// directories whose attributes changed.
+    Dir {
+        mtime_nanos: u64,
+        attributes: Attributes,
+    },
+    /// Present for Added/Modified/PermissionsChanged symlinks and type changes
+    /// to symlinks.
+    Symlink {
+        symlink_target: PathBuf,
+        attributes: Attributes,
+    },
+    /// Present for Added/Modified/PermissionsChanged FIFOs, sockets and
+    /// device nodes, and type changes to them.
+    Special {
+        kind: SpecialKind,
+        attributes: Attributes,
     },
-    /// Present for Added directories and type changes to directories.
-    Dir { mtime_nanos: u64 },
-    /// Present for Added/Modified symlinks and type changes to symlinks.
-    Symlink { symlink_target: PathBuf },
     /// Present for Removed entries (captures prior ward state).
     ///
     /// Removed entries have no filesystem-side object to hash, so the previous ward
     /// data is the only stable identity for what was reviewed. Capturing it prevents
     /// path-only `R` entries from masking ward-state drift between status and update.
     Removed { ward_entry: WardEntry },
+    /// Present for Renamed entries: the `Removed` payload of the old path and
+    /// the `Added` payload of the new one, bound to the old path.
+    Renamed {
+        from: String,
+        removed: Box<FingerprintPayload>,
+        added: Box<FingerprintPayload>,
+    },
+    /// Present for Error entries: there is no state to capture beyond why.
+    Error { kind: EntryErrorKind },
+}
+
+/// Knobs for a single `compute_status` run.
+///
+/// See `compute_status` for what each field controls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatusOptions<'a> {
+    pub policy: ChecksumPolicy,
+    pub mode: StatusMode,
+    pub purpose: StatusPurpose,
+    pub diff_mode: DiffMode,
+    /// Maximum number of files checksummed concurrently.
+    pub jobs: NonZeroUsize,
+    /// Algorithm for checksums of files that have no recorded checksum to
+    /// compare against, and for every checksum recorded by a ward update.
+    pub hash: HashAlgorithm,
+    /// When set, every ward file read must carry a valid signature by this
+    /// key; an unsigned or wrongly signed one is a fatal `SignatureError`.
+    pub trusted_key: Option<&'a TrustedKey>,
+    /// Attributes to track in every directory. `None` keeps each directory's
+    /// recorded setting; a directory without a ward file inherits its
+    /// parent's.
+    pub track: Option<&'a Track>,
+    /// FEC protection for every directory within the pathspecs. `None` keeps
+    /// each directory's recorded setting; a directory without a ward file
+    /// inherits its parent's.
+    pub protection: Option<Protection>,
+    /// Report an entry that cannot be read (permission denied, vanished or
+    /// modified while being read) as a `StatusEntry::Error` and carry on,
+    /// instead of failing the whole run. The root directory must be readable.
+    pub keep_going: bool,
+    /// Subtrees to compare, absolute or relative to the root (see `pathspec`).
+    /// Empty compares the whole tree.
+    pub pathspecs: &'a [PathBuf],
+    /// Compare against the ward files of this baseline tree (see `baseline`)
+    /// instead of the tree's own. FEC data is not checked then: it belongs
+    /// to the live ward.
+    pub baseline: Option<&'a Path>,
+}
+
+impl Default for StatusOptions<'_> {
+    fn default() -> Self {
+        StatusOptions {
+            policy: ChecksumPolicy::Never,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            jobs: NonZeroUsize::MIN,
+            hash: HashAlgorithm::Sha256,
+            trusted_key: None,
+            track: None,
+            protection: None,
+            keep_going: false,
+            pathspecs: &[],
+            baseline: None,
+        }
+    }
 }
 
-/// Walk-invariant parameters for the recursive status traversal.
+/// Walk-invariant parameters for the status comparison.
 ///
 /// Bundles the canonicalized tree root with the knobs that stay fixed for the
-/// duration of a walk, so the recursion passes one context instead of
+/// duration of a walk, so the comparison passes one context instead of
 /// repeating the same argument list at every level.
 #[derive(Debug, Clone, Copy)]
 struct WalkContext<'a> {
@@ -292,6 +616,134 @@ struct WalkContext<'a> {
     mode: StatusMode,
     purpose: StatusPurpose,
     diff_mode: DiffMode,
+    hash: HashAlgorithm,
+    /// Maximum number of files checksummed concurrently.
+    jobs: NonZeroUsize,
+    keep_going: bool,
+}
+
+/// One directory's ward state and filesystem listing, as captured by the walk.
+#[derive(Debug)]
+struct DirSnapshot {
+    dir: PathBuf,
+    /// The directory, if it exists. Its entries are read and its FEC data
+    /// checked through it.
+    handle: Option<DirHandle>,
+    /// Identity of the directory, if it was listed.
+    id: Option<DirId>,
+    /// Ward and filesystem entries, both without ignored names.
+    ward_entries: BTreeMap<String, WardEntry>,
+    fs_entries: BTreeMap<String, FsEntry>,
+    /// Ignored names that exist on the filesystem.
+    ignored: Vec<String>,
+    /// Recorded entries that the ignore rules now exclude. They are reported
+    /// as Removed, and their directories are not descended into.
+    dropped: BTreeMap<String, WardEntry>,
+    /// Ignore rules in effect in the directory and below it.
+    rules: IgnoreRules,
+    /// Whether the directory has its own `.treeward`.
+    has_ward_file: bool,
+    /// Set when the parent's ward recorded a digest for this directory's
+    /// `.treeward` and the file does not match it.
+    digest_mismatch: Option<DigestMismatch>,
+    /// Attributes this directory's ward entries were recorded with.
+    recorded_track: Track,
+    /// Attributes recorded for this directory's entries from now on.
+    track: Track,
+    /// FEC protection the ward file records.
+    recorded_protection: Protection,
+    /// FEC protection from now on.
+    protection: Protection,
+    /// FEC files in the directory's store, if `recorded_protection` is
+    /// enabled.
+    fec_names: BTreeSet<String>,
+    /// Set when the directory could not be read under `keep_going`; the
+    /// snapshot is then empty and the directory's subtree is not walked.
+    error: Option<EntryErrorKind>,
+    /// Recorded entries outside the pathspecs, left out of the comparison.
+    out_of_scope: BTreeMap<String, WardEntry>,
+}
+
+/// How a `.treeward` fails the digest recorded in its parent's ward.
+#[derive(Debug, Clone)]
+enum DigestMismatch {
+    Missing,
+    /// Digest of the actual content, in the recorded digest's algorithm.
+    Content(Checksum),
+}
+
+/// Entries of a directory classified together. The checksums a batch needs
+/// are computed (on up to `jobs` threads) before any of its entries is
+/// classified, so this bounds both how many checksums are held at once and
+/// how much is read past a failure.
+const CLASSIFY_BATCH: usize = 256;
+
+/// An entry of a directory to classify: its name, its ward entry and its
+/// filesystem entry, at least one of them present.
+type Pending<'e> = (&'e str, Option<&'e WardEntry>, Option<&'e FsEntry>);
+
+/// What the walk gathers across directories for `compute_status`. Only
+/// per-directory summaries and the entries to report are kept; a directory's
+/// listing and checksums are dropped once it has been classified.
+#[derive(Debug, Default)]
+struct WalkState {
+    statuses: Vec<StatusEntry>,
+    fingerprint_records: Vec<FingerprintRecord>,
+    ignored: Vec<String>,
+    digest_mismatches: Vec<(String, DigestMismatch)>,
+    tracks: BTreeMap<PathBuf, Track>,
+    protections: BTreeMap<PathBuf, Protection>,
+    out_of_scope: BTreeMap<PathBuf, BTreeMap<String, WardEntry>>,
+    /// Identity of every directory listed, by absolute path. Files are only
+    /// checksummed in the directory they were listed in.
+    dir_ids: HashMap<PathBuf, DirId>,
+    /// Directories with their own `.treeward`, by absolute path.
+    warded_dirs: HashSet<PathBuf>,
+    links: LinkSets,
+    linked_files: Vec<LinkedFile>,
+    fec: Vec<(String, FecState)>,
+    pending_fec: Vec<PendingFec>,
+}
+
+/// The files of directories that record or are about to record `links`, as
+/// far as link groups need them (see `LinkSets::assign`).
+#[derive(Debug, Default)]
+struct LinkSets {
+    /// Files with more than one hard link, by device and inode number.
+    sets: BTreeMap<(u64, u64), Vec<LinkMember>>,
+    /// Relative paths of the files currently in the tree, by recorded group.
+    recorded_groups: BTreeMap<String, BTreeSet<String>>,
+}
+
+#[derive(Debug)]
+struct LinkMember {
+    /// Relative path of the file.
+    path: String,
+    /// Link group its ward entry records.
+    recorded: Option<String>,
+}
+
+/// A file with more than one hard link in a directory that tracks `links`.
+/// Its link group depends on where all its links are, so the walk only takes
+/// its checksum, in order like any other, and it is classified once the whole
+/// tree has been walked.
+#[derive(Debug)]
+struct LinkedFile {
+    dir: PathBuf,
+    name: String,
+    ward_entry: Option<WardEntry>,
+    fs_entry: FsEntry,
+    recorded_track: Track,
+    track: Track,
+    file_checksum: Option<FileChecksum>,
+}
+
+/// Picks `algorithm`'s result out of a `FileChecksum` that was requested with it.
+fn checksum_for(file_checksum: &FileChecksum, algorithm: HashAlgorithm) -> Checksum {
+    file_checksum
+        .checksum(algorithm)
+        .expect("checksum was requested with this algorithm")
+        .clone()
 }
 
 /// Compare filesystem state against ward files to detect changes.
@@ -303,19 +755,42 @@ struct WalkContext<'a> {
 /// # Arguments
 ///
 /// * `root` - Directory to analyze (will be canonicalized)
-/// * `policy` - Controls when checksums are computed:
+/// * `options.policy` - Controls when checksums are computed:
 ///   - `Never`: Only compare metadata (mtime/size)
 ///   - `WhenPossiblyModified`: Checksum files with differing metadata
 ///   - `Always`: Checksum all files (detects silent corruption)
-/// * `mode` - Controls which files are included in results:
+/// * `options.mode` - Controls which files are included in results:
 ///   - `Interesting`: Only include changed files
 ///   - `All`: Include all files, even unchanged ones
-/// * `purpose` - Controls whether to generate complete ward entries:
+/// * `options.purpose` - Controls whether to generate complete ward entries:
 ///   - `Display`: Only checksum based on policy (for user display)
 ///   - `WardUpdate`: Always provide complete ward entries, reusing checksums when possible
-/// * `diff_mode` - Controls whether to capture old ward entry data for diff display:
+/// * `options.diff_mode` - Controls whether to capture old ward entry data for diff display:
 ///   - `None`: Don't capture diff data (default)
 ///   - `Capture`: Include old ward entry in Modified, PossiblyModified, and Removed variants
+/// * `options.jobs` - Maximum number of files checksummed concurrently. Has no
+///   effect on results, only on throughput.
+/// * `options.hash` - Algorithm for new checksums. A file with a recorded
+///   checksum is always compared using the recorded algorithm; `hash` applies
+///   to files without one, and under `WardUpdate` to every checksum that ends
+///   up in a new ward entry. Checksums reused from the ward keep their
+///   recorded algorithm.
+/// * `options.trusted_key` - When set, each ward file's signature is checked
+///   as it is loaded, so the comparison only ever uses signed ward state.
+/// * `options.track` - Attributes that new ward entries record (see
+///   `attributes`), overriding each directory's recorded setting. Existing
+///   entries are always compared on exactly the attributes they record, so
+///   this only affects Added entries' fingerprint payloads and what
+///   `WardUpdate` entries record.
+/// * `options.baseline` - Ward files to compare against instead of the
+///   tree's own: those of a baseline, mirroring the tree's directories (see
+///   `baseline`). Their digest chain is checked as usual; FEC data is not.
+/// * `options.pathspecs` - Subtrees to compare. The directories on the way
+///   down to them are entered, and their ward files checked against their
+///   recorded digests, but only the entries leading on are compared; the rest
+///   end up in `out_of_scope`. Statuses and the fingerprint only cover the
+///   subtrees (and the entries leading to them), and rename pairing and hard
+///   link groups only consider files within them.
 ///
 /// # Returns
 ///
@@ -330,45 +805,108 @@ struct WalkContext<'a> {
 /// * `PossiblyModified` - Metadata differs (only with `ChecksumPolicy::Never`)
 /// * `Modified` - Content differs (checksum mismatch, symlink target changed,
 ///   or type changed)
+/// * `PermissionsChanged` - Content matches but a recorded attribute differs
 /// * `Unchanged` - Entry exists in both and matches (with `StatusMode::All`, and
 ///   always with `StatusPurpose::WardUpdate` regardless of mode)
 ///
+/// # Concurrency
+///
+/// Directories are walked one at a time, and each one's entries are
+/// classified in batches: the files a batch needs checksums of are
+/// checksummed together (on up to `jobs` threads) before its entries are
+/// classified in order, so only one batch of checksums is held at a time.
+/// Failures keep sequential semantics: the error reported is the first one
+/// in walk order, whether from a checksum or not, and concurrent modification
+/// is fatal as always.
+///
 /// # Errors
 ///
 /// Returns error if:
 /// * Ward files are corrupted or have unsupported versions
+/// * A ward file is unsigned or wrongly signed (with `options.trusted_key`)
 /// * Permission denied accessing files or directories
 /// * File modified during checksumming
-pub fn compute_status(
-    root: &Path,
-    policy: ChecksumPolicy,
-    mode: StatusMode,
-    purpose: StatusPurpose,
-    diff_mode: DiffMode,
-) -> Result<StatusResult, StatusError> {
+/// * A pathspec lies outside the root or matches nothing
+pub fn compute_status(root: &Path, options: &StatusOptions) -> Result<StatusResult, StatusError> {
     let root = root
         .canonicalize()
         .map_err(|e| StatusError::DirList(DirListError::Io(e)))?;
 
-    let mut statuses = Vec::new();
-    let mut fingerprint_records = Vec::new();
-
     let ctx = WalkContext {
         tree_root: &root,
-        policy,
-        mode,
-        purpose,
-        diff_mode,
+        policy: options.policy,
+        mode: options.mode,
+        purpose: options.purpose,
+        diff_mode: options.diff_mode,
+        hash: options.hash,
+        jobs: options.jobs,
+        keep_going: options.keep_going,
     };
+    let scope = Scope::new(&root, options.pathspecs)?;
+    let mut state = WalkState::default();
     walk_directory(
         ctx,
         &root,
+        open_root(&root),
         DirExpectation::Present,
+        None,
+        &Track::default(),
+        Protection::default(),
+        options,
+        &IgnoreRules::default(),
+        &scope,
+        &mut state,
+    )?;
+    let WalkState {
+        mut statuses,
+        mut fingerprint_records,
+        mut ignored,
+        mut digest_mismatches,
+        tracks,
+        protections,
+        out_of_scope,
+        dir_ids,
+        warded_dirs,
+        links,
+        linked_files,
+        mut fec,
+        pending_fec,
+    } = state;
+
+    let groups = links.assign();
+    for mut file in linked_files {
+        let attributes = file.fs_entry.attributes_mut();
+        if let (Some(file_id), Some(links)) = (attributes.file_id, attributes.links.as_mut()) {
+            links.group = groups.get(&file_id).cloned();
+        }
+        classify_entry(
+            ctx,
+            &file.dir,
+            &file.name,
+            file.ward_entry.as_ref(),
+            Some(&file.fs_entry),
+            None,
+            Ok(file.file_checksum),
+            &file.recorded_track,
+            &file.track,
+            &mut statuses,
+            &mut fingerprint_records,
+        )?;
+    }
+
+    pair_renames(
+        ctx,
+        &warded_dirs,
+        &dir_ids,
         &mut statuses,
         &mut fingerprint_records,
     )?;
+    check_pending_fec(ctx, pending_fec, &statuses, &mut fec)?;
 
     statuses.sort_by(|a, b| a.path().cmp(b.path()));
+    ignored.sort();
+    digest_mismatches.sort_by(|a, b| a.0.cmp(&b.0));
+    fec.sort_by(|a, b| a.0.cmp(&b.0));
     // Keep fingerprint deterministic even if traversal order changes in the future.
     fingerprint_records.sort_by(|a, b| {
         a.path
@@ -376,14 +914,232 @@ pub fn compute_status(
             .then_with(|| status_type_code(a.status_type).cmp(status_type_code(b.status_type)))
     });
 
-    let fingerprint = compute_fingerprint(&fingerprint_records);
+    let fingerprint = compute_fingerprint(&fingerprint_records, &digest_mismatches);
 
     Ok(StatusResult {
         statuses,
+        ignored,
+        digest_mismatches: digest_mismatches
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect(),
+        tracks,
+        protections,
+        fec,
+        out_of_scope,
+        scope,
+        dir_ids: dir_ids
+            .into_iter()
+            .map(|(dir, id)| Ok((dir.strip_prefix(&root)?.to_path_buf(), id)))
+            .collect::<Result<_, StripPrefixError>>()?,
         fingerprint,
     })
 }
 
+/// FEC states of the recorded files of the protected directory `snapshot`
+/// (see `FecState`), given the statuses of its entries.
+///
+/// Under `ChecksumPolicy::Always`, a modified or removed file is corruption
+/// until shown otherwise, so for `StatusPurpose::Display` its FEC data is
+/// tried (without writing anything) to tell whether `repair` can restore it,
+/// and every FEC file's integrity is checked. Files whose status is not
+/// final yet are left to `check_pending_fec`.
+fn check_fec(
+    ctx: WalkContext<'_>,
+    snapshot: &DirSnapshot,
+    dir: &DirHandle,
+    linked: &BTreeSet<String>,
+    statuses: &[StatusEntry],
+    fec: &mut Vec<(String, FecState)>,
+    pending_fec: &mut Vec<PendingFec>,
+) -> Result<(), StatusError> {
+    let status_types: HashMap<&str, StatusType> = statuses
+        .iter()
+        .map(|entry| (entry.path(), entry.status_type()))
+        .collect();
+    let try_repair = ctx.policy == ChecksumPolicy::Always && ctx.purpose == StatusPurpose::Display;
+    let mut pending = Vec::new();
+    for (name, entry) in &snapshot.ward_entries {
+        let WardEntry::File {
+            checksum,
+            mtime_nanos,
+            ..
+        } = entry
+        else {
+            continue;
+        };
+        let path = make_relative_path(ctx.tree_root, &snapshot.dir, name)?;
+        let stored = snapshot.fec_names.contains(&fec_file_name(checksum));
+        let status = status_types.get(path.as_str()).copied();
+        let classified_later = status.is_none() && linked.contains(name);
+        if classified_later || (try_repair && status == Some(StatusType::Removed)) {
+            pending.push((name.clone(), checksum.clone(), *mtime_nanos, stored));
+            continue;
+        }
+        let recorded = RecordedFile {
+            checksum,
+            mtime_nanos: *mtime_nanos,
+        };
+        if let Some(state) = fec_state(ctx, dir, &snapshot.dir, name, &recorded, stored, status)? {
+            fec.push((path, state));
+        }
+    }
+    if !pending.is_empty() {
+        pending_fec.push(PendingFec {
+            dir: snapshot.dir.clone(),
+            id: snapshot.id,
+            files: pending,
+        });
+    }
+    Ok(())
+}
+
+/// FEC states of the files `check_fec` left pending, now that every status
+/// is final. Their directories are reopened from the root and must still be
+/// the ones the walk listed.
+fn check_pending_fec(
+    ctx: WalkContext<'_>,
+    pending_fec: Vec<PendingFec>,
+    statuses: &[StatusEntry],
+    fec: &mut Vec<(String, FecState)>,
+) -> Result<(), StatusError> {
+    if pending_fec.is_empty() {
+        return Ok(());
+    }
+
+    let status_types: HashMap<&str, StatusType> = statuses
+        .iter()
+        .map(|entry| (entry.path(), entry.status_type()))
+        .collect();
+    let root_dir = open_root(ctx.tree_root)?;
+    for pending in pending_fec {
+        let dir = reopen_directory(&root_dir, ctx.tree_root, &pending.dir, pending.id)?;
+        for (name, checksum, mtime_nanos, stored) in &pending.files {
+            let path = make_relative_path(ctx.tree_root, &pending.dir, name)?;
+            let recorded = RecordedFile {
+                checksum,
+                mtime_nanos: *mtime_nanos,
+            };
+            let status = status_types.get(path.as_str()).copied();
+            if let Some(state) =
+                fec_state(ctx, &dir, &pending.dir, name, &recorded, *stored, status)?
+            {
+                fec.push((path, state));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// FEC state of the recorded file `name` of the protected directory `dir` (at
+/// `dir_path`), given its final status (`None` for an unchanged file, or one
+/// renamed away) and whether its FEC file is `stored`. `None` if there is
+/// nothing to report.
+fn fec_state(
+    ctx: WalkContext<'_>,
+    dir: &DirHandle,
+    dir_path: &Path,
+    name: &str,
+    recorded: &RecordedFile,
+    stored: bool,
+    status: Option<StatusType>,
+) -> Result<Option<FecState>, StatusError> {
+    let always = ctx.policy == ChecksumPolicy::Always;
+    let try_repair = always && ctx.purpose == StatusPurpose::Display;
+    Ok(match status {
+        Some(StatusType::Error) => None,
+        Some(StatusType::Modified | StatusType::Removed) if try_repair => {
+            Some(match repair_file(dir, dir_path, name, recorded, true)? {
+                RepairOutcome::Repaired => FecState::Repairable,
+                RepairOutcome::NoFec => FecState::Missing,
+                RepairOutcome::Unrepairable(_) | RepairOutcome::NotAFile => FecState::Unrepairable,
+            })
+        }
+        _ if !stored => Some(FecState::Missing),
+        _ if always => check_fec_file(dir, dir_path, &fec_file_name(recorded.checksum))?
+            .map(|_| FecState::Damaged),
+        _ => None,
+    })
+}
+
+impl LinkSets {
+    /// Notes the files of `snapshot`, a directory that records or is about to
+    /// record `links`, and returns the names of those with more than one hard
+    /// link: their link groups are only known once the walk is done.
+    fn note(
+        &mut self,
+        root: &Path,
+        snapshot: &DirSnapshot,
+    ) -> Result<BTreeSet<String>, StatusError> {
+        let mut linked = BTreeSet::new();
+        for (name, fs_entry) in &snapshot.fs_entries {
+            let attributes = fs_entry.attributes();
+            let (Some(file_id), Some(links)) = (attributes.file_id, &attributes.links) else {
+                continue;
+            };
+            let path = make_relative_path(root, &snapshot.dir, name)?;
+            let recorded = recorded_link_group(snapshot, name);
+            if let Some(group) = recorded {
+                self.recorded_groups
+                    .entry(group.to_string())
+                    .or_default()
+                    .insert(path.clone());
+            }
+            if links.nlink > 1 {
+                self.sets.entry(file_id).or_default().push(LinkMember {
+                    path,
+                    recorded: recorded.map(str::to_string),
+                });
+                linked.insert(name.clone());
+            }
+        }
+        Ok(linked)
+    }
+
+    /// Link group ids of the files with more than one hard link, by device
+    /// and inode number.
+    ///
+    /// The files sharing an inode keep the link group they recorded if they
+    /// all recorded the same one and no other file of the tree still recorded
+    /// it. Otherwise they get a fresh id, so a link set that was split, merged
+    /// or joined by another file shows up as a changed `link_group` on its
+    /// files, whichever directories they are in.
+    fn assign(self) -> HashMap<(u64, u64), String> {
+        let LinkSets {
+            sets,
+            recorded_groups,
+        } = self;
+        sets.into_iter()
+            .map(|(file_id, members)| {
+                let paths: BTreeSet<String> =
+                    members.iter().map(|member| member.path.clone()).collect();
+                let intact = members[0].recorded.as_deref().filter(|group| {
+                    members
+                        .iter()
+                        .all(|member| member.recorded.as_deref() == Some(*group))
+                        && recorded_groups.get(*group) == Some(&paths)
+                });
+                let group = match intact {
+                    Some(group) => group.to_string(),
+                    None => attributes::new_link_group(paths.iter().map(String::as_str)),
+                };
+                (file_id, group)
+            })
+            .collect()
+    }
+}
+
+fn recorded_link_group<'a>(snapshot: &'a DirSnapshot, name: &str) -> Option<&'a str> {
+    snapshot
+        .ward_entries
+        .get(name)?
+        .attributes()
+        .links
+        .as_ref()?
+        .group
+        .as_deref()
+}
+
 /// How a directory's absence should be interpreted when walking into it.
 ///
 /// The same `DirectoryNotFound` from listing means two very different things
@@ -402,70 +1158,661 @@ enum DirExpectation {
     MaybeRemoved,
 }
 
+/// Walks `current_dir`, opened as `opened`, and, recursively, every directory
+/// below it that is in `scope`, gathering the comparison in `state`.
+///
+/// The directory's own entries are classified first, in batches, then its
+/// subdirectories are walked, and only then are its entries that are
+/// directories classified: their parent reports a subdirectory that could
+/// not be read (under `keep_going`), which is what this returns.
+#[allow(clippy::too_many_arguments)]
 fn walk_directory(
     ctx: WalkContext<'_>,
     current_dir: &Path,
+    opened: Result<DirHandle, DirListError>,
     expectation: DirExpectation,
-    statuses: &mut Vec<StatusEntry>,
-    fingerprint_records: &mut Vec<FingerprintRecord>,
-) -> Result<(), StatusError> {
+    recorded_digest: Option<&Checksum>,
+    inherited_track: &Track,
+    inherited_protection: Protection,
+    options: &StatusOptions,
+    inherited_rules: &IgnoreRules,
+    scope: &Scope,
+    state: &mut WalkState,
+) -> Result<Option<EntryErrorKind>, StatusError> {
+    let snapshot = snapshot_directory(
+        ctx.tree_root,
+        current_dir,
+        opened,
+        expectation,
+        recorded_digest,
+        inherited_track,
+        inherited_protection,
+        options,
+        inherited_rules,
+        scope,
+    )?;
+    if let Some(kind) = snapshot.error {
+        return Ok(Some(kind));
+    }
+
+    let relative_dir = current_dir.strip_prefix(ctx.tree_root)?;
+    if let Some(id) = snapshot.id {
+        state.dir_ids.insert(current_dir.to_path_buf(), id);
+    }
+    if snapshot.has_ward_file {
+        state.warded_dirs.insert(current_dir.to_path_buf());
+    }
+    if !snapshot.out_of_scope.is_empty() {
+        state
+            .out_of_scope
+            .insert(relative_dir.to_path_buf(), snapshot.out_of_scope.clone());
+    }
+    if !snapshot.track.is_empty() {
+        state
+            .tracks
+            .insert(relative_dir.to_path_buf(), snapshot.track.clone());
+    }
+    if !snapshot.protection.is_disabled() {
+        state
+            .protections
+            .insert(relative_dir.to_path_buf(), snapshot.protection);
+    }
+    for name in &snapshot.ignored {
+        state
+            .ignored
+            .push(make_relative_path(ctx.tree_root, current_dir, name)?);
+    }
+    if let Some(mismatch) = &snapshot.digest_mismatch {
+        state.digest_mismatches.push((
+            make_relative_path(ctx.tree_root, current_dir, TREEWARD_FILENAME)?,
+            mismatch.clone(),
+        ));
+    }
+    let linked = if snapshot.recorded_track.links || snapshot.track.links {
+        state.links.note(ctx.tree_root, &snapshot)?
+    } else {
+        BTreeSet::new()
+    };
+
+    // Added entries, then recorded ones, each by name: the order entries
+    // have always been classified in, and so the order failures surface in.
+    let (dir_entries, entries): (Vec<Pending>, Vec<Pending>) = snapshot
+        .fs_entries
+        .iter()
+        .filter(|(name, _)| !snapshot.ward_entries.contains_key(*name))
+        .map(|(name, fs_entry)| (name.as_str(), None, Some(fs_entry)))
+        .chain(snapshot.ward_entries.iter().map(|(name, ward_entry)| {
+            (
+                name.as_str(),
+                Some(ward_entry),
+                snapshot.fs_entries.get(name),
+            )
+        }))
+        .chain(
+            snapshot
+                .dropped
+                .iter()
+                .map(|(name, ward_entry)| (name.as_str(), Some(ward_entry), None)),
+        )
+        .partition(|(_, _, fs_entry)| matches!(fs_entry, Some(FsEntry::Dir { .. })));
+    let mut statuses = Vec::new();
+    let mut fingerprint_records = Vec::new();
+    for batch in entries.chunks(CLASSIFY_BATCH) {
+        classify_batch(
+            ctx,
+            &snapshot,
+            batch,
+            &linked,
+            &mut statuses,
+            &mut fingerprint_records,
+            &mut state.linked_files,
+        )?;
+    }
+
+    let open_child = |name: &str, path: &Path| match &snapshot.handle {
+        Some(dir) => open_directory(dir, name, path),
+        None => Err(DirListError::DirectoryNotFound(path.to_path_buf())),
+    };
+    // Both sides only hold names in scope by now.
+    let child_scope = |name: &str| scope.child(name).expect("entry is in scope");
+    let mut child_errors = HashMap::new();
+    for (name, _, _) in &dir_entries {
+        let child_path = current_dir.join(names::to_os(name));
+        let recorded_digest = match snapshot.ward_entries.get(*name) {
+            Some(WardEntry::Dir { digest, .. }) => digest.as_ref(),
+            _ => None,
+        };
+        if let Some(kind) = walk_directory(
+            ctx,
+            &child_path,
+            open_child(name, &child_path),
+            DirExpectation::Present,
+            recorded_digest,
+            &snapshot.track,
+            snapshot.protection,
+            options,
+            &snapshot.rules,
+            child_scope(name),
+            state,
+        )? {
+            child_errors.insert(*name, kind);
+        }
+    }
+    for (name, ward_entry) in &snapshot.ward_entries {
+        if !matches!(ward_entry, WardEntry::Dir { .. }) || snapshot.fs_entries.contains_key(name) {
+            continue;
+        }
+        let child_path = current_dir.join(names::to_os(name));
+        walk_directory(
+            ctx,
+            &child_path,
+            open_child(name, &child_path),
+            DirExpectation::MaybeRemoved,
+            None,
+            &snapshot.track,
+            snapshot.protection,
+            options,
+            &snapshot.rules,
+            child_scope(name),
+            state,
+        )?;
+    }
+
+    for (name, ward_entry, fs_entry) in dir_entries {
+        classify_entry(
+            ctx,
+            current_dir,
+            name,
+            ward_entry,
+            fs_entry,
+            child_errors.get(name).copied(),
+            Ok(None),
+            &snapshot.recorded_track,
+            &snapshot.track,
+            &mut statuses,
+            &mut fingerprint_records,
+        )?;
+    }
+
+    if options.baseline.is_none()
+        && !snapshot.recorded_protection.is_disabled()
+        && let Some(dir) = &snapshot.handle
+    {
+        check_fec(
+            ctx,
+            &snapshot,
+            dir,
+            &linked,
+            &statuses,
+            &mut state.fec,
+            &mut state.pending_fec,
+        )?;
+    }
+    state.statuses.append(&mut statuses);
+    state.fingerprint_records.append(&mut fingerprint_records);
+    Ok(None)
+}
+
+/// Captures `current_dir`, opened as `opened`, narrowed to the names in
+/// `scope`. Everything in the directory is read through its handle, and
+/// subdirectories are opened relative to it, so the walk never follows a
+/// directory swapped for a symlink.
+#[allow(clippy::too_many_arguments)]
+fn snapshot_directory(
+    tree_root: &Path,
+    current_dir: &Path,
+    opened: Result<DirHandle, DirListError>,
+    expectation: DirExpectation,
+    recorded_digest: Option<&Checksum>,
+    inherited_track: &Track,
+    inherited_protection: Protection,
+    options: &StatusOptions,
+    inherited_rules: &IgnoreRules,
+    scope: &Scope,
+) -> Result<DirSnapshot, StatusError> {
     info!("Entering directory {}", current_dir.display());
 
-    let ward_path = current_dir.join(".treeward");
-    let ward_file = WardFile::load_if_exists(&ward_path)?;
-    let ward_entries = ward_file.map(|wf| wf.entries).unwrap_or_default();
+    // Under `keep_going`, a directory that cannot be read is captured as an
+    // empty, errored snapshot and its subtree is skipped. The root's failure
+    // has no parent to be reported in and stays fatal.
+    let unreadable = |error: StatusError| match EntryErrorKind::of(&error) {
+        Some(kind) if options.keep_going && current_dir != tree_root => Ok(DirSnapshot {
+            dir: current_dir.to_path_buf(),
+            handle: None,
+            id: None,
+            ward_entries: BTreeMap::new(),
+            fs_entries: BTreeMap::new(),
+            ignored: Vec::new(),
+            dropped: BTreeMap::new(),
+            rules: IgnoreRules::default(),
+            has_ward_file: false,
+            digest_mismatch: None,
+            recorded_track: Track::default(),
+            track: Track::default(),
+            recorded_protection: Protection::default(),
+            protection: Protection::default(),
+            fec_names: BTreeSet::new(),
+            error: Some(kind),
+            out_of_scope: BTreeMap::new(),
+        }),
+        _ => Err(error),
+    };
 
-    // Per-entry failures inside an existing directory (including a child
-    // vanishing mid-listing) are always fatal and propagate. A missing
-    // directory is tolerated only when ward state is the sole reason we are
-    // here; a directory that was just observed on the filesystem must still
-    // exist, or we are racing a concurrent modification.
-    let fs_entries = match list_directory(current_dir) {
-        Ok(entries) => entries,
+    // A missing directory is tolerated only when ward state is the sole
+    // reason we are here; a directory that was just observed on the
+    // filesystem must still exist, or we are racing a concurrent
+    // modification. One replaced by a symlink is always fatal.
+    let dir = match opened {
+        Ok(dir) => Some(dir),
         Err(DirListError::DirectoryNotFound(_)) if expectation == DirExpectation::MaybeRemoved => {
-            BTreeMap::new()
+            None
         }
-        Err(e) => return Err(StatusError::DirList(e)),
+        Err(e @ DirListError::DirectoryReplaced(_)) => return Err(e.into()),
+        Err(e) => return unreadable(e.into()),
+    };
+    let id = match dir.as_ref().map(DirHandle::id).transpose() {
+        Ok(id) => id,
+        Err(e) => return unreadable(DirListError::Io(e).into()),
     };
 
-    compare_entries(
-        ctx,
-        current_dir,
-        &ward_entries,
-        &fs_entries,
-        statuses,
-        fingerprint_records,
-    )?;
+    let load_ward_file = |dir: &DirHandle| match options.baseline {
+        Some(baseline) => WardFile::load_with_content_if_exists(
+            &baseline
+                .join(current_dir.strip_prefix(tree_root)?)
+                .join(TREEWARD_FILENAME),
+        )
+        .map_err(StatusError::from),
+        None => WardFile::load_with_content_if_exists_in(dir, current_dir).map_err(Into::into),
+    };
+    let ward_file = match dir.as_ref().map(load_ward_file).transpose() {
+        Ok(ward_file) => ward_file.flatten(),
+        Err(e) => return unreadable(e),
+    };
+    if let (Some(key), Some(dir), Some((_, content))) = (options.trusted_key, &dir, &ward_file) {
+        check_ward_signature(dir, current_dir, content, key)?;
+    }
+    let digest_mismatch = recorded_digest.and_then(|recorded| match &ward_file {
+        None => Some(DigestMismatch::Missing),
+        Some((_, content)) => {
+            let actual = content_digest(content, recorded.algorithm);
+            (actual != *recorded).then_some(DigestMismatch::Content(actual))
+        }
+    });
+    let has_ward_file = ward_file.is_some();
+    let recorded_track = match &ward_file {
+        Some((wf, _)) => wf.track().clone(),
+        None => Track::default(),
+    };
+    let track = match (options.track, &ward_file) {
+        (Some(track), _) => track.clone(),
+        (None, Some(_)) => recorded_track.clone(),
+        (None, None) => inherited_track.clone(),
+    };
+    // Unlike tracking, protection is only changed within the pathspecs: the
+    // directories on the way down to them keep their FEC stores as they are.
+    let recorded_protection = match &ward_file {
+        Some((wf, _)) => wf.protection(),
+        None => Protection::default(),
+    };
+    let protection = match (options.protection, &ward_file) {
+        (Some(protection), _) if matches!(scope, Scope::All) => protection,
+        (_, Some(_)) => recorded_protection,
+        (_, None) => inherited_protection,
+    };
+    let fec_names = match &dir {
+        Some(dir) if !recorded_protection.is_disabled() => match stored_names(dir, current_dir) {
+            Ok(fec_names) => fec_names,
+            Err(e) => return unreadable(e.into()),
+        },
+        _ => BTreeSet::new(),
+    };
+    let mut ward_entries = ward_file.map(|(wf, _)| wf.entries).unwrap_or_default();
 
-    for (name, entry) in &fs_entries {
-        if matches!(entry, FsEntry::Dir { .. }) {
-            let child_path = current_dir.join(name);
-            walk_directory(
-                ctx,
-                &child_path,
-                DirExpectation::Present,
-                statuses,
-                fingerprint_records,
-            )?;
+    // Per-entry failures inside an existing directory (including a child
+    // vanishing mid-listing) are fatal and propagate, unless `keep_going`
+    // turns them into an error for this directory.
+    let (mut fs_entries, listed) = match &dir {
+        Some(dir) => match list_directory_at(dir, current_dir, current_dir == tree_root) {
+            Ok(entries) => (entries, true),
+            Err(e) => return unreadable(e.into()),
+        },
+        None => (BTreeMap::new(), false),
+    };
+
+    // The ignore file applies to the whole directory, so it is looked for
+    // before the listing is narrowed to the pathspecs.
+    let has_ignore_file = matches!(
+        fs_entries.get(TREEWARDIGNORE_FILENAME),
+        Some(FsEntry::File { .. })
+    );
+    let mut out_of_scope = BTreeMap::new();
+    if let Scope::Only(children) = scope {
+        // Below a removed directory, there is nothing left for a pathspec to
+        // match; the directory itself is reported as Removed.
+        if listed {
+            check_pathspec_matches(tree_root, current_dir, children, &ward_entries, &fs_entries)?;
         }
+        fs_entries.retain(|name, _| children.contains_key(name));
+        (out_of_scope, ward_entries) = std::mem::take(&mut ward_entries)
+            .into_iter()
+            .partition(|(name, _)| !children.contains_key(name));
     }
 
-    for (name, entry) in &ward_entries {
-        if matches!(entry, WardEntry::Dir {}) && !fs_entries.contains_key(name) {
-            let child_path = current_dir.join(name);
-            walk_directory(
-                ctx,
-                &child_path,
-                DirExpectation::MaybeRemoved,
-                statuses,
-                fingerprint_records,
-            )?;
+    let rules = inherited_rules.descend(current_dir, dir.as_ref().filter(|_| has_ignore_file))?;
+    let (ignored, dropped) =
+        remove_ignored(&rules, current_dir, &mut ward_entries, &mut fs_entries);
+    if let Some(dir) = &dir
+        && (recorded_track.records_xattrs() || track.records_xattrs())
+        && let Err(e) = read_extended_attributes(dir, current_dir, &mut fs_entries, |name| {
+            recorded_track.selects_xattr(name) || track.selects_xattr(name)
+        })
+    {
+        return unreadable(e.into());
+    }
+
+    Ok(DirSnapshot {
+        dir: current_dir.to_path_buf(),
+        handle: dir,
+        id,
+        ward_entries,
+        fs_entries,
+        ignored,
+        dropped,
+        rules,
+        has_ward_file,
+        digest_mismatch,
+        recorded_track,
+        track,
+        recorded_protection,
+        protection,
+        fec_names,
+        error: None,
+        out_of_scope,
+    })
+}
+
+/// Fails unless each name `children` leads to exists in `current_dir`, on the
+/// filesystem or in the ward, and is a directory where the pathspec goes on
+/// below it.
+fn check_pathspec_matches(
+    tree_root: &Path,
+    current_dir: &Path,
+    children: &BTreeMap<String, Scope>,
+    ward_entries: &BTreeMap<String, WardEntry>,
+    fs_entries: &BTreeMap<String, FsEntry>,
+) -> Result<(), StatusError> {
+    for (name, child_scope) in children {
+        let fs_entry = fs_entries.get(name);
+        let ward_entry = ward_entries.get(name);
+        if fs_entry.is_none() && ward_entry.is_none() {
+            let path = make_relative_path(tree_root, current_dir, name)?;
+            return Err(PathspecError::NoMatch(path).into());
+        }
+        if matches!(child_scope, Scope::Only(_))
+            && !matches!(fs_entry, Some(FsEntry::Dir { .. }))
+            && !matches!(ward_entry, Some(WardEntry::Dir { .. }))
+        {
+            let path = make_relative_path(tree_root, current_dir, name)?;
+            return Err(PathspecError::NotADirectory(path).into());
         }
     }
+    Ok(())
+}
 
+/// Drops ignored names from both sides of a directory's comparison and
+/// returns the ignored names that exist on the filesystem, and the recorded
+/// entries of ignored names.
+///
+/// A name is judged by its filesystem entry when it has one, and by its ward
+/// entry otherwise, so directory-only patterns see the type that is actually
+/// there. Removing the name from the filesystem side keeps an ignored entry
+/// out of everything downstream: it is neither reported, nor fingerprinted,
+/// nor recorded on update. A recorded entry of an ignored name leaves the
+/// ward on update, so it is reported as Removed rather than dropped silently;
+/// for a directory, that is what tells its `.treeward` is no longer part of
+/// the tree. `.treewardignore` itself is never ignored, so that changes to
+/// the rules always show.
+fn remove_ignored(
+    rules: &IgnoreRules,
+    current_dir: &Path,
+    ward_entries: &mut BTreeMap<String, WardEntry>,
+    fs_entries: &mut BTreeMap<String, FsEntry>,
+) -> (Vec<String>, BTreeMap<String, WardEntry>) {
+    let is_ignored = |name: &str, is_dir: bool| {
+        name != TREEWARDIGNORE_FILENAME
+            && rules.is_ignored(&current_dir.join(names::to_os(name)), is_dir)
+    };
+    let ignored: Vec<String> = fs_entries
+        .iter()
+        .filter(|(name, entry)| is_ignored(name, matches!(entry, FsEntry::Dir { .. })))
+        .map(|(name, _)| name.clone())
+        .collect();
+    for name in &ignored {
+        fs_entries.remove(name);
+    }
+
+    let dropped = ward_entries
+        .extract_if(.., |name, entry| {
+            ignored.binary_search(name).is_ok()
+                || (!fs_entries.contains_key(name)
+                    && is_ignored(name, matches!(entry, WardEntry::Dir { .. })))
+        })
+        .collect();
+
+    (ignored, dropped)
+}
+
+/// The algorithms classification checksums the file `fs_entry` at `path`
+/// with, given its ward entry `ward_entry` if it has one, or `None` if it
+/// does not read the file.
+///
+/// This is the one place that decides: the walk checksums a batch of files
+/// by what it asks for, and classification then takes exactly those
+/// checksums.
+fn checksum_algorithms(
+    ctx: WalkContext<'_>,
+    path: &Path,
+    ward_entry: Option<&WardEntry>,
+    fs_entry: &FsEntry,
+) -> Result<Option<Vec<HashAlgorithm>>, StatusError> {
+    let FsEntry::File { mtime, size, .. } = fs_entry else {
+        return Ok(None);
+    };
+    Ok(match ward_entry {
+        None => added_file_needs_checksum(ctx).then(|| vec![ctx.hash]),
+        Some(WardEntry::File {
+            checksum,
+            mtime_nanos,
+            size: ward_size,
+            ..
+        }) => {
+            let metadata_differs =
+                mtime_to_nanos(mtime, path)? != *mtime_nanos || size != ward_size;
+            checksum_needed(ctx, metadata_differs)
+                .then(|| algorithms_for_recorded(ctx, checksum.algorithm))
+        }
+        Some(_) => type_change_needs_checksum(ctx).then(|| vec![ctx.hash]),
+    })
+}
+
+/// Recorded files of a protected directory whose FEC state depends on a
+/// status only known after the walk: removed files, which may turn out
+/// renamed, and linked files (see `LinkedFile`).
+#[derive(Debug)]
+struct PendingFec {
+    dir: PathBuf,
+    id: Option<DirId>,
+    /// Name, recorded checksum and mtime of each file, and whether its FEC
+    /// file is in the store.
+    files: Vec<(String, Checksum, u64, bool)>,
+}
+
+/// Checksums `requests` on up to `ctx.jobs` threads and returns each one's
+/// result, in order. Without `keep_going` the first failure is fatal, so no
+/// request is started after it (see `checksum_files`) and later ones may
+/// have no result; classification stops at the failure before reaching them.
+fn checksum_batch(
+    ctx: WalkContext<'_>,
+    requests: &[ChecksumRequest],
+) -> Vec<Option<Result<FileChecksum, ChecksumError>>> {
+    if ctx.keep_going {
+        checksum_each_file(requests, ctx.jobs)
+            .into_iter()
+            .map(Some)
+            .collect()
+    } else {
+        checksum_files(requests, ctx.jobs)
+    }
+}
+
+/// Classifies `batch`, entries of `snapshot` that are not directories on the
+/// filesystem, in order, once the checksums they need are computed. The
+/// files in `linked` only get their checksums here and are classified after
+/// the walk (see `LinkedFile`).
+fn classify_batch(
+    ctx: WalkContext<'_>,
+    snapshot: &DirSnapshot,
+    batch: &[Pending<'_>],
+    linked: &BTreeSet<String>,
+    statuses: &mut Vec<StatusEntry>,
+    fingerprint_records: &mut Vec<FingerprintRecord>,
+    linked_files: &mut Vec<LinkedFile>,
+) -> Result<(), StatusError> {
+    let decisions: Vec<_> = batch
+        .iter()
+        .map(|(name, ward_entry, fs_entry)| {
+            let path = snapshot.dir.join(names::to_os(name));
+            match fs_entry {
+                Some(fs_entry) => checksum_algorithms(ctx, &path, *ward_entry, fs_entry)
+                    .map(|algorithms| algorithms.map(|algorithms| (path, algorithms))),
+                None => Ok(None),
+            }
+        })
+        .collect();
+    let requests: Vec<ChecksumRequest> = decisions
+        .iter()
+        .filter_map(|decision| match decision {
+            Ok(Some((path, algorithms))) => Some(ChecksumRequest {
+                path: path.clone(),
+                algorithms: algorithms.clone(),
+                walked_dir: snapshot.id,
+            }),
+            _ => None,
+        })
+        .collect();
+    let mut results = checksum_batch(ctx, &requests).into_iter();
+
+    for (&(name, ward_entry, fs_entry), decision) in batch.iter().zip(decisions) {
+        let file_checksum = match decision {
+            Ok(Some(_)) => match results.next().flatten() {
+                Some(result) => result.map(Some).map_err(StatusError::from),
+                None => unreachable!("classification stops at the failure that ended checksumming"),
+            },
+            Ok(None) => Ok(None),
+            Err(e) => Err(e),
+        };
+        if linked.contains(name) {
+            match file_checksum {
+                Ok(file_checksum) => linked_files.push(LinkedFile {
+                    dir: snapshot.dir.clone(),
+                    name: name.to_string(),
+                    ward_entry: ward_entry.cloned(),
+                    fs_entry: fs_entry.expect("linked files are listed").clone(),
+                    recorded_track: snapshot.recorded_track.clone(),
+                    track: snapshot.track.clone(),
+                    file_checksum,
+                }),
+                Err(e) => {
+                    let kind = recoverable(ctx, e)?;
+                    push_entry_error(
+                        ctx,
+                        &snapshot.dir,
+                        name,
+                        kind,
+                        ward_entry,
+                        statuses,
+                        fingerprint_records,
+                    )?;
+                }
+            }
+            continue;
+        }
+        classify_entry(
+            ctx,
+            &snapshot.dir,
+            name,
+            ward_entry,
+            fs_entry,
+            None,
+            file_checksum,
+            &snapshot.recorded_track,
+            &snapshot.track,
+            statuses,
+            fingerprint_records,
+        )?;
+    }
     Ok(())
 }
 
+/// Algorithm a re-checksummed file with a recorded checksum in `recorded`
+/// gets in its new ward entry: a ward update records `ctx.hash`, anything else
+/// (diff display) stays comparable with the recorded checksum.
+fn new_entry_algorithm(ctx: WalkContext<'_>, recorded: HashAlgorithm) -> HashAlgorithm {
+    if ctx.purpose == StatusPurpose::WardUpdate {
+        ctx.hash
+    } else {
+        recorded
+    }
+}
+
+/// Algorithms to compute for a file with a recorded checksum in `recorded`:
+/// the recorded one for comparison, plus the new entry's if it differs.
+fn algorithms_for_recorded(ctx: WalkContext<'_>, recorded: HashAlgorithm) -> Vec<HashAlgorithm> {
+    let new_algorithm = new_entry_algorithm(ctx, recorded);
+    if new_algorithm == recorded {
+        vec![recorded]
+    } else {
+        vec![recorded, new_algorithm]
+    }
+}
+
+/// Whether the status policy itself calls for checksumming a file that is in
+/// both the ward and the filesystem.
+fn checksum_needed_for_status(ctx: WalkContext<'_>, metadata_differs: bool) -> bool {
+    match ctx.policy {
+        ChecksumPolicy::Never => false,
+        ChecksumPolicy::WhenPossiblyModified => metadata_differs,
+        ChecksumPolicy::Always => true,
+    }
+}
+
+/// Whether a file in both the ward and the filesystem gets checksummed for
+/// any reason: status policy, ward building, or diff capture.
+fn checksum_needed(ctx: WalkContext<'_>, metadata_differs: bool) -> bool {
+    let need_checksum_for_ward = ctx.purpose == StatusPurpose::WardUpdate && metadata_differs;
+    let need_checksum_for_diff = ctx.diff_mode == DiffMode::Capture && metadata_differs;
+    checksum_needed_for_status(ctx, metadata_differs)
+        || need_checksum_for_ward
+        || need_checksum_for_diff
+}
+
+/// Whether a file with no ward entry gets checksummed: to build its ward
+/// entry (for an update or a captured diff), or for a checksum-backed
+/// fingerprint payload.
+fn added_file_needs_checksum(ctx: WalkContext<'_>) -> bool {
+    ctx.purpose == StatusPurpose::WardUpdate
+        || ctx.diff_mode == DiffMode::Capture
+        || ctx.policy != ChecksumPolicy::Never
+}
+
+/// Whether a file that replaced a non-file ward entry gets checksummed: to
+/// build its new ward entry, or for a checksum-backed fingerprint payload.
+fn type_change_needs_checksum(ctx: WalkContext<'_>) -> bool {
+    ctx.purpose == StatusPurpose::WardUpdate
+        || ctx.diff_mode == DiffMode::Capture
+        || ctx.policy != ChecksumPolicy::Never
+}
+
 /// Convert an mtime to the persisted `u64` nanos-since-epoch representation.
 ///
 /// Pre-1970 mtimes and mtimes above `i64::MAX` nanoseconds since the epoch
@@ -493,168 +1840,292 @@ fn mtime_to_nanos(mtime: &std::time::SystemTime, path: &Path) -> Result<u64, Sta
 }
 
 fn build_ward_entry_from_fs(
+    ctx: WalkContext<'_>,
     dir: &Path,
     name: &str,
     fs_entry: &FsEntry,
+    track: &Track,
+    file_checksum: Option<&FileChecksum>,
 ) -> Result<WardEntry, StatusError> {
+    let attributes = fs_entry.attributes().tracked(track);
     match fs_entry {
         FsEntry::File { .. } => {
-            let path = dir.join(name);
-            let checksum = checksum_file(&path)?;
+            let path = dir.join(names::to_os(name));
+            let file_checksum =
+                file_checksum.expect("a file whose ward entry is built is checksummed");
 
             Ok(WardEntry::File {
-                sha256: checksum.sha256,
-                mtime_nanos: mtime_to_nanos(&checksum.mtime, &path)?,
-                size: checksum.size,
+                checksum: checksum_for(file_checksum, ctx.hash),
+                mtime_nanos: mtime_to_nanos(&file_checksum.mtime, &path)?,
+                size: file_checksum.size,
+                attributes,
             })
         }
-        FsEntry::Dir { .. } => Ok(WardEntry::Dir {}),
+        FsEntry::Dir { .. } => Ok(WardEntry::Dir {
+            digest: None,
+            attributes,
+        }),
         FsEntry::Symlink { symlink_target, .. } => Ok(WardEntry::Symlink {
             symlink_target: symlink_target.clone(),
+            attributes,
+        }),
+        FsEntry::Special { kind, .. } => Ok(WardEntry::Special {
+            kind: *kind,
+            attributes,
         }),
     }
 }
 
-fn compare_entries(
+/// Classifies the entry `name` of `current_dir`, which is on the filesystem
+/// or in the ward or both, given the checksum `checksum_algorithms` asked
+/// for (or why it could not be taken). Under `keep_going`, a failure that
+/// only concerns the entry makes it an Error entry, as does `dir_error`, the
+/// failure of the subdirectory it is.
+#[allow(clippy::too_many_arguments)]
+fn classify_entry(
     ctx: WalkContext<'_>,
     current_dir: &Path,
-    ward_entries: &BTreeMap<String, WardEntry>,
-    fs_entries: &BTreeMap<String, FsEntry>,
+    name: &str,
+    ward_entry: Option<&WardEntry>,
+    fs_entry: Option<&FsEntry>,
+    dir_error: Option<EntryErrorKind>,
+    file_checksum: Result<Option<FileChecksum>, StatusError>,
+    recorded_track: &Track,
+    track: &Track,
     statuses: &mut Vec<StatusEntry>,
     fingerprint_records: &mut Vec<FingerprintRecord>,
 ) -> Result<(), StatusError> {
-    for (name, fs_entry) in fs_entries {
-        if !ward_entries.contains_key(name) {
-            let relative_path = make_relative_path(ctx.tree_root, current_dir, name)?;
-
-            let ward_entry = if ctx.purpose == StatusPurpose::WardUpdate {
-                Some(build_ward_entry_from_fs(current_dir, name, fs_entry)?)
-            } else {
-                None
-            };
-            let fingerprint_payload = current_entry_fingerprint_payload(
-                current_dir,
-                name,
-                fs_entry,
-                ward_entry.as_ref(),
-                ctx.policy,
-            )?;
+    let Some(fs_entry) = fs_entry else {
+        let ward_entry = ward_entry.expect("entry is on at least one side");
+        let relative_path = make_relative_path(ctx.tree_root, current_dir, name)?;
+        let old_ward_entry = (ctx.diff_mode == DiffMode::Capture).then(|| ward_entry.clone());
+        statuses.push(StatusEntry::Removed {
+            path: relative_path.clone(),
+            old_ward_entry,
+        });
+        fingerprint_records.push(FingerprintRecord {
+            path: relative_path,
+            status_type: StatusType::Removed,
+            payload: FingerprintPayload::Removed {
+                ward_entry: ward_entry.clone(),
+            },
+        });
+        return Ok(());
+    };
 
-            statuses.push(StatusEntry::Added {
-                path: relative_path.clone(),
-                ward_entry,
-            });
-            fingerprint_records.push(FingerprintRecord {
-                path: relative_path,
-                status_type: StatusType::Added,
-                payload: fingerprint_payload,
-            });
-        }
+    let error = match dir_error {
+        Some(kind) => Some(kind),
+        None => file_checksum
+            .and_then(|file_checksum| match ward_entry {
+                None => check_addition(
+                    ctx,
+                    current_dir,
+                    name,
+                    fs_entry,
+                    track,
+                    file_checksum.as_ref(),
+                    statuses,
+                    fingerprint_records,
+                ),
+                Some(ward_entry) => check_modification(
+                    ctx,
+                    current_dir,
+                    name,
+                    ward_entry,
+                    fs_entry,
+                    recorded_track,
+                    track,
+                    file_checksum.as_ref(),
+                    statuses,
+                    fingerprint_records,
+                ),
+            })
+            .err()
+            .map(|e| recoverable(ctx, e))
+            .transpose()?,
+    };
+    if let Some(kind) = error {
+        push_entry_error(
+            ctx,
+            current_dir,
+            name,
+            kind,
+            ward_entry,
+            statuses,
+            fingerprint_records,
+        )?;
     }
+    Ok(())
+}
+
+/// Classifies an entry that exists on the filesystem but not in the ward
+/// file as Added.
+#[allow(clippy::too_many_arguments)]
+fn check_addition(
+    ctx: WalkContext<'_>,
+    current_dir: &Path,
+    name: &str,
+    fs_entry: &FsEntry,
+    track: &Track,
+    file_checksum: Option<&FileChecksum>,
+    statuses: &mut Vec<StatusEntry>,
+    fingerprint_records: &mut Vec<FingerprintRecord>,
+) -> Result<(), StatusError> {
+    let relative_path = make_relative_path(ctx.tree_root, current_dir, name)?;
 
-    // Ordering between Removed and Modified entries does not matter here:
-    // compute_status sorts statuses and fingerprint records after the walk.
-    for (name, ward_entry) in ward_entries {
-        match fs_entries.get(name) {
-            Some(fs_entry) => check_modification(
+    let ward_entry =
+        if ctx.purpose == StatusPurpose::WardUpdate || ctx.diff_mode == DiffMode::Capture {
+            Some(build_ward_entry_from_fs(
                 ctx,
                 current_dir,
                 name,
-                ward_entry,
                 fs_entry,
-                statuses,
-                fingerprint_records,
-            )?,
-            None => {
-                let relative_path = make_relative_path(ctx.tree_root, current_dir, name)?;
-                let old_ward_entry =
-                    (ctx.diff_mode == DiffMode::Capture).then(|| ward_entry.clone());
-                statuses.push(StatusEntry::Removed {
-                    path: relative_path.clone(),
-                    old_ward_entry,
-                });
-                fingerprint_records.push(FingerprintRecord {
-                    path: relative_path,
-                    status_type: StatusType::Removed,
-                    payload: FingerprintPayload::Removed {
-                        ward_entry: ward_entry.clone(),
-                    },
-                });
-            }
-        }
+                track,
+                file_checksum,
+            )?)
+        } else {
+            None
+        };
+    let fingerprint_payload = current_entry_fingerprint_payload(
+        ctx,
+        current_dir,
+        name,
+        fs_entry,
+        ward_entry.as_ref(),
+        track,
+        file_checksum,
+    )?;
+
+    statuses.push(StatusEntry::Added {
+        path: relative_path.clone(),
+        ward_entry,
+    });
+    fingerprint_records.push(FingerprintRecord {
+        path: relative_path,
+        status_type: StatusType::Added,
+        payload: fingerprint_payload,
+    });
+    Ok(())
+}
+
+/// Under `keep_going`, the kind of a failure that only concerns the entry
+/// being classified (see `EntryErrorKind::of`), to be reported as an Error
+/// entry. Any other failure is passed on.
+///
+/// Classification pushes nothing for an entry before its last fallible step,
+/// so a failed entry never leaves a partial status behind.
+fn recoverable(ctx: WalkContext<'_>, error: StatusError) -> Result<EntryErrorKind, StatusError> {
+    match EntryErrorKind::of(&error) {
+        Some(kind) if ctx.keep_going => Ok(kind),
+        _ => Err(error),
     }
+}
 
+/// Reports an entry that could not be read. Under `WardUpdate`, the recorded
+/// `ward_entry` is carried forward so the update keeps it as it was.
+fn push_entry_error(
+    ctx: WalkContext<'_>,
+    current_dir: &Path,
+    name: &str,
+    kind: EntryErrorKind,
+    ward_entry: Option<&WardEntry>,
+    statuses: &mut Vec<StatusEntry>,
+    fingerprint_records: &mut Vec<FingerprintRecord>,
+) -> Result<(), StatusError> {
+    let relative_path = make_relative_path(ctx.tree_root, current_dir, name)?;
+    statuses.push(StatusEntry::Error {
+        path: relative_path.clone(),
+        kind,
+        ward_entry: ward_entry
+            .filter(|_| ctx.purpose == StatusPurpose::WardUpdate)
+            .cloned(),
+    });
+    fingerprint_records.push(FingerprintRecord {
+        path: relative_path,
+        status_type: StatusType::Error,
+        payload: FingerprintPayload::Error { kind },
+    });
     Ok(())
 }
 
 /// Compares a single entry that exists in both the ward file and filesystem.
 ///
-/// Determines whether the entry is Modified, PossiblyModified, or Unchanged by
-/// comparing the ward entry against the current filesystem state. For files,
-/// this involves metadata comparison and optionally checksumming based on policy.
-/// For directories and symlinks, comparison is simpler (dirs always match,
-/// symlinks compare targets).
+/// Determines whether the entry is Modified, PossiblyModified,
+/// PermissionsChanged, or Unchanged by comparing the ward entry against the
+/// current filesystem state. For files, this involves metadata comparison and
+/// optionally checksumming based on policy. For directories and symlinks,
+/// comparison is simpler (dirs always match, symlinks compare targets).
+/// Attributes are compared only as far as the ward entry records them. A
+/// changed extended attribute or hard link makes the entry Modified; a
+/// content change takes precedence over a permission change.
 ///
 /// Type changes (e.g., file becoming symlink) are always reported as Modified.
 ///
 /// Appends the resulting `StatusEntry` to `statuses` based on `mode` and `purpose`.
+#[allow(clippy::too_many_arguments)]
 fn check_modification(
     ctx: WalkContext<'_>,
     current_dir: &Path,
     name: &str,
     ward_entry: &WardEntry,
     fs_entry: &FsEntry,
+    recorded_track: &Track,
+    track: &Track,
+    file_checksum: Option<&FileChecksum>,
     statuses: &mut Vec<StatusEntry>,
     fingerprint_records: &mut Vec<FingerprintRecord>,
 ) -> Result<(), StatusError> {
     let relative_path = make_relative_path(ctx.tree_root, current_dir, name)?;
-    let absolute_path = current_dir.join(name);
+    let absolute_path = current_dir.join(names::to_os(name));
+
+    let current_attributes = fingerprint_attributes(fs_entry, ward_entry, recorded_track);
+    // Changed extended attributes and hard links are known without reading
+    // the content, so they are reported as a modification under any policy.
+    let xattrs_or_links_differ = current_attributes.xattrs != ward_entry.attributes().xattrs
+        || current_attributes.links != ward_entry.attributes().links;
+    let attributes_differ = current_attributes != *ward_entry.attributes();
+    let new_attributes = fs_entry.attributes().tracked(track);
 
     match (ward_entry, fs_entry) {
         (
             WardEntry::File {
-                sha256: ward_sha,
+                checksum: ward_checksum,
                 mtime_nanos: ward_mtime_nanos,
                 size: ward_size,
+                ..
             },
             FsEntry::File {
                 mtime: fs_mtime,
                 size: fs_size,
+                ..
             },
         ) => {
             let fs_mtime_nanos = mtime_to_nanos(fs_mtime, &absolute_path)?;
             let metadata_differs = fs_mtime_nanos != *ward_mtime_nanos || fs_size != ward_size;
 
-            let need_checksum_for_status = match ctx.policy {
-                ChecksumPolicy::Never => false,
-                ChecksumPolicy::WhenPossiblyModified => metadata_differs,
-                ChecksumPolicy::Always => true,
-            };
-            let need_checksum_for_ward =
-                ctx.purpose == StatusPurpose::WardUpdate && metadata_differs;
-            let need_checksum_for_diff = ctx.diff_mode == DiffMode::Capture && metadata_differs;
-            let need_checksum =
-                need_checksum_for_status || need_checksum_for_ward || need_checksum_for_diff;
-
-            let (sha256_differs, new_checksum) = if need_checksum {
-                let checksum = checksum_file(&absolute_path)?;
-                (checksum.sha256 != *ward_sha, Some(checksum))
-            } else {
-                (false, None)
-            };
+            let need_checksum_for_status = checksum_needed_for_status(ctx, metadata_differs);
+
+            // Content is always compared using the recorded algorithm.
+            let recorded_algorithm = ward_checksum.algorithm;
+            let new_checksum = file_checksum;
+            let checksum_differs = new_checksum.is_some_and(|file_checksum| {
+                checksum_for(file_checksum, recorded_algorithm) != *ward_checksum
+            });
 
             let new_ward_entry =
                 if ctx.purpose == StatusPurpose::WardUpdate || ctx.diff_mode == DiffMode::Capture {
                     Some(match &new_checksum {
                         Some(c) => WardEntry::File {
-                            sha256: c.sha256.clone(),
+                            checksum: checksum_for(c, new_entry_algorithm(ctx, recorded_algorithm)),
                             mtime_nanos: mtime_to_nanos(&c.mtime, &absolute_path)?,
                             size: c.size,
+                            attributes: new_attributes,
                         },
                         None => WardEntry::File {
-                            sha256: ward_sha.clone(),
+                            checksum: ward_checksum.clone(),
                             mtime_nanos: fs_mtime_nanos,
                             size: *fs_size,
+                            attributes: new_attributes,
                         },
                     })
                 } else {
@@ -664,47 +2135,53 @@ fn check_modification(
             // Capture old_ward_entry when diff mode is enabled and the entry differs
             // (either metadata or checksum - for --always-verify detecting silent corruption)
             let old_ward_entry = (ctx.diff_mode == DiffMode::Capture
-                && (metadata_differs || sha256_differs))
+                && (metadata_differs || checksum_differs || attributes_differ))
                 .then(|| ward_entry.clone());
 
             // Fingerprint should reflect file state at status-time, not just path/status.
             let fingerprint_payload = FingerprintPayload::File {
                 mtime_nanos: fs_mtime_nanos,
                 size: *fs_size,
-                sha256: if need_checksum_for_status {
+                checksum: if need_checksum_for_status {
                     // Include hash only when status policy was checksum-driven.
                     // This preserves fingerprint parity between `status` and `update`
-                    // when the same verify flags are used.
-                    new_checksum.as_ref().map(|c| c.sha256.clone())
+                    // when the same verify flags are used. It is always the
+                    // recorded algorithm's checksum, which both compute
+                    // regardless of `--hash`.
+                    new_checksum
+                        .as_ref()
+                        .map(|c| checksum_for(c, recorded_algorithm))
                 } else {
                     None
                 },
+                attributes: current_attributes,
             };
 
-            if metadata_differs && !need_checksum_for_status {
+            let status_type = if xattrs_or_links_differ {
+                StatusType::Modified
+            } else if metadata_differs && !need_checksum_for_status {
                 // Policy says don't checksum for status reporting, so report
                 // PossiblyModified regardless of whether we checksummed for ward
                 // building. This ensures fingerprint consistency between status
                 // and ward commands when using the same --verify/--always-verify flags.
-                statuses.push(StatusEntry::PossiblyModified {
-                    path: relative_path.clone(),
-                    ward_entry: new_ward_entry,
-                    old_ward_entry,
-                });
-                fingerprint_records.push(FingerprintRecord {
-                    path: relative_path,
-                    status_type: StatusType::PossiblyModified,
-                    payload: fingerprint_payload,
-                });
-            } else if sha256_differs {
-                statuses.push(StatusEntry::Modified {
-                    path: relative_path.clone(),
-                    ward_entry: new_ward_entry,
+                StatusType::PossiblyModified
+            } else if checksum_differs {
+                StatusType::Modified
+            } else if attributes_differ {
+                StatusType::PermissionsChanged
+            } else {
+                StatusType::Unchanged
+            };
+            if status_type != StatusType::Unchanged {
+                statuses.push(changed_entry(
+                    status_type,
+                    relative_path.clone(),
+                    new_ward_entry,
                     old_ward_entry,
-                });
+                ));
                 fingerprint_records.push(FingerprintRecord {
                     path: relative_path,
-                    status_type: StatusType::Modified,
+                    status_type,
                     payload: fingerprint_payload,
                 });
             } else if ctx.mode == StatusMode::All || ctx.purpose == StatusPurpose::WardUpdate {
@@ -714,10 +2191,38 @@ fn check_modification(
                 });
             }
         }
-        (WardEntry::Dir {}, FsEntry::Dir { .. }) => {
-            if ctx.mode == StatusMode::All || ctx.purpose == StatusPurpose::WardUpdate {
-                let new_ward_entry =
-                    (ctx.purpose == StatusPurpose::WardUpdate).then_some(WardEntry::Dir {});
+        (WardEntry::Dir { .. }, FsEntry::Dir { mtime, .. }) => {
+            let new_ward_entry = (ctx.purpose == StatusPurpose::WardUpdate
+                || ctx.diff_mode == DiffMode::Capture)
+                .then_some(WardEntry::Dir {
+                    digest: None,
+                    attributes: new_attributes,
+                });
+            let status_type = if xattrs_or_links_differ {
+                StatusType::Modified
+            } else if attributes_differ {
+                StatusType::PermissionsChanged
+            } else {
+                StatusType::Unchanged
+            };
+            if status_type != StatusType::Unchanged {
+                let old_ward_entry =
+                    (ctx.diff_mode == DiffMode::Capture).then(|| ward_entry.clone());
+                statuses.push(changed_entry(
+                    status_type,
+                    relative_path.clone(),
+                    new_ward_entry,
+                    old_ward_entry,
+                ));
+                fingerprint_records.push(FingerprintRecord {
+                    path: relative_path,
+                    status_type,
+                    payload: FingerprintPayload::Dir {
+                        mtime_nanos: mtime_to_nanos(mtime, &absolute_path)?,
+                        attributes: current_attributes,
+                    },
+                });
+            } else if ctx.mode == StatusMode::All || ctx.purpose == StatusPurpose::WardUpdate {
                 statuses.push(StatusEntry::Unchanged {
                     path: relative_path,
                     ward_entry: new_ward_entry,
@@ -727,34 +2232,97 @@ fn check_modification(
         (
             WardEntry::Symlink {
                 symlink_target: ward_target,
+                ..
             },
             FsEntry::Symlink {
                 symlink_target: fs_target,
+                ..
             },
         ) => {
             let new_ward_entry =
                 if ctx.purpose == StatusPurpose::WardUpdate || ctx.diff_mode == DiffMode::Capture {
                     Some(WardEntry::Symlink {
                         symlink_target: fs_target.clone(),
+                        attributes: new_attributes,
                     })
                 } else {
                     None
                 };
+            let fingerprint_payload = FingerprintPayload::Symlink {
+                symlink_target: fs_target.clone(),
+                attributes: current_attributes,
+            };
 
-            if ward_target != fs_target {
+            let status_type = if ward_target != fs_target || xattrs_or_links_differ {
+                StatusType::Modified
+            } else if attributes_differ {
+                StatusType::PermissionsChanged
+            } else {
+                StatusType::Unchanged
+            };
+            if status_type != StatusType::Unchanged {
                 let old_ward_entry =
                     (ctx.diff_mode == DiffMode::Capture).then(|| ward_entry.clone());
-                statuses.push(StatusEntry::Modified {
-                    path: relative_path.clone(),
-                    ward_entry: new_ward_entry,
+                statuses.push(changed_entry(
+                    status_type,
+                    relative_path.clone(),
+                    new_ward_entry,
                     old_ward_entry,
+                ));
+                fingerprint_records.push(FingerprintRecord {
+                    path: relative_path,
+                    status_type,
+                    payload: fingerprint_payload,
+                });
+            } else if ctx.mode == StatusMode::All || ctx.purpose == StatusPurpose::WardUpdate {
+                statuses.push(StatusEntry::Unchanged {
+                    path: relative_path,
+                    ward_entry: new_ward_entry,
                 });
+            }
+        }
+        (
+            WardEntry::Special {
+                kind: ward_kind, ..
+            },
+            FsEntry::Special { kind: fs_kind, .. },
+        ) => {
+            // Special files are never opened: their type and device number
+            // are all there is to compare.
+            let new_ward_entry =
+                if ctx.purpose == StatusPurpose::WardUpdate || ctx.diff_mode == DiffMode::Capture {
+                    Some(WardEntry::Special {
+                        kind: *fs_kind,
+                        attributes: new_attributes,
+                    })
+                } else {
+                    None
+                };
+            let fingerprint_payload = FingerprintPayload::Special {
+                kind: *fs_kind,
+                attributes: current_attributes,
+            };
+
+            let status_type = if ward_kind != fs_kind || xattrs_or_links_differ {
+                StatusType::Modified
+            } else if attributes_differ {
+                StatusType::PermissionsChanged
+            } else {
+                StatusType::Unchanged
+            };
+            if status_type != StatusType::Unchanged {
+                let old_ward_entry =
+                    (ctx.diff_mode == DiffMode::Capture).then(|| ward_entry.clone());
+                statuses.push(changed_entry(
+                    status_type,
+                    relative_path.clone(),
+                    new_ward_entry,
+                    old_ward_entry,
+                ));
                 fingerprint_records.push(FingerprintRecord {
                     path: relative_path,
-                    status_type: StatusType::Modified,
-                    payload: FingerprintPayload::Symlink {
-                        symlink_target: fs_target.clone(),
-                    },
+                    status_type,
+                    payload: fingerprint_payload,
                 });
             } else if ctx.mode == StatusMode::All || ctx.purpose == StatusPurpose::WardUpdate {
                 statuses.push(StatusEntry::Unchanged {
@@ -767,17 +2335,26 @@ fn check_modification(
             // Type change (e.g., file -> symlink)
             let new_ward_entry =
                 if ctx.purpose == StatusPurpose::WardUpdate || ctx.diff_mode == DiffMode::Capture {
-                    Some(build_ward_entry_from_fs(current_dir, name, fs_entry)?)
+                    Some(build_ward_entry_from_fs(
+                        ctx,
+                        current_dir,
+                        name,
+                        fs_entry,
+                        track,
+                        file_checksum,
+                    )?)
                 } else {
                     None
                 };
             let old_ward_entry = (ctx.diff_mode == DiffMode::Capture).then(|| ward_entry.clone());
             let fingerprint_payload = current_entry_fingerprint_payload(
+                ctx,
                 current_dir,
                 name,
                 fs_entry,
                 new_ward_entry.as_ref(),
-                ctx.policy,
+                track,
+                file_checksum,
             )?;
             statuses.push(StatusEntry::Modified {
                 path: relative_path.clone(),
@@ -795,34 +2372,291 @@ fn check_modification(
     Ok(())
 }
 
-/// Converts a path to a UTF-8 string, returning an error if the path contains
-/// non-UTF-8 bytes.
-///
-/// Non-UTF-8 paths are unsupported because handling them portably is complex:
-/// Unix paths are arbitrary byte sequences (except NUL), while Windows paths
-/// are UTF-16 (potentially unpaired surrogates). Using lossy conversion would
-/// risk fingerprint collisions where different invalid paths map to the same
-/// replacement characters. Platform-specific raw byte access (OsStrExt on Unix)
-/// would work but complicates cross-platform support. Since non-UTF-8 filenames
-/// are rare in practice, we require valid UTF-8 for now. If this is fixed in the
-/// future it must be done carefully and correctly (e.g. don't just use lossy conversion
-/// which would create potential collisions).
-fn path_to_str(path: &Path) -> Result<&str, StatusError> {
-    path.to_str()
-        .ok_or_else(|| StatusError::Other(format!("non-UTF-8 path not supported: {:?}", path)))
+/// Current values of the attributes `ward_entry` records, which are the ones
+/// compared and fingerprinted for an entry in both the ward and the
+/// filesystem. Depending only on the ward and the filesystem (not on
+/// `--track`) keeps such entries' fingerprints independent of the option.
+fn fingerprint_attributes(
+    fs_entry: &FsEntry,
+    ward_entry: &WardEntry,
+    recorded_track: &Track,
+) -> Attributes {
+    fs_entry
+        .attributes()
+        .matching(ward_entry.attributes(), recorded_track)
+}
+
+/// The status entry of a changed entry that exists in both the ward and the
+/// filesystem under the same type.
+fn changed_entry(
+    status_type: StatusType,
+    path: String,
+    ward_entry: Option<WardEntry>,
+    old_ward_entry: Option<WardEntry>,
+) -> StatusEntry {
+    match status_type {
+        StatusType::PossiblyModified => StatusEntry::PossiblyModified {
+            path,
+            ward_entry,
+            old_ward_entry,
+        },
+        StatusType::Modified => StatusEntry::Modified {
+            path,
+            ward_entry,
+            old_ward_entry,
+        },
+        StatusType::PermissionsChanged => StatusEntry::PermissionsChanged {
+            path,
+            ward_entry,
+            old_ward_entry,
+        },
+        _ => unreachable!("{status_type:?} is not a change of an existing entry"),
+    }
 }
 
 /// Constructs a relative path string from tree_root, current_dir, and entry name.
 ///
-/// Returns the path as a validated UTF-8 String suitable for use in StatusEntry.
+/// Returns the path in the string form of `util::names`, suitable for use in
+/// StatusEntry. It fails only on platforms that cannot represent the path.
 fn make_relative_path(
     tree_root: &Path,
     current_dir: &Path,
     name: &str,
 ) -> Result<String, StatusError> {
     let relative_dir = current_dir.strip_prefix(tree_root)?;
-    let relative_path = relative_dir.join(name);
-    path_to_str(&relative_path).map(|s| s.to_string())
+    let relative_path = relative_dir.join(names::to_os(name));
+    names::from_os(relative_path.as_os_str())
+        .map(Cow::into_owned)
+        .ok_or_else(|| {
+            StatusError::Other(format!("non-UTF-8 path not supported: {:?}", relative_path))
+        })
+}
+
+/// Replaces each `Removed` + `Added` pair that is one object moved to a new
+/// path with a single `Renamed` entry, in both `statuses` and `records`.
+///
+/// - A removed file pairs with an added file whose content matches the
+///   removed file's recorded checksum (computed with the recorded algorithm).
+///   Every added file with the size of some removed file is checksummed for
+///   this, even under `ChecksumPolicy::Never`, unless classification already
+///   took the checksum needed; they are checksummed in batches like the
+///   walk's.
+/// - A removed directory pairs with an added directory of the same name that
+///   has its own `.treeward` (a warded subtree moved as a unit), provided no
+///   other removed or added directory has that name. The moved subtree's
+///   contents are compared against the ward that moved with it, like those of
+///   any added directory.
+///
+/// Candidates pair in path order, so pairing among identical files is
+/// deterministic and the fingerprint agrees between `status` and `update`.
+/// Pairing never changes what an update writes: a `Renamed` entry carries
+/// the same ward data as the `Added` and `Removed` entries it replaces.
+fn pair_renames(
+    ctx: WalkContext<'_>,
+    warded_dirs: &HashSet<PathBuf>,
+    dir_ids: &HashMap<PathBuf, DirId>,
+    statuses: &mut Vec<StatusEntry>,
+    records: &mut Vec<FingerprintRecord>,
+) -> Result<(), StatusError> {
+    let mut removed: Vec<(&str, &WardEntry)> = records
+        .iter()
+        .filter_map(|record| match &record.payload {
+            FingerprintPayload::Removed { ward_entry } => Some((record.path.as_str(), ward_entry)),
+            _ => None,
+        })
+        .collect();
+    removed.sort_by_key(|(path, _)| *path);
+    let mut added: Vec<(&str, &FingerprintPayload)> = records
+        .iter()
+        .filter(|record| record.status_type == StatusType::Added)
+        .map(|record| (record.path.as_str(), &record.payload))
+        .collect();
+    added.sort_by_key(|(path, _)| *path);
+
+    // (from, to)
+    let mut pairs: Vec<(String, String)> = Vec::new();
+
+    // Added files with removed files of their size, with the checksum
+    // classification took, if any.
+    let mut sized = Vec::new();
+    for (path, payload) in &added {
+        let FingerprintPayload::File { size, checksum, .. } = payload else {
+            continue;
+        };
+        let candidates: Vec<(&str, &Checksum)> = removed
+            .iter()
+            .filter_map(|(from, entry)| match entry {
+                WardEntry::File {
+                    checksum,
+                    size: removed_size,
+                    ..
+                } if removed_size == size => Some((*from, checksum)),
+                _ => None,
+            })
+            .collect();
+        if !candidates.is_empty() {
+            sized.push((path, checksum.as_ref(), candidates));
+        }
+    }
+
+    let mut paired_sources = HashSet::new();
+    for batch in sized.chunks(CLASSIFY_BATCH) {
+        let algorithms: Vec<Vec<HashAlgorithm>> = batch
+            .iter()
+            .map(|(_, known, candidates)| {
+                let algorithms: BTreeSet<HashAlgorithm> = candidates
+                    .iter()
+                    .map(|(_, checksum)| checksum.algorithm)
+                    .collect();
+                match known {
+                    Some(known) if algorithms.iter().all(|a| *a == known.algorithm) => Vec::new(),
+                    _ => algorithms.into_iter().collect(),
+                }
+            })
+            .collect();
+        let requests: Vec<ChecksumRequest> = batch
+            .iter()
+            .zip(&algorithms)
+            .filter(|(_, algorithms)| !algorithms.is_empty())
+            .map(|((path, _, _), algorithms)| {
+                let path = ctx.tree_root.join(names::to_os(path));
+                let walked_dir = path.parent().and_then(|dir| dir_ids.get(dir)).copied();
+                ChecksumRequest {
+                    path,
+                    algorithms: algorithms.clone(),
+                    walked_dir,
+                }
+            })
+            .collect();
+        let mut results = checksum_batch(ctx, &requests).into_iter();
+
+        for ((path, known, candidates), algorithms) in batch.iter().zip(&algorithms) {
+            let current: Vec<Checksum> = if algorithms.is_empty() {
+                vec![
+                    (*known)
+                        .expect("only a known checksum is not retaken")
+                        .clone(),
+                ]
+            } else {
+                match results.next().flatten() {
+                    Some(Ok(file_checksum)) => algorithms
+                        .iter()
+                        .map(|algorithm| checksum_for(&file_checksum, *algorithm))
+                        .collect(),
+                    // An added file that can no longer be read is left unpaired.
+                    Some(Err(e)) => {
+                        recoverable(ctx, e.into())?;
+                        continue;
+                    }
+                    None => unreachable!("pairing stops at the failure that ended checksumming"),
+                }
+            };
+            let source = candidates.iter().find(|(from, checksum)| {
+                !paired_sources.contains(from) && current.contains(checksum)
+            });
+            if let Some((from, _)) = source {
+                paired_sources.insert(*from);
+                pairs.push((from.to_string(), path.to_string()));
+            }
+        }
+    }
+
+    let mut removed_dirs: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for (path, entry) in &removed {
+        if matches!(entry, WardEntry::Dir { .. }) {
+            removed_dirs.entry(file_name(path)).or_default().push(path);
+        }
+    }
+    let mut added_dirs: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for (path, payload) in &added {
+        if matches!(payload, FingerprintPayload::Dir { .. })
+            && warded_dirs.contains(ctx.tree_root.join(names::to_os(path)).as_path())
+        {
+            added_dirs.entry(file_name(path)).or_default().push(path);
+        }
+    }
+    for (name, sources) in &removed_dirs {
+        if let (&[from], Some(&[to])) =
+            (sources.as_slice(), added_dirs.get(name).map(Vec::as_slice))
+        {
+            pairs.push((from.to_string(), to.to_string()));
+        }
+    }
+
+    if pairs.is_empty() {
+        return Ok(());
+    }
+
+    let sources: HashSet<&str> = pairs.iter().map(|(from, _)| from.as_str()).collect();
+    let targets: HashSet<&str> = pairs.iter().map(|(_, to)| to.as_str()).collect();
+
+    let mut old_ward_entries = HashMap::new();
+    let mut new_ward_entries = HashMap::new();
+    let mut kept = Vec::with_capacity(statuses.len());
+    for status in std::mem::take(statuses) {
+        match status {
+            StatusEntry::Removed {
+                path,
+                old_ward_entry,
+            } if sources.contains(path.as_str()) => {
+                old_ward_entries.insert(path, old_ward_entry);
+            }
+            StatusEntry::Added { path, ward_entry } if targets.contains(path.as_str()) => {
+                new_ward_entries.insert(path, ward_entry);
+            }
+            other => kept.push(other),
+        }
+    }
+
+    let mut removed_payloads = HashMap::new();
+    let mut added_payloads = HashMap::new();
+    let mut kept_records = Vec::with_capacity(records.len());
+    for record in std::mem::take(records) {
+        match record.status_type {
+            StatusType::Removed if sources.contains(record.path.as_str()) => {
+                removed_payloads.insert(record.path, record.payload);
+            }
+            StatusType::Added if targets.contains(record.path.as_str()) => {
+                added_payloads.insert(record.path, record.payload);
+            }
+            _ => kept_records.push(record),
+        }
+    }
+
+    for (from, to) in pairs {
+        kept.push(StatusEntry::Renamed {
+            path: to.clone(),
+            from: from.clone(),
+            ward_entry: new_ward_entries.remove(&to).flatten(),
+            old_ward_entry: old_ward_entries.remove(&from).flatten(),
+        });
+        kept_records.push(FingerprintRecord {
+            path: to.clone(),
+            status_type: StatusType::Renamed,
+            payload: FingerprintPayload::Renamed {
+                removed: Box::new(
+                    removed_payloads
+                        .remove(&from)
+                        .expect("rename source has a Removed record"),
+                ),
+                added: Box::new(
+                    added_payloads
+                        .remove(&to)
+                        .expect("rename target has an Added record"),
+                ),
+                from,
+            },
+        });
+    }
+
+    *statuses = kept;
+    *records = kept_records;
+    Ok(())
+}
+
+/// Last component of a relative status path.
+fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
 }
 
 /// Builds fingerprint material for a filesystem-side entry with no comparable file ward state.
@@ -830,49 +2664,65 @@ fn make_relative_path(
 /// Added entries and type changes are already interesting without a checksum, but
 /// checksum-based policies still need file content in the fingerprint. Otherwise
 /// a file could be reviewed under `--verify` or `--always-verify`, changed while
-/// preserving size and mtime, and then accepted by `update --fingerprint`.
+/// preserving size and mtime, and then accepted by `update --fingerprint`. The
+/// same goes for the attributes the new entry records under `track`.
 fn current_entry_fingerprint_payload(
+    ctx: WalkContext<'_>,
     current_dir: &Path,
     name: &str,
     fs_entry: &FsEntry,
     ward_entry: Option<&WardEntry>,
-    policy: ChecksumPolicy,
+    track: &Track,
+    file_checksum: Option<&FileChecksum>,
 ) -> Result<FingerprintPayload, StatusError> {
-    let path = current_dir.join(name);
-    let file_sha256 = if policy != ChecksumPolicy::Never {
+    let path = current_dir.join(names::to_os(name));
+    let file_checksum = if ctx.policy != ChecksumPolicy::Never {
         match (fs_entry, ward_entry) {
-            (
-                FsEntry::File { .. },
-                Some(WardEntry::File {
-                    sha256: ward_sha, ..
-                }),
-            ) => Some(ward_sha.clone()),
-            (FsEntry::File { .. }, _) => Some(checksum_file(&path)?.sha256),
+            (FsEntry::File { .. }, Some(WardEntry::File { checksum, .. })) => {
+                Some(checksum.clone())
+            }
+            (FsEntry::File { .. }, _) => Some(checksum_for(
+                file_checksum.expect("a file fingerprinted by content is checksummed"),
+                ctx.hash,
+            )),
             _ => None,
         }
     } else {
         None
     };
 
-    fingerprint_payload_from_fs_entry(fs_entry, file_sha256, &path)
+    fingerprint_payload_from_fs_entry(
+        fs_entry,
+        file_checksum,
+        fs_entry.attributes().tracked(track),
+        &path,
+    )
 }
 
 fn fingerprint_payload_from_fs_entry(
     fs_entry: &FsEntry,
-    file_sha256: Option<String>,
+    file_checksum: Option<Checksum>,
+    attributes: Attributes,
     path: &Path,
 ) -> Result<FingerprintPayload, StatusError> {
     match fs_entry {
-        FsEntry::File { mtime, size } => Ok(FingerprintPayload::File {
+        FsEntry::File { mtime, size, .. } => Ok(FingerprintPayload::File {
             mtime_nanos: mtime_to_nanos(mtime, path)?,
             size: *size,
-            sha256: file_sha256,
+            checksum: file_checksum,
+            attributes,
         }),
-        FsEntry::Dir { mtime } => Ok(FingerprintPayload::Dir {
+        FsEntry::Dir { mtime, .. } => Ok(FingerprintPayload::Dir {
             mtime_nanos: mtime_to_nanos(mtime, path)?,
+            attributes,
         }),
-        FsEntry::Symlink { symlink_target } => Ok(FingerprintPayload::Symlink {
+        FsEntry::Symlink { symlink_target, .. } => Ok(FingerprintPayload::Symlink {
             symlink_target: symlink_target.clone(),
+            attributes,
+        }),
+        FsEntry::Special { kind, .. } => Ok(FingerprintPayload::Special {
+            kind: *kind,
+            attributes,
         }),
     }
 }
@@ -884,6 +2734,9 @@ pub(crate) fn status_type_code(status_type: StatusType) -> &'static str {
         StatusType::Removed => "R",
         StatusType::PossiblyModified => "M?",
         StatusType::Modified => "M",
+        StatusType::Renamed => "R>",
+        StatusType::PermissionsChanged => "P",
+        StatusType::Error => "E",
         StatusType::Unchanged => ".",
     }
 }
@@ -898,48 +2751,132 @@ fn hash_fingerprint_payload(hasher: &mut Sha256, payload: &FingerprintPayload) {
         FingerprintPayload::File {
             mtime_nanos,
             size,
-            sha256,
+            checksum,
+            attributes,
         } => {
             hasher.update(b"file");
             hashing::hash_u64_field(hasher, *mtime_nanos);
             hashing::hash_u64_field(hasher, *size);
-            match sha256 {
-                Some(sha) => {
+            match checksum {
+                Some(checksum) => {
                     hasher.update([1u8]);
-                    hashing::hash_field(hasher, sha.as_bytes());
+                    hashing::hash_field(hasher, checksum.to_string().as_bytes());
                 }
                 None => {
                     hasher.update([0u8]);
                 }
             }
+            hash_attributes(hasher, attributes);
         }
-        FingerprintPayload::Dir { mtime_nanos } => {
+        FingerprintPayload::Dir {
+            mtime_nanos,
+            attributes,
+        } => {
             hasher.update(b"dir");
             hashing::hash_u64_field(hasher, *mtime_nanos);
+            hash_attributes(hasher, attributes);
         }
-        FingerprintPayload::Symlink { symlink_target } => {
+        FingerprintPayload::Symlink {
+            symlink_target,
+            attributes,
+        } => {
             hasher.update(b"symlink");
             hashing::hash_path_field(hasher, symlink_target);
+            hash_attributes(hasher, attributes);
         }
-        FingerprintPayload::Removed { ward_entry } => match ward_entry {
-            WardEntry::File {
-                sha256,
-                mtime_nanos,
-                size,
-            } => {
-                hasher.update(b"removed_file");
-                hashing::hash_field(hasher, sha256.as_bytes());
-                hashing::hash_u64_field(hasher, *mtime_nanos);
-                hashing::hash_u64_field(hasher, *size);
+        FingerprintPayload::Special { kind, attributes } => {
+            hasher.update(b"special");
+            hash_special_kind(hasher, kind);
+            hash_attributes(hasher, attributes);
+        }
+        FingerprintPayload::Removed { ward_entry } => {
+            match ward_entry {
+                WardEntry::File {
+                    checksum,
+                    mtime_nanos,
+                    size,
+                    ..
+                } => {
+                    hasher.update(b"removed_file");
+                    hashing::hash_field(hasher, checksum.to_string().as_bytes());
+                    hashing::hash_u64_field(hasher, *mtime_nanos);
+                    hashing::hash_u64_field(hasher, *size);
+                }
+                WardEntry::Dir { .. } => {
+                    hasher.update(b"removed_dir");
+                }
+                WardEntry::Symlink { symlink_target, .. } => {
+                    hasher.update(b"removed_symlink");
+                    hashing::hash_path_field(hasher, symlink_target);
+                }
+                WardEntry::Special { kind, .. } => {
+                    hasher.update(b"removed_special");
+                    hash_special_kind(hasher, kind);
+                }
             }
-            WardEntry::Dir {} => {
-                hasher.update(b"removed_dir");
+            hash_attributes(hasher, ward_entry.attributes());
+        }
+        FingerprintPayload::Renamed {
+            from,
+            removed,
+            added,
+        } => {
+            hasher.update(b"renamed");
+            hashing::hash_path_field(hasher, Path::new(&names::to_os(from)));
+            hash_fingerprint_payload(hasher, removed);
+            hash_fingerprint_payload(hasher, added);
+        }
+        FingerprintPayload::Error { kind } => {
+            hasher.update(b"error");
+            hashing::hash_field(hasher, kind.as_str().as_bytes());
+        }
+    }
+}
+
+/// Hashes the type and, for device nodes, the device number of a special file.
+fn hash_special_kind(hasher: &mut Sha256, kind: &SpecialKind) {
+    hashing::hash_field(hasher, kind.type_name().as_bytes());
+    if let Some((major, minor)) = kind.device() {
+        hashing::hash_u64_field(hasher, major.into());
+        hashing::hash_u64_field(hasher, minor.into());
+    }
+}
+
+/// Hashes the attributes part of a payload. Nothing is hashed when no
+/// attribute is tracked, so fingerprints of trees without attribute tracking
+/// are unaffected by it.
+fn hash_attributes(hasher: &mut Sha256, attributes: &Attributes) {
+    if attributes.is_empty() {
+        return;
+    }
+    hasher.update(b"attributes");
+    for value in [attributes.mode, attributes.uid, attributes.gid] {
+        match value {
+            Some(value) => {
+                hasher.update([1u8]);
+                hashing::hash_u64_field(hasher, u64::from(value));
             }
-            WardEntry::Symlink { symlink_target } => {
-                hasher.update(b"removed_symlink");
-                hashing::hash_path_field(hasher, symlink_target);
+            None => hasher.update([0u8]),
+        }
+    }
+    match &attributes.xattrs {
+        Some(xattrs) => {
+            hasher.update([1u8]);
+            hashing::hash_u64_field(hasher, xattrs.len() as u64);
+            for (name, digest) in xattrs {
+                hashing::hash_field(hasher, name.as_bytes());
+                hashing::hash_field(hasher, digest.to_string().as_bytes());
             }
-        },
+        }
+        None => hasher.update([0u8]),
+    }
+    match &attributes.links {
+        Some(links) => {
+            hasher.update([1u8]);
+            hashing::hash_u64_field(hasher, links.nlink);
+            hashing::hash_field(hasher, links.group.as_deref().unwrap_or("").as_bytes());
+        }
+        None => hasher.update([0u8]),
     }
 }
 
@@ -947,15 +2884,32 @@ fn hash_fingerprint_payload(hasher: &mut Sha256, payload: &FingerprintPayload) {
 ///
 /// Unchanged entries are intentionally excluded because fingerprints are used to
 /// guard the reviewed change set for init/update acceptance.
-fn compute_fingerprint(records: &[FingerprintRecord]) -> String {
+fn compute_fingerprint(
+    records: &[FingerprintRecord],
+    digest_mismatches: &[(String, DigestMismatch)],
+) -> String {
     let mut hasher = Sha256::new();
 
     for record in records {
-        hashing::hash_field(&mut hasher, record.path.as_bytes());
+        hashing::hash_path_field(&mut hasher, Path::new(&names::to_os(&record.path)));
         hashing::hash_field(&mut hasher, status_type_code(record.status_type).as_bytes());
         hash_fingerprint_payload(&mut hasher, &record.payload);
     }
 
+    // After all records, and only when present, so fingerprints of trees
+    // without mismatches are unaffected.
+    for (path, mismatch) in digest_mismatches {
+        hasher.update(b"digest_mismatch");
+        hashing::hash_path_field(&mut hasher, Path::new(&names::to_os(path)));
+        match mismatch {
+            DigestMismatch::Missing => hasher.update(b"missing"),
+            DigestMismatch::Content(actual) => {
+                hasher.update(b"content");
+                hashing::hash_field(&mut hasher, actual.to_string().as_bytes());
+            }
+        }
+    }
+
     let hash_bytes = hasher.finalize();
     base64::engine::general_purpose::STANDARD.encode(hash_bytes)
 }
@@ -968,7 +2922,10 @@ fn compute_fingerprint(records: &[FingerprintRecord]) -> String {
 /// This function expects the StatusResult to come from `compute_status()` called
 /// with `StatusPurpose::WardUpdate`, which ensures all entries (except `Removed`)
 /// have complete ward_entry data. `Removed` entries are intentionally skipped
-/// since they should not appear in the new ward files.
+/// since they should not appear in the new ward files. An `Error` entry keeps
+/// its recorded ward entry (or stays unrecorded if it has none); for an
+/// unreadable directory, no ward file is built inside it. Entries left out
+/// by pathspecs (`out_of_scope`) are kept as recorded.
 ///
 /// This function also ensures that all directories that exist on the filesystem
 /// get .treeward files, even if they are empty (have no child entries).
@@ -994,8 +2951,10 @@ pub fn build_ward_files(
     for entry in &status_result.statuses {
         match entry.ward_entry() {
             Some(ward_entry) => {
+                // Split in string form; only the joined paths are OS paths.
                 let entry_path = Path::new(entry.path());
-                let parent_dir = root.join(entry_path.parent().unwrap_or(Path::new("")));
+                let parent = entry_path.parent().and_then(Path::to_str).unwrap_or("");
+                let parent_dir = root.join(names::to_os(parent));
                 let filename = entry_path
                     .file_name()
                     .ok_or_else(|| {
@@ -1005,7 +2964,7 @@ pub fn build_ward_files(
                         )))
                     })?
                     .to_str()
-                    .expect("path is already validated as UTF-8")
+                    .expect("string form of a path is UTF-8")
                     .to_string();
 
                 dir_entries
@@ -1013,13 +2972,18 @@ pub fn build_ward_files(
                     .or_default()
                     .insert(filename, ward_entry.clone());
 
-                if matches!(ward_entry, WardEntry::Dir {}) {
-                    let dir_path = root.join(entry_path);
+                if matches!(ward_entry, WardEntry::Dir { .. })
+                    && !matches!(entry, StatusEntry::Error { .. })
+                {
+                    let dir_path = root.join(names::to_os(entry.path()));
                     dir_entries.entry(dir_path).or_default();
                 }
             }
             None => {
-                if !matches!(entry, StatusEntry::Removed { .. }) {
+                if !matches!(
+                    entry,
+                    StatusEntry::Removed { .. } | StatusEntry::Error { .. }
+                ) {
                     return Err(StatusError::Other(format!(
                         "missing ward_entry for non-Removed status: {}",
                         entry.path()
@@ -1029,10 +2993,36 @@ pub fn build_ward_files(
         }
     }
 
-    Ok(dir_entries
+    for (dir, entries) in &status_result.out_of_scope {
+        dir_entries.entry(root.join(dir)).or_default().extend(
+            entries
+                .iter()
+                .map(|(name, entry)| (name.clone(), entry.clone())),
+        );
+    }
+
+    dir_entries
         .into_iter()
-        .map(|(path, entries)| (path, WardFile::new(entries)))
-        .collect())
+        .map(|(path, entries)| {
+            let relative = path.strip_prefix(root)?;
+            let track = status_result
+                .tracks
+                .get(relative)
+                .cloned()
+                .unwrap_or_default();
+            let protection = status_result
+                .protections
+                .get(relative)
+                .copied()
+                .unwrap_or_default();
+            Ok((
+                path,
+                WardFile::new(entries)
+                    .with_track(track)
+                    .with_protection(protection),
+            ))
+        })
+        .collect()
 }
 
 #[cfg(test)]
@@ -0,0 +1,390 @@
+use super::*;
+use crate::attributes::{Links, XattrSelection};
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+
+const MODE_AND_OWNER: Track = Track {
+    mode: true,
+    owner: true,
+    acl: false,
+    xattrs: XattrSelection::None,
+    links: false,
+};
+
+/// Ward options that record `track` from now on.
+fn tracking(track: Track) -> WardOptions {
+    WardOptions {
+        track: Some(track),
+        ..ward_options()
+    }
+}
+
+#[cfg(unix)]
+fn chmod(path: &Path, mode: u32) {
+    fs::set_permissions(path, fs::Permissions::from_mode(mode)).unwrap();
+}
+
+fn tree() -> TempDir {
+    let temp = TempDir::new().unwrap();
+    fs::create_dir(temp.path().join("sub")).unwrap();
+    fs::write(temp.path().join("sub/tool"), "binary").unwrap();
+    temp
+}
+
+#[test]
+#[cfg(unix)]
+fn test_mode_change_is_permissions_changed() {
+    let temp = tree();
+    chmod(&temp.path().join("sub/tool"), 0o755);
+    ward_tree(temp.path(), tracking(MODE_AND_OWNER));
+
+    chmod(&temp.path().join("sub/tool"), 0o4755);
+    let result = compute_status(
+        temp.path(),
+        &StatusOptions {
+            diff_mode: DiffMode::Capture,
+            ..StatusOptions::default()
+        },
+    )
+    .unwrap();
+
+    assert_eq!(result.statuses.len(), 1, "{:?}", result.statuses);
+    let StatusEntry::PermissionsChanged {
+        path,
+        ward_entry: Some(new),
+        old_ward_entry: Some(old),
+    } = &result.statuses[0]
+    else {
+        panic!("expected PermissionsChanged, got {:?}", result.statuses[0]);
+    };
+    assert_eq!(path, "sub/tool");
+    assert_eq!(old.attributes().mode, Some(0o755));
+    assert_eq!(new.attributes().mode, Some(0o4755));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_directory_mode_change_is_permissions_changed() {
+    let temp = tree();
+    ward_tree(temp.path(), tracking(MODE_AND_OWNER));
+
+    chmod(&temp.path().join("sub"), 0o700);
+    let result = compute_status(temp.path(), &StatusOptions::default()).unwrap();
+
+    assert_eq!(result.statuses.len(), 1, "{:?}", result.statuses);
+    assert_eq!(result.statuses[0].path(), "sub");
+    assert_eq!(
+        result.statuses[0].status_type(),
+        StatusType::PermissionsChanged
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn test_untracked_mode_change_is_invisible() {
+    let temp = tree();
+    ward_tree(temp.path(), ward_options());
+
+    chmod(&temp.path().join("sub/tool"), 0o4755);
+    let result = compute_status(temp.path(), &StatusOptions::default()).unwrap();
+
+    assert!(result.statuses.is_empty(), "{:?}", result.statuses);
+}
+
+#[test]
+#[cfg(unix)]
+fn test_content_change_takes_precedence_over_mode_change() {
+    let temp = tree();
+    ward_tree(temp.path(), tracking(MODE_AND_OWNER));
+
+    fs::write(temp.path().join("sub/tool"), "patched").unwrap();
+    chmod(&temp.path().join("sub/tool"), 0o4755);
+    let result = compute_status(
+        temp.path(),
+        &StatusOptions {
+            policy: ChecksumPolicy::WhenPossiblyModified,
+            ..StatusOptions::default()
+        },
+    )
+    .unwrap();
+
+    let tool = result
+        .statuses
+        .iter()
+        .find(|status| status.path() == "sub/tool")
+        .unwrap();
+    assert_eq!(tool.status_type(), StatusType::Modified);
+}
+
+#[test]
+#[cfg(unix)]
+fn test_fingerprint_covers_the_current_mode() {
+    let temp = tree();
+    chmod(&temp.path().join("sub/tool"), 0o755);
+    ward_tree(temp.path(), tracking(MODE_AND_OWNER));
+
+    chmod(&temp.path().join("sub/tool"), 0o4755);
+    let setuid = compute_status(temp.path(), &StatusOptions::default()).unwrap();
+    chmod(&temp.path().join("sub/tool"), 0o2755);
+    let setgid = compute_status(temp.path(), &StatusOptions::default()).unwrap();
+
+    assert_eq!(setuid.statuses, setgid.statuses);
+    assert_ne!(setuid.fingerprint, setgid.fingerprint);
+}
+
+#[test]
+fn test_new_directories_inherit_tracking() {
+    let temp = tree();
+    ward_tree(temp.path(), tracking(MODE_AND_OWNER));
+
+    fs::create_dir(temp.path().join("sub/new")).unwrap();
+    fs::write(temp.path().join("sub/new/file"), "new").unwrap();
+    ward_tree(temp.path(), ward_options());
+
+    let ward_file = WardFile::load(&temp.path().join("sub/new/.treeward")).unwrap();
+    assert_eq!(*ward_file.track(), MODE_AND_OWNER);
+    #[cfg(unix)]
+    assert!(ward_file.entries["file"].attributes().mode.is_some());
+}
+
+#[test]
+fn test_track_none_stops_recording_attributes() {
+    let temp = tree();
+    ward_tree(temp.path(), tracking(MODE_AND_OWNER));
+
+    ward_tree(temp.path(), tracking(Track::default()));
+
+    let ward_file = WardFile::load(&temp.path().join("sub/.treeward")).unwrap();
+    assert!(ward_file.track().is_empty());
+    assert!(ward_file.entries["tool"].attributes().is_empty());
+}
+
+#[test]
+fn test_untracked_tree_fingerprint_is_unaffected_by_track_option() {
+    let temp = tree();
+    create_ward_file(temp.path(), BTreeMap::new());
+
+    let without = compute_status(temp.path(), &StatusOptions::default()).unwrap();
+    let with_none = compute_status(
+        temp.path(),
+        &StatusOptions {
+            track: Some(&Track::default()),
+            ..StatusOptions::default()
+        },
+    )
+    .unwrap();
+
+    assert_eq!(without.fingerprint, with_none.fingerprint);
+}
+
+/// Sets a `user.` extended attribute, or returns false when the filesystem
+/// backing the temp dir does not support them.
+#[cfg(target_os = "linux")]
+fn set_xattr(path: &Path, name: &str, value: &str) -> bool {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes()).unwrap();
+    let name = CString::new(name).unwrap();
+    // SAFETY: all pointers are valid for the given lengths.
+    let rc = unsafe {
+        libc::setxattr(
+            path.as_ptr(),
+            name.as_ptr(),
+            value.as_ptr().cast(),
+            value.len(),
+            0,
+        )
+    };
+    rc == 0
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_xattr_change_is_modified() {
+    let temp = tree();
+    let tool = temp.path().join("sub/tool");
+    if !set_xattr(&tool, "user.tag", "one") {
+        return;
+    }
+    ward_tree(temp.path(), tracking("xattr:user.tag".parse().unwrap()));
+
+    set_xattr(&tool, "user.tag", "two");
+    set_xattr(&tool, "user.other", "ignored");
+    let result = compute_status(
+        temp.path(),
+        &StatusOptions {
+            diff_mode: DiffMode::Capture,
+            ..StatusOptions::default()
+        },
+    )
+    .unwrap();
+
+    assert_eq!(result.statuses.len(), 1, "{:?}", result.statuses);
+    let StatusEntry::Modified {
+        path,
+        ward_entry: Some(new),
+        old_ward_entry: Some(old),
+    } = &result.statuses[0]
+    else {
+        panic!("expected Modified, got {:?}", result.statuses[0]);
+    };
+    assert_eq!(path, "sub/tool");
+    let names = |entry: &WardEntry| -> Vec<String> {
+        entry
+            .attributes()
+            .xattrs
+            .as_ref()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect()
+    };
+    assert_eq!(names(old), ["user.tag"]);
+    assert_eq!(names(new), ["user.tag"]);
+    assert_ne!(old.attributes().xattrs, new.attributes().xattrs);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_added_xattr_is_modified_when_tracking_all() {
+    let temp = tree();
+    ward_tree(temp.path(), tracking("xattr".parse().unwrap()));
+
+    if !set_xattr(&temp.path().join("sub"), "user.new", "value") {
+        return;
+    }
+    let result = compute_status(temp.path(), &StatusOptions::default()).unwrap();
+
+    assert_eq!(result.statuses.len(), 1, "{:?}", result.statuses);
+    assert_eq!(result.statuses[0].path(), "sub");
+    assert_eq!(result.statuses[0].status_type(), StatusType::Modified);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_fingerprint_covers_xattr_values() {
+    let temp = tree();
+    let tool = temp.path().join("sub/tool");
+    if !set_xattr(&tool, "user.tag", "one") {
+        return;
+    }
+    ward_tree(temp.path(), tracking("xattr".parse().unwrap()));
+
+    set_xattr(&tool, "user.tag", "two");
+    let two = compute_status(temp.path(), &StatusOptions::default()).unwrap();
+    set_xattr(&tool, "user.tag", "three");
+    let three = compute_status(temp.path(), &StatusOptions::default()).unwrap();
+
+    assert_eq!(two.statuses, three.statuses);
+    assert_ne!(two.fingerprint, three.fingerprint);
+}
+
+#[cfg(unix)]
+fn links_of(root: &Path, path: &str) -> Option<Links> {
+    let (dir, name) = path.rsplit_once('/').unwrap();
+    let ward_file = WardFile::load(&root.join(dir).join(".treeward")).unwrap();
+    ward_file.entries[name].attributes().links.clone()
+}
+
+/// `a/f` and `b/g` are hard links of each other; `a/single` is not linked.
+#[cfg(unix)]
+fn linked_tree() -> TempDir {
+    let temp = TempDir::new().unwrap();
+    fs::create_dir(temp.path().join("a")).unwrap();
+    fs::create_dir(temp.path().join("b")).unwrap();
+    fs::write(temp.path().join("a/f"), "shared").unwrap();
+    fs::write(temp.path().join("a/single"), "single").unwrap();
+    fs::hard_link(temp.path().join("a/f"), temp.path().join("b/g")).unwrap();
+    ward_tree(temp.path(), tracking("links".parse().unwrap()));
+    temp
+}
+
+#[test]
+#[cfg(unix)]
+fn test_hard_links_share_a_link_group_across_directories() {
+    let temp = linked_tree();
+
+    let f = links_of(temp.path(), "a/f").unwrap();
+    let g = links_of(temp.path(), "b/g").unwrap();
+    assert_eq!(f.nlink, 2);
+    assert!(f.group.is_some());
+    assert_eq!(f, g);
+    assert_eq!(
+        links_of(temp.path(), "a/single"),
+        Some(Links {
+            nlink: 1,
+            group: None
+        })
+    );
+
+    let result = compute_status(temp.path(), &StatusOptions::default()).unwrap();
+    assert!(result.statuses.is_empty(), "{:?}", result.statuses);
+}
+
+#[test]
+#[cfg(unix)]
+fn test_split_link_set_is_modified() {
+    let temp = linked_tree();
+
+    let g = temp.path().join("b/g");
+    fs::remove_file(&g).unwrap();
+    fs::copy(temp.path().join("a/f"), &g).unwrap();
+    let result = compute_status(temp.path(), &StatusOptions::default()).unwrap();
+
+    let modified: Vec<&str> = result
+        .statuses
+        .iter()
+        .filter(|status| status.status_type() == StatusType::Modified)
+        .map(|status| status.path())
+        .collect();
+    assert_eq!(modified, ["a/f", "b/g"], "{:?}", result.statuses);
+}
+
+#[test]
+#[cfg(unix)]
+fn test_new_link_outside_the_tree_is_modified() {
+    let temp = linked_tree();
+    let outside = TempDir::new_in(temp.path().parent().unwrap()).unwrap();
+
+    fs::hard_link(temp.path().join("a/single"), outside.path().join("x")).unwrap();
+    let result = compute_status(
+        temp.path(),
+        &StatusOptions {
+            diff_mode: DiffMode::Capture,
+            ..StatusOptions::default()
+        },
+    )
+    .unwrap();
+
+    assert_eq!(result.statuses.len(), 1, "{:?}", result.statuses);
+    let StatusEntry::Modified {
+        path,
+        ward_entry: Some(new),
+        ..
+    } = &result.statuses[0]
+    else {
+        panic!("expected Modified, got {:?}", result.statuses[0]);
+    };
+    assert_eq!(path, "a/single");
+    let links = new.attributes().links.as_ref().unwrap();
+    assert_eq!(links.nlink, 2);
+    assert!(links.group.is_some());
+}
+
+#[test]
+#[cfg(unix)]
+fn test_link_groups_are_kept_by_later_updates() {
+    let temp = linked_tree();
+    let before = links_of(temp.path(), "a/f");
+
+    fs::write(temp.path().join("b/new"), "new").unwrap();
+    fs::hard_link(temp.path().join("b/new"), temp.path().join("a/new")).unwrap();
+    ward_tree(temp.path(), ward_options());
+
+    assert_eq!(links_of(temp.path(), "a/f"), before);
+    assert_eq!(links_of(temp.path(), "b/g"), before);
+    let new = links_of(temp.path(), "a/new").unwrap();
+    assert_eq!(links_of(temp.path(), "b/new").unwrap(), new);
+    assert_ne!(new.group, before.unwrap().group);
+}
@@ -9,8 +9,8 @@ fn test_no_changes() {
     fs::create_dir(root.join("dir1")).unwrap();
     fs::write(root.join("dir1/file2.txt"), "content2").unwrap();
 
-    let checksum1 = checksum_file(&root.join("file1.txt")).unwrap();
-    let checksum2 = checksum_file(&root.join("dir1/file2.txt")).unwrap();
+    let checksum1 = checksum_file(&root.join("file1.txt"), &[HashAlgorithm::Sha256]).unwrap();
+    let checksum2 = checksum_file(&root.join("dir1/file2.txt"), &[HashAlgorithm::Sha256]).unwrap();
 
     let metadata1 = std::fs::metadata(root.join("file1.txt")).unwrap();
     let metadata2 = std::fs::metadata(root.join("dir1/file2.txt")).unwrap();
@@ -19,7 +19,7 @@ fn test_no_changes() {
     root_entries.insert(
         "file1.txt".to_string(),
         WardEntry::File {
-            sha256: checksum1.sha256.clone(),
+            checksum: checksum1.checksums[0].clone(),
             mtime_nanos: metadata1
                 .modified()
                 .unwrap()
@@ -27,16 +27,23 @@ fn test_no_changes() {
                 .unwrap()
                 .as_nanos() as u64,
             size: metadata1.len(),
+            attributes: Attributes::default(),
+        },
+    );
+    root_entries.insert(
+        "dir1".to_string(),
+        WardEntry::Dir {
+            digest: None,
+            attributes: Attributes::default(),
         },
     );
-    root_entries.insert("dir1".to_string(), WardEntry::Dir {});
     create_ward_file(root, root_entries);
 
     let mut dir1_entries = BTreeMap::new();
     dir1_entries.insert(
         "file2.txt".to_string(),
         WardEntry::File {
-            sha256: checksum2.sha256.clone(),
+            checksum: checksum2.checksums[0].clone(),
             mtime_nanos: metadata2
                 .modified()
                 .unwrap()
@@ -44,26 +51,33 @@ fn test_no_changes() {
                 .unwrap()
                 .as_nanos() as u64,
             size: metadata2.len(),
+            attributes: Attributes::default(),
         },
     );
     create_ward_file(&root.join("dir1"), dir1_entries);
 
     let result = compute_status(
         root,
-        ChecksumPolicy::Never,
-        StatusMode::Interesting,
-        StatusPurpose::Display,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::Never,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     )
     .unwrap();
     assert_eq!(result.statuses.len(), 0);
 
     let result2 = compute_status(
         root,
-        ChecksumPolicy::Never,
-        StatusMode::Interesting,
-        StatusPurpose::Display,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::Never,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     )
     .unwrap();
     assert_eq!(result.fingerprint, result2.fingerprint);
@@ -80,10 +94,13 @@ fn test_added_files() {
 
     let result = compute_status(
         root,
-        ChecksumPolicy::Never,
-        StatusMode::Interesting,
-        StatusPurpose::Display,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::Never,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     )
     .unwrap();
     assert_eq!(result.statuses.len(), 1);
@@ -103,10 +120,13 @@ fn test_added_directory() {
 
     let result = compute_status(
         root,
-        ChecksumPolicy::Never,
-        StatusMode::Interesting,
-        StatusPurpose::Display,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::Never,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     )
     .unwrap();
     assert_eq!(result.statuses.len(), 2);
@@ -129,19 +149,26 @@ fn test_removed_files() {
     entries.insert(
         "file1.txt".to_string(),
         WardEntry::File {
-            sha256: "abc123abc123abc123abc123abc123abc123abc123abc123abc123abc123abc1".to_string(),
+            checksum: Checksum::new(
+                HashAlgorithm::Sha256,
+                "abc123abc123abc123abc123abc123abc123abc123abc123abc123abc123abc1",
+            ),
             mtime_nanos: 1000,
             size: 100,
+            attributes: Attributes::default(),
         },
     );
     create_ward_file(root, entries);
 
     let result = compute_status(
         root,
-        ChecksumPolicy::Never,
-        StatusMode::Interesting,
-        StatusPurpose::Display,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::Never,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     )
     .unwrap();
     assert_eq!(result.statuses.len(), 1);
@@ -157,16 +184,26 @@ fn test_removed_directory() {
     fs::create_dir(root.join("dir1")).unwrap();
 
     let mut root_entries = BTreeMap::new();
-    root_entries.insert("dir1".to_string(), WardEntry::Dir {});
+    root_entries.insert(
+        "dir1".to_string(),
+        WardEntry::Dir {
+            digest: None,
+            attributes: Attributes::default(),
+        },
+    );
     create_ward_file(root, root_entries);
 
     let mut dir1_entries = BTreeMap::new();
     dir1_entries.insert(
         "file1.txt".to_string(),
         WardEntry::File {
-            sha256: "abc123abc123abc123abc123abc123abc123abc123abc123abc123abc123abc1".to_string(),
+            checksum: Checksum::new(
+                HashAlgorithm::Sha256,
+                "abc123abc123abc123abc123abc123abc123abc123abc123abc123abc123abc1",
+            ),
             mtime_nanos: 1000,
             size: 100,
+            attributes: Attributes::default(),
         },
     );
     create_ward_file(&root.join("dir1"), dir1_entries);
@@ -175,10 +212,13 @@ fn test_removed_directory() {
 
     let result = compute_status(
         root,
-        ChecksumPolicy::Never,
-        StatusMode::Interesting,
-        StatusPurpose::Display,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::Never,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     )
     .unwrap();
     assert_eq!(result.statuses.len(), 1);
@@ -193,26 +233,30 @@ fn test_modified_file_without_verify() {
     let root = temp.path();
 
     fs::write(root.join("file1.txt"), "content1").unwrap();
-    let checksum = checksum_file(&root.join("file1.txt")).unwrap();
+    let checksum = checksum_file(&root.join("file1.txt"), &[HashAlgorithm::Sha256]).unwrap();
     let metadata = std::fs::metadata(root.join("file1.txt")).unwrap();
 
     let mut entries = BTreeMap::new();
     entries.insert(
         "file1.txt".to_string(),
         WardEntry::File {
-            sha256: checksum.sha256,
+            checksum: checksum.checksums[0].clone(),
             mtime_nanos: 1000,
             size: metadata.len(),
+            attributes: Attributes::default(),
         },
     );
     create_ward_file(root, entries);
 
     let result = compute_status(
         root,
-        ChecksumPolicy::Never,
-        StatusMode::Interesting,
-        StatusPurpose::Display,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::Never,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     )
     .unwrap();
     assert_eq!(result.statuses.len(), 1);
@@ -234,19 +278,26 @@ fn test_modified_file_with_verify_changed() {
     entries.insert(
         "file1.txt".to_string(),
         WardEntry::File {
-            sha256: "baadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaad".to_string(),
+            checksum: Checksum::new(
+                HashAlgorithm::Sha256,
+                "baadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaad",
+            ),
             mtime_nanos: 1000,
             size: 8,
+            attributes: Attributes::default(),
         },
     );
     create_ward_file(root, entries);
 
     let result = compute_status(
         root,
-        ChecksumPolicy::WhenPossiblyModified,
-        StatusMode::Interesting,
-        StatusPurpose::Display,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::WhenPossiblyModified,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     )
     .unwrap();
     assert_eq!(result.statuses.len(), 1);
@@ -260,26 +311,30 @@ fn test_modified_file_with_verify_unchanged() {
     let root = temp.path();
 
     fs::write(root.join("file1.txt"), "content1").unwrap();
-    let checksum = checksum_file(&root.join("file1.txt")).unwrap();
+    let checksum = checksum_file(&root.join("file1.txt"), &[HashAlgorithm::Sha256]).unwrap();
     let metadata = std::fs::metadata(root.join("file1.txt")).unwrap();
 
     let mut entries = BTreeMap::new();
     entries.insert(
         "file1.txt".to_string(),
         WardEntry::File {
-            sha256: checksum.sha256,
+            checksum: checksum.checksums[0].clone(),
             mtime_nanos: 1000,
             size: metadata.len(),
+            attributes: Attributes::default(),
         },
     );
     create_ward_file(root, entries);
 
     let result = compute_status(
         root,
-        ChecksumPolicy::WhenPossiblyModified,
-        StatusMode::Interesting,
-        StatusPurpose::Display,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::WhenPossiblyModified,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     )
     .unwrap();
     assert_eq!(result.statuses.len(), 0);
@@ -293,34 +348,42 @@ fn test_mixed_changes() {
     fs::write(root.join("file1.txt"), "content1").unwrap();
     fs::write(root.join("file4.txt"), "new file").unwrap();
 
-    let checksum1 = checksum_file(&root.join("file1.txt")).unwrap();
+    let checksum1 = checksum_file(&root.join("file1.txt"), &[HashAlgorithm::Sha256]).unwrap();
     let metadata1 = std::fs::metadata(root.join("file1.txt")).unwrap();
 
     let mut entries = BTreeMap::new();
     entries.insert(
         "file1.txt".to_string(),
         WardEntry::File {
-            sha256: checksum1.sha256,
+            checksum: checksum1.checksums[0].clone(),
             mtime_nanos: 1000,
             size: metadata1.len(),
+            attributes: Attributes::default(),
         },
     );
     entries.insert(
         "file2.txt".to_string(),
         WardEntry::File {
-            sha256: "abcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabca".to_string(),
+            checksum: Checksum::new(
+                HashAlgorithm::Sha256,
+                "abcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabca",
+            ),
             mtime_nanos: 1000,
             size: 100,
+            attributes: Attributes::default(),
         },
     );
     create_ward_file(root, entries);
 
     let result = compute_status(
         root,
-        ChecksumPolicy::Never,
-        StatusMode::Interesting,
-        StatusPurpose::Display,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::Never,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     )
     .unwrap();
     assert_eq!(result.statuses.len(), 3);
@@ -352,18 +415,40 @@ fn test_nested_directories() {
     fs::create_dir(root.join("dir1/dir2/dir3")).unwrap();
     fs::write(root.join("dir1/dir2/dir3/file.txt"), "content").unwrap();
 
-    let checksum = checksum_file(&root.join("dir1/dir2/dir3/file.txt")).unwrap();
+    let checksum = checksum_file(
+        &root.join("dir1/dir2/dir3/file.txt"),
+        &[HashAlgorithm::Sha256],
+    )
+    .unwrap();
 
     let mut root_entries = BTreeMap::new();
-    root_entries.insert("dir1".to_string(), WardEntry::Dir {});
+    root_entries.insert(
+        "dir1".to_string(),
+        WardEntry::Dir {
+            digest: None,
+            attributes: Attributes::default(),
+        },
+    );
     create_ward_file(root, root_entries);
 
     let mut dir1_entries = BTreeMap::new();
-    dir1_entries.insert("dir2".to_string(), WardEntry::Dir {});
+    dir1_entries.insert(
+        "dir2".to_string(),
+        WardEntry::Dir {
+            digest: None,
+            attributes: Attributes::default(),
+        },
+    );
     create_ward_file(&root.join("dir1"), dir1_entries);
 
     let mut dir2_entries = BTreeMap::new();
-    dir2_entries.insert("dir3".to_string(), WardEntry::Dir {});
+    dir2_entries.insert(
+        "dir3".to_string(),
+        WardEntry::Dir {
+            digest: None,
+            attributes: Attributes::default(),
+        },
+    );
     create_ward_file(&root.join("dir1/dir2"), dir2_entries);
 
     let metadata = std::fs::metadata(root.join("dir1/dir2/dir3/file.txt")).unwrap();
@@ -372,19 +457,23 @@ fn test_nested_directories() {
     dir3_entries.insert(
         "file.txt".to_string(),
         WardEntry::File {
-            sha256: checksum.sha256,
+            checksum: checksum.checksums[0].clone(),
             mtime_nanos: 1000,
             size: metadata.len(),
+            attributes: Attributes::default(),
         },
     );
     create_ward_file(&root.join("dir1/dir2/dir3"), dir3_entries);
 
     let result = compute_status(
         root,
-        ChecksumPolicy::Never,
-        StatusMode::Interesting,
-        StatusPurpose::Display,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::Never,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     )
     .unwrap();
 
@@ -410,10 +499,13 @@ fn test_uninitialized_directory() {
 
     let result = compute_status(
         root,
-        ChecksumPolicy::Never,
-        StatusMode::Interesting,
-        StatusPurpose::Display,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::Never,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     )
     .unwrap();
     assert_eq!(result.statuses.len(), 3);
@@ -433,10 +525,13 @@ fn test_malformed_ward_file_is_error() {
 
     let result = compute_status(
         root,
-        ChecksumPolicy::Never,
-        StatusMode::Interesting,
-        StatusPurpose::Display,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::Never,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     );
 
     assert!(result.is_err());
@@ -0,0 +1,80 @@
+use super::*;
+
+fn tree_with_subdirectory() -> TempDir {
+    let temp = TempDir::new().unwrap();
+    fs::create_dir_all(temp.path().join("sub/inner")).unwrap();
+    fs::write(temp.path().join("sub/file.txt"), "content").unwrap();
+    ward_tree(temp.path(), ward_options());
+    temp
+}
+
+#[test]
+fn test_intact_chain_has_no_mismatches() {
+    let temp = tree_with_subdirectory();
+
+    let result = compute_status(temp.path(), &StatusOptions::default()).unwrap();
+
+    assert!(result.digest_mismatches.is_empty());
+    assert!(result.statuses.is_empty());
+}
+
+#[test]
+fn test_edited_ward_file_is_a_mismatch() {
+    let temp = tree_with_subdirectory();
+    let ward_path = temp.path().join("sub/inner/.treeward");
+    let content = fs::read_to_string(&ward_path).unwrap();
+    fs::write(&ward_path, format!("{content}\n")).unwrap();
+
+    let result = compute_status(temp.path(), &StatusOptions::default()).unwrap();
+
+    assert_eq!(result.digest_mismatches, vec!["sub/inner/.treeward"]);
+    // The edit does not change what the ward says, so nothing else differs.
+    assert!(result.statuses.is_empty());
+}
+
+#[test]
+fn test_deleted_ward_file_is_a_mismatch() {
+    let temp = tree_with_subdirectory();
+    fs::remove_file(temp.path().join("sub/.treeward")).unwrap();
+
+    let result = compute_status(temp.path(), &StatusOptions::default()).unwrap();
+
+    assert_eq!(result.digest_mismatches, vec!["sub/.treeward"]);
+}
+
+#[test]
+fn test_mismatch_changes_fingerprint() {
+    let temp = tree_with_subdirectory();
+    fs::write(temp.path().join("new.txt"), "new").unwrap();
+    let before = compute_status(temp.path(), &StatusOptions::default()).unwrap();
+
+    let ward_path = temp.path().join("sub/.treeward");
+    let content = fs::read_to_string(&ward_path).unwrap();
+    fs::write(&ward_path, format!("{content}\n")).unwrap();
+    let after = compute_status(temp.path(), &StatusOptions::default()).unwrap();
+
+    assert_eq!(before.statuses, after.statuses);
+    assert_ne!(before.fingerprint, after.fingerprint);
+}
+
+#[test]
+fn test_directory_entry_without_digest_is_not_checked() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path();
+    fs::create_dir(root.join("sub")).unwrap();
+    create_ward_file(
+        root,
+        BTreeMap::from([(
+            "sub".to_string(),
+            WardEntry::Dir {
+                digest: None,
+                attributes: Attributes::default(),
+            },
+        )]),
+    );
+    create_ward_file(&root.join("sub"), BTreeMap::new());
+
+    let result = compute_status(root, &StatusOptions::default()).unwrap();
+
+    assert!(result.digest_mismatches.is_empty());
+}
@@ -0,0 +1,194 @@
+use super::*;
+
+#[test]
+fn test_ignored_entries_are_not_reported() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path();
+
+    fs::write(root.join(".treewardignore"), "*.swp\n/cache/\n").unwrap();
+    fs::write(root.join("keep.txt"), "keep").unwrap();
+    fs::write(root.join("keep.txt.swp"), "swap").unwrap();
+    fs::create_dir(root.join("cache")).unwrap();
+    fs::write(root.join("cache/blob"), "blob").unwrap();
+    fs::create_dir(root.join("sub")).unwrap();
+    fs::write(root.join("sub/deep.swp"), "swap").unwrap();
+    // Anchored: only the top-level cache directory is ignored.
+    fs::create_dir(root.join("sub/cache")).unwrap();
+
+    let result = compute_status(root, &StatusOptions::default()).unwrap();
+
+    assert_eq!(
+        all_paths(&result),
+        vec![
+            (".treewardignore", StatusType::Added),
+            ("keep.txt", StatusType::Added),
+            ("sub", StatusType::Added),
+            ("sub/cache", StatusType::Added),
+        ]
+    );
+    assert_eq!(
+        result.ignored,
+        vec!["cache", "keep.txt.swp", "sub/deep.swp"]
+    );
+}
+
+#[test]
+fn test_negation_in_subdirectory_overrides_parent() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path();
+
+    fs::write(root.join(".treewardignore"), "*.log\n").unwrap();
+    fs::create_dir(root.join("sub")).unwrap();
+    fs::write(root.join("sub/.treewardignore"), "!important.log\n").unwrap();
+    fs::write(root.join("sub/important.log"), "keep").unwrap();
+    fs::write(root.join("sub/noise.log"), "drop").unwrap();
+
+    let result = compute_status(root, &StatusOptions::default()).unwrap();
+
+    let paths: Vec<&str> = result.statuses.iter().map(|s| s.path()).collect();
+    assert_eq!(
+        paths,
+        vec![
+            ".treewardignore",
+            "sub",
+            "sub/.treewardignore",
+            "sub/important.log"
+        ]
+    );
+    assert_eq!(result.ignored, vec!["sub/noise.log"]);
+}
+
+#[test]
+fn test_newly_ignored_ward_entry_is_reported_removed_and_not_kept() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path();
+
+    fs::write(root.join("data.txt"), "data").unwrap();
+    fs::write(root.join("scratch.tmp"), "scratch").unwrap();
+    fs::create_dir(root.join("gone")).unwrap();
+    fs::create_dir(root.join("build")).unwrap();
+    fs::write(root.join("build/out.o"), "object").unwrap();
+    let ward = build_ward_files(
+        root,
+        &compute_status(
+            root,
+            &StatusOptions {
+                purpose: StatusPurpose::WardUpdate,
+                mode: StatusMode::All,
+                ..StatusOptions::default()
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    for (dir, ward_file) in ward {
+        ward_file.save(&dir.join(".treeward")).unwrap();
+    }
+
+    // Ignore a file and a directory that are still there, and a directory
+    // that was removed. The ignored directory's own ward file is left in
+    // place and not descended into.
+    fs::remove_dir_all(root.join("gone")).unwrap();
+    fs::write(root.join(".treewardignore"), "*.tmp\ngone/\nbuild/\n").unwrap();
+
+    let status = compute_status(root, &StatusOptions::default()).unwrap();
+    assert_eq!(
+        all_paths(&status),
+        vec![
+            (".treewardignore", StatusType::Added),
+            ("build", StatusType::Removed),
+            ("gone", StatusType::Removed),
+            ("scratch.tmp", StatusType::Removed),
+        ]
+    );
+    assert_eq!(status.ignored, vec!["build", "scratch.tmp"]);
+
+    let update = compute_status(
+        root,
+        &StatusOptions {
+            purpose: StatusPurpose::WardUpdate,
+            mode: StatusMode::All,
+            ..StatusOptions::default()
+        },
+    )
+    .unwrap();
+    let ward_files = build_ward_files(root, &update).unwrap();
+    let root_ward = &ward_files[&root.canonicalize().unwrap()];
+    assert_eq!(
+        root_ward.entries.keys().collect::<Vec<_>>(),
+        vec![".treewardignore", "data.txt"]
+    );
+}
+
+#[test]
+fn test_ignore_file_is_warded_even_when_a_pattern_matches_it() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path();
+
+    fs::write(root.join(".treewardignore"), ".*\n").unwrap();
+    fs::write(root.join(".hidden"), "hidden").unwrap();
+    fs::write(root.join("data.txt"), "data").unwrap();
+
+    let result = compute_status(root, &StatusOptions::default()).unwrap();
+
+    assert_eq!(
+        all_paths(&result),
+        vec![
+            (".treewardignore", StatusType::Added),
+            ("data.txt", StatusType::Added),
+        ]
+    );
+    assert_eq!(result.ignored, vec![".hidden"]);
+}
+
+#[test]
+fn test_ignored_entries_do_not_affect_fingerprint() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path();
+
+    fs::write(root.join(".treewardignore"), "*.tmp\n").unwrap();
+    fs::write(root.join("data.txt"), "data").unwrap();
+    let before = compute_status(root, &StatusOptions::default()).unwrap();
+
+    fs::write(root.join("new.tmp"), "noise").unwrap();
+    let after = compute_status(root, &StatusOptions::default()).unwrap();
+
+    assert_eq!(before.fingerprint, after.fingerprint);
+    assert_eq!(after.ignored, vec!["new.tmp"]);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_symlinked_ignore_file_is_not_read() {
+    let temp = TempDir::new().unwrap();
+    let outside = TempDir::new().unwrap();
+    let root = temp.path();
+
+    fs::write(outside.path().join("rules"), "*\n").unwrap();
+    unix::fs::symlink(outside.path().join("rules"), root.join(".treewardignore")).unwrap();
+    fs::write(root.join("data.txt"), "data").unwrap();
+
+    let result = compute_status(root, &StatusOptions::default()).unwrap();
+
+    assert_eq!(
+        all_paths(&result),
+        vec![
+            (".treewardignore", StatusType::Added),
+            ("data.txt", StatusType::Added),
+        ]
+    );
+    assert!(result.ignored.is_empty());
+}
+
+#[test]
+fn test_invalid_ignore_pattern_is_fatal() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join(".treewardignore"), "a[z-a]\n").unwrap();
+
+    let result = compute_status(temp.path(), &StatusOptions::default());
+
+    assert!(matches!(
+        result,
+        Err(StatusError::Ignore(IgnoreError::InvalidPattern(..)))
+    ));
+}
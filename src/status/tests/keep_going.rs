@@ -0,0 +1,207 @@
+use super::*;
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+
+/// Makes `path` unreadable, or returns false when permissions are not
+/// enforced (running as root), in which case the caller skips the test.
+#[cfg(unix)]
+fn make_unreadable(path: &Path) -> bool {
+    fs::set_permissions(path, fs::Permissions::from_mode(0o000)).unwrap();
+    if fs::read_dir(path).is_ok() || fs::read(path).is_ok() {
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+        return false;
+    }
+    true
+}
+
+#[test]
+fn test_entry_error_kind_of_recoverable_errors() {
+    let path = PathBuf::from("x");
+    assert_eq!(
+        EntryErrorKind::of(&StatusError::Checksum(ChecksumError::PermissionDenied(
+            path.clone()
+        ))),
+        Some(EntryErrorKind::PermissionDenied)
+    );
+    assert_eq!(
+        EntryErrorKind::of(&StatusError::DirList(DirListError::EntryVanished(
+            path.clone()
+        ))),
+        Some(EntryErrorKind::Vanished)
+    );
+    assert_eq!(
+        EntryErrorKind::of(&StatusError::Checksum(
+            ChecksumError::ConcurrentModification(path.clone())
+        )),
+        Some(EntryErrorKind::ConcurrentModification)
+    );
+    assert_eq!(
+        EntryErrorKind::of(&StatusError::DirList(DirListError::DirectoryReplaced(
+            path.clone()
+        ))),
+        None
+    );
+    assert_eq!(
+        EntryErrorKind::of(&StatusError::Other("fingerprint mismatch".to_string())),
+        None
+    );
+}
+
+#[test]
+fn test_build_ward_files_keeps_errored_entries() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path();
+    let recorded = WardEntry::File {
+        checksum: Checksum::new(HashAlgorithm::Sha256, "a".repeat(64)),
+        mtime_nanos: 1000,
+        size: 3,
+        attributes: Attributes::default(),
+    };
+    let result = StatusResult {
+        statuses: vec![
+            StatusEntry::Error {
+                path: "locked.txt".to_string(),
+                kind: EntryErrorKind::PermissionDenied,
+                ward_entry: Some(recorded.clone()),
+            },
+            StatusEntry::Error {
+                path: "new.txt".to_string(),
+                kind: EntryErrorKind::Vanished,
+                ward_entry: None,
+            },
+        ],
+        ignored: Vec::new(),
+        digest_mismatches: Vec::new(),
+        tracks: BTreeMap::new(),
+        protections: BTreeMap::new(),
+        fec: Vec::new(),
+        out_of_scope: BTreeMap::new(),
+        scope: crate::pathspec::Scope::All,
+        dir_ids: BTreeMap::new(),
+        fingerprint: String::new(),
+    };
+
+    let ward_files = build_ward_files(root, &result).unwrap();
+    let entries = &ward_files[root].entries;
+    assert_eq!(entries.get("locked.txt"), Some(&recorded));
+    assert!(!entries.contains_key("new.txt"));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_unreadable_file_is_error_and_walk_continues() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path();
+    fs::write(root.join("locked.txt"), "secret").unwrap();
+    fs::write(root.join("other.txt"), "fine").unwrap();
+    ward_tree(root, ward_options());
+
+    fs::write(root.join("locked.txt"), "changed").unwrap();
+    fs::write(root.join("added.txt"), "new").unwrap();
+    if !make_unreadable(&root.join("locked.txt")) {
+        return;
+    }
+
+    let options = StatusOptions {
+        policy: ChecksumPolicy::Always,
+        keep_going: true,
+        ..StatusOptions::default()
+    };
+    let result = compute_status(root, &options);
+    let strict = compute_status(
+        root,
+        &StatusOptions {
+            keep_going: false,
+            ..options
+        },
+    );
+    fs::set_permissions(root.join("locked.txt"), fs::Permissions::from_mode(0o644)).unwrap();
+
+    assert!(strict.is_err());
+    let statuses = result.unwrap().statuses;
+    assert!(statuses.contains(&StatusEntry::Error {
+        path: "locked.txt".to_string(),
+        kind: EntryErrorKind::PermissionDenied,
+        ward_entry: None,
+    }));
+    assert!(
+        statuses
+            .iter()
+            .any(|s| s.path() == "added.txt" && s.status_type() == StatusType::Added)
+    );
+}
+
+/// Without `keep_going` the walk fails on the first failure in walk order,
+/// however many files are checksummed at once: an unreadable file beats a
+/// broken ward file in a directory walked after it.
+#[test]
+#[cfg(unix)]
+fn test_strict_walk_fails_on_the_first_failure_in_walk_order() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path();
+    fs::write(root.join("a.txt"), "first").unwrap();
+    fs::create_dir(root.join("z")).unwrap();
+    fs::write(root.join("z/file.txt"), "later").unwrap();
+    ward_tree(root, ward_options());
+
+    fs::write(root.join("z/.treeward"), "not a ward file").unwrap();
+    if !make_unreadable(&root.join("a.txt")) {
+        return;
+    }
+    let result = compute_status(
+        root,
+        &StatusOptions {
+            policy: ChecksumPolicy::Always,
+            jobs: NonZeroUsize::new(4).unwrap(),
+            ..StatusOptions::default()
+        },
+    );
+    fs::set_permissions(root.join("a.txt"), fs::Permissions::from_mode(0o644)).unwrap();
+
+    assert!(matches!(
+        result,
+        Err(StatusError::Checksum(ChecksumError::PermissionDenied(path))) if path == root.join("a.txt")
+    ));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_update_keeps_recorded_state_of_unreadable_directory() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path();
+    fs::create_dir(root.join("locked")).unwrap();
+    fs::write(root.join("locked/file.txt"), "content").unwrap();
+    ward_tree(root, ward_options());
+    let recorded_root = WardFile::load(&root.join(".treeward")).unwrap();
+    let recorded_locked = fs::read(root.join("locked/.treeward")).unwrap();
+
+    fs::write(root.join("added.txt"), "new").unwrap();
+    if !make_unreadable(&root.join("locked")) {
+        return;
+    }
+    let result = ward_directory(
+        root,
+        WardOptions {
+            checksum_policy: ChecksumPolicy::Always,
+            keep_going: true,
+            ..ward_options()
+        },
+    );
+    fs::set_permissions(root.join("locked"), fs::Permissions::from_mode(0o755)).unwrap();
+
+    let result = result.unwrap();
+    assert_eq!(
+        result.errors,
+        vec![("locked".to_string(), EntryErrorKind::PermissionDenied)]
+    );
+    let updated_root = WardFile::load(&root.join(".treeward")).unwrap();
+    assert_eq!(
+        updated_root.entries.get("locked"),
+        recorded_root.entries.get("locked")
+    );
+    assert!(updated_root.entries.contains_key("added.txt"));
+    assert_eq!(
+        fs::read(root.join("locked/.treeward")).unwrap(),
+        recorded_locked
+    );
+}
@@ -1,4 +1,6 @@
 use super::*;
+use crate::checksum::checksum_file;
+use crate::update::{WardOptions, ward_directory};
 use crate::ward_file::WardEntry;
 use std::fs;
 #[cfg(unix)]
@@ -10,9 +12,49 @@ fn create_ward_file(dir: &Path, entries: BTreeMap<String, WardEntry>) {
     ward.save(&dir.join(".treeward")).unwrap();
 }
 
+/// Options for warding a test tree with `ward_directory`: an update that
+/// initializes the tree if needed. Tests change what they exercise with
+/// struct update syntax.
+fn ward_options() -> WardOptions {
+    WardOptions {
+        init: false,
+        allow_init: true,
+        fingerprint: None,
+        accept_digest_mismatch: false,
+        dry_run: false,
+        checksum_policy: ChecksumPolicy::Never,
+        jobs: NonZeroUsize::MIN,
+        hash: HashAlgorithm::Sha256,
+        signing_key: None,
+        track: None,
+        protection: None,
+        keep_going: false,
+        pathspecs: Vec::new(),
+    }
+}
+
+fn ward_tree(root: &Path, options: WardOptions) {
+    ward_directory(root, options).unwrap();
+}
+
+fn all_paths(result: &StatusResult) -> Vec<(&str, StatusType)> {
+    result
+        .statuses
+        .iter()
+        .map(|s| (s.path(), s.status_type()))
+        .collect()
+}
+
+mod attributes;
 mod basic;
+mod digest;
+mod ignore;
+mod keep_going;
 mod mode_and_fingerprint;
+mod pathspec;
 mod policy;
+mod rename;
+mod signature;
 #[path = "unix.rs"]
 mod unix_tests;
 mod ward_update;
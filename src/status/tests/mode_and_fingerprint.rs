@@ -11,18 +11,24 @@ fn test_fingerprint_deterministic() {
 
     let result1 = compute_status(
         root,
-        ChecksumPolicy::Never,
-        StatusMode::Interesting,
-        StatusPurpose::Display,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::Never,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     )
     .unwrap();
     let result2 = compute_status(
         root,
-        ChecksumPolicy::Never,
-        StatusMode::Interesting,
-        StatusPurpose::Display,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::Never,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     )
     .unwrap();
 
@@ -43,18 +49,24 @@ fn test_different_fingerprints() {
 
     let result1 = compute_status(
         root1,
-        ChecksumPolicy::Never,
-        StatusMode::Interesting,
-        StatusPurpose::Display,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::Never,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     )
     .unwrap();
     let result2 = compute_status(
         root2,
-        ChecksumPolicy::Never,
-        StatusMode::Interesting,
-        StatusPurpose::Display,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::Never,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     )
     .unwrap();
 
@@ -69,8 +81,8 @@ fn test_status_mode_all_shows_unchanged() {
     fs::write(root.join("file1.txt"), "content1").unwrap();
     fs::write(root.join("file2.txt"), "content2").unwrap();
 
-    let checksum1 = checksum_file(&root.join("file1.txt")).unwrap();
-    let checksum2 = checksum_file(&root.join("file2.txt")).unwrap();
+    let checksum1 = checksum_file(&root.join("file1.txt"), &[HashAlgorithm::Sha256]).unwrap();
+    let checksum2 = checksum_file(&root.join("file2.txt"), &[HashAlgorithm::Sha256]).unwrap();
     let metadata1 = std::fs::metadata(root.join("file1.txt")).unwrap();
     let metadata2 = std::fs::metadata(root.join("file2.txt")).unwrap();
 
@@ -78,7 +90,7 @@ fn test_status_mode_all_shows_unchanged() {
     entries.insert(
         "file1.txt".to_string(),
         WardEntry::File {
-            sha256: checksum1.sha256.clone(),
+            checksum: checksum1.checksums[0].clone(),
             mtime_nanos: metadata1
                 .modified()
                 .unwrap()
@@ -86,12 +98,13 @@ fn test_status_mode_all_shows_unchanged() {
                 .unwrap()
                 .as_nanos() as u64,
             size: metadata1.len(),
+            attributes: Attributes::default(),
         },
     );
     entries.insert(
         "file2.txt".to_string(),
         WardEntry::File {
-            sha256: checksum2.sha256.clone(),
+            checksum: checksum2.checksums[0].clone(),
             mtime_nanos: metadata2
                 .modified()
                 .unwrap()
@@ -99,16 +112,20 @@ fn test_status_mode_all_shows_unchanged() {
                 .unwrap()
                 .as_nanos() as u64,
             size: metadata2.len(),
+            attributes: Attributes::default(),
         },
     );
     create_ward_file(root, entries);
 
     let result = compute_status(
         root,
-        ChecksumPolicy::Never,
-        StatusMode::All,
-        StatusPurpose::Display,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::Never,
+            mode: StatusMode::All,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     )
     .unwrap();
     assert_eq!(result.statuses.len(), 2);
@@ -130,14 +147,15 @@ fn test_status_mode_all_with_changes() {
     fs::write(root.join("unchanged.txt"), "unchanged").unwrap();
     fs::write(root.join("modified.txt"), "modified").unwrap();
 
-    let checksum_unchanged = checksum_file(&root.join("unchanged.txt")).unwrap();
+    let checksum_unchanged =
+        checksum_file(&root.join("unchanged.txt"), &[HashAlgorithm::Sha256]).unwrap();
     let metadata_unchanged = std::fs::metadata(root.join("unchanged.txt")).unwrap();
 
     let mut entries = BTreeMap::new();
     entries.insert(
         "unchanged.txt".to_string(),
         WardEntry::File {
-            sha256: checksum_unchanged.sha256.clone(),
+            checksum: checksum_unchanged.checksums[0].clone(),
             mtime_nanos: metadata_unchanged
                 .modified()
                 .unwrap()
@@ -145,22 +163,31 @@ fn test_status_mode_all_with_changes() {
                 .unwrap()
                 .as_nanos() as u64,
             size: metadata_unchanged.len(),
+            attributes: Attributes::default(),
         },
     );
     entries.insert(
         "modified.txt".to_string(),
         WardEntry::File {
-            sha256: "baadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaad".to_string(),
+            checksum: Checksum::new(
+                HashAlgorithm::Sha256,
+                "baadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaad",
+            ),
             mtime_nanos: 1000,
             size: 8,
+            attributes: Attributes::default(),
         },
     );
     entries.insert(
         "removed.txt".to_string(),
         WardEntry::File {
-            sha256: "abcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabca".to_string(),
+            checksum: Checksum::new(
+                HashAlgorithm::Sha256,
+                "abcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabca",
+            ),
             mtime_nanos: 1000,
             size: 100,
+            attributes: Attributes::default(),
         },
     );
     create_ward_file(root, entries);
@@ -169,10 +196,13 @@ fn test_status_mode_all_with_changes() {
 
     let result = compute_status(
         root,
-        ChecksumPolicy::Never,
-        StatusMode::All,
-        StatusPurpose::Display,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::Never,
+            mode: StatusMode::All,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     )
     .unwrap();
     assert_eq!(result.statuses.len(), 4);
@@ -211,14 +241,14 @@ fn test_unchanged_not_in_fingerprint() {
 
     fs::write(root.join("file1.txt"), "content1").unwrap();
 
-    let checksum = checksum_file(&root.join("file1.txt")).unwrap();
+    let checksum = checksum_file(&root.join("file1.txt"), &[HashAlgorithm::Sha256]).unwrap();
     let metadata = std::fs::metadata(root.join("file1.txt")).unwrap();
 
     let mut entries = BTreeMap::new();
     entries.insert(
         "file1.txt".to_string(),
         WardEntry::File {
-            sha256: checksum.sha256,
+            checksum: checksum.checksums[0].clone(),
             mtime_nanos: metadata
                 .modified()
                 .unwrap()
@@ -226,6 +256,7 @@ fn test_unchanged_not_in_fingerprint() {
                 .unwrap()
                 .as_nanos() as u64,
             size: metadata.len(),
+            attributes: Attributes::default(),
         },
     );
     create_ward_file(root, entries);
@@ -233,26 +264,35 @@ fn test_unchanged_not_in_fingerprint() {
 
     let result_interesting = compute_status(
         root,
-        ChecksumPolicy::Never,
-        StatusMode::Interesting,
-        StatusPurpose::Display,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::Never,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     )
     .unwrap();
     let result_all = compute_status(
         root,
-        ChecksumPolicy::Never,
-        StatusMode::All,
-        StatusPurpose::Display,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::Never,
+            mode: StatusMode::All,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     )
     .unwrap();
     let result_empty = compute_status(
         empty_root,
-        ChecksumPolicy::Never,
-        StatusMode::Interesting,
-        StatusPurpose::Display,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::Never,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     )
     .unwrap();
 
@@ -282,19 +322,23 @@ fn test_removed_fingerprint_bound_to_prior_ward_state() {
         entries.insert(
             "removed.txt".to_string(),
             WardEntry::File {
-                sha256: sha.to_string(),
+                checksum: Checksum::new(HashAlgorithm::Sha256, sha.to_string()),
                 mtime_nanos: 1000,
                 size: 5,
+                attributes: Attributes::default(),
             },
         );
         create_ward_file(root, entries);
 
         let result = compute_status(
             root,
-            ChecksumPolicy::Never,
-            StatusMode::Interesting,
-            StatusPurpose::Display,
-            DiffMode::None,
+            &StatusOptions {
+                policy: ChecksumPolicy::Never,
+                mode: StatusMode::Interesting,
+                purpose: StatusPurpose::Display,
+                diff_mode: DiffMode::None,
+                ..Default::default()
+            },
         )
         .unwrap();
 
@@ -330,16 +374,20 @@ fn test_removed_symlink_fingerprint_bound_to_prior_target() {
             "removed-link".to_string(),
             WardEntry::Symlink {
                 symlink_target: PathBuf::from(target),
+                attributes: Attributes::default(),
             },
         );
         create_ward_file(root, entries);
 
         let result = compute_status(
             root,
-            ChecksumPolicy::Never,
-            StatusMode::Interesting,
-            StatusPurpose::Display,
-            DiffMode::None,
+            &StatusOptions {
+                policy: ChecksumPolicy::Never,
+                mode: StatusMode::Interesting,
+                purpose: StatusPurpose::Display,
+                diff_mode: DiffMode::None,
+                ..Default::default()
+            },
         )
         .unwrap();
 
@@ -379,10 +427,13 @@ fn test_removed_dir_fingerprint_bound_to_entry_kind() {
 
         let result = compute_status(
             root,
-            ChecksumPolicy::Never,
-            StatusMode::Interesting,
-            StatusPurpose::Display,
-            DiffMode::None,
+            &StatusOptions {
+                policy: ChecksumPolicy::Never,
+                mode: StatusMode::Interesting,
+                purpose: StatusPurpose::Display,
+                diff_mode: DiffMode::None,
+                ..Default::default()
+            },
         )
         .unwrap();
 
@@ -392,11 +443,15 @@ fn test_removed_dir_fingerprint_bound_to_entry_kind() {
         result.fingerprint
     };
 
-    let dir_fingerprint = fingerprint_for_entry(WardEntry::Dir {});
+    let dir_fingerprint = fingerprint_for_entry(WardEntry::Dir {
+        digest: None,
+        attributes: Attributes::default(),
+    });
     let file_fingerprint = fingerprint_for_entry(WardEntry::File {
-        sha256: "a".repeat(64),
+        checksum: Checksum::new(HashAlgorithm::Sha256, "a".repeat(64)),
         mtime_nanos: 1000,
         size: 5,
+        attributes: Attributes::default(),
     });
 
     assert_ne!(
@@ -419,7 +474,10 @@ fn test_removed_dir_payload_contributes_its_variant_tag() {
     hash_fingerprint_payload(
         &mut with_payload,
         &FingerprintPayload::Removed {
-            ward_entry: WardEntry::Dir {},
+            ward_entry: WardEntry::Dir {
+                digest: None,
+                attributes: Attributes::default(),
+            },
         },
     );
 
@@ -479,10 +537,13 @@ fn test_status_errors_on_pre_epoch_mtime() {
 
     let err = compute_status(
         root,
-        ChecksumPolicy::Never,
-        StatusMode::Interesting,
-        StatusPurpose::Display,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::Never,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     )
     .unwrap_err();
     let msg = err.to_string();
@@ -493,72 +554,42 @@ fn test_status_errors_on_pre_epoch_mtime() {
     );
 }
 
-/// WARNING: This test verifies that non-UTF-8 paths are rejected rather than
-/// silently converted. Do not change this behavior without extreme care!
-///
-/// The fingerprint mechanism relies on paths being converted to bytes for
-/// hashing. If we used lossy UTF-8 conversion (to_string_lossy), different
-/// non-UTF-8 byte sequences could map to the same replacement character
-/// sequence, causing distinct file sets to produce identical fingerprints.
-/// This would break the TOCTOU protection that fingerprints provide.
-///
-/// If we need to support non-UTF-8 paths in the future, we must use a
-/// collision-free encoding (e.g., raw OS bytes via OsStrExt on Unix).
+/// Non-UTF-8 names are listed in their lossless string form (see
+/// `util::names`), never converted lossily: two names that differ only in
+/// invalid bytes would otherwise share a path and fingerprint, breaking the
+/// TOCTOU protection that fingerprints provide.
 #[test]
-#[cfg(unix)]
-fn test_non_utf8_path_rejected() {
+#[cfg(target_os = "linux")]
+fn test_non_utf8_names_keep_distinct_paths_and_fingerprints() {
     use std::ffi::OsStr;
     use std::os::unix::ffi::OsStrExt;
 
-    let temp = TempDir::new().unwrap();
-    let root = temp.path();
+    // Both names read as "file\u{fffd}.txt" when converted lossily.
+    let fingerprint_with = |raw: &[u8]| {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path();
+        fs::write(root.join(OsStr::from_bytes(raw)), "content").unwrap();
+        create_ward_file(root, BTreeMap::new());
 
-    // Create a filename with invalid UTF-8: 0xFF is not valid in any UTF-8 sequence
-    let invalid_utf8_name =
-        OsStr::from_bytes(&[0x66, 0x69, 0x6c, 0x65, 0xFF, 0x2e, 0x74, 0x78, 0x74]); // "file\xFF.txt"
-    let invalid_path = root.join(invalid_utf8_name);
-
-    // Linux (ext4, etc.) allows non-UTF-8 filenames. Other platforms like macOS
-    // (APFS/HFS+) enforce UTF-8 at the filesystem level. If file creation fails,
-    // test path_to_str directly on the in-memory path object. This is more prone
-    // to errors since we are testing a specific function that may or
-    // may not actually be used in the logic that is relevant.
-    // However, it is better than nothing given that we cannot actually
-    // create the real files on these platforms.
-    if fs::write(&invalid_path, "content").is_err() {
-        #[cfg(target_os = "linux")]
-        panic!("expected non-UTF-8 filename to be allowed on Linux");
-
-        #[cfg(not(target_os = "linux"))]
-        {
-            let result = path_to_str(&invalid_path);
-            assert!(result.is_err());
-            let err_msg = result.unwrap_err().to_string();
-            assert!(
-                err_msg.contains("non-UTF-8"),
-                "Error should mention non-UTF-8: {}",
-                err_msg
-            );
-            return;
-        }
-    }
-
-    create_ward_file(root, BTreeMap::new());
+        let result = compute_status(
+            root,
+            &StatusOptions {
+                policy: ChecksumPolicy::Never,
+                mode: StatusMode::Interesting,
+                purpose: StatusPurpose::Display,
+                diff_mode: DiffMode::None,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(result.statuses.len(), 1);
+        (result.statuses[0].path().to_string(), result.fingerprint)
+    };
 
-    let result = compute_status(
-        root,
-        ChecksumPolicy::Never,
-        StatusMode::Interesting,
-        StatusPurpose::Display,
-        DiffMode::None,
-    );
+    let (path_ff, fingerprint_ff) = fingerprint_with(b"file\xff.txt");
+    let (path_fe, fingerprint_fe) = fingerprint_with(b"file\xfe.txt");
 
-    assert!(result.is_err());
-    let err = result.unwrap_err();
-    let err_msg = err.to_string();
-    assert!(
-        err_msg.contains("non-UTF-8"),
-        "Error should mention non-UTF-8: {}",
-        err_msg
-    );
+    assert_eq!(path_ff, "file\0ff.txt");
+    assert_eq!(path_fe, "file\0fe.txt");
+    assert_ne!(fingerprint_ff, fingerprint_fe);
 }
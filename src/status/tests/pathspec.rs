@@ -0,0 +1,136 @@
+use super::*;
+use crate::pathspec::PathspecError;
+
+/// Ward options that only update the subtrees `pathspecs`.
+fn within(pathspecs: &[&str]) -> WardOptions {
+    WardOptions {
+        checksum_policy: ChecksumPolicy::WhenPossiblyModified,
+        pathspecs: pathspecs.iter().map(PathBuf::from).collect(),
+        ..ward_options()
+    }
+}
+
+fn status(root: &Path, pathspecs: &[&str]) -> Result<StatusResult, StatusError> {
+    let pathspecs: Vec<PathBuf> = pathspecs.iter().map(PathBuf::from).collect();
+    compute_status(
+        root,
+        &StatusOptions {
+            pathspecs: &pathspecs,
+            ..StatusOptions::default()
+        },
+    )
+}
+
+/// a/b/one.txt, a/c/two.txt and top.txt, warded.
+fn warded_tree() -> TempDir {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path();
+    fs::create_dir_all(root.join("a/b")).unwrap();
+    fs::create_dir_all(root.join("a/c")).unwrap();
+    fs::write(root.join("a/b/one.txt"), "one").unwrap();
+    fs::write(root.join("a/c/two.txt"), "two").unwrap();
+    fs::write(root.join("top.txt"), "top").unwrap();
+    ward_tree(root, within(&[]));
+    temp
+}
+
+#[test]
+fn test_status_only_reports_changes_in_pathspecs() {
+    let temp = warded_tree();
+    let root = temp.path();
+    fs::write(root.join("a/b/new.txt"), "new").unwrap();
+    fs::write(root.join("a/c/other.txt"), "other").unwrap();
+    fs::remove_file(root.join("top.txt")).unwrap();
+
+    let scoped = status(root, &["a/b"]).unwrap();
+    let full = status(root, &[]).unwrap();
+
+    assert_eq!(all_paths(&scoped), vec![("a/b/new.txt", StatusType::Added)]);
+    assert_eq!(full.statuses.len(), 3);
+    assert_ne!(scoped.fingerprint, full.fingerprint);
+}
+
+#[test]
+fn test_several_pathspecs_combine() {
+    let temp = warded_tree();
+    let root = temp.path();
+    fs::write(root.join("a/b/new.txt"), "new").unwrap();
+    fs::write(root.join("a/c/other.txt"), "other").unwrap();
+    fs::remove_file(root.join("top.txt")).unwrap();
+
+    let result = status(root, &["a/c", "top.txt"]).unwrap();
+
+    assert_eq!(
+        all_paths(&result),
+        vec![
+            ("a/c/other.txt", StatusType::Added),
+            ("top.txt", StatusType::Removed),
+        ]
+    );
+}
+
+#[test]
+fn test_scoped_update_keeps_entries_outside_pathspecs() {
+    let temp = warded_tree();
+    let root = temp.path();
+    fs::write(root.join("a/b/new.txt"), "new").unwrap();
+    fs::write(root.join("a/c/other.txt"), "other").unwrap();
+    fs::remove_file(root.join("top.txt")).unwrap();
+
+    ward_tree(root, within(&["a/b"]));
+
+    // The digest chain from a/b up to the root is intact, and everything
+    // outside a/b is still compared against its old recorded state.
+    let result = status(root, &[]).unwrap();
+    assert!(result.digest_mismatches.is_empty());
+    assert_eq!(
+        all_paths(&result),
+        vec![
+            ("a/c/other.txt", StatusType::Added),
+            ("top.txt", StatusType::Removed),
+        ]
+    );
+}
+
+#[test]
+fn test_ignore_rules_of_directories_above_pathspecs_apply() {
+    let temp = warded_tree();
+    let root = temp.path();
+    fs::write(root.join(".treewardignore"), "*.tmp\n").unwrap();
+    ward_tree(root, within(&[]));
+    fs::write(root.join("a/b/scratch.tmp"), "scratch").unwrap();
+
+    let result = status(root, &["a/b"]).unwrap();
+    assert!(result.statuses.is_empty());
+    assert_eq!(result.ignored, vec!["a/b/scratch.tmp"]);
+
+    ward_tree(root, within(&["a/b"]));
+    let ward = WardFile::load(&root.join("a/b/.treeward")).unwrap();
+    assert!(!ward.entries.contains_key("scratch.tmp"));
+}
+
+#[test]
+fn test_pathspec_to_removed_directory_reports_it_removed() {
+    let temp = warded_tree();
+    let root = temp.path();
+    fs::remove_dir_all(root.join("a/c")).unwrap();
+
+    let result = status(root, &["a/c"]).unwrap();
+
+    assert_eq!(all_paths(&result), vec![("a/c", StatusType::Removed)]);
+}
+
+#[test]
+fn test_pathspec_matching_nothing_is_an_error() {
+    let temp = warded_tree();
+    let root = temp.path();
+
+    assert!(matches!(
+        status(root, &["a/missing"]),
+        Err(StatusError::Pathspec(PathspecError::NoMatch(path))) if path == "a/missing"
+    ));
+    assert!(matches!(
+        status(root, &["top.txt/below"]),
+        Err(StatusError::Pathspec(PathspecError::NotADirectory(path))) if path == "top.txt"
+    ));
+}
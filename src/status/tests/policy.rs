@@ -6,16 +6,18 @@ fn test_checksum_policy_never() {
     let root = temp.path();
 
     fs::write(root.join("file1.txt"), "original content").unwrap();
-    let original_checksum = checksum_file(&root.join("file1.txt")).unwrap();
+    let original_checksum =
+        checksum_file(&root.join("file1.txt"), &[HashAlgorithm::Sha256]).unwrap();
     let original_metadata = std::fs::metadata(root.join("file1.txt")).unwrap();
 
     let mut entries = BTreeMap::new();
     entries.insert(
         "file1.txt".to_string(),
         WardEntry::File {
-            sha256: original_checksum.sha256.clone(),
+            checksum: original_checksum.checksums[0].clone(),
             mtime_nanos: 1000,
             size: original_metadata.len(),
+            attributes: Attributes::default(),
         },
     );
     create_ward_file(root, entries);
@@ -24,10 +26,13 @@ fn test_checksum_policy_never() {
 
     let result = compute_status(
         root,
-        ChecksumPolicy::Never,
-        StatusMode::Interesting,
-        StatusPurpose::Display,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::Never,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     )
     .unwrap();
     assert_eq!(result.statuses.len(), 1);
@@ -49,19 +54,26 @@ fn test_checksum_policy_when_possibly_modified_metadata_differs() {
     entries.insert(
         "file1.txt".to_string(),
         WardEntry::File {
-            sha256: "baadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaad".to_string(),
+            checksum: Checksum::new(
+                HashAlgorithm::Sha256,
+                "baadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaad",
+            ),
             mtime_nanos: 1000,
             size: 16,
+            attributes: Attributes::default(),
         },
     );
     create_ward_file(root, entries);
 
     let result = compute_status(
         root,
-        ChecksumPolicy::WhenPossiblyModified,
-        StatusMode::Interesting,
-        StatusPurpose::Display,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::WhenPossiblyModified,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     )
     .unwrap();
     assert_eq!(result.statuses.len(), 1);
@@ -75,7 +87,7 @@ fn test_checksum_policy_when_possibly_modified_metadata_same() {
     let root = temp.path();
 
     fs::write(root.join("file1.txt"), "content").unwrap();
-    let checksum = checksum_file(&root.join("file1.txt")).unwrap();
+    let checksum = checksum_file(&root.join("file1.txt"), &[HashAlgorithm::Sha256]).unwrap();
     let metadata = std::fs::metadata(root.join("file1.txt")).unwrap();
     let mtime_nanos = metadata
         .modified()
@@ -88,19 +100,23 @@ fn test_checksum_policy_when_possibly_modified_metadata_same() {
     entries.insert(
         "file1.txt".to_string(),
         WardEntry::File {
-            sha256: checksum.sha256,
+            checksum: checksum.checksums[0].clone(),
             mtime_nanos,
             size: metadata.len(),
+            attributes: Attributes::default(),
         },
     );
     create_ward_file(root, entries);
 
     let result = compute_status(
         root,
-        ChecksumPolicy::WhenPossiblyModified,
-        StatusMode::Interesting,
-        StatusPurpose::Display,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::WhenPossiblyModified,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     )
     .unwrap();
     assert_eq!(result.statuses.len(), 0);
@@ -124,19 +140,26 @@ fn test_checksum_policy_always_with_corruption() {
     entries.insert(
         "file1.txt".to_string(),
         WardEntry::File {
-            sha256: "c0dec0dec0dec0dec0dec0dec0dec0dec0dec0dec0dec0dec0dec0dec0dec0de".to_string(),
+            checksum: Checksum::new(
+                HashAlgorithm::Sha256,
+                "c0dec0dec0dec0dec0dec0dec0dec0dec0dec0dec0dec0dec0dec0dec0dec0de",
+            ),
             mtime_nanos,
             size: metadata.len(),
+            attributes: Attributes::default(),
         },
     );
     create_ward_file(root, entries);
 
     let result = compute_status(
         root,
-        ChecksumPolicy::Always,
-        StatusMode::Interesting,
-        StatusPurpose::Display,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::Always,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     )
     .unwrap();
     assert_eq!(result.statuses.len(), 1);
@@ -156,12 +179,16 @@ fn test_diff_capture_on_checksum_only_difference() {
     let root = temp.path();
 
     fs::write(root.join("file1.txt"), "content").unwrap();
-    let actual_checksum = checksum_file(&root.join("file1.txt")).unwrap();
+    let actual_checksum = checksum_file(&root.join("file1.txt"), &[HashAlgorithm::Sha256]).unwrap();
 
     let recorded_entry = WardEntry::File {
-        sha256: "c0dec0dec0dec0dec0dec0dec0dec0dec0dec0dec0dec0dec0dec0dec0dec0de".to_string(),
+        checksum: Checksum::new(
+            HashAlgorithm::Sha256,
+            "c0dec0dec0dec0dec0dec0dec0dec0dec0dec0dec0dec0dec0dec0dec0dec0de",
+        ),
         mtime_nanos: mtime_to_nanos(&actual_checksum.mtime, Path::new("file1.txt")).unwrap(),
         size: actual_checksum.size,
+        attributes: Attributes::default(),
     };
     let mut entries = BTreeMap::new();
     entries.insert("file1.txt".to_string(), recorded_entry.clone());
@@ -169,10 +196,13 @@ fn test_diff_capture_on_checksum_only_difference() {
 
     let result = compute_status(
         root,
-        ChecksumPolicy::Always,
-        StatusMode::Interesting,
-        StatusPurpose::Display,
-        DiffMode::Capture,
+        &StatusOptions {
+            policy: ChecksumPolicy::Always,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::Capture,
+            ..Default::default()
+        },
     )
     .unwrap();
 
@@ -190,8 +220,8 @@ fn test_diff_capture_on_checksum_only_difference() {
                 "old ward entry must be captured for diff display"
             );
             match ward_entry {
-                Some(WardEntry::File { sha256, .. }) => {
-                    assert_eq!(sha256, &actual_checksum.sha256);
+                Some(WardEntry::File { checksum, .. }) => {
+                    assert_eq!(checksum, &actual_checksum.checksums[0]);
                 }
                 other => panic!("Expected new file ward entry, got {:?}", other),
             }
@@ -213,27 +243,31 @@ fn test_diff_capture_forces_checksum_under_policy_never() {
     let root = temp.path();
 
     fs::write(root.join("file1.txt"), "original content").unwrap();
-    let old_checksum = checksum_file(&root.join("file1.txt")).unwrap();
+    let old_checksum = checksum_file(&root.join("file1.txt"), &[HashAlgorithm::Sha256]).unwrap();
 
     let recorded_entry = WardEntry::File {
-        sha256: old_checksum.sha256.clone(),
+        checksum: old_checksum.checksums[0].clone(),
         // Stale mtime so the entry is metadata-differing.
         mtime_nanos: 1000,
         size: old_checksum.size,
+        attributes: Attributes::default(),
     };
     let mut entries = BTreeMap::new();
     entries.insert("file1.txt".to_string(), recorded_entry.clone());
     create_ward_file(root, entries);
 
     fs::write(root.join("file1.txt"), "modified content").unwrap();
-    let new_checksum = checksum_file(&root.join("file1.txt")).unwrap();
+    let new_checksum = checksum_file(&root.join("file1.txt"), &[HashAlgorithm::Sha256]).unwrap();
 
     let result = compute_status(
         root,
-        ChecksumPolicy::Never,
-        StatusMode::Interesting,
-        StatusPurpose::Display,
-        DiffMode::Capture,
+        &StatusOptions {
+            policy: ChecksumPolicy::Never,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::Capture,
+            ..Default::default()
+        },
     )
     .unwrap();
 
@@ -247,9 +281,9 @@ fn test_diff_capture_forces_checksum_under_policy_never() {
             assert_eq!(path, "file1.txt");
             assert_eq!(old_ward_entry.as_ref(), Some(&recorded_entry));
             match ward_entry {
-                Some(WardEntry::File { sha256, .. }) => {
+                Some(WardEntry::File { checksum, .. }) => {
                     assert_eq!(
-                        sha256, &new_checksum.sha256,
+                        checksum, &new_checksum.checksums[0],
                         "Capture must have checksummed the current content"
                     );
                 }
@@ -266,7 +300,7 @@ fn test_checksum_policy_always_without_corruption() {
     let root = temp.path();
 
     fs::write(root.join("file1.txt"), "content").unwrap();
-    let checksum = checksum_file(&root.join("file1.txt")).unwrap();
+    let checksum = checksum_file(&root.join("file1.txt"), &[HashAlgorithm::Sha256]).unwrap();
     let metadata = std::fs::metadata(root.join("file1.txt")).unwrap();
     let mtime_nanos = metadata
         .modified()
@@ -279,19 +313,23 @@ fn test_checksum_policy_always_without_corruption() {
     entries.insert(
         "file1.txt".to_string(),
         WardEntry::File {
-            sha256: checksum.sha256,
+            checksum: checksum.checksums[0].clone(),
             mtime_nanos,
             size: metadata.len(),
+            attributes: Attributes::default(),
         },
     );
     create_ward_file(root, entries);
 
     let result = compute_status(
         root,
-        ChecksumPolicy::Always,
-        StatusMode::Interesting,
-        StatusPurpose::Display,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::Always,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     )
     .unwrap();
     assert_eq!(result.statuses.len(), 0);
@@ -303,27 +341,127 @@ fn test_metadata_changed_but_content_unchanged() {
     let root = temp.path();
 
     fs::write(root.join("file1.txt"), "content").unwrap();
-    let checksum = checksum_file(&root.join("file1.txt")).unwrap();
+    let checksum = checksum_file(&root.join("file1.txt"), &[HashAlgorithm::Sha256]).unwrap();
     let metadata = std::fs::metadata(root.join("file1.txt")).unwrap();
 
     let mut entries = BTreeMap::new();
     entries.insert(
         "file1.txt".to_string(),
         WardEntry::File {
-            sha256: checksum.sha256,
+            checksum: checksum.checksums[0].clone(),
             mtime_nanos: 1000,
             size: metadata.len(),
+            attributes: Attributes::default(),
         },
     );
     create_ward_file(root, entries);
 
     let result = compute_status(
         root,
-        ChecksumPolicy::WhenPossiblyModified,
-        StatusMode::Interesting,
-        StatusPurpose::Display,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::WhenPossiblyModified,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     )
     .unwrap();
     assert_eq!(result.statuses.len(), 0);
 }
+
+#[test]
+fn test_parallel_checksums_match_sequential() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path();
+
+    let mut entries = BTreeMap::new();
+    for i in 0..8 {
+        let name = format!("file{i}.txt");
+        fs::write(root.join(&name), format!("content {i}")).unwrap();
+        entries.insert(
+            name,
+            WardEntry::File {
+                checksum: Checksum::new(
+                    HashAlgorithm::Sha256,
+                    "baadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaadbaad",
+                ),
+                mtime_nanos: 1000,
+                size: 9,
+                attributes: Attributes::default(),
+            },
+        );
+    }
+    entries.insert(
+        "sub".to_string(),
+        WardEntry::Dir {
+            digest: None,
+            attributes: Attributes::default(),
+        },
+    );
+    create_ward_file(root, entries);
+    fs::create_dir(root.join("sub")).unwrap();
+    fs::write(root.join("sub/added.txt"), "added").unwrap();
+    create_ward_file(&root.join("sub"), BTreeMap::new());
+
+    let options = StatusOptions {
+        policy: ChecksumPolicy::Always,
+        mode: StatusMode::All,
+        purpose: StatusPurpose::WardUpdate,
+        diff_mode: DiffMode::Capture,
+        ..Default::default()
+    };
+    let sequential = compute_status(root, &options).unwrap();
+    let parallel = compute_status(
+        root,
+        &StatusOptions {
+            jobs: NonZeroUsize::new(4).unwrap(),
+            ..options
+        },
+    )
+    .unwrap();
+
+    assert_eq!(sequential.statuses.len(), 10);
+    assert_eq!(sequential.statuses, parallel.statuses);
+    assert_eq!(sequential.fingerprint, parallel.fingerprint);
+}
+
+/// Directories with more entries than one checksum batch classify them all,
+/// in the same order, whatever the number of threads.
+#[test]
+fn test_checksum_batches_cover_large_directories() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path();
+    let count = CLASSIFY_BATCH * 2 + 3;
+    for i in 0..count {
+        fs::write(root.join(format!("file{i:04}.txt")), format!("content {i}")).unwrap();
+    }
+    create_ward_file(root, BTreeMap::new());
+
+    let options = StatusOptions {
+        policy: ChecksumPolicy::Always,
+        mode: StatusMode::All,
+        purpose: StatusPurpose::WardUpdate,
+        ..Default::default()
+    };
+    let sequential = compute_status(root, &options).unwrap();
+    let parallel = compute_status(
+        root,
+        &StatusOptions {
+            jobs: NonZeroUsize::new(4).unwrap(),
+            ..options
+        },
+    )
+    .unwrap();
+
+    assert_eq!(sequential.statuses.len(), count);
+    assert!(sequential.statuses.iter().all(|status| matches!(
+        status,
+        StatusEntry::Added {
+            ward_entry: Some(WardEntry::File { .. }),
+            ..
+        }
+    )));
+    assert_eq!(sequential.statuses, parallel.statuses);
+    assert_eq!(sequential.fingerprint, parallel.fingerprint);
+}
@@ -0,0 +1,232 @@
+use super::*;
+
+/// Ward `root` the way `update` does: compute with `WardUpdate` and save every
+/// ward file built from the result.
+fn ward_tree(root: &Path) {
+    let result = compute_status(
+        root,
+        &StatusOptions {
+            policy: ChecksumPolicy::WhenPossiblyModified,
+            mode: StatusMode::All,
+            purpose: StatusPurpose::WardUpdate,
+            ..StatusOptions::default()
+        },
+    )
+    .unwrap();
+    for (dir, ward_file) in build_ward_files(&root.canonicalize().unwrap(), &result).unwrap() {
+        ward_file.save(&dir.join(".treeward")).unwrap();
+    }
+}
+
+fn renamed_from(result: &StatusResult, path: &str) -> String {
+    match result.statuses.iter().find(|s| s.path() == path) {
+        Some(StatusEntry::Renamed { from, .. }) => from.clone(),
+        other => panic!("expected {path} to be renamed, got {other:?}"),
+    }
+}
+
+#[test]
+fn test_file_moved_between_directories_is_renamed() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path();
+    fs::create_dir(root.join("a")).unwrap();
+    fs::create_dir(root.join("b")).unwrap();
+    fs::write(root.join("a/file.txt"), "content").unwrap();
+    ward_tree(root);
+
+    fs::rename(root.join("a/file.txt"), root.join("b/moved.txt")).unwrap();
+
+    let result = compute_status(root, &StatusOptions::default()).unwrap();
+
+    assert_eq!(
+        all_paths(&result),
+        vec![("b/moved.txt", StatusType::Renamed)]
+    );
+    assert_eq!(renamed_from(&result, "b/moved.txt"), "a/file.txt");
+}
+
+#[test]
+fn test_added_file_with_different_content_is_not_renamed() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path();
+    fs::write(root.join("old.txt"), "aaaa").unwrap();
+    ward_tree(root);
+
+    fs::remove_file(root.join("old.txt")).unwrap();
+    // Same size, different content.
+    fs::write(root.join("new.txt"), "bbbb").unwrap();
+
+    let result = compute_status(root, &StatusOptions::default()).unwrap();
+
+    assert_eq!(
+        all_paths(&result),
+        vec![
+            ("new.txt", StatusType::Added),
+            ("old.txt", StatusType::Removed),
+        ]
+    );
+}
+
+#[test]
+fn test_identical_files_pair_in_path_order() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path();
+    fs::write(root.join("a1"), "same").unwrap();
+    fs::write(root.join("a2"), "same").unwrap();
+    ward_tree(root);
+
+    fs::rename(root.join("a1"), root.join("z1")).unwrap();
+    fs::rename(root.join("a2"), root.join("z2")).unwrap();
+    fs::write(root.join("z3"), "same").unwrap();
+
+    let result = compute_status(root, &StatusOptions::default()).unwrap();
+
+    assert_eq!(
+        all_paths(&result),
+        vec![
+            ("z1", StatusType::Renamed),
+            ("z2", StatusType::Renamed),
+            ("z3", StatusType::Added),
+        ]
+    );
+    assert_eq!(renamed_from(&result, "z1"), "a1");
+    assert_eq!(renamed_from(&result, "z2"), "a2");
+}
+
+#[test]
+fn test_warded_subtree_moved_as_unit_is_renamed() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path();
+    fs::create_dir_all(root.join("old/project/src")).unwrap();
+    fs::create_dir(root.join("new")).unwrap();
+    fs::write(root.join("old/project/README"), "readme").unwrap();
+    fs::write(root.join("old/project/src/main.c"), "int main;").unwrap();
+    ward_tree(root);
+
+    fs::rename(root.join("old/project"), root.join("new/project")).unwrap();
+
+    let result = compute_status(root, &StatusOptions::default()).unwrap();
+
+    // The subtree's contents match the ward files that moved with it.
+    assert_eq!(
+        all_paths(&result),
+        vec![("new/project", StatusType::Renamed)]
+    );
+    assert_eq!(renamed_from(&result, "new/project"), "old/project");
+}
+
+#[test]
+fn test_ambiguous_directory_names_are_not_paired() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path();
+    fs::create_dir_all(root.join("a/data")).unwrap();
+    fs::create_dir_all(root.join("b/data")).unwrap();
+    fs::create_dir(root.join("c")).unwrap();
+    fs::create_dir(root.join("d")).unwrap();
+    ward_tree(root);
+
+    fs::rename(root.join("a/data"), root.join("c/data")).unwrap();
+    fs::rename(root.join("b/data"), root.join("d/data")).unwrap();
+
+    let result = compute_status(root, &StatusOptions::default()).unwrap();
+
+    assert_eq!(
+        all_paths(&result),
+        vec![
+            ("a/data", StatusType::Removed),
+            ("b/data", StatusType::Removed),
+            ("c/data", StatusType::Added),
+            ("d/data", StatusType::Added),
+        ]
+    );
+}
+
+#[test]
+fn test_unwarded_directory_is_not_paired() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path();
+    fs::create_dir(root.join("dir")).unwrap();
+    ward_tree(root);
+
+    fs::rename(root.join("dir"), root.join("elsewhere")).unwrap();
+    fs::create_dir(root.join("other")).unwrap();
+    fs::rename(root.join("elsewhere"), root.join("other/dir")).unwrap();
+    fs::remove_file(root.join("other/dir/.treeward")).unwrap();
+
+    let result = compute_status(root, &StatusOptions::default()).unwrap();
+
+    assert_eq!(
+        all_paths(&result),
+        vec![
+            ("dir", StatusType::Removed),
+            ("other", StatusType::Added),
+            ("other/dir", StatusType::Added),
+        ]
+    );
+}
+
+#[test]
+fn test_fingerprint_covers_rename_source() {
+    // Two trees whose listings are both just `R> z`, from different sources.
+    let fingerprint_after_moving = |source: &str| {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path();
+        fs::write(root.join("a"), "same").unwrap();
+        fs::write(root.join("b"), "same").unwrap();
+        ward_tree(root);
+        fs::rename(root.join(source), root.join("z")).unwrap();
+
+        let result = compute_status(root, &StatusOptions::default()).unwrap();
+        assert_eq!(all_paths(&result), vec![("z", StatusType::Renamed)]);
+        assert_eq!(renamed_from(&result, "z"), source);
+        result.fingerprint
+    };
+
+    assert_ne!(fingerprint_after_moving("a"), fingerprint_after_moving("b"));
+}
+
+#[test]
+fn test_rename_fingerprint_matches_between_display_and_ward_update() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path();
+    fs::create_dir(root.join("sub")).unwrap();
+    fs::write(root.join("file.txt"), "content").unwrap();
+    ward_tree(root);
+
+    fs::rename(root.join("file.txt"), root.join("sub/file.txt")).unwrap();
+
+    let display = compute_status(root, &StatusOptions::default()).unwrap();
+    let ward_update = compute_status(
+        root,
+        &StatusOptions {
+            purpose: StatusPurpose::WardUpdate,
+            ..StatusOptions::default()
+        },
+    )
+    .unwrap();
+
+    assert_eq!(display.fingerprint, ward_update.fingerprint);
+}
+
+#[test]
+fn test_ward_update_records_renamed_entry_at_new_path() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path();
+    fs::create_dir(root.join("sub")).unwrap();
+    fs::write(root.join("file.txt"), "content").unwrap();
+    ward_tree(root);
+
+    fs::rename(root.join("file.txt"), root.join("sub/file.txt")).unwrap();
+    ward_tree(root);
+
+    let root_ward = WardFile::load(&root.join(".treeward")).unwrap();
+    let sub_ward = WardFile::load(&root.join("sub/.treeward")).unwrap();
+    assert!(!root_ward.entries.contains_key("file.txt"));
+    assert!(matches!(
+        sub_ward.entries.get("file.txt"),
+        Some(WardEntry::File { size: 7, .. })
+    ));
+
+    let result = compute_status(root, &StatusOptions::default()).unwrap();
+    assert!(result.statuses.is_empty());
+}
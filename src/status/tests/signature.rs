@@ -0,0 +1,107 @@
+use super::*;
+use crate::signing::SigningKey;
+
+fn key(seed: u8) -> SigningKey {
+    SigningKey::Ed25519(ed25519_dalek::SigningKey::from_bytes(&[seed; 32]))
+}
+
+fn signed_tree() -> TempDir {
+    let temp = TempDir::new().unwrap();
+    fs::create_dir(temp.path().join("sub")).unwrap();
+    fs::write(temp.path().join("sub/file.txt"), "content").unwrap();
+    ward_tree(
+        temp.path(),
+        WardOptions {
+            signing_key: Some(key(1)),
+            ..ward_options()
+        },
+    );
+    temp
+}
+
+fn status_trusting(root: &Path, trusted_key: &TrustedKey) -> Result<StatusResult, StatusError> {
+    compute_status(
+        root,
+        &StatusOptions {
+            trusted_key: Some(trusted_key),
+            ..StatusOptions::default()
+        },
+    )
+}
+
+#[test]
+fn test_signed_tree_passes_and_signatures_are_not_entries() {
+    let temp = signed_tree();
+
+    let result = status_trusting(temp.path(), &key(1).trusted_key()).unwrap();
+
+    assert!(result.statuses.is_empty(), "{:?}", result.statuses);
+}
+
+#[test]
+fn test_other_key_is_rejected() {
+    let temp = signed_tree();
+
+    let result = status_trusting(temp.path(), &key(2).trusted_key());
+
+    assert!(
+        matches!(
+            result,
+            Err(StatusError::Signature(SignatureError::BadSignature(_)))
+        ),
+        "{result:?}"
+    );
+}
+
+#[test]
+fn test_unsigned_ward_file_is_rejected() {
+    let temp = signed_tree();
+    fs::remove_file(temp.path().join("sub/.treeward.sig")).unwrap();
+
+    let result = status_trusting(temp.path(), &key(1).trusted_key());
+
+    assert!(
+        matches!(
+            result,
+            Err(StatusError::Signature(SignatureError::Unsigned(ref path)))
+                if path.ends_with("sub/.treeward")
+        ),
+        "{result:?}"
+    );
+}
+
+#[test]
+fn test_update_without_key_cannot_bless_changes() {
+    let temp = signed_tree();
+
+    fs::write(temp.path().join("sub/file.txt"), "altered").unwrap();
+    ward_tree(temp.path(), ward_options());
+
+    let result = status_trusting(temp.path(), &key(1).trusted_key());
+    assert!(
+        matches!(result, Err(StatusError::Signature(_))),
+        "{result:?}"
+    );
+
+    // Without a trusted key the (unsigned) ward is accepted as before.
+    let result = compute_status(temp.path(), &StatusOptions::default()).unwrap();
+    assert!(result.statuses.is_empty());
+}
+
+#[test]
+fn test_edited_ward_file_signature_is_rejected() {
+    let temp = signed_tree();
+    let ward_path = temp.path().join(".treeward");
+    let content = fs::read_to_string(&ward_path).unwrap();
+    fs::write(&ward_path, format!("# edited\n{content}")).unwrap();
+
+    let result = status_trusting(temp.path(), &key(1).trusted_key());
+
+    assert!(
+        matches!(
+            result,
+            Err(StatusError::Signature(SignatureError::BadSignature(_)))
+        ),
+        "{result:?}"
+    );
+}
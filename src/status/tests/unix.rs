@@ -15,10 +15,13 @@ fn test_status_accepts_symlinked_root() {
 
     let result = compute_status(
         &linked_root,
-        ChecksumPolicy::Never,
-        StatusMode::Interesting,
-        StatusPurpose::Display,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::Never,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     )
     .unwrap();
 
@@ -42,32 +45,44 @@ fn test_symlink_target_changed() {
         "link".to_string(),
         WardEntry::Symlink {
             symlink_target: PathBuf::from("target1.txt"),
+            attributes: Attributes::default(),
         },
     );
     entries.insert(
         "target1.txt".to_string(),
         WardEntry::File {
-            sha256: "abcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabca".to_string(),
+            checksum: Checksum::new(
+                HashAlgorithm::Sha256,
+                "abcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabca",
+            ),
             mtime_nanos: 1000,
             size: 7,
+            attributes: Attributes::default(),
         },
     );
     entries.insert(
         "target2.txt".to_string(),
         WardEntry::File {
-            sha256: "abcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabca".to_string(),
+            checksum: Checksum::new(
+                HashAlgorithm::Sha256,
+                "abcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabca",
+            ),
             mtime_nanos: 1000,
             size: 7,
+            attributes: Attributes::default(),
         },
     );
     create_ward_file(root, entries);
 
     let result = compute_status(
         root,
-        ChecksumPolicy::Never,
-        StatusMode::Interesting,
-        StatusPurpose::Display,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::Never,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     )
     .unwrap();
 
@@ -89,27 +104,38 @@ fn test_type_change() {
     entries.insert(
         "item".to_string(),
         WardEntry::File {
-            sha256: "abc123abc123abc123abc123abc123abc123abc123abc123abc123abc123abc1".to_string(),
+            checksum: Checksum::new(
+                HashAlgorithm::Sha256,
+                "abc123abc123abc123abc123abc123abc123abc123abc123abc123abc123abc1",
+            ),
             mtime_nanos: 1000,
             size: 100,
+            attributes: Attributes::default(),
         },
     );
     entries.insert(
         "target.txt".to_string(),
         WardEntry::File {
-            sha256: "abcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabca".to_string(),
+            checksum: Checksum::new(
+                HashAlgorithm::Sha256,
+                "abcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabca",
+            ),
             mtime_nanos: 1000,
             size: 7,
+            attributes: Attributes::default(),
         },
     );
     create_ward_file(root, entries);
 
     let result = compute_status(
         root,
-        ChecksumPolicy::Never,
-        StatusMode::Interesting,
-        StatusPurpose::Display,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::Never,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     )
     .unwrap();
 
@@ -141,10 +167,13 @@ fn test_symlink_cycle_does_not_cause_infinite_loop() {
     // This should complete without hanging
     let result = compute_status(
         root,
-        ChecksumPolicy::Never,
-        StatusMode::Interesting,
-        StatusPurpose::Display,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::Never,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     );
 
     assert!(result.is_ok());
@@ -157,3 +186,115 @@ fn test_symlink_cycle_does_not_cause_infinite_loop() {
     assert!(paths.iter().any(|p| p.ends_with("b")));
     assert!(paths.iter().any(|p| p.ends_with("parent_link")));
 }
+
+/// Records `kind` for `name` in an otherwise empty ward file.
+#[cfg(unix)]
+fn ward_special(root: &Path, name: &str, kind: SpecialKind) {
+    let mut entries = BTreeMap::new();
+    entries.insert(
+        name.to_string(),
+        WardEntry::Special {
+            kind,
+            attributes: Attributes::default(),
+        },
+    );
+    create_ward_file(root, entries);
+}
+
+#[test]
+#[cfg(unix)]
+fn test_fifo_is_recorded_and_never_opened() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path();
+    nix::unistd::mkfifo(&root.join("pipe"), nix::sys::stat::Mode::S_IRWXU).unwrap();
+    create_ward_file(root, BTreeMap::new());
+
+    let added = compute_status(
+        root,
+        &StatusOptions {
+            policy: ChecksumPolicy::Always,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::WardUpdate,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(added.statuses.len(), 1);
+    assert_eq!(added.statuses[0].status_type(), StatusType::Added);
+    assert!(matches!(
+        added.statuses[0].ward_entry(),
+        Some(WardEntry::Special {
+            kind: SpecialKind::Fifo,
+            ..
+        })
+    ));
+
+    // Opening the FIFO would block forever, so finishing is the assertion.
+    ward_special(root, "pipe", SpecialKind::Fifo);
+    let unchanged = compute_status(
+        root,
+        &StatusOptions {
+            policy: ChecksumPolicy::Always,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert!(unchanged.statuses.is_empty());
+}
+
+#[test]
+#[cfg(unix)]
+fn test_special_file_type_change_is_modified() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path();
+    nix::unistd::mkfifo(&root.join("pipe"), nix::sys::stat::Mode::S_IRWXU).unwrap();
+    ward_special(root, "pipe", SpecialKind::Socket);
+
+    let result = compute_status(
+        root,
+        &StatusOptions {
+            policy: ChecksumPolicy::Never,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    assert_eq!(result.statuses.len(), 1);
+    assert_eq!(result.statuses[0].status_type(), StatusType::Modified);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_device_number_change_is_modified() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path();
+
+    // Creating device nodes needs CAP_MKNOD.
+    let path = std::ffi::CString::new(root.join("null").to_str().unwrap()).unwrap();
+    if unsafe { libc::mknod(path.as_ptr(), libc::S_IFCHR | 0o666, libc::makedev(1, 3)) } != 0 {
+        return;
+    }
+    ward_special(root, "null", SpecialKind::CharDevice { major: 1, minor: 5 });
+
+    let result = compute_status(
+        root,
+        &StatusOptions {
+            policy: ChecksumPolicy::Never,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    assert_eq!(result.statuses.len(), 1);
+    assert_eq!(result.statuses[0].status_type(), StatusType::Modified);
+}
@@ -19,14 +19,15 @@ fn test_display_purpose_without_diff_ward_entry_is_none() {
     fs::write(root.join("unchanged.txt"), "unchanged").unwrap();
     fs::write(root.join("modified.txt"), "modified content").unwrap();
 
-    let checksum_unchanged = checksum_file(&root.join("unchanged.txt")).unwrap();
+    let checksum_unchanged =
+        checksum_file(&root.join("unchanged.txt"), &[HashAlgorithm::Sha256]).unwrap();
     let metadata_unchanged = std::fs::metadata(root.join("unchanged.txt")).unwrap();
 
     let mut entries = BTreeMap::new();
     entries.insert(
         "unchanged.txt".to_string(),
         WardEntry::File {
-            sha256: checksum_unchanged.sha256,
+            checksum: checksum_unchanged.checksums[0].clone(),
             mtime_nanos: metadata_unchanged
                 .modified()
                 .unwrap()
@@ -34,32 +35,44 @@ fn test_display_purpose_without_diff_ward_entry_is_none() {
                 .unwrap()
                 .as_nanos() as u64,
             size: metadata_unchanged.len(),
+            attributes: Attributes::default(),
         },
     );
     entries.insert(
         "modified.txt".to_string(),
         WardEntry::File {
-            sha256: "01d01d01d01d01d01d01d01d01d01d01d01d01d01d01d01d01d01d01d01d01d0".to_string(),
+            checksum: Checksum::new(
+                HashAlgorithm::Sha256,
+                "01d01d01d01d01d01d01d01d01d01d01d01d01d01d01d01d01d01d01d01d01d0",
+            ),
             mtime_nanos: 1000,
             size: 8,
+            attributes: Attributes::default(),
         },
     );
     entries.insert(
         "removed.txt".to_string(),
         WardEntry::File {
-            sha256: "abcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabca".to_string(),
+            checksum: Checksum::new(
+                HashAlgorithm::Sha256,
+                "abcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabca",
+            ),
             mtime_nanos: 1000,
             size: 100,
+            attributes: Attributes::default(),
         },
     );
     create_ward_file(root, entries);
 
     let result = compute_status(
         root,
-        ChecksumPolicy::WhenPossiblyModified,
-        StatusMode::All,
-        StatusPurpose::Display,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::WhenPossiblyModified,
+            mode: StatusMode::All,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     )
     .unwrap();
 
@@ -95,14 +108,15 @@ fn test_ward_update_purpose_ward_entry_is_some() {
     fs::write(root.join("unchanged.txt"), "unchanged").unwrap();
     fs::write(root.join("modified.txt"), "modified content").unwrap();
 
-    let checksum_unchanged = checksum_file(&root.join("unchanged.txt")).unwrap();
+    let checksum_unchanged =
+        checksum_file(&root.join("unchanged.txt"), &[HashAlgorithm::Sha256]).unwrap();
     let metadata_unchanged = std::fs::metadata(root.join("unchanged.txt")).unwrap();
 
     let mut entries = BTreeMap::new();
     entries.insert(
         "unchanged.txt".to_string(),
         WardEntry::File {
-            sha256: checksum_unchanged.sha256.clone(),
+            checksum: checksum_unchanged.checksums[0].clone(),
             mtime_nanos: metadata_unchanged
                 .modified()
                 .unwrap()
@@ -110,32 +124,44 @@ fn test_ward_update_purpose_ward_entry_is_some() {
                 .unwrap()
                 .as_nanos() as u64,
             size: metadata_unchanged.len(),
+            attributes: Attributes::default(),
         },
     );
     entries.insert(
         "modified.txt".to_string(),
         WardEntry::File {
-            sha256: "01d01d01d01d01d01d01d01d01d01d01d01d01d01d01d01d01d01d01d01d01d0".to_string(),
+            checksum: Checksum::new(
+                HashAlgorithm::Sha256,
+                "01d01d01d01d01d01d01d01d01d01d01d01d01d01d01d01d01d01d01d01d01d0",
+            ),
             mtime_nanos: 1000,
             size: 8,
+            attributes: Attributes::default(),
         },
     );
     entries.insert(
         "removed.txt".to_string(),
         WardEntry::File {
-            sha256: "abcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabca".to_string(),
+            checksum: Checksum::new(
+                HashAlgorithm::Sha256,
+                "abcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabca",
+            ),
             mtime_nanos: 1000,
             size: 100,
+            attributes: Attributes::default(),
         },
     );
     create_ward_file(root, entries);
 
     let result = compute_status(
         root,
-        ChecksumPolicy::WhenPossiblyModified,
-        StatusMode::All,
-        StatusPurpose::WardUpdate,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::WhenPossiblyModified,
+            mode: StatusMode::All,
+            purpose: StatusPurpose::WardUpdate,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     )
     .unwrap();
 
@@ -163,10 +189,10 @@ fn test_ward_update_purpose_ward_entry_is_some() {
         .iter()
         .find(|s| s.path() == "added.txt")
         .unwrap();
-    let added_checksum = checksum_file(&root.join("added.txt")).unwrap();
+    let added_checksum = checksum_file(&root.join("added.txt"), &[HashAlgorithm::Sha256]).unwrap();
     match added.ward_entry().unwrap() {
-        WardEntry::File { sha256, size, .. } => {
-            assert_eq!(sha256, &added_checksum.sha256);
+        WardEntry::File { checksum, size, .. } => {
+            assert_eq!(checksum, &added_checksum.checksums[0]);
             assert_eq!(*size, added_checksum.size);
         }
         _ => panic!("Expected File entry"),
@@ -177,10 +203,11 @@ fn test_ward_update_purpose_ward_entry_is_some() {
         .iter()
         .find(|s| s.path() == "modified.txt")
         .unwrap();
-    let modified_checksum = checksum_file(&root.join("modified.txt")).unwrap();
+    let modified_checksum =
+        checksum_file(&root.join("modified.txt"), &[HashAlgorithm::Sha256]).unwrap();
     match modified.ward_entry().unwrap() {
-        WardEntry::File { sha256, size, .. } => {
-            assert_eq!(sha256, &modified_checksum.sha256);
+        WardEntry::File { checksum, size, .. } => {
+            assert_eq!(checksum, &modified_checksum.checksums[0]);
             assert_eq!(*size, modified_checksum.size);
         }
         _ => panic!("Expected File entry"),
@@ -220,19 +247,23 @@ fn test_ward_update_checksum_reuse_when_metadata_unchanged() {
     entries.insert(
         "file.txt".to_string(),
         WardEntry::File {
-            sha256: fake_checksum.clone(),
+            checksum: Checksum::new(HashAlgorithm::Sha256, fake_checksum.clone()),
             mtime_nanos,
             size: metadata.len(),
+            attributes: Attributes::default(),
         },
     );
     create_ward_file(root, entries);
 
     let result = compute_status(
         root,
-        ChecksumPolicy::WhenPossiblyModified,
-        StatusMode::All,
-        StatusPurpose::WardUpdate,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::WhenPossiblyModified,
+            mode: StatusMode::All,
+            purpose: StatusPurpose::WardUpdate,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     )
     .unwrap();
 
@@ -241,9 +272,9 @@ fn test_ward_update_checksum_reuse_when_metadata_unchanged() {
     assert_eq!(status.status_type(), StatusType::Unchanged);
 
     match status.ward_entry().unwrap() {
-        WardEntry::File { sha256, .. } => {
+        WardEntry::File { checksum, .. } => {
             assert_eq!(
-                sha256, &fake_checksum,
+                checksum.hex, fake_checksum,
                 "Checksum should be reused from ward file when metadata matches"
             );
         }
@@ -282,9 +313,13 @@ fn test_checksum_policy_always_forces_checksum_even_when_metadata_matches() {
     entries.insert(
         "file.txt".to_string(),
         WardEntry::File {
-            sha256: "c0dec0dec0dec0dec0dec0dec0dec0dec0dec0dec0dec0dec0dec0dec0dec0de".to_string(),
+            checksum: Checksum::new(
+                HashAlgorithm::Sha256,
+                "c0dec0dec0dec0dec0dec0dec0dec0dec0dec0dec0dec0dec0dec0dec0dec0de",
+            ),
             mtime_nanos,
             size: metadata.len(),
+            attributes: Attributes::default(),
         },
     );
     create_ward_file(root, entries);
@@ -292,10 +327,13 @@ fn test_checksum_policy_always_forces_checksum_even_when_metadata_matches() {
     // With Always policy, even though metadata matches, it should detect the mismatch
     let result = compute_status(
         root,
-        ChecksumPolicy::Always,
-        StatusMode::Interesting,
-        StatusPurpose::WardUpdate,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::Always,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::WardUpdate,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     )
     .unwrap();
 
@@ -303,10 +341,10 @@ fn test_checksum_policy_always_forces_checksum_even_when_metadata_matches() {
     assert_eq!(result.statuses[0].status_type(), StatusType::Modified);
 
     // The ward_entry should have the correct (freshly computed) checksum
-    let real_checksum = checksum_file(&root.join("file.txt")).unwrap();
+    let real_checksum = checksum_file(&root.join("file.txt"), &[HashAlgorithm::Sha256]).unwrap();
     match result.statuses[0].ward_entry().unwrap() {
-        WardEntry::File { sha256, .. } => {
-            assert_eq!(sha256, &real_checksum.sha256);
+        WardEntry::File { checksum, .. } => {
+            assert_eq!(checksum, &real_checksum.checksums[0]);
         }
         _ => panic!("Expected File entry"),
     }
@@ -332,26 +370,30 @@ fn test_checksum_policy_never_with_ward_update_still_checksums_modified() {
     let root = temp.path();
 
     fs::write(root.join("file.txt"), "content").unwrap();
-    let real_checksum = checksum_file(&root.join("file.txt")).unwrap();
+    let real_checksum = checksum_file(&root.join("file.txt"), &[HashAlgorithm::Sha256]).unwrap();
 
     // Same checksum but different mtime - metadata differs but content same
     let mut entries = BTreeMap::new();
     entries.insert(
         "file.txt".to_string(),
         WardEntry::File {
-            sha256: real_checksum.sha256.clone(),
+            checksum: real_checksum.checksums[0].clone(),
             mtime_nanos: 1000,
             size: real_checksum.size,
+            attributes: Attributes::default(),
         },
     );
     create_ward_file(root, entries);
 
     let result = compute_status(
         root,
-        ChecksumPolicy::Never,
-        StatusMode::Interesting,
-        StatusPurpose::WardUpdate,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::Never,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::WardUpdate,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     )
     .unwrap();
 
@@ -366,11 +408,12 @@ fn test_checksum_policy_never_with_ward_update_still_checksums_modified() {
     // But ward_entry should have the freshly computed checksum and updated mtime
     match result.statuses[0].ward_entry().unwrap() {
         WardEntry::File {
-            sha256,
+            checksum,
             size,
             mtime_nanos,
+            ..
         } => {
-            assert_eq!(sha256, &real_checksum.sha256);
+            assert_eq!(checksum, &real_checksum.checksums[0]);
             assert_eq!(*size, real_checksum.size);
             // mtime should be updated to current value, not the old 1000
             assert_ne!(*mtime_nanos, 1000);
@@ -408,9 +451,13 @@ fn test_checksum_policy_never_with_ward_update_content_also_differs() {
     entries.insert(
         "file.txt".to_string(),
         WardEntry::File {
-            sha256: "deaddeaddeaddeaddeaddeaddeaddeaddeaddeaddeaddeaddeaddeaddeaddead".to_string(),
+            checksum: Checksum::new(
+                HashAlgorithm::Sha256,
+                "deaddeaddeaddeaddeaddeaddeaddeaddeaddeaddeaddeaddeaddeaddeaddead",
+            ),
             mtime_nanos: 1000,
             size: 50,
+            attributes: Attributes::default(),
         },
     );
     create_ward_file(root, entries);
@@ -418,10 +465,13 @@ fn test_checksum_policy_never_with_ward_update_content_also_differs() {
     // With Display purpose: would report PossiblyModified (no checksumming)
     let display_result = compute_status(
         root,
-        ChecksumPolicy::Never,
-        StatusMode::Interesting,
-        StatusPurpose::Display,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::Never,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     )
     .unwrap();
     assert_eq!(display_result.statuses.len(), 1);
@@ -433,10 +483,13 @@ fn test_checksum_policy_never_with_ward_update_content_also_differs() {
     // With WardUpdate purpose: must ALSO report PossiblyModified for fingerprint consistency
     let ward_result = compute_status(
         root,
-        ChecksumPolicy::Never,
-        StatusMode::Interesting,
-        StatusPurpose::WardUpdate,
-        DiffMode::None,
+        &StatusOptions {
+            policy: ChecksumPolicy::Never,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::WardUpdate,
+            diff_mode: DiffMode::None,
+            ..Default::default()
+        },
     )
     .unwrap();
     assert_eq!(ward_result.statuses.len(), 1);
@@ -453,12 +506,102 @@ fn test_checksum_policy_never_with_ward_update_content_also_differs() {
     );
 
     // But ward_entry should still have the correct (freshly computed) checksum
-    let real_checksum = checksum_file(&root.join("file.txt")).unwrap();
+    let real_checksum = checksum_file(&root.join("file.txt"), &[HashAlgorithm::Sha256]).unwrap();
     match ward_result.statuses[0].ward_entry().unwrap() {
-        WardEntry::File { sha256, size, .. } => {
-            assert_eq!(sha256, &real_checksum.sha256);
+        WardEntry::File { checksum, size, .. } => {
+            assert_eq!(checksum, &real_checksum.checksums[0]);
             assert_eq!(*size, real_checksum.size);
         }
         _ => panic!("Expected File entry"),
     }
 }
+
+/// Content is compared using the recorded algorithm, independently of the
+/// algorithm requested for new checksums.
+#[test]
+fn test_compares_using_recorded_algorithm() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path();
+
+    fs::write(root.join("file.txt"), "content").unwrap();
+    let blake3 = checksum_file(&root.join("file.txt"), &[HashAlgorithm::Blake3]).unwrap();
+
+    let mut entries = BTreeMap::new();
+    entries.insert(
+        "file.txt".to_string(),
+        WardEntry::File {
+            checksum: blake3.checksums[0].clone(),
+            mtime_nanos: 1000,
+            size: blake3.size,
+            attributes: Attributes::default(),
+        },
+    );
+    create_ward_file(root, entries);
+
+    let result = compute_status(
+        root,
+        &StatusOptions {
+            policy: ChecksumPolicy::Always,
+            hash: HashAlgorithm::Sha512,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    assert!(result.statuses.is_empty());
+}
+
+/// A ward update records re-checksummed files with the requested algorithm
+/// and leaves reused checksums in their recorded algorithm.
+#[test]
+fn test_ward_update_records_requested_algorithm() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path();
+
+    fs::write(root.join("file.txt"), "content").unwrap();
+    let path = root.join("file.txt");
+    let checksums = checksum_file(&path, &[HashAlgorithm::Sha256, HashAlgorithm::Blake3]).unwrap();
+    let mtime_nanos = mtime_to_nanos(&checksums.mtime, &path).unwrap();
+
+    let mut entries = BTreeMap::new();
+    entries.insert(
+        "file.txt".to_string(),
+        WardEntry::File {
+            checksum: checksums.checksum(HashAlgorithm::Sha256).unwrap().clone(),
+            mtime_nanos,
+            size: checksums.size,
+            attributes: Attributes::default(),
+        },
+    );
+    create_ward_file(root, entries);
+
+    for (policy, expected) in [
+        (
+            ChecksumPolicy::WhenPossiblyModified,
+            checksums.checksum(HashAlgorithm::Sha256).unwrap(),
+        ),
+        (
+            ChecksumPolicy::Always,
+            checksums.checksum(HashAlgorithm::Blake3).unwrap(),
+        ),
+    ] {
+        let result = compute_status(
+            root,
+            &StatusOptions {
+                policy,
+                mode: StatusMode::All,
+                purpose: StatusPurpose::WardUpdate,
+                hash: HashAlgorithm::Blake3,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(result.statuses.len(), 1);
+        assert_eq!(result.statuses[0].status_type(), StatusType::Unchanged);
+        match result.statuses[0].ward_entry().unwrap() {
+            WardEntry::File { checksum, .. } => assert_eq!(checksum, expected, "{policy:?}"),
+            _ => panic!("Expected File entry"),
+        }
+    }
+}
@@ -0,0 +1,773 @@
+//! Undoing the last update.
+//!
+//! `WardFile::save_in` replaces a ward file atomically, so whatever an update
+//! overwrote would otherwise be gone for good, including the checksums of
+//! files whose corruption the update accepted. Every update that rewrites
+//! ward files therefore keeps their previous versions (and signatures) in
+//! `.treeward-undo/last` at the root, replacing the generation kept by the
+//! update before it, together with a `manifest.toml` listing each rewritten
+//! ward file and the digest of what the update wrote. FEC files the update
+//! removes from a store are moved there too, so that `repair` can still
+//! restore the content the previous ward files record.
+//!
+//! `undo_last_update` puts the previous versions back, but only while every
+//! ward file the update wrote is still exactly as it wrote it: undoing on top
+//! of later changes would mix two recorded states. Like an update, it writes
+//! the ward files under an intent record (see `intent`) and journals what it
+//! did.
+
+use crate::checksum::HashAlgorithm;
+use crate::dir_list::{FEC_DIRNAME, SIGNATURE_FILENAME, TREEWARD_FILENAME, UNDO_DIRNAME};
+use crate::intent::{IntendedWardFile, Intent, IntentError};
+use crate::journal::{JournalError, append_record, next_undo_record};
+use crate::signing::{SignatureError, read_signature};
+use crate::util::dir_handle::DirHandle;
+use crate::util::names;
+use crate::ward_file::{
+    WardFile, WardFileError, content_digest, create_temp_file, sync_dir, write_atomic,
+};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+const GENERATION_DIRNAME: &str = "last";
+const MANIFEST_FILENAME: &str = "manifest.toml";
+const TREE_DIRNAME: &str = "tree";
+
+#[derive(Debug, thiserror::Error)]
+pub enum UndoError {
+    #[error("Ward file error: {0}")]
+    WardFile(#[from] WardFileError),
+    #[error("Signature error: {0}")]
+    Signature(#[from] SignatureError),
+    #[error("Intent record error: {0}")]
+    Intent(#[from] IntentError),
+    #[error("Journal error: {0}")]
+    Journal(#[from] JournalError),
+    #[error("IO error on {}: {source}", path.display())]
+    Io { path: PathBuf, source: io::Error },
+    #[error("Nothing to undo (no update has rewritten ward files here yet)")]
+    NothingToUndo,
+    #[error("Ward file {0} changed since the last update; not undoing")]
+    Changed(PathBuf),
+    #[error("Invalid undo manifest {}: {source}", path.display())]
+    Manifest {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[error("Cannot serialize undo manifest: {0}")]
+    Serialize(#[from] toml::ser::Error),
+    #[error("Non-UTF-8 path not supported: {0:?}")]
+    NonUtf8(PathBuf),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    /// When the update was written, RFC 3339 in UTC.
+    time: String,
+    /// Fingerprint of the changes the update accepted.
+    fingerprint: String,
+    ward_files: Vec<KeptWardFile>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    fec_files: Vec<KeptFecFile>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct KeptWardFile {
+    /// String form of the directory, relative to the root; empty for the root.
+    path: String,
+    /// Whether there was a ward file before (kept under `tree/<path>`), as
+    /// opposed to one the update created.
+    previous: bool,
+    /// Whether the previous ward file was signed (signature kept next to it).
+    signature: bool,
+    /// Digest of the ward file the update wrote.
+    written: String,
+}
+
+/// An FEC file the update removed, kept under `tree/<path>/.treeward-fec`.
+#[derive(Debug, Serialize, Deserialize)]
+struct KeptFecFile {
+    /// String form of the directory, relative to the root; empty for the root.
+    path: String,
+    name: String,
+}
+
+/// Collects the previous versions of the ward files an update rewrites, and
+/// the FEC files it removes, as the next generation to undo.
+pub(crate) struct UndoRecorder {
+    root: PathBuf,
+    staging: PathBuf,
+    started: bool,
+    ward_files: Vec<KeptWardFile>,
+    fec_files: Vec<KeptFecFile>,
+}
+
+impl UndoRecorder {
+    /// A recorder for the tree at `root` (canonical). Nothing is written
+    /// until the first `keep` or `keep_fec`.
+    pub(crate) fn new(root: &Path) -> Self {
+        UndoRecorder {
+            root: root.to_path_buf(),
+            staging: root
+                .join(UNDO_DIRNAME)
+                .join(format!(".{}.tmp", std::process::id())),
+            started: false,
+            ward_files: Vec::new(),
+            fec_files: Vec::new(),
+        }
+    }
+
+    /// The staging directory for what is kept of the directory at
+    /// `dir_path`, with the string form of its path relative to the root.
+    fn kept_dir(&mut self, dir_path: &Path) -> Result<(PathBuf, String), UndoError> {
+        if !self.started {
+            remove_dir_if_exists(&self.staging)?;
+            fs::create_dir_all(&self.staging).map_err(|e| io_error(&self.staging, e))?;
+            self.started = true;
+        }
+        let relative = dir_path
+            .strip_prefix(&self.root)
+            .expect("ward directories are below the root");
+        let path = names::from_os(relative.as_os_str())
+            .map(Cow::into_owned)
+            .ok_or_else(|| UndoError::NonUtf8(relative.to_path_buf()))?;
+        Ok((self.staging.join(TREE_DIRNAME).join(relative), path))
+    }
+
+    /// Keep the ward file of the open directory `dir`, which is at
+    /// `dir_path` and currently has content `previous` (`None` if it has no
+    /// ward file), before it is replaced by `written`.
+    pub(crate) fn keep(
+        &mut self,
+        dir: &DirHandle,
+        dir_path: &Path,
+        previous: Option<&str>,
+        written: &str,
+    ) -> Result<(), UndoError> {
+        let (kept_dir, path) = self.kept_dir(dir_path)?;
+        let signature = match previous {
+            Some(previous) => {
+                fs::create_dir_all(&kept_dir).map_err(|e| io_error(&kept_dir, e))?;
+                write_atomic(&kept_dir.join(TREEWARD_FILENAME), previous.as_bytes())?;
+                let signature = read_signature(dir, dir_path)?;
+                if let Some(signature) = &signature {
+                    write_atomic(&kept_dir.join(SIGNATURE_FILENAME), signature.as_bytes())?;
+                }
+                signature.is_some()
+            }
+            None => false,
+        };
+        self.ward_files.push(KeptWardFile {
+            path,
+            previous: previous.is_some(),
+            signature,
+            written: content_digest(written, HashAlgorithm::default()).to_string(),
+        });
+        Ok(())
+    }
+
+    /// Keep the FEC file `name` of the open FEC store `store` of the
+    /// directory at `dir_path` instead of removing it: it is moved into the
+    /// generation.
+    pub(crate) fn keep_fec(
+        &mut self,
+        store: &DirHandle,
+        dir_path: &Path,
+        name: &str,
+    ) -> Result<(), UndoError> {
+        let (kept_dir, path) = self.kept_dir(dir_path)?;
+        let kept_store_path = kept_dir.join(FEC_DIRNAME);
+        fs::create_dir_all(&kept_store_path).map_err(|e| io_error(&kept_store_path, e))?;
+        let kept_store =
+            DirHandle::open(&kept_store_path).map_err(|e| io_error(&kept_store_path, e))?;
+        move_file(
+            store,
+            &dir_path.join(FEC_DIRNAME),
+            &kept_store,
+            &kept_store_path,
+            name,
+        )?;
+        self.fec_files.push(KeptFecFile {
+            path,
+            name: name.to_string(),
+        });
+        Ok(())
+    }
+
+    /// Make the kept ward files the generation to undo, replacing the one
+    /// before. If no ward file was kept, the generation before stays, and
+    /// kept FEC files (orphans an update without a ward file to undo
+    /// removed) are deleted.
+    pub(crate) fn commit(self, fingerprint: &str) -> Result<(), UndoError> {
+        if self.ward_files.is_empty() {
+            if self.started {
+                remove_dir_if_exists(&self.staging)?;
+            }
+            return Ok(());
+        }
+        let manifest = Manifest {
+            time: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            fingerprint: fingerprint.to_string(),
+            ward_files: self.ward_files,
+            fec_files: self.fec_files,
+        };
+        write_atomic(
+            &self.staging.join(MANIFEST_FILENAME),
+            toml::to_string(&manifest)?.as_bytes(),
+        )?;
+
+        // Directories cannot be renamed over one another, so the previous
+        // generation is moved aside first.
+        let undo_dir = self.root.join(UNDO_DIRNAME);
+        let generation = undo_dir.join(GENERATION_DIRNAME);
+        let replaced = undo_dir.join(format!(".{}.old", std::process::id()));
+        remove_dir_if_exists(&replaced)?;
+        match fs::rename(&generation, &replaced) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(io_error(&generation, e)),
+            _ => {}
+        }
+        fs::rename(&self.staging, &generation).map_err(|e| io_error(&generation, e))?;
+        let dir = DirHandle::open(&undo_dir).map_err(|e| io_error(&undo_dir, e))?;
+        sync_dir(&dir)?;
+
+        // Besides the generation just replaced, hidden entries are what
+        // failed or interrupted updates left behind.
+        let entries = fs::read_dir(&undo_dir).map_err(|e| io_error(&undo_dir, e))?;
+        for entry in entries {
+            let entry = entry.map_err(|e| io_error(&undo_dir, e))?;
+            if entry.file_name().as_encoded_bytes().starts_with(b".") {
+                remove_dir_if_exists(&entry.path())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct UndoResult {
+    /// When the undone update was written.
+    pub time: String,
+    pub fingerprint: String,
+    /// Relative paths of the `.treeward` files put back (or that would be).
+    pub restored: Vec<PathBuf>,
+    /// Relative paths of the `.treeward` files the update created, removed
+    /// (or that would be).
+    pub removed: Vec<PathBuf>,
+}
+
+/// A ward file to put back.
+struct Restore {
+    /// The directory, relative to the root.
+    relative: PathBuf,
+    dir: DirHandle,
+    dir_path: PathBuf,
+    ward_path: PathBuf,
+    /// What the update wrote, and its signature.
+    current: (String, Option<String>),
+    /// The kept version and its signature; `None` to remove the ward file.
+    previous: Option<(String, Option<String>)>,
+}
+
+/// Restore the ward files of the tree rooted at `root` to what they were
+/// before the last update that rewrote any.
+///
+/// Every ward file that update wrote must still have the content it wrote,
+/// or nothing is changed (`Changed`). FEC files the update removed are first
+/// moved back into their stores. The previous versions are then written
+/// under an intent record: first next to their ward files under temporary
+/// names, and only once all of them are in place renamed over the ward files
+/// in one pass. Ward files the update created are removed. Signatures are
+/// restored (or removed) with their ward files, except the root's: the root
+/// ward file records the journal record of the undo as its head, which its
+/// kept signature does not cover. The kept generation is removed afterwards,
+/// so an update can be undone once.
+pub fn undo_last_update(root: &Path, dry_run: bool) -> Result<UndoResult, UndoError> {
+    let root = root.canonicalize().map_err(|e| io_error(root, e))?;
+    let generation = root.join(UNDO_DIRNAME).join(GENERATION_DIRNAME);
+    let manifest_path = generation.join(MANIFEST_FILENAME);
+    let manifest = match fs::read_to_string(&manifest_path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(UndoError::NothingToUndo),
+        Err(e) => return Err(io_error(&manifest_path, e)),
+    };
+    let manifest: Manifest = toml::from_str(&manifest).map_err(|source| UndoError::Manifest {
+        path: manifest_path,
+        source,
+    })?;
+
+    // Check everything and load every previous version before changing
+    // anything.
+    let root_dir = DirHandle::open(&root).map_err(|e| io_error(&root, e))?;
+    let mut pending = Vec::new();
+    for kept in &manifest.ward_files {
+        let relative = PathBuf::from(names::to_os(&kept.path).into_owned());
+        let dir_path = root.join(&relative);
+        let ward_path = relative.join(TREEWARD_FILENAME);
+        let dir = root_dir
+            .open_path(&relative)
+            .map_err(|_| UndoError::Changed(ward_path.clone()))?;
+        let current = WardFile::load_with_content_if_exists_in(&dir, &dir_path)?
+            .map(|(_, content)| content)
+            .filter(|content| {
+                content_digest(content, HashAlgorithm::default()).to_string() == kept.written
+            })
+            .ok_or_else(|| UndoError::Changed(ward_path.clone()))?;
+        let current = (current, read_signature(&dir, &dir_path)?);
+
+        let kept_dir = generation.join(TREE_DIRNAME).join(&relative);
+        let previous = if kept.previous {
+            let (_, content) =
+                WardFile::load_with_content_if_exists(&kept_dir.join(TREEWARD_FILENAME))?
+                    .ok_or_else(|| {
+                        io_error(
+                            &kept_dir.join(TREEWARD_FILENAME),
+                            io::ErrorKind::NotFound.into(),
+                        )
+                    })?;
+            let signature = if kept.signature {
+                let path = kept_dir.join(SIGNATURE_FILENAME);
+                Some(fs::read_to_string(&path).map_err(|e| io_error(&path, e))?)
+            } else {
+                None
+            };
+            Some((content, signature))
+        } else {
+            None
+        };
+        pending.push(Restore {
+            relative,
+            dir,
+            dir_path,
+            ward_path,
+            current,
+            previous,
+        });
+    }
+
+    let mut restored = Vec::new();
+    let mut removed = Vec::new();
+    for restore in &pending {
+        match restore.previous {
+            Some(_) => restored.push(restore.ward_path.clone()),
+            None => removed.push(restore.ward_path.clone()),
+        }
+    }
+    restored.sort();
+    removed.sort();
+    let result = UndoResult {
+        time: manifest.time,
+        fingerprint: manifest.fingerprint,
+        restored,
+        removed,
+    };
+    if dry_run {
+        return Ok(result);
+    }
+
+    // Moved back first: should the undo fail after this, the FEC files are
+    // merely orphans of the ward files left in place.
+    restore_fec_files(&root_dir, &root, &generation, &manifest.fec_files)?;
+
+    // Every generation includes the root, which an update that rewrites any
+    // ward file rewrites to record its journal record as the head.
+    let mut ward_paths: Vec<String> = manifest
+        .ward_files
+        .iter()
+        .map(|kept| match kept.path.as_str() {
+            "" => TREEWARD_FILENAME.to_string(),
+            path => format!("{path}/{TREEWARD_FILENAME}"),
+        })
+        .collect();
+    ward_paths.sort();
+    let record = next_undo_record(&root_dir, &root, &result.fingerprint, ward_paths)?;
+    for restore in &mut pending {
+        if restore.relative.as_os_str().is_empty()
+            && let Some((content, signature)) = &mut restore.previous
+        {
+            *content = WardFile::from_toml(content)?
+                .with_journal_head(Some(record.head()?))
+                .to_toml()?;
+            *signature = None;
+        }
+    }
+    let intended = pending
+        .iter()
+        .map(|restore| {
+            let (current, current_signature) = restore.current.clone();
+            let (previous, previous_signature) = restore.previous.clone().unzip();
+            IntendedWardFile::new(
+                &restore.relative,
+                Some(current),
+                current_signature,
+                previous,
+                previous_signature.flatten(),
+            )
+        })
+        .collect::<Result<_, _>>()?;
+    let intent = Intent::new(Some(record.clone()), intended).begin(&root_dir, &root)?;
+
+    let mut staged: Vec<(&DirHandle, &Path, String, &str)> = Vec::new();
+    let staging = pending.iter().try_for_each(|restore| {
+        let Some((content, signature)) = &restore.previous else {
+            return Ok(());
+        };
+        let (dir, dir_path) = (&restore.dir, &restore.dir_path);
+        let files = [(TREEWARD_FILENAME, Some(content))]
+            .into_iter()
+            .chain([(SIGNATURE_FILENAME, signature.as_ref())]);
+        for (name, content) in files {
+            let Some(content) = content else {
+                continue;
+            };
+            let (temp_name, mut temp_file) =
+                create_temp_file(dir, name).map_err(|e| io_error(dir_path, e))?;
+            staged.push((dir, dir_path.as_path(), temp_name, name));
+            temp_file
+                .write_all(content.as_bytes())
+                .and_then(|()| temp_file.sync_all())
+                .map_err(|e| io_error(&dir_path.join(name), e))?;
+        }
+        Ok(())
+    });
+    if let Err(e) = staging {
+        for (dir, _, temp_name, _) in &staged {
+            let _ = dir.remove_file(temp_name.as_ref());
+        }
+        return Err(e);
+    }
+
+    for (dir, dir_path, temp_name, name) in &staged {
+        dir.rename(temp_name.as_ref(), name.as_ref())
+            .map_err(|e| io_error(&dir_path.join(name), e))?;
+    }
+    for restore in &pending {
+        let names: &[&str] = match restore.previous {
+            None => &[TREEWARD_FILENAME, SIGNATURE_FILENAME],
+            Some((_, None)) => &[SIGNATURE_FILENAME],
+            Some((_, Some(_))) => &[],
+        };
+        for name in names {
+            match restore.dir.remove_file(name.as_ref()) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                    return Err(io_error(&restore.dir_path.join(name), e));
+                }
+                _ => {}
+            }
+        }
+        sync_dir(&restore.dir)?;
+    }
+
+    append_record(&root_dir, &root, &record)?;
+    remove_generation(&root).map_err(|e| io_error(&generation, e))?;
+    intent.finish(&root_dir, &root)?;
+    Ok(result)
+}
+
+/// Remove the generation kept in the tree rooted at `root`, if any.
+pub(crate) fn remove_generation(root: &Path) -> io::Result<()> {
+    match fs::remove_dir_all(root.join(UNDO_DIRNAME).join(GENERATION_DIRNAME)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Move the FEC files `fec_files` kept in `generation` back into their
+/// stores in the tree rooted at `root`, creating stores as needed. One that
+/// is no longer kept was moved back by an undo that failed later on.
+fn restore_fec_files(
+    root_dir: &DirHandle,
+    root: &Path,
+    generation: &Path,
+    fec_files: &[KeptFecFile],
+) -> Result<(), UndoError> {
+    for kept in fec_files {
+        let relative = PathBuf::from(names::to_os(&kept.path).into_owned());
+        let kept_store_path = generation
+            .join(TREE_DIRNAME)
+            .join(&relative)
+            .join(FEC_DIRNAME);
+        if !kept_store_path.join(&kept.name).exists() {
+            continue;
+        }
+        let kept_store =
+            DirHandle::open(&kept_store_path).map_err(|e| io_error(&kept_store_path, e))?;
+        let dir_path = root.join(&relative);
+        let store_path = dir_path.join(FEC_DIRNAME);
+        let dir = root_dir
+            .open_path(&relative)
+            .map_err(|e| io_error(&dir_path, e))?;
+        match dir.create_dir(FEC_DIRNAME.as_ref()) {
+            Err(e) if e.kind() != io::ErrorKind::AlreadyExists => {
+                return Err(io_error(&store_path, e));
+            }
+            _ => {}
+        }
+        let store = dir
+            .open_dir(FEC_DIRNAME.as_ref())
+            .map_err(|e| io_error(&store_path, e))?;
+        move_file(
+            &kept_store,
+            &kept_store_path,
+            &store,
+            &store_path,
+            &kept.name,
+        )?;
+    }
+    Ok(())
+}
+
+/// Move the file `name` from the open directory `from` (at `from_path`) to
+/// `to` (at `to_path`), replacing any file of that name there. Across
+/// filesystems it is copied and the original removed.
+fn move_file(
+    from: &DirHandle,
+    from_path: &Path,
+    to: &DirHandle,
+    to_path: &Path,
+    name: &str,
+) -> Result<(), UndoError> {
+    match from.rename_to(name.as_ref(), to, name.as_ref()) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            let (temp_name, mut temp_file) =
+                create_temp_file(to, name).map_err(|e| io_error(to_path, e))?;
+            let copied = from
+                .open_file(name.as_ref())
+                .and_then(|mut file| io::copy(&mut file, &mut temp_file))
+                .and_then(|_| temp_file.sync_all())
+                .map_err(|e| io_error(&from_path.join(name), e))
+                .and_then(|()| {
+                    to.rename(temp_name.as_ref(), name.as_ref())
+                        .map_err(|e| io_error(&to_path.join(name), e))
+                });
+            if copied.is_err() {
+                let _ = to.remove_file(temp_name.as_ref());
+            }
+            copied?;
+            from.remove_file(name.as_ref())
+                .map_err(|e| io_error(&from_path.join(name), e))?;
+        }
+        Err(e) => return Err(io_error(&from_path.join(name), e)),
+    }
+    sync_dir(to)?;
+    Ok(sync_dir(from)?)
+}
+
+fn remove_dir_if_exists(path: &Path) -> Result<(), UndoError> {
+    match fs::remove_dir_all(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(io_error(path, e)),
+        _ => Ok(()),
+    }
+}
+
+/// Maps an I/O failure on `path`, singling out permission problems as
+/// `WardFile::save_in` does.
+fn io_error(path: &Path, source: io::Error) -> UndoError {
+    if source.kind() == io::ErrorKind::PermissionDenied {
+        WardFileError::PermissionDenied(path.to_path_buf()).into()
+    } else {
+        UndoError::Io {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::journal::{RecordKind, read_journal};
+    use crate::protection::Protection;
+    use crate::status::ChecksumPolicy;
+    use crate::update::{WardOptions, ward_directory};
+    use std::num::NonZeroUsize;
+    use tempfile::TempDir;
+
+    fn ward(root: &Path, init: bool) {
+        ward_with_protection(root, init, None);
+    }
+
+    fn ward_with_protection(root: &Path, init: bool, protection: Option<Protection>) {
+        ward_directory(
+            root,
+            WardOptions {
+                init,
+                allow_init: false,
+                fingerprint: None,
+                accept_digest_mismatch: false,
+                dry_run: false,
+                checksum_policy: ChecksumPolicy::Never,
+                jobs: NonZeroUsize::MIN,
+                hash: HashAlgorithm::Sha256,
+                signing_key: None,
+                track: None,
+                protection,
+                keep_going: false,
+                pathspecs: Vec::new(),
+            },
+        )
+        .unwrap();
+    }
+
+    fn ward_files(root: &Path) -> Vec<(PathBuf, String)> {
+        ["", "sub", "new"]
+            .iter()
+            .filter_map(|dir| {
+                let path = root.join(dir).join(TREEWARD_FILENAME);
+                fs::read_to_string(&path)
+                    .ok()
+                    .map(|content| (PathBuf::from(dir), content))
+            })
+            .collect()
+    }
+
+    /// `ward_files` without the journal head, which an undo moves on.
+    fn ward_files_without_head(root: &Path) -> Vec<(PathBuf, String)> {
+        ward_files(root)
+            .into_iter()
+            .map(|(dir, content)| {
+                let ward_file = WardFile::from_toml(&content).unwrap();
+                (dir, ward_file.with_journal_head(None).to_toml().unwrap())
+            })
+            .collect()
+    }
+
+    fn tree() -> TempDir {
+        let temp = TempDir::new().unwrap();
+        fs::create_dir(temp.path().join("sub")).unwrap();
+        fs::write(temp.path().join("file.txt"), "content").unwrap();
+        fs::write(temp.path().join("sub/inner.txt"), "inner").unwrap();
+        ward(temp.path(), true);
+        temp
+    }
+
+    #[test]
+    fn test_undo_restores_previous_ward_files() {
+        let temp = tree();
+        let before = ward_files_without_head(temp.path());
+
+        fs::write(temp.path().join("sub/inner.txt"), "changed").unwrap();
+        fs::create_dir(temp.path().join("new")).unwrap();
+        fs::write(temp.path().join("new/file.txt"), "new").unwrap();
+        ward(temp.path(), false);
+        assert_ne!(ward_files(temp.path()), before);
+
+        let result = undo_last_update(temp.path(), false).unwrap();
+
+        assert_eq!(
+            result.restored,
+            vec![PathBuf::from(".treeward"), PathBuf::from("sub/.treeward")]
+        );
+        assert_eq!(result.removed, vec![PathBuf::from("new/.treeward")]);
+        assert_eq!(ward_files_without_head(temp.path()), before);
+        assert!(matches!(
+            undo_last_update(temp.path(), false),
+            Err(UndoError::NothingToUndo)
+        ));
+    }
+
+    #[test]
+    fn test_only_the_last_update_is_kept() {
+        let temp = tree();
+        fs::write(temp.path().join("file.txt"), "first").unwrap();
+        ward(temp.path(), false);
+        let after_first = ward_files_without_head(temp.path());
+        fs::write(temp.path().join("file.txt"), "second").unwrap();
+        ward(temp.path(), false);
+
+        undo_last_update(temp.path(), false).unwrap();
+
+        assert_eq!(ward_files_without_head(temp.path()), after_first);
+        let kept: Vec<_> = fs::read_dir(temp.path().join(UNDO_DIRNAME))
+            .unwrap()
+            .collect();
+        assert!(kept.is_empty());
+    }
+
+    #[test]
+    fn test_undo_is_journaled() {
+        let temp = tree();
+        fs::write(temp.path().join("sub/inner.txt"), "changed").unwrap();
+        fs::create_dir(temp.path().join("new")).unwrap();
+        fs::write(temp.path().join("new/file.txt"), "new").unwrap();
+        ward(temp.path(), false);
+
+        let result = undo_last_update(temp.path(), false).unwrap();
+
+        let journal = read_journal(temp.path()).unwrap();
+        assert!(journal.breaks.is_empty());
+        assert_eq!(journal.lost_head, None);
+        let record = journal.records.last().unwrap();
+        assert_eq!(record.seq, 2);
+        assert_eq!(record.kind, RecordKind::Undo);
+        assert_eq!(record.fingerprint, result.fingerprint);
+        assert_eq!(record.policy, None);
+        assert!(record.entries.is_empty());
+        assert_eq!(
+            record.restored,
+            vec![".treeward", "new/.treeward", "sub/.treeward"]
+        );
+        let root = WardFile::load(&temp.path().join(TREEWARD_FILENAME)).unwrap();
+        assert_eq!(root.journal_head(), Some(&record.head().unwrap()));
+        assert!(!crate::intent::is_pending(temp.path()));
+    }
+
+    #[test]
+    fn test_undo_restores_removed_fec_files() {
+        let temp = tree();
+        let protection = Protection::Enabled { loss_tolerance: 10 };
+        ward_with_protection(temp.path(), false, Some(protection));
+        let store = temp.path().join(FEC_DIRNAME);
+        let protected: Vec<_> = fs::read_dir(&store)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(protected.len(), 1);
+
+        fs::write(temp.path().join("file.txt"), "changed").unwrap();
+        ward(temp.path(), false);
+        assert!(!store.join(&protected[0]).exists());
+
+        undo_last_update(temp.path(), false).unwrap();
+
+        assert!(store.join(&protected[0]).exists());
+        let kept: Vec<_> = fs::read_dir(temp.path().join(UNDO_DIRNAME))
+            .unwrap()
+            .collect();
+        assert!(kept.is_empty());
+    }
+
+    #[test]
+    fn test_refuses_when_ward_files_changed() {
+        let temp = tree();
+        fs::write(temp.path().join("sub/inner.txt"), "changed").unwrap();
+        ward(temp.path(), false);
+        let sub_ward = temp.path().join("sub/.treeward");
+        let edited = fs::read_to_string(&sub_ward).unwrap() + "\n";
+        fs::write(&sub_ward, &edited).unwrap();
+        let current = ward_files(temp.path());
+
+        assert!(matches!(
+            undo_last_update(temp.path(), false),
+            Err(UndoError::Changed(path)) if path == Path::new("sub/.treeward")
+        ));
+        assert_eq!(ward_files(temp.path()), current);
+    }
+
+    #[test]
+    fn test_dry_run_and_unchanged_update() {
+        let temp = tree();
+        // Nothing to record: the generation kept by init stays.
+        ward(temp.path(), false);
+        let current = ward_files(temp.path());
+
+        let result = undo_last_update(temp.path(), true).unwrap();
+
+        assert!(result.restored.is_empty());
+        assert_eq!(result.removed.len(), 2);
+        assert_eq!(ward_files(temp.path()), current);
+    }
+}
@@ -2,15 +2,26 @@
 //!
 //! Runs status traversal in ward-update mode, validates optional fingerprints,
 //! builds new per-directory ward snapshots, and writes changed `.treeward`
-//! files.
-
-use crate::checksum::ChecksumError;
-use crate::dir_list::DirListError;
+//! files (and, with a signing key, their signatures).
+
+use crate::attributes::Track;
+use crate::checksum::{ChecksumError, HashAlgorithm};
+use crate::dir_list::{DirListError, FEC_DIRNAME, SIGNATURE_FILENAME, open_root, reopen_directory};
+use crate::intent::{IntendedWardFile, Intent, IntentError};
+use crate::journal::{JournalEntry, JournalError, append_record, next_record};
+use crate::protection::{
+    ProtectedFile, Protection, ProtectionError, fec_file_name, remove_store, sync_store,
+};
+use crate::signing::{SignatureError, SigningKey, read_signature, remove_signature};
 use crate::status::{
-    ChecksumPolicy, DiffMode, StatusEntry, StatusError, StatusMode, StatusPurpose,
-    build_ward_files, compute_status,
+    ChecksumPolicy, DiffMode, EntryErrorKind, FecState, StatusEntry, StatusError, StatusMode,
+    StatusOptions, StatusPurpose, build_ward_files, compute_status,
 };
-use crate::ward_file::{WardEntry, WardFile, WardFileError};
+use crate::undo::{UndoError, UndoRecorder};
+use crate::util::names;
+use crate::ward_file::{WardEntry, WardFile, WardFileError, content_digest, write_atomic_in};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf, StripPrefixError};
 
 #[derive(Debug, thiserror::Error)]
@@ -23,6 +34,16 @@ pub enum WardError {
     DirList(#[from] DirListError),
     #[error("Checksum error: {0}")]
     Checksum(#[from] ChecksumError),
+    #[error("Signature error: {0}")]
+    Signature(#[from] SignatureError),
+    #[error("Protection error: {0}")]
+    Protection(#[from] ProtectionError),
+    #[error("Journal error: {0}")]
+    Journal(#[from] JournalError),
+    #[error("Undo error: {0}")]
+    Undo(UndoError),
+    #[error("Intent record error: {0}")]
+    Intent(IntentError),
     #[error("Path error: {0}")]
     StripPrefix(#[from] StripPrefixError),
     #[error("Not initialized (use treeward init to initialize)")]
@@ -33,20 +54,78 @@ pub enum WardError {
         "Fingerprint mismatch: expected {expected}, got {actual}. Ensure --verify/--always-verify flags match between status and init/update commands."
     )]
     FingerprintMismatch { expected: String, actual: String },
+    #[error(
+        "Ward file {0} does not match the digest recorded in its parent ward (changed outside treeward). Review with treeward status and accept with --accept-digest-mismatch --fingerprint."
+    )]
+    DigestMismatch(String),
+    #[error(
+        "{0} entries differ from the ward (record them with treeward update before changing protection)"
+    )]
+    PendingChanges(usize),
+}
+
+// Recording the intent or keeping a ward file fails for the same reasons as
+// writing the ward file itself (say, a read-only root), so ward file errors
+// are reported as such.
+impl From<IntentError> for WardError {
+    fn from(e: IntentError) -> Self {
+        match e {
+            IntentError::WardFile(e) => WardError::WardFile(e),
+            e => WardError::Intent(e),
+        }
+    }
+}
+
+impl From<UndoError> for WardError {
+    fn from(e: UndoError) -> Self {
+        match e {
+            UndoError::WardFile(e) => WardError::WardFile(e),
+            e => WardError::Undo(e),
+        }
+    }
 }
 
 pub struct WardOptions {
     pub init: bool,
     pub allow_init: bool,
     pub fingerprint: Option<String>,
+    /// Accept ward files that do not match the digest recorded in their
+    /// parent, and re-record the chain. Requires `fingerprint`, so that only
+    /// a reviewed state is accepted.
+    pub accept_digest_mismatch: bool,
     pub dry_run: bool,
     pub checksum_policy: ChecksumPolicy,
+    pub jobs: NonZeroUsize,
+    pub hash: HashAlgorithm,
+    /// Sign every ward file in the tree with this key.
+    pub signing_key: Option<SigningKey>,
+    /// Attributes to track from now on in every directory; `None` keeps each
+    /// directory's setting.
+    pub track: Option<Track>,
+    /// FEC protection from now on for every directory within the pathspecs;
+    /// `None` keeps each directory's setting.
+    pub protection: Option<Protection>,
+    /// Keep the recorded state of entries that cannot be read, instead of
+    /// failing (see `StatusOptions::keep_going`).
+    pub keep_going: bool,
+    /// Subtrees to update, absolute or relative to the root; empty updates
+    /// the whole tree (see `StatusOptions::pathspecs`).
+    pub pathspecs: Vec<PathBuf>,
 }
 
 #[derive(Debug)]
 pub struct WardResult {
     pub files_warded: usize,
     pub ward_files_updated: Vec<PathBuf>,
+    /// Relative paths of `.treeward.sig` files written (or that would be).
+    pub signatures_updated: Vec<PathBuf>,
+    /// Relative paths of FEC files written (or that would be).
+    pub fec_files_written: Vec<PathBuf>,
+    /// Relative paths of FEC files removed (or that would be).
+    pub fec_files_removed: Vec<PathBuf>,
+    /// Entries that could not be read (with `keep_going`), by relative path.
+    /// Their recorded ward entries were kept unchanged.
+    pub errors: Vec<(String, EntryErrorKind)>,
 }
 
 /// Create or update `.treeward` files to record the current state of a directory tree.
@@ -66,6 +145,25 @@ pub struct WardResult {
 ///   - `checksum_policy`: When to checksum files; affects reported status types and
 ///     therefore fingerprint validation (must match the policy used to produce the
 ///     fingerprint)
+///   - `jobs`: Maximum number of files checksummed concurrently; never affects
+///     results or fingerprints
+///   - `hash`: Algorithm for every checksum computed for a new ward entry.
+///     Checksums reused from existing ward entries keep their algorithm, so
+///     converting a tree takes `ChecksumPolicy::Always`
+///   - `signing_key`: Key to sign ward files with (see below)
+///   - `track`: Attributes (mode, owner) to record in every directory from now
+///     on; without it each directory keeps its recorded setting and new
+///     directories inherit their parent's. Like `hash`, it must match the
+///     `status` run that produced a fingerprint
+///   - `pathspecs`: Subtrees to update. Ward files inside them are updated as
+///     usual; those of the directories on the way down to them only get the
+///     entries leading on updated (including the digest chain up to the
+///     root) and keep every other entry as recorded. Like `hash`, they must
+///     match the `status` run that produced a fingerprint
+///   - `protection`: FEC protection to record from now on in every directory
+///     within the pathspecs (see below); without it each directory keeps its
+///     setting and new directories inherit their parent's. FEC data is not
+///     covered by fingerprints, so it need not match the `status` run
 ///
 /// # Behavior
 ///
@@ -88,9 +186,57 @@ pub struct WardResult {
 /// - This prevents TOCTOU issues where files change between `status` and `ward`
 /// - **No ward files are written if fingerprint doesn't match**
 ///
+/// **Digest mismatches:**
+/// - A `.treeward` that does not match the digest its parent recorded is a
+///   `DigestMismatch` error unless `options.accept_digest_mismatch` is set
+///   along with `options.fingerprint`; the fingerprint covers such
+///   mismatches, so passing both accepts the reviewed ward state
+///
 /// **Selective writing:**
-/// - Only rewrites `.treeward` files if their contents actually changed
+/// - Directory entries record the digest of the child's `.treeward` as it is
+///   after this update, so ward files are settled deepest first and a changed
+///   ward file also rewrites every ancestor up to the root
+/// - Only rewrites `.treeward` files if their entries actually changed
 /// - Avoids unnecessary disk writes and preserves mtimes of unchanged ward files
+/// - A ward file in an older format version is rewritten in the current
+///   version only when its entries change
+///
+/// **Signing:**
+/// - With `options.signing_key`, every ward file in the tree whose
+///   `.treeward.sig` is missing or not a valid signature by that key over its
+///   final content gets a new signature, whether or not it was rewritten
+/// - Without a key, a rewritten ward file's signature is removed, since it no
+///   longer matches
+///
+/// **FEC protection:**
+/// - After a protected directory's ward file is settled, its FEC store is
+///   brought in line with it: FEC data is generated for recorded files that
+///   have none (or have it with another loss tolerance, or damaged, as found
+///   under `ChecksumPolicy::Always`), and FEC files of content no longer
+///   recorded are removed. A file whose content no longer matches its
+///   recorded checksum fails the update with the ward file already written
+/// - An unprotected directory's FEC store is removed
+/// - The directories on the way down to the pathspecs keep their FEC stores
+///   as they are
+///
+/// **Undo:**
+/// - Before a ward file is rewritten, its previous version is kept; once all
+///   are written they replace the generation kept by the previous update
+///   (see `undo`)
+///
+/// **Intent record:**
+/// - All contents are settled before any ward file is written; the previous
+///   and new content of each ward file to rewrite is then recorded at the
+///   root, and the record is removed after the journal append (see `intent`)
+/// - Fails with `IntentError::Pending` if a record is already there
+///
+/// **Journal:**
+/// - Once everything is written, an update that accepted any entry or
+///   rewrote any ward file appends a record of it to the journal at the root
+///   (see `journal`)
+/// - The record is prepared before anything is written, and the root ward
+///   file records it as the journal head, so such an update always rewrites
+///   the root ward file
 ///
 /// **Dry run:**
 /// - If `options.dry_run`, computes what would be updated but writes no files
@@ -103,6 +249,8 @@ pub struct WardResult {
 ///   Unchanged files may still be checksummed when using `--always-verify`.
 /// * `ward_files_updated` - Relative paths of `.treeward` files that were written (or
 ///   would be written in dry-run mode)
+/// * `signatures_updated` - Likewise for `.treeward.sig` files
+/// * `fec_files_written`, `fec_files_removed` - Likewise for FEC files
 pub fn ward_directory(root: &Path, options: WardOptions) -> Result<WardResult, WardError> {
     let root = root.canonicalize().map_err(DirListError::Io)?;
 
@@ -121,19 +269,49 @@ pub fn ward_directory(root: &Path, options: WardOptions) -> Result<WardResult, W
     // for fingerprint validation to work correctly.
     let status = compute_status(
         &root,
-        options.checksum_policy,
-        StatusMode::All,
-        StatusPurpose::WardUpdate,
-        DiffMode::None,
+        &StatusOptions {
+            policy: options.checksum_policy,
+            mode: StatusMode::All,
+            purpose: StatusPurpose::WardUpdate,
+            diff_mode: DiffMode::None,
+            jobs: options.jobs,
+            hash: options.hash,
+            trusted_key: None,
+            track: options.track.as_ref(),
+            protection: options.protection,
+            keep_going: options.keep_going,
+            pathspecs: &options.pathspecs,
+            baseline: None,
+        },
     )?;
 
     // Build ward files in memory from status result
     let mut ward_files = build_ward_files(&root, &status)?;
 
     // Ensure root directory always has a ward file (even if empty)
-    ward_files
-        .entry(root.clone())
-        .or_insert_with(|| WardFile::new(std::collections::BTreeMap::new()));
+    ward_files.entry(root.clone()).or_insert_with(|| {
+        WardFile::new(std::collections::BTreeMap::new())
+            .with_track(
+                status
+                    .tracks
+                    .get(Path::new(""))
+                    .cloned()
+                    .unwrap_or_default(),
+            )
+            .with_protection(
+                status
+                    .protections
+                    .get(Path::new(""))
+                    .copied()
+                    .unwrap_or_default(),
+            )
+    });
+
+    if !(options.accept_digest_mismatch && options.fingerprint.is_some())
+        && let Some(ward_path) = status.digest_mismatches.first()
+    {
+        return Err(WardError::DigestMismatch(ward_path.clone()));
+    }
 
     // Intentionally validating fingerprint AFTER generating ward
     // to avoid TOCTOU conditions.
@@ -146,28 +324,240 @@ pub fn ward_directory(root: &Path, options: WardOptions) -> Result<WardResult, W
         });
     }
 
-    // Write ward files - only changed ones.
+    // Settle ward files deepest first, so that each child's final content is
+    // known when its parent records its digest. Each directory is reopened
+    // from the root one component at a time and must still be the directory
+    // the walk listed, so a directory swapped or moved since cannot redirect
+    // a read or write.
+    let root_dir = open_root(&root)?;
+    let mut dirs: Vec<PathBuf> = ward_files.keys().cloned().collect();
+    dirs.sort_by_key(|dir| std::cmp::Reverse(dir.components().count()));
+    let accepted: Vec<JournalEntry> = status
+        .statuses
+        .iter()
+        .filter_map(JournalEntry::from_status)
+        .collect();
+    let mut contents: HashMap<PathBuf, String> = HashMap::new();
+    let mut settled = Vec::new();
+    let mut intended = Vec::new();
+    let mut rewrites_any = false;
+    let mut record = None;
+    for dir_path in dirs {
+        let mut ward_file = ward_files
+            .remove(&dir_path)
+            .expect("directory list comes from the map");
+        let walked = status.dir_ids.get(dir_path.strip_prefix(&root)?).copied();
+        let dir = reopen_directory(&root_dir, &root, &dir_path, walked)?;
+        let existing = WardFile::load_with_content_if_exists_in(&dir, &dir_path)?;
+
+        for (name, entry) in &mut ward_file.entries {
+            if let WardEntry::Dir { digest, .. } = entry {
+                // Keep a recorded digest's algorithm, as for file checksums.
+                let algorithm = match existing
+                    .as_ref()
+                    .and_then(|(existing, _)| existing.entries.get(name))
+                {
+                    Some(WardEntry::Dir {
+                        digest: Some(recorded),
+                        ..
+                    }) => recorded.algorithm,
+                    _ => options.hash,
+                };
+                // Every warded directory gets a ward file, except one that
+                // could not be read, whose entry keeps its recorded digest.
+                if let Some(child_content) = contents.get(&dir_path.join(names::to_os(name))) {
+                    *digest = Some(content_digest(child_content, algorithm));
+                }
+            }
+        }
+
+        // Compare entries and tracking only: a ward file in an older format
+        // version whose entries are unchanged is left alone rather than
+        // rewritten.
+        let unchanged = existing.as_ref().is_some_and(|(existing, _)| {
+            existing.entries == ward_file.entries
+                && existing.track() == ward_file.track()
+                && existing.protection() == ward_file.protection()
+        });
+        ward_file = ward_file.with_journal_head(
+            existing
+                .as_ref()
+                .and_then(|(existing, _)| existing.journal_head().cloned()),
+        );
+        // The root is settled last. An update that accepts anything or
+        // rewrites any ward file is journaled, and the root records its
+        // record as the journal head, so that records removed from the end
+        // of the journal are noticed.
+        if dir_path == root && (!unchanged || !accepted.is_empty() || rewrites_any) {
+            let next = next_record(
+                &root_dir,
+                &root,
+                &status.fingerprint,
+                options.checksum_policy,
+                accepted.clone(),
+            )?;
+            ward_file = ward_file.with_journal_head(Some(next.head()?));
+            record = Some(next);
+        }
+        let (content, previous) = match existing {
+            Some((existing, content))
+                if unchanged && existing.journal_head() == ward_file.journal_head() =>
+            {
+                (content, None)
+            }
+            existing => {
+                let content = ward_file.to_toml()?;
+                let previous = existing.map(|(_, content)| content);
+                if !options.dry_run {
+                    intended.push(IntendedWardFile::new(
+                        dir_path.strip_prefix(&root)?,
+                        previous.clone(),
+                        read_signature(&dir, &dir_path)?,
+                        Some(content.clone()),
+                        options
+                            .signing_key
+                            .as_ref()
+                            .map(|key| key.sign(&content))
+                            .transpose()?,
+                    )?);
+                }
+                (content, Some(previous))
+            }
+        };
+        rewrites_any |= previous.is_some();
+        contents.insert(dir_path.clone(), content.clone());
+        settled.push((dir_path, ward_file, content, previous));
+    }
+
+    let intent = match &record {
+        Some(record) if !intended.is_empty() => {
+            Some(Intent::new(Some(record.clone()), intended).begin(&root_dir, &root)?)
+        }
+        _ => None,
+    };
+
+    // Write ward files - only changed ones - in the same order, then their
+    // signatures and FEC data.
     let mut ward_files_updated = Vec::new();
-    for (dir_path, ward_file) in &ward_files {
+    let mut signatures_updated = Vec::new();
+    let mut fec_files_written = Vec::new();
+    let mut fec_files_removed = Vec::new();
+    let mut undo = UndoRecorder::new(&root);
+    let unreadable = names_by_dir(
+        &root,
+        status.statuses.iter().filter_map(|s| match s {
+            StatusEntry::Error { path, .. } => Some(path.as_str()),
+            _ => None,
+        }),
+    );
+    let damaged_fec = names_by_dir(
+        &root,
+        status
+            .fec
+            .iter()
+            .filter(|(_, state)| *state == FecState::Damaged)
+            .map(|(path, _)| path.as_str()),
+    );
+    for (dir_path, ward_file, content, previous) in settled {
         let ward_path = dir_path.join(".treeward");
-        let existing = WardFile::load_if_exists(&ward_path)?;
-
-        if existing.as_ref() != Some(ward_file) {
+        let walked = status.dir_ids.get(dir_path.strip_prefix(&root)?).copied();
+        let dir = reopen_directory(&root_dir, &root, &dir_path, walked)?;
+        let rewritten = previous.is_some();
+        if let Some(previous) = previous {
             if !options.dry_run {
-                ward_file.save(&ward_path)?;
+                undo.keep(&dir, &dir_path, previous.as_deref(), &content)?;
+                ward_file.save_in(&dir, &dir_path)?;
             }
             ward_files_updated.push(ward_path.strip_prefix(&root)?.to_path_buf());
         }
-    }
 
-    // Count files that were checksummed for the ward file. This includes Added, Modified,
-    // and PossiblyModified (which are checksummed for ward building even though the status
+        // Signing covers unchanged ward files too, so that one signed update
+        // leaves the whole tree verifiable with the key.
+        if let Some(signing_key) = &options.signing_key {
+            let signed = read_signature(&dir, &dir_path)?
+                .is_some_and(|signature| signing_key.trusted_key().verifies(&content, &signature));
+            if !signed {
+                let signature_path = dir_path.join(SIGNATURE_FILENAME);
+                if !options.dry_run {
+                    write_atomic_in(
+                        &dir,
+                        &dir_path,
+                        SIGNATURE_FILENAME,
+                        signing_key.sign(&content)?.as_bytes(),
+                    )?;
+                }
+                signatures_updated.push(signature_path.strip_prefix(&root)?.to_path_buf());
+            }
+        } else if rewritten && !options.dry_run {
+            remove_signature(&dir, &dir_path)?;
+        }
+
+        let relative_dir = dir_path.strip_prefix(&root)?;
+        if status.scope.covers(relative_dir) {
+            let fec_path = |fec_name: String| relative_dir.join(FEC_DIRNAME).join(fec_name);
+            match ward_file.protection().loss_tolerance() {
+                Some(loss_tolerance) => {
+                    let files: Vec<ProtectedFile> = ward_file
+                        .entries
+                        .iter()
+                        .filter_map(|(name, entry)| match entry {
+                            WardEntry::File { checksum, .. } => Some(ProtectedFile {
+                                name: name.as_str(),
+                                checksum,
+                            }),
+                            _ => None,
+                        })
+                        .collect();
+                    let unreadable: BTreeSet<&str> = unreadable
+                        .get(&dir_path)
+                        .into_iter()
+                        .flatten()
+                        .map(String::as_str)
+                        .collect();
+                    let regenerate: BTreeSet<String> = damaged_fec
+                        .get(&dir_path)
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|name| match ward_file.entries.get(name) {
+                            Some(WardEntry::File { checksum, .. }) => Some(fec_file_name(checksum)),
+                            _ => None,
+                        })
+                        .collect();
+                    let changes = sync_store(
+                        &dir,
+                        &dir_path,
+                        &files,
+                        loss_tolerance,
+                        &unreadable,
+                        &regenerate,
+                        Some(&mut undo),
+                        options.dry_run,
+                    )?;
+                    fec_files_written.extend(changes.written.into_iter().map(fec_path));
+                    fec_files_removed.extend(changes.removed.into_iter().map(fec_path));
+                }
+                None => {
+                    let removed = remove_store(&dir, &dir_path, Some(&mut undo), options.dry_run)?;
+                    fec_files_removed.extend(removed.into_iter().map(fec_path));
+                }
+            }
+        }
+    }
+    undo.commit(&status.fingerprint)?;
+    ward_files_updated.sort();
+    signatures_updated.sort();
+    fec_files_written.sort();
+    fec_files_removed.sort();
+
+    // Count files that were checksummed for the ward file. This includes Added, Renamed,
+    // Modified, and PossiblyModified (which are checksummed for ward building even though the status
     // is reported as PossiblyModified for fingerprint consistency with ChecksumPolicy::Never).
     let files_warded = status
         .statuses
         .iter()
         .filter(|s| match s {
             StatusEntry::Added { ward_entry, .. }
+            | StatusEntry::Renamed { ward_entry, .. }
             | StatusEntry::Modified { ward_entry, .. }
             | StatusEntry::PossiblyModified { ward_entry, .. } => {
                 matches!(ward_entry, Some(WardEntry::File { .. }))
@@ -175,13 +565,128 @@ pub fn ward_directory(root: &Path, options: WardOptions) -> Result<WardResult, W
             _ => false,
         })
         .count();
+    let errors = status
+        .statuses
+        .iter()
+        .filter_map(|s| match s {
+            StatusEntry::Error { path, kind, .. } => Some((path.clone(), *kind)),
+            _ => None,
+        })
+        .collect();
+
+    if let Some(record) = &record
+        && !options.dry_run
+    {
+        append_record(&root_dir, &root, record)?;
+    }
+    if let Some(intent) = intent {
+        intent.finish(&root_dir, &root)?;
+    }
 
     Ok(WardResult {
         files_warded,
         ward_files_updated,
+        signatures_updated,
+        fec_files_written,
+        fec_files_removed,
+        errors,
     })
 }
 
+/// Record `protection` in every directory within `pathspecs` (all of the
+/// tree if empty) and bring their FEC stores in line with it, as
+/// `ward_directory` does: `protect` generates FEC data for every recorded
+/// file, `unprotect` removes the stores.
+///
+/// Only the recorded state is protected, so the tree must match its ward: any
+/// entry that differs from it (under `ChecksumPolicy::Never`) or a ward file
+/// that fails its recorded digest is a `PendingChanges` or `DigestMismatch`
+/// error and nothing is written. The ward is then written with the
+/// fingerprint of that check, so a change in between fails with
+/// `FingerprintMismatch`. Content that changed without touching the metadata
+/// still fails FEC generation, which checks every file against its recorded
+/// checksum.
+///
+/// Returns the result of the update along with the protection each directory
+/// within the pathspecs had before (protected directories only, keyed by path
+/// relative to the root).
+pub fn set_protection(
+    root: &Path,
+    protection: Protection,
+    dry_run: bool,
+    jobs: NonZeroUsize,
+    pathspecs: Vec<PathBuf>,
+) -> Result<(WardResult, BTreeMap<PathBuf, Protection>), WardError> {
+    if !root.join(".treeward").exists() {
+        return Err(WardError::NotInitialized);
+    }
+    let status = compute_status(
+        root,
+        &StatusOptions {
+            policy: ChecksumPolicy::Never,
+            mode: StatusMode::Interesting,
+            purpose: StatusPurpose::Display,
+            diff_mode: DiffMode::None,
+            jobs,
+            hash: HashAlgorithm::default(),
+            trusted_key: None,
+            track: None,
+            protection: None,
+            keep_going: false,
+            pathspecs: &pathspecs,
+            baseline: None,
+        },
+    )?;
+    if let Some(ward_path) = status.digest_mismatches.first() {
+        return Err(WardError::DigestMismatch(ward_path.clone()));
+    }
+    if !status.statuses.is_empty() {
+        return Err(WardError::PendingChanges(status.statuses.len()));
+    }
+
+    let previous = status
+        .protections
+        .into_iter()
+        .filter(|(dir, _)| status.scope.covers(dir))
+        .collect();
+    let result = ward_directory(
+        root,
+        WardOptions {
+            init: false,
+            allow_init: false,
+            fingerprint: Some(status.fingerprint),
+            accept_digest_mismatch: false,
+            dry_run,
+            checksum_policy: ChecksumPolicy::Never,
+            jobs,
+            hash: HashAlgorithm::default(),
+            signing_key: None,
+            track: None,
+            protection: Some(protection),
+            keep_going: false,
+            pathspecs,
+        },
+    )?;
+    Ok((result, previous))
+}
+
+/// Groups relative entry `paths` by their directory's absolute path.
+fn names_by_dir<'a>(
+    root: &Path,
+    paths: impl Iterator<Item = &'a str>,
+) -> HashMap<PathBuf, BTreeSet<String>> {
+    let mut by_dir: HashMap<PathBuf, BTreeSet<String>> = HashMap::new();
+    for path in paths {
+        // Split in string form; only the joined paths are OS paths.
+        let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+        by_dir
+            .entry(root.join(names::to_os(parent)))
+            .or_default()
+            .insert(name.to_string());
+    }
+    by_dir
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -204,8 +709,16 @@ mod tests {
             init: true,
             allow_init: false,
             fingerprint: None,
+            accept_digest_mismatch: false,
             dry_run: false,
             checksum_policy: ChecksumPolicy::Never,
+            jobs: NonZeroUsize::MIN,
+            hash: HashAlgorithm::Sha256,
+            signing_key: None,
+            track: None,
+            protection: None,
+            keep_going: false,
+            pathspecs: Vec::new(),
         };
 
         let result = ward_directory(root, options).unwrap();
@@ -238,8 +751,16 @@ mod tests {
             init: true,
             allow_init: false,
             fingerprint: None,
+            accept_digest_mismatch: false,
             dry_run: false,
             checksum_policy: ChecksumPolicy::Never,
+            jobs: NonZeroUsize::MIN,
+            hash: HashAlgorithm::Sha256,
+            signing_key: None,
+            track: None,
+            protection: None,
+            keep_going: false,
+            pathspecs: Vec::new(),
         };
 
         let result = ward_directory(&linked_root, options).unwrap();
@@ -269,8 +790,16 @@ mod tests {
             init: false,
             allow_init: false,
             fingerprint: None,
+            accept_digest_mismatch: false,
             dry_run: false,
             checksum_policy: ChecksumPolicy::Never,
+            jobs: NonZeroUsize::MIN,
+            hash: HashAlgorithm::Sha256,
+            signing_key: None,
+            track: None,
+            protection: None,
+            keep_going: false,
+            pathspecs: Vec::new(),
         };
 
         let result = ward_directory(root, options);
@@ -293,8 +822,16 @@ mod tests {
             init: true,
             allow_init: false,
             fingerprint: None,
+            accept_digest_mismatch: false,
             dry_run: false,
             checksum_policy: ChecksumPolicy::Never,
+            jobs: NonZeroUsize::MIN,
+            hash: HashAlgorithm::Sha256,
+            signing_key: None,
+            track: None,
+            protection: None,
+            keep_going: false,
+            pathspecs: Vec::new(),
         };
 
         ward_directory(root, init_options).unwrap();
@@ -304,8 +841,16 @@ mod tests {
             init: true,
             allow_init: false,
             fingerprint: None,
+            accept_digest_mismatch: false,
             dry_run: false,
             checksum_policy: ChecksumPolicy::Never,
+            jobs: NonZeroUsize::MIN,
+            hash: HashAlgorithm::Sha256,
+            signing_key: None,
+            track: None,
+            protection: None,
+            keep_going: false,
+            pathspecs: Vec::new(),
         };
 
         let result = ward_directory(root, update_options);
@@ -326,8 +871,16 @@ mod tests {
             init: true,
             allow_init: false,
             fingerprint: None,
+            accept_digest_mismatch: false,
             dry_run: false,
             checksum_policy: ChecksumPolicy::Never,
+            jobs: NonZeroUsize::MIN,
+            hash: HashAlgorithm::Sha256,
+            signing_key: None,
+            track: None,
+            protection: None,
+            keep_going: false,
+            pathspecs: Vec::new(),
         };
 
         ward_directory(root, init_options).unwrap();
@@ -337,10 +890,13 @@ mod tests {
         // fingerprint validation to work correctly.
         let status = compute_status(
             root,
-            ChecksumPolicy::WhenPossiblyModified,
-            StatusMode::Interesting,
-            StatusPurpose::Display,
-            DiffMode::None,
+            &StatusOptions {
+                policy: ChecksumPolicy::WhenPossiblyModified,
+                mode: StatusMode::Interesting,
+                purpose: StatusPurpose::Display,
+                diff_mode: DiffMode::None,
+                ..Default::default()
+            },
         )
         .unwrap();
 
@@ -348,8 +904,16 @@ mod tests {
             init: false,
             allow_init: false,
             fingerprint: Some(status.fingerprint.clone()),
+            accept_digest_mismatch: false,
             dry_run: false,
             checksum_policy: ChecksumPolicy::WhenPossiblyModified,
+            jobs: NonZeroUsize::MIN,
+            hash: HashAlgorithm::Sha256,
+            signing_key: None,
+            track: None,
+            protection: None,
+            keep_going: false,
+            pathspecs: Vec::new(),
         };
 
         let result = ward_directory(root, options);
@@ -376,8 +940,16 @@ mod tests {
             init: true,
             allow_init: false,
             fingerprint: None,
+            accept_digest_mismatch: false,
             dry_run: false,
             checksum_policy: ChecksumPolicy::Never,
+            jobs: NonZeroUsize::MIN,
+            hash: HashAlgorithm::Sha256,
+            signing_key: None,
+            track: None,
+            protection: None,
+            keep_going: false,
+            pathspecs: Vec::new(),
         };
 
         ward_directory(root, init_options).unwrap();
@@ -393,10 +965,13 @@ mod tests {
         // Status with Never policy (CLI default) - file appears as M?
         let status = compute_status(
             root,
-            ChecksumPolicy::Never,
-            StatusMode::Interesting,
-            StatusPurpose::Display,
-            DiffMode::None,
+            &StatusOptions {
+                policy: ChecksumPolicy::Never,
+                mode: StatusMode::Interesting,
+                purpose: StatusPurpose::Display,
+                diff_mode: DiffMode::None,
+                ..Default::default()
+            },
         )
         .unwrap();
 
@@ -411,8 +986,16 @@ mod tests {
             init: false,
             allow_init: false,
             fingerprint: Some(status.fingerprint.clone()),
+            accept_digest_mismatch: false,
             dry_run: false,
             checksum_policy: ChecksumPolicy::Never,
+            jobs: NonZeroUsize::MIN,
+            hash: HashAlgorithm::Sha256,
+            signing_key: None,
+            track: None,
+            protection: None,
+            keep_going: false,
+            pathspecs: Vec::new(),
         };
 
         let result = ward_directory(root, options);
@@ -434,8 +1017,16 @@ mod tests {
             init: true,
             allow_init: false,
             fingerprint: None,
+            accept_digest_mismatch: false,
             dry_run: false,
             checksum_policy: ChecksumPolicy::Never,
+            jobs: NonZeroUsize::MIN,
+            hash: HashAlgorithm::Sha256,
+            signing_key: None,
+            track: None,
+            protection: None,
+            keep_going: false,
+            pathspecs: Vec::new(),
         };
 
         ward_directory(root, init_options).unwrap();
@@ -448,8 +1039,16 @@ mod tests {
             init: false,
             allow_init: false,
             fingerprint: Some("wrong_fingerprint".to_string()),
+            accept_digest_mismatch: false,
             dry_run: false,
             checksum_policy: ChecksumPolicy::Never,
+            jobs: NonZeroUsize::MIN,
+            hash: HashAlgorithm::Sha256,
+            signing_key: None,
+            track: None,
+            protection: None,
+            keep_going: false,
+            pathspecs: Vec::new(),
         };
 
         let result = ward_directory(root, options);
@@ -494,8 +1093,16 @@ mod tests {
             init: true,
             allow_init: false,
             fingerprint: None,
+            accept_digest_mismatch: false,
             dry_run: false,
             checksum_policy: ChecksumPolicy::Never,
+            jobs: NonZeroUsize::MIN,
+            hash: HashAlgorithm::Sha256,
+            signing_key: None,
+            track: None,
+            protection: None,
+            keep_going: false,
+            pathspecs: Vec::new(),
         };
         ward_directory(root, init_options).unwrap();
 
@@ -505,10 +1112,13 @@ mod tests {
         // User runs status and gets fingerprint (shows file1 as Modified)
         let status = compute_status(
             root,
-            ChecksumPolicy::WhenPossiblyModified,
-            StatusMode::Interesting,
-            StatusPurpose::Display,
-            DiffMode::None,
+            &StatusOptions {
+                policy: ChecksumPolicy::WhenPossiblyModified,
+                mode: StatusMode::Interesting,
+                purpose: StatusPurpose::Display,
+                diff_mode: DiffMode::None,
+                ..Default::default()
+            },
         )
         .unwrap();
         assert_eq!(status.statuses.len(), 1);
@@ -523,8 +1133,16 @@ mod tests {
             init: false,
             allow_init: false,
             fingerprint: Some(fingerprint_at_status_time),
+            accept_digest_mismatch: false,
             dry_run: false,
             checksum_policy: ChecksumPolicy::WhenPossiblyModified,
+            jobs: NonZeroUsize::MIN,
+            hash: HashAlgorithm::Sha256,
+            signing_key: None,
+            track: None,
+            protection: None,
+            keep_going: false,
+            pathspecs: Vec::new(),
         };
 
         let result = ward_directory(root, update_options);
@@ -545,12 +1163,12 @@ mod tests {
         let temp_for_checksum = TempDir::new().unwrap();
         let checksum_path = temp_for_checksum.path().join("temp");
         fs::write(&checksum_path, "content1").unwrap();
-        let original_checksum = checksum_file(&checksum_path).unwrap();
+        let original_checksum = checksum_file(&checksum_path, &[HashAlgorithm::Sha256]).unwrap();
 
         match ward.entries.get("file1.txt").unwrap() {
-            WardEntry::File { sha256, .. } => {
+            WardEntry::File { checksum, .. } => {
                 assert_eq!(
-                    sha256, &original_checksum.sha256,
+                    checksum, &original_checksum.checksums[0],
                     "file1.txt should still have original checksum after failed update"
                 );
             }
@@ -572,8 +1190,16 @@ mod tests {
             init: true,
             allow_init: false,
             fingerprint: None,
+            accept_digest_mismatch: false,
             dry_run: false,
             checksum_policy: ChecksumPolicy::Never,
+            jobs: NonZeroUsize::MIN,
+            hash: HashAlgorithm::Sha256,
+            signing_key: None,
+            track: None,
+            protection: None,
+            keep_going: false,
+            pathspecs: Vec::new(),
         };
         ward_directory(root, init_options).unwrap();
 
@@ -584,10 +1210,13 @@ mod tests {
         // User runs status and gets fingerprint (shows both files as Modified)
         let status = compute_status(
             root,
-            ChecksumPolicy::WhenPossiblyModified,
-            StatusMode::Interesting,
-            StatusPurpose::Display,
-            DiffMode::None,
+            &StatusOptions {
+                policy: ChecksumPolicy::WhenPossiblyModified,
+                mode: StatusMode::Interesting,
+                purpose: StatusPurpose::Display,
+                diff_mode: DiffMode::None,
+                ..Default::default()
+            },
         )
         .unwrap();
         assert_eq!(status.statuses.len(), 2);
@@ -601,8 +1230,16 @@ mod tests {
             init: false,
             allow_init: false,
             fingerprint: Some(fingerprint_at_status_time),
+            accept_digest_mismatch: false,
             dry_run: false,
             checksum_policy: ChecksumPolicy::WhenPossiblyModified,
+            jobs: NonZeroUsize::MIN,
+            hash: HashAlgorithm::Sha256,
+            signing_key: None,
+            track: None,
+            protection: None,
+            keep_going: false,
+            pathspecs: Vec::new(),
         };
 
         let result = ward_directory(root, update_options);
@@ -631,8 +1268,16 @@ mod tests {
             init: true,
             allow_init: false,
             fingerprint: None,
+            accept_digest_mismatch: false,
             dry_run: true,
             checksum_policy: ChecksumPolicy::Never,
+            jobs: NonZeroUsize::MIN,
+            hash: HashAlgorithm::Sha256,
+            signing_key: None,
+            track: None,
+            protection: None,
+            keep_going: false,
+            pathspecs: Vec::new(),
         };
 
         let result = ward_directory(root, options).unwrap();
@@ -656,8 +1301,16 @@ mod tests {
             init: true,
             allow_init: false,
             fingerprint: None,
+            accept_digest_mismatch: false,
             dry_run: true,
             checksum_policy: ChecksumPolicy::Never,
+            jobs: NonZeroUsize::MIN,
+            hash: HashAlgorithm::Sha256,
+            signing_key: None,
+            track: None,
+            protection: None,
+            keep_going: false,
+            pathspecs: Vec::new(),
         };
 
         let result = ward_directory(root, options).unwrap();
@@ -687,8 +1340,16 @@ mod tests {
             init: true,
             allow_init: false,
             fingerprint: None,
+            accept_digest_mismatch: false,
             dry_run: false,
             checksum_policy: ChecksumPolicy::Never,
+            jobs: NonZeroUsize::MIN,
+            hash: HashAlgorithm::Sha256,
+            signing_key: None,
+            track: None,
+            protection: None,
+            keep_going: false,
+            pathspecs: Vec::new(),
         };
 
         ward_directory(root, init_options).unwrap();
@@ -704,8 +1365,16 @@ mod tests {
             init: false,
             allow_init: false,
             fingerprint: None,
+            accept_digest_mismatch: false,
             dry_run: false,
             checksum_policy: ChecksumPolicy::Never,
+            jobs: NonZeroUsize::MIN,
+            hash: HashAlgorithm::Sha256,
+            signing_key: None,
+            track: None,
+            protection: None,
+            keep_going: false,
+            pathspecs: Vec::new(),
         };
 
         let result = ward_directory(root, update_options).unwrap();
@@ -719,21 +1388,10 @@ mod tests {
 
         assert_eq!(dir1_ward_mtime_before, dir1_ward_mtime_after);
 
-        assert_eq!(result.ward_files_updated.len(), 1);
-        assert!(
-            result
-                .ward_files_updated
-                .contains(&PathBuf::from("dir2/.treeward"))
-        );
-        assert!(
-            !result
-                .ward_files_updated
-                .contains(&PathBuf::from(".treeward"))
-        );
-        assert!(
-            !result
-                .ward_files_updated
-                .contains(&PathBuf::from("dir1/.treeward"))
+        // dir2's ward changed, and with it the digest the root records for it.
+        assert_eq!(
+            result.ward_files_updated,
+            vec![PathBuf::from(".treeward"), PathBuf::from("dir2/.treeward")]
         );
     }
 
@@ -754,8 +1412,16 @@ mod tests {
             init: true,
             allow_init: false,
             fingerprint: None,
+            accept_digest_mismatch: false,
             dry_run: false,
             checksum_policy: ChecksumPolicy::Never,
+            jobs: NonZeroUsize::MIN,
+            hash: HashAlgorithm::Sha256,
+            signing_key: None,
+            track: None,
+            protection: None,
+            keep_going: false,
+            pathspecs: Vec::new(),
         };
 
         let result = ward_directory(root, options).unwrap();
@@ -772,7 +1438,7 @@ mod tests {
         assert!(root_ward.entries.contains_key("link1"));
 
         match root_ward.entries.get("link1").unwrap() {
-            WardEntry::Symlink { symlink_target } => {
+            WardEntry::Symlink { symlink_target, .. } => {
                 assert_eq!(symlink_target, &PathBuf::from("file1.txt"));
             }
             _ => panic!("Expected symlink entry"),
@@ -792,8 +1458,16 @@ mod tests {
             init: true,
             allow_init: false,
             fingerprint: None,
+            accept_digest_mismatch: false,
             dry_run: false,
             checksum_policy: ChecksumPolicy::Never,
+            jobs: NonZeroUsize::MIN,
+            hash: HashAlgorithm::Sha256,
+            signing_key: None,
+            track: None,
+            protection: None,
+            keep_going: false,
+            pathspecs: Vec::new(),
         };
 
         let init_result = ward_directory(root, init_options).unwrap();
@@ -806,8 +1480,16 @@ mod tests {
             init: false,
             allow_init: false,
             fingerprint: None,
+            accept_digest_mismatch: false,
             dry_run: false,
             checksum_policy: ChecksumPolicy::Never,
+            jobs: NonZeroUsize::MIN,
+            hash: HashAlgorithm::Sha256,
+            signing_key: None,
+            track: None,
+            protection: None,
+            keep_going: false,
+            pathspecs: Vec::new(),
         };
 
         let update_result = ward_directory(root, update_options).unwrap();
@@ -825,8 +1507,16 @@ mod tests {
             init: true,
             allow_init: false,
             fingerprint: None,
+            accept_digest_mismatch: false,
             dry_run: false,
             checksum_policy: ChecksumPolicy::Never,
+            jobs: NonZeroUsize::MIN,
+            hash: HashAlgorithm::Sha256,
+            signing_key: None,
+            track: None,
+            protection: None,
+            keep_going: false,
+            pathspecs: Vec::new(),
         };
 
         let result = ward_directory(root, options).unwrap();
@@ -852,8 +1542,16 @@ mod tests {
             init: true,
             allow_init: false,
             fingerprint: None,
+            accept_digest_mismatch: false,
             dry_run: false,
             checksum_policy: ChecksumPolicy::Never,
+            jobs: NonZeroUsize::MIN,
+            hash: HashAlgorithm::Sha256,
+            signing_key: None,
+            track: None,
+            protection: None,
+            keep_going: false,
+            pathspecs: Vec::new(),
         };
 
         ward_directory(root, init_options).unwrap();
@@ -865,8 +1563,16 @@ mod tests {
             init: false,
             allow_init: false,
             fingerprint: None,
+            accept_digest_mismatch: false,
             dry_run: false,
             checksum_policy: ChecksumPolicy::Never,
+            jobs: NonZeroUsize::MIN,
+            hash: HashAlgorithm::Sha256,
+            signing_key: None,
+            track: None,
+            protection: None,
+            keep_going: false,
+            pathspecs: Vec::new(),
         };
 
         let result = ward_directory(root, options);
@@ -896,8 +1602,16 @@ mod tests {
             init: true,
             allow_init: false,
             fingerprint: None,
+            accept_digest_mismatch: false,
             dry_run: false,
             checksum_policy: ChecksumPolicy::Never,
+            jobs: NonZeroUsize::MIN,
+            hash: HashAlgorithm::Sha256,
+            signing_key: None,
+            track: None,
+            protection: None,
+            keep_going: false,
+            pathspecs: Vec::new(),
         };
 
         let result = ward_directory(root, options);
@@ -928,8 +1642,16 @@ mod tests {
             init: true,
             allow_init: false,
             fingerprint: None,
+            accept_digest_mismatch: false,
             dry_run: false,
             checksum_policy: ChecksumPolicy::Never,
+            jobs: NonZeroUsize::MIN,
+            hash: HashAlgorithm::Sha256,
+            signing_key: None,
+            track: None,
+            protection: None,
+            keep_going: false,
+            pathspecs: Vec::new(),
         };
 
         ward_directory(root, init_options).unwrap();
@@ -947,8 +1669,16 @@ mod tests {
             init: false,
             allow_init: false,
             fingerprint: None,
+            accept_digest_mismatch: false,
             dry_run: false,
             checksum_policy: ChecksumPolicy::Never,
+            jobs: NonZeroUsize::MIN,
+            hash: HashAlgorithm::Sha256,
+            signing_key: None,
+            track: None,
+            protection: None,
+            keep_going: false,
+            pathspecs: Vec::new(),
         };
 
         let result = ward_directory(root, options);
@@ -968,4 +1698,313 @@ mod tests {
             result
         );
     }
+
+    fn digest_test_options(init: bool, fingerprint: Option<String>) -> WardOptions {
+        WardOptions {
+            init,
+            allow_init: false,
+            fingerprint,
+            accept_digest_mismatch: false,
+            dry_run: false,
+            checksum_policy: ChecksumPolicy::Never,
+            jobs: NonZeroUsize::MIN,
+            hash: HashAlgorithm::Sha256,
+            signing_key: None,
+            track: None,
+            protection: None,
+            keep_going: false,
+            pathspecs: Vec::new(),
+        }
+    }
+
+    fn recorded_digest(ward_path: &Path, name: &str) -> Option<crate::checksum::Checksum> {
+        match WardFile::load(ward_path).unwrap().entries.get(name) {
+            Some(WardEntry::Dir { digest, .. }) => digest.clone(),
+            other => panic!("expected directory entry {name}, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_directory_entries_record_child_ward_digest() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path();
+        fs::create_dir_all(root.join("a/b")).unwrap();
+        fs::write(root.join("a/b/file.txt"), "content").unwrap();
+
+        ward_directory(root, digest_test_options(true, None)).unwrap();
+
+        for (parent, name) in [("", "a"), ("a", "b")] {
+            let child_content =
+                fs::read_to_string(root.join(parent).join(name).join(".treeward")).unwrap();
+            assert_eq!(
+                recorded_digest(&root.join(parent).join(".treeward"), name),
+                Some(content_digest(&child_content, HashAlgorithm::Sha256))
+            );
+        }
+    }
+
+    #[test]
+    fn test_nested_change_rewrites_ancestors_only() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path();
+        fs::create_dir_all(root.join("a/b")).unwrap();
+        fs::create_dir(root.join("c")).unwrap();
+        ward_directory(root, digest_test_options(true, None)).unwrap();
+
+        fs::write(root.join("a/b/new.txt"), "new").unwrap();
+        let result = ward_directory(root, digest_test_options(false, None)).unwrap();
+
+        assert_eq!(
+            result.ward_files_updated,
+            vec![
+                PathBuf::from(".treeward"),
+                PathBuf::from("a/.treeward"),
+                PathBuf::from("a/b/.treeward"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_recorded_digest_algorithm_is_kept() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path();
+        fs::create_dir(root.join("sub")).unwrap();
+        ward_directory(root, digest_test_options(true, None)).unwrap();
+
+        fs::write(root.join("sub/new.txt"), "new").unwrap();
+        let options = WardOptions {
+            hash: HashAlgorithm::Blake3,
+            ..digest_test_options(false, None)
+        };
+        ward_directory(root, options).unwrap();
+
+        let digest = recorded_digest(&root.join(".treeward"), "sub").unwrap();
+        assert_eq!(digest.algorithm, HashAlgorithm::Sha256);
+    }
+
+    #[test]
+    fn test_tampered_ward_file_requires_explicit_acceptance() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path();
+        fs::create_dir(root.join("sub")).unwrap();
+        fs::write(root.join("sub/file.txt"), "content").unwrap();
+        ward_directory(root, digest_test_options(true, None)).unwrap();
+
+        // Forget the file: without the digest chain, update would silently
+        // re-record it as if it had just been added.
+        WardFile::new(Default::default())
+            .save(&root.join("sub/.treeward"))
+            .unwrap();
+
+        let result = ward_directory(root, digest_test_options(false, None));
+        assert!(
+            matches!(result, Err(WardError::DigestMismatch(ref path)) if path == "sub/.treeward"),
+            "{result:?}"
+        );
+
+        let status = compute_status(root, &StatusOptions::default()).unwrap();
+        assert_eq!(status.digest_mismatches, vec!["sub/.treeward"]);
+        // The fingerprint alone does not re-chain a mismatching ward file.
+        let result = ward_directory(
+            root,
+            digest_test_options(false, Some(status.fingerprint.clone())),
+        );
+        assert!(
+            matches!(result, Err(WardError::DigestMismatch(_))),
+            "{result:?}"
+        );
+        ward_directory(
+            root,
+            WardOptions {
+                accept_digest_mismatch: true,
+                ..digest_test_options(false, Some(status.fingerprint))
+            },
+        )
+        .unwrap();
+
+        let status = compute_status(root, &StatusOptions::default()).unwrap();
+        assert!(status.digest_mismatches.is_empty());
+        assert!(status.statuses.is_empty());
+    }
+
+    fn signing_test_options(init: bool, seed: u8) -> WardOptions {
+        WardOptions {
+            signing_key: Some(SigningKey::Ed25519(ed25519_dalek::SigningKey::from_bytes(
+                &[seed; 32],
+            ))),
+            ..digest_test_options(init, None)
+        }
+    }
+
+    fn signature_verifies(dir: &Path, seed: u8) -> bool {
+        let key = SigningKey::Ed25519(ed25519_dalek::SigningKey::from_bytes(&[seed; 32]));
+        let content = fs::read_to_string(dir.join(".treeward")).unwrap();
+        read_signature(&crate::util::dir_handle::DirHandle::open(dir).unwrap(), dir)
+            .unwrap()
+            .is_some_and(|signature| key.trusted_key().verifies(&content, &signature))
+    }
+
+    #[test]
+    fn test_signing_key_signs_every_ward_file() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path();
+        fs::create_dir_all(root.join("a/b")).unwrap();
+        fs::create_dir(root.join("c")).unwrap();
+
+        let result = ward_directory(root, signing_test_options(true, 1)).unwrap();
+
+        assert_eq!(
+            result.signatures_updated,
+            vec![
+                PathBuf::from(".treeward.sig"),
+                PathBuf::from("a/.treeward.sig"),
+                PathBuf::from("a/b/.treeward.sig"),
+                PathBuf::from("c/.treeward.sig"),
+            ]
+        );
+        for dir in ["", "a", "a/b", "c"] {
+            assert!(signature_verifies(&root.join(dir), 1), "{dir}");
+        }
+
+        // Signatures that are still valid are left alone.
+        let result = ward_directory(root, signing_test_options(false, 1)).unwrap();
+        assert!(result.signatures_updated.is_empty());
+    }
+
+    #[test]
+    fn test_signing_re_signs_rewritten_and_foreign_signatures() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path();
+        fs::create_dir(root.join("a")).unwrap();
+        fs::create_dir(root.join("c")).unwrap();
+        ward_directory(root, signing_test_options(true, 1)).unwrap();
+        // `c` was signed by some other key.
+        ward_directory(
+            &root.join("c"),
+            WardOptions {
+                allow_init: true,
+                ..signing_test_options(false, 2)
+            },
+        )
+        .unwrap();
+
+        fs::write(root.join("a/new.txt"), "new").unwrap();
+        let result = ward_directory(root, signing_test_options(false, 1)).unwrap();
+
+        assert_eq!(
+            result.signatures_updated,
+            vec![
+                PathBuf::from(".treeward.sig"),
+                PathBuf::from("a/.treeward.sig"),
+                PathBuf::from("c/.treeward.sig"),
+            ]
+        );
+        for dir in ["", "a", "c"] {
+            assert!(signature_verifies(&root.join(dir), 1), "{dir}");
+        }
+    }
+
+    #[test]
+    fn test_unsigned_rewrite_removes_stale_signature() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path();
+        fs::create_dir(root.join("a")).unwrap();
+        fs::create_dir(root.join("c")).unwrap();
+        ward_directory(root, signing_test_options(true, 1)).unwrap();
+
+        fs::write(root.join("a/new.txt"), "new").unwrap();
+        ward_directory(root, digest_test_options(false, None)).unwrap();
+
+        assert!(!root.join(".treeward.sig").exists());
+        assert!(!root.join("a/.treeward.sig").exists());
+        // Not rewritten, so its signature is still valid and kept.
+        assert!(signature_verifies(&root.join("c"), 1));
+    }
+
+    #[test]
+    fn test_dry_run_writes_no_signatures() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path();
+
+        let result = ward_directory(
+            root,
+            WardOptions {
+                dry_run: true,
+                ..signing_test_options(true, 1)
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            result.signatures_updated,
+            vec![PathBuf::from(".treeward.sig")]
+        );
+        assert!(!root.join(".treeward.sig").exists());
+    }
+
+    #[test]
+    fn test_set_protection_records_setting_and_previous() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path();
+        fs::create_dir(root.join("sub")).unwrap();
+        fs::write(root.join("sub/file.txt"), "content").unwrap();
+        ward_directory(root, digest_test_options(true, None)).unwrap();
+
+        let enabled = Protection::Enabled { loss_tolerance: 5 };
+        let (result, previous) =
+            set_protection(root, enabled, false, NonZeroUsize::MIN, Vec::new()).unwrap();
+        assert!(previous.is_empty());
+        assert_eq!(result.fec_files_written.len(), 1);
+        assert_eq!(
+            WardFile::load(&root.join("sub/.treeward"))
+                .unwrap()
+                .protection(),
+            enabled
+        );
+
+        let (result, previous) = set_protection(
+            root,
+            Protection::Disabled,
+            false,
+            NonZeroUsize::MIN,
+            vec![root.join("sub")],
+        )
+        .unwrap();
+        assert_eq!(previous.get(Path::new("sub")), Some(&enabled));
+        // The root is on the way down to `sub` and keeps its setting.
+        assert!(!previous.contains_key(Path::new("")));
+        assert_eq!(result.fec_files_removed.len(), 1);
+        assert!(!root.join("sub/.treeward-fec").exists());
+        assert_eq!(
+            WardFile::load(&root.join(".treeward"))
+                .unwrap()
+                .protection(),
+            enabled
+        );
+    }
+
+    #[test]
+    fn test_set_protection_refuses_pending_changes() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path();
+        fs::write(root.join("file.txt"), "content").unwrap();
+        ward_directory(root, digest_test_options(true, None)).unwrap();
+        fs::write(root.join("added.txt"), "added").unwrap();
+
+        let result = set_protection(
+            root,
+            Protection::Enabled { loss_tolerance: 10 },
+            false,
+            NonZeroUsize::MIN,
+            Vec::new(),
+        );
+
+        assert!(matches!(result, Err(WardError::PendingChanges(1))));
+        assert!(
+            WardFile::load(&root.join(".treeward"))
+                .unwrap()
+                .protection()
+                .is_disabled()
+        );
+    }
 }
@@ -0,0 +1,656 @@
+//! Directory handles for walks that must not be redirected through symlinks.
+//!
+//! A walk opens its canonical root once and every directory below it relative
+//! to the parent's handle with `O_NOFOLLOW | O_DIRECTORY`, then lists,
+//! inspects, reads and writes entries relative to the handle (`fstatat`,
+//! `openat`, `renameat`, ...). Once a directory is open, swapping it (or any
+//! directory above it) for a symlink cannot lead the operation anywhere else;
+//! a swap at the point of opening fails with `ELOOP` or `ENOTDIR` instead (see
+//! `is_swap`).
+//!
+//! On other platforms a handle is just the directory's path and every
+//! operation goes by path, without that guarantee.
+
+use std::ffi::OsStr;
+use std::io;
+use std::time::SystemTime;
+
+/// Identity of a directory (device and inode number), recorded when a walk
+/// opens it so that a later reopen can tell whether it still is the same
+/// directory. Always equal on platforms without inode numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct DirId {
+    dev: u64,
+    ino: u64,
+}
+
+/// The type of a directory entry, as reported without following symlinks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum EntryType {
+    File,
+    Dir,
+    Symlink,
+    Fifo,
+    Socket,
+    CharDevice,
+    BlockDevice,
+    Unknown,
+}
+
+/// Metadata of a directory entry, as `lstat` reports it. The fields besides
+/// the type, mtime and size are zero on platforms without them.
+#[derive(Debug, Clone)]
+pub(crate) struct EntryStat {
+    pub entry_type: EntryType,
+    pub mtime: SystemTime,
+    pub size: u64,
+    /// Permission bits, without the file type.
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub nlink: u64,
+    pub dev: u64,
+    pub ino: u64,
+    pub rdev: u64,
+}
+
+impl EntryStat {
+    pub fn is_file(&self) -> bool {
+        self.entry_type == EntryType::File
+    }
+}
+
+pub(crate) use imp::{DirHandle, is_swap};
+
+#[cfg(unix)]
+mod imp {
+    use super::{DirId, EntryStat, EntryType};
+    use std::ffi::{CStr, CString, OsStr, OsString};
+    use std::fs::File;
+    use std::io;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::os::unix::ffi::{OsStrExt, OsStringExt};
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, SystemTime};
+
+    /// An open directory.
+    #[derive(Debug)]
+    pub(crate) struct DirHandle {
+        fd: OwnedFd,
+    }
+
+    /// Whether a failure to open a directory (or file) that was just seen as
+    /// one means that it has been replaced: by a symlink (`ELOOP` under
+    /// `O_NOFOLLOW`) or by something that is not a directory.
+    pub(crate) fn is_swap(e: &io::Error) -> bool {
+        matches!(e.raw_os_error(), Some(libc::ELOOP) | Some(libc::ENOTDIR))
+    }
+
+    fn c_name(name: &OsStr) -> io::Result<CString> {
+        CString::new(name.as_bytes()).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+    }
+
+    /// Wraps the result of a call returning a new file descriptor.
+    fn owned_fd(fd: libc::c_int) -> io::Result<OwnedFd> {
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `fd` was just returned by the kernel and is owned by no one
+        // else.
+        Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+    }
+
+    fn check(result: libc::c_int) -> io::Result<()> {
+        if result < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    impl DirHandle {
+        /// Opens the directory at `path`, following symlinks. Only for the
+        /// root of a walk; everything below is opened with `open_dir`.
+        pub fn open(path: &Path) -> io::Result<Self> {
+            let path = c_name(path.as_os_str())?;
+            // SAFETY: `path` is NUL-terminated.
+            let fd = unsafe {
+                libc::open(
+                    path.as_ptr(),
+                    libc::O_RDONLY | libc::O_DIRECTORY | libc::O_CLOEXEC,
+                )
+            };
+            Ok(DirHandle { fd: owned_fd(fd)? })
+        }
+
+        /// Opens the subdirectory `name`, failing if it is not a directory or
+        /// is a symlink (see `is_swap`).
+        pub fn open_dir(&self, name: &OsStr) -> io::Result<Self> {
+            let fd = self.openat(
+                name,
+                libc::O_RDONLY | libc::O_DIRECTORY | libc::O_NOFOLLOW | libc::O_CLOEXEC,
+                0,
+            )?;
+            Ok(DirHandle { fd })
+        }
+
+        /// Opens the directory at `relative`, a path of plain names below
+        /// this one, one component at a time with `open_dir`.
+        pub fn open_path(&self, relative: &Path) -> io::Result<Self> {
+            let mut dir = self.open_dir(OsStr::new("."))?;
+            for name in relative.iter() {
+                dir = dir.open_dir(name)?;
+            }
+            Ok(dir)
+        }
+
+        pub fn id(&self) -> io::Result<DirId> {
+            let mut stat = std::mem::MaybeUninit::<libc::stat>::uninit();
+            // SAFETY: `stat` is valid for writing a `struct stat`.
+            check(unsafe { libc::fstat(self.fd.as_raw_fd(), stat.as_mut_ptr()) })?;
+            // SAFETY: fstat succeeded, so it filled in `stat`.
+            let stat = unsafe { stat.assume_init() };
+            #[allow(clippy::unnecessary_cast)]
+            Ok(DirId {
+                dev: stat.st_dev as u64,
+                ino: stat.st_ino as u64,
+            })
+        }
+
+        /// Names of the entries, without `.` and `..`, in no particular order.
+        pub fn entry_names(&self) -> io::Result<Vec<OsString>> {
+            // A fresh open of "." gets its own read position, so listing
+            // never disturbs (or depends on) other uses of this handle.
+            let fd = self.openat(
+                OsStr::new("."),
+                libc::O_RDONLY | libc::O_DIRECTORY | libc::O_CLOEXEC,
+                0,
+            )?;
+            let raw_fd = fd.as_raw_fd();
+            // SAFETY: `raw_fd` is an open directory; on success the stream
+            // owns it, so ownership is given up below.
+            let stream = unsafe { libc::fdopendir(raw_fd) };
+            if stream.is_null() {
+                return Err(io::Error::last_os_error());
+            }
+            std::mem::forget(fd);
+
+            let mut names = Vec::new();
+            let result = loop {
+                // readdir reports errors only through errno.
+                set_errno(0);
+                // SAFETY: `stream` is an open directory stream.
+                let entry = unsafe { libc::readdir(stream) };
+                if entry.is_null() {
+                    let e = io::Error::last_os_error();
+                    break if e.raw_os_error() == Some(0) {
+                        Ok(())
+                    } else {
+                        Err(e)
+                    };
+                }
+                // SAFETY: readdir returned an entry with a NUL-terminated
+                // name, valid until the next readdir call.
+                let name = unsafe { CStr::from_ptr((*entry).d_name.as_ptr()) }.to_bytes();
+                if name != b"." && name != b".." {
+                    names.push(OsString::from_vec(name.to_vec()));
+                }
+            };
+            // SAFETY: `stream` is open and not used after this.
+            unsafe { libc::closedir(stream) };
+            result.map(|()| names)
+        }
+
+        /// Metadata of the entry `name`, not following a symlink.
+        pub fn stat(&self, name: &OsStr) -> io::Result<EntryStat> {
+            let name = c_name(name)?;
+            let mut stat = std::mem::MaybeUninit::<libc::stat>::uninit();
+            // SAFETY: `name` is NUL-terminated and `stat` is valid for
+            // writing a `struct stat`.
+            check(unsafe {
+                libc::fstatat(
+                    self.fd.as_raw_fd(),
+                    name.as_ptr(),
+                    stat.as_mut_ptr(),
+                    libc::AT_SYMLINK_NOFOLLOW,
+                )
+            })?;
+            // SAFETY: fstatat succeeded, so it filled in `stat`.
+            Ok(entry_stat(&unsafe { stat.assume_init() }))
+        }
+
+        /// Target of the symlink `name`.
+        pub fn read_link(&self, name: &OsStr) -> io::Result<PathBuf> {
+            let name = c_name(name)?;
+            let mut buf = vec![0u8; 256];
+            loop {
+                // SAFETY: `name` is NUL-terminated and `buf` is valid for
+                // `buf.len()` bytes.
+                let n = unsafe {
+                    libc::readlinkat(
+                        self.fd.as_raw_fd(),
+                        name.as_ptr(),
+                        buf.as_mut_ptr().cast(),
+                        buf.len(),
+                    )
+                };
+                if n < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                // A target that fills the buffer may have been truncated.
+                if (n as usize) < buf.len() {
+                    buf.truncate(n as usize);
+                    return Ok(PathBuf::from(OsString::from_vec(buf)));
+                }
+                buf.resize(buf.len() * 2, 0);
+            }
+        }
+
+        /// Opens the entry `name` for reading, failing with `ELOOP` if it is
+        /// a symlink. `O_NONBLOCK` keeps the open from waiting for a writer
+        /// if a FIFO was swapped in; it has no effect on regular files.
+        pub fn open_file(&self, name: &OsStr) -> io::Result<File> {
+            let fd = self.openat(
+                name,
+                libc::O_RDONLY | libc::O_NOFOLLOW | libc::O_NONBLOCK | libc::O_CLOEXEC,
+                0,
+            )?;
+            Ok(File::from(fd))
+        }
+
+        /// Opens the entry `name` itself, whatever its type and without
+        /// following a symlink, for inspection only (`O_PATH`): no permission
+        /// on it is needed, and a device is not opened.
+        #[cfg(target_os = "linux")]
+        pub fn open_entry(&self, name: &OsStr) -> io::Result<OwnedFd> {
+            self.openat(name, libc::O_PATH | libc::O_NOFOLLOW | libc::O_CLOEXEC, 0)
+        }
+
+        /// Creates the file `name`, failing if any entry of that name exists.
+        /// The file gets mode 0666 less the process umask, like any normally
+        /// created file.
+        pub fn create_new(&self, name: &OsStr) -> io::Result<File> {
+            let fd = self.openat(
+                name,
+                libc::O_WRONLY | libc::O_CREAT | libc::O_EXCL | libc::O_NOFOLLOW | libc::O_CLOEXEC,
+                0o666,
+            )?;
+            Ok(File::from(fd))
+        }
+
+        /// Opens the file `name` for appending, creating it (like
+        /// `create_new`) if there is none, and failing with `ELOOP` if it is
+        /// a symlink.
+        pub fn open_append(&self, name: &OsStr) -> io::Result<File> {
+            let fd = self.openat(
+                name,
+                libc::O_WRONLY
+                    | libc::O_APPEND
+                    | libc::O_CREAT
+                    | libc::O_NOFOLLOW
+                    | libc::O_CLOEXEC,
+                0o666,
+            )?;
+            Ok(File::from(fd))
+        }
+
+        /// Renames the entry `from` to `to`, both in this directory,
+        /// replacing `to`.
+        pub fn rename(&self, from: &OsStr, to: &OsStr) -> io::Result<()> {
+            let (from, to) = (c_name(from)?, c_name(to)?);
+            let fd = self.fd.as_raw_fd();
+            // SAFETY: both names are NUL-terminated.
+            check(unsafe { libc::renameat(fd, from.as_ptr(), fd, to.as_ptr()) })
+        }
+
+        /// Moves the entry `from` of this directory to `to` in `to_dir`,
+        /// replacing `to`. Fails with `EXDEV` across filesystems.
+        pub fn rename_to(&self, from: &OsStr, to_dir: &DirHandle, to: &OsStr) -> io::Result<()> {
+            let (from, to) = (c_name(from)?, c_name(to)?);
+            // SAFETY: both names are NUL-terminated.
+            check(unsafe {
+                libc::renameat(
+                    self.fd.as_raw_fd(),
+                    from.as_ptr(),
+                    to_dir.fd.as_raw_fd(),
+                    to.as_ptr(),
+                )
+            })
+        }
+
+        /// Removes the file (or symlink) `name`.
+        pub fn remove_file(&self, name: &OsStr) -> io::Result<()> {
+            let name = c_name(name)?;
+            // SAFETY: `name` is NUL-terminated.
+            check(unsafe { libc::unlinkat(self.fd.as_raw_fd(), name.as_ptr(), 0) })
+        }
+
+        /// Creates the subdirectory `name`, with mode 0777 less the process
+        /// umask.
+        pub fn create_dir(&self, name: &OsStr) -> io::Result<()> {
+            let name = c_name(name)?;
+            // SAFETY: `name` is NUL-terminated.
+            check(unsafe { libc::mkdirat(self.fd.as_raw_fd(), name.as_ptr(), 0o777) })
+        }
+
+        /// Removes the empty subdirectory `name`.
+        pub fn remove_dir(&self, name: &OsStr) -> io::Result<()> {
+            let name = c_name(name)?;
+            // SAFETY: `name` is NUL-terminated.
+            check(unsafe { libc::unlinkat(self.fd.as_raw_fd(), name.as_ptr(), libc::AT_REMOVEDIR) })
+        }
+
+        /// Fsyncs the directory, making renames into it durable.
+        pub fn sync(&self) -> io::Result<()> {
+            // SAFETY: plain call on an open descriptor.
+            check(unsafe { libc::fsync(self.fd.as_raw_fd()) })
+        }
+
+        fn openat(
+            &self,
+            name: &OsStr,
+            flags: libc::c_int,
+            mode: libc::c_uint,
+        ) -> io::Result<OwnedFd> {
+            let name = c_name(name)?;
+            // SAFETY: `name` is NUL-terminated.
+            owned_fd(unsafe { libc::openat(self.fd.as_raw_fd(), name.as_ptr(), flags, mode) })
+        }
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn set_errno(value: libc::c_int) {
+        // SAFETY: errno is thread-local.
+        unsafe { *libc::__errno_location() = value };
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+    fn set_errno(value: libc::c_int) {
+        // SAFETY: errno is thread-local.
+        unsafe { *libc::__error() = value };
+    }
+
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd"
+    )))]
+    fn set_errno(_value: libc::c_int) {}
+
+    #[allow(clippy::unnecessary_cast)]
+    fn entry_stat(stat: &libc::stat) -> EntryStat {
+        let entry_type = match stat.st_mode & libc::S_IFMT {
+            libc::S_IFREG => EntryType::File,
+            libc::S_IFDIR => EntryType::Dir,
+            libc::S_IFLNK => EntryType::Symlink,
+            libc::S_IFIFO => EntryType::Fifo,
+            libc::S_IFSOCK => EntryType::Socket,
+            libc::S_IFCHR => EntryType::CharDevice,
+            libc::S_IFBLK => EntryType::BlockDevice,
+            _ => EntryType::Unknown,
+        };
+        let (secs, nanos) = (stat.st_mtime as i64, stat.st_mtime_nsec as u32);
+        let mtime = if secs >= 0 {
+            SystemTime::UNIX_EPOCH + Duration::new(secs as u64, nanos)
+        } else {
+            SystemTime::UNIX_EPOCH - Duration::from_secs(secs.unsigned_abs())
+                + Duration::from_nanos(nanos as u64)
+        };
+        EntryStat {
+            entry_type,
+            mtime,
+            size: stat.st_size as u64,
+            mode: stat.st_mode as u32 & 0o7777,
+            uid: stat.st_uid as u32,
+            gid: stat.st_gid as u32,
+            nlink: stat.st_nlink as u64,
+            dev: stat.st_dev as u64,
+            ino: stat.st_ino as u64,
+            rdev: stat.st_rdev as u64,
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use super::{DirId, EntryStat, EntryType};
+    use std::ffi::{OsStr, OsString};
+    use std::fs::File;
+    use std::io;
+    use std::path::{Path, PathBuf};
+
+    /// A directory, by path.
+    #[derive(Debug)]
+    pub(crate) struct DirHandle {
+        path: PathBuf,
+    }
+
+    pub(crate) fn is_swap(_e: &io::Error) -> bool {
+        false
+    }
+
+    impl DirHandle {
+        pub fn open(path: &Path) -> io::Result<Self> {
+            if !std::fs::metadata(path)?.is_dir() {
+                return Err(io::Error::other(format!(
+                    "not a directory: {}",
+                    path.display()
+                )));
+            }
+            Ok(DirHandle {
+                path: path.to_path_buf(),
+            })
+        }
+
+        pub fn open_dir(&self, name: &OsStr) -> io::Result<Self> {
+            let path = self.path.join(name);
+            if !std::fs::symlink_metadata(&path)?.is_dir() {
+                return Err(io::Error::other(format!(
+                    "not a directory: {}",
+                    path.display()
+                )));
+            }
+            Ok(DirHandle { path })
+        }
+
+        pub fn open_path(&self, relative: &Path) -> io::Result<Self> {
+            let mut dir = DirHandle {
+                path: self.path.clone(),
+            };
+            for name in relative.iter() {
+                dir = dir.open_dir(name)?;
+            }
+            Ok(dir)
+        }
+
+        pub fn id(&self) -> io::Result<DirId> {
+            Ok(DirId { dev: 0, ino: 0 })
+        }
+
+        pub fn entry_names(&self) -> io::Result<Vec<OsString>> {
+            std::fs::read_dir(&self.path)?
+                .map(|entry| Ok(entry?.file_name()))
+                .collect()
+        }
+
+        pub fn stat(&self, name: &OsStr) -> io::Result<EntryStat> {
+            let metadata = std::fs::symlink_metadata(self.path.join(name))?;
+            let file_type = metadata.file_type();
+            let entry_type = if file_type.is_symlink() {
+                EntryType::Symlink
+            } else if file_type.is_dir() {
+                EntryType::Dir
+            } else if file_type.is_file() {
+                EntryType::File
+            } else {
+                EntryType::Unknown
+            };
+            Ok(EntryStat {
+                entry_type,
+                mtime: metadata.modified()?,
+                size: metadata.len(),
+                mode: 0,
+                uid: 0,
+                gid: 0,
+                nlink: 0,
+                dev: 0,
+                ino: 0,
+                rdev: 0,
+            })
+        }
+
+        pub fn read_link(&self, name: &OsStr) -> io::Result<PathBuf> {
+            std::fs::read_link(self.path.join(name))
+        }
+
+        pub fn open_file(&self, name: &OsStr) -> io::Result<File> {
+            File::open(self.path.join(name))
+        }
+
+        pub fn create_new(&self, name: &OsStr) -> io::Result<File> {
+            std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(self.path.join(name))
+        }
+
+        pub fn open_append(&self, name: &OsStr) -> io::Result<File> {
+            std::fs::OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(self.path.join(name))
+        }
+
+        pub fn rename(&self, from: &OsStr, to: &OsStr) -> io::Result<()> {
+            std::fs::rename(self.path.join(from), self.path.join(to))
+        }
+
+        pub fn rename_to(&self, from: &OsStr, to_dir: &DirHandle, to: &OsStr) -> io::Result<()> {
+            std::fs::rename(self.path.join(from), to_dir.path.join(to))
+        }
+
+        pub fn remove_file(&self, name: &OsStr) -> io::Result<()> {
+            std::fs::remove_file(self.path.join(name))
+        }
+
+        pub fn create_dir(&self, name: &OsStr) -> io::Result<()> {
+            std::fs::create_dir(self.path.join(name))
+        }
+
+        pub fn remove_dir(&self, name: &OsStr) -> io::Result<()> {
+            std::fs::remove_dir(self.path.join(name))
+        }
+
+        /// std exposes no way to fsync a directory here, so rename
+        /// durability is not guaranteed.
+        pub fn sync(&self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+}
+
+impl DirHandle {
+    /// Reads the entry `name` (see `open_file`) into a string.
+    pub fn read_to_string(&self, name: &OsStr) -> io::Result<String> {
+        io::read_to_string(self.open_file(name)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsString;
+    use std::fs;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_lists_and_stats_entries() {
+        let temp = TempDir::new().unwrap();
+        fs::write(temp.path().join("file.txt"), "content").unwrap();
+        fs::create_dir(temp.path().join("sub")).unwrap();
+
+        let dir = DirHandle::open(temp.path()).unwrap();
+        let mut names = dir.entry_names().unwrap();
+        names.sort();
+
+        assert_eq!(names, vec![OsString::from("file.txt"), "sub".into()]);
+        let stat = dir.stat(OsStr::new("file.txt")).unwrap();
+        assert!(stat.is_file());
+        assert_eq!(stat.size, 7);
+        assert_eq!(
+            stat.mtime,
+            fs::metadata(temp.path().join("file.txt"))
+                .unwrap()
+                .modified()
+                .unwrap()
+        );
+        assert_eq!(
+            dir.stat(OsStr::new("sub")).unwrap().entry_type,
+            EntryType::Dir
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_open_dir_refuses_symlink() {
+        let temp = TempDir::new().unwrap();
+        fs::create_dir(temp.path().join("real")).unwrap();
+        std::os::unix::fs::symlink(temp.path().join("real"), temp.path().join("link")).unwrap();
+
+        let dir = DirHandle::open(temp.path()).unwrap();
+
+        let e = dir.open_dir(OsStr::new("link")).unwrap_err();
+        assert!(is_swap(&e), "{e}");
+        let e = dir.open_path(Path::new("link/below")).unwrap_err();
+        assert!(is_swap(&e), "{e}");
+        assert!(dir.open_path(Path::new("real")).is_ok());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_handle_keeps_pointing_at_renamed_directory() {
+        let temp = TempDir::new().unwrap();
+        fs::create_dir(temp.path().join("a")).unwrap();
+        fs::write(temp.path().join("a/file.txt"), "old").unwrap();
+
+        let root = DirHandle::open(temp.path()).unwrap();
+        let a = root.open_dir(OsStr::new("a")).unwrap();
+        let id = a.id().unwrap();
+        fs::rename(temp.path().join("a"), temp.path().join("moved")).unwrap();
+        fs::create_dir(temp.path().join("a")).unwrap();
+
+        assert_eq!(
+            a.read_to_string(OsStr::new("file.txt")).unwrap(),
+            "old".to_string()
+        );
+        assert_ne!(root.open_dir(OsStr::new("a")).unwrap().id().unwrap(), id);
+    }
+
+    #[test]
+    fn test_write_and_rename_through_handle() {
+        let temp = TempDir::new().unwrap();
+        let dir = DirHandle::open(temp.path()).unwrap();
+
+        use std::io::Write;
+        dir.create_new(OsStr::new("tmp"))
+            .unwrap()
+            .write_all(b"data")
+            .unwrap();
+        assert!(dir.create_new(OsStr::new("tmp")).is_err());
+        dir.rename(OsStr::new("tmp"), OsStr::new("final")).unwrap();
+        dir.sync().unwrap();
+
+        assert_eq!(fs::read(temp.path().join("final")).unwrap(), b"data");
+        assert!(!temp.path().join("tmp").exists());
+        dir.remove_file(OsStr::new("final")).unwrap();
+        assert!(!temp.path().join("final").exists());
+
+        dir.create_dir(OsStr::new("sub")).unwrap();
+        assert!(temp.path().join("sub").is_dir());
+        dir.remove_dir(OsStr::new("sub")).unwrap();
+        assert!(!temp.path().join("sub").exists());
+    }
+}
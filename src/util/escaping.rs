@@ -1,5 +1,6 @@
 //! Escaping helpers for terminal-facing text.
 
+use super::names::RAW_BYTE_MARKER;
 use std::borrow::Cow;
 
 /// Escape control characters so untrusted text cannot inject terminal escape sequences.
@@ -14,15 +15,25 @@ use std::borrow::Cow;
 /// are rendered with Rust's debug escapes (`\n`, `\u{1b}`, ...). Literal
 /// backslashes are doubled so escaped output stays unambiguous: a name
 /// containing the literal text `\u{1b}` cannot be confused with an escaped
-/// real ESC. All other Unicode passes through unchanged.
+/// real ESC. A raw byte of a non-UTF-8 name (see `names`) is rendered as
+/// `\xe9`. All other Unicode passes through unchanged.
 pub(crate) fn escape_control(s: &str) -> Cow<'_, str> {
     if !s.chars().any(|c| c.is_control() || c == '\\') {
         return Cow::Borrowed(s);
     }
     let mut out = String::with_capacity(s.len());
-    for c in s.chars() {
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
         if c == '\\' {
             out.push_str("\\\\");
+        } else if let Some(hex) = (c == RAW_BYTE_MARKER)
+            .then(|| chars.as_str().get(..2))
+            .flatten()
+            .filter(|hex| hex.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')))
+        {
+            out.push_str("\\x");
+            out.push_str(hex);
+            chars.nth(1);
         } else if c.is_control() {
             out.extend(c.escape_debug());
         } else {
@@ -61,4 +72,12 @@ mod tests {
         // distinguishable from a real escaped ESC.
         assert_eq!(escape_control("fake\\u{1b}.txt"), "fake\\\\u{1b}.txt");
     }
+
+    #[test]
+    fn escape_control_renders_raw_bytes_as_hex() {
+        assert_eq!(escape_control("caf\0e9.txt"), r"caf\xe9.txt");
+        assert_eq!(escape_control(r"caf\xe9.txt"), r"caf\\xe9.txt");
+        // A NUL that is not a raw byte marker stays a control escape.
+        assert_eq!(escape_control("a\0zz"), r"a\0zz");
+    }
 }
@@ -0,0 +1,16 @@
+//! Filesystem helpers for walks that follow ward entries.
+
+use std::path::Path;
+
+/// Whether `path` is a directory, without following a final symlink.
+///
+/// A directory entry replaced by a symlink must not lead a walk that follows
+/// ward entries into (and possibly write ward files in) a tree outside the
+/// one being walked.
+pub(crate) fn is_real_dir(path: &Path) -> std::io::Result<bool> {
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) => Ok(metadata.is_dir()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
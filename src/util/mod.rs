@@ -6,3 +6,8 @@ pub mod hashing;
 
 pub(crate) mod escaping;
 pub(crate) use escaping::escape_control;
+
+pub(crate) mod dir_handle;
+pub(crate) mod fs;
+pub(crate) mod names;
+pub(crate) use fs::is_real_dir;
@@ -0,0 +1,182 @@
+//! Lossless string form of entry names that are not valid UTF-8.
+//!
+//! Entry names and relative paths are `String`s everywhere: as ward file keys,
+//! in status entries and in fingerprint records. A name that is valid UTF-8 is
+//! its own string form. On Unix, where a name is an arbitrary byte sequence,
+//! every byte of an invalid UTF-8 sequence is instead written as U+0000
+//! followed by two lowercase hex digits, so `caf\xe9` becomes `"caf\u0000e9"`.
+//!
+//! No file name can contain a NUL byte, so the encoded form of a non-UTF-8 name
+//! never collides with the string form of any other name, and decoding restores
+//! the exact OS bytes. Since the encoding is byte-wise and leaves `/` alone,
+//! the string form of a relative path is the `/`-join of the string forms of
+//! its components. A string is only ever accepted in its canonical form (see
+//! `is_canonical`), so each name has exactly one string form.
+//!
+//! Other platforms only support names that are valid UTF-8.
+
+use std::borrow::Cow;
+use std::ffi::OsStr;
+
+/// Marks a raw byte in the string form of a name.
+pub(crate) const RAW_BYTE_MARKER: char = '\0';
+
+/// The string form of an OS name or relative path, or `None` if this platform
+/// cannot represent it.
+pub(crate) fn from_os(name: &OsStr) -> Option<Cow<'_, str>> {
+    #[cfg(unix)]
+    {
+        use std::fmt::Write;
+        use std::os::unix::ffi::OsStrExt;
+
+        let bytes = name.as_bytes();
+        if let Ok(name) = std::str::from_utf8(bytes) {
+            return Some(Cow::Borrowed(name));
+        }
+        let mut out = String::with_capacity(bytes.len() * 2);
+        for chunk in bytes.utf8_chunks() {
+            out.push_str(chunk.valid());
+            for byte in chunk.invalid() {
+                write!(out, "{RAW_BYTE_MARKER}{byte:02x}")
+                    .expect("writing to a String cannot fail");
+            }
+        }
+        Some(Cow::Owned(out))
+    }
+    #[cfg(not(unix))]
+    {
+        name.to_str().map(Cow::Borrowed)
+    }
+}
+
+/// The OS name or relative path whose string form is `name`.
+///
+/// `name` is expected in canonical form, as produced by `from_os` or checked
+/// by `is_canonical`. A malformed raw byte is passed through as is, so at
+/// worst the path fails to open (a NUL byte is never a valid path).
+pub(crate) fn to_os(name: &str) -> Cow<'_, OsStr> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStringExt;
+
+        if !name.contains(RAW_BYTE_MARKER) {
+            return Cow::Borrowed(OsStr::new(name));
+        }
+        let bytes = decode(name).unwrap_or_else(|| name.as_bytes().to_vec());
+        Cow::Owned(std::ffi::OsString::from_vec(bytes))
+    }
+    #[cfg(not(unix))]
+    {
+        Cow::Borrowed(OsStr::new(name))
+    }
+}
+
+/// Whether `name` is the string form `from_os` produces for some OS name.
+///
+/// Names read from a ward file must pass this: otherwise two different keys
+/// (say `"é"` and its bytes written as raw bytes) could name the same entry.
+pub(crate) fn is_canonical(name: &str) -> bool {
+    if !name.contains(RAW_BYTE_MARKER) {
+        return true;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+
+        decode(name)
+            .is_some_and(|bytes| from_os(OsStr::from_bytes(&bytes)).as_deref() == Some(name))
+    }
+    #[cfg(not(unix))]
+    {
+        false
+    }
+}
+
+/// The string form of the relative path `path` as seen from `base`, both
+/// relative to the same directory: `path` below `base` loses the `base/`
+/// prefix, anything else climbs out of `base` with `..`, and `base` itself is
+/// `.`. An empty `base` leaves `path` as is.
+pub(crate) fn relative_to(base: &str, path: &str) -> String {
+    if base.is_empty() {
+        return path.to_string();
+    }
+    let base: Vec<&str> = base.split('/').collect();
+    let path: Vec<&str> = path.split('/').collect();
+    let common = base.iter().zip(&path).take_while(|(a, b)| a == b).count();
+    let parts: Vec<&str> = std::iter::repeat_n("..", base.len() - common)
+        .chain(path[common..].iter().copied())
+        .collect();
+    if parts.is_empty() {
+        ".".to_string()
+    } else {
+        parts.join("/")
+    }
+}
+
+/// Raw bytes of an encoded name, or `None` if a marker is not followed by two
+/// lowercase hex digits.
+#[cfg(unix)]
+fn decode(name: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(name.len());
+    let mut chars = name.chars();
+    while let Some(c) = chars.next() {
+        if c == RAW_BYTE_MARKER {
+            let hex: String = chars.by_ref().take(2).collect();
+            if hex.len() != 2 || !hex.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')) {
+                return None;
+            }
+            bytes.push(u8::from_str_radix(&hex, 16).ok()?);
+        } else {
+            let mut buf = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+        }
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn utf8_names_are_their_own_string_form() {
+        assert_eq!(
+            from_os(OsStr::new("café/名前.txt")).unwrap(),
+            "café/名前.txt"
+        );
+        assert_eq!(to_os("café/名前.txt"), OsStr::new("café/名前.txt"));
+        assert!(is_canonical("café"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn non_utf8_names_round_trip() {
+        use std::os::unix::ffi::OsStrExt;
+
+        for raw in [&b"caf\xe9"[..], b"\xff\xfe", b"dir/\xc3", b"\xc3(x\x80"] {
+            let name = from_os(OsStr::from_bytes(raw)).unwrap();
+            assert!(name.contains(RAW_BYTE_MARKER), "{name:?}");
+            assert!(is_canonical(&name));
+            assert_eq!(to_os(&name).as_bytes(), raw);
+        }
+        assert_eq!(from_os(OsStr::from_bytes(b"caf\xe9")).unwrap(), "caf\0e9");
+    }
+
+    #[test]
+    fn relative_to_climbs_out_of_base() {
+        assert_eq!(relative_to("", "a/b"), "a/b");
+        assert_eq!(relative_to("a/b", "a/b/c.txt"), "c.txt");
+        assert_eq!(relative_to("a/b", "a/b"), ".");
+        assert_eq!(relative_to("a/b", "a/x.txt"), "../x.txt");
+        assert_eq!(relative_to("a/b", "ab/c"), "../../ab/c");
+        assert_eq!(relative_to("a/b", "a"), "..");
+    }
+
+    #[test]
+    fn rejects_non_canonical_encodings() {
+        // Valid UTF-8 written as raw bytes, upper-case hex, truncated markers.
+        for name in ["\0c3\0a9", "caf\0E9", "caf\0e", "bad\0name", "\0"] {
+            assert!(!is_canonical(name), "{name:?}");
+        }
+    }
+}
@@ -1,16 +1,41 @@
 //! Persistent `.treeward` schema and I/O.
 //!
 //! This module defines the versioned TOML format that stores per-directory ward
-//! state. Parsing checks file version and schema fields.
+//! state. Parsing checks file version and schema fields. Every historical
+//! version is still readable; writing always produces the current version.
+//!
+//! - Version 1: file entries carry a bare `sha256` hex digest.
+//! - Version 2: file entries carry an algorithm-tagged `checksum`
+//!   (`<algorithm>:<hex>`), so entries in one file may use different algorithms.
+//! - Version 3: directory entries carry a `digest` of the child directory's
+//!   `.treeward` bytes, chaining every ward file up to the root. Entries may
+//!   carry `mode`, `uid` and `gid`, as selected by the metadata's `track`
+//!   list (see `attributes`). FIFOs, sockets and device nodes are recorded
+//!   as `fifo`, `socket`, `char_device` and `block_device` entries, device
+//!   nodes with their `major` and `minor` numbers. The metadata may carry a
+//!   `protection` table (see `protection`), and the root's a `journal` table
+//!   with the head of the journal (see `journal`).
+//!
+//! Entry names are keys in the string form of `util::names`, so names that
+//! are not valid UTF-8 are stored losslessly in every version.
 //!
 //! Saving writes through a temp file, fsync, and rename sequence, followed on
-//! Unix by a parent-directory fsync so the rename itself is durable.
+//! Unix by a parent-directory fsync so the rename itself is durable. Walks
+//! read and write ward files through a handle on their directory (see
+//! `util::dir_handle`), never by path.
 
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 
-use crate::dir_list::TREEWARD_FILENAME;
+use crate::attributes::{self, Attributes, Links, Track};
+use crate::checksum::{self, Checksum, HashAlgorithm};
+use crate::dir_list::{FEC_DIRNAME, SIGNATURE_FILENAME, SpecialKind, TREEWARD_FILENAME};
+use crate::journal::JournalHead;
+use crate::protection::Protection;
+use crate::util::dir_handle::DirHandle;
+use crate::util::names;
 
 #[derive(Debug, thiserror::Error)]
 pub enum WardFileError {
@@ -26,31 +51,76 @@ pub enum WardFileError {
     UnsupportedVersion(u32),
     #[error("Invalid ward entry name: {0}")]
     InvalidEntryName(String),
-    #[error("Invalid sha256 for entry {0}: must be 64 lowercase hex characters")]
-    InvalidSha256(String),
+    #[error(
+        "Invalid checksum for entry {0}: must be a known algorithm and its digest in lowercase hex"
+    )]
+    InvalidChecksum(String),
+    #[error("Invalid mode for entry {0}: must be four octal digits")]
+    InvalidMode(String),
+    #[error("Invalid extended attribute name for entry {0}")]
+    InvalidXattrName(String),
+    #[error("Invalid symlink target for entry {0}")]
+    InvalidSymlinkTarget(String),
+    #[error("Invalid link_group for entry {0}: must be 16 lowercase hex digits next to nlink")]
+    InvalidLinkGroup(String),
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-#[serde(tag = "type", deny_unknown_fields)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum WardEntry {
-    #[serde(rename = "file")]
     File {
-        sha256: String,
+        checksum: Checksum,
         /// Modification time in nanoseconds since Unix epoch.
         /// Modern filesystems (ext4, APFS, etc.) support nanosecond precision.
         mtime_nanos: u64,
         size: u64,
+        attributes: Attributes,
     },
-    #[serde(rename = "dir")]
-    Dir {},
-    #[serde(rename = "symlink")]
-    Symlink { symlink_target: PathBuf },
+    Dir {
+        /// Digest of the child directory's `.treeward` file as written
+        /// (see `content_digest`). `None` only for entries loaded from a
+        /// version 1 or 2 ward file, and transiently while an update fills
+        /// digests in bottom-up.
+        digest: Option<Checksum>,
+        attributes: Attributes,
+    },
+    Symlink {
+        symlink_target: PathBuf,
+        attributes: Attributes,
+    },
+    /// A FIFO, socket or device node. Only its type and device number are
+    /// recorded; it is never opened.
+    Special {
+        kind: SpecialKind,
+        attributes: Attributes,
+    },
+}
+
+impl WardEntry {
+    /// The optional attributes this entry records (see `attributes`).
+    pub fn attributes(&self) -> &Attributes {
+        match self {
+            WardEntry::File { attributes, .. }
+            | WardEntry::Dir { attributes, .. }
+            | WardEntry::Symlink { attributes, .. }
+            | WardEntry::Special { attributes, .. } => attributes,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 struct Metadata {
     version: u32,
+    /// Attributes recorded for this directory's entries, and for entries of
+    /// new subdirectories.
+    #[serde(default, skip_serializing_if = "Track::is_empty")]
+    track: Track,
+    /// FEC protection of this directory's files (see `protection`).
+    #[serde(default, skip_serializing_if = "Protection::is_disabled")]
+    protection: Protection,
+    /// Last record of the journal, in the root's ward file only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    journal: Option<JournalHead>,
 }
 
 /// Helper struct to extract only the metadata section from a TOML file,
@@ -62,26 +132,213 @@ struct MetadataOnly {
     metadata: Metadata,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// Whole-file TOML layout, shared by all versions; only the entry schema varies.
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
+struct TomlWardFile<E> {
+    metadata: Metadata,
+    entries: BTreeMap<String, E>,
+}
+
+/// Version 1 entry schema.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", deny_unknown_fields)]
+enum TomlEntryV1 {
+    #[serde(rename = "file")]
+    File {
+        sha256: String,
+        mtime_nanos: u64,
+        size: u64,
+    },
+    #[serde(rename = "dir")]
+    Dir {},
+    #[serde(rename = "symlink")]
+    Symlink { symlink_target: PathBuf },
+}
+
+/// Version 2 entry schema. `checksum` is `<algorithm>:<hex>`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", deny_unknown_fields)]
+enum TomlEntryV2 {
+    #[serde(rename = "file")]
+    File {
+        checksum: String,
+        mtime_nanos: u64,
+        size: u64,
+    },
+    #[serde(rename = "dir")]
+    Dir {},
+    #[serde(rename = "symlink")]
+    Symlink { symlink_target: PathBuf },
+}
+
+/// Version 3 entry schema: version 2 plus a directory `digest`, in the same
+/// `<algorithm>:<hex>` form as `checksum`, and optional attributes on every
+/// entry type. `mode` is four octal digits (`"0644"`); `xattrs` maps extended
+/// attribute names to `<algorithm>:<hex>` digests of their values.
+///
+/// The attribute fields are repeated per variant because serde's `flatten`
+/// does not combine with `deny_unknown_fields`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", deny_unknown_fields)]
+enum TomlEntryV3 {
+    #[serde(rename = "file")]
+    File {
+        checksum: String,
+        mtime_nanos: u64,
+        size: u64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        mode: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        uid: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        gid: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        xattrs: Option<BTreeMap<String, String>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        nlink: Option<u64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        link_group: Option<String>,
+    },
+    #[serde(rename = "dir")]
+    Dir {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        digest: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        mode: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        uid: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        gid: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        xattrs: Option<BTreeMap<String, String>>,
+    },
+    #[serde(rename = "symlink")]
+    Symlink {
+        /// In the string form of `util::names`.
+        symlink_target: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        mode: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        uid: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        gid: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        xattrs: Option<BTreeMap<String, String>>,
+    },
+    #[serde(rename = "fifo")]
+    Fifo {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        mode: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        uid: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        gid: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        xattrs: Option<BTreeMap<String, String>>,
+    },
+    #[serde(rename = "socket")]
+    Socket {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        mode: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        uid: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        gid: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        xattrs: Option<BTreeMap<String, String>>,
+    },
+    #[serde(rename = "char_device")]
+    CharDevice {
+        major: u32,
+        minor: u32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        mode: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        uid: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        gid: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        xattrs: Option<BTreeMap<String, String>>,
+    },
+    #[serde(rename = "block_device")]
+    BlockDevice {
+        major: u32,
+        minor: u32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        mode: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        uid: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        gid: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        xattrs: Option<BTreeMap<String, String>>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct WardFile {
     metadata: Metadata,
     pub entries: BTreeMap<String, WardEntry>,
 }
 
 impl WardFile {
-    const SUPPORTED_VERSION: u32 = 1;
+    /// Version written by `save`/`to_toml`.
+    pub(crate) const CURRENT_VERSION: u32 = 3;
 
-    /// Create a new WardFile with the current supported version
+    /// Create a new WardFile with the current version
     pub fn new(entries: BTreeMap<String, WardEntry>) -> Self {
         WardFile {
             metadata: Metadata {
-                version: Self::SUPPORTED_VERSION,
+                version: Self::CURRENT_VERSION,
+                track: Track::default(),
+                protection: Protection::default(),
+                journal: None,
             },
             entries,
         }
     }
 
+    /// Set which attributes this directory tracks.
+    pub fn with_track(mut self, track: Track) -> Self {
+        self.metadata.track = track;
+        self
+    }
+
+    /// Attributes this directory's entries record (see `attributes`).
+    pub fn track(&self) -> &Track {
+        &self.metadata.track
+    }
+
+    /// Set whether this directory's files are protected by FEC data.
+    pub fn with_protection(mut self, protection: Protection) -> Self {
+        self.metadata.protection = protection;
+        self
+    }
+
+    /// FEC protection of this directory's files (see `protection`).
+    pub fn protection(&self) -> Protection {
+        self.metadata.protection
+    }
+
+    /// Set the journal head this (root) ward file records.
+    pub fn with_journal_head(mut self, head: Option<JournalHead>) -> Self {
+        self.metadata.journal = head;
+        self
+    }
+
+    /// Last record of the journal, as of the update that wrote this (root)
+    /// ward file.
+    pub fn journal_head(&self) -> Option<&JournalHead> {
+        self.metadata.journal.as_ref()
+    }
+
+    /// Format version this ward file was loaded from (`CURRENT_VERSION` for
+    /// ward files built in memory).
+    pub fn version(&self) -> u32 {
+        self.metadata.version
+    }
+
     /// Parse a TOML string into a WardFile structure
     pub fn from_toml(content: &str) -> Result<Self, WardFileError> {
         // First, extract only the metadata to check version. Otherwise
@@ -90,19 +347,43 @@ impl WardFile {
         // explanation.
         let metadata_only: MetadataOnly = toml::from_str(content)?;
 
-        if metadata_only.metadata.version != Self::SUPPORTED_VERSION {
-            return Err(WardFileError::UnsupportedVersion(
-                metadata_only.metadata.version,
-            ));
-        }
-
-        // Version is supported, now parse the full file
-        let ward_file: WardFile = toml::from_str(content)?;
+        let ward_file = match metadata_only.metadata.version {
+            1 => {
+                let parsed: TomlWardFile<TomlEntryV1> = toml::from_str(content)?;
+                Self::from_parsed(parsed, entry_from_v1)?
+            }
+            2 => {
+                let parsed: TomlWardFile<TomlEntryV2> = toml::from_str(content)?;
+                Self::from_parsed(parsed, entry_from_v2)?
+            }
+            3 => {
+                let parsed: TomlWardFile<TomlEntryV3> = toml::from_str(content)?;
+                Self::from_parsed(parsed, entry_from_v3)?
+            }
+            version => return Err(WardFileError::UnsupportedVersion(version)),
+        };
         ward_file.validate_entry_names()?;
-        ward_file.validate_file_checksums()?;
         Ok(ward_file)
     }
 
+    fn from_parsed<E>(
+        parsed: TomlWardFile<E>,
+        convert: fn(&str, E) -> Result<WardEntry, WardFileError>,
+    ) -> Result<Self, WardFileError> {
+        let entries = parsed
+            .entries
+            .into_iter()
+            .map(|(name, entry)| {
+                let entry = convert(&name, entry)?;
+                Ok((name, entry))
+            })
+            .collect::<Result<_, WardFileError>>()?;
+        Ok(WardFile {
+            metadata: parsed.metadata,
+            entries,
+        })
+    }
+
     /// Reject persisted names that cannot be produced by directory listing.
     ///
     /// A ward file stores only immediate children of one directory. Entry names
@@ -120,170 +401,528 @@ impl WardFile {
         Ok(())
     }
 
-    /// Reject persisted checksums that cannot have been produced by checksumming.
-    ///
-    /// treeward always writes sha256 as 64 lowercase hex characters. Anything
-    /// else in a loaded ward file is corruption or tampering and fails fast at
-    /// parse time, consistent with the `deny_unknown_fields` posture. This also
-    /// keeps hostile bytes out of every downstream consumer of the field
-    /// (display, fingerprint hashing).
-    fn validate_file_checksums(&self) -> Result<(), WardFileError> {
-        for (name, entry) in &self.entries {
-            if let WardEntry::File { sha256, .. } = entry
-                && !is_valid_sha256_hex(sha256)
-            {
-                return Err(WardFileError::InvalidSha256(name.clone()));
-            }
-        }
-
-        Ok(())
-    }
-
-    /// Serialize a WardFile structure to TOML string
+    /// Serialize a WardFile structure to a TOML string in the current version
     pub fn to_toml(&self) -> Result<String, WardFileError> {
-        Ok(toml::to_string_pretty(self)?)
+        let toml_file = TomlWardFile {
+            metadata: Metadata {
+                version: Self::CURRENT_VERSION,
+                track: self.metadata.track.clone(),
+                protection: self.metadata.protection,
+                journal: self.metadata.journal.clone(),
+            },
+            entries: self
+                .entries
+                .iter()
+                .map(|(name, entry)| Ok((name.clone(), entry_to_v3(name, entry)?)))
+                .collect::<Result<_, WardFileError>>()?,
+        };
+        Ok(toml::to_string_pretty(&toml_file)?)
     }
 
-    /// Load a WardFile from the filesystem
+    /// Load a WardFile from the filesystem. Test convenience: production code
+    /// goes through `load_with_content_if_exists`.
+    #[cfg(test)]
     pub fn load(path: &Path) -> Result<Self, WardFileError> {
-        let content = std::fs::read_to_string(path).map_err(|e| {
-            if e.kind() == std::io::ErrorKind::PermissionDenied {
-                WardFileError::PermissionDenied(path.to_path_buf())
-            } else {
-                WardFileError::Io(e)
-            }
-        })?;
-
-        Self::from_toml(&content)
+        Self::from_toml(&read_content(path)?)
     }
 
-    /// Load a WardFile from the filesystem if it exists.
+    /// Load a WardFile and its exact content (from the same read, for checking
+    /// against a recorded `content_digest`) from the filesystem if it exists.
     ///
     /// Missing files return `Ok(None)` so callers can treat the uninitialized
     /// case separately from parse and permission failures.
-    pub(crate) fn load_if_exists(path: &Path) -> Result<Option<Self>, WardFileError> {
-        match Self::load(path) {
-            Ok(wf) => Ok(Some(wf)),
+    pub(crate) fn load_with_content_if_exists(
+        path: &Path,
+    ) -> Result<Option<(Self, String)>, WardFileError> {
+        Self::parse_if_exists(read_content(path))
+    }
+
+    /// Like `load_with_content_if_exists`, for the `.treeward` in the open
+    /// directory `dir`, which is at `dir_path`. A `.treeward` that is a
+    /// symlink is not followed, and fails to load.
+    pub(crate) fn load_with_content_if_exists_in(
+        dir: &DirHandle,
+        dir_path: &Path,
+    ) -> Result<Option<(Self, String)>, WardFileError> {
+        let content = dir
+            .read_to_string(TREEWARD_FILENAME.as_ref())
+            .map_err(|e| io_error(&dir_path.join(TREEWARD_FILENAME), e));
+        Self::parse_if_exists(content)
+    }
+
+    fn parse_if_exists(
+        content: Result<String, WardFileError>,
+    ) -> Result<Option<(Self, String)>, WardFileError> {
+        match content {
+            Ok(content) => Ok(Some((Self::from_toml(&content)?, content))),
             Err(WardFileError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
             Err(e) => Err(e),
         }
     }
 
-    /// Save a WardFile to the filesystem atomically.
-    ///
-    /// Writes to a temporary file, fsyncs it, atomically renames it into
-    /// place, then (on Unix) fsyncs the parent directory so the rename is
-    /// durable. The resulting file gets standard umask-derived permissions,
-    /// like any normally created file.
+    /// Save a WardFile to the filesystem atomically (see `write_atomic`).
+    /// Test convenience: production code goes through `save_in`.
+    #[cfg(test)]
     pub fn save(&self, path: &Path) -> Result<(), WardFileError> {
-        use std::io::Write;
-
-        let content = self.to_toml()?;
-
-        let parent = path.parent().unwrap_or(Path::new("."));
-
-        // The only mutation of `builder` is the unix-only permissions()
-        // call below, so non-unix builds would fail -D warnings with
-        // unused_mut without this.
-        #[cfg_attr(not(unix), allow(unused_mut))]
-        let mut builder = tempfile::Builder::new();
-        // NamedTempFile defaults to mode 0600 (right for secrets, wrong here):
-        // persist() carries that to the final file, so every .treeward would
-        // end up owner-only and other users in a group-shared tree would hit
-        // PermissionDenied on verify. Ask for 0666 so the kernel applies the
-        // process umask, matching what File::create would produce.
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            builder.permissions(std::fs::Permissions::from_mode(0o666));
-        }
+        write_atomic(path, self.to_toml()?.as_bytes())
+    }
 
-        let mut temp_file = builder.tempfile_in(parent).map_err(|e| {
-            if e.kind() == std::io::ErrorKind::PermissionDenied {
-                WardFileError::PermissionDenied(parent.to_path_buf())
-            } else {
-                WardFileError::Io(e)
-            }
-        })?;
+    /// Save a WardFile as the `.treeward` of the open directory `dir`, which
+    /// is at `dir_path` (see `write_atomic_in`).
+    pub(crate) fn save_in(&self, dir: &DirHandle, dir_path: &Path) -> Result<(), WardFileError> {
+        write_atomic_in(dir, dir_path, TREEWARD_FILENAME, self.to_toml()?.as_bytes())
+    }
+}
 
-        temp_file.write_all(content.as_bytes()).map_err(|e| {
-            if e.kind() == std::io::ErrorKind::PermissionDenied {
-                WardFileError::PermissionDenied(path.to_path_buf())
-            } else {
-                WardFileError::Io(e)
-            }
-        })?;
+/// Write a file next to the ward files atomically.
+///
+/// Opens the parent directory of `path` and writes through it with
+/// `write_atomic_in`.
+pub(crate) fn write_atomic(path: &Path, content: &[u8]) -> Result<(), WardFileError> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| WardFileError::InvalidEntryName(path.display().to_string()))?;
+    let dir = DirHandle::open(parent).map_err(|e| io_error(parent, e))?;
+    write_atomic_in(&dir, parent, name, content)
+}
 
-        temp_file.as_file().sync_all().map_err(WardFileError::Io)?;
+/// Write the file `name` in the open directory `dir`, which is at `dir_path`,
+/// atomically.
+///
+/// Writes to a temporary file in `dir`, fsyncs it, atomically renames it into
+/// place, then (on Unix) fsyncs the directory so the rename is durable. Every
+/// step goes through the handle, so a directory swapped for a symlink in the
+/// meantime cannot redirect the write. The resulting file gets standard
+/// umask-derived permissions, like any normally created file. Used for ward
+/// files and their signatures.
+pub(crate) fn write_atomic_in(
+    dir: &DirHandle,
+    dir_path: &Path,
+    name: &str,
+    content: &[u8],
+) -> Result<(), WardFileError> {
+    use std::io::Write;
+
+    let (temp_name, mut temp_file) =
+        create_temp_file(dir, name).map_err(|e| io_error(dir_path, e))?;
+
+    let path = dir_path.join(name);
+    let written = temp_file
+        .write_all(content)
+        .and_then(|()| temp_file.sync_all())
+        .map_err(|e| io_error(&path, e))
+        .and_then(|()| {
+            dir.rename(temp_name.as_ref(), name.as_ref())
+                .map_err(|e| io_error(&path, e))
+        });
+    if written.is_err() {
+        let _ = dir.remove_file(temp_name.as_ref());
+    }
+    written?;
 
-        temp_file.persist(path).map_err(|e| {
-            if e.error.kind() == std::io::ErrorKind::PermissionDenied {
-                WardFileError::PermissionDenied(path.to_path_buf())
-            } else {
-                WardFileError::Io(e.error)
-            }
-        })?;
+    sync_dir(dir)
+}
 
-        sync_dir(parent)?;
+/// Creates a new temporary file in `dir` to be renamed to `name` once
+/// written, and returns it with its name.
+pub(crate) fn create_temp_file(
+    dir: &DirHandle,
+    name: &str,
+) -> std::io::Result<(String, std::fs::File)> {
+    static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    // A name left behind by a run that crashed with the same pid is skipped.
+    loop {
+        let temp_name = format!(
+            ".{name}.{}.{}.tmp",
+            std::process::id(),
+            TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        );
+        match dir.create_new(temp_name.as_ref()) {
+            Ok(file) => return Ok((temp_name, file)),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
 
-        Ok(())
+/// Maps an I/O failure on `path`, singling out permission problems.
+fn io_error(path: &Path, e: std::io::Error) -> WardFileError {
+    if e.kind() == std::io::ErrorKind::PermissionDenied {
+        WardFileError::PermissionDenied(path.to_path_buf())
+    } else {
+        WardFileError::Io(e)
     }
 }
 
+fn read_content(path: &Path) -> Result<String, WardFileError> {
+    std::fs::read_to_string(path).map_err(|e| io_error(path, e))
+}
+
+/// Digest of a ward file's exact content, as recorded in the parent's
+/// `WardEntry::Dir`.
+///
+/// Hashing the bytes rather than the parsed entries means any edit to a ward
+/// file breaks the chain, including edits that would not change what it
+/// parses to. A ward file that is not rewritten keeps its digest even when it
+/// is in an older format version.
+pub fn content_digest(content: &str, algorithm: HashAlgorithm) -> Checksum {
+    checksum::checksum_bytes(content.as_bytes(), algorithm)
+}
+
 /// Fsync a directory so a preceding rename into it is durable.
 ///
-/// `persist` makes the new file's *contents* durable (the temp file was
-/// fsynced), but the rename lives in the directory entry; without flushing the
+/// The temp file was fsynced, which makes the new file's *contents* durable,
+/// but the rename lives in the directory entry; without flushing the
 /// directory, a crash shortly after a successful save can roll the path back
 /// to the old `.treeward` or none at all. Unacceptable for an integrity tool
 /// that just told the user their state was recorded.
 ///
 /// Unix only: std exposes no way to fsync a directory handle on Windows, so
 /// rename durability is not guaranteed there.
-#[cfg(unix)]
-fn sync_dir(dir: &Path) -> Result<(), WardFileError> {
-    let dir_file = std::fs::File::open(dir).map_err(|e| {
-        if e.kind() == std::io::ErrorKind::PermissionDenied {
-            WardFileError::PermissionDenied(dir.to_path_buf())
-        } else {
-            WardFileError::Io(e)
-        }
-    })?;
-    dir_file.sync_all().or_else(|e| {
+pub(crate) fn sync_dir(dir: &DirHandle) -> Result<(), WardFileError> {
+    dir.sync().or_else(|e| {
         // Some filesystems (FUSE, network mounts) cannot fsync a directory and
         // report ENOTSUP/EINVAL/ENOSYS. Failing the whole save would make
         // treeward unusable there even though the rename succeeded, so accept
         // the filesystem's best as our best. Real I/O errors still propagate.
-        match e.raw_os_error() {
-            Some(libc::ENOTSUP) | Some(libc::EINVAL) | Some(libc::ENOSYS) => Ok(()),
-            _ => Err(WardFileError::Io(e)),
+        #[cfg(unix)]
+        if matches!(
+            e.raw_os_error(),
+            Some(libc::ENOTSUP) | Some(libc::EINVAL) | Some(libc::ENOSYS)
+        ) {
+            return Ok(());
         }
+        Err(WardFileError::Io(e))
     })
 }
 
-#[cfg(not(unix))]
-fn sync_dir(_dir: &Path) -> Result<(), WardFileError> {
-    Ok(())
-}
-
 /// Returns whether a persisted entry name can come from `list_directory`.
 ///
 /// Ward entries name immediate children only. Names containing NUL bytes,
 /// path separators, or `.`/`..` cannot appear as listed children, and
-/// `.treeward` is reserved for the ward file that `list_directory` excludes.
+/// `.treeward`, `.treeward.sig` and `.treeward-fec` are reserved for the
+/// ward file, its signature and its FEC store, which `list_directory`
+/// excludes.
 /// Accepting `.treeward` would let a corrupted ward file make recursive walks
 /// descend into the ward file itself and fail later with a misleading I/O
 /// error instead of rejecting the ward file at parse time.
 fn is_valid_entry_name(name: &str) -> bool {
     name != TREEWARD_FILENAME
-        && !name.contains('\0')
+        && name != SIGNATURE_FILENAME
+        && name != FEC_DIRNAME
+        && names::is_canonical(name)
         && Path::new(name).file_name().is_some_and(|part| part == name)
 }
 
+fn entry_from_v1(name: &str, entry: TomlEntryV1) -> Result<WardEntry, WardFileError> {
+    Ok(match entry {
+        TomlEntryV1::File {
+            sha256,
+            mtime_nanos,
+            size,
+        } => WardEntry::File {
+            checksum: validated_checksum(name, HashAlgorithm::Sha256, sha256)?,
+            mtime_nanos,
+            size,
+            attributes: Attributes::default(),
+        },
+        TomlEntryV1::Dir {} => WardEntry::Dir {
+            digest: None,
+            attributes: Attributes::default(),
+        },
+        TomlEntryV1::Symlink { symlink_target } => WardEntry::Symlink {
+            symlink_target,
+            attributes: Attributes::default(),
+        },
+    })
+}
+
+fn entry_from_v2(name: &str, entry: TomlEntryV2) -> Result<WardEntry, WardFileError> {
+    Ok(match entry {
+        TomlEntryV2::File {
+            checksum,
+            mtime_nanos,
+            size,
+        } => WardEntry::File {
+            checksum: parse_tagged_checksum(name, &checksum)?,
+            mtime_nanos,
+            size,
+            attributes: Attributes::default(),
+        },
+        TomlEntryV2::Dir {} => WardEntry::Dir {
+            digest: None,
+            attributes: Attributes::default(),
+        },
+        TomlEntryV2::Symlink { symlink_target } => WardEntry::Symlink {
+            symlink_target,
+            attributes: Attributes::default(),
+        },
+    })
+}
+
+fn entry_from_v3(name: &str, entry: TomlEntryV3) -> Result<WardEntry, WardFileError> {
+    Ok(match entry {
+        TomlEntryV3::File {
+            checksum,
+            mtime_nanos,
+            size,
+            mode,
+            uid,
+            gid,
+            xattrs,
+            nlink,
+            link_group,
+        } => WardEntry::File {
+            checksum: parse_tagged_checksum(name, &checksum)?,
+            mtime_nanos,
+            size,
+            attributes: Attributes {
+                links: parse_links(name, nlink, link_group)?,
+                ..parse_attributes(name, mode, uid, gid, xattrs)?
+            },
+        },
+        TomlEntryV3::Dir {
+            digest,
+            mode,
+            uid,
+            gid,
+            xattrs,
+        } => WardEntry::Dir {
+            digest: digest
+                .map(|digest| parse_tagged_checksum(name, &digest))
+                .transpose()?,
+            attributes: parse_attributes(name, mode, uid, gid, xattrs)?,
+        },
+        TomlEntryV3::Symlink {
+            symlink_target,
+            mode,
+            uid,
+            gid,
+            xattrs,
+        } => {
+            if !names::is_canonical(&symlink_target) {
+                return Err(WardFileError::InvalidSymlinkTarget(name.to_string()));
+            }
+            WardEntry::Symlink {
+                symlink_target: PathBuf::from(names::to_os(&symlink_target).into_owned()),
+                attributes: parse_attributes(name, mode, uid, gid, xattrs)?,
+            }
+        }
+        TomlEntryV3::Fifo {
+            mode,
+            uid,
+            gid,
+            xattrs,
+        } => WardEntry::Special {
+            kind: SpecialKind::Fifo,
+            attributes: parse_attributes(name, mode, uid, gid, xattrs)?,
+        },
+        TomlEntryV3::Socket {
+            mode,
+            uid,
+            gid,
+            xattrs,
+        } => WardEntry::Special {
+            kind: SpecialKind::Socket,
+            attributes: parse_attributes(name, mode, uid, gid, xattrs)?,
+        },
+        TomlEntryV3::CharDevice {
+            major,
+            minor,
+            mode,
+            uid,
+            gid,
+            xattrs,
+        } => WardEntry::Special {
+            kind: SpecialKind::CharDevice { major, minor },
+            attributes: parse_attributes(name, mode, uid, gid, xattrs)?,
+        },
+        TomlEntryV3::BlockDevice {
+            major,
+            minor,
+            mode,
+            uid,
+            gid,
+            xattrs,
+        } => WardEntry::Special {
+            kind: SpecialKind::BlockDevice { major, minor },
+            attributes: parse_attributes(name, mode, uid, gid, xattrs)?,
+        },
+    })
+}
+
+fn entry_to_v3(name: &str, entry: &WardEntry) -> Result<TomlEntryV3, WardFileError> {
+    Ok(match entry {
+        WardEntry::File {
+            checksum,
+            mtime_nanos,
+            size,
+            attributes,
+        } => TomlEntryV3::File {
+            checksum: checksum.to_string(),
+            mtime_nanos: *mtime_nanos,
+            size: *size,
+            mode: attributes.mode.map(attributes::format_mode),
+            uid: attributes.uid,
+            gid: attributes.gid,
+            xattrs: format_xattrs(attributes),
+            nlink: attributes.links.as_ref().map(|links| links.nlink),
+            link_group: attributes
+                .links
+                .as_ref()
+                .and_then(|links| links.group.clone()),
+        },
+        WardEntry::Dir { digest, attributes } => TomlEntryV3::Dir {
+            digest: digest.as_ref().map(Checksum::to_string),
+            mode: attributes.mode.map(attributes::format_mode),
+            uid: attributes.uid,
+            gid: attributes.gid,
+            xattrs: format_xattrs(attributes),
+        },
+        WardEntry::Symlink {
+            symlink_target,
+            attributes,
+        } => TomlEntryV3::Symlink {
+            symlink_target: names::from_os(symlink_target.as_os_str())
+                .ok_or_else(|| WardFileError::InvalidSymlinkTarget(name.to_string()))?
+                .into_owned(),
+            mode: attributes.mode.map(attributes::format_mode),
+            uid: attributes.uid,
+            gid: attributes.gid,
+            xattrs: format_xattrs(attributes),
+        },
+        WardEntry::Special { kind, attributes } => {
+            let mode = attributes.mode.map(attributes::format_mode);
+            let (uid, gid, xattrs) = (attributes.uid, attributes.gid, format_xattrs(attributes));
+            match *kind {
+                SpecialKind::Fifo => TomlEntryV3::Fifo {
+                    mode,
+                    uid,
+                    gid,
+                    xattrs,
+                },
+                SpecialKind::Socket => TomlEntryV3::Socket {
+                    mode,
+                    uid,
+                    gid,
+                    xattrs,
+                },
+                SpecialKind::CharDevice { major, minor } => TomlEntryV3::CharDevice {
+                    major,
+                    minor,
+                    mode,
+                    uid,
+                    gid,
+                    xattrs,
+                },
+                SpecialKind::BlockDevice { major, minor } => TomlEntryV3::BlockDevice {
+                    major,
+                    minor,
+                    mode,
+                    uid,
+                    gid,
+                    xattrs,
+                },
+            }
+        }
+    })
+}
+
+fn parse_attributes(
+    name: &str,
+    mode: Option<String>,
+    uid: Option<u32>,
+    gid: Option<u32>,
+    xattrs: Option<BTreeMap<String, String>>,
+) -> Result<Attributes, WardFileError> {
+    let mode = mode
+        .map(|mode| {
+            attributes::parse_mode(&mode)
+                .ok_or_else(|| WardFileError::InvalidMode(name.to_string()))
+        })
+        .transpose()?;
+    let xattrs = xattrs
+        .map(|xattrs| {
+            xattrs
+                .into_iter()
+                .map(|(xattr, digest)| {
+                    if !names::is_canonical(&xattr) {
+                        return Err(WardFileError::InvalidXattrName(name.to_string()));
+                    }
+                    Ok((xattr, parse_tagged_checksum(name, &digest)?))
+                })
+                .collect::<Result<BTreeMap<_, _>, WardFileError>>()
+        })
+        .transpose()?;
+    Ok(Attributes {
+        mode,
+        uid,
+        gid,
+        xattrs,
+        ..Attributes::default()
+    })
+}
+
+fn parse_links(
+    name: &str,
+    nlink: Option<u64>,
+    link_group: Option<String>,
+) -> Result<Option<Links>, WardFileError> {
+    match (nlink, link_group) {
+        (None, None) => Ok(None),
+        (Some(nlink), group) if group.as_deref().is_none_or(attributes::is_valid_link_group) => {
+            Ok(Some(Links { nlink, group }))
+        }
+        _ => Err(WardFileError::InvalidLinkGroup(name.to_string())),
+    }
+}
+
+fn format_xattrs(attributes: &Attributes) -> Option<BTreeMap<String, String>> {
+    attributes.xattrs.as_ref().map(|xattrs| {
+        xattrs
+            .iter()
+            .map(|(name, digest)| (name.clone(), digest.to_string()))
+            .collect()
+    })
+}
+
+/// Parse an `<algorithm>:<hex>` field of the entry `name`.
+fn parse_tagged_checksum(name: &str, tagged: &str) -> Result<Checksum, WardFileError> {
+    let (algorithm, hex) = tagged
+        .split_once(':')
+        .and_then(|(algorithm, hex)| Some((HashAlgorithm::from_name(algorithm)?, hex)))
+        .ok_or_else(|| WardFileError::InvalidChecksum(name.to_string()))?;
+    validated_checksum(name, algorithm, hex.to_string())
+}
+
+/// Reject persisted digests that cannot have been produced by checksumming.
+///
+/// treeward always writes digests as lowercase hex of the algorithm's exact
+/// length. Anything else in a loaded ward file is corruption or tampering and
+/// fails fast at parse time, consistent with the `deny_unknown_fields`
+/// posture. This also keeps hostile bytes out of every downstream consumer of
+/// the field (display, fingerprint hashing).
+fn validated_checksum(
+    name: &str,
+    algorithm: HashAlgorithm,
+    hex: String,
+) -> Result<Checksum, WardFileError> {
+    if !is_valid_hex_digest(&hex, algorithm.hex_len()) {
+        return Err(WardFileError::InvalidChecksum(name.to_string()));
+    }
+    Ok(Checksum::new(algorithm, hex))
+}
+
 /// Lowercase-only on purpose: treeward never writes uppercase hex, so accepting
 /// it would mask corruption rather than tolerate legitimate input.
-fn is_valid_sha256_hex(s: &str) -> bool {
-    s.len() == 64 && s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+fn is_valid_hex_digest(s: &str, len: usize) -> bool {
+    s.len() == len && s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
 }
 
 #[cfg(test)]
@@ -311,13 +950,17 @@ size = 42
         let entry = ward_file.entries.get("file1.txt").unwrap();
         match entry {
             WardEntry::File {
-                sha256,
+                checksum,
                 mtime_nanos,
                 size,
+                ..
             } => {
                 assert_eq!(
-                    sha256,
-                    "abc123abc123abc123abc123abc123abc123abc123abc123abc123abc123abc1"
+                    checksum,
+                    &Checksum::new(
+                        HashAlgorithm::Sha256,
+                        "abc123abc123abc123abc123abc123abc123abc123abc123abc123abc123abc1"
+                    )
                 );
                 assert_eq!(*mtime_nanos, 1234567890);
                 assert_eq!(*size, 42);
@@ -340,7 +983,7 @@ type = "dir"
         assert_eq!(ward_file.entries.len(), 1);
 
         let entry = ward_file.entries.get("dir1").unwrap();
-        assert!(matches!(entry, WardEntry::Dir {}));
+        assert!(matches!(entry, WardEntry::Dir { .. }));
     }
 
     #[test]
@@ -359,7 +1002,7 @@ symlink_target = "/some/path"
 
         let entry = ward_file.entries.get("link1").unwrap();
         match entry {
-            WardEntry::Symlink { symlink_target } => {
+            WardEntry::Symlink { symlink_target, .. } => {
                 assert_eq!(symlink_target, Path::new("/some/path"));
             }
             _ => panic!("Expected Symlink entry"),
@@ -413,6 +1056,97 @@ type = "dir"
         assert!(matches!(result, Err(WardFileError::InvalidEntryName(_))));
     }
 
+    #[test]
+    #[cfg(unix)]
+    fn test_non_utf8_name_round_trips() {
+        let ward_file = WardFile::new(BTreeMap::from([
+            (
+                "caf\0e9".to_string(),
+                WardEntry::Dir {
+                    digest: None,
+                    attributes: Attributes::default(),
+                },
+            ),
+            (
+                "café".to_string(),
+                WardEntry::Dir {
+                    digest: None,
+                    attributes: Attributes::default(),
+                },
+            ),
+        ]));
+
+        let toml_string = ward_file.to_toml().unwrap();
+
+        assert!(
+            toml_string.contains(r#"[entries."caf\u0000e9"]"#),
+            "{toml_string}"
+        );
+        assert_eq!(WardFile::from_toml(&toml_string).unwrap(), ward_file);
+    }
+
+    /// Only the canonical encoding of a non-UTF-8 name is accepted, so no two
+    /// keys can name the same entry.
+    #[test]
+    fn test_rejects_non_canonical_raw_byte_names() {
+        for name in [r"\u0000c3\u0000a9", r"caf\u0000E9", r"caf\u0000e"] {
+            let result = WardFile::from_toml(&format!(
+                r#"
+[metadata]
+version = 3
+
+[entries."{name}"]
+type = "dir"
+"#
+            ));
+            assert!(
+                matches!(result, Err(WardFileError::InvalidEntryName(_))),
+                "{name}: {result:?}"
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_non_utf8_symlink_target_round_trips() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let ward_file = WardFile::new(BTreeMap::from([(
+            "link".to_string(),
+            WardEntry::Symlink {
+                symlink_target: PathBuf::from(OsStr::from_bytes(b"dir/caf\xe9")),
+                attributes: Attributes::default(),
+            },
+        )]));
+
+        let toml_string = ward_file.to_toml().unwrap();
+
+        assert!(
+            toml_string.contains(r#"symlink_target = "dir/caf\u0000e9""#),
+            "{toml_string}"
+        );
+        assert_eq!(WardFile::from_toml(&toml_string).unwrap(), ward_file);
+    }
+
+    #[test]
+    fn test_rejects_non_canonical_symlink_targets() {
+        let result = WardFile::from_toml(
+            r#"
+[metadata]
+version = 3
+
+[entries.link]
+type = "symlink"
+symlink_target = "caf\u0000E9"
+"#,
+        );
+        assert!(
+            matches!(result, Err(WardFileError::InvalidSymlinkTarget(ref name)) if name == "link"),
+            "{result:?}"
+        );
+    }
+
     #[test]
     fn test_rejects_reserved_treeward_entry_name() {
         let result = WardFile::from_toml(
@@ -428,6 +1162,24 @@ type = "dir"
         assert!(matches!(result, Err(WardFileError::InvalidEntryName(_))));
     }
 
+    #[test]
+    fn test_rejects_reserved_signature_entry_name() {
+        let result = WardFile::from_toml(
+            r#"
+[metadata]
+version = 3
+
+[entries.".treeward.sig"]
+type = "file"
+checksum = "sha256:2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+mtime_nanos = 1
+size = 5
+"#,
+        );
+
+        assert!(matches!(result, Err(WardFileError::InvalidEntryName(_))));
+    }
+
     /// A sha256 that is not 64 lowercase hex characters is corruption and must
     /// fail at parse time, before any downstream code (display, fingerprints,
     /// comparison) sees the value.
@@ -463,14 +1215,428 @@ size = 456
 
             let result = WardFile::from_toml(&toml_content);
             assert!(
-                matches!(result, Err(WardFileError::InvalidSha256(ref name)) if name == "file1.txt"),
-                "expected InvalidSha256 for {:?}, got {:?}",
+                matches!(result, Err(WardFileError::InvalidChecksum(ref name)) if name == "file1.txt"),
+                "expected InvalidChecksum for {:?}, got {:?}",
                 bad_sha,
                 result
             );
         }
     }
 
+    #[test]
+    fn test_parse_version_2_tagged_checksums() {
+        let toml_content = format!(
+            r#"
+[metadata]
+version = 2
+
+[entries."a.txt"]
+type = "file"
+checksum = "blake3:{}"
+mtime_nanos = 1
+size = 2
+
+[entries."b.txt"]
+type = "file"
+checksum = "sha512:{}"
+mtime_nanos = 3
+size = 4
+"#,
+            "b".repeat(64),
+            "5".repeat(128)
+        );
+
+        let ward_file = WardFile::from_toml(&toml_content).unwrap();
+
+        assert_eq!(
+            ward_file.entries["a.txt"],
+            WardEntry::File {
+                checksum: Checksum::new(HashAlgorithm::Blake3, "b".repeat(64)),
+                mtime_nanos: 1,
+                size: 2,
+                attributes: Attributes::default(),
+            }
+        );
+        assert_eq!(
+            ward_file.entries["b.txt"],
+            WardEntry::File {
+                checksum: Checksum::new(HashAlgorithm::Sha512, "5".repeat(128)),
+                mtime_nanos: 3,
+                size: 4,
+                attributes: Attributes::default(),
+            }
+        );
+    }
+
+    /// The digest length is checked against the tagged algorithm, and the tag
+    /// itself must name a known algorithm.
+    #[test]
+    fn test_rejects_invalid_tagged_checksum() {
+        for bad_checksum in [
+            "a".repeat(64),
+            format!("md5:{}", "a".repeat(32)),
+            format!("sha512:{}", "a".repeat(64)),
+            format!("blake3:{}", "a".repeat(128)),
+            format!("SHA256:{}", "a".repeat(64)),
+            format!("sha256:{}", "A".repeat(64)),
+            "sha256:".to_string(),
+        ] {
+            let toml_content = format!(
+                r#"
+[metadata]
+version = 2
+
+[entries."file1.txt"]
+type = "file"
+checksum = "{}"
+mtime_nanos = 123
+size = 456
+"#,
+                bad_checksum
+            );
+
+            let result = WardFile::from_toml(&toml_content);
+            assert!(
+                matches!(result, Err(WardFileError::InvalidChecksum(ref name)) if name == "file1.txt"),
+                "expected InvalidChecksum for {:?}, got {:?}",
+                bad_checksum,
+                result
+            );
+        }
+    }
+
+    /// Each version accepts only its own entry schema.
+    #[test]
+    fn test_rejects_schema_of_other_version() {
+        let v2_with_sha256_field = r#"
+[metadata]
+version = 2
+
+[entries."file1.txt"]
+type = "file"
+sha256 = "abc123abc123abc123abc123abc123abc123abc123abc123abc123abc123abc1"
+mtime_nanos = 123
+size = 456
+"#;
+        assert!(matches!(
+            WardFile::from_toml(v2_with_sha256_field),
+            Err(WardFileError::TomlParse(_))
+        ));
+
+        let v1_with_checksum_field = v2_with_sha256_field
+            .replace("version = 2", "version = 1")
+            .replace("sha256 = \"", "checksum = \"sha256:");
+        assert!(matches!(
+            WardFile::from_toml(&v1_with_checksum_field),
+            Err(WardFileError::TomlParse(_))
+        ));
+    }
+
+    #[test]
+    fn test_version_3_directory_digest_round_trip() {
+        let digest = Checksum::new(HashAlgorithm::Blake3, "c".repeat(64));
+        let ward_file = WardFile::new(BTreeMap::from([
+            (
+                "pinned".to_string(),
+                WardEntry::Dir {
+                    digest: Some(digest.clone()),
+                    attributes: Attributes::default(),
+                },
+            ),
+            (
+                "legacy".to_string(),
+                WardEntry::Dir {
+                    digest: None,
+                    attributes: Attributes::default(),
+                },
+            ),
+        ]));
+
+        let toml_string = ward_file.to_toml().unwrap();
+
+        assert!(
+            toml_string.contains(&format!("digest = \"{digest}\"")),
+            "{toml_string}"
+        );
+        assert_eq!(WardFile::from_toml(&toml_string).unwrap(), ward_file);
+    }
+
+    #[test]
+    fn test_rejects_invalid_directory_digest() {
+        let toml_content = r#"
+[metadata]
+version = 3
+
+[entries.sub]
+type = "dir"
+digest = "sha256:abc"
+"#;
+
+        let result = WardFile::from_toml(toml_content);
+        assert!(
+            matches!(result, Err(WardFileError::InvalidChecksum(ref name)) if name == "sub"),
+            "{result:?}"
+        );
+    }
+
+    #[test]
+    fn test_version_3_attributes_and_track_round_trip() {
+        let ward_file = WardFile::new(BTreeMap::from([
+            (
+                "bin".to_string(),
+                WardEntry::Dir {
+                    digest: None,
+                    attributes: Attributes {
+                        mode: Some(0o755),
+                        uid: Some(0),
+                        gid: Some(0),
+                        xattrs: Some(BTreeMap::from([
+                            (
+                                "security.capability".to_string(),
+                                crate::checksum::checksum_bytes(b"cap", HashAlgorithm::Sha256),
+                            ),
+                            (
+                                "user.caf\0e9".to_string(),
+                                crate::checksum::checksum_bytes(b"tag", HashAlgorithm::Sha256),
+                            ),
+                        ])),
+                        ..Attributes::default()
+                    },
+                },
+            ),
+            (
+                "link".to_string(),
+                WardEntry::Symlink {
+                    symlink_target: PathBuf::from("bin"),
+                    attributes: Attributes {
+                        mode: None,
+                        uid: Some(1000),
+                        gid: Some(100),
+                        xattrs: Some(BTreeMap::new()),
+                        ..Attributes::default()
+                    },
+                },
+            ),
+        ]))
+        .with_track("mode,owner,xattr:security.capability".parse().unwrap());
+
+        let toml_string = ward_file.to_toml().unwrap();
+
+        assert!(toml_string.contains("mode = \"0755\""), "{toml_string}");
+        assert!(toml_string.contains("\"owner\""), "{toml_string}");
+        assert!(
+            toml_string.contains("\"xattr:security.capability\""),
+            "{toml_string}"
+        );
+        assert_eq!(WardFile::from_toml(&toml_string).unwrap(), ward_file);
+    }
+
+    #[test]
+    fn test_protection_round_trip() {
+        let ward_file = WardFile::new(BTreeMap::new()).with_protection(Protection::Enabled {
+            loss_tolerance: 100,
+        });
+
+        let toml_string = ward_file.to_toml().unwrap();
+
+        assert!(
+            toml_string.contains("[metadata.protection]\nenabled = true\nloss_tolerance = 100\n"),
+            "{toml_string}"
+        );
+        assert_eq!(WardFile::from_toml(&toml_string).unwrap(), ward_file);
+        assert!(
+            !WardFile::new(BTreeMap::new())
+                .to_toml()
+                .unwrap()
+                .contains("protection")
+        );
+        assert!(matches!(
+            WardFile::from_toml(
+                "[metadata]\nversion = 3\n\n[metadata.protection]\nenabled = true\nloss_tolerance = 0\n"
+            ),
+            Err(WardFileError::TomlParse(_))
+        ));
+    }
+
+    #[test]
+    fn test_special_files_round_trip() {
+        let special = |kind| WardEntry::Special {
+            kind,
+            attributes: Attributes::default(),
+        };
+        let ward_file = WardFile::new(BTreeMap::from([
+            ("fifo".to_string(), special(SpecialKind::Fifo)),
+            ("socket".to_string(), special(SpecialKind::Socket)),
+            (
+                "null".to_string(),
+                special(SpecialKind::CharDevice { major: 1, minor: 3 }),
+            ),
+            (
+                "sda".to_string(),
+                special(SpecialKind::BlockDevice { major: 8, minor: 0 }),
+            ),
+        ]));
+
+        let toml_string = ward_file.to_toml().unwrap();
+
+        assert!(
+            toml_string.contains("[entries.null]\ntype = \"char_device\"\nmajor = 1\nminor = 3\n"),
+            "{toml_string}"
+        );
+        assert!(
+            toml_string.contains("type = \"block_device\""),
+            "{toml_string}"
+        );
+        assert_eq!(WardFile::from_toml(&toml_string).unwrap(), ward_file);
+    }
+
+    #[test]
+    fn test_rejects_invalid_mode() {
+        for bad_mode in ["755", "0789", "rwxr-xr-x"] {
+            let toml_content = format!(
+                r#"
+[metadata]
+version = 3
+
+[entries.sub]
+type = "dir"
+mode = "{bad_mode}"
+"#
+            );
+
+            let result = WardFile::from_toml(&toml_content);
+            assert!(
+                matches!(result, Err(WardFileError::InvalidMode(ref name)) if name == "sub"),
+                "{bad_mode}: {result:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_rejects_non_canonical_xattr_names() {
+        let result = WardFile::from_toml(
+            r#"
+[metadata]
+version = 3
+
+[entries.sub]
+type = "dir"
+xattrs = { "user.caf\u0000E9" = "sha256:2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824" }
+"#,
+        );
+        assert!(
+            matches!(result, Err(WardFileError::InvalidXattrName(ref name)) if name == "sub"),
+            "{result:?}"
+        );
+    }
+
+    #[test]
+    fn test_links_round_trip_and_validation() {
+        let ward_file = WardFile::new(BTreeMap::from([(
+            "file".to_string(),
+            WardEntry::File {
+                checksum: Checksum::new(HashAlgorithm::Sha256, "a".repeat(64)),
+                mtime_nanos: 1,
+                size: 2,
+                attributes: Attributes {
+                    links: Some(Links {
+                        nlink: 2,
+                        group: Some("0123456789abcdef".to_string()),
+                    }),
+                    ..Attributes::default()
+                },
+            },
+        )]))
+        .with_track("links".parse().unwrap());
+
+        let toml_string = ward_file.to_toml().unwrap();
+        assert!(toml_string.contains("nlink = 2"), "{toml_string}");
+        assert_eq!(WardFile::from_toml(&toml_string).unwrap(), ward_file);
+
+        for bad_links in [
+            "nlink = 2\nlink_group = \"XYZ\"",
+            "link_group = \"0123456789abcdef\"",
+        ] {
+            let toml_content = format!(
+                r#"
+[metadata]
+version = 3
+
+[entries.file]
+type = "file"
+checksum = "sha256:{}"
+mtime_nanos = 1
+size = 2
+{bad_links}
+"#,
+                "a".repeat(64)
+            );
+
+            let result = WardFile::from_toml(&toml_content);
+            assert!(
+                matches!(result, Err(WardFileError::InvalidLinkGroup(ref name)) if name == "file"),
+                "{bad_links}: {result:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_rejects_unknown_tracked_attribute() {
+        let result = WardFile::from_toml(
+            r#"
+[metadata]
+version = 3
+track = ["mode", "inode"]
+"#,
+        );
+
+        assert!(matches!(result, Err(WardFileError::TomlParse(_))));
+    }
+
+    #[test]
+    fn test_content_digest_covers_exact_bytes() {
+        let content = "[metadata]\nversion = 3\n\n[entries]\n";
+
+        assert_ne!(
+            content_digest(content, HashAlgorithm::Sha256),
+            content_digest(&format!("{content}\n"), HashAlgorithm::Sha256)
+        );
+        assert_eq!(
+            content_digest(content, HashAlgorithm::Sha512).algorithm,
+            HashAlgorithm::Sha512
+        );
+    }
+
+    /// Loading a version 1 file and saving it produces the current version.
+    #[test]
+    fn test_version_1_is_written_back_as_current_version() {
+        let v1 = r#"
+[metadata]
+version = 1
+
+[entries."file1.txt"]
+type = "file"
+sha256 = "abc123abc123abc123abc123abc123abc123abc123abc123abc123abc123abc1"
+mtime_nanos = 123
+size = 456
+"#;
+
+        let ward_file = WardFile::from_toml(v1).unwrap();
+        assert_eq!(ward_file.version(), 1);
+        let toml_string = ward_file.to_toml().unwrap();
+
+        assert!(toml_string.contains("version = 3"), "{toml_string}");
+        assert!(
+            toml_string.contains(
+                "checksum = \"sha256:abc123abc123abc123abc123abc123abc123abc123abc123abc123abc123abc1\""
+            ),
+            "{toml_string}"
+        );
+        let reloaded = WardFile::from_toml(&toml_string).unwrap();
+        assert_eq!(reloaded.version(), WardFile::CURRENT_VERSION);
+        assert_eq!(reloaded.entries, ward_file.entries);
+    }
+
     #[test]
     fn test_corrupted_symlink_missing_target() {
         let toml_content = r#"
@@ -508,17 +1674,29 @@ sha256 = "should_be_rejected"
         entries.insert(
             "file1.txt".to_string(),
             WardEntry::File {
-                sha256: "abc123abc123abc123abc123abc123abc123abc123abc123abc123abc123abc1"
-                    .to_string(),
+                checksum: Checksum::new(
+                    HashAlgorithm::Sha256,
+                    "abc123abc123abc123abc123abc123abc123abc123abc123abc123abc123abc1",
+                ),
                 mtime_nanos: 1234567890,
                 size: 42,
+                attributes: Attributes::default(),
+            },
+        );
+        entries.insert(
+            "dir1".to_string(),
+            WardEntry::Dir {
+                digest: None,
+                attributes: Attributes::default(),
             },
         );
-        entries.insert("dir1".to_string(), WardEntry::Dir {});
 
         let ward_file = WardFile {
             metadata: Metadata {
-                version: WardFile::SUPPORTED_VERSION,
+                version: WardFile::CURRENT_VERSION,
+                track: Track::default(),
+                protection: Protection::default(),
+                journal: None,
             },
             entries,
         };
@@ -557,16 +1735,20 @@ sha256 = "should_be_rejected"
             entries.insert(
                 format!("{}.txt", name),
                 WardEntry::File {
-                    sha256: format!("hash{}", i),
+                    checksum: Checksum::new(HashAlgorithm::Sha256, format!("hash{}", i)),
                     mtime_nanos: 1000 + i as u64,
                     size: 10 + i as u64,
+                    attributes: Attributes::default(),
                 },
             );
         }
 
         let ward_file = WardFile {
             metadata: Metadata {
-                version: WardFile::SUPPORTED_VERSION,
+                version: WardFile::CURRENT_VERSION,
+                track: Track::default(),
+                protection: Protection::default(),
+                journal: None,
             },
             entries: entries.clone(),
         };
@@ -628,23 +1810,36 @@ sha256 = "should_be_rejected"
         entries.insert(
             "test_file.txt".to_string(),
             WardEntry::File {
-                sha256: "7e577e577e577e577e577e577e577e577e577e577e577e577e577e577e577e57"
-                    .to_string(),
+                checksum: Checksum::new(
+                    HashAlgorithm::Sha256,
+                    "7e577e577e577e577e577e577e577e577e577e577e577e577e577e577e577e57",
+                ),
                 mtime_nanos: 9876543210,
                 size: 100,
+                attributes: Attributes::default(),
+            },
+        );
+        entries.insert(
+            "test_dir".to_string(),
+            WardEntry::Dir {
+                digest: None,
+                attributes: Attributes::default(),
             },
         );
-        entries.insert("test_dir".to_string(), WardEntry::Dir {});
         entries.insert(
             "test_link".to_string(),
             WardEntry::Symlink {
                 symlink_target: PathBuf::from("/target/path"),
+                attributes: Attributes::default(),
             },
         );
 
         let ward_file = WardFile {
             metadata: Metadata {
-                version: WardFile::SUPPORTED_VERSION,
+                version: WardFile::CURRENT_VERSION,
+                track: Track::default(),
+                protection: Protection::default(),
+                journal: None,
             },
             entries,
         };
@@ -657,24 +1852,27 @@ sha256 = "should_be_rejected"
     }
 
     #[test]
-    fn test_load_if_exists_missing_returns_none() {
+    fn test_load_with_content_if_exists_missing_returns_none() {
         let temp_file = NamedTempFile::new().unwrap();
         let missing_path = temp_file.path().with_file_name("missing.treeward");
 
-        let loaded = WardFile::load_if_exists(&missing_path).unwrap();
+        let loaded = WardFile::load_with_content_if_exists(&missing_path).unwrap();
         assert_eq!(loaded, None);
     }
 
     #[test]
-    fn test_load_if_exists_existing_returns_some() {
+    fn test_load_with_content_if_exists_existing_returns_some() {
         let mut entries = BTreeMap::new();
         entries.insert(
             "test_file.txt".to_string(),
             WardEntry::File {
-                sha256: "7e577e577e577e577e577e577e577e577e577e577e577e577e577e577e577e57"
-                    .to_string(),
+                checksum: Checksum::new(
+                    HashAlgorithm::Sha256,
+                    "7e577e577e577e577e577e577e577e577e577e577e577e577e577e577e577e57",
+                ),
                 mtime_nanos: 9876543210,
                 size: 100,
+                attributes: Attributes::default(),
             },
         );
 
@@ -682,8 +1880,9 @@ sha256 = "should_be_rejected"
         let temp_file = NamedTempFile::new().unwrap();
         ward_file.save(temp_file.path()).unwrap();
 
-        let loaded = WardFile::load_if_exists(temp_file.path()).unwrap();
-        assert_eq!(loaded, Some(ward_file));
+        let loaded = WardFile::load_with_content_if_exists(temp_file.path()).unwrap();
+        let content = ward_file.to_toml().unwrap();
+        assert_eq!(loaded, Some((ward_file, content)));
     }
 
     #[test]
@@ -804,17 +2003,27 @@ unknown_field = "should_be_rejected"
         entries.insert(
             "🎉party🎊.txt".to_string(),
             WardEntry::File {
-                sha256: "abc123abc123abc123abc123abc123abc123abc123abc123abc123abc123abc1"
-                    .to_string(),
+                checksum: Checksum::new(
+                    HashAlgorithm::Sha256,
+                    "abc123abc123abc123abc123abc123abc123abc123abc123abc123abc123abc1",
+                ),
                 mtime_nanos: 1234567890,
                 size: 42,
+                attributes: Attributes::default(),
+            },
+        );
+        entries.insert(
+            "📁folder📂".to_string(),
+            WardEntry::Dir {
+                digest: None,
+                attributes: Attributes::default(),
             },
         );
-        entries.insert("📁folder📂".to_string(), WardEntry::Dir {});
         entries.insert(
             "🔗link→target".to_string(),
             WardEntry::Symlink {
                 symlink_target: PathBuf::from("🎯target🎯"),
+                attributes: Attributes::default(),
             },
         );
 
@@ -828,7 +2037,7 @@ unknown_field = "should_be_rejected"
         assert!(parsed.entries.contains_key("🔗link→target"));
 
         match parsed.entries.get("🔗link→target").unwrap() {
-            WardEntry::Symlink { symlink_target } => {
+            WardEntry::Symlink { symlink_target, .. } => {
                 assert_eq!(symlink_target, &PathBuf::from("🎯target🎯"));
             }
             _ => panic!("Expected Symlink entry"),
@@ -843,22 +2052,34 @@ unknown_field = "should_be_rejected"
         entries.insert(
             "ملف.txt".to_string(),
             WardEntry::File {
-                sha256: "abc123abc123abc123abc123abc123abc123abc123abc123abc123abc123abc1"
-                    .to_string(),
+                checksum: Checksum::new(
+                    HashAlgorithm::Sha256,
+                    "abc123abc123abc123abc123abc123abc123abc123abc123abc123abc123abc1",
+                ),
                 mtime_nanos: 1234567890,
                 size: 42,
+                attributes: Attributes::default(),
             },
         );
         // Hebrew text
-        entries.insert("קובץ".to_string(), WardEntry::Dir {});
+        entries.insert(
+            "קובץ".to_string(),
+            WardEntry::Dir {
+                digest: None,
+                attributes: Attributes::default(),
+            },
+        );
         // Mixed LTR and RTL
         entries.insert(
             "file_ملف_mixed.txt".to_string(),
             WardEntry::File {
-                sha256: "def456def456def456def456def456def456def456def456def456def456def4"
-                    .to_string(),
+                checksum: Checksum::new(
+                    HashAlgorithm::Sha256,
+                    "def456def456def456def456def456def456def456def456def456def456def4",
+                ),
                 mtime_nanos: 9876543210,
                 size: 100,
+                attributes: Attributes::default(),
             },
         );
 
@@ -887,31 +2108,46 @@ unknown_field = "should_be_rejected"
         entries.insert(
             decomposed.clone(),
             WardEntry::File {
-                sha256: "abc123abc123abc123abc123abc123abc123abc123abc123abc123abc123abc1"
-                    .to_string(),
+                checksum: Checksum::new(
+                    HashAlgorithm::Sha256,
+                    "abc123abc123abc123abc123abc123abc123abc123abc123abc123abc123abc1",
+                ),
                 mtime_nanos: 1234567890,
                 size: 42,
+                attributes: Attributes::default(),
             },
         );
         entries.insert(
             precomposed.clone(),
             WardEntry::File {
-                sha256: "def456def456def456def456def456def456def456def456def456def456def4"
-                    .to_string(),
+                checksum: Checksum::new(
+                    HashAlgorithm::Sha256,
+                    "def456def456def456def456def456def456def456def456def456def456def4",
+                ),
                 mtime_nanos: 9876543210,
                 size: 100,
+                attributes: Attributes::default(),
             },
         );
         entries.insert(
             multi_combining.clone(),
             WardEntry::File {
-                sha256: "7897897897897897897897897897897897897897897897897897897897897897"
-                    .to_string(),
+                checksum: Checksum::new(
+                    HashAlgorithm::Sha256,
+                    "7897897897897897897897897897897897897897897897897897897897897897",
+                ),
                 mtime_nanos: 5555555555,
                 size: 50,
+                attributes: Attributes::default(),
+            },
+        );
+        entries.insert(
+            zalgo.clone(),
+            WardEntry::Dir {
+                digest: None,
+                attributes: Attributes::default(),
             },
         );
-        entries.insert(zalgo.clone(), WardEntry::Dir {});
 
         let ward_file = WardFile::new(entries);
         let toml_string = ward_file.to_toml().unwrap();
@@ -933,22 +2169,22 @@ unknown_field = "should_be_rejected"
         match (decomposed_entry, precomposed_entry) {
             (
                 WardEntry::File {
-                    sha256: sha1,
+                    checksum: sha1,
                     size: size1,
                     ..
                 },
                 WardEntry::File {
-                    sha256: sha2,
+                    checksum: sha2,
                     size: size2,
                     ..
                 },
             ) => {
                 assert_eq!(
-                    sha1,
+                    sha1.hex,
                     "abc123abc123abc123abc123abc123abc123abc123abc123abc123abc123abc1"
                 );
                 assert_eq!(
-                    sha2,
+                    sha2.hex,
                     "def456def456def456def456def456def456def456def456def456def456def4"
                 );
                 assert_eq!(*size1, 42);
@@ -965,64 +2201,85 @@ unknown_field = "should_be_rejected"
         entries.insert(
             "file with spaces.txt".to_string(),
             WardEntry::File {
-                sha256: "abcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabca"
-                    .to_string(),
+                checksum: Checksum::new(
+                    HashAlgorithm::Sha256,
+                    "abcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabcabca",
+                ),
                 mtime_nanos: 1000,
                 size: 10,
+                attributes: Attributes::default(),
             },
         );
         entries.insert(
             "file\twith\ttabs.txt".to_string(),
             WardEntry::File {
-                sha256: "defdefdefdefdefdefdefdefdefdefdefdefdefdefdefdefdefdefdefdefdefd"
-                    .to_string(),
+                checksum: Checksum::new(
+                    HashAlgorithm::Sha256,
+                    "defdefdefdefdefdefdefdefdefdefdefdefdefdefdefdefdefdefdefdefdefd",
+                ),
                 mtime_nanos: 2000,
                 size: 20,
+                attributes: Attributes::default(),
             },
         );
         entries.insert(
             "file\"with\"quotes.txt".to_string(),
             WardEntry::File {
-                sha256: "1212121212121212121212121212121212121212121212121212121212121212"
-                    .to_string(),
+                checksum: Checksum::new(
+                    HashAlgorithm::Sha256,
+                    "1212121212121212121212121212121212121212121212121212121212121212",
+                ),
                 mtime_nanos: 3000,
                 size: 30,
+                attributes: Attributes::default(),
             },
         );
         entries.insert(
             "file\\with\\backslashes.txt".to_string(),
             WardEntry::File {
-                sha256: "3434343434343434343434343434343434343434343434343434343434343434"
-                    .to_string(),
+                checksum: Checksum::new(
+                    HashAlgorithm::Sha256,
+                    "3434343434343434343434343434343434343434343434343434343434343434",
+                ),
                 mtime_nanos: 4000,
                 size: 40,
+                attributes: Attributes::default(),
             },
         );
         entries.insert(
             "file=with=equals.txt".to_string(),
             WardEntry::File {
-                sha256: "5656565656565656565656565656565656565656565656565656565656565656"
-                    .to_string(),
+                checksum: Checksum::new(
+                    HashAlgorithm::Sha256,
+                    "5656565656565656565656565656565656565656565656565656565656565656",
+                ),
                 mtime_nanos: 5000,
                 size: 50,
+                attributes: Attributes::default(),
             },
         );
         entries.insert(
             "file[with]brackets.txt".to_string(),
             WardEntry::File {
-                sha256: "7878787878787878787878787878787878787878787878787878787878787878"
-                    .to_string(),
+                checksum: Checksum::new(
+                    HashAlgorithm::Sha256,
+                    "7878787878787878787878787878787878787878787878787878787878787878",
+                ),
                 mtime_nanos: 6000,
                 size: 60,
+                attributes: Attributes::default(),
             },
         );
         entries.insert(
             "file#with#hash.txt".to_string(),
             WardEntry::File {
-                sha256: "9090909090909090909090909090909090909090909090909090909090909090"
-                    .to_string(),
+                checksum: Checksum::new(
+                    HashAlgorithm::Sha256,
+                    "9090909090909090909090909090909090909090909090909090909090909090",
+                ),
                 mtime_nanos: 7000,
                 size: 70,
+                attributes: Attributes::default(),
             },
         );
 
@@ -0,0 +1,765 @@
+//! Live status of a tree, kept up to date as it changes (`treeward watch`).
+//!
+//! `watch` compares the whole tree once, keeping the status of every entry
+//! (unchanged ones included) in memory, and watches each directory with
+//! inotify. Changes are collected until none has arrived for the debounce
+//! period; then only the changed paths are compared again, by running
+//! `compute_status` with them as pathspecs, and each entry whose status is
+//! not what it was is reported. An entry that leaves the listing (back to its
+//! recorded state, or added and gone again) is reported as unchanged.
+//!
+//! A rewritten `.treeward` (an update accepted changes) has its whole
+//! directory compared again. With `verify`, possibly modified files are
+//! checksummed on a background thread and reported again as modified or
+//! unchanged, unless they changed again meanwhile.
+
+mod inotify;
+
+use crate::dir_list::{TREEWARD_FILENAME, is_left_out};
+use crate::lock::{LockError, LockMode, TreeLock};
+use crate::status::{
+    self, ChecksumPolicy, StatusEntry, StatusError, StatusMode, StatusOptions, StatusResult,
+    StatusType,
+};
+use crate::util::names;
+use inotify::{CREATED, Event, IS_DIR, Inotify, MOVED_FROM, MOVED_SELF, OVERFLOW, WATCH_REMOVED};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::io;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// How often to look for finished checksums while some are outstanding.
+const VERIFY_POLL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, thiserror::Error)]
+pub enum WatchError {
+    #[error("Status error: {0}")]
+    Status(#[from] StatusError),
+    #[error("Lock error: {0}")]
+    Lock(#[from] LockError),
+    #[error("IO error on {}: {source}", path.display())]
+    Io { path: PathBuf, source: io::Error },
+    #[error("{} was removed or moved away", .0.display())]
+    RootGone(PathBuf),
+}
+
+pub struct WatchOptions {
+    /// Checksum possibly modified files in the background, to tell whether
+    /// their content changed.
+    pub verify: bool,
+    /// How long no change must arrive before the changes are compared.
+    pub debounce: Duration,
+    /// Maximum number of files checksummed concurrently.
+    pub jobs: NonZeroUsize,
+}
+
+/// What `watch` reports.
+#[derive(Debug)]
+pub enum WatchEvent<'a> {
+    /// The entry's status is new (or, for `StatusEntry::Unchanged`, it left
+    /// the listing).
+    Entry(&'a StatusEntry),
+    /// The ward file at this path no longer matches the digest recorded for
+    /// it in its parent (see `StatusResult::digest_mismatches`).
+    DigestMismatch(&'a str),
+}
+
+/// Watch the tree rooted at `root` and report to `report`: first each entry
+/// that is not unchanged, then every change of status as it happens. Runs
+/// until an error stops it.
+///
+/// `lock` is a shared lock of the tree (see `lock`), held while the initial
+/// status is computed. Later comparisons lock the tree again, waiting for any
+/// update to finish. A comparison that fails is logged and the tree is
+/// watched on; only losing the root or the watches ends the run.
+pub fn watch(
+    root: &Path,
+    lock: TreeLock,
+    options: &WatchOptions,
+    report: &mut dyn FnMut(WatchEvent),
+) -> Result<(), WatchError> {
+    let root = root.canonicalize().map_err(|source| WatchError::Io {
+        path: root.to_path_buf(),
+        source,
+    })?;
+    let inotify = Inotify::new().map_err(|source| WatchError::Io {
+        path: root.clone(),
+        source,
+    })?;
+    let initial = compute(&root, &[String::new()], ChecksumPolicy::Never, options.jobs)?;
+    drop(lock);
+
+    let mut watcher = Watcher {
+        inotify,
+        dirs: HashMap::new(),
+        live: LiveStatus::default(),
+        verifier: options
+            .verify
+            .then(|| Verifier::spawn(root.clone(), options.jobs)),
+        root,
+        jobs: options.jobs,
+    };
+    watcher.live.apply(&[String::new()], initial, report);
+    watcher.watch_tree("")?;
+
+    // Whatever changed before the watches were in place is found by
+    // comparing everything once more.
+    let mut changes = Changes::default();
+    changes.paths.insert(String::new());
+    changes.last = Some(Instant::now());
+    loop {
+        let mut timeout = changes
+            .last
+            .map(|last| options.debounce.saturating_sub(last.elapsed()));
+        if watcher.verifying() {
+            timeout = Some(timeout.map_or(VERIFY_POLL, |timeout| timeout.min(VERIFY_POLL)));
+        }
+        let events = watcher
+            .inotify
+            .read_events(timeout)
+            .map_err(|source| WatchError::Io {
+                path: watcher.root.clone(),
+                source,
+            })?;
+        for event in events {
+            if watcher.note(event, &mut changes)? {
+                changes.last = Some(Instant::now());
+            }
+        }
+        watcher.collect_checksums(report);
+        if changes
+            .last
+            .is_some_and(|last| last.elapsed() >= options.debounce)
+        {
+            watcher.refresh(std::mem::take(&mut changes), report)?;
+        }
+    }
+}
+
+/// Changes noticed since the last comparison.
+#[derive(Debug, Default)]
+struct Changes {
+    /// Paths of the changed entries, relative to the root; the empty path
+    /// stands for the whole tree.
+    paths: BTreeSet<String>,
+    /// Directories created or moved in, to watch.
+    new_dirs: BTreeSet<String>,
+    /// When the last change arrived.
+    last: Option<Instant>,
+}
+
+struct Watcher {
+    root: PathBuf,
+    jobs: NonZeroUsize,
+    inotify: Inotify,
+    /// Watched directories by watch descriptor, relative to the root.
+    dirs: HashMap<i32, String>,
+    live: LiveStatus,
+    verifier: Option<Verifier>,
+}
+
+impl Watcher {
+    /// Add the change `event` reports to `changes`; false if it is of no
+    /// interest (such as one of treeward's own files other than a ward file).
+    fn note(&mut self, event: Event, changes: &mut Changes) -> Result<bool, WatchError> {
+        if event.mask & OVERFLOW != 0 {
+            // Events were lost: only comparing everything is safe.
+            changes.paths.insert(String::new());
+            return Ok(true);
+        }
+        let Some(dir) = self.dirs.get(&event.wd).cloned() else {
+            return Ok(false);
+        };
+        if event.mask & WATCH_REMOVED != 0 {
+            self.dirs.remove(&event.wd);
+            if dir.is_empty() {
+                return Err(WatchError::RootGone(self.root.clone()));
+            }
+            return Ok(false);
+        }
+        if dir.is_empty() && event.mask & MOVED_SELF != 0 {
+            return Err(WatchError::RootGone(self.root.clone()));
+        }
+        // Changes of a directory itself are reported by its parent's watch.
+        let Some(name) = event.name else {
+            return Ok(false);
+        };
+        if is_left_out(&name, dir.is_empty()) {
+            if name == TREEWARD_FILENAME {
+                changes.paths.insert(dir);
+                return Ok(true);
+            }
+            return Ok(false);
+        }
+        let Some(name) = names::from_os(&name) else {
+            return Ok(false);
+        };
+        let path = child_path(&dir, &name);
+        if event.mask & IS_DIR != 0 {
+            if event.mask & MOVED_FROM != 0 {
+                self.unwatch(&path);
+            }
+            if event.mask & CREATED != 0 {
+                changes.new_dirs.insert(path.clone());
+            }
+        }
+        changes.paths.insert(path);
+        Ok(true)
+    }
+
+    /// Compare the entries `changes` names again and report what changed.
+    fn refresh(
+        &mut self,
+        changes: Changes,
+        report: &mut dyn FnMut(WatchEvent),
+    ) -> Result<(), WatchError> {
+        // Watch new directories before comparing them, so that nothing
+        // happening in them in between goes unnoticed.
+        for dir in &changes.new_dirs {
+            self.watch_tree(dir)?;
+        }
+        let scopes = self.scopes(changes.paths, report);
+        if scopes.is_empty() {
+            return Ok(());
+        }
+        if let Some(verifier) = &mut self.verifier {
+            verifier
+                .pending
+                .retain(|path, _| !scopes.iter().any(|scope| covers(scope, path)));
+        }
+
+        let result = match compute(&self.root, &scopes, ChecksumPolicy::Never, self.jobs) {
+            Ok(result) => Ok((scopes, result)),
+            // A path gone (or replaced) again by the time it is compared;
+            // the whole tree always matches.
+            Err(WatchError::Status(StatusError::Pathspec(_))) => {
+                let scopes = vec![String::new()];
+                compute(&self.root, &scopes, ChecksumPolicy::Never, self.jobs)
+                    .map(|result| (scopes, result))
+            }
+            Err(e) => Err(e),
+        };
+        let (scopes, result) = match result {
+            Ok(result) => result,
+            Err(e @ WatchError::Lock(_)) => return Err(e),
+            // A removed root only reports its removal once nothing uses it
+            // any more, which this process (its working directory) may.
+            Err(_) if fs::symlink_metadata(&self.root).is_err() => {
+                return Err(WatchError::RootGone(self.root.clone()));
+            }
+            Err(e) => {
+                warn!("Could not compare the changes: {e}");
+                return Ok(());
+            }
+        };
+
+        let ignored: Vec<String> = result.ignored.clone();
+        for path in &ignored {
+            self.unwatch(path);
+        }
+        self.live.apply(&scopes, result, report);
+        self.verify_possibly_modified(&scopes);
+        Ok(())
+    }
+
+    /// The paths to compare for the changed `paths`: each path not below
+    /// another one, with the other side of any rename involving it. A path
+    /// that is gone and was never recorded (added and removed again) needs no
+    /// comparison: it leaves the listing here.
+    fn scopes(
+        &mut self,
+        paths: BTreeSet<String>,
+        report: &mut dyn FnMut(WatchEvent),
+    ) -> Vec<String> {
+        if paths.contains("") {
+            return vec![String::new()];
+        }
+        let mut scopes = BTreeSet::new();
+        for path in paths {
+            let exists = fs::symlink_metadata(self.root.join(names::to_os(&path))).is_ok();
+            if exists || self.live.is_recorded(&path) {
+                scopes.insert(path);
+            } else {
+                self.live.forget(&path, report);
+            }
+        }
+        for (path, from) in self.live.renames() {
+            if scopes
+                .iter()
+                .any(|scope| covers(scope, &path) || covers(scope, &from))
+            {
+                scopes.insert(path);
+                scopes.insert(from);
+            }
+        }
+        let outermost: Vec<String> = scopes
+            .iter()
+            .filter(|path| !ancestors(path).any(|ancestor| scopes.contains(ancestor)))
+            .cloned()
+            .collect();
+        outermost
+    }
+
+    /// Watch the directory at `path` and every directory below it, except
+    /// ignored ones and treeward's own.
+    fn watch_tree(&mut self, path: &str) -> Result<(), WatchError> {
+        let mut stack = vec![path.to_string()];
+        while let Some(dir) = stack.pop() {
+            if self.live.ignored.contains(&dir) {
+                continue;
+            }
+            let abs = self.root.join(names::to_os(&dir));
+            match self.inotify.add_watch(&abs) {
+                Ok(wd) => {
+                    self.dirs.insert(wd, dir.clone());
+                }
+                // Gone again, or not a directory after all: its parent's
+                // watch reports that.
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::NotFound | io::ErrorKind::NotADirectory
+                    ) =>
+                {
+                    continue;
+                }
+                Err(source) => return Err(WatchError::Io { path: abs, source }),
+            }
+            let Ok(entries) = fs::read_dir(&abs) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let name = entry.file_name();
+                if is_left_out(&name, dir.is_empty())
+                    || !entry.file_type().is_ok_and(|t| t.is_dir())
+                {
+                    continue;
+                }
+                if let Some(name) = names::from_os(&name) {
+                    stack.push(child_path(&dir, &name));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Stop watching the directory at `path` and those below it.
+    fn unwatch(&mut self, path: &str) {
+        let inotify = &self.inotify;
+        self.dirs.retain(|wd, dir| {
+            let below = covers(path, dir);
+            if below {
+                inotify.remove_watch(*wd);
+            }
+            !below
+        });
+    }
+
+    fn verifying(&self) -> bool {
+        self.verifier
+            .as_ref()
+            .is_some_and(|verifier| !verifier.pending.is_empty())
+    }
+
+    /// Have the possibly modified files within `scopes` checksummed.
+    fn verify_possibly_modified(&mut self, scopes: &[String]) {
+        let Some(verifier) = &mut self.verifier else {
+            return;
+        };
+        let paths: Vec<String> = self
+            .live
+            .entries_within(scopes)
+            .filter(|entry| entry.status_type() == StatusType::PossiblyModified)
+            .map(|entry| entry.path().to_string())
+            .collect();
+        verifier.request(paths);
+    }
+
+    /// Take in the checksums the verifier has finished.
+    fn collect_checksums(&mut self, report: &mut dyn FnMut(WatchEvent)) {
+        let Some(verifier) = &mut self.verifier else {
+            return;
+        };
+        while let Ok((id, paths, result)) = verifier.results.try_recv() {
+            // Files that changed since the request are compared anew.
+            let current: Vec<String> = paths
+                .into_iter()
+                .filter(|path| verifier.pending.get(path) == Some(&id))
+                .collect();
+            for path in &current {
+                verifier.pending.remove(path);
+            }
+            match result {
+                Ok(result) => self.live.apply(&current, result, report),
+                // One of the files is gone (its removal is being compared);
+                // the others are checksummed one by one.
+                Err(WatchError::Status(StatusError::Pathspec(_))) if current.len() > 1 => {
+                    for path in current {
+                        verifier.request(vec![path]);
+                    }
+                }
+                Err(WatchError::Status(StatusError::Pathspec(_))) => {}
+                Err(e) => warn!("Could not checksum the possibly modified files: {e}"),
+            }
+        }
+    }
+}
+
+/// Compare the entries `scopes` covers (relative to the canonical `root`;
+/// the empty path is the whole tree) under a shared lock of the tree.
+fn compute(
+    root: &Path,
+    scopes: &[String],
+    policy: ChecksumPolicy,
+    jobs: NonZeroUsize,
+) -> Result<StatusResult, WatchError> {
+    let pathspecs: Vec<PathBuf> = if scopes.iter().any(String::is_empty) {
+        Vec::new()
+    } else {
+        scopes
+            .iter()
+            .map(|scope| root.join(names::to_os(scope)))
+            .collect()
+    };
+    let _lock = TreeLock::acquire(root, LockMode::Shared, true)?;
+    Ok(status::compute_status(
+        root,
+        &StatusOptions {
+            policy,
+            mode: StatusMode::All,
+            jobs,
+            keep_going: true,
+            pathspecs: &pathspecs,
+            ..StatusOptions::default()
+        },
+    )?)
+}
+
+/// Checksums possibly modified files on a thread of its own.
+struct Verifier {
+    requests: Sender<(u64, Vec<String>)>,
+    results: Receiver<(u64, Vec<String>, Result<StatusResult, WatchError>)>,
+    /// Files awaiting their checksum, with the request they are in.
+    pending: HashMap<String, u64>,
+    next_id: u64,
+}
+
+impl Verifier {
+    fn spawn(root: PathBuf, jobs: NonZeroUsize) -> Self {
+        let (requests, requests_rx) = channel::<(u64, Vec<String>)>();
+        let (results_tx, results) = channel();
+        thread::spawn(move || {
+            for (id, paths) in requests_rx {
+                let result = compute(&root, &paths, ChecksumPolicy::WhenPossiblyModified, jobs);
+                if results_tx.send((id, paths, result)).is_err() {
+                    break;
+                }
+            }
+        });
+        Verifier {
+            requests,
+            results,
+            pending: HashMap::new(),
+            next_id: 0,
+        }
+    }
+
+    fn request(&mut self, paths: Vec<String>) {
+        if paths.is_empty() {
+            return;
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        for path in &paths {
+            self.pending.insert(path.clone(), id);
+        }
+        // The thread only ends when `results` is dropped, with `self`.
+        let _ = self.requests.send((id, paths));
+    }
+}
+
+/// Status of every entry of the tree, as last compared.
+#[derive(Debug, Default)]
+struct LiveStatus {
+    /// Every entry by path, unchanged ones included.
+    entries: BTreeMap<String, StatusEntry>,
+    /// Ward files that do not match their recorded digest.
+    digest_mismatches: BTreeSet<String>,
+    /// Paths excluded by ignore rules.
+    ignored: BTreeSet<String>,
+}
+
+impl LiveStatus {
+    /// Replace the status of everything `scopes` covers with `result`, a
+    /// comparison of (at least) those paths, and report each entry whose
+    /// status changed, and each new digest mismatch.
+    fn apply(
+        &mut self,
+        scopes: &[String],
+        result: StatusResult,
+        report: &mut dyn FnMut(WatchEvent),
+    ) {
+        let in_scope = |path: &str| scopes.iter().any(|scope| covers(scope, path));
+        let mut previous: BTreeMap<String, StatusEntry> = BTreeMap::new();
+        for scope in scopes {
+            for path in self.paths_within(scope) {
+                let entry = self.entries.remove(&path).expect("path is listed");
+                previous.insert(path, entry);
+            }
+        }
+
+        for entry in result.statuses {
+            if !in_scope(entry.path()) {
+                continue;
+            }
+            let before = previous.remove(entry.path());
+            if !same_status(before.as_ref(), &entry) {
+                report(WatchEvent::Entry(&entry));
+            }
+            self.entries.insert(entry.path().to_string(), entry);
+        }
+        for (path, entry) in previous {
+            if entry.status_type() != StatusType::Unchanged {
+                report(WatchEvent::Entry(&StatusEntry::Unchanged {
+                    path,
+                    ward_entry: None,
+                }));
+            }
+        }
+
+        self.ignored.retain(|path| !in_scope(path));
+        self.ignored
+            .extend(result.ignored.into_iter().filter(|path| in_scope(path)));
+        self.digest_mismatches.retain(|path| !in_scope(path));
+        for path in result.digest_mismatches {
+            if self.digest_mismatches.insert(path.clone()) {
+                report(WatchEvent::DigestMismatch(&path));
+            }
+        }
+    }
+
+    /// Drop the entries at and below `path`, which is gone without having
+    /// been recorded, reporting that they left the listing.
+    fn forget(&mut self, path: &str, report: &mut dyn FnMut(WatchEvent)) {
+        for path in self.paths_within(path) {
+            let entry = self.entries.remove(&path).expect("path is listed");
+            if entry.status_type() != StatusType::Unchanged {
+                report(WatchEvent::Entry(&StatusEntry::Unchanged {
+                    path,
+                    ward_entry: None,
+                }));
+            }
+        }
+    }
+
+    /// Whether the ward records an entry at `path`.
+    fn is_recorded(&self, path: &str) -> bool {
+        match self.entries.get(path) {
+            Some(entry) if entry.status_type() != StatusType::Added => true,
+            _ => self.renames().any(|(_, from)| from == path),
+        }
+    }
+
+    /// New and old path of each renamed entry.
+    fn renames(&self) -> impl Iterator<Item = (String, String)> + '_ {
+        self.entries.values().filter_map(|entry| match entry {
+            StatusEntry::Renamed { path, from, .. } => Some((path.clone(), from.clone())),
+            _ => None,
+        })
+    }
+
+    /// Paths of the entries at and below `scope`.
+    fn paths_within(&self, scope: &str) -> Vec<String> {
+        if scope.is_empty() {
+            return self.entries.keys().cloned().collect();
+        }
+        // Paths below `scope` sort after it, but not all paths that do are
+        // below it ("a-b" sorts between "a" and "a/b").
+        self.entries
+            .range(scope.to_string()..)
+            .map(|(path, _)| path)
+            .take_while(|path| path.starts_with(scope))
+            .filter(|path| covers(scope, path))
+            .cloned()
+            .collect()
+    }
+
+    fn entries_within<'a>(&'a self, scopes: &'a [String]) -> impl Iterator<Item = &'a StatusEntry> {
+        scopes
+            .iter()
+            .flat_map(|scope| self.paths_within(scope))
+            .filter_map(|path| self.entries.get(&path))
+    }
+}
+
+/// Whether `entry` reports what `before` did: the same status (and, for a
+/// rename, the same old path). No entry counts as unchanged.
+fn same_status(before: Option<&StatusEntry>, entry: &StatusEntry) -> bool {
+    match (before, entry) {
+        (None, entry) => entry.status_type() == StatusType::Unchanged,
+        (Some(StatusEntry::Renamed { from: before, .. }), StatusEntry::Renamed { from, .. }) => {
+            before == from
+        }
+        (Some(before), entry) => before.status_type() == entry.status_type(),
+    }
+}
+
+/// Whether `path` is `scope` or lies below it; the empty scope is the whole
+/// tree.
+fn covers(scope: &str, path: &str) -> bool {
+    scope.is_empty()
+        || path
+            .strip_prefix(scope)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// The directories `path` lies in, the root (empty) included.
+fn ancestors(path: &str) -> impl Iterator<Item = &str> {
+    path.rmatch_indices('/')
+        .map(move |(i, _)| &path[..i])
+        .chain(std::iter::once(""))
+}
+
+fn child_path(dir: &str, name: &str) -> String {
+    if dir.is_empty() {
+        name.to_string()
+    } else {
+        format!("{dir}/{name}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(code: &str, path: &str) -> StatusEntry {
+        let path = path.to_string();
+        match code {
+            "A" => StatusEntry::Added {
+                path,
+                ward_entry: None,
+            },
+            "R" => StatusEntry::Removed {
+                path,
+                old_ward_entry: None,
+            },
+            "M?" => StatusEntry::PossiblyModified {
+                path,
+                ward_entry: None,
+                old_ward_entry: None,
+            },
+            "." => StatusEntry::Unchanged {
+                path,
+                ward_entry: None,
+            },
+            _ => panic!("unexpected code {code}"),
+        }
+    }
+
+    fn result(statuses: Vec<StatusEntry>) -> StatusResult {
+        StatusResult {
+            statuses,
+            ignored: Vec::new(),
+            digest_mismatches: Vec::new(),
+            tracks: Default::default(),
+            protections: Default::default(),
+            fec: Vec::new(),
+            out_of_scope: Default::default(),
+            scope: crate::pathspec::Scope::All,
+            dir_ids: Default::default(),
+            fingerprint: "fp".to_string(),
+        }
+    }
+
+    /// Apply `statuses` for `scopes`, returning what was reported as
+    /// `<code> <path>` lines.
+    fn apply(live: &mut LiveStatus, scopes: &[&str], statuses: Vec<StatusEntry>) -> Vec<String> {
+        let scopes: Vec<String> = scopes.iter().map(|scope| scope.to_string()).collect();
+        let mut reported = Vec::new();
+        live.apply(&scopes, result(statuses), &mut |event| {
+            if let WatchEvent::Entry(entry) = event {
+                reported.push(format!(
+                    "{} {}",
+                    status::status_type_code(entry.status_type()),
+                    entry.path()
+                ));
+            }
+        });
+        reported
+    }
+
+    #[test]
+    fn test_reports_only_entries_whose_status_changed() {
+        let mut live = LiveStatus::default();
+        let initial = apply(
+            &mut live,
+            &[""],
+            vec![entry(".", "a"), entry(".", "a/x"), entry("M?", "b")],
+        );
+        assert_eq!(initial, vec!["M? b"]);
+
+        let reported = apply(
+            &mut live,
+            &["a/x", "a/y", "b"],
+            vec![entry("M?", "a/x"), entry("A", "a/y"), entry("M?", "b")],
+        );
+        assert_eq!(reported, vec!["M? a/x", "A a/y"]);
+        assert_eq!(live.entries.len(), 4);
+    }
+
+    #[test]
+    fn test_entries_leaving_the_listing_are_reported_unchanged() {
+        let mut live = LiveStatus::default();
+        apply(&mut live, &[""], vec![entry("M?", "a"), entry("A", "b")]);
+
+        assert_eq!(apply(&mut live, &["a"], vec![entry(".", "a")]), vec![". a"]);
+
+        let mut reported = Vec::new();
+        live.forget("b", &mut |event| {
+            if let WatchEvent::Entry(entry) = event {
+                reported.push(entry.path().to_string());
+            }
+        });
+        assert_eq!(reported, vec!["b"]);
+        assert!(!live.entries.contains_key("b"));
+    }
+
+    #[test]
+    fn test_scope_covers_only_paths_below_it() {
+        let mut live = LiveStatus::default();
+        apply(
+            &mut live,
+            &[""],
+            vec![entry(".", "a"), entry(".", "a-b"), entry(".", "a/c")],
+        );
+
+        assert_eq!(live.paths_within("a"), vec!["a", "a/c"]);
+        assert!(covers("", "a"));
+        assert!(!covers("a/c", "a"));
+    }
+
+    #[test]
+    fn test_removed_entries_stay_recorded() {
+        let mut live = LiveStatus::default();
+        apply(
+            &mut live,
+            &[""],
+            vec![entry("R", "gone"), entry("A", "new")],
+        );
+
+        assert!(live.is_recorded("gone"));
+        assert!(!live.is_recorded("new"));
+        assert!(!live.is_recorded("unknown"));
+    }
+
+    #[test]
+    fn test_ancestors_end_at_the_root() {
+        assert_eq!(ancestors("a/b/c").collect::<Vec<_>>(), vec!["a/b", "a", ""]);
+        assert_eq!(ancestors("a").collect::<Vec<_>>(), vec![""]);
+    }
+}
@@ -0,0 +1,248 @@
+//! Minimal inotify binding: watches on directories and the events they
+//! report. Other platforms get a stand-in whose `new` fails.
+
+use std::ffi::OsString;
+
+/// One event of a watched directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Event {
+    /// The watch it came from (see `Inotify::add_watch`).
+    pub wd: i32,
+    pub mask: u32,
+    /// The entry of the directory it is about; `None` for the directory
+    /// itself.
+    pub name: Option<OsString>,
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use super::Event;
+    use std::ffi::{CString, OsStr};
+    use std::io;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+    use std::time::Duration;
+
+    /// Changes of a directory's entries (and of the directory itself) that
+    /// can change its status.
+    const WATCH_MASK: u32 = libc::IN_CREATE
+        | libc::IN_DELETE
+        | libc::IN_MODIFY
+        | libc::IN_ATTRIB
+        | libc::IN_MOVED_FROM
+        | libc::IN_MOVED_TO
+        | libc::IN_DELETE_SELF
+        | libc::IN_MOVE_SELF
+        | libc::IN_ONLYDIR
+        | libc::IN_DONT_FOLLOW
+        | libc::IN_EXCL_UNLINK;
+
+    pub(in crate::watch) const IS_DIR: u32 = libc::IN_ISDIR;
+    pub(in crate::watch) const CREATED: u32 = libc::IN_CREATE | libc::IN_MOVED_TO;
+    pub(in crate::watch) const MOVED_FROM: u32 = libc::IN_MOVED_FROM;
+    pub(in crate::watch) const OVERFLOW: u32 = libc::IN_Q_OVERFLOW;
+    pub(in crate::watch) const WATCH_REMOVED: u32 = libc::IN_IGNORED;
+    pub(in crate::watch) const MOVED_SELF: u32 = libc::IN_MOVE_SELF;
+
+    pub(in crate::watch) struct Inotify {
+        fd: OwnedFd,
+    }
+
+    impl Inotify {
+        pub(in crate::watch) fn new() -> io::Result<Self> {
+            // SAFETY: plain system call without pointers.
+            let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC | libc::IN_NONBLOCK) };
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            // SAFETY: `fd` was just opened and is owned by nothing else.
+            Ok(Inotify {
+                fd: unsafe { OwnedFd::from_raw_fd(fd) },
+            })
+        }
+
+        /// Watch the directory at `path` (not following a symlink). A
+        /// directory already watched keeps its watch descriptor, under
+        /// whichever path it was reached by.
+        pub(in crate::watch) fn add_watch(&self, path: &Path) -> io::Result<i32> {
+            let path = CString::new(path.as_os_str().as_bytes())
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+            // SAFETY: `path` is NUL-terminated and outlives the call.
+            let wd =
+                unsafe { libc::inotify_add_watch(self.fd.as_raw_fd(), path.as_ptr(), WATCH_MASK) };
+            if wd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(wd)
+        }
+
+        /// Stop watching `wd`. Its `WATCH_REMOVED` event follows.
+        pub(in crate::watch) fn remove_watch(&self, wd: i32) {
+            // SAFETY: plain system call without pointers. A watch that is
+            // already gone only makes it fail.
+            unsafe { libc::inotify_rm_watch(self.fd.as_raw_fd(), wd) };
+        }
+
+        /// The events that arrive within `timeout` (`None`: however long it
+        /// takes for the first one); none if it passes first.
+        pub(in crate::watch) fn read_events(
+            &self,
+            timeout: Option<Duration>,
+        ) -> io::Result<Vec<Event>> {
+            let mut pollfd = libc::pollfd {
+                fd: self.fd.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            let timeout = timeout.map_or(-1, |timeout| {
+                libc::c_int::try_from(timeout.as_millis()).unwrap_or(libc::c_int::MAX)
+            });
+            // SAFETY: `pollfd` is valid for the one entry passed.
+            if unsafe { libc::poll(&mut pollfd, 1, timeout) } < 0 {
+                let e = io::Error::last_os_error();
+                return match e.kind() {
+                    io::ErrorKind::Interrupted => Ok(Vec::new()),
+                    _ => Err(e),
+                };
+            }
+
+            let mut buf = vec![0u8; 64 * 1024];
+            // SAFETY: `buf` is valid for writing `buf.len()` bytes.
+            let len =
+                unsafe { libc::read(self.fd.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len()) };
+            if len < 0 {
+                let e = io::Error::last_os_error();
+                return match e.kind() {
+                    io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted => Ok(Vec::new()),
+                    _ => Err(e),
+                };
+            }
+            let len = usize::try_from(len).expect("read length is not negative");
+            Ok(parse_events(&buf[..len]))
+        }
+    }
+
+    /// The events in `buf`, as the kernel lays them out: each a fixed header
+    /// followed by its NUL-padded name.
+    pub(super) fn parse_events(mut buf: &[u8]) -> Vec<Event> {
+        const HEADER: usize = std::mem::size_of::<libc::inotify_event>();
+        let mut events = Vec::new();
+        while buf.len() >= HEADER {
+            // SAFETY: `buf` holds at least `HEADER` bytes; the read does not
+            // need them to be aligned.
+            let header: libc::inotify_event =
+                unsafe { std::ptr::read_unaligned(buf.as_ptr().cast()) };
+            let name_len = header.len as usize;
+            let Some(name) = buf.get(HEADER..HEADER + name_len) else {
+                break;
+            };
+            let name = name.split(|&b| b == 0).next().unwrap_or_default();
+            events.push(Event {
+                wd: header.wd,
+                mask: header.mask,
+                name: (!name.is_empty()).then(|| OsStr::from_bytes(name).to_os_string()),
+            });
+            buf = &buf[HEADER + name_len..];
+        }
+        events
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod imp {
+    use super::Event;
+    use std::io;
+    use std::path::Path;
+    use std::time::Duration;
+
+    pub(in crate::watch) const IS_DIR: u32 = 1 << 0;
+    pub(in crate::watch) const CREATED: u32 = 1 << 1;
+    pub(in crate::watch) const MOVED_FROM: u32 = 1 << 2;
+    pub(in crate::watch) const OVERFLOW: u32 = 1 << 3;
+    pub(in crate::watch) const WATCH_REMOVED: u32 = 1 << 4;
+    pub(in crate::watch) const MOVED_SELF: u32 = 1 << 5;
+
+    pub(in crate::watch) struct Inotify;
+
+    impl Inotify {
+        pub(in crate::watch) fn new() -> io::Result<Self> {
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "watching a tree needs inotify, which only Linux has",
+            ))
+        }
+
+        pub(in crate::watch) fn add_watch(&self, _path: &Path) -> io::Result<i32> {
+            unreachable!("Inotify cannot be created")
+        }
+
+        pub(in crate::watch) fn remove_watch(&self, _wd: i32) {}
+
+        pub(in crate::watch) fn read_events(
+            &self,
+            _timeout: Option<Duration>,
+        ) -> io::Result<Vec<Event>> {
+            unreachable!("Inotify cannot be created")
+        }
+    }
+}
+
+pub(super) use imp::{CREATED, IS_DIR, Inotify, MOVED_FROM, MOVED_SELF, OVERFLOW, WATCH_REMOVED};
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::imp::parse_events;
+    use super::*;
+    use std::time::Duration;
+
+    fn raw_event(wd: i32, mask: u32, name: &[u8], padded_len: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&wd.to_ne_bytes());
+        bytes.extend_from_slice(&mask.to_ne_bytes());
+        bytes.extend_from_slice(&0u32.to_ne_bytes());
+        bytes.extend_from_slice(&padded_len.to_ne_bytes());
+        let mut name = name.to_vec();
+        name.resize(padded_len as usize, 0);
+        bytes.extend_from_slice(&name);
+        bytes
+    }
+
+    #[test]
+    fn test_parses_events_with_and_without_names() {
+        let mut buf = raw_event(1, libc::IN_CREATE, b"new.txt", 16);
+        buf.extend(raw_event(2, libc::IN_DELETE_SELF, b"", 0));
+
+        assert_eq!(
+            parse_events(&buf),
+            vec![
+                Event {
+                    wd: 1,
+                    mask: libc::IN_CREATE,
+                    name: Some("new.txt".into()),
+                },
+                Event {
+                    wd: 2,
+                    mask: libc::IN_DELETE_SELF,
+                    name: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reports_changes_in_a_watched_directory() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let inotify = Inotify::new().unwrap();
+        let wd = inotify.add_watch(temp.path()).unwrap();
+        std::fs::write(temp.path().join("file.txt"), "x").unwrap();
+
+        let events = inotify.read_events(Some(Duration::from_secs(5))).unwrap();
+        assert!(
+            events
+                .iter()
+                .any(|e| e.wd == wd && e.mask & CREATED != 0 && e.name == Some("file.txt".into())),
+            "{events:?}"
+        );
+    }
+}
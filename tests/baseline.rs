@@ -0,0 +1,93 @@
+mod common;
+
+use common::treeward_cmd;
+use predicates::prelude::*;
+use std::fs;
+use tempfile::TempDir;
+
+#[test]
+fn status_against_baseline_sees_accepted_changes() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("kept.txt"), "kept").unwrap();
+    fs::write(temp.path().join("gone.txt"), "gone").unwrap();
+    fs::create_dir(temp.path().join("sub")).unwrap();
+    fs::write(temp.path().join("sub/inner.txt"), "inner").unwrap();
+    treeward_cmd(temp.path()).arg("init").assert().success();
+
+    treeward_cmd(temp.path())
+        .args(["baseline", "create", "v1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Created baseline v1 (2 ward files",
+        ));
+
+    fs::remove_file(temp.path().join("gone.txt")).unwrap();
+    fs::write(temp.path().join("sub/new.txt"), "new").unwrap();
+    treeward_cmd(temp.path()).arg("update").assert().success();
+    treeward_cmd(temp.path()).arg("status").assert().success();
+
+    treeward_cmd(temp.path())
+        .args(["status", "--against", "v1"])
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("R  gone.txt"))
+        .stdout(predicate::str::contains("A  sub/new.txt"))
+        .stdout(predicate::str::contains("kept.txt").not())
+        .stdout(predicate::str::contains("Fingerprint").not());
+
+    // The baseline is found from a subdirectory too.
+    treeward_cmd(&temp.path().join("sub"))
+        .args(["status", "--against", "v1"])
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("A  new.txt"));
+}
+
+#[test]
+fn baseline_list_and_delete() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("file.txt"), "content").unwrap();
+    treeward_cmd(temp.path()).arg("init").assert().success();
+    treeward_cmd(temp.path())
+        .args(["baseline", "create", "first"])
+        .assert()
+        .success();
+    treeward_cmd(temp.path())
+        .args(["baseline", "create", "first"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Baseline first already exists"));
+
+    // The baselines directory is not part of the tree.
+    treeward_cmd(temp.path()).arg("status").assert().success();
+
+    let digest = treeward_cmd(temp.path())
+        .arg("digest")
+        .output()
+        .unwrap()
+        .stdout;
+    let digest = String::from_utf8(digest).unwrap();
+    treeward_cmd(temp.path())
+        .args(["baseline", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("first  "))
+        .stdout(predicate::str::contains(digest.trim()));
+
+    treeward_cmd(temp.path())
+        .args(["baseline", "delete", "first"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Deleted baseline first"));
+    treeward_cmd(temp.path())
+        .args(["baseline", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_empty());
+    treeward_cmd(temp.path())
+        .args(["status", "--against", "first"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No baseline named first"));
+}
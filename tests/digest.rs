@@ -0,0 +1,150 @@
+mod common;
+
+use common::{status_fingerprint, treeward_cmd};
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+fn initialized_tree() -> TempDir {
+    let temp = TempDir::new().unwrap();
+    fs::create_dir(temp.path().join("sub")).unwrap();
+    fs::write(temp.path().join("top.txt"), "top").unwrap();
+    fs::write(temp.path().join("sub/file.txt"), "file").unwrap();
+    treeward_cmd(temp.path()).arg("init").assert().success();
+    temp
+}
+
+fn digest_of(root: &Path) -> String {
+    let output = treeward_cmd(root).arg("digest").output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap().trim().to_string()
+}
+
+/// Edit a subdirectory's ward file by hand, leaving its entries intact.
+fn tamper_with_sub_ward(root: &Path) {
+    let ward_path = root.join("sub/.treeward");
+    let content = fs::read_to_string(&ward_path).unwrap();
+    fs::write(&ward_path, format!("# edited\n{content}")).unwrap();
+}
+
+#[test]
+fn digest_prints_tagged_root_digest() {
+    let temp = initialized_tree();
+
+    treeward_cmd(temp.path())
+        .arg("digest")
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match("^sha256:[0-9a-f]{64}\n$").unwrap());
+    treeward_cmd(temp.path())
+        .args(["digest", "--hash", "blake3"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("blake3:"));
+}
+
+#[test]
+fn digest_changes_when_nested_file_is_updated() {
+    let temp = initialized_tree();
+    let before = digest_of(temp.path());
+
+    fs::write(temp.path().join("sub/file.txt"), "changed").unwrap();
+    treeward_cmd(temp.path()).arg("update").assert().success();
+
+    assert_ne!(digest_of(temp.path()), before);
+}
+
+#[test]
+fn digest_from_subdirectory_prints_root_digest() {
+    let temp = initialized_tree();
+    let root_digest = digest_of(temp.path());
+
+    assert_eq!(digest_of(&temp.path().join("sub")), root_digest);
+
+    let output = treeward_cmd(&temp.path().join("sub"))
+        .args(["digest", "--no-discover"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let sub_digest = String::from_utf8(output.stdout).unwrap();
+    assert_ne!(sub_digest.trim(), root_digest);
+}
+
+#[test]
+fn tampered_ward_file_is_detected() {
+    let temp = initialized_tree();
+    tamper_with_sub_ward(temp.path());
+
+    treeward_cmd(temp.path())
+        .arg("verify")
+        .assert()
+        .code(2)
+        .stderr(
+            predicate::str::contains("Tampering detected")
+                .and(predicate::str::contains("sub/.treeward"))
+                .and(predicate::str::contains("digest chain broken")),
+        );
+    treeward_cmd(temp.path())
+        .arg("status")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("sub/.treeward"));
+    treeward_cmd(temp.path())
+        .arg("digest")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("sub/.treeward"));
+}
+
+#[test]
+fn verify_reports_tampering_alongside_changes() {
+    let temp = initialized_tree();
+    tamper_with_sub_ward(temp.path());
+    fs::write(temp.path().join("sub/file.txt"), "changed").unwrap();
+
+    treeward_cmd(temp.path())
+        .arg("verify")
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "Verification failed: ward digest chain broken at 1 ward file(s)",
+        ));
+}
+
+#[test]
+fn update_accepts_tampered_ward_file_only_when_told_to() {
+    let temp = initialized_tree();
+    tamper_with_sub_ward(temp.path());
+
+    treeward_cmd(temp.path())
+        .arg("update")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "--accept-digest-mismatch --fingerprint",
+        ));
+
+    let (_, fingerprint) = status_fingerprint(temp.path(), &[]);
+    treeward_cmd(temp.path())
+        .args(["update", "--fingerprint", &fingerprint])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("sub/.treeward"));
+    treeward_cmd(temp.path())
+        .args(["update", "--accept-digest-mismatch"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--fingerprint"));
+    treeward_cmd(temp.path())
+        .args([
+            "update",
+            "--accept-digest-mismatch",
+            "--fingerprint",
+            &fingerprint,
+        ])
+        .assert()
+        .success();
+
+    treeward_cmd(temp.path()).arg("verify").assert().success();
+}
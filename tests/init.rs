@@ -45,7 +45,7 @@ fn init_respects_matching_fingerprint() {
     assert_file_checksum(
         &temp.path().join(".treeward"),
         "file.txt",
-        "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824",
+        "sha256:2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824",
     );
 }
 
@@ -99,7 +99,7 @@ fn init_verify_matches_verify_fingerprint_and_writes_checksum() {
     assert_file_checksum(
         &temp.path().join(".treeward"),
         "root.txt",
-        "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824",
+        "sha256:2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824",
     );
 }
 
@@ -127,15 +127,44 @@ fn init_always_verify_matches_always_verify_fingerprint_and_writes_checksum() {
     assert_file_checksum(
         &temp.path().join(".treeward"),
         "root.txt",
-        "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824",
+        "sha256:2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824",
     );
     assert_file_checksum(
         &temp.path().join("subdir/.treeward"),
         "file.txt",
-        "0baf982fcab396fdb1c6d82f8f1eb0d2aea9cdd347fb244cf0b2c748df350069",
+        "sha256:0baf982fcab396fdb1c6d82f8f1eb0d2aea9cdd347fb244cf0b2c748df350069",
     );
 }
 
+#[test]
+fn init_with_hash_records_tagged_checksums() {
+    for (algorithm, expected) in [
+        (
+            "blake3",
+            "blake3:ea8f163db38682925e4491c5e58d4bb3506ef8c14eb78a86e908c5624a67200f",
+        ),
+        (
+            "sha512",
+            "sha512:9b71d224bd62f3785d96d46ad3ea3d73319bfbc2890caadae2dff72519673ca7\
+             2323c3d99ba5c11d7c7acc6e14b8c5da0c4663475c2e5c3adef46f73bcdec043",
+        ),
+    ] {
+        let temp = TempDir::new().unwrap();
+        fs::write(temp.path().join("file.txt"), "hello").unwrap();
+
+        let (_, fingerprint) = status_fingerprint(temp.path(), &["--verify", "--hash", algorithm]);
+
+        treeward_cmd(temp.path())
+            .args(["init", "--verify", "--hash", algorithm, "--fingerprint"])
+            .arg(&fingerprint)
+            .assert()
+            .success();
+
+        assert_file_checksum(&temp.path().join(".treeward"), "file.txt", expected);
+        treeward_cmd(temp.path()).arg("verify").assert().success();
+    }
+}
+
 /// Verifies that -v flag enables info-level output showing warded file count.
 #[test]
 fn init_verbose_shows_warded_count() {
@@ -291,15 +320,15 @@ fn make_partially_warded_tree_with_hidden_subdir_change(temp: &TempDir) {
     set_file_mtime(&file_path, original_mtime).unwrap();
 }
 
-fn assert_file_checksum(ward_path: &std::path::Path, entry_name: &str, expected_sha256: &str) {
+fn assert_file_checksum(ward_path: &std::path::Path, entry_name: &str, expected_checksum: &str) {
     let ward_content = fs::read_to_string(ward_path).unwrap();
     let ward: toml::Value = toml::from_str(&ward_content).unwrap();
-    let sha256 = ward
+    let checksum = ward
         .get("entries")
         .and_then(|entries| entries.get(entry_name))
-        .and_then(|entry| entry.get("sha256"))
+        .and_then(|entry| entry.get("checksum"))
         .and_then(toml::Value::as_str)
-        .expect("file entry should have a sha256");
+        .expect("file entry should have a checksum");
 
-    assert_eq!(sha256, expected_sha256);
+    assert_eq!(checksum, expected_checksum);
 }
@@ -0,0 +1,108 @@
+mod common;
+
+use common::treeward_cmd;
+use predicates::prelude::*;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+use tempfile::TempDir;
+
+/// Lock the tree at `root` the way another treeward process on host
+/// `elsewhere` would, recording it as the holder.
+fn hold_lock(root: &Path, exclusive: bool) -> File {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .append(true)
+        .open(root.join(".treeward-lock"))
+        .unwrap();
+    if exclusive {
+        file.lock().unwrap();
+    } else {
+        file.lock_shared().unwrap();
+    }
+    let mode = if exclusive { "exclusive" } else { "shared" };
+    writeln!(file, r#"{{"pid":4242,"host":"elsewhere","mode":"{mode}"}}"#).unwrap();
+    file
+}
+
+fn warded_tree() -> TempDir {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("file.txt"), "hello").unwrap();
+    treeward_cmd(temp.path()).arg("init").assert().success();
+    assert!(temp.path().join(".treeward-lock").exists());
+    temp
+}
+
+#[test]
+fn commands_fail_naming_the_process_holding_the_lock() {
+    let temp = warded_tree();
+    fs::write(temp.path().join("file.txt"), "modified").unwrap();
+    let lock = hold_lock(temp.path(), true);
+
+    for command in ["update", "status"] {
+        treeward_cmd(temp.path())
+            .arg(command)
+            .assert()
+            .code(255)
+            .stderr(predicate::str::contains(
+                "is locked by process 4242 on elsewhere (writing)",
+            ))
+            .stderr(predicate::str::contains("--wait"));
+    }
+
+    drop(lock);
+    treeward_cmd(temp.path()).arg("update").assert().success();
+    assert_eq!(
+        fs::read_to_string(temp.path().join(".treeward-lock")).unwrap(),
+        ""
+    );
+}
+
+#[test]
+fn readers_share_the_lock() {
+    let temp = warded_tree();
+    let lock = hold_lock(temp.path(), false);
+
+    treeward_cmd(temp.path()).arg("status").assert().success();
+    treeward_cmd(temp.path()).arg("verify").assert().success();
+    treeward_cmd(temp.path())
+        .arg("update")
+        .assert()
+        .code(255)
+        .stderr(predicate::str::contains(
+            "is locked by process 4242 on elsewhere (reading)",
+        ));
+
+    drop(lock);
+}
+
+#[test]
+fn wait_blocks_until_the_lock_is_released() {
+    let temp = warded_tree();
+    fs::write(temp.path().join("file.txt"), "modified").unwrap();
+    let lock = hold_lock(temp.path(), true);
+    let releaser = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(300));
+        drop(lock);
+    });
+
+    treeward_cmd(temp.path())
+        .args(["update", "--wait"])
+        .assert()
+        .success();
+    releaser.join().unwrap();
+    treeward_cmd(temp.path()).arg("status").assert().success();
+}
+
+#[test]
+fn no_wait_overrides_wait() {
+    let temp = warded_tree();
+    let _lock = hold_lock(temp.path(), true);
+
+    treeward_cmd(temp.path())
+        .args(["status", "--wait", "--no-wait"])
+        .assert()
+        .code(255);
+}
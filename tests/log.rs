@@ -0,0 +1,146 @@
+mod common;
+
+use common::treeward_cmd;
+use predicates::prelude::*;
+use std::fs;
+use tempfile::TempDir;
+
+fn tree_with_history() -> TempDir {
+    let temp = TempDir::new().unwrap();
+    fs::create_dir(temp.path().join("sub")).unwrap();
+    fs::write(temp.path().join("top.txt"), "top").unwrap();
+    fs::write(temp.path().join("sub/file.txt"), "file").unwrap();
+    treeward_cmd(temp.path()).arg("init").assert().success();
+    fs::write(temp.path().join("sub/new.txt"), "new").unwrap();
+    treeward_cmd(temp.path()).arg("update").assert().success();
+    temp
+}
+
+#[test]
+fn log_lists_updates_newest_first() {
+    let temp = tree_with_history();
+    // Nothing to accept, nothing recorded.
+    treeward_cmd(temp.path()).arg("update").assert().success();
+    treeward_cmd(temp.path())
+        .args(["update", "--dry-run"])
+        .assert()
+        .success();
+
+    let output = treeward_cmd(temp.path()).arg("log").output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("update 1\n"));
+    assert!(stdout.contains("\nupdate 0\n"));
+    assert!(!stdout.contains("update 2"));
+    assert!(stdout.contains("    A  sub/new.txt\n"));
+    assert!(stdout.contains("    A  top.txt\n"));
+    assert!(stdout.contains("Policy:      metadata only\n"));
+}
+
+#[test]
+fn log_lists_undo_with_the_ward_files_it_restored() {
+    let temp = tree_with_history();
+    treeward_cmd(temp.path()).arg("undo").assert().success();
+
+    let output = treeward_cmd(temp.path()).arg("log").output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let (undo, updates) = stdout.split_once("\nupdate 1\n").unwrap();
+    assert!(undo.starts_with("undo 2\n"));
+    assert!(undo.contains("    .treeward\n"));
+    assert!(undo.contains("    sub/.treeward\n"));
+    assert!(!undo.contains("Policy:"));
+    assert!(updates.contains("    A  sub/new.txt\n"));
+
+    treeward_cmd(&temp.path().join("sub"))
+        .args(["log", "."])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("undo 2\n"))
+        .stdout(predicate::str::contains("    .treeward\n"));
+}
+
+#[test]
+fn log_of_path_shows_only_updates_touching_it() {
+    let temp = tree_with_history();
+
+    treeward_cmd(temp.path())
+        .args(["log", "top.txt"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("update 0"))
+        .stdout(predicate::str::contains("update 1").not())
+        .stdout(predicate::str::contains("sub/file.txt").not());
+
+    // Relative to the working directory.
+    treeward_cmd(&temp.path().join("sub"))
+        .args(["log", "new.txt"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("    A  new.txt\n"))
+        .stdout(predicate::str::contains("update 0").not());
+}
+
+#[test]
+fn log_detects_removed_records() {
+    let temp = tree_with_history();
+    fs::write(temp.path().join("top.txt"), "changed").unwrap();
+    treeward_cmd(temp.path()).arg("update").assert().success();
+    let journal_path = temp.path().join(".treeward-journal");
+    let journal = fs::read_to_string(&journal_path).unwrap();
+    let lines: Vec<&str> = journal.lines().collect();
+    assert_eq!(lines.len(), 3);
+    fs::write(&journal_path, format!("{}\n{}\n", lines[0], lines[2])).unwrap();
+
+    treeward_cmd(temp.path())
+        .arg("log")
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("Journal line 2 does not chain"));
+}
+
+#[test]
+fn log_and_verify_detect_records_removed_from_the_end() {
+    let temp = tree_with_history();
+    let journal_path = temp.path().join(".treeward-journal");
+    let journal = fs::read_to_string(&journal_path).unwrap();
+    let first_line = journal.lines().next().unwrap();
+    fs::write(&journal_path, format!("{first_line}\n")).unwrap();
+
+    treeward_cmd(temp.path())
+        .arg("log")
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("Journal ends before record 1"));
+    treeward_cmd(temp.path())
+        .arg("verify")
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("journal truncated"));
+}
+
+#[test]
+fn undone_update_keeps_the_journal_whole() {
+    let temp = tree_with_history();
+    treeward_cmd(temp.path()).arg("undo").assert().success();
+
+    // The restored root ward file records the update before it as the
+    // head; the undone update's record stays in the journal after it.
+    treeward_cmd(temp.path()).arg("log").assert().success();
+    treeward_cmd(temp.path())
+        .arg("verify")
+        .assert()
+        .stderr(predicate::str::contains("journal").not());
+}
+
+#[test]
+fn journal_is_not_an_entry() {
+    let temp = tree_with_history();
+
+    treeward_cmd(temp.path())
+        .arg("status")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("journal").not());
+    treeward_cmd(temp.path()).arg("verify").assert().success();
+}
@@ -180,19 +180,20 @@ fn warn_error_emojis_suppressed_when_not_tty() {
 /// style than the formatter's own `\u{..}` escapes, and either is fine as
 /// long as nothing raw gets through. The formatter unit test in src/main.rs
 /// pins the exact escaping applied at our own boundary.
-#[cfg(unix)]
 #[test]
-fn status_escapes_control_characters_from_unsupported_file_type_errors() {
-    use nix::sys::stat;
-    use nix::unistd;
-
+fn status_escapes_control_characters_from_error_paths() {
     let temp = temp_dir_with_file();
-    treeward_cmd(temp.path()).arg("init").assert().success();
 
     // C0 (ESC, BEL) plus a C1 control (U+009B, the single-byte CSI) so the
     // spec's "including C1 controls" claim has end-to-end coverage.
-    let hostile_name = "fifo-\x1b]0;pwned\x07\u{9b}31m";
-    unistd::mkfifo(&temp.path().join(hostile_name), stat::Mode::S_IRWXU).unwrap();
+    let hostile_name = "dir-\x1b]0;pwned\x07\u{9b}31m";
+    fs::create_dir(temp.path().join(hostile_name)).unwrap();
+    treeward_cmd(temp.path()).arg("init").assert().success();
+
+    // A hand-edited ward file makes status fail naming its path.
+    let ward_path = temp.path().join(hostile_name).join(".treeward");
+    let content = fs::read_to_string(&ward_path).unwrap();
+    fs::write(&ward_path, format!("# edited\n{content}")).unwrap();
 
     let output = treeward_cmd(temp.path())
         .arg("status")
@@ -206,7 +207,7 @@ fn status_escapes_control_characters_from_unsupported_file_type_errors() {
     let stderr = std::str::from_utf8(&output.stderr).expect("stderr must be valid UTF-8");
 
     assert!(
-        stderr.contains("fifo-"),
+        stderr.contains("dir-"),
         "stderr did not name the offending file: {stderr:?}"
     );
     assert!(
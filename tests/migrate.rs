@@ -0,0 +1,122 @@
+mod common;
+
+use common::treeward_cmd;
+use filetime::{FileTime, set_file_mtime};
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+const HELLO_SHA256: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+/// Write a version 1 ward for a directory holding `hello.txt` and, optionally,
+/// a subdirectory `sub`.
+fn write_v1_ward(dir: &Path, with_sub: bool) {
+    fs::write(dir.join("hello.txt"), "hello").unwrap();
+    let hello = dir.join("hello.txt");
+    set_file_mtime(&hello, FileTime::from_unix_time(1_700_000_000, 0)).unwrap();
+
+    let mut ward = format!(
+        r#"[metadata]
+version = 1
+
+[entries."hello.txt"]
+type = "file"
+sha256 = "{HELLO_SHA256}"
+mtime_nanos = 1700000000000000000
+size = 5
+"#
+    );
+    if with_sub {
+        ward.push_str("\n[entries.sub]\ntype = \"dir\"\n");
+    }
+    fs::write(dir.join(".treeward"), ward).unwrap();
+}
+
+#[test]
+fn migrate_rewrites_version_1_tree() {
+    let temp = TempDir::new().unwrap();
+    fs::create_dir(temp.path().join("sub")).unwrap();
+    write_v1_ward(&temp.path().join("sub"), false);
+    write_v1_ward(temp.path(), true);
+
+    treeward_cmd(temp.path())
+        .args(["-v", "migrate"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Migrated 2 of 2 ward files"));
+
+    for ward_path in [".treeward", "sub/.treeward"] {
+        let ward = fs::read_to_string(temp.path().join(ward_path)).unwrap();
+        assert!(ward.contains("version = 3"), "{ward}");
+        assert!(
+            ward.contains(&format!("checksum = \"sha256:{HELLO_SHA256}\"")),
+            "{ward}"
+        );
+    }
+
+    // The root now pins the subdirectory's ward file.
+    let root_ward = fs::read_to_string(temp.path().join(".treeward")).unwrap();
+    assert!(root_ward.contains("digest = \"sha256:"), "{root_ward}");
+    treeward_cmd(temp.path()).arg("digest").assert().success();
+
+    treeward_cmd(temp.path()).arg("verify").assert().success();
+
+    // A second run has nothing left to do.
+    treeward_cmd(temp.path())
+        .args(["-v", "migrate"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Migrated 0 of 2 ward files"));
+}
+
+#[test]
+fn migrate_from_subdirectory_rewrites_whole_tree() {
+    let temp = TempDir::new().unwrap();
+    fs::create_dir(temp.path().join("sub")).unwrap();
+    write_v1_ward(&temp.path().join("sub"), false);
+    write_v1_ward(temp.path(), true);
+
+    treeward_cmd(&temp.path().join("sub"))
+        .args(["-v", "migrate", "--no-discover", "--dry-run"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Migrated 1 of 1 ward files"));
+
+    treeward_cmd(&temp.path().join("sub"))
+        .args(["-v", "migrate"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Migrated 2 of 2 ward files"));
+
+    treeward_cmd(temp.path()).arg("verify").assert().success();
+}
+
+#[test]
+fn migrate_dry_run_writes_nothing() {
+    let temp = TempDir::new().unwrap();
+    write_v1_ward(temp.path(), false);
+    let before = fs::read_to_string(temp.path().join(".treeward")).unwrap();
+
+    treeward_cmd(temp.path())
+        .args(["-v", "migrate", "--dry-run"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("DRY RUN").and(predicate::str::contains(".treeward")));
+
+    assert_eq!(
+        fs::read_to_string(temp.path().join(".treeward")).unwrap(),
+        before
+    );
+}
+
+#[test]
+fn migrate_fails_when_not_initialized() {
+    let temp = TempDir::new().unwrap();
+
+    treeward_cmd(temp.path())
+        .arg("migrate")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Not initialized"));
+}
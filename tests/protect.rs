@@ -0,0 +1,272 @@
+mod common;
+
+use common::treeward_cmd;
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+/// Deterministic, incompressible-looking content.
+fn data(len: usize) -> Vec<u8> {
+    (0..len as u32)
+        .map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8)
+        .collect()
+}
+
+fn fec_files(dir: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(dir.join(".treeward-fec")) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    names.sort();
+    names
+}
+
+fn protected_tree() -> TempDir {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("data.bin"), data(20_000)).unwrap();
+    fs::create_dir(temp.path().join("sub")).unwrap();
+    fs::write(temp.path().join("sub/small.txt"), "small").unwrap();
+    treeward_cmd(temp.path()).arg("init").assert().success();
+    treeward_cmd(temp.path())
+        .args(["protect", "--loss-tolerance", "20"])
+        .assert()
+        .success();
+    temp
+}
+
+#[test]
+fn protect_corrupt_repair_cycle() {
+    let temp = protected_tree();
+    let path = temp.path().join("data.bin");
+    assert_eq!(fec_files(temp.path()).len(), 1);
+    assert_eq!(fec_files(&temp.path().join("sub")).len(), 1);
+    assert!(
+        fs::read_to_string(temp.path().join(".treeward"))
+            .unwrap()
+            .contains("[metadata.protection]")
+    );
+    treeward_cmd(temp.path()).arg("verify").assert().success();
+
+    // Silent corruption: same size, same mtime.
+    let mtime = fs::metadata(&path).unwrap().modified().unwrap();
+    let mut content = fs::read(&path).unwrap();
+    for byte in &mut content[1000..1100] {
+        *byte ^= 0xff;
+    }
+    fs::write(&path, &content).unwrap();
+    fs::File::options()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_modified(mtime)
+        .unwrap();
+
+    treeward_cmd(temp.path())
+        .args(["status", "--always-verify"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("M  data.bin"))
+        .stderr(predicate::str::contains(
+            "data.bin can be restored with treeward repair",
+        ));
+
+    treeward_cmd(temp.path())
+        .args(["repair", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Would repair data.bin"));
+    assert_eq!(fs::read(&path).unwrap(), content);
+
+    treeward_cmd(temp.path())
+        .arg("repair")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Repaired data.bin"));
+    assert_eq!(fs::read(&path).unwrap(), data(20_000));
+    assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), mtime);
+    treeward_cmd(temp.path()).arg("verify").assert().success();
+}
+
+#[test]
+fn repair_fails_when_too_much_is_lost() {
+    let temp = protected_tree();
+    fs::remove_file(temp.path().join("data.bin")).unwrap();
+
+    treeward_cmd(temp.path())
+        .args(["status", "--always-verify", "--format", "json"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains(
+            r#""fec":[{"path":"data.bin","state":"unrepairable"}]"#,
+        ));
+
+    treeward_cmd(temp.path())
+        .arg("repair")
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("Cannot repair data.bin"));
+    assert!(!temp.path().join("data.bin").exists());
+}
+
+#[test]
+fn full_loss_tolerance_restores_removed_file() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("gone.txt"), "precious").unwrap();
+    treeward_cmd(temp.path()).arg("init").assert().success();
+    treeward_cmd(temp.path())
+        .args(["protect", "--loss-tolerance", "100"])
+        .assert()
+        .success();
+
+    fs::remove_file(temp.path().join("gone.txt")).unwrap();
+    treeward_cmd(temp.path())
+        .arg("repair")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Repaired gone.txt"));
+    assert_eq!(
+        fs::read_to_string(temp.path().join("gone.txt")).unwrap(),
+        "precious"
+    );
+}
+
+#[test]
+fn update_regenerates_and_removes_fec_files() {
+    let temp = protected_tree();
+    let before = fec_files(temp.path());
+
+    fs::write(temp.path().join("data.bin"), data(30_000)).unwrap();
+    fs::write(temp.path().join("new.txt"), "new").unwrap();
+    fs::create_dir(temp.path().join("newdir")).unwrap();
+    fs::write(temp.path().join("newdir/inner.txt"), "inner").unwrap();
+    treeward_cmd(temp.path()).arg("update").assert().success();
+
+    let after = fec_files(temp.path());
+    assert_eq!(after.len(), 2);
+    assert!(before.iter().all(|name| !after.contains(name)));
+    // New directories inherit protection.
+    assert_eq!(fec_files(&temp.path().join("newdir")).len(), 1);
+    treeward_cmd(temp.path()).arg("verify").assert().success();
+}
+
+#[test]
+fn verify_fails_on_damaged_fec_file() {
+    let temp = protected_tree();
+    let fec_dir = temp.path().join("sub/.treeward-fec");
+    let fec_path = fec_dir.join(&fec_files(&temp.path().join("sub"))[0]);
+    let mut fec = fs::read(&fec_path).unwrap();
+    let middle = fec.len() / 2;
+    fec[middle] ^= 1;
+    fs::write(&fec_path, &fec).unwrap();
+
+    treeward_cmd(temp.path())
+        .arg("verify")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "sub/small.txt has damaged FEC data",
+        ));
+
+    treeward_cmd(temp.path())
+        .args(["update", "--always-verify"])
+        .assert()
+        .success();
+    assert_ne!(fs::read(&fec_path).unwrap(), fec);
+    treeward_cmd(temp.path()).arg("verify").assert().success();
+}
+
+#[test]
+fn protect_requires_recorded_changes() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("file.txt"), "content").unwrap();
+    treeward_cmd(temp.path()).arg("init").assert().success();
+    fs::write(temp.path().join("added.txt"), "added").unwrap();
+
+    treeward_cmd(temp.path())
+        .arg("protect")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("treeward update"));
+    assert!(!temp.path().join(".treeward-fec").exists());
+}
+
+#[test]
+fn unprotect_removes_fec_data() {
+    let temp = protected_tree();
+
+    treeward_cmd(temp.path())
+        .args(["unprotect", "sub"])
+        .assert()
+        .success();
+    assert!(!temp.path().join("sub/.treeward-fec").exists());
+    assert_eq!(fec_files(temp.path()).len(), 1);
+
+    treeward_cmd(temp.path())
+        .arg("unprotect")
+        .assert()
+        .success();
+    assert!(!temp.path().join(".treeward-fec").exists());
+    assert!(
+        !fs::read_to_string(temp.path().join(".treeward"))
+            .unwrap()
+            .contains("protection")
+    );
+    treeward_cmd(temp.path()).arg("verify").assert().success();
+}
+
+#[test]
+fn repair_from_mirror_without_protection() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("data.bin"), data(20_000)).unwrap();
+    fs::write(temp.path().join("notes.txt"), "notes").unwrap();
+    treeward_cmd(temp.path()).arg("init").assert().success();
+    let mirror = TempDir::new().unwrap();
+    fs::write(mirror.path().join("data.bin"), data(20_000)).unwrap();
+    fs::write(mirror.path().join("notes.txt"), "notes").unwrap();
+
+    // Silent corruption of one file, an unrecorded edit of the other.
+    let path = temp.path().join("data.bin");
+    let mtime = fs::metadata(&path).unwrap().modified().unwrap();
+    let mut content = fs::read(&path).unwrap();
+    content[10] ^= 1;
+    fs::write(&path, &content).unwrap();
+    fs::File::options()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_modified(mtime)
+        .unwrap();
+    fs::write(temp.path().join("notes.txt"), "edited").unwrap();
+    fs::File::options()
+        .write(true)
+        .open(temp.path().join("notes.txt"))
+        .unwrap()
+        .set_modified(std::time::SystemTime::UNIX_EPOCH)
+        .unwrap();
+
+    treeward_cmd(temp.path())
+        .arg("repair")
+        .arg("--from")
+        .arg(mirror.path())
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("Restored data.bin from"))
+        .stderr(predicate::str::contains("Not repairing notes.txt"));
+    assert_eq!(fs::read(&path).unwrap(), data(20_000));
+    assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), mtime);
+    assert_eq!(
+        fs::read_to_string(temp.path().join("notes.txt")).unwrap(),
+        "edited"
+    );
+
+    treeward_cmd(temp.path())
+        .args(["repair", "--force", "--from"])
+        .arg(mirror.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Restored notes.txt from"));
+    treeward_cmd(temp.path()).arg("verify").assert().success();
+}
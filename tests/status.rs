@@ -98,6 +98,26 @@ fn status_verify_reports_modified_files() {
         .stderr(predicate::str::is_empty());
 }
 
+#[test]
+fn status_tracks_root_only_treeward_names_in_subdirectories() {
+    let temp = TempDir::new().unwrap();
+    fs::create_dir_all(temp.path().join("sub/.treeward-undo")).unwrap();
+    fs::write(temp.path().join("sub/.treeward-undo/important"), "data").unwrap();
+    fs::write(temp.path().join("sub/.treeward-journal"), "notes").unwrap();
+
+    treeward_cmd(temp.path()).arg("init").assert().success();
+
+    fs::write(temp.path().join("sub/.treeward-undo/important"), "lost").unwrap();
+    fs::remove_file(temp.path().join("sub/.treeward-journal")).unwrap();
+
+    treeward_cmd(temp.path())
+        .args(["status", "--verify"])
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("M  sub/.treeward-undo/important"))
+        .stdout(predicate::str::contains("R  sub/.treeward-journal"));
+}
+
 #[test]
 fn status_default_uses_metadata_only_policy() {
     let temp = TempDir::new().unwrap();
@@ -362,6 +382,33 @@ fn status_diff_shows_removed_file_details() {
         .stderr(predicate::str::is_empty());
 }
 
+#[test]
+fn status_diff_shows_renamed_file() {
+    let temp = TempDir::new().unwrap();
+    fs::create_dir(temp.path().join("sub")).unwrap();
+    fs::write(temp.path().join("file.txt"), "hello").unwrap();
+
+    treeward_cmd(temp.path()).arg("init").assert().success();
+
+    fs::rename(
+        temp.path().join("file.txt"),
+        temp.path().join("sub/moved.txt"),
+    )
+    .unwrap();
+
+    treeward_cmd(temp.path())
+        .arg("status")
+        .arg("--diff")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("R> sub/moved.txt"))
+        .stdout(predicate::str::contains(
+            "renamed: file.txt -> sub/moved.txt",
+        ))
+        .stdout(predicate::str::contains("A ").not())
+        .stderr(predicate::str::is_empty());
+}
+
 #[test]
 fn status_diff_no_details_for_added_files() {
     let temp = TempDir::new().unwrap();
@@ -574,3 +621,376 @@ fn status_exits_code_255_on_permission_error() {
         "status should exit with code 255 on permission error"
     );
 }
+
+#[test]
+fn status_json_reports_entries_and_fingerprint() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("file.txt"), "hello").unwrap();
+
+    treeward_cmd(temp.path()).arg("init").assert().success();
+
+    fs::write(temp.path().join("file.txt"), "hello, world").unwrap();
+    fs::write(temp.path().join("new.txt"), "new").unwrap();
+
+    let text_output = status_output(temp.path(), &["--diff"]);
+    let output = status_output(temp.path(), &["--format", "json", "--diff"]);
+    assert_eq!(output.status.code(), Some(1));
+
+    let document: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(document["schema_version"], 1);
+    assert_eq!(document["clean"], false);
+    assert_eq!(
+        document["fingerprint"].as_str().unwrap(),
+        common::extract_fingerprint(&text_output.stdout)
+    );
+
+    let entries = document["entries"].as_array().unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0]["path"], "file.txt");
+    assert_eq!(entries[0]["code"], "M");
+    assert_eq!(entries[0]["old"]["size"], 5);
+    assert_eq!(entries[0]["new"]["size"], 12);
+    assert_eq!(
+        entries[0]["old"]["checksum"],
+        "sha256:2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+    );
+    assert_eq!(entries[1]["path"], "new.txt");
+    assert_eq!(entries[1]["status"], "added");
+    assert!(entries[1].get("old").is_none());
+    assert_eq!(entries[1]["new"]["type"], "file");
+    assert_eq!(entries[1]["new"]["size"], 3);
+    assert_eq!(
+        entries[1]["new"]["checksum"],
+        "sha256:11507a0e2f5e69d5dfa40a62a1bd7b6ee57e6bcd85c67c9b8431b36fff21c437"
+    );
+}
+
+#[test]
+fn status_ndjson_clean_tree_emits_only_summary() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("file.txt"), "hello").unwrap();
+
+    treeward_cmd(temp.path()).arg("init").assert().success();
+
+    let output = status_output(temp.path(), &["--format", "ndjson"]);
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 1, "{stdout}");
+    let summary: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+    assert_eq!(summary["type"], "summary");
+    assert_eq!(summary["clean"], true);
+    assert_eq!(summary["counts"]["unchanged"], 0);
+}
+
+#[test]
+fn status_ignored_lists_skipped_entries() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join(".treewardignore"), "*.swp\ncache/\n").unwrap();
+    fs::write(temp.path().join("file.txt"), "hello").unwrap();
+    fs::write(temp.path().join("file.txt.swp"), "swap").unwrap();
+    fs::create_dir(temp.path().join("cache")).unwrap();
+    fs::write(temp.path().join("cache/blob"), "blob").unwrap();
+
+    treeward_cmd(temp.path()).arg("init").assert().success();
+
+    // Ignored entries are neither reported nor make the tree unclean.
+    treeward_cmd(temp.path())
+        .arg("status")
+        .assert()
+        .success()
+        .stdout("");
+
+    treeward_cmd(temp.path())
+        .args(["status", "--ignored"])
+        .assert()
+        .success()
+        .stdout("!  cache\n!  file.txt.swp\n");
+}
+
+#[test]
+#[cfg(unix)]
+fn status_diff_shows_mode_change_of_tracked_file() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp = TempDir::new().unwrap();
+    let tool = temp.path().join("tool");
+    fs::write(&tool, "binary").unwrap();
+    fs::set_permissions(&tool, fs::Permissions::from_mode(0o755)).unwrap();
+
+    treeward_cmd(temp.path())
+        .args(["init", "--track", "mode,owner"])
+        .assert()
+        .success();
+
+    fs::set_permissions(&tool, fs::Permissions::from_mode(0o4755)).unwrap();
+
+    treeward_cmd(temp.path())
+        .args(["status", "--diff"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::starts_with(
+            "P  tool\n   mode: 0755 -> 4755\n",
+        ));
+}
+
+/// Sets a `user.` extended attribute, or returns false when the filesystem
+/// backing the temp dir does not support them.
+#[cfg(target_os = "linux")]
+fn set_xattr(path: &std::path::Path, name: &str, value: &str) -> bool {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes()).unwrap();
+    let name = CString::new(name).unwrap();
+    // SAFETY: all pointers are valid for the given lengths.
+    let rc = unsafe {
+        libc::setxattr(
+            path.as_ptr(),
+            name.as_ptr(),
+            value.as_ptr().cast(),
+            value.len(),
+            0,
+        )
+    };
+    rc == 0
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn status_diff_shows_xattr_change_of_tracked_file() {
+    let temp = TempDir::new().unwrap();
+    let tool = temp.path().join("tool");
+    fs::write(&tool, "binary").unwrap();
+    if !set_xattr(&tool, "user.label", "old") {
+        return;
+    }
+
+    treeward_cmd(temp.path())
+        .args(["init", "--track", "xattr:user.label"])
+        .assert()
+        .success();
+
+    set_xattr(&tool, "user.label", "new");
+
+    treeward_cmd(temp.path())
+        .args(["status", "--diff"])
+        .assert()
+        .failure()
+        .stdout(
+            predicate::str::is_match(
+                "^M  tool\n   xattr user.label: sha256: [0-9a-f]{12}\\.\\.\\. -> sha256: [0-9a-f]{12}\\.\\.\\.\n",
+            )
+            .unwrap(),
+        );
+}
+
+#[test]
+#[cfg(unix)]
+fn status_diff_shows_split_hard_link() {
+    let temp = TempDir::new().unwrap();
+    fs::create_dir(temp.path().join("a")).unwrap();
+    fs::create_dir(temp.path().join("b")).unwrap();
+    fs::write(temp.path().join("a/data"), "data").unwrap();
+    fs::hard_link(temp.path().join("a/data"), temp.path().join("b/data")).unwrap();
+
+    treeward_cmd(temp.path())
+        .args(["init", "--track", "links"])
+        .assert()
+        .success();
+
+    fs::remove_file(temp.path().join("b/data")).unwrap();
+    fs::write(temp.path().join("b/data"), "data").unwrap();
+
+    treeward_cmd(temp.path())
+        .args(["status", "--diff"])
+        .assert()
+        .failure()
+        .stdout(
+            predicate::str::is_match(
+                "^M  a/data\n   nlink: 2 -> 1\n   link_group: [0-9a-f]{16} -> none\n",
+            )
+            .unwrap(),
+        );
+}
+
+#[test]
+#[cfg(unix)]
+fn status_verifies_tree_with_fifo_and_socket() {
+    let temp = TempDir::new().unwrap();
+    nix::unistd::mkfifo(&temp.path().join("pipe"), nix::sys::stat::Mode::S_IRWXU).unwrap();
+    let _listener = std::os::unix::net::UnixListener::bind(temp.path().join("sock")).unwrap();
+
+    treeward_cmd(temp.path()).arg("init").assert().success();
+    let ward = fs::read_to_string(temp.path().join(".treeward")).unwrap();
+    assert!(ward.contains("type = \"fifo\""), "{ward}");
+    assert!(ward.contains("type = \"socket\""), "{ward}");
+
+    // `--always-verify` checksums every file; the FIFO must not be opened.
+    treeward_cmd(temp.path())
+        .args(["status", "--always-verify"])
+        .assert()
+        .success()
+        .stdout("");
+
+    fs::remove_file(temp.path().join("pipe")).unwrap();
+    fs::write(temp.path().join("pipe"), "").unwrap();
+    treeward_cmd(temp.path())
+        .args(["status", "--diff"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::starts_with(
+            "M  pipe\n   was: fifo\n   now: file (0 bytes, ",
+        ));
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn status_diff_shows_device_number_change() {
+    let temp = TempDir::new().unwrap();
+    let mknod = |minor| {
+        let path = std::ffi::CString::new(temp.path().join("null").to_str().unwrap()).unwrap();
+        unsafe {
+            libc::mknod(
+                path.as_ptr(),
+                libc::S_IFCHR | 0o666,
+                libc::makedev(1, minor),
+            ) == 0
+        }
+    };
+    // Creating device nodes needs CAP_MKNOD.
+    if !mknod(3) {
+        return;
+    }
+    treeward_cmd(temp.path()).arg("init").assert().success();
+
+    fs::remove_file(temp.path().join("null")).unwrap();
+    assert!(mknod(5));
+
+    treeward_cmd(temp.path())
+        .args(["status", "--diff"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::starts_with(
+            "M  null\n   device: 1:3 -> 1:5\n\n",
+        ));
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn status_wards_non_utf8_names() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let temp = TempDir::new().unwrap();
+    let dir = temp.path().join(OsStr::from_bytes(b"dir\xff"));
+    fs::create_dir(&dir).unwrap();
+    fs::write(dir.join(OsStr::from_bytes(b"caf\xe9.txt")), "latin-1").unwrap();
+    fs::write(dir.join("café.txt"), "utf-8").unwrap();
+
+    treeward_cmd(temp.path()).arg("init").assert().success();
+    let ward = fs::read_to_string(dir.join(".treeward")).unwrap();
+    assert!(ward.contains(r#"[entries."caf\u0000e9.txt"]"#), "{ward}");
+    treeward_cmd(temp.path()).arg("verify").assert().success();
+
+    fs::write(dir.join(OsStr::from_bytes(b"caf\xe9.txt")), "changed").unwrap();
+    treeward_cmd(temp.path())
+        .args(["status", "--verify"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::starts_with("M  dir\\xff/caf\\xe9.txt\n\n"));
+
+    treeward_cmd(temp.path()).arg("update").assert().success();
+    treeward_cmd(temp.path()).arg("verify").assert().success();
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn status_wards_non_utf8_symlink_targets() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::symlink;
+
+    let temp = TempDir::new().unwrap();
+    symlink(OsStr::from_bytes(b"caf\xe9"), temp.path().join("link")).unwrap();
+
+    treeward_cmd(temp.path()).arg("init").assert().success();
+    let ward = fs::read_to_string(temp.path().join(".treeward")).unwrap();
+    assert!(ward.contains(r#"symlink_target = "caf\u0000e9""#), "{ward}");
+    treeward_cmd(temp.path()).arg("verify").assert().success();
+
+    fs::remove_file(temp.path().join("link")).unwrap();
+    symlink(OsStr::from_bytes(b"caf\xe8"), temp.path().join("link")).unwrap();
+    treeward_cmd(temp.path())
+        .arg("status")
+        .assert()
+        .failure()
+        .stdout(predicate::str::starts_with("M  link\n"));
+}
+
+#[test]
+fn status_rejects_unknown_tracked_attribute() {
+    let temp = TempDir::new().unwrap();
+
+    treeward_cmd(temp.path())
+        .args(["status", "--track", "mode,inode"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown attribute 'inode'"));
+}
+
+#[test]
+fn status_from_subdirectory_discovers_ward_root() {
+    let temp = TempDir::new().unwrap();
+    let sub = temp.path().join("a/b");
+    fs::create_dir_all(&sub).unwrap();
+    fs::write(sub.join("file.txt"), "hello").unwrap();
+
+    treeward_cmd(temp.path()).arg("init").assert().success();
+
+    fs::write(sub.join("new.txt"), "new").unwrap();
+    fs::write(temp.path().join("a/sibling.txt"), "sibling").unwrap();
+
+    // Paths are relative to the working directory, the whole tree is checked.
+    treeward_cmd(&sub)
+        .arg("status")
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("A  new.txt"))
+        .stdout(predicate::str::contains("A  ../sibling.txt"));
+
+    // Pathspecs are relative to the working directory too.
+    treeward_cmd(&sub)
+        .args(["status", "."])
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("A  new.txt"))
+        .stdout(predicate::str::contains("sibling").not());
+
+    treeward_cmd(&sub)
+        .args(["status", "--no-discover"])
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("A  new.txt"))
+        .stdout(predicate::str::contains("sibling").not());
+}
+
+#[test]
+fn update_from_subdirectory_keeps_digest_chain() {
+    let temp = TempDir::new().unwrap();
+    let sub = temp.path().join("a");
+    fs::create_dir(&sub).unwrap();
+    fs::write(sub.join("file.txt"), "hello").unwrap();
+
+    treeward_cmd(temp.path()).arg("init").assert().success();
+
+    fs::write(sub.join("new.txt"), "new").unwrap();
+    treeward_cmd(&sub).arg("update").assert().success();
+
+    treeward_cmd(temp.path())
+        .arg("verify")
+        .assert()
+        .success()
+        .stdout(predicate::str::is_empty());
+}
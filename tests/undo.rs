@@ -0,0 +1,67 @@
+mod common;
+
+use common::treeward_cmd;
+use predicates::prelude::*;
+use std::fs;
+use tempfile::TempDir;
+
+#[test]
+fn undo_brings_back_checksums_of_accepted_corruption() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("data.txt"), "original").unwrap();
+    treeward_cmd(temp.path()).arg("init").assert().success();
+
+    fs::write(temp.path().join("data.txt"), "corrupt!").unwrap();
+    treeward_cmd(temp.path())
+        .args(["update", "--verify"])
+        .assert()
+        .success();
+    treeward_cmd(temp.path()).arg("verify").assert().success();
+
+    treeward_cmd(temp.path())
+        .args(["undo", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Would restore .treeward"));
+    treeward_cmd(temp.path()).arg("verify").assert().success();
+
+    treeward_cmd(temp.path())
+        .arg("undo")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Restored .treeward"));
+    treeward_cmd(temp.path())
+        .args(["status", "--verify"])
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("M  data.txt"));
+
+    treeward_cmd(temp.path())
+        .arg("undo")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Nothing to undo"));
+}
+
+#[test]
+fn undo_refuses_after_later_changes() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("data.txt"), "original").unwrap();
+    treeward_cmd(temp.path()).arg("init").assert().success();
+    fs::write(temp.path().join("data.txt"), "edited").unwrap();
+    treeward_cmd(temp.path()).arg("update").assert().success();
+
+    // Any byte counts, even one that does not change what the ward records.
+    let ward_path = temp.path().join(".treeward");
+    let edited = fs::read_to_string(&ward_path).unwrap() + "\n";
+    fs::write(&ward_path, &edited).unwrap();
+
+    treeward_cmd(temp.path())
+        .arg("undo")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Ward file .treeward changed since the last update",
+        ));
+    assert_eq!(fs::read_to_string(&ward_path).unwrap(), edited);
+}
@@ -50,6 +50,96 @@ fn update_respects_fingerprint() {
         .stdout(predicate::str::is_empty());
 }
 
+/// A reviewed rename is accepted by update and recorded at the new path.
+#[test]
+fn update_accepts_fingerprint_of_renamed_file() {
+    let temp = TempDir::new().unwrap();
+    fs::create_dir(temp.path().join("sub")).unwrap();
+    fs::write(temp.path().join("file.txt"), "hello").unwrap();
+
+    treeward_cmd(temp.path()).arg("init").assert().success();
+
+    fs::rename(
+        temp.path().join("file.txt"),
+        temp.path().join("sub/file.txt"),
+    )
+    .unwrap();
+
+    let (status_output, fingerprint) = status_fingerprint(temp.path(), &[]);
+    assert!(String::from_utf8_lossy(&status_output.stdout).contains("R> sub/file.txt"));
+
+    treeward_cmd(temp.path())
+        .arg("update")
+        .arg("--fingerprint")
+        .arg(&fingerprint)
+        .assert()
+        .success();
+
+    treeward_cmd(temp.path()).arg("status").assert().success();
+}
+
+/// A fingerprint from a sequential status must be accepted by a parallel
+/// update: `--jobs` never changes the fingerprint.
+#[test]
+fn update_with_jobs_accepts_sequential_fingerprint() {
+    let temp = TempDir::new().unwrap();
+    for i in 0..6 {
+        fs::write(temp.path().join(format!("file{i}.txt")), format!("v{i}")).unwrap();
+    }
+
+    treeward_cmd(temp.path()).arg("init").assert().success();
+
+    for i in 0..6 {
+        fs::write(
+            temp.path().join(format!("file{i}.txt")),
+            format!("updated {i}"),
+        )
+        .unwrap();
+    }
+    fs::write(temp.path().join("new.txt"), "new").unwrap();
+
+    let (_, fingerprint) = status_fingerprint(temp.path(), &["--always-verify"]);
+
+    treeward_cmd(temp.path())
+        .args(["update", "--always-verify", "--jobs", "4", "--fingerprint"])
+        .arg(&fingerprint)
+        .assert()
+        .success();
+
+    treeward_cmd(temp.path())
+        .args(["verify", "-j", "3"])
+        .assert()
+        .success();
+}
+
+/// `--hash` applies to checksums update computes; entries whose checksum is
+/// reused keep their algorithm until re-checksummed with --always-verify.
+#[test]
+fn update_hash_converts_only_rechecksummed_entries() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("file.txt"), "hello").unwrap();
+
+    treeward_cmd(temp.path()).arg("init").assert().success();
+
+    treeward_cmd(temp.path())
+        .args(["update", "--hash", "blake3"])
+        .assert()
+        .success();
+    let ward = fs::read_to_string(temp.path().join(".treeward")).unwrap();
+    assert!(ward.contains("checksum = \"sha256:"), "{ward}");
+
+    fs::write(temp.path().join("new.txt"), "new").unwrap();
+    treeward_cmd(temp.path())
+        .args(["update", "--always-verify", "--hash", "blake3"])
+        .assert()
+        .success();
+    let ward = fs::read_to_string(temp.path().join(".treeward")).unwrap();
+    assert!(!ward.contains("checksum = \"sha256:"), "{ward}");
+    assert_eq!(ward.matches("checksum = \"blake3:").count(), 2, "{ward}");
+
+    treeward_cmd(temp.path()).arg("verify").assert().success();
+}
+
 #[test]
 fn update_dry_run_skips_writes() {
     let temp = TempDir::new().unwrap();
@@ -447,3 +537,91 @@ fn update_exits_code_255_on_permission_error() {
         "update should exit with code 255 on permission error"
     );
 }
+
+#[test]
+fn update_never_records_ignored_entries() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("data.txt"), "data").unwrap();
+    fs::create_dir(temp.path().join(".git")).unwrap();
+    fs::write(temp.path().join(".git/HEAD"), "ref").unwrap();
+    fs::write(temp.path().join(".treewardignore"), "/.git/\n").unwrap();
+
+    treeward_cmd(temp.path()).arg("init").assert().success();
+
+    let ward = fs::read_to_string(temp.path().join(".treeward")).unwrap();
+    assert!(ward.contains("data.txt"), "{ward}");
+    assert!(ward.contains(".treewardignore"), "{ward}");
+    assert!(!ward.contains(".git\""), "{ward}");
+    assert!(!temp.path().join(".git/.treeward").exists());
+
+    fs::write(temp.path().join(".git/HEAD"), "changed ref").unwrap();
+    treeward_cmd(temp.path()).arg("verify").assert().success();
+}
+
+#[test]
+fn update_with_paths_only_records_those_subtrees() {
+    let temp = TempDir::new().unwrap();
+    fs::create_dir(temp.path().join("deployed")).unwrap();
+    fs::write(temp.path().join("deployed/app.bin"), "v1").unwrap();
+    fs::write(temp.path().join("notes.txt"), "notes").unwrap();
+
+    treeward_cmd(temp.path()).arg("init").assert().success();
+
+    fs::write(temp.path().join("deployed/app.bin"), "version 2").unwrap();
+    fs::write(temp.path().join("notes.txt"), "edited notes").unwrap();
+
+    treeward_cmd(temp.path())
+        .args(["status", "deployed"])
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("M? deployed/app.bin"))
+        .stdout(predicate::str::contains("notes.txt").not());
+
+    treeward_cmd(temp.path())
+        .args(["update", "deployed"])
+        .assert()
+        .success();
+
+    treeward_cmd(temp.path())
+        .arg("status")
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("M? notes.txt"))
+        .stdout(predicate::str::contains("deployed").not());
+}
+
+#[test]
+#[cfg(unix)]
+fn update_interrupted_by_permission_error_is_rolled_back() {
+    let temp = TempDir::new().unwrap();
+    fs::create_dir_all(temp.path().join("a/b")).unwrap();
+    fs::write(temp.path().join("a/b/file.txt"), "hello").unwrap();
+    treeward_cmd(temp.path()).arg("init").assert().success();
+    let before = fs::read_to_string(temp.path().join("a/b/.treeward")).unwrap();
+    fs::write(temp.path().join("a/b/file.txt"), "modified").unwrap();
+
+    // a/b/.treeward is written first, then a/.treeward fails.
+    let mut perms = fs::metadata(temp.path().join("a")).unwrap().permissions();
+    perms.set_mode(0o555);
+    fs::set_permissions(temp.path().join("a"), perms.clone()).unwrap();
+    let output = treeward_cmd(temp.path()).arg("update").output().unwrap();
+    perms.set_mode(0o755);
+    fs::set_permissions(temp.path().join("a"), perms).unwrap();
+    assert_eq!(output.status.code(), Some(255));
+    assert!(temp.path().join(".treeward-intent").exists());
+
+    treeward_cmd(temp.path())
+        .arg("status")
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("M? a/b/file.txt"))
+        .stderr(predicate::str::contains(
+            "Rolled back an interrupted update",
+        ));
+    assert_eq!(
+        fs::read_to_string(temp.path().join("a/b/.treeward")).unwrap(),
+        before
+    );
+    assert!(!temp.path().join(".treeward-intent").exists());
+    treeward_cmd(temp.path()).arg("update").assert().success();
+}
@@ -170,3 +170,152 @@ fn verify_exits_code_255_on_permission_error() {
         "verify should exit with code 255 on permission error"
     );
 }
+
+#[test]
+fn verify_jobs_output_matches_sequential() {
+    let temp = TempDir::new().unwrap();
+    for i in 0..6 {
+        fs::write(temp.path().join(format!("file{i}.txt")), format!("v{i}")).unwrap();
+    }
+    fs::create_dir(temp.path().join("sub")).unwrap();
+    fs::write(temp.path().join("sub/nested.txt"), "nested").unwrap();
+
+    treeward_cmd(temp.path()).arg("init").assert().success();
+
+    for i in [1, 4] {
+        let path = temp.path().join(format!("file{i}.txt"));
+        let mtime = FileTime::from_last_modification_time(&fs::metadata(&path).unwrap());
+        fs::write(&path, format!("w{i}")).unwrap();
+        set_file_mtime(&path, mtime).unwrap();
+    }
+    fs::write(temp.path().join("sub/added.txt"), "added").unwrap();
+
+    let sequential = treeward_cmd(temp.path()).arg("verify").output().unwrap();
+    let parallel = treeward_cmd(temp.path())
+        .args(["verify", "--jobs", "4"])
+        .output()
+        .unwrap();
+
+    assert!(!sequential.status.success());
+    assert_eq!(sequential.status.code(), parallel.status.code());
+    assert_eq!(sequential.stdout, parallel.stdout);
+    let stdout = String::from_utf8(parallel.stdout).unwrap();
+    assert!(stdout.contains("M  file1.txt"));
+    assert!(stdout.contains("M  file4.txt"));
+    assert!(stdout.contains("A  sub/added.txt"));
+}
+
+#[test]
+fn verify_rejects_zero_jobs() {
+    let temp = TempDir::new().unwrap();
+
+    treeward_cmd(temp.path())
+        .args(["verify", "--jobs", "0"])
+        .assert()
+        .failure();
+}
+
+/// Ward files written by older releases (format version 1, bare `sha256`)
+/// must keep verifying.
+#[test]
+fn verify_reads_version_1_ward_files() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("good.txt"), "hello").unwrap();
+    fs::write(temp.path().join("bad.txt"), "hello").unwrap();
+    let hello_sha256 = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+    let other_sha256 = "0baf982fcab396fdb1c6d82f8f1eb0d2aea9cdd347fb244cf0b2c748df350069";
+    fs::write(
+        temp.path().join(".treeward"),
+        format!(
+            r#"[metadata]
+version = 1
+
+[entries."bad.txt"]
+type = "file"
+sha256 = "{other_sha256}"
+mtime_nanos = 1
+size = 5
+
+[entries."good.txt"]
+type = "file"
+sha256 = "{hello_sha256}"
+mtime_nanos = 1
+size = 5
+"#
+        ),
+    )
+    .unwrap();
+
+    treeward_cmd(temp.path())
+        .arg("verify")
+        .assert()
+        .failure()
+        .stdout("M  bad.txt\n");
+}
+
+#[test]
+fn verify_ndjson_reports_corruption() {
+    let temp = TempDir::new().unwrap();
+    let file = temp.path().join("file.txt");
+    fs::write(&file, "hello").unwrap();
+
+    treeward_cmd(temp.path()).arg("init").assert().success();
+
+    // Same size and mtime, different content: only a checksum can tell.
+    let mtime = FileTime::from_last_modification_time(&fs::metadata(&file).unwrap());
+    fs::write(&file, "HELLO").unwrap();
+    set_file_mtime(&file, mtime).unwrap();
+
+    let output = treeward_cmd(temp.path())
+        .args(["verify", "--format", "ndjson"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let records: Vec<serde_json::Value> = stdout
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(records.len(), 2, "{stdout}");
+    assert_eq!(records[0]["type"], "entry");
+    assert_eq!(records[0]["path"], "file.txt");
+    assert_eq!(records[0]["code"], "M");
+    assert_eq!(records[1]["type"], "summary");
+    assert_eq!(records[1]["counts"]["modified"], 1);
+}
+
+#[test]
+#[cfg(unix)]
+fn verify_keep_going_reports_unreadable_file() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp = TempDir::new().unwrap();
+    let locked = temp.path().join("locked.txt");
+    fs::write(&locked, "secret").unwrap();
+    fs::write(temp.path().join("file.txt"), "hello").unwrap();
+
+    treeward_cmd(temp.path()).arg("init").assert().success();
+
+    fs::write(temp.path().join("new.txt"), "new").unwrap();
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+    // Permissions are not enforced for root; nothing to test then.
+    if fs::read(&locked).is_ok() {
+        return;
+    }
+
+    let strict = treeward_cmd(temp.path()).arg("verify").assert().failure();
+    let keep_going = treeward_cmd(temp.path())
+        .args(["verify", "--keep-going"])
+        .assert()
+        .code(1);
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o644)).unwrap();
+
+    strict.stdout(predicate::str::is_empty());
+    keep_going
+        .stdout(predicate::str::contains("E  locked.txt"))
+        .stdout(predicate::str::contains("A  new.txt"))
+        .stderr(predicate::str::contains(
+            "Could not read locked.txt: permission denied",
+        ));
+}
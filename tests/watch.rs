@@ -0,0 +1,147 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use common::treeward_cmd;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{Receiver, channel};
+use std::thread;
+use std::time::Duration;
+use tempfile::TempDir;
+
+/// A running `treeward watch --format ndjson`, killed when dropped.
+struct Watch {
+    child: Child,
+    lines: Receiver<String>,
+}
+
+impl Watch {
+    fn start(root: &Path, args: &[&str]) -> Watch {
+        let mut child = Command::new(assert_cmd::cargo::cargo_bin!("treeward"))
+            .arg("-C")
+            .arg(root)
+            .args(["watch", "--format", "ndjson", "--debounce", "50"])
+            .args(args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let stdout = child.stdout.take().unwrap();
+        let (sender, lines) = channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                if sender.send(line.unwrap()).is_err() {
+                    break;
+                }
+            }
+        });
+        Watch { child, lines }
+    }
+
+    /// The next reported change, as `<code> <path>`.
+    fn next(&self) -> String {
+        let line = self
+            .lines
+            .recv_timeout(Duration::from_secs(10))
+            .expect("watch reported nothing");
+        let record: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(record["type"], "entry", "{line}");
+        format!(
+            "{} {}",
+            record["code"].as_str().unwrap(),
+            record["path"].as_str().unwrap()
+        )
+    }
+
+    /// Wait until the watches are in place: the first comparison after
+    /// startup reports nothing for an unchanged tree, so a marker file
+    /// being reported means it has happened.
+    fn settle(&self, root: &Path) {
+        fs::write(root.join("marker"), "").unwrap();
+        assert_eq!(self.next(), "A marker");
+        fs::remove_file(root.join("marker")).unwrap();
+        assert_eq!(self.next(), ". marker");
+    }
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn warded_tree() -> TempDir {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("file.txt"), "hello").unwrap();
+    fs::create_dir(temp.path().join("dir")).unwrap();
+    fs::write(temp.path().join("dir/nested.txt"), "nested").unwrap();
+    treeward_cmd(temp.path()).arg("init").assert().success();
+    temp
+}
+
+#[test]
+fn watch_reports_changes_as_they_happen() {
+    let temp = warded_tree();
+    fs::write(temp.path().join("pending.txt"), "added before watching").unwrap();
+    let watch = Watch::start(temp.path(), &[]);
+    assert_eq!(watch.next(), "A pending.txt");
+    watch.settle(temp.path());
+
+    fs::write(temp.path().join("dir/nested.txt"), "edited").unwrap();
+    assert_eq!(watch.next(), "M? dir/nested.txt");
+
+    fs::remove_file(temp.path().join("file.txt")).unwrap();
+    assert_eq!(watch.next(), "R file.txt");
+
+    fs::create_dir(temp.path().join("new")).unwrap();
+    assert_eq!(watch.next(), "A new");
+    fs::write(temp.path().join("new/deep.txt"), "in a new directory").unwrap();
+    assert_eq!(watch.next(), "A new/deep.txt");
+}
+
+#[test]
+fn watch_reports_accepted_changes_as_unchanged() {
+    let temp = warded_tree();
+    let watch = Watch::start(temp.path(), &[]);
+    watch.settle(temp.path());
+
+    fs::write(temp.path().join("added.txt"), "new").unwrap();
+    assert_eq!(watch.next(), "A added.txt");
+
+    treeward_cmd(temp.path()).arg("update").assert().success();
+    assert_eq!(watch.next(), ". added.txt");
+}
+
+#[test]
+fn watch_leaves_out_ignored_files() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join(".treewardignore"), "*.tmp\n").unwrap();
+    fs::create_dir(temp.path().join("dir")).unwrap();
+    treeward_cmd(temp.path()).arg("init").assert().success();
+    let watch = Watch::start(temp.path(), &[]);
+    watch.settle(temp.path());
+
+    fs::write(temp.path().join("dir/scratch.tmp"), "ignored").unwrap();
+    thread::sleep(Duration::from_millis(300));
+    fs::write(temp.path().join("dir/kept.txt"), "kept").unwrap();
+    assert_eq!(watch.next(), "A dir/kept.txt");
+}
+
+#[test]
+fn watch_verify_tells_modified_from_unchanged() {
+    let temp = warded_tree();
+    let watch = Watch::start(temp.path(), &["--verify"]);
+    watch.settle(temp.path());
+
+    fs::write(temp.path().join("file.txt"), "changed").unwrap();
+    assert_eq!(watch.next(), "M? file.txt");
+    assert_eq!(watch.next(), "M file.txt");
+
+    fs::write(temp.path().join("file.txt"), "hello").unwrap();
+    assert_eq!(watch.next(), "M? file.txt");
+    assert_eq!(watch.next(), ". file.txt");
+}